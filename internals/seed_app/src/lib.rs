@@ -1,2 +1,187 @@
+use std::any::TypeId;
+
+use seed_ecs::event::EventSettings;
+use seed_ecs::system::{IntoSystemConfig, Schedule};
+use seed_ecs::{FromWorld, World};
+
 pub struct AppBuilder;
 
+/// Names a schedule of an [`App`], unit structs make good labels. The app runs the schedules of
+/// its own labels, the others run through [`App::run_schedule`].
+pub trait ScheduleLabel: Send + Sync + 'static {}
+
+/// Runs once on the first [`App::update`], before [`Startup`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PreStartup;
+
+/// Runs once on the first [`App::update`], for the setup code like spawning the camera.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Startup;
+
+/// Runs once on the first [`App::update`], after [`Startup`] and before the first [`Update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PostStartup;
+
+/// Runs on every [`App::update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Update;
+
+impl ScheduleLabel for PreStartup {}
+impl ScheduleLabel for Startup {}
+impl ScheduleLabel for PostStartup {}
+impl ScheduleLabel for Update {}
+
+/// A world and everything needed to run it.
+#[derive(Default)]
+pub struct App {
+    world: World,
+    schedules: Vec<(TypeId, Schedule)>,
+    // Whether the startup schedules ran.
+    started: bool,
+}
+
+impl App {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    pub fn world_mut(&mut self) -> &mut World {
+        &mut self.world
+    }
+
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, value: T) -> &mut Self {
+        self.world.insert_resource(value);
+        self
+    }
+
+    /// Inserts the resource built by [`FromWorld`] unless there is one already.
+    pub fn init_resource<T: FromWorld + Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.world.init_resource::<T>();
+        self
+    }
+
+    /// Adds the events of type `T`, see [`World::add_event`].
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.world.add_event::<T>();
+        self
+    }
+
+    /// Adds the events of type `T` bounded by `settings`, see [`World::add_event_with`].
+    pub fn add_event_with<T: Send + Sync + 'static>(&mut self, settings: EventSettings) -> &mut Self {
+        self.world.add_event_with::<T>(settings);
+        self
+    }
+
+    /// Adds a system to the schedule of `label`, created if the app doesn't have it yet.
+    pub fn add_systems<L: ScheduleLabel, M>(&mut self, label: L, system: impl IntoSystemConfig<M>) -> &mut Self {
+        self.schedule_mut(label).add_system(system);
+        self
+    }
+
+    /// The schedule of `label`, created if the app doesn't have it yet.
+    pub fn schedule_mut<L: ScheduleLabel>(&mut self, _label: L) -> &mut Schedule {
+        let id = TypeId::of::<L>();
+        let index = match self.schedules.iter().position(|(label, _)| *label == id) {
+            Some(index) => index,
+            None => {
+                self.schedules.push((id, Schedule::new()));
+                self.schedules.len() - 1
+            }
+        };
+        &mut self.schedules[index].1
+    }
+
+    /// Runs the schedule of `label`, does nothing if the app doesn't have it.
+    pub fn run_schedule<L: ScheduleLabel>(&mut self, _label: L) {
+        let id = TypeId::of::<L>();
+        if let Some((_, schedule)) = self.schedules.iter_mut().find(|(label, _)| *label == id) {
+            schedule.run(&mut self.world);
+        }
+    }
+
+    /// Runs a frame: [`Update`], after [`PreStartup`], [`Startup`] and [`PostStartup`] the first
+    /// time. Each system applies its commands before the next one runs, so what the startup
+    /// systems spawn is there for the first update.
+    pub fn update(&mut self) {
+        if !self.started {
+            self.started = true;
+            self.run_schedule(PreStartup);
+            self.run_schedule(Startup);
+            self.run_schedule(PostStartup);
+        }
+        self.run_schedule(Update);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use seed_ecs::commands::Commands;
+    use seed_ecs::prelude::*;
+
+    use super::*;
+
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    fn logger(name: &'static str) -> impl FnMut(ResMut<Log>) + Send + Sync + 'static {
+        move |mut log: ResMut<Log>| log.0.push(name)
+    }
+
+    #[test]
+    fn startup_runs_once() {
+        let mut app = App::new();
+        app.init_resource::<Log>()
+            .add_systems(Update, logger("update"))
+            .add_systems(Startup, logger("startup"));
+        for _ in 0..5 {
+            app.update();
+        }
+        let log = &app.world().get_resource::<Log>().unwrap().0;
+        assert_eq!(log.iter().filter(|name| **name == "startup").count(), 1);
+        assert_eq!(log.iter().filter(|name| **name == "update").count(), 5);
+        assert_eq!(log[0], "startup");
+    }
+
+    #[test]
+    fn startup_phases_run_in_order() {
+        let mut app = App::new();
+        app.init_resource::<Log>()
+            .add_systems(Update, logger("update"))
+            .add_systems(PostStartup, logger("post"))
+            .add_systems(Startup, logger("startup"))
+            .add_systems(PreStartup, logger("pre"));
+        app.update();
+        app.update();
+        let log = &app.world().get_resource::<Log>().unwrap().0;
+        assert_eq!(*log, ["pre", "startup", "post", "update", "update"]);
+    }
+
+    #[derive(Component)]
+    struct Camera;
+    struct Seen(Vec<usize>);
+
+    fn spawn_camera(mut commands: Commands) {
+        commands.add(|world: &mut World| {
+            world.spawn_batch([Camera]);
+        });
+    }
+
+    fn count_cameras(cameras: Query<&Camera>, mut seen: ResMut<Seen>) {
+        seen.0.push(cameras.iter().count());
+    }
+
+    #[test]
+    fn startup_commands_are_seen_by_the_first_update() {
+        let mut app = App::new();
+        app.insert_resource(Seen(Vec::new()))
+            .add_systems(Startup, spawn_camera)
+            .add_systems(Update, count_cameras);
+        app.update();
+        app.update();
+        assert_eq!(app.world().get_resource::<Seen>().unwrap().0, [1, 1]);
+    }
+}
@@ -0,0 +1,68 @@
+//! Compares pulling the items of a query out of its iterator with handing them to a closure, on a
+//! full world and on one where one entity out of sixteen matches.
+//!
+//! Run with `cargo bench -p seed_ecs --bench for_each`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use seed_ecs::prelude::*;
+
+#[derive(Component)]
+struct Position(f32);
+#[derive(Component)]
+struct Velocity(f32);
+
+// The most entities a world holds, 32 to the fourth.
+const ENTITIES: usize = 1 << 20;
+const RUNS: u32 = 200;
+
+fn world(every: usize) -> World {
+    let mut world = World::new();
+    for (i, e) in world.spawn_batch((0..ENTITIES).map(|i| Position(i as f32))).into_iter().enumerate() {
+        if i % every == 0 {
+            world.add_component(e, Velocity(1.0));
+        }
+    }
+    world
+}
+
+fn time(mut run: impl FnMut()) -> Duration {
+    run();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        run();
+    }
+    start.elapsed() / RUNS
+}
+
+fn bench(name: &str, every: usize) {
+    let mut world = world(every);
+    let mut state = world.query::<(&mut Position, &Velocity)>();
+    let iter = time(|| {
+        for (mut position, velocity) in state.iter_mut(&mut world) {
+            position.0 += velocity.0;
+        }
+    });
+    let for_each = time(|| {
+        state.for_each_mut(&mut world, |(mut position, velocity)| position.0 += velocity.0);
+    });
+    let positions = world.query::<&Position>();
+    let sum_iter = time(|| {
+        black_box(positions.iter(&world).map(|p| p.0).sum::<f32>());
+    });
+    let sum_for_each = time(|| {
+        let mut sum = 0.0;
+        positions.for_each(&world, |p| sum += p.0);
+        black_box(sum);
+    });
+    println!(
+        "{:<7} iter {:>10.2?}  for_each {:>10.2?}  sum iter {:>10.2?}  sum for_each {:>10.2?}",
+        name, iter, for_each, sum_iter, sum_for_each
+    );
+}
+
+fn main() {
+    bench("dense", 1);
+    bench("sparse", 16);
+}
@@ -0,0 +1,72 @@
+//! Compares iterating over a group with iterating over the same components left ungrouped, with
+//! the unordered iterators that take the fast path of groups.
+//!
+//! Run with `cargo bench -p seed_ecs --bench group`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use seed_ecs::prelude::*;
+
+#[derive(Component)]
+struct Position(f32);
+#[derive(Component)]
+struct Velocity(f32);
+#[derive(Component)]
+struct Health;
+
+const ENTITIES: usize = 30_000;
+const RUNS: u32 = 100;
+
+// One entity out of three moves and one out of two has health, so the masks are fragmented.
+fn world(grouped: bool) -> World {
+    let mut world = World::new();
+    if grouped {
+        world.register_group::<(Position, Velocity)>();
+    }
+    for (i, e) in world.spawn_batch((0..ENTITIES).map(|i| Position(i as f32))).into_iter().enumerate() {
+        if i % 3 == 0 {
+            world.add_component(e, Velocity(1.0));
+        }
+        if i % 2 == 0 {
+            world.add_component(e, Health);
+        }
+    }
+    world
+}
+
+fn time(mut run: impl FnMut()) -> Duration {
+    run();
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        run();
+    }
+    start.elapsed() / RUNS
+}
+
+fn bench(name: &str, grouped: bool) {
+    let mut world = world(grouped);
+    let mut state = world.query::<(&mut Position, &Velocity)>();
+    let iter = time(|| {
+        for (mut position, velocity) in state.iter_unordered_mut(&mut world) {
+            position.0 += velocity.0;
+        }
+    });
+    let chunks = time(|| {
+        for (_, (positions, velocities)) in state.query_mut(&mut world).iter_chunks_unordered() {
+            for (position, velocity) in positions.iter_mut().zip(velocities) {
+                position.0 += velocity.0;
+            }
+        }
+    });
+    let positions = world.query::<&Position>();
+    let single = time(|| {
+        black_box(positions.iter(&world).map(|p| p.0).sum::<f32>());
+    });
+    println!("{:<10} iter {:>10.2?}  chunks {:>10.2?}  positions only {:>10.2?}", name, iter, chunks, single);
+}
+
+fn main() {
+    bench("ungrouped", false);
+    bench("grouped", true);
+}
@@ -0,0 +1,443 @@
+//! A bump allocator reset every frame, for the temporary buffers systems build every frame like
+//! visibility lists or broadphase pairs.
+//!
+//! Add [`reset_frame_arena`] first in the schedule and take an [`Arena`] in the systems:
+//!
+//! ```
+//! # use seed_ecs::arena::{reset_frame_arena, Arena};
+//! # use seed_ecs::system::Schedule;
+//! # use seed_ecs::World;
+//! fn visibility(mut arena: Arena) {
+//!     let mut visible = arena.alloc_vec::<u32>();
+//!     for i in 0..100 {
+//!         visible.push(&mut arena, i);
+//!     }
+//!     assert_eq!(visible.as_slice(&arena).len(), 100);
+//! }
+//!
+//! let mut world = World::new();
+//! let mut schedule = Schedule::new();
+//! schedule.add_system(reset_frame_arena).add_system(visibility);
+//! schedule.run(&mut world);
+//! ```
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::ptr::{self, NonNull};
+use core::slice;
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use crate::system::{SystemMeta, SystemParam};
+use crate::{UnsafeWorldCell, World};
+
+// Every block is aligned for the most aligned values the arena accepts.
+const BLOCK_ALIGN: usize = 64;
+const MIN_BLOCK_SIZE: usize = 4096;
+
+static NEXT_ARENA: AtomicU32 = AtomicU32::new(0);
+
+struct Block {
+    data: NonNull<u8>,
+    size: usize,
+}
+
+impl Block {
+    fn new(size: usize) -> Self {
+        let layout = Layout::from_size_align(size, BLOCK_ALIGN).unwrap();
+        let data = unsafe { alloc(layout) };
+        Self {
+            data: NonNull::new(data).unwrap_or_else(|| handle_alloc_error(layout)),
+            size,
+        }
+    }
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        let layout = Layout::from_size_align(self.size, BLOCK_ALIGN).unwrap();
+        unsafe { dealloc(self.data.as_ptr(), layout) }
+    }
+}
+
+// A block is plain bytes, the values in it are only reached through the arena.
+unsafe impl Send for Block {}
+unsafe impl Sync for Block {}
+
+// Which arena and which frame a buffer was allocated in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    arena: u32,
+    generation: u64,
+}
+
+/// The resource holding the memory of the [`ArenaVec`]s, reset by [`reset_frame_arena`].
+///
+/// Memory is bumped out of blocks that are kept across frames. When a frame needed several blocks,
+/// the reset replaces them by a single one as large as all of them, so that after a few frames the
+/// arena stops touching the global allocator.
+pub struct FrameArena {
+    blocks: Vec<Block>,
+    // Bytes used in the last block, the others are full.
+    used: usize,
+    stamp: Stamp,
+}
+
+impl FrameArena {
+    pub fn new() -> Self {
+        Self {
+            blocks: Vec::new(),
+            used: 0,
+            stamp: Stamp {
+                arena: NEXT_ARENA.fetch_add(1, Ordering::Relaxed),
+                generation: 0,
+            },
+        }
+    }
+
+    /// An arena that can hold `bytes` before allocating.
+    pub fn with_capacity(bytes: usize) -> Self {
+        let mut arena = Self::new();
+        if bytes > 0 {
+            arena.blocks.push(Block::new(bytes));
+        }
+        arena
+    }
+
+    /// Frees every buffer at once, the [`ArenaVec`]s allocated before panic when used.
+    pub fn reset(&mut self) {
+        self.stamp.generation += 1;
+        if self.blocks.len() > 1 {
+            let size = self.allocated_bytes();
+            self.blocks.clear();
+            self.blocks.push(Block::new(size));
+        }
+        self.used = 0;
+    }
+
+    /// Number of bytes held by the arena, used or not.
+    pub fn allocated_bytes(&self) -> usize {
+        self.blocks.iter().map(|block| block.size).sum()
+    }
+
+    /// Number of bytes handed out since the last reset, alignment padding included.
+    pub fn used_bytes(&self) -> usize {
+        match self.blocks.split_last() {
+            Some((_, full)) => full.iter().map(|block| block.size).sum::<usize>() + self.used,
+            None => 0,
+        }
+    }
+
+    /// An empty growable buffer.
+    pub fn alloc_vec<T: Copy + Send + Sync>(&mut self) -> ArenaVec<T> {
+        ArenaVec {
+            data: NonNull::dangling(),
+            len: 0,
+            // Zero sized values never need memory.
+            capacity: if size_of::<T>() == 0 { usize::MAX } else { 0 },
+            stamp: self.stamp,
+            _marker: PhantomData,
+        }
+    }
+
+    /// A buffer of `len` default values, that can grow like the ones of [`FrameArena::alloc_vec`].
+    pub fn alloc_slice<T: Copy + Default + Send + Sync>(&mut self, len: usize) -> ArenaVec<T> {
+        let mut vec = self.alloc_vec::<T>();
+        vec.reserve(self, len);
+        for i in 0..len {
+            unsafe { vec.data.as_ptr().add(i).write(T::default()) };
+        }
+        vec.len = len;
+        vec
+    }
+
+    fn bump(&mut self, layout: Layout) -> NonNull<u8> {
+        assert!(layout.align() <= BLOCK_ALIGN, "Frame arenas align values to {} bytes at most", BLOCK_ALIGN);
+        if let Some(block) = self.blocks.last() {
+            let start = self.used.next_multiple_of(layout.align());
+            if start + layout.size() <= block.size {
+                self.used = start + layout.size();
+                return unsafe { block.data.add(start) };
+            }
+        }
+        let last = self.blocks.last().map_or(0, |block| block.size);
+        let block = Block::new((last * 2).max(layout.size()).max(MIN_BLOCK_SIZE));
+        let data = block.data;
+        self.blocks.push(block);
+        self.used = layout.size();
+        data
+    }
+
+    // Grows the allocation ending at `end` by `additional` bytes if it is the last one and the
+    // block has room.
+    fn grow_in_place(&mut self, end: *const u8, additional: usize) -> bool {
+        let Some(block) = self.blocks.last() else {
+            return false;
+        };
+        let top = unsafe { block.data.as_ptr().add(self.used) };
+        if !ptr::eq(end, top) || self.used + additional > block.size {
+            return false;
+        }
+        self.used += additional;
+        true
+    }
+
+    fn check(&self, stamp: Stamp) {
+        assert!(stamp.arena == self.stamp.arena, "ArenaVec used with another arena than its own");
+        assert!(stamp.generation == self.stamp.generation, "ArenaVec used after its frame arena was reset");
+    }
+}
+
+impl Default for FrameArena {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A growable buffer living in a [`FrameArena`] until its next reset.
+///
+/// It doesn't borrow the arena, every access takes it instead and checks that the buffer comes
+/// from it and from the current frame, so a buffer kept past the reset panics instead of reading
+/// the values of the next frame.
+pub struct ArenaVec<T> {
+    data: NonNull<T>,
+    len: usize,
+    capacity: usize,
+    stamp: Stamp,
+    _marker: PhantomData<T>,
+}
+
+// The values are only reached through `&self` or `&mut self`, like a `Vec`.
+unsafe impl<T: Send> Send for ArenaVec<T> {}
+unsafe impl<T: Sync> Sync for ArenaVec<T> {}
+
+impl<T: Copy + Send + Sync> ArenaVec<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Makes room for `additional` more values, moving them to a larger allocation if needed.
+    pub fn reserve(&mut self, arena: &mut FrameArena, additional: usize) {
+        arena.check(self.stamp);
+        let needed = self.len + additional;
+        if needed <= self.capacity {
+            return;
+        }
+        let capacity = needed.max(self.capacity * 2).max(4);
+        let end = unsafe { self.data.as_ptr().add(self.capacity) } as *const u8;
+        if self.capacity > 0 && arena.grow_in_place(end, (capacity - self.capacity) * size_of::<T>()) {
+            self.capacity = capacity;
+            return;
+        }
+        let data = arena.bump(Layout::array::<T>(capacity).unwrap()).cast::<T>();
+        unsafe { ptr::copy_nonoverlapping(self.data.as_ptr(), data.as_ptr(), self.len) };
+        self.data = data;
+        self.capacity = capacity;
+    }
+
+    pub fn push(&mut self, arena: &mut FrameArena, value: T) {
+        if self.len == self.capacity {
+            self.reserve(arena, 1);
+        }
+        arena.check(self.stamp);
+        unsafe { self.data.as_ptr().add(self.len).write(value) };
+        self.len += 1;
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `arena` was reset since the buffer was allocated, or didn't allocate it.
+    pub fn pop(&mut self, arena: &FrameArena) -> Option<T> {
+        arena.check(self.stamp);
+        self.len = self.len.checked_sub(1)?;
+        Some(unsafe { self.data.as_ptr().add(self.len).read() })
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `arena` was reset since the buffer was allocated, or didn't allocate it.
+    pub fn as_slice<'a>(&'a self, arena: &'a FrameArena) -> &'a [T] {
+        arena.check(self.stamp);
+        unsafe { slice::from_raw_parts(self.data.as_ptr(), self.len) }
+    }
+
+    /// The arena is only borrowed shared, several buffers can be written at once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `arena` was reset since the buffer was allocated, or didn't allocate it.
+    pub fn as_mut_slice<'a>(&'a mut self, arena: &'a FrameArena) -> &'a mut [T] {
+        arena.check(self.stamp);
+        unsafe { slice::from_raw_parts_mut(self.data.as_ptr(), self.len) }
+    }
+}
+
+/// Exclusive access to the [`FrameArena`] of the world, inserted when the system is initialized.
+pub struct Arena<'w> {
+    arena: &'w mut FrameArena,
+}
+
+impl<'w> Deref for Arena<'w> {
+    type Target = FrameArena;
+
+    fn deref(&self) -> &Self::Target {
+        self.arena
+    }
+}
+
+impl<'w> DerefMut for Arena<'w> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.arena
+    }
+}
+
+unsafe impl<'a> SystemParam for Arena<'a> {
+    type State = ();
+    type Item<'w, 's> = Arena<'w>;
+
+    fn init_state(world: &mut World, meta: &mut SystemMeta) -> Self::State {
+        world.init_resource::<FrameArena>();
+        meta.add_resource_write::<FrameArena>();
+    }
+
+    unsafe fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        let arena = world.resource_mut::<FrameArena>().unwrap_or_else(|| {
+            panic!("The frame arena requested by {} was removed", meta.name())
+        });
+        Arena {
+            arena: arena.into_inner(),
+        }
+    }
+}
+
+/// Resets the [`FrameArena`], to run at the start of every frame.
+pub fn reset_frame_arena(mut arena: Arena) {
+    arena.reset();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::{IntoSystem, Schedule, System};
+    use crate::utils::counting_alloc::count_allocations;
+
+    #[derive(Debug, Clone, Copy, Default, PartialEq)]
+    struct Pair(u32, u32);
+
+    #[test]
+    fn memory_is_reused_across_frames() {
+        let mut arena = FrameArena::new();
+        let mut pairs = arena.alloc_slice::<Pair>(3);
+        pairs.as_mut_slice(&arena)[1] = Pair(1, 2);
+        let mut ids = arena.alloc_vec::<u8>();
+        ids.push(&mut arena, 7);
+        pairs.push(&mut arena, Pair(3, 4));
+        assert_eq!(pairs.as_slice(&arena), [Pair(0, 0), Pair(1, 2), Pair(0, 0), Pair(3, 4)]);
+        assert_eq!(ids.pop(&arena), Some(7));
+        let first = pairs.as_slice(&arena).as_ptr();
+
+        arena.reset();
+        let again = arena.alloc_slice::<Pair>(3);
+        assert_eq!(again.as_slice(&arena).as_ptr(), first);
+        assert_eq!(again.as_slice(&arena), [Pair::default(); 3]);
+
+        // A frame spilling over several blocks gets one block as large as them afterwards.
+        let mut big = arena.alloc_vec::<u64>();
+        for i in 0..10_000 {
+            big.push(&mut arena, i);
+        }
+        assert_eq!(big.as_slice(&arena).iter().sum::<u64>(), 49_995_000);
+        assert!(arena.blocks.len() > 1);
+        let allocated = arena.allocated_bytes();
+        arena.reset();
+        assert_eq!((arena.blocks.len(), arena.allocated_bytes(), arena.used_bytes()), (1, allocated, 0));
+    }
+
+    #[test]
+    fn growing_the_last_buffer_stays_in_place() {
+        let mut arena = FrameArena::with_capacity(1 << 16);
+        let mut values = arena.alloc_vec::<u32>();
+        values.push(&mut arena, 0);
+        let start = values.as_slice(&arena).as_ptr();
+        for i in 1..1000 {
+            values.push(&mut arena, i);
+        }
+        assert_eq!(values.as_slice(&arena).as_ptr(), start);
+        assert_eq!(arena.used_bytes(), values.capacity() * 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "used after its frame arena was reset")]
+    fn stale_buffers_panic() {
+        let mut world = World::new();
+        let mut kept = None;
+        let mut system = (move |mut arena: Arena| match kept.take() {
+            None => kept = Some(arena.alloc_slice::<u32>(4)),
+            Some(stale) => {
+                stale.as_slice(&arena);
+            }
+        })
+        .into_system();
+        system.run(&mut world);
+        reset_frame_arena.into_system().run(&mut world);
+        system.run(&mut world);
+    }
+
+    #[test]
+    #[should_panic(expected = "used after its frame arena was reset")]
+    fn stale_pops_panic() {
+        let mut arena = FrameArena::new();
+        let mut values = arena.alloc_vec::<u64>();
+        for i in 0..10_000 {
+            values.push(&mut arena, i);
+        }
+        // The blocks the values spilled into are freed by the reset.
+        arena.reset();
+        values.pop(&arena);
+    }
+
+    #[test]
+    fn frames_stop_allocating_after_warmup() {
+        const LEN: usize = 100_000;
+        let mut world = World::new();
+        let mut schedule = Schedule::new();
+        schedule.add_system(reset_frame_arena).add_system(|mut arena: Arena| {
+            let mut pairs = arena.alloc_vec::<Pair>();
+            for i in 0..LEN as u32 {
+                pairs.push(&mut arena, Pair(i, i + 1));
+            }
+            let mut visible = arena.alloc_slice::<u32>(LEN);
+            let visible = visible.as_mut_slice(&arena);
+            for (slot, pair) in visible.iter_mut().zip(pairs.as_slice(&arena)) {
+                *slot = pair.1 - pair.0;
+            }
+            assert_eq!(visible.iter().sum::<u32>(), LEN as u32);
+        });
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+        let ((), allocations) = count_allocations(|| {
+            for _ in 0..10 {
+                schedule.run(&mut world);
+            }
+        });
+        assert_eq!(allocations, 0);
+    }
+}
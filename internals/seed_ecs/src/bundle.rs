@@ -0,0 +1,624 @@
+//! Sets of components spawned, inserted and removed together, see [`World::spawn_batch_iter`].
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::cell::UnsafeCell;
+use core::fmt;
+
+use crate::component::{Component, ComponentId, ComponentInfo};
+use crate::entity::{Entity, EntityMapper};
+use crate::observer::ObserverKind;
+use crate::storage::{AnyStorage, Storage};
+use crate::tuples::all_tuples_indexed;
+use crate::{EcsError, World};
+
+pub use seed_ecs_derive::Bundle;
+
+/// The sets of components that [`World::spawn_batch_iter`] gives to each entity, and that
+/// [`World::insert_bundle`] and [`World::remove_bundle`] move at once: the tuples of components,
+/// and the structs deriving it.
+///
+/// The derive takes the fields as components, or as nested bundles with `#[bundle]`, flattened
+/// into one set:
+///
+/// ```
+/// # use seed_ecs::prelude::*;
+/// #[derive(Component)]
+/// struct Position(f32);
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(Component)]
+/// struct Enemy;
+///
+/// #[derive(Bundle)]
+/// struct Orc {
+///     position: Position,
+///     #[bundle]
+///     stats: (Health, Enemy),
+/// }
+///
+/// let mut world = World::new();
+/// let orc = world.spawn_batch_iter([Orc { position: Position(2.0), stats: (Health(30), Enemy) }])[0];
+/// assert_eq!(world.get_component::<Health>(orc).unwrap().0, 30);
+/// let orc = world.remove_bundle::<Orc>(orc).unwrap();
+/// assert_eq!(orc.position.0, 2.0);
+/// ```
+///
+/// A set holding a component twice panics when it is registered, and fails to compile when the
+/// same struct names it twice:
+///
+/// ```compile_fail
+/// # use seed_ecs::prelude::*;
+/// # #[derive(Component)]
+/// # struct Health(u32);
+/// #[derive(Bundle)]
+/// struct Shielded {
+///     health: Health,
+///     shield: Health,
+/// }
+/// ```
+pub trait Bundle: Send + Sync + 'static {
+    /// One vector per component of the bundle, holding the values along with their entity index.
+    type Columns;
+
+    /// Number of components, nested bundles included.
+    const COUNT: usize;
+
+    /// Registers the components and returns their ids, in the order of the fields.
+    fn register(world: &mut World) -> Vec<ComponentId>;
+
+    fn columns(capacity: usize) -> Self::Columns;
+
+    /// Pushes each component of the bundle to its column.
+    fn push(self, index: usize, columns: &mut Self::Columns);
+
+    /// Writes each column to the storage of its component, `ids` comes from [`Bundle::register`].
+    fn insert_columns(world: &mut World, ids: &[ComponentId], columns: Self::Columns);
+
+    /// Writes the components to their storage at `index`, replacing the ones there, and pushes
+    /// the ids of the new ones to `added`. Nothing else is updated, see [`World::insert_bundle`].
+    fn write(self, world: &mut World, ids: &[ComponentId], index: usize, added: &mut Vec<ComponentId>);
+
+    /// Takes the components out of their storage at `index`, `None` if one is missing. The
+    /// others are taken all the same.
+    fn take(world: &mut World, ids: &[ComponentId], index: usize) -> Option<Self>
+    where
+        Self: Sized;
+}
+
+macro_rules! impl_bundle {
+    ($($name: ident $idx: tt),*) => {
+        #[allow(clippy::unused_unit)]
+        impl<$($name: Component),*> Bundle for ($($name,)*) {
+            type Columns = ($(Vec<(usize, $name)>,)*);
+
+            const COUNT: usize = <[usize]>::len(&[$($idx),*]);
+
+            fn register(_world: &mut World) -> Vec<ComponentId> {
+                vec![$(_world.register_component::<$name>()),*]
+            }
+
+            fn columns(_capacity: usize) -> Self::Columns {
+                ($(Vec::<(usize, $name)>::with_capacity(_capacity),)*)
+            }
+
+            fn push(self, _index: usize, _columns: &mut Self::Columns) {
+                $(_columns.$idx.push((_index, self.$idx));)*
+            }
+
+            fn insert_columns(_world: &mut World, _ids: &[ComponentId], _columns: Self::Columns) {
+                let _tick = _world.change_tick;
+                $(_world.storages.typed_mut::<$name>(_ids[$idx]).insert_many(_columns.$idx, _tick);)*
+            }
+
+            fn write(self, _world: &mut World, _ids: &[ComponentId], _index: usize, _added: &mut Vec<ComponentId>) {
+                let _tick = _world.change_tick;
+                $(
+                    if _world.storages.typed_mut::<$name>(_ids[$idx]).insert(_index, self.$idx, _tick).is_none() {
+                        _added.push(_ids[$idx]);
+                    }
+                )*
+            }
+
+            fn take(_world: &mut World, _ids: &[ComponentId], _index: usize) -> Option<Self> {
+                let _taken = ($(_world.storages.typed_mut::<$name>(_ids[$idx]).take(_index),)*);
+                Some(($(_taken.$idx?,)*))
+            }
+        }
+    };
+}
+
+all_tuples_indexed!(impl_bundle);
+
+/// The components taken out of an entity by [`World::take_all`], without their types, to insert
+/// them into another entity of this world or another one with [`World::insert_dynamic_bundle`].
+///
+/// Each component sits in a storage of its own until it is inserted, the ones never inserted are
+/// dropped with the bundle.
+pub struct DynamicBundle {
+    components: Vec<(ComponentInfo, Box<UnsafeCell<dyn AnyStorage>>)>,
+}
+
+// The storages are only mutated through `&mut self`.
+unsafe impl Sync for DynamicBundle {}
+
+impl DynamicBundle {
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+
+    /// The components of the bundle, as registered in the world they were taken from.
+    pub fn components(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.components.iter().map(|(info, _)| info)
+    }
+
+    pub fn contains<T: Component>(&self) -> bool {
+        self.components.iter().any(|(info, _)| info.type_id() == Some(TypeId::of::<T>()))
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        let (_, storage) = self.components.iter().find(|(info, _)| info.type_id() == Some(TypeId::of::<T>()))?;
+        let storage = unsafe { &*storage.get() };
+        storage.as_any().downcast_ref::<Storage<T>>()?.get(0)
+    }
+
+    /// Rewrites the entities stored in the components registered with
+    /// [`World::register_map_entities`] in the world they were taken from, before inserting them
+    /// into another one.
+    pub fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        for (info, storage) in &mut self.components {
+            if let Some(map_entities) = info.map_entities() {
+                map_entities(storage.get_mut(), mapper);
+            }
+        }
+    }
+}
+
+impl fmt::Debug for DynamicBundle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.components().map(|info| info.name())).finish()
+    }
+}
+
+impl World {
+    /// Registers the components of the bundle and returns their ids, flattened.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bundle holds a component more than once.
+    pub fn register_bundle<B: Bundle>(&mut self) -> Vec<ComponentId> {
+        let ids = B::register(self);
+        let mut sorted = ids.clone();
+        sorted.sort_unstable();
+        if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+            let name = self.components.info(pair[0]).unwrap().name();
+            panic!("Bundle {} holds the component {} more than once", type_name::<B>(), name);
+        }
+        ids
+    }
+
+    /// Adds the components of the bundle to the entity, replacing the ones it has. Each storage
+    /// is resolved once and the observers of the added components run once all are there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive or if the bundle holds a component twice.
+    pub fn insert_bundle<B: Bundle>(&mut self, entity: Entity, bundle: B) {
+        self.try_insert_bundle(entity, bundle).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Same as [`World::insert_bundle`], failing if the entity is not alive.
+    pub fn try_insert_bundle<B: Bundle>(&mut self, entity: Entity, bundle: B) -> Result<(), EcsError> {
+        self.entities.check_alive(entity)?;
+        let ids = self.register_bundle::<B>();
+        let mut added = Vec::with_capacity(ids.len());
+        bundle.write(self, &ids, entity.index() as usize, &mut added);
+        for id in &added {
+            self.metrics.inserted(*id, 1);
+            self.join_groups(entity, *id);
+        }
+        self.flush_relocations(&[]);
+        for id in &added {
+            self.trigger_component(ObserverKind::Add, *id, entity);
+        }
+        Ok(())
+    }
+
+    /// Removes the components of the bundle from the entity and returns them, `None` without
+    /// removing any if the entity is dead or misses one. The observers of the removed components
+    /// run before any is removed.
+    pub fn remove_bundle<B: Bundle>(&mut self, entity: Entity) -> Option<B> {
+        let ids = self.register_bundle::<B>();
+        let index = entity.index() as usize;
+        let has_all = |world: &World| ids.iter().all(|id| world.storages.get(*id).contains(index));
+        if !self.is_alive(entity) || !has_all(self) {
+            return None;
+        }
+        for id in &ids {
+            self.trigger_component(ObserverKind::Remove, *id, entity);
+            self.leave_groups(entity, *id);
+        }
+        // Observers may have removed some already, the others are dropped with the bundle.
+        let left: Vec<ComponentId> = ids.iter().copied().filter(|id| self.storages.get(*id).contains(index)).collect();
+        let bundle = B::take(self, &ids, index);
+        for id in left {
+            self.removed.push(id, entity);
+            self.replication.removed(id, entity);
+            self.metrics.removed(id, 1);
+        }
+        self.flush_relocations(&[]);
+        bundle
+    }
+
+    /// Takes the components of the bundle out of the entity, all of them or none, like
+    /// [`World::remove_bundle`]. For the components not known at compile time see
+    /// [`World::take_all`].
+    pub fn take_bundle<B: Bundle>(&mut self, entity: Entity) -> Option<B> {
+        self.remove_bundle(entity)
+    }
+
+    /// Takes every component out of the entity, which stays alive without any, `None` if it is
+    /// dead. The observers of the removed components run before any is removed, and the removals
+    /// are tracked like those of [`World::remove_component`].
+    pub fn take_all(&mut self, entity: Entity) -> Option<DynamicBundle> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let index = entity.index() as usize;
+        let ids: Vec<ComponentId> =
+            (0..self.storages.len()).map(ComponentId::new).filter(|id| self.storages.get(*id).contains(index)).collect();
+        for id in &ids {
+            self.trigger_component(ObserverKind::Remove, *id, entity);
+            self.leave_groups(entity, *id);
+        }
+        let mut components = Vec::with_capacity(ids.len());
+        for id in ids {
+            let src = self.storages.get_mut(id);
+            let mut storage = src.empty();
+            // Observers may have removed some already.
+            if !src.move_to(index, storage.get_mut(), 0, self.change_tick) {
+                continue;
+            }
+            components.push((self.components.info(id).unwrap().clone(), storage));
+            self.removed.push(id, entity);
+            self.replication.removed(id, entity);
+            self.metrics.removed(id, 1);
+        }
+        self.flush_relocations(&[]);
+        Some(DynamicBundle { components })
+    }
+
+    /// Adds the components of the bundle to the entity, replacing the ones it has, registering
+    /// the ones this world doesn't know yet. They count as added now, the observers of the added
+    /// ones run once all are there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive.
+    pub fn insert_dynamic_bundle(&mut self, entity: Entity, bundle: DynamicBundle) {
+        self.try_insert_dynamic_bundle(entity, bundle).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Same as [`World::insert_dynamic_bundle`], failing if the entity is not alive. The bundle
+    /// is dropped then.
+    pub fn try_insert_dynamic_bundle(&mut self, entity: Entity, bundle: DynamicBundle) -> Result<(), EcsError> {
+        self.entities.check_alive(entity)?;
+        let index = entity.index() as usize;
+        let mut added = Vec::with_capacity(bundle.len());
+        for (info, mut storage) in bundle.components {
+            let id = self.components.register_info(&info);
+            if id.index() == self.storages.len() {
+                self.storages.push_like(id, storage.get_mut());
+            }
+            let dst = self.storages.get_mut(id);
+            let replaced = dst.contains(index);
+            storage.get_mut().move_to(0, dst, index, self.change_tick);
+            if !replaced {
+                added.push(id);
+            }
+        }
+        for id in &added {
+            self.metrics.inserted(*id, 1);
+            self.join_groups(entity, *id);
+        }
+        self.flush_relocations(&[]);
+        for id in &added {
+            self.trigger_component(ObserverKind::Add, *id, entity);
+        }
+        Ok(())
+    }
+
+    /// Spawns an entity for each bundle of `bundles`, with the components of the bundle. The
+    /// storages are resolved once for the whole batch and each one is filled in one go, in
+    /// ascending index order when the spawned indices are fresh.
+    ///
+    /// ```
+    /// # use seed_ecs::component::Component;
+    /// # use seed_ecs::World;
+    /// #[derive(Component)]
+    /// struct Position(f32, f32);
+    /// #[derive(Component)]
+    /// struct Enemy;
+    ///
+    /// let mut world = World::new();
+    /// let spawns = [(1.0, 2.0), (3.0, 4.0)];
+    /// let enemies = world.spawn_batch_iter(spawns.iter().map(|(x, y)| (Position(*x, *y), Enemy)));
+    /// assert_eq!(world.get_component::<Position>(enemies[1]).map(|p| p.0), Some(3.0));
+    /// ```
+    ///
+    /// The observers of the added components run once every entity of the batch is spawned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bundle holds a component twice.
+    pub fn spawn_batch_iter<B: Bundle>(&mut self, bundles: impl IntoIterator<Item = B>) -> Vec<Entity> {
+        let mut ids = self.register_bundle::<B>();
+        let bundles = bundles.into_iter();
+        let len = bundles.size_hint().0;
+        self.entities.reserve(len);
+        let mut spawned = Vec::with_capacity(len);
+        let mut columns = B::columns(len);
+        for bundle in bundles {
+            let entity = *self.entities.spawn_entity();
+            bundle.push(entity.index() as usize, &mut columns);
+            spawned.push(entity);
+        }
+        B::insert_columns(self, &ids, columns);
+        ids.sort();
+        ids.dedup();
+        self.metrics.spawned(spawned.len() as u32);
+        for id in &ids {
+            self.metrics.inserted(*id, spawned.len() as u32);
+        }
+        for id in &ids {
+            for entity in &spawned {
+                self.join_groups(*entity, *id);
+            }
+        }
+        self.flush_relocations(&[]);
+        if self.has_observers() {
+            for id in &ids {
+                for entity in &spawned {
+                    self.trigger_component(ObserverKind::Add, *id, *entity);
+                }
+            }
+        }
+        spawned
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::String;
+
+    use super::*;
+    use crate::observer::{DeferredWorld, OnAdd, OnRemove, Trigger};
+    use crate::query::With;
+    use crate::utils::counting_alloc::count_allocations;
+    use crate::{ComponentGroup, StorageStats};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(f32, f32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Enemy;
+    #[derive(Component)]
+    struct Kind(String);
+    struct Added(usize);
+
+    #[derive(Debug, PartialEq, Bundle)]
+    struct Orc {
+        position: Position,
+        #[bundle]
+        stats: (Health, Enemy),
+    }
+
+    #[derive(Bundle)]
+    struct Boss(#[bundle] Orc, Kind);
+
+    #[derive(Bundle)]
+    struct Twice {
+        health: Health,
+        #[bundle]
+        orc: Orc,
+    }
+
+    #[test]
+    fn spawn_batch_iter_from_data() {
+        const LEN: usize = 30_000;
+        let mut world = World::new();
+        let positions: Vec<Position> = (0..LEN).map(|i| Position(i as f32, -(i as f32))).collect();
+        let (enemies, allocations) = count_allocations(|| {
+            world.spawn_batch_iter(positions.iter().enumerate().map(|(i, p)| (*p, Health(i as u32), Enemy)))
+        });
+        assert_eq!(enemies.len(), LEN);
+        // The returned entities, the columns, the pages of the dense storages and of their ticks,
+        // the entity slots and the growth of the masks, nothing per entity.
+        assert!(allocations * 100 < LEN, "{} allocations", allocations);
+        for i in (0..LEN).step_by(997).chain([LEN - 1]) {
+            assert_eq!(world.get_component::<Position>(enemies[i]), Some(&positions[i]));
+            assert_eq!(world.get_component::<Health>(enemies[i]), Some(&Health(i as u32)));
+            assert!(world.has_component::<Enemy>(enemies[i]));
+        }
+        let query = world.query_filtered::<(Entity, &Health), With<Enemy>>();
+        assert!(query.iter(&world).map(|(e, h)| (e.index(), h.0)).eq((0..LEN as u32).map(|i| (i, i))));
+        assert_eq!(world.storage_stats::<Position>().live, LEN);
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn reused_indices_observers_and_groups() {
+        let mut world = World::new();
+        world.insert_resource(Added(0));
+        world.register_group::<(Position, Health)>();
+        world.add_observer(|_: Trigger<OnAdd<Health>>, world: &mut DeferredWorld| {
+            world.get_resource_mut::<Added>().unwrap().0 += 1;
+        });
+        let first = world.spawn_batch_iter((0..10).map(|i| (Health(i),)));
+        for e in &first[2..6] {
+            world.despawn_entity(*e);
+        }
+        // Freed indices come back in reverse order, the storages take them in any order.
+        let second = world.spawn_batch_iter((0..6u32).map(|i| (Position(i as f32, 0.0), Health(100 + i), Kind(String::from("orc")))));
+        assert_eq!(world.get_resource::<Added>().unwrap().0, 16);
+        for (i, e) in second.iter().enumerate() {
+            assert_eq!(world.get_component::<Health>(*e), Some(&Health(100 + i as u32)));
+            assert_eq!(world.get_component::<Kind>(*e).map(|kind| kind.0.as_str()), Some("orc"));
+        }
+        let grouped = world.query::<(Entity, &Position, &Health)>();
+        assert_eq!(grouped.iter_unordered(&world).count(), 6);
+        assert_ne!(world.storage_stats::<Health>(), StorageStats::default());
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn struct_bundles_flatten_their_nested_bundles() {
+        let mut world = World::new();
+        world.insert_resource(Added(0));
+        world.add_observer(|_: Trigger<OnAdd<Health>>, world: &mut DeferredWorld| {
+            world.get_resource_mut::<Added>().unwrap().0 += 1;
+        });
+        assert_eq!(<Boss as Bundle>::COUNT, 4);
+        let orc = Orc { position: Position(1.0, 2.0), stats: (Health(30), Enemy) };
+        let entities = world.spawn_batch_iter([Boss(orc, Kind(String::from("warlord")))]);
+        let entity = *world.spawn_entity();
+        world.insert_bundle(entity, Orc { position: Position(3.0, 4.0), stats: (Health(10), Enemy) });
+        assert_eq!(world.get_resource::<Added>().unwrap().0, 2);
+        assert_eq!(world.get_component::<Kind>(entities[0]).unwrap().0, "warlord");
+        let orcs = world.query_filtered::<(&Position, &Health), With<Enemy>>();
+        assert_eq!(orcs.iter(&world).map(|(position, health)| (position.0, health.0)).collect::<Vec<_>>(), [(1.0, 30), (3.0, 10)]);
+
+        // Inserting again replaces the values, only the new components are reported added.
+        world.insert_bundle(entity, (Health(12), Kind(String::from("scout"))));
+        assert_eq!(world.get_resource::<Added>().unwrap().0, 2);
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(12)));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn removing_a_bundle_returns_it() {
+        let mut world = World::new();
+        let orc = Orc { position: Position(1.0, 2.0), stats: (Health(30), Enemy) };
+        let entity = world.spawn_batch_iter([Boss(orc, Kind(String::from("warlord")))])[0];
+        let removed = world.remove_bundle::<Orc>(entity);
+        assert_eq!(removed, Some(Orc { position: Position(1.0, 2.0), stats: (Health(30), Enemy) }));
+        assert!(!world.has_component::<Health>(entity) && world.has_component::<Kind>(entity));
+        assert_eq!(world.removed::<Enemy>().collect::<Vec<_>>(), [entity]);
+
+        // Missing a component leaves the others in place.
+        world.add_component(entity, Health(5));
+        assert_eq!(world.remove_bundle::<Orc>(entity), None);
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(5)));
+        world.despawn_entity(entity);
+        assert_eq!(world.remove_bundle::<(Kind,)>(entity).map(|(kind,)| kind.0), None);
+    }
+
+    #[test]
+    fn taking_a_bundle_is_all_or_nothing() {
+        let mut world = World::new();
+        let entity = *world.spawn_entity();
+        world.insert_bundle(entity, (Position(1.0, 2.0), Health(30)));
+        assert!(world.take_bundle::<Orc>(entity).is_none());
+        assert_eq!(world.get_component::<Position>(entity), Some(&Position(1.0, 2.0)));
+        assert_eq!(world.get_component::<Health>(entity), Some(&Health(30)));
+        assert_eq!(world.removed::<Position>().count(), 0);
+
+        world.add_component(entity, Enemy);
+        let orc = world.take_bundle::<Orc>(entity).unwrap();
+        assert_eq!(orc.stats, (Health(30), Enemy));
+        assert!(world.is_alive(entity) && !world.has_component::<Position>(entity));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn dynamic_bundles_move_entities_between_worlds() {
+        let mut world = World::new();
+        world.insert_resource(Added(0));
+        world.add_observer(|trigger: Trigger<OnRemove<Kind>>, world: &mut DeferredWorld| {
+            // The values are still there for the observers.
+            assert_eq!(world.get_component::<Kind>(trigger.entity()).unwrap().0, "goblin");
+            world.get_resource_mut::<Added>().unwrap().0 += 1;
+        });
+        let entity = *world.spawn_entity();
+        world.insert_bundle(entity, (Position(1.0, 2.0), Health(30), Kind(String::from("goblin"))));
+        let other = *world.spawn_entity();
+        world.add_component(other, Health(1));
+
+        let bundle = world.take_all(entity).unwrap();
+        assert_eq!(bundle.len(), 3);
+        assert_eq!(bundle.get::<Kind>().unwrap().0, "goblin");
+        assert!(!bundle.contains::<Enemy>());
+        assert_eq!(world.get_resource::<Added>().unwrap().0, 1);
+        assert!(world.is_alive(entity) && world.take_all(entity).unwrap().is_empty());
+        assert_eq!(world.removed::<Health>().collect::<Vec<_>>(), [entity]);
+        assert_eq!(world.get_component::<Health>(other), Some(&Health(1)));
+        world.validate().unwrap();
+
+        let mut target = World::new();
+        target.insert_resource(Added(0));
+        target.add_observer(|_: Trigger<OnAdd<Health>>, world: &mut DeferredWorld| {
+            world.get_resource_mut::<Added>().unwrap().0 += 1;
+        });
+        target.register_component::<Enemy>();
+        let copy = *target.spawn_entity();
+        target.add_component(copy, Position(0.0, 0.0));
+        target.insert_dynamic_bundle(copy, bundle);
+        assert_eq!(target.get_component::<Position>(copy), Some(&Position(1.0, 2.0)));
+        assert_eq!(target.get_component::<Health>(copy), Some(&Health(30)));
+        assert_eq!(target.get_component::<Kind>(copy).unwrap().0, "goblin");
+        assert_eq!(target.get_resource::<Added>().unwrap().0, 1);
+        let healthy = target.query::<(Entity, &Health)>();
+        assert_eq!(healthy.iter(&target).map(|(e, h)| (e, h.0)).collect::<Vec<_>>(), [(copy, 30)]);
+        target.validate().unwrap();
+    }
+
+    #[test]
+    fn dynamic_bundles_drop_what_they_hold() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        #[derive(Component)]
+        struct Counted(Arc<AtomicUsize>);
+
+        impl Drop for Counted {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        let drops = Arc::new(AtomicUsize::new(0));
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..3)
+            .map(|_| {
+                let e = *world.spawn_entity();
+                world.insert_bundle(e, (Counted(drops.clone()), Kind(String::from("crate"))));
+                e
+            })
+            .collect();
+        drop(world.take_all(entities[0]));
+        assert_eq!(drops.load(Ordering::Relaxed), 1);
+
+        // Inserting into a dead entity fails and drops the bundle.
+        let bundle = world.take_all(entities[1]).unwrap();
+        world.despawn_entity(entities[2]);
+        assert_eq!(drops.load(Ordering::Relaxed), 2);
+        assert!(world.try_insert_dynamic_bundle(entities[2], bundle).is_err());
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+        drop(world);
+        assert_eq!(drops.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "holds the component seed_ecs::bundle::tests::Health more than once")]
+    fn flattened_duplicates_are_rejected() {
+        let mut world = World::new();
+        let orc = Orc { position: Position(1.0, 2.0), stats: (Health(30), Enemy) };
+        world.spawn_batch_iter([Twice { health: Health(1), orc }]);
+    }
+}
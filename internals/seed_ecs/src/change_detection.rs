@@ -0,0 +1,406 @@
+//! Ticks recording when components and resources were added and last changed.
+//!
+//! Every system run gets its own tick from the world. A value is changed for a system when it
+//! was written after the previous run of that system, see [`Changed`](crate::query::Changed).
+//! Code running outside of systems compares against [`World::clear_trackers`].
+//!
+//! [`World::clear_trackers`]: crate::World::clear_trackers
+//!
+//! Ticks are `u32` and wrap around. [`World::check_change_ticks`] clamps the ticks older than
+//! [`MAX_CHANGE_AGE`] so that they keep comparing as old, [`Schedule::run`] calls it as needed.
+//!
+//! [`World::check_change_ticks`]: crate::World::check_change_ticks
+//! [`Schedule::run`]: crate::system::Schedule::run
+
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+
+use crate::component::ComponentId;
+use crate::entity::Entity;
+
+/// How many ticks may pass between two clamps of the ticks of a world.
+pub const CHECK_TICK_THRESHOLD: u32 = 518_400_000;
+
+/// The age past which ticks are clamped. It leaves room for `CHECK_TICK_THRESHOLD` ticks between
+/// two clamps before any comparison wraps.
+pub const MAX_CHANGE_AGE: u32 = u32::MAX - (2 * CHECK_TICK_THRESHOLD - 1);
+
+/// A point in the life of a world, see [`World::change_tick`](crate::World::change_tick).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Tick(u32);
+
+impl Tick {
+    pub const fn new(tick: u32) -> Self {
+        Self(tick)
+    }
+
+    pub fn get(self) -> u32 {
+        self.0
+    }
+
+    /// True if the tick comes after `last_run`, both seen from `this_run`.
+    ///
+    /// Ages are capped at [`MAX_CHANGE_AGE`], ticks older than that only compare correctly once
+    /// they are clamped by [`Tick::check_tick`].
+    pub fn is_newer_than(self, last_run: Tick, this_run: Tick) -> bool {
+        let age = this_run.0.wrapping_sub(self.0).min(MAX_CHANGE_AGE);
+        let last_run_age = this_run.0.wrapping_sub(last_run.0).min(MAX_CHANGE_AGE);
+        age < last_run_age
+    }
+
+    /// Brings the tick up to [`MAX_CHANGE_AGE`] if it is older than that seen from `tick`,
+    /// returns true if it was clamped.
+    pub fn check_tick(&mut self, tick: Tick) -> bool {
+        if tick.0.wrapping_sub(self.0) > MAX_CHANGE_AGE {
+            self.0 = tick.0.wrapping_sub(MAX_CHANGE_AGE);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn next(self) -> Self {
+        Self(self.0.wrapping_add(1))
+    }
+}
+
+/// When a component or a resource was added and last changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ComponentTicks {
+    pub added: Tick,
+    pub changed: Tick,
+}
+
+impl ComponentTicks {
+    pub fn new(tick: Tick) -> Self {
+        Self {
+            added: tick,
+            changed: tick,
+        }
+    }
+
+    pub fn is_added(&self, last_run: Tick, this_run: Tick) -> bool {
+        self.added.is_newer_than(last_run, this_run)
+    }
+
+    pub fn is_changed(&self, last_run: Tick, this_run: Tick) -> bool {
+        self.changed.is_newer_than(last_run, this_run)
+    }
+
+    /// Clamps both ticks, see [`Tick::check_tick`].
+    pub fn check_ticks(&mut self, tick: Tick) {
+        self.added.check_tick(tick);
+        self.changed.check_tick(tick);
+    }
+}
+
+/// The entities that lost a component, per component, for the two latest frames.
+///
+/// [`World::clear_trackers`](crate::World::clear_trackers) starts a new frame by swapping the
+/// buffers, the removals of the frame before the previous one are forgotten.
+#[derive(Default)]
+pub(crate) struct RemovedComponents {
+    // Indexed by component id.
+    current: Vec<Vec<Entity>>,
+    previous: Vec<Vec<Entity>>,
+}
+
+impl RemovedComponents {
+    pub fn push(&mut self, id: ComponentId, entity: Entity) {
+        self.extend(id, [entity]);
+    }
+
+    pub fn extend(&mut self, id: ComponentId, entities: impl IntoIterator<Item = Entity>) {
+        if self.current.len() <= id.index() {
+            self.current.resize_with(id.index() + 1, Vec::new);
+        }
+        self.current[id.index()].extend(entities);
+    }
+
+    /// The entities that lost the component in the previous frame then in the current one.
+    pub fn get(&self, id: ComponentId) -> impl Iterator<Item = Entity> + '_ {
+        let previous = self.previous.get(id.index()).into_iter().flatten();
+        previous.chain(self.current.get(id.index()).into_iter().flatten()).copied()
+    }
+
+    pub fn swap(&mut self) {
+        mem::swap(&mut self.current, &mut self.previous);
+        for removed in &mut self.current {
+            removed.clear();
+        }
+    }
+}
+
+struct TicksMut<'a> {
+    ticks: &'a mut ComponentTicks,
+    last_run: Tick,
+    this_run: Tick,
+}
+
+/// Exclusive access to a value stored in a world, marking it changed when it is dereferenced
+/// mutably.
+///
+/// Zero sized components keep no ticks, their `Mut` never reports anything.
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    ticks: Option<TicksMut<'a>>,
+}
+
+impl<'a, T> Mut<'a, T> {
+    /// A reference whose changes are not tracked.
+    pub fn new(value: &'a mut T) -> Self {
+        Self { value, ticks: None }
+    }
+
+    pub(crate) fn with_ticks(value: &'a mut T, ticks: &'a mut ComponentTicks, last_run: Tick, this_run: Tick) -> Self {
+        Self {
+            value,
+            ticks: Some(TicksMut {
+                ticks,
+                last_run,
+                this_run,
+            }),
+        }
+    }
+
+    /// True if the value was added since the last run of the system looking at it.
+    pub fn is_added(&self) -> bool {
+        self.ticks.as_ref().is_some_and(|t| t.ticks.is_added(t.last_run, t.this_run))
+    }
+
+    /// True if the value was added or written since the last run of the system looking at it.
+    pub fn is_changed(&self) -> bool {
+        self.ticks.as_ref().is_some_and(|t| t.ticks.is_changed(t.last_run, t.this_run))
+    }
+
+    /// Marks the value changed without writing it.
+    pub fn set_changed(&mut self) {
+        if let Some(t) = &mut self.ticks {
+            t.ticks.changed = t.this_run;
+        }
+    }
+
+    /// Writes through this without marking the value changed, for caches that nobody reacts to.
+    pub fn bypass_change_detection(&mut self) -> &mut T {
+        self.value
+    }
+
+    /// The inner reference, for the callers that need the full lifetime. The value is marked
+    /// changed.
+    pub fn into_inner(mut self) -> &'a mut T {
+        self.set_changed();
+        self.value
+    }
+}
+
+impl<'a, T: PartialEq> Mut<'a, T> {
+    /// Replaces the value only if it differs, so that equal writes don't mark it changed.
+    /// Returns true if it was replaced.
+    pub fn set_if_neq(&mut self, value: T) -> bool {
+        if *self.value == value {
+            return false;
+        }
+        **self = value;
+        true
+    }
+}
+
+impl<'a, T> Deref for Mut<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'a, T> DerefMut for Mut<'a, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.set_changed();
+        self.value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::system::Schedule;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(i32);
+    #[derive(Debug, PartialEq, Default, Component)]
+    struct Score(u32);
+
+    #[test]
+    fn tick_comparison_wraps() {
+        let (last_run, this_run) = (Tick::new(u32::MAX - 1), Tick::new(2));
+        assert!(Tick::new(u32::MAX).is_newer_than(last_run, this_run));
+        assert!(Tick::new(1).is_newer_than(last_run, this_run));
+        assert!(!Tick::new(u32::MAX - 1).is_newer_than(last_run, this_run));
+        assert!(!Tick::new(100).is_newer_than(Tick::new(100), Tick::new(100)));
+    }
+
+    #[test]
+    fn clamped_ticks_stay_old() {
+        let this_run = Tick::new(5);
+        let mut tick = Tick::new(10);
+        assert!(tick.check_tick(this_run));
+        assert_eq!(this_run.get().wrapping_sub(tick.get()), MAX_CHANGE_AGE);
+        assert!(!tick.check_tick(this_run));
+        assert!(!tick.is_newer_than(Tick::new(0), this_run));
+    }
+
+    fn changed(world: &mut World) -> Vec<Entity> {
+        let query = world.query_filtered::<Entity, Changed<Position>>();
+        query.iter(world).collect()
+    }
+
+    #[test]
+    fn only_writes_mark_changed() {
+        let mut world = World::new();
+        let [read, write, same, different, bypassed] = [(); 5].map(|_| *world.spawn_entity());
+        for e in [read, write, same, different, bypassed] {
+            world.add_component(e, Position(0));
+        }
+        assert_eq!(changed(&mut world).len(), 5);
+        world.clear_trackers();
+        assert_eq!(changed(&mut world), []);
+
+        let position = world.get_component_mut::<Position>(read).unwrap();
+        assert_eq!(*position, Position(0));
+        assert!(!position.is_changed() && !position.is_added());
+        world.get_component_mut::<Position>(write).unwrap().0 = 1;
+        assert!(!world.get_component_mut::<Position>(same).unwrap().set_if_neq(Position(0)));
+        assert!(world.get_component_mut::<Position>(different).unwrap().set_if_neq(Position(2)));
+        world.get_component_mut::<Position>(bypassed).unwrap().bypass_change_detection().0 = 3;
+        assert_eq!(changed(&mut world), [write, different]);
+        assert!(world.get_component_mut::<Position>(write).unwrap().is_changed());
+        assert_eq!(world.get_component::<Position>(bypassed), Some(&Position(3)));
+
+        world.clear_trackers();
+        assert_eq!(changed(&mut world), []);
+    }
+
+    #[derive(Default)]
+    struct Seen(Vec<Entity>, Vec<Entity>);
+
+    fn track(query: Query<Entity, Changed<Position>>, added: Query<Entity, Added<Position>>, mut seen: ResMut<Seen>) {
+        seen.0 = query.iter().collect();
+        seen.1 = added.iter().collect();
+    }
+
+    fn nudge(mut query: Query<(Entity, &mut Position)>, mut score: ResMut<Score>) {
+        for (entity, mut position) in query.iter_mut() {
+            if entity.index() == 1 {
+                position.0 += 1;
+            }
+        }
+        score.set_if_neq(Score(7));
+    }
+
+    #[test]
+    fn systems_see_changes_since_their_last_run() {
+        let mut world = World::new();
+        world.init_resource::<Seen>();
+        world.init_resource::<Score>();
+        let entities = [(); 3].map(|_| *world.spawn_entity());
+        for e in entities {
+            world.add_component(e, Position(0));
+        }
+        let mut nudge = IntoSystem::into_system(nudge);
+        let mut track = IntoSystem::into_system(track);
+
+        // Everything is new to a system that never ran.
+        track.run(&mut world);
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, entities);
+        assert_eq!(world.get_resource::<Seen>().unwrap().1, entities);
+
+        nudge.run(&mut world);
+        track.run(&mut world);
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, [entities[1]]);
+        assert_eq!(world.get_resource::<Seen>().unwrap().1, []);
+
+        // Nothing changed since the previous run of `track`.
+        track.run(&mut world);
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, []);
+        let score = world.get_resource_mut::<Score>().unwrap();
+        assert!(score.is_changed() && score.is_added());
+        assert_eq!(*score, Score(7));
+    }
+
+    // Moves the world `by` ticks ahead in steps the schedule would have clamped at.
+    fn advance(world: &mut World, system: &mut dyn System, mut by: u64) {
+        while by > 0 {
+            let step = by.min(CHECK_TICK_THRESHOLD as u64);
+            world.change_tick = Tick::new(world.change_tick.get().wrapping_add(step as u32));
+            if let Some(tick) = world.check_change_ticks() {
+                system.check_change_tick(tick);
+            }
+            by -= step;
+        }
+    }
+
+    #[test]
+    fn clamping_keeps_old_ticks_old_across_wraparound() {
+        let mut world = World::new();
+        world.init_resource::<Seen>();
+        let start = u32::MAX - 5;
+        world.change_tick = Tick::new(start);
+        let entity = *world.spawn_entity();
+        world.add_component(entity, Position(0));
+        world.clear_trackers();
+        let mut track = IntoSystem::into_system(track);
+        track.run(&mut world);
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, [entity]);
+
+        // Close to a full turn of the ticks later the component would otherwise look brand new.
+        advance(&mut world, &mut track, (1 << 32) - 2);
+        assert_eq!(world.change_tick(), Tick::new(start));
+        assert!(Tick::new(start).is_newer_than(Tick::new(start.wrapping_add(1)), Tick::new(start)));
+        track.run(&mut world);
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, []);
+        assert_eq!(world.get_resource::<Seen>().unwrap().1, []);
+
+        world.get_component_mut::<Position>(entity).unwrap().0 = 1;
+        track.run(&mut world);
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, [entity]);
+        assert_eq!(world.get_resource::<Seen>().unwrap().1, []);
+    }
+
+    #[test]
+    fn schedules_clamp_once_enough_ticks_passed() {
+        let mut world = World::new();
+        world.init_resource::<Seen>();
+        let mut schedule = Schedule::new();
+        schedule.add_system(track);
+        schedule.run(&mut world);
+        assert_eq!(world.check_change_ticks(), None);
+
+        world.change_tick = Tick::new(CHECK_TICK_THRESHOLD + 10);
+        schedule.run(&mut world);
+        assert_eq!(world.last_check_tick, Tick::new(CHECK_TICK_THRESHOLD + 11));
+        assert_eq!(world.check_change_ticks(), None);
+    }
+
+    #[test]
+    fn removals_are_kept_for_two_frames() {
+        let mut world = World::new();
+        let [a, b, c] = [(); 3].map(|_| *world.spawn_entity());
+        for e in [a, b, c] {
+            world.add_component(e, Position(0));
+        }
+        world.remove_component::<Position>(a);
+        world.despawn_entity(b);
+        assert_eq!(world.removed::<Position>().collect::<Vec<_>>(), [a, b]);
+        assert_eq!(world.removed::<Score>().count(), 0);
+
+        world.clear_trackers();
+        world.remove_component::<Position>(c);
+        assert_eq!(world.removed::<Position>().collect::<Vec<_>>(), [a, b, c]);
+        world.clear_trackers();
+        assert_eq!(world.removed::<Position>().collect::<Vec<_>>(), [c]);
+        world.clear_trackers();
+        assert_eq!(world.removed::<Position>().count(), 0);
+    }
+}
@@ -0,0 +1,104 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::World;
+
+type Command = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// World mutations recorded while the world can't be changed structurally, applied later in
+/// order.
+#[derive(Default)]
+pub struct CommandQueue {
+    commands: Vec<Command>,
+}
+
+impl CommandQueue {
+    pub fn push<C: FnOnce(&mut World) + Send + Sync + 'static>(&mut self, command: C) {
+        self.commands.push(Box::new(command));
+    }
+
+    /// Runs the commands in the order they were pushed and empties the queue.
+    ///
+    /// If a command panics, the commands after it are dropped without running.
+    pub fn apply(&mut self, world: &mut World) {
+        for command in core::mem::take(&mut self.commands) {
+            command(world);
+        }
+    }
+
+    /// Drops the commands without running them.
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+/// Records commands into a [`CommandQueue`].
+pub struct Commands<'a> {
+    queue: &'a mut CommandQueue,
+}
+
+impl<'a> Commands<'a> {
+    pub fn new(queue: &'a mut CommandQueue) -> Self {
+        Self { queue }
+    }
+
+    pub fn add<C: FnOnce(&mut World) + Send + Sync + 'static>(&mut self, command: C) -> &mut Self {
+        self.queue.push(command);
+        self
+    }
+
+    /// Adds a component to the entity, nothing happens if it is dead by then.
+    pub fn insert<T: Component>(&mut self, entity: Entity, component: T) -> &mut Self {
+        self.add(move |world: &mut World| {
+            if world.is_alive(entity) {
+                world.add_component(entity, component);
+            }
+        })
+    }
+
+    /// Adds a component to each entity in one command, the dead ones are skipped.
+    pub fn insert_batch<T: Component>(&mut self, batch: impl IntoIterator<Item = (Entity, T)>) -> &mut Self {
+        let batch: Vec<(Entity, T)> = batch.into_iter().collect();
+        self.add(move |world: &mut World| {
+            for (entity, component) in batch {
+                if world.is_alive(entity) {
+                    world.add_component(entity, component);
+                }
+            }
+        })
+    }
+
+    pub fn remove<T: Component>(&mut self, entity: Entity) -> &mut Self {
+        self.add(move |world: &mut World| {
+            world.remove_component::<T>(entity);
+        })
+    }
+
+    pub fn despawn(&mut self, entity: Entity) -> &mut Self {
+        self.add(move |world: &mut World| {
+            world.despawn_entity(entity);
+        })
+    }
+
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, value: T) -> &mut Self {
+        self.add(move |world: &mut World| {
+            world.insert_resource(value);
+        })
+    }
+
+    pub fn remove_resource<T: Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.add(|world: &mut World| {
+            world.remove_resource::<T>();
+        })
+    }
+}
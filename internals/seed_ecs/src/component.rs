@@ -0,0 +1,593 @@
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::any::{type_name, TypeId};
+use core::ffi::c_void;
+use core::{fmt, mem};
+
+use crate::change_detection::Tick;
+use crate::entity::{Entity, EntityMapper};
+use crate::reflect::Reflect;
+use crate::storage::{AnyStorage, Storage};
+use crate::utils::TypeIdMap;
+use crate::{EcsError, World};
+
+pub use seed_ecs_derive::Component;
+
+/// Rewrites the entities stored in every component of a storage.
+pub(crate) type MapEntitiesFn = fn(&mut dyn AnyStorage, &mut EntityMapper);
+
+/// Clones the component at a source index of a storage to a destination index, as added at a
+/// tick.
+pub(crate) type CloneFn = fn(&mut dyn AnyStorage, usize, usize, Tick);
+
+/// Formats the component at an index of a storage with its `Debug` impl.
+pub(crate) type DebugFn = fn(&dyn AnyStorage, usize) -> Option<String>;
+
+type InsertDefault = dyn Fn(&mut World, Entity) -> Result<bool, EcsError> + Send + Sync;
+
+/// Adds the default value of a component to an entity, returns true if it replaced a value.
+#[derive(Clone)]
+pub(crate) struct DefaultFn(pub Arc<InsertDefault>);
+
+impl fmt::Debug for DefaultFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DefaultFn")
+    }
+}
+
+/// Drops a component registered with a [`ComponentDescriptor`] in place.
+///
+/// It uses the C ABI so that components described by a foreign host can be dropped by it.
+pub type DropFn = unsafe extern "C" fn(*mut c_void);
+
+/// Views the component at an index of a storage as a [`Reflect`] value.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReflectFns {
+    pub get: fn(&dyn AnyStorage, usize) -> Option<&dyn Reflect>,
+    pub get_mut: fn(&mut dyn AnyStorage, usize) -> Option<&mut dyn Reflect>,
+}
+
+/// The types stored on entities: inserted by [`World::add_component`](crate::World::add_component),
+/// spawned in bundles and read by queries. Implement it with `#[derive(Component)]`, which takes
+/// `#[component(storage = "sparse")]` to store the values in a sparse set from the start and
+/// `#[component(immutable)]` to forbid the mutable borrows, or enable the `loose-components`
+/// feature to make every `Send + Sync + 'static` type a dense mutable component, the derive then
+/// ignoring its attributes.
+///
+#[cfg_attr(feature = "loose-components", doc = "```ignore")]
+#[cfg_attr(not(feature = "loose-components"), doc = "```")]
+/// use seed_ecs::component::{Component, StorageKind};
+/// use seed_ecs::World;
+///
+/// #[derive(Component)]
+/// #[component(storage = "sparse")]
+/// struct Stunned(f32);
+///
+/// let mut world = World::new();
+/// let entity = world.spawn_batch([Stunned(0.5)])[0];
+/// let id = world.components().id::<Stunned>().unwrap();
+/// assert_eq!(world.components().info(id).unwrap().storage(), StorageKind::SparseSet);
+/// assert_eq!(world.get_component::<Stunned>(entity).unwrap().0, 0.5);
+/// ```
+///
+/// Other types are rejected at compile time:
+///
+#[cfg_attr(feature = "loose-components", doc = "```")]
+#[cfg_attr(not(feature = "loose-components"), doc = "```compile_fail")]
+/// # use seed_ecs::World;
+/// struct Loose(u32);
+///
+/// let mut world = World::new();
+/// world.spawn_batch([Loose(1)]);
+/// ```
+///
+/// And so are the mutable borrows of immutable components:
+///
+#[cfg_attr(feature = "loose-components", doc = "```")]
+#[cfg_attr(not(feature = "loose-components"), doc = "```compile_fail")]
+/// # use seed_ecs::component::Component;
+/// # use seed_ecs::World;
+/// #[derive(Component)]
+/// #[component(immutable)]
+/// struct Seed(u64);
+///
+/// let mut world = World::new();
+/// let entity = world.spawn_batch([Seed(7)])[0];
+/// world.get_component_mut::<Seed>(entity).unwrap().0 = 8;
+/// ```
+pub trait Component: Send + Sync + 'static {
+    /// The storage created when the component is registered, [`StorageKind::Dense`] or
+    /// [`StorageKind::SparseSet`]. Zero sized components are always stored as tags.
+    const STORAGE: StorageKind = StorageKind::Dense;
+    /// False if the values never change once inserted, borrowing them mutably, by
+    /// `&mut T` queries or [`World::get_component_mut`](crate::World::get_component_mut), is
+    /// then a compile error. They can still be replaced.
+    const MUTABLE: bool = true;
+}
+
+#[cfg(feature = "loose-components")]
+impl<T: Send + Sync + 'static> Component for T {}
+
+/// Fails to compile the mutable borrows of the immutable components, once monomorphized.
+pub(crate) const fn assert_mutable<T: Component>() {
+    assert!(T::MUTABLE, "Immutable components can't be borrowed mutably");
+}
+
+/// Identifies a component type inside of a [`World`](crate::World).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ComponentId(usize);
+
+impl ComponentId {
+    /// The id of index `index`, only meaningful for a world that registered that many components.
+    pub fn new(index: usize) -> Self {
+        Self(index)
+    }
+
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// A set of components, one bit per [`ComponentId`], like the components an entity has, see
+/// [`World::entity_signature`](crate::World::entity_signature).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ComponentSet {
+    // Bit `i % 32` of word `i / 32` stands for the component of index `i`. Never ends with an
+    // empty word, so that equal sets compare equal.
+    words: Vec<u32>,
+}
+
+impl ComponentSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the component, returns false if it was there already.
+    pub fn insert(&mut self, id: ComponentId) -> bool {
+        let (word, bit) = (id.index() / 32, 1 << (id.index() % 32));
+        if self.words.len() <= word {
+            self.words.resize(word + 1, 0);
+        }
+        let added = self.words[word] & bit == 0;
+        self.words[word] |= bit;
+        added
+    }
+
+    /// Removes the component, returns false if it wasn't there.
+    pub fn remove(&mut self, id: ComponentId) -> bool {
+        let removed = self.contains(id);
+        if removed {
+            self.words[id.index() / 32] &= !(1 << (id.index() % 32));
+            self.trim();
+        }
+        removed
+    }
+
+    pub fn contains(&self, id: ComponentId) -> bool {
+        self.words.get(id.index() / 32).is_some_and(|word| word & (1 << (id.index() % 32)) != 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// The components in ascending id order.
+    pub fn iter(&self) -> impl Iterator<Item = ComponentId> + '_ {
+        self.words.iter().enumerate().flat_map(|(word_idx, word)| {
+            let mut bits = *word;
+            core::iter::from_fn(move || {
+                let bit = (bits != 0).then(|| bits.trailing_zeros() as usize)?;
+                bits &= bits - 1;
+                Some(ComponentId::new(word_idx * 32 + bit))
+            })
+        })
+    }
+
+    pub fn union(&self, other: &ComponentSet) -> ComponentSet {
+        let (long, short) = if self.words.len() >= other.words.len() { (self, other) } else { (other, self) };
+        let mut words = long.words.clone();
+        words.iter_mut().zip(&short.words).for_each(|(word, other)| *word |= other);
+        ComponentSet { words }
+    }
+
+    pub fn intersection(&self, other: &ComponentSet) -> ComponentSet {
+        let words = self.words.iter().zip(&other.words).map(|(word, other)| word & other).collect();
+        ComponentSet { words }.trimmed()
+    }
+
+    /// The components of `self` that are not in `other`.
+    pub fn difference(&self, other: &ComponentSet) -> ComponentSet {
+        let other_words = other.words.iter().chain(core::iter::repeat(&0));
+        let words = self.words.iter().zip(other_words).map(|(word, other)| word & !other).collect();
+        ComponentSet { words }.trimmed()
+    }
+
+    pub fn is_subset(&self, other: &ComponentSet) -> bool {
+        self.difference(other).is_empty()
+    }
+
+    pub fn is_superset(&self, other: &ComponentSet) -> bool {
+        other.is_subset(self)
+    }
+
+    pub fn is_disjoint(&self, other: &ComponentSet) -> bool {
+        self.words.iter().zip(&other.words).all(|(word, other)| word & other == 0)
+    }
+
+    /// The raw words of the set, bit `i % 32` of word `i / 32` for the component of index `i`.
+    pub(crate) fn words(&self) -> &[u32] {
+        &self.words
+    }
+
+    fn trim(&mut self) {
+        while self.words.last() == Some(&0) {
+            self.words.pop();
+        }
+    }
+
+    fn trimmed(mut self) -> Self {
+        self.trim();
+        self
+    }
+}
+
+impl FromIterator<ComponentId> for ComponentSet {
+    fn from_iter<I: IntoIterator<Item = ComponentId>>(ids: I) -> Self {
+        let mut set = ComponentSet::new();
+        for id in ids {
+            set.insert(id);
+        }
+        set
+    }
+}
+
+/// How the values of a component are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageKind {
+    /// Values live in a `BVec` indexed by the entity index.
+    Dense,
+    /// Zero sized types only need to know which entities have them, they are stored as a mask.
+    Tag,
+    /// Components described by a [`ComponentDescriptor`] are stored as untyped bytes.
+    Blob,
+    /// Values are packed in a vector, removals move the last value into the hole unless the
+    /// component was registered with [`RemovalPolicy::Tombstone`]. The components of a group use
+    /// it, see [`World::register_group`](crate::World::register_group).
+    SparseSet,
+    /// Equal values are stored once and shared by the entities holding them, see
+    /// [`World::register_interned`](crate::World::register_interned).
+    Interned,
+}
+
+/// What removing a value from a sparse set storage does to the others, see
+/// [`World::register_sparse`](crate::World::register_sparse).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RemovalPolicy {
+    /// The last value moves into the hole, the values stay packed.
+    SwapRemove,
+    /// The hole stays until [`World::compact`](crate::World::compact), the other values keep
+    /// their position. Iteration skips the holes and insertions reuse them.
+    Tombstone,
+}
+
+/// Describes a component type known only at runtime, like the components of a scripting language
+/// or of a host engine driving the world through FFI.
+#[derive(Debug, Clone, Copy)]
+pub struct ComponentDescriptor {
+    name: &'static str,
+    layout: Layout,
+    drop: Option<DropFn>,
+}
+
+impl ComponentDescriptor {
+    pub fn new(name: &'static str, layout: Layout) -> Self {
+        Self {
+            name,
+            layout,
+            drop: None,
+        }
+    }
+
+    /// Sets the function called on every value the world drops, values are forgotten otherwise.
+    pub fn with_drop(mut self, drop: DropFn) -> Self {
+        self.drop = Some(drop);
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn drop_fn(&self) -> Option<DropFn> {
+        self.drop
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ComponentInfo {
+    id: ComponentId,
+    name: &'static str,
+    type_id: Option<TypeId>,
+    layout: Layout,
+    storage: StorageKind,
+    map_entities: Option<MapEntitiesFn>,
+    clone: Option<CloneFn>,
+    debug: Option<DebugFn>,
+    reflect: Option<ReflectFns>,
+    default: Option<DefaultFn>,
+}
+
+impl ComponentInfo {
+    pub fn id(&self) -> ComponentId {
+        self.id
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The Rust type of the component, `None` for components registered by a
+    /// [`ComponentDescriptor`].
+    pub fn type_id(&self) -> Option<TypeId> {
+        self.type_id
+    }
+
+    pub fn layout(&self) -> Layout {
+        self.layout
+    }
+
+    pub fn storage(&self) -> StorageKind {
+        self.storage
+    }
+
+    pub(crate) fn map_entities(&self) -> Option<MapEntitiesFn> {
+        self.map_entities
+    }
+
+    pub(crate) fn clone_fn(&self) -> Option<CloneFn> {
+        self.clone
+    }
+
+    pub(crate) fn debug_fn(&self) -> Option<DebugFn> {
+        self.debug
+    }
+
+    pub(crate) fn reflect_fns(&self) -> Option<ReflectFns> {
+        self.reflect
+    }
+
+    pub(crate) fn default_fn(&self) -> Option<&DefaultFn> {
+        self.default.as_ref()
+    }
+
+    /// The type name without its module path, `Position` for `game::physics::Position`.
+    pub fn short_name(&self) -> &'static str {
+        let end = self.name.find('<').unwrap_or(self.name.len());
+        let start = self.name[..end].rfind("::").map_or(0, |i| i + 2);
+        &self.name[start..]
+    }
+}
+
+/// The registry of all the component types known by a world.
+#[derive(Default)]
+pub struct Components {
+    infos: Vec<ComponentInfo>,
+    indices: TypeIdMap<ComponentId>,
+}
+
+impl Components {
+    /// Registers `T` if needed and returns its id.
+    pub fn register<T: 'static>(&mut self) -> ComponentId {
+        let type_id = TypeId::of::<T>();
+        if let Some(id) = self.indices.get(&type_id) {
+            return *id;
+        }
+        let id = ComponentId(self.infos.len());
+        let storage = if mem::size_of::<T>() == 0 {
+            StorageKind::Tag
+        } else {
+            StorageKind::Dense
+        };
+        self.infos.push(ComponentInfo {
+            id,
+            name: type_name::<T>(),
+            type_id: Some(type_id),
+            layout: Layout::new::<T>(),
+            storage,
+            map_entities: None,
+            clone: None,
+            debug: None,
+            reflect: None,
+            default: None,
+        });
+        self.indices.insert(type_id, id);
+        id
+    }
+
+    /// Registers a new component from its descriptor, registering the same descriptor twice gives
+    /// two different components.
+    pub fn register_descriptor(&mut self, descriptor: &ComponentDescriptor) -> ComponentId {
+        let id = ComponentId(self.infos.len());
+        self.infos.push(ComponentInfo {
+            id,
+            name: descriptor.name,
+            type_id: None,
+            layout: descriptor.layout,
+            storage: StorageKind::Blob,
+            map_entities: None,
+            clone: None,
+            debug: None,
+            reflect: None,
+            default: None,
+        });
+        id
+    }
+
+    /// Registers a component described by the info of another world and returns its id here.
+    ///
+    /// Components without a Rust type are matched by name and layout.
+    pub(crate) fn register_info(&mut self, info: &ComponentInfo) -> ComponentId {
+        let existing = match info.type_id {
+            Some(type_id) => self.indices.get(&type_id).copied(),
+            None => self
+                .infos
+                .iter()
+                .find(|i| i.type_id.is_none() && i.name == info.name && i.layout == info.layout)
+                .map(|i| i.id),
+        };
+        if let Some(id) = existing {
+            return id;
+        }
+        let id = ComponentId(self.infos.len());
+        self.infos.push(ComponentInfo { id, ..info.clone() });
+        if let Some(type_id) = info.type_id {
+            self.indices.insert(type_id, id);
+        }
+        id
+    }
+
+    pub(crate) fn set_storage(&mut self, id: ComponentId, storage: StorageKind) {
+        self.infos[id.0].storage = storage;
+    }
+
+    pub(crate) fn set_map_entities(&mut self, id: ComponentId, map_entities: MapEntitiesFn) {
+        self.infos[id.0].map_entities = Some(map_entities);
+    }
+
+    pub(crate) fn set_clone(&mut self, id: ComponentId, clone: CloneFn) {
+        self.infos[id.0].clone = Some(clone);
+    }
+
+    pub(crate) fn set_debug(&mut self, id: ComponentId, debug: DebugFn) {
+        self.infos[id.0].debug = Some(debug);
+    }
+
+    pub(crate) fn set_reflect(&mut self, id: ComponentId, reflect: ReflectFns) {
+        self.infos[id.0].reflect = Some(reflect);
+    }
+
+    pub(crate) fn set_default(&mut self, id: ComponentId, default: DefaultFn) {
+        self.infos[id.0].default = Some(default);
+    }
+
+    pub fn id<T: 'static>(&self) -> Option<ComponentId> {
+        self.indices.get(&TypeId::of::<T>()).copied()
+    }
+
+    pub fn get_id(&self, type_id: TypeId) -> Option<ComponentId> {
+        self.indices.get(&type_id).copied()
+    }
+
+    pub fn info(&self, id: ComponentId) -> Option<&ComponentInfo> {
+        self.infos.get(id.0)
+    }
+
+    /// Looks a component up by its full type name or by its [short name](ComponentInfo::short_name).
+    pub fn get_id_by_name(&self, name: &str) -> Option<ComponentId> {
+        let info = self.infos.iter().find(|info| info.name == name);
+        info.or_else(|| self.infos.iter().find(|info| info.short_name() == name))
+            .map(|info| info.id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.infos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.infos.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ComponentInfo> {
+        self.infos.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::PAGE_SIZE;
+
+    struct Marker;
+    #[derive(Component)]
+    struct Health(u32);
+
+    #[test]
+    fn register_picks_storage() {
+        let mut components = Components::default();
+        let marker = components.register::<Marker>();
+        let health = components.register::<Health>();
+        assert_eq!(components.register::<Marker>(), marker);
+        assert_eq!(components.info(marker).unwrap().storage(), StorageKind::Tag);
+        assert_eq!(components.info(health).unwrap().storage(), StorageKind::Dense);
+        assert_eq!(components.id::<Health>(), Some(health));
+        assert_eq!(components.id::<u8>(), None);
+    }
+
+    #[derive(Component)]
+    #[component(storage = "dense")]
+    struct Position(f32);
+    #[derive(Component)]
+    #[component(storage = "sparse")]
+    struct Burning(f32);
+    #[derive(Component)]
+    #[component(storage = "sparse")]
+    struct Stunned;
+    #[derive(Component)]
+    #[component(storage = "sparse", immutable)]
+    struct Target<T>(T);
+
+    // The attributes of the derive are ignored with `loose-components`.
+    #[test]
+    #[cfg(not(feature = "loose-components"))]
+    fn derive_selects_the_storage() {
+        assert_eq!(Position::STORAGE, StorageKind::Dense);
+        assert_eq!([Position::MUTABLE, Health::MUTABLE, Target::<u32>::MUTABLE], [true, true, false]);
+        let mut world = World::new();
+        let entity = world.spawn_batch_iter((0..4096).map(|_| ())).pop().unwrap();
+        world.entity_mut(entity).insert(Position(1.0)).insert(Burning(2.0)).insert(Stunned).insert(Target(7u32));
+        let kind = |world: &World, id: Option<ComponentId>| world.components().info(id.unwrap()).unwrap().storage();
+        assert_eq!(kind(&world, world.components().id::<Position>()), StorageKind::Dense);
+        assert_eq!(kind(&world, world.components().id::<Burning>()), StorageKind::SparseSet);
+        assert_eq!(kind(&world, world.components().id::<Target<u32>>()), StorageKind::SparseSet);
+        // Zero sized components stay tags.
+        assert_eq!(kind(&world, world.components().id::<Stunned>()), StorageKind::Tag);
+
+        // The dense storage allocates the page of the entity index, the sparse sets only their values.
+        let (dense, sparse) = (world.storage_stats::<Position>(), world.storage_stats::<Burning>());
+        assert_eq!((dense.live, sparse.live), (1, 1));
+        assert_eq!(dense.capacity_slots, PAGE_SIZE);
+        assert!(sparse.capacity_slots < PAGE_SIZE);
+        assert_eq!(world.get_component::<Target<u32>>(entity).unwrap().0, 7);
+    }
+
+    #[test]
+    fn component_set_operations() {
+        let ids = |indices: &[usize]| indices.iter().map(|index| ComponentId::new(*index)).collect::<ComponentSet>();
+        let mut a = ids(&[1, 5, 40]);
+        let b = ids(&[5, 70]);
+        assert_eq!(a.iter().map(ComponentId::index).collect::<Vec<_>>(), [1, 5, 40]);
+        assert_eq!(a.union(&b), ids(&[1, 5, 40, 70]));
+        assert_eq!(a.intersection(&b), ids(&[5]));
+        assert_eq!(b.difference(&a), ids(&[70]));
+        assert!(ids(&[1, 40]).is_subset(&a) && a.is_superset(&ids(&[5])));
+        assert!(!a.is_subset(&b) && a.is_disjoint(&ids(&[2, 70])));
+
+        // Emptied words don't make equal sets differ.
+        assert!(!a.insert(ComponentId::new(40)));
+        assert!(a.remove(ComponentId::new(40)));
+        assert!(!a.remove(ComponentId::new(40)));
+        assert_eq!(a, ids(&[1, 5]));
+        assert_eq!((a.len(), a.contains(ComponentId::new(40))), (2, false));
+        assert!(ids(&[64]).difference(&ids(&[64])).is_empty());
+    }
+}
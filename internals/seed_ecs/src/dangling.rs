@@ -0,0 +1,291 @@
+//! Despawn events and the cleanup of the components still referencing despawned entities.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::change_detection::Tick;
+use crate::component::{Component, ComponentId};
+use crate::entity::{DanglingPolicy, Entity, EntityMapper, MapEntities};
+use crate::system::{AccessConflict, System, SystemMeta, SystemParam};
+use crate::{UnsafeWorldCell, World};
+
+/// Sent by [`World::despawn_entity`] for every entity it despawns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EntityDespawned(pub Entity);
+
+/// The entities despawned during the current and the previous frame, like the removed components.
+#[derive(Default)]
+pub(crate) struct DespawnEvents {
+    current: Vec<Entity>,
+    previous: Vec<Entity>,
+    // Despawns ever recorded, tells the cleanup whether any happened since it last ran.
+    total: u64,
+}
+
+impl DespawnEvents {
+    pub fn push(&mut self, entity: Entity) {
+        self.current.push(entity);
+        self.total += 1;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = EntityDespawned> + '_ {
+        self.previous.iter().chain(&self.current).map(|entity| EntityDespawned(*entity))
+    }
+
+    pub fn swap(&mut self) {
+        mem::swap(&mut self.current, &mut self.previous);
+        self.current.clear();
+    }
+}
+
+/// What [`World::clear_dangling_references`] does with a component referencing a despawned
+/// entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DanglingCleanup {
+    /// Replaces the despawned entities by [`Entity::PLACEHOLDER`], which is never alive.
+    #[default]
+    Clear,
+    /// Removes the component from its entity.
+    Remove,
+}
+
+// Cleans the components of one type, scanning only the ones changed since the given tick if any.
+// The cleared values are marked changed at the last tick. Returns how many were cleaned.
+type CleanupFn = fn(&mut World, ComponentId, DanglingCleanup, Option<Tick>, Tick) -> usize;
+
+#[derive(Default)]
+pub(crate) struct DanglingCleanups {
+    cleanups: Vec<(ComponentId, DanglingCleanup, CleanupFn)>,
+    last_run: Option<(Tick, u64)>,
+}
+
+impl DanglingCleanups {
+    pub fn check_change_ticks(&mut self, tick: Tick) {
+        if let Some((last_run, _)) = &mut self.last_run {
+            last_run.check_tick(tick);
+        }
+    }
+}
+
+fn cleanup<T: Component + MapEntities>(
+    world: &mut World,
+    id: ComponentId,
+    policy: DanglingCleanup,
+    since: Option<Tick>,
+    this_run: Tick,
+) -> usize {
+    let entities = &world.entities;
+    let storage = world.storages.typed_mut::<T>(id);
+    // Under the error policy the mapper keeps every entity as is and lists it.
+    let mut seen = EntityMapper::new(DanglingPolicy::Error);
+    let mut holders = Vec::new();
+    let indices: Vec<usize> = storage.mask().iter().collect();
+    for index in indices {
+        let (value, ticks) = storage.get_with_ticks_mut(index).unwrap();
+        if let Some(last_run) = since {
+            if !ticks.as_ref().is_some_and(|ticks| ticks.is_changed(last_run, this_run)) {
+                continue;
+            }
+        }
+        value.map_entities(&mut seen);
+        let mut dead: Vec<Entity> = seen.take_dangling();
+        dead.retain(|entity| *entity != Entity::PLACEHOLDER && !entities.is_alive(*entity));
+        if dead.is_empty() {
+            continue;
+        }
+        match policy {
+            DanglingCleanup::Clear => {
+                let mut mapper = EntityMapper::new(DanglingPolicy::Keep);
+                for entity in dead {
+                    mapper.insert(entity, Entity::PLACEHOLDER);
+                }
+                value.map_entities(&mut mapper);
+                if let Some(ticks) = ticks {
+                    ticks.changed = this_run;
+                }
+            }
+            DanglingCleanup::Remove => {}
+        }
+        holders.push(index);
+    }
+    if policy == DanglingCleanup::Remove {
+        for index in &holders {
+            let entity = world.entities.get(*index as u32).unwrap();
+            world.remove_component::<T>(entity);
+        }
+    }
+    holders.len()
+}
+
+impl World {
+    /// The entities despawned during the current and the previous frame, as delimited by
+    /// [`World::clear_trackers`].
+    pub fn despawned(&self) -> impl Iterator<Item = EntityDespawned> + '_ {
+        self.despawned.iter()
+    }
+
+    /// Lets [`World::clear_dangling_references`] clean the references to despawned entities stored
+    /// in `T`. Registering again replaces the policy.
+    pub fn register_dangling_cleanup<T: Component + MapEntities>(&mut self, policy: DanglingCleanup) {
+        let id = self.register_component::<T>();
+        let cleanups = &mut self.dangling.cleanups;
+        cleanups.retain(|(other, ..)| *other != id);
+        cleanups.push((id, policy, cleanup::<T>));
+    }
+
+    /// Cleans the components registered with [`World::register_dangling_cleanup`] that reference
+    /// despawned entities and returns how many were cleaned.
+    ///
+    /// When no entity was despawned since the previous call, only the components added or
+    /// changed since then are looked at.
+    pub fn clear_dangling_references(&mut self) -> usize {
+        let total = self.despawned.total;
+        let last_run = self.dangling.last_run;
+        let this_run = self.increment_change_tick();
+        self.dangling.last_run = Some((this_run, total));
+        let since = match last_run {
+            Some((tick, despawns)) if despawns == total => Some(tick),
+            _ => None,
+        };
+        let cleanups = self.dangling.cleanups.clone();
+        cleanups
+            .into_iter()
+            .map(|(id, policy, cleanup)| cleanup(self, id, policy, since, this_run))
+            .sum()
+    }
+}
+
+/// Runs [`World::clear_dangling_references`], for the schedules.
+pub struct ClearDanglingReferences;
+
+impl System for ClearDanglingReferences {
+    fn name(&self) -> &'static str {
+        "ClearDanglingReferences"
+    }
+
+    fn initialize(&mut self, _world: &mut World) -> Result<(), AccessConflict> {
+        Ok(())
+    }
+
+    fn run(&mut self, world: &mut World) {
+        world.clear_dangling_references();
+    }
+
+    // The world clamps the tick it keeps for the cleanup.
+    fn check_change_tick(&mut self, _tick: Tick) {}
+}
+
+/// The [`EntityDespawned`] events of the current and the previous frame.
+pub struct Despawned<'w> {
+    events: &'w DespawnEvents,
+}
+
+impl<'w> Despawned<'w> {
+    pub fn iter(&self) -> impl Iterator<Item = EntityDespawned> + 'w {
+        self.events.iter()
+    }
+}
+
+// Only the world writes the events, through `&mut World` while no system runs.
+unsafe impl<'a> SystemParam for Despawned<'a> {
+    type State = ();
+    type Item<'w, 's> = Despawned<'w>;
+
+    fn init_state(_world: &mut World, _meta: &mut SystemMeta) -> Self::State {}
+
+    unsafe fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        _meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        Despawned {
+            events: world.despawned(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::system::{IntoSystem, Schedule};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Target(Entity);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Owners(Vec<Entity>);
+
+    impl MapEntities for Target {
+        fn map_entities(&mut self, mapper: &mut EntityMapper) {
+            self.0.map_entities(mapper);
+        }
+    }
+
+    impl MapEntities for Owners {
+        fn map_entities(&mut self, mapper: &mut EntityMapper) {
+            self.0.map_entities(mapper);
+        }
+    }
+
+    #[test]
+    fn dangling_references_are_cleaned() {
+        let mut world = World::new();
+        world.register_dangling_cleanup::<Target>(DanglingCleanup::Remove);
+        world.register_dangling_cleanup::<Owners>(DanglingCleanup::Clear);
+        let enemy = *world.spawn_entity();
+        let friend = *world.spawn_entity();
+        let hunter = *world.spawn_entity();
+        let guard = *world.spawn_entity();
+        world.add_component(hunter, Target(enemy));
+        world.add_component(guard, Target(friend));
+        world.add_component(guard, Owners(vec![enemy, friend]));
+        assert_eq!(world.clear_dangling_references(), 0);
+
+        world.despawn_entity(enemy);
+        assert_eq!(world.clear_dangling_references(), 2);
+        assert!(!world.has_component::<Target>(hunter));
+        assert_eq!(world.get_component::<Target>(guard), Some(&Target(friend)));
+        assert_eq!(world.get_component::<Owners>(guard), Some(&Owners(vec![Entity::PLACEHOLDER, friend])));
+        assert_eq!(world.clear_dangling_references(), 0);
+
+        // Without despawns, only the new values are looked at.
+        world.add_component(hunter, Target(enemy));
+        let mut schedule = Schedule::new();
+        schedule.add_system(ClearDanglingReferences);
+        schedule.run(&mut world);
+        assert!(!world.has_component::<Target>(hunter));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn despawn_events_are_read_the_same_frame() {
+        let mut world = World::new();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(|mut commands: crate::commands::Commands| {
+                commands.add(|world: &mut World| {
+                    let doomed = world.enities().iter().next().unwrap();
+                    world.despawn_recursive(doomed);
+                });
+            })
+            .add_system(move |despawned: Despawned| {
+                log.lock().unwrap().extend(despawned.iter());
+            });
+        let parent = *world.spawn_entity();
+        let child = *world.spawn_entity();
+        world.set_parent(child, parent);
+        schedule.run(&mut world);
+        assert_eq!(*seen.lock().unwrap(), [EntityDespawned(parent), EntityDespawned(child)]);
+        assert_eq!(world.despawned().count(), 2);
+
+        world.clear_trackers();
+        assert_eq!(world.despawned().count(), 2);
+        world.clear_trackers();
+        assert_eq!(world.despawned().count(), 0);
+        let mut reader = (|despawned: Despawned| assert_eq!(despawned.iter().count(), 0)).into_system();
+        reader.run(&mut world);
+    }
+}
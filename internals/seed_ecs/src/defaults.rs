@@ -0,0 +1,125 @@
+//! Components added with a value registered ahead, for the editors adding a component picked
+//! from a list without knowing its type.
+
+use alloc::sync::Arc;
+
+use crate::component::{Component, ComponentId, DefaultFn};
+use crate::entity::Entity;
+use crate::{EcsError, World};
+
+impl World {
+    /// Lets [`World::insert_default_component`] add `T` with its `Default` value.
+    pub fn register_with_default<T: Component + Default>(&mut self) -> ComponentId {
+        self.register_with_default_fn(T::default)
+    }
+
+    /// Lets [`World::insert_default_component`] add `T` with the value `make` builds, for the
+    /// components without a `Default` impl or whose default differs in the editor. Replaces the
+    /// previous default.
+    pub fn register_with_default_fn<T: Component>(
+        &mut self,
+        make: impl Fn() -> T + Send + Sync + 'static,
+    ) -> ComponentId {
+        let id = self.register_component::<T>();
+        let insert = move |world: &mut World, entity: Entity| {
+            world.try_add_component(entity, make()).map(|previous| previous.is_some())
+        };
+        self.components.set_default(id, DefaultFn(Arc::new(insert)));
+        id
+    }
+
+    /// Adds the registered default value of the component `id` to the entity like
+    /// [`World::add_component`] does, observers included, and returns true if it replaced a
+    /// value.
+    ///
+    /// Fails if the entity is not alive, if the world has no component `id` or if the component
+    /// was registered without a default value.
+    pub fn insert_default_component(&mut self, entity: Entity, id: ComponentId) -> Result<bool, EcsError> {
+        self.entities.check_alive(entity)?;
+        let info = self.components.info(id).ok_or(EcsError::UnknownComponent(id))?;
+        let default = info.default_fn().cloned().ok_or(EcsError::NoDefault {
+            type_name: info.name(),
+        })?;
+        (default.0)(self, entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use core::any::type_name;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::observer::{DeferredWorld, OnAdd, Trigger};
+
+    #[derive(Debug, Default, PartialEq, Component)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Light {
+        radius: f32,
+    }
+    #[derive(Component)]
+    struct Script;
+
+    #[test]
+    fn inserting_by_id_uses_the_registered_default() {
+        let mut world = World::new();
+        let health = world.register_with_default::<Health>();
+        let light = world.register_with_default_fn(|| Light { radius: 4.0 });
+        let e = *world.spawn_entity();
+        assert_eq!(world.insert_default_component(e, health), Ok(false));
+        assert_eq!(world.get_component::<Health>(e), Some(&Health(0)));
+        world.get_component_mut::<Health>(e).unwrap().0 = 30;
+        assert_eq!(world.insert_default_component(e, health), Ok(true));
+        assert_eq!(world.get_component::<Health>(e), Some(&Health(0)));
+
+        world.entity_mut(e).insert_default_by_id(light).unwrap().remove::<Health>();
+        assert_eq!(world.get_component::<Light>(e), Some(&Light { radius: 4.0 }));
+        let other = *world.spawn_entity();
+        world.entity_mut(other).insert_default::<Health>().unwrap();
+        assert!(world.has_component::<Health>(other));
+    }
+
+    #[test]
+    fn components_without_a_default_are_rejected() {
+        let mut world = World::new();
+        let e = *world.spawn_entity();
+        let no_default = EcsError::NoDefault {
+            type_name: type_name::<Script>(),
+        };
+        // Never registered, then registered without a default.
+        assert_eq!(world.entity_mut(e).insert_default::<Script>().err(), Some(no_default));
+        let script = world.register_component::<Script>();
+        assert_eq!(world.insert_default_component(e, script), Err(no_default));
+        assert_eq!(
+            no_default.to_string(),
+            format!("Component {} has no default value registered", type_name::<Script>())
+        );
+        assert!(!world.has_component::<Script>(e));
+
+        let unknown = ComponentId::new(script.index() + 10);
+        assert_eq!(world.insert_default_component(e, unknown), Err(EcsError::UnknownComponent(unknown)));
+        let health = world.register_with_default::<Health>();
+        world.despawn_entity(e);
+        assert_eq!(world.insert_default_component(e, health), Err(EcsError::EntityNotAlive(e)));
+    }
+
+    #[test]
+    fn observers_see_default_inserts() {
+        static ADDED: AtomicU32 = AtomicU32::new(0);
+        let mut world = World::new();
+        let health = world.register_with_default::<Health>();
+        world.add_observer(|trigger: Trigger<OnAdd<Health>>, world: &mut DeferredWorld| {
+            assert_eq!(world.get_component::<Health>(trigger.entity()), Some(&Health(0)));
+            ADDED.fetch_add(1, Ordering::Relaxed);
+        });
+        let e = *world.spawn_entity();
+        world.insert_default_component(e, health).unwrap();
+        // Replacing isn't adding.
+        world.insert_default_component(e, health).unwrap();
+        assert_eq!(ADDED.load(Ordering::Relaxed), 1);
+        #[cfg(feature = "metrics")]
+        assert_eq!(world.structural_metrics().inserts(health), 1);
+    }
+}
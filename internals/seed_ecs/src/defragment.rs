@@ -0,0 +1,140 @@
+use crate::entity::{DanglingPolicy, EntityMapper};
+use crate::World;
+
+impl World {
+    /// Moves the live entities to the lowest indices, so that the masks and the storages only
+    /// span as many indices as there are entities, then releases the memory left unused.
+    ///
+    /// The entities past the first [`Entities::len`](crate::entity::Entities::len) indices move
+    /// into the free indices below with their components, their old handles die and `mapper`
+    /// gets the `(old, new)` pairs for the ids kept outside of the world. The references stored
+    /// in groups, relations, [`World::weak_refs`] and the components registered with
+    /// [`World::register_map_entities`] are rewritten, the change ticks are kept and no observer runs.
+    ///
+    /// It touches every entity and component, meant for loading screens.
+    pub fn defragment(&mut self, mapper: &mut EntityMapper) {
+        let moves = self.entities.defragment();
+        if moves.is_empty() {
+            self.compact_all();
+            return;
+        }
+        // Only the moved entities are known, the others keep their ids.
+        let mut moved = EntityMapper::new(DanglingPolicy::Keep);
+        for (old, new) in moves {
+            let (from, to) = (old.index() as usize, new.index() as usize);
+            for storage in self.storages.iter_mut() {
+                storage.relocate(from, to);
+            }
+            self.partitions.relocate(from, to);
+            self.flush_relocations(&[old]);
+            moved.insert(old, new);
+            mapper.insert(old, new);
+        }
+        for info in self.components.iter() {
+            if let Some(map_entities) = info.map_entities() {
+                map_entities(self.storages.get_mut(info.id()), &mut moved);
+            }
+        }
+        self.groups.map_entities(&mut moved);
+        self.relations.map_entities(&mut moved);
+        self.weak_refs.map_entities(&mut moved);
+        self.compact_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::entity::Entity;
+    use crate::query::Added;
+    use crate::relation::Relation;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Component)]
+    struct Id(u32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(f32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Velocity(f32);
+    #[derive(Component)]
+    struct Enemy;
+    struct Targets;
+
+    impl Relation for Targets {}
+
+    // Everything about the entities, by id instead of by handle.
+    type Content = Vec<(Id, Option<u32>, Option<u32>, bool, Option<Id>, Vec<Id>)>;
+
+    fn content(world: &World) -> Content {
+        let id = |entity: Entity| *world.get_component::<Id>(entity).unwrap();
+        let mut content: Content = world
+            .enities()
+            .iter()
+            .map(|e| {
+                let position = world.get_component::<Position>(e).map(|p| p.0 as u32);
+                let velocity = world.get_component::<Velocity>(e).map(|v| v.0 as u32);
+                let mut targets: Vec<Id> = world.related::<Targets>(e).map(id).collect();
+                targets.sort();
+                (id(e), position, velocity, world.has_component::<Enemy>(e), world.parent(e).map(id), targets)
+            })
+            .collect();
+        content.sort_by_key(|(id, ..)| *id);
+        content
+    }
+
+    #[test]
+    fn fragmented_world_compacts_to_a_prefix() {
+        let mut world = World::new();
+        world.register_group::<(Position, Velocity)>();
+        let entities: Vec<Entity> = world.spawn_batch((0..4000).map(Id));
+        let mut kept = Vec::new();
+        for (i, e) in entities.iter().enumerate() {
+            if i % 37 != 5 {
+                world.despawn_entity(*e);
+                continue;
+            }
+            world.add_component(*e, Position(i as f32));
+            if i % 2 == 1 {
+                world.add_component(*e, Velocity(i as f32 * 2.0));
+            }
+            if i % 3 == 0 {
+                world.add_component(*e, Enemy);
+            }
+            if let Some(parent) = kept.last() {
+                world.set_parent(*e, *parent);
+                world.relate::<Targets>(*parent, *e);
+            }
+            kept.push(*e);
+        }
+        let before = content(&world);
+        let pages = world.storage_stats::<Position>().pages;
+        world.clear_trackers();
+
+        let mut mapper = EntityMapper::new(DanglingPolicy::Error);
+        world.defragment(&mut mapper);
+        world.validate().unwrap();
+        let len = kept.len();
+        assert_eq!(world.enities().iter().map(|e| e.index() as usize).collect::<Vec<_>>(), (0..len).collect::<Vec<_>>());
+        assert_eq!(content(&world), before);
+        assert!(world.storage_stats::<Position>().pages < pages);
+
+        // Every entity past the prefix moved and its old handle is dead.
+        let moved: Vec<Entity> = kept.iter().copied().filter(|e| e.index() as usize >= len).collect();
+        assert_eq!(mapper.len(), moved.len());
+        for old in moved {
+            let new = mapper.get(old).unwrap();
+            assert!(!world.is_alive(old) && world.is_alive(new));
+            assert_eq!(world.get_component::<Id>(new), Some(&Id(old.index())));
+        }
+        assert!(world.is_alive(kept[0]));
+        // Moving is not adding.
+        let added = world.query_filtered::<Entity, Added<Position>>();
+        assert_eq!(added.iter(&world).count(), 0);
+
+        // New entities go after the prefix.
+        assert_eq!(world.spawn_entity().index() as usize, len);
+        let mut again = EntityMapper::default();
+        world.defragment(&mut again);
+        assert!(again.is_empty());
+    }
+}
@@ -0,0 +1,254 @@
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use crate::change_detection::Tick;
+use crate::component::{Component, ComponentId};
+use crate::entity::Entity;
+use crate::hierarchy::{Children, Parent};
+use crate::observer::ObserverKind;
+use crate::storage::{AnyStorage, Storage};
+use crate::World;
+
+/// How [`World::duplicate_entity_with`] treats the source entity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicateOptions {
+    /// Skip the components that have no clone function registered instead of failing.
+    pub skip_non_clone: bool,
+    /// Attach the duplicate to the parent of the source. When false, the duplicate is a root.
+    pub attach_to_parent: bool,
+}
+
+impl Default for DuplicateOptions {
+    fn default() -> Self {
+        Self { skip_non_clone: true, attach_to_parent: true }
+    }
+}
+
+/// Why an entity could not be duplicated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateError {
+    NotAlive(Entity),
+    /// The component has no clone function, see [`World::register_clone`].
+    NotClone(&'static str),
+}
+
+impl fmt::Display for DuplicateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAlive(entity) => write!(f, "Entity {:?} is not alive", entity),
+            Self::NotClone(name) => write!(f, "Component {} has no clone function registered", name),
+        }
+    }
+}
+
+impl Error for DuplicateError {}
+
+fn clone_component<T: Clone + 'static>(storage: &mut dyn AnyStorage, src: usize, dst: usize, tick: Tick) {
+    let storage = storage.as_any_mut().downcast_mut::<Storage<T>>().expect("Storage type mismatch");
+    if let Some(value) = storage.get(src).cloned() {
+        storage.insert(dst, value, tick);
+    }
+}
+
+impl World {
+    /// Lets the world copy `T` when duplicating entities.
+    pub fn register_clone<T: Component + Clone>(&mut self) {
+        let id = self.register_component::<T>();
+        self.components.set_clone(id, clone_component::<T>);
+    }
+
+    /// Spawns a copy of `src` with every component that can be cloned, see [`DuplicateOptions`].
+    ///
+    /// The hierarchy is not copied: the duplicate has no children.
+    pub fn duplicate_entity(&mut self, src: Entity) -> Option<Entity> {
+        self.duplicate_entity_with(src, DuplicateOptions::default()).ok()
+    }
+
+    pub fn duplicate_entity_with(&mut self, src: Entity, options: DuplicateOptions) -> Result<Entity, DuplicateError> {
+        let components = self.cloned_components(&[src], options)?;
+        let dup = self.copy_components(src, &components);
+        self.attach_duplicate(src, dup, options);
+        Ok(dup)
+    }
+
+    /// Duplicates `src` and all of its descendants, the copies are wired to each other.
+    pub fn duplicate_recursive(&mut self, src: Entity) -> Option<Entity> {
+        self.duplicate_recursive_with(src, DuplicateOptions::default()).ok()
+    }
+
+    pub fn duplicate_recursive_with(&mut self, src: Entity, options: DuplicateOptions) -> Result<Entity, DuplicateError> {
+        let mut nodes = vec![src];
+        let mut cursor = 0;
+        while cursor < nodes.len() {
+            let children = self.children(nodes[cursor]).to_vec();
+            nodes.extend(children);
+            cursor += 1;
+        }
+        let components = self.cloned_components(&nodes, options)?;
+        let dups: Vec<Entity> = nodes.iter().map(|node| self.copy_components(*node, &components)).collect();
+
+        let dup_of = |entity: Entity| nodes.iter().position(|node| *node == entity).map(|i| dups[i]);
+        for (node, dup) in nodes.iter().zip(&dups) {
+            let children: Vec<Entity> = self.children(*node).iter().filter_map(|c| dup_of(*c)).collect();
+            for child in &children {
+                self.add_component(*child, Parent(*dup));
+            }
+            if !children.is_empty() {
+                self.add_component(*dup, Children(children));
+            }
+        }
+        self.attach_duplicate(src, dups[0], options);
+        Ok(dups[0])
+    }
+
+    // The components of `entities` to copy, the hierarchy excluded.
+    fn cloned_components(&self, entities: &[Entity], options: DuplicateOptions) -> Result<Vec<ComponentId>, DuplicateError> {
+        let hierarchy = [self.components.id::<Parent>(), self.components.id::<Children>()];
+        let mut components = Vec::new();
+        for entity in entities {
+            if !self.is_alive(*entity) {
+                return Err(DuplicateError::NotAlive(*entity));
+            }
+            for info in self.components.iter() {
+                if hierarchy.contains(&Some(info.id())) || !self.storages.get(info.id()).contains(entity.index() as usize) {
+                    continue;
+                }
+                match info.clone_fn() {
+                    Some(_) => components.push(info.id()),
+                    None if options.skip_non_clone => {}
+                    None => return Err(DuplicateError::NotClone(info.name())),
+                }
+            }
+        }
+        components.sort();
+        components.dedup();
+        Ok(components)
+    }
+
+    fn copy_components(&mut self, src: Entity, components: &[ComponentId]) -> Entity {
+        let dup = *self.spawn_entity();
+        for id in components {
+            // Only components with a clone function were kept.
+            let clone = self.components.info(*id).and_then(|info| info.clone_fn()).unwrap();
+            clone(self.storages.get_mut(*id), src.index() as usize, dup.index() as usize, self.change_tick);
+            self.metrics.inserted(*id, 1);
+        }
+        for id in components {
+            self.join_groups(dup, *id);
+        }
+        self.flush_relocations(&[]);
+        for id in components {
+            self.trigger_component(ObserverKind::Add, *id, dup);
+        }
+        dup
+    }
+
+    fn attach_duplicate(&mut self, src: Entity, dup: Entity, options: DuplicateOptions) {
+        if let Some(parent) = self.parent(src).filter(|_| options.attach_to_parent) {
+            self.set_parent(dup, parent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Health(u32);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Inventory(Vec<&'static str>);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Name(String);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Enemy;
+    #[derive(Debug, PartialEq, Component)]
+    struct Unique(u32);
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register_clone::<Health>();
+        world.register_clone::<Inventory>();
+        world.register_clone::<Name>();
+        world.register_clone::<Enemy>();
+        world
+    }
+
+    #[test]
+    fn duplicate_is_a_deep_copy() {
+        let mut world = world();
+        let orc = *world.spawn_entity();
+        world.add_component(orc, Health(10));
+        world.add_component(orc, Inventory(vec!["axe"]));
+        world.add_component(orc, Name("orc".into()));
+        world.add_component(orc, Enemy);
+        world.add_component(orc, Unique(1));
+
+        let copy = world.duplicate_entity(orc).unwrap();
+        assert_eq!(world.get_component::<Health>(copy), Some(&Health(10)));
+        assert_eq!(world.get_component::<Enemy>(copy), Some(&Enemy));
+        assert_eq!(world.get_component::<Unique>(copy), None);
+        world.get_component_mut::<Inventory>(copy).unwrap().0.push("shield");
+        world.get_component_mut::<Name>(copy).unwrap().0.push_str(" copy");
+        assert_eq!(world.get_component::<Inventory>(orc), Some(&Inventory(vec!["axe"])));
+        assert_eq!(world.get_component::<Name>(orc), Some(&Name("orc".into())));
+
+        let strict = DuplicateOptions { skip_non_clone: false, ..DuplicateOptions::default() };
+        let before = world.enities().len();
+        assert_eq!(world.duplicate_entity_with(orc, strict), Err(DuplicateError::NotClone(std::any::type_name::<Unique>())));
+        assert_eq!(world.enities().len(), before);
+    }
+
+    #[test]
+    fn duplicate_attaches_to_parent_on_demand() {
+        let mut world = world();
+        let parent = *world.spawn_entity();
+        let child = *world.spawn_entity();
+        world.set_parent(child, parent);
+
+        let attached = world.duplicate_entity(child).unwrap();
+        let detached = DuplicateOptions { attach_to_parent: false, ..DuplicateOptions::default() };
+        let root = world.duplicate_entity_with(child, detached).unwrap();
+        assert_eq!(world.children(parent), &[child, attached]);
+        assert_eq!(world.parent(root), None);
+        // The children of the source are not shared with a plain duplicate.
+        let copy = world.duplicate_entity(parent).unwrap();
+        assert!(world.children(copy).is_empty());
+    }
+
+    #[test]
+    fn duplicate_tree() {
+        let mut world = world();
+        let holder = *world.spawn_entity();
+        let root = *world.spawn_entity();
+        let left = *world.spawn_entity();
+        let right = *world.spawn_entity();
+        let leaf = *world.spawn_entity();
+        for (i, e) in [root, left, right, leaf].into_iter().enumerate() {
+            world.add_component(e, Health(i as u32));
+        }
+        world.set_parent(root, holder);
+        world.set_parent(left, root);
+        world.set_parent(right, root);
+        world.set_parent(leaf, left);
+
+        let copy = world.duplicate_recursive(root).unwrap();
+        assert_eq!(world.parent(copy), Some(holder));
+        assert_eq!(world.children(holder), &[root, copy]);
+        let kids = world.children(copy).to_vec();
+        assert_eq!(kids.len(), 2);
+        assert!(!kids.contains(&left) && !kids.contains(&right));
+        assert_eq!(world.get_component::<Health>(kids[0]), Some(&Health(1)));
+        assert_eq!(world.get_component::<Health>(kids[1]), Some(&Health(2)));
+        assert!(kids.iter().all(|kid| world.parent(*kid) == Some(copy)));
+        let leaf_copy = world.children(kids[0])[0];
+        assert_ne!(leaf_copy, leaf);
+        assert_eq!(world.parent(leaf_copy), Some(kids[0]));
+        assert_eq!(world.get_component::<Health>(leaf_copy), Some(&Health(3)));
+        // The source tree is untouched.
+        assert_eq!(world.children(root), &[left, right]);
+        assert_eq!(world.children(left), &[leaf]);
+        world.validate().unwrap();
+    }
+}
@@ -0,0 +1,376 @@
+//! Components known only at runtime, accessed through their [`ComponentId`] and raw pointers.
+
+use alloc::vec::Vec;
+use core::ptr::NonNull;
+
+use crate::component::{ComponentDescriptor, ComponentId};
+use crate::entity::Entity;
+use crate::observer::ObserverKind;
+use crate::query::Disabled;
+use crate::utils::BMask;
+use crate::{EcsError, World};
+
+impl World {
+    /// Registers a component described at runtime, its values are stored as untyped bytes.
+    pub fn register_component_with_descriptor(&mut self, descriptor: ComponentDescriptor) -> ComponentId {
+        let id = self.components.register_descriptor(&descriptor);
+        self.storages.push_blob(id, descriptor.layout(), descriptor.drop_fn());
+        id
+    }
+
+    /// Moves the value behind `value` into the component `id` of the entity, returns true if it
+    /// replaced a previous value. Works for every component, registered by type or by descriptor.
+    ///
+    /// # Safety
+    ///
+    /// `value` must point to a valid value of the component, it may be unaligned. The value is
+    /// owned by the world afterwards and must not be used or dropped by the caller.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive or if the world has no component `id`.
+    pub unsafe fn insert_by_id(&mut self, entity: Entity, id: ComponentId, value: NonNull<u8>) -> bool {
+        self.try_insert_by_id(entity, id, value).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Same as [`World::insert_by_id`] but fails instead of panicking.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`World::insert_by_id`]. On failure the value is left to the caller.
+    pub unsafe fn try_insert_by_id(&mut self, entity: Entity, id: ComponentId, value: NonNull<u8>) -> Result<bool, EcsError> {
+        self.entities.check_alive(entity)?;
+        if self.components.info(id).is_none() {
+            return Err(EcsError::UnknownComponent(id));
+        }
+        let replaced = self.storages.get_mut(id).insert_ptr(entity.index() as usize, value.as_ptr(), self.change_tick);
+        if !replaced {
+            self.metrics.inserted(id, 1);
+            self.join_groups(entity, id);
+            self.flush_relocations(&[]);
+            self.trigger_component(ObserverKind::Add, id, entity);
+        }
+        Ok(replaced)
+    }
+
+    /// A pointer to the component `id` of the entity, `None` if the entity or the component is
+    /// missing.
+    pub fn get_by_id(&self, entity: Entity, id: ComponentId) -> Option<NonNull<u8>> {
+        if !self.entities.is_alive(entity) || self.components.info(id).is_none() {
+            return None;
+        }
+        self.storages.get(id).get_ptr(entity.index() as usize)
+    }
+
+    /// A pointer to the component `id` of the entity that can be written through.
+    pub fn get_mut_by_id(&mut self, entity: Entity, id: ComponentId) -> Option<NonNull<u8>> {
+        if !self.entities.is_alive(entity) || self.components.info(id).is_none() {
+            return None;
+        }
+        self.storages.get_mut(id).get_mut_ptr(entity.index() as usize)
+    }
+
+    /// Drops the component `id` of the entity, returns false if it didn't have it.
+    pub fn remove_by_id(&mut self, entity: Entity, id: ComponentId) -> bool {
+        if !self.entities.is_alive(entity) || self.components.info(id).is_none() {
+            return false;
+        }
+        if !self.storages.get(id).contains(entity.index() as usize) {
+            return false;
+        }
+        self.trigger_component(ObserverKind::Remove, id, entity);
+        self.leave_groups(entity, id);
+        let removed = self.storages.get_mut(id).remove(entity.index() as usize);
+        if removed {
+            self.removed.push(id, entity);
+            self.replication.removed(id, entity);
+            self.metrics.removed(id, 1);
+            self.flush_relocations(&[]);
+        }
+        removed
+    }
+
+    /// The entities having every component of `with` and none of `without`, in ascending index
+    /// order, for the filters picked at runtime like an editor search. Same as the typed filters,
+    /// an id the world never registered matches nothing in `with` and is ignored in `without`,
+    /// and the [`Disabled`] entities are skipped unless one of the lists has it.
+    pub fn query_dynamic<'w>(
+        &'w self,
+        with: &[ComponentId],
+        without: &[ComponentId],
+    ) -> impl Iterator<Item = Entity> + 'w {
+        let query = DynamicQuery::new(with.to_vec(), without.to_vec());
+        DynamicIter::new(self, &query)
+    }
+}
+
+/// Builds a [`DynamicQuery`] from component ids, the ids of the components found by name with
+/// [`Components::get_id_by_name`](crate::component::Components::get_id_by_name) for instance.
+#[derive(Debug, Clone, Default)]
+pub struct DynamicQueryBuilder {
+    with: Vec<ComponentId>,
+    without: Vec<ComponentId>,
+}
+
+impl DynamicQueryBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches the entities that have the component, its pointer is fetched.
+    pub fn with(mut self, id: ComponentId) -> Self {
+        if !self.with.contains(&id) {
+            self.with.push(id);
+        }
+        self
+    }
+
+    /// Matches the entities that don't have the component.
+    pub fn without(mut self, id: ComponentId) -> Self {
+        self.without.push(id);
+        self
+    }
+
+    pub fn build(self) -> DynamicQuery {
+        DynamicQuery::new(self.with, self.without)
+    }
+}
+
+/// A query on components known by id, see [`World::query_dynamic`] for what it matches. It can
+/// be kept and run on any world.
+#[derive(Debug, Clone)]
+pub struct DynamicQuery {
+    // In the order they were given, the order of the fetched pointers.
+    with: Vec<ComponentId>,
+    required: Vec<ComponentId>,
+    excluded: Vec<ComponentId>,
+}
+
+impl DynamicQuery {
+    fn new(with: Vec<ComponentId>, mut excluded: Vec<ComponentId>) -> Self {
+        let mut required = with.clone();
+        required.sort();
+        required.dedup();
+        excluded.sort();
+        excluded.dedup();
+        Self {
+            with,
+            required,
+            excluded,
+        }
+    }
+
+    /// The components fetched, in the order of [`DynamicQueryBuilder::with`].
+    pub fn components(&self) -> &[ComponentId] {
+        &self.with
+    }
+
+    pub fn iter<'w>(&self, world: &'w World) -> impl Iterator<Item = Entity> + 'w {
+        DynamicIter::new(world, self)
+    }
+
+    /// The matched entities with a read only pointer to each of their components, in the order
+    /// of [`DynamicQuery::components`].
+    pub fn iter_ptrs<'w>(&'w self, world: &'w World) -> impl Iterator<Item = (Entity, Vec<NonNull<u8>>)> + 'w {
+        DynamicIter::new(world, self).map(move |entity| {
+            let index = entity.index() as usize;
+            // The entity has every component, it matched.
+            let ptrs = self.with.iter().map(|id| world.storages.get(*id).get_ptr(index).unwrap());
+            (entity, ptrs.collect())
+        })
+    }
+}
+
+struct DynamicIter<'w> {
+    world: &'w World,
+    required: Vec<&'w BMask>,
+    excluded: Vec<&'w BMask>,
+    // The smallest of the required masks, its empty words are skipped.
+    driver: Option<&'w BMask>,
+    next_word: usize,
+    word_idx: usize,
+    bits: u32,
+}
+
+impl<'w> DynamicIter<'w> {
+    fn new(world: &'w World, query: &DynamicQuery) -> Self {
+        let mask = |id: &ComponentId| (id.index() < world.storages.len()).then(|| world.storages.get(*id).mask());
+        let mut excluded: Vec<&BMask> = query.excluded.iter().filter_map(mask).collect();
+        if let Some(disabled) = world.components.id::<Disabled>() {
+            if !query.required.contains(&disabled) && !query.excluded.contains(&disabled) {
+                excluded.push(world.storages.get(disabled).mask());
+            }
+        }
+        let required: Option<Vec<&BMask>> = query.required.iter().map(mask).collect();
+        let required = required.unwrap_or_default();
+        let driver = match required.len() == query.required.len() {
+            true => required.iter().copied().chain([world.entities.mask()]).min_by_key(|mask| mask.word_count()),
+            // An unregistered component is required.
+            false => None,
+        };
+        Self {
+            world,
+            required,
+            excluded,
+            driver,
+            next_word: 0,
+            word_idx: 0,
+            bits: 0,
+        }
+    }
+}
+
+impl Iterator for DynamicIter<'_> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.bits != 0 {
+                let bit = self.bits.trailing_zeros() as usize;
+                self.bits &= self.bits - 1;
+                return self.world.entities.get(((self.word_idx << 5) | bit) as u32);
+            }
+            let word_idx = self.driver?.next_word(self.next_word)?;
+            let mut word = self.world.entities.mask().word(word_idx);
+            for mask in &self.required {
+                word &= mask.word(word_idx);
+            }
+            for mask in &self.excluded {
+                word &= !mask.word(word_idx);
+            }
+            self.word_idx = word_idx;
+            self.next_word = word_idx + 1;
+            self.bits = word;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::alloc::Layout;
+    use core::ffi::c_void;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::component::Component;
+
+    static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+    unsafe extern "C" fn count_drop(value: *mut c_void) {
+        DROPPED.fetch_add(*(value as *const u64) as usize, Ordering::Relaxed);
+    }
+
+    #[test]
+    fn descriptor_components() {
+        let mut world = World::new();
+        let descriptor = ComponentDescriptor::new("Score", Layout::new::<u64>()).with_drop(count_drop);
+        let score = world.register_component_with_descriptor(descriptor);
+        assert_eq!(world.components().info(score).unwrap().type_id(), None);
+        let entities: Vec<Entity> = (0..3).map(|_| *world.spawn_entity()).collect();
+        for (i, entity) in entities.iter().enumerate() {
+            let mut value = i as u64 + 1;
+            assert!(!unsafe { world.insert_by_id(*entity, score, NonNull::from(&mut value).cast()) });
+        }
+        let read = |world: &World, e| world.get_by_id(e, score).map(|p| unsafe { *p.cast::<u64>().as_ptr() });
+        assert_eq!(read(&world, entities[2]), Some(3));
+
+        unsafe { *world.get_mut_by_id(entities[0], score).unwrap().cast::<u64>().as_ptr() = 10 };
+        let mut value = 100u64;
+        assert!(unsafe { world.insert_by_id(entities[0], score, NonNull::from(&mut value).cast()) });
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 10);
+        world.despawn_entity(entities[1]);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 12);
+        assert!(world.remove_by_id(entities[2], score));
+        assert!(!world.remove_by_id(entities[2], score));
+        assert_eq!(read(&world, entities[2]), None);
+        assert_eq!(entities.iter().filter(|e| world.get_by_id(**e, score).is_some()).count(), 1);
+        drop(world);
+        assert_eq!(DROPPED.load(Ordering::Relaxed), 115);
+    }
+
+    #[test]
+    fn typed_components_by_id() {
+        let mut world = World::new();
+        let id = world.register_component::<Label>();
+        let e = *world.spawn_entity();
+        let value = core::mem::ManuallyDrop::new(Label(String::from("typed")));
+        unsafe { world.insert_by_id(e, id, NonNull::from(&*value).cast()) };
+        assert_eq!(world.get_component::<Label>(e).map(|label| label.0.as_str()), Some("typed"));
+        let ptr = world.get_by_id(e, id).unwrap();
+        assert_eq!(unsafe { ptr.cast::<Label>().as_ref() }.0, "typed");
+        assert_eq!(world.get_by_id(e, ComponentId::new(7)), None);
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(i32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Velocity(i32);
+    #[derive(Component)]
+    struct Frozen;
+    #[derive(Component)]
+    struct Label(String);
+
+    fn spawn_mix(world: &mut World) -> Vec<Entity> {
+        let mut entities = world.spawn_batch_iter((0..40).map(|i| (Position(i), Velocity(-i))));
+        entities.extend(world.spawn_batch((40..60).map(Position)));
+        entities.extend(world.spawn_batch((60..70).map(Velocity)));
+        for entity in entities.iter().step_by(3) {
+            world.add_component(*entity, Frozen);
+        }
+        world.entity_mut(entities[1]).set_enabled(false);
+        world.despawn_batch(&entities[10..15]);
+        entities
+    }
+
+    #[test]
+    fn dynamic_queries_match_the_typed_ones() {
+        use crate::query::{With, Without};
+
+        let mut world = World::new();
+        spawn_mix(&mut world);
+        let [position, velocity, frozen, disabled] = ["Position", "Velocity", "Frozen", "Disabled"]
+            .map(|name| world.components().get_id_by_name(name).unwrap());
+
+        let typed = world.query_filtered::<Entity, (With<Position>, With<Velocity>, Without<Frozen>)>();
+        let dynamic: Vec<Entity> = world.query_dynamic(&[position, velocity], &[frozen]).collect();
+        assert_eq!(dynamic, typed.query(&world).iter().collect::<Vec<_>>());
+        assert_eq!(dynamic.len(), 21);
+
+        let typed = world.query_filtered::<Entity, (With<Velocity>, Without<Position>)>();
+        let dynamic: Vec<Entity> = world.query_dynamic(&[velocity], &[position]).collect();
+        assert_eq!(dynamic, typed.query(&world).iter().collect::<Vec<_>>());
+
+        let typed = world.query_filtered::<Entity, With<Disabled>>();
+        let dynamic: Vec<Entity> = world.query_dynamic(&[disabled], &[]).collect();
+        assert_eq!(dynamic, typed.query(&world).iter().collect::<Vec<_>>());
+        assert_eq!(world.query_dynamic(&[], &[]).count(), world.query::<Entity>().query(&world).iter().count());
+
+        // Ids the world never registered.
+        let unknown = ComponentId::new(world.components().len() + 3);
+        assert_eq!(world.query_dynamic(&[position, unknown], &[]).count(), 0);
+        let all: Vec<Entity> = world.query_dynamic(&[position], &[]).collect();
+        assert_eq!(world.query_dynamic(&[position], &[unknown]).collect::<Vec<_>>(), all);
+    }
+
+    #[test]
+    fn built_queries_fetch_the_components() {
+        let mut world = World::new();
+        spawn_mix(&mut world);
+        let components = world.components();
+        let position = components.get_id_by_name("Position").unwrap();
+        let velocity = components.get_id_by_name("Velocity").unwrap();
+        let frozen = components.get_id_by_name("Frozen").unwrap();
+        let query = DynamicQueryBuilder::new().with(velocity).with(position).without(frozen).build();
+        assert_eq!(query.components(), [velocity, position]);
+        let mut count = 0;
+        for (entity, ptrs) in query.iter_ptrs(&world) {
+            let velocity = unsafe { ptrs[0].cast::<Velocity>().as_ref() };
+            let position = unsafe { ptrs[1].cast::<Position>().as_ref() };
+            assert_eq!(world.get_component::<Velocity>(entity), Some(velocity));
+            assert_eq!(world.get_component::<Position>(entity), Some(position));
+            assert_eq!(velocity.0, -position.0);
+            count += 1;
+        }
+        assert_eq!(count, query.iter(&world).count());
+        assert_eq!(count, 21);
+    }
+}
@@ -0,0 +1,117 @@
+use alloc::vec::Vec;
+
+use crate::utils::HashMap;
+
+use super::Entity;
+
+/// What to do with a reference to an entity the mapper doesn't know about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DanglingPolicy {
+    /// Abort the operation and report the dangling references.
+    #[default]
+    Error,
+    /// Replace the reference with [`Entity::PLACEHOLDER`], which is never alive.
+    MapToDead,
+    /// Leave the reference untouched.
+    Keep,
+}
+
+/// Maps the entities of a source world to the entities of a destination world.
+///
+/// The destination entities are allocated when the source entities are first seen, by
+/// [`World::merge_from`](crate::World::merge_from) for instance, and the references stored in
+/// components are then rewritten through [`MapEntities`].
+#[derive(Debug, Default)]
+pub struct EntityMapper {
+    map: HashMap<Entity, Entity>,
+    policy: DanglingPolicy,
+    dangling: Vec<Entity>,
+}
+
+impl EntityMapper {
+    pub fn new(policy: DanglingPolicy) -> Self {
+        Self {
+            map: HashMap::new(),
+            policy,
+            dangling: Vec::new(),
+        }
+    }
+
+    pub fn policy(&self) -> DanglingPolicy {
+        self.policy
+    }
+
+    /// Returns the entity `source` is mapped to, applying the dangling policy if it is unknown.
+    pub fn map(&mut self, source: Entity) -> Entity {
+        if let Some(target) = self.map.get(&source) {
+            return *target;
+        }
+        match self.policy {
+            DanglingPolicy::Error => {
+                self.dangling.push(source);
+                source
+            }
+            DanglingPolicy::MapToDead => Entity::PLACEHOLDER,
+            DanglingPolicy::Keep => source,
+        }
+    }
+
+    pub fn get(&self, source: Entity) -> Option<Entity> {
+        self.map.get(&source).copied()
+    }
+
+    /// Maps `source` to `target`, returns the previous target if any.
+    pub fn insert(&mut self, source: Entity, target: Entity) -> Option<Entity> {
+        self.map.insert(source, target)
+    }
+
+    pub fn remove(&mut self, source: Entity) -> Option<Entity> {
+        self.map.remove(&source)
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// Iterates over the `(source, target)` pairs.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.map.iter().map(|(source, target)| (*source, *target))
+    }
+
+    /// References seen since the last call that were unknown under [`DanglingPolicy::Error`].
+    pub fn take_dangling(&mut self) -> Vec<Entity> {
+        core::mem::take(&mut self.dangling)
+    }
+}
+
+/// Components storing [`Entity`] values implement this to have them rewritten when they are
+/// moved to another world.
+pub trait MapEntities {
+    fn map_entities(&mut self, mapper: &mut EntityMapper);
+}
+
+impl MapEntities for Entity {
+    fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        *self = mapper.map(*self);
+    }
+}
+
+impl<T: MapEntities> MapEntities for Option<T> {
+    fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        if let Some(value) = self {
+            value.map_entities(mapper);
+        }
+    }
+}
+
+impl<T: MapEntities> MapEntities for Vec<T> {
+    fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        for value in self {
+            value.map_entities(mapper);
+        }
+    }
+}
@@ -0,0 +1,678 @@
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::cmp::Ordering;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::mem::size_of;
+use core::num::NonZeroU32;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU32, Ordering as AtomicOrdering};
+
+use crate::utils::{BMask, BVec, CAPACITY};
+use crate::EcsError;
+
+mod map_entities;
+
+pub use map_entities::*;
+
+/// Tells apart the worlds of a program, every [`World`](crate::World) gets a new one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WorldId(NonZeroU32);
+
+impl WorldId {
+    fn new() -> Self {
+        static NEXT: AtomicU32 = AtomicU32::new(1);
+        let id = NEXT.fetch_add(1, AtomicOrdering::Relaxed);
+        Self(NonZeroU32::new(id).expect("Too many worlds were created"))
+    }
+}
+
+impl fmt::Display for WorldId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A handle to an entity of a [`World`](crate::World).
+///
+/// The generation allows to tell apart two entities that used the same index one after the other.
+/// It starts at 1 so that `Option<Entity>` is as small as `Entity`.
+///
+/// In debug builds, or with the `strict-checks` feature, the entity also remembers the world that
+/// spawned it and the worlds reject the entities of other worlds with [`EcsError::WrongWorld`].
+/// The world is not part of the comparisons nor of [`Entity::to_bits`].
+#[derive(Clone, Copy)]
+pub struct Entity {
+    index: u32,
+    generation: NonZeroU32,
+    // `None` for the entities not spawned by a world, like the ones made from bits.
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    world: Option<WorldId>,
+}
+
+const _: () = assert!(size_of::<Option<Entity>>() == size_of::<Entity>());
+
+impl Entity {
+    /// An entity that is never alive, its index is past the capacity of any world.
+    pub const PLACEHOLDER: Entity = Entity {
+        index: u32::MAX,
+        generation: NonZeroU32::MIN,
+        #[cfg(any(debug_assertions, feature = "strict-checks"))]
+        world: None,
+    };
+
+    /// # Panics
+    ///
+    /// Panics if the generation is 0.
+    pub(crate) fn new(index: u32, generation: u32) -> Self {
+        Self {
+            index,
+            generation: NonZeroU32::new(generation).expect("Entity generations start at 1"),
+            #[cfg(any(debug_assertions, feature = "strict-checks"))]
+            world: None,
+        }
+    }
+
+    #[allow(unused_mut)]
+    fn in_world(mut self, world: WorldId) -> Self {
+        #[cfg(any(debug_assertions, feature = "strict-checks"))]
+        {
+            self.world = Some(world);
+        }
+        self
+    }
+
+    /// The world that spawned the entity, always `None` without the checks.
+    pub fn world(&self) -> Option<WorldId> {
+        #[cfg(any(debug_assertions, feature = "strict-checks"))]
+        return self.world;
+        #[cfg(not(any(debug_assertions, feature = "strict-checks")))]
+        None
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    pub fn generation(&self) -> u32 {
+        self.generation.get()
+    }
+
+    /// Packs the entity in a `u64`, the generation in the high bits.
+    pub fn to_bits(self) -> u64 {
+        (self.generation.get() as u64) << 32 | self.index as u64
+    }
+
+    /// Unpacks an entity packed by [`Entity::to_bits`], `None` if the generation is 0.
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        Some(Self {
+            index: bits as u32,
+            generation: NonZeroU32::new((bits >> 32) as u32)?,
+            #[cfg(any(debug_assertions, feature = "strict-checks"))]
+            world: None,
+        })
+    }
+
+    /// A handle to keep for later, telling that the entity may be gone by then.
+    pub fn downgrade(self) -> EntityWeak {
+        EntityWeak(self)
+    }
+}
+
+/// A handle to an entity that may have been despawned since, only usable through
+/// [`EntityWeak::upgrade`]. Made by [`Entity::downgrade`], with the same bits.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityWeak(Entity);
+
+impl EntityWeak {
+    /// The entity if it is still alive in `world`, the same generation and not another entity
+    /// reusing its index.
+    pub fn upgrade(&self, world: &crate::World) -> Option<Entity> {
+        world.is_alive(self.0).then_some(self.0)
+    }
+
+    // The entity without checking it, for the world.
+    pub(crate) fn entity(self) -> Entity {
+        self.0
+    }
+
+    pub fn to_bits(self) -> u64 {
+        self.0.to_bits()
+    }
+
+    /// See [`Entity::from_bits`].
+    pub fn from_bits(bits: u64) -> Option<Self> {
+        Entity::from_bits(bits).map(Self)
+    }
+}
+
+impl From<Entity> for EntityWeak {
+    fn from(entity: Entity) -> Self {
+        entity.downgrade()
+    }
+}
+
+impl fmt::Debug for EntityWeak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Weak({})", self.0)
+    }
+}
+
+impl MapEntities for EntityWeak {
+    fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        self.0.map_entities(mapper);
+    }
+}
+
+/// Entities are keys of a [`SparseMap`](crate::utils::SparseMap) by their bits.
+impl From<Entity> for u64 {
+    fn from(entity: Entity) -> u64 {
+        entity.to_bits()
+    }
+}
+
+impl PartialEq for Entity {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bits() == other.to_bits()
+    }
+}
+
+impl Eq for Entity {}
+
+impl Hash for Entity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+        self.generation.hash(state);
+    }
+}
+
+impl PartialOrd for Entity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.index, self.generation).cmp(&(other.index, other.generation))
+    }
+}
+
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Entity({}v{})", self.index, self.generation)
+    }
+}
+
+impl fmt::Debug for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+/// Why [`Entities::spawn_at`] could not claim a slot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnAtError {
+    /// An entity is already alive at this index.
+    Occupied(Entity),
+    /// The generation is older than one already used at this index, it would revive stale handles.
+    StaleGeneration { index: u32, generation: u32, next: u32 },
+    /// The index is past the last addressable slot.
+    OutOfRange(u32),
+}
+
+impl fmt::Display for SpawnAtError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Occupied(entity) => write!(f, "Slot {} is already used by {:?}", entity.index, entity),
+            Self::StaleGeneration { index, generation, next } => write!(
+                f,
+                "Generation {} is stale for slot {}, the next generation is {}",
+                generation, index, next
+            ),
+            Self::OutOfRange(index) => write!(f, "Slot {} exceeds the entity capacity ({})", index, CAPACITY),
+        }
+    }
+}
+
+impl Error for SpawnAtError {}
+
+/// Which freed index a spawn reuses, indices are only allocated fresh once none is free.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexReuse {
+    /// The most recently freed index, keeps the live indices packed.
+    #[default]
+    Lifo,
+    /// The index freed the longest ago, stale handles to an index stay around longer before the
+    /// index is handed out again.
+    Fifo,
+}
+
+/// How [`Entities`] allocates the slots of the new indices, see
+/// [`World::set_growth_policy`](crate::World::set_growth_policy).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GrowthPolicy {
+    /// The slots are allocated this many at a time when a spawn needs a new index, rounded up
+    /// to the pages of 1024 slots. With 0 the spawns allocate what they need, a page at a time
+    /// and the generations doubling.
+    pub entity_chunk: usize,
+    /// Writes the memory of the slots once as soon as it is allocated, so that the system maps
+    /// it then instead of on the first spawns.
+    pub pre_touch: bool,
+}
+
+/// Keeps track of the living entities.
+pub struct Entities {
+    entities: BVec<Entity>,
+    // Generation to give to the next entity spawned at each index, never 0.
+    generations: Vec<u32>,
+    // The indices of despawned entities, in the order they were freed.
+    free: VecDeque<u32>,
+    reuse: IndexReuse,
+    growth: GrowthPolicy,
+    // The indices below it have their slots allocated by `Entities::grow_to`.
+    grown: usize,
+    world: WorldId,
+}
+
+impl Entities {
+    pub fn init() -> Self {
+        Self {
+            entities: BVec::new(),
+            generations: Vec::new(),
+            free: VecDeque::new(),
+            reuse: IndexReuse::default(),
+            growth: GrowthPolicy::default(),
+            grown: 0,
+            world: WorldId::new(),
+        }
+    }
+
+    /// The id stamped into the spawned entities.
+    pub fn world_id(&self) -> WorldId {
+        self.world
+    }
+
+    pub fn index_reuse(&self) -> IndexReuse {
+        self.reuse
+    }
+
+    /// Changes the order freed indices are reused in, the indices already free included.
+    pub fn set_index_reuse(&mut self, reuse: IndexReuse) {
+        self.reuse = reuse;
+    }
+
+    /// Allocates what spawning `additional` more entities needs, so that the spawns don't allocate.
+    pub fn reserve(&mut self, additional: usize) {
+        let len = self.index_end() + additional.saturating_sub(self.free.len());
+        let len = len.min(CAPACITY);
+        self.generations.reserve(len.saturating_sub(self.generations.len()));
+        self.entities.reserve(len);
+    }
+
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.growth
+    }
+
+    /// Changes how the next spawns allocate, the slots already allocated stay.
+    pub fn set_growth_policy(&mut self, growth: GrowthPolicy) {
+        self.growth = growth;
+    }
+
+    /// Allocates the slots of the indices below `len`, written once if the growth policy
+    /// pre-touches. The spawns up to `len` indices don't allocate afterwards.
+    pub fn grow_to(&mut self, len: usize) {
+        let len = len.min(CAPACITY);
+        if self.generations.capacity() < len {
+            self.generations.reserve_exact(len - self.generations.len());
+        }
+        match self.growth.pre_touch {
+            true => {
+                for generation in self.generations.spare_capacity_mut() {
+                    generation.write(1);
+                }
+                self.entities.reserve_touched(len);
+            }
+            false => self.entities.reserve(len),
+        }
+        self.grown = self.grown.max(len);
+    }
+
+    /// Number of entity slots allocated.
+    pub fn capacity(&self) -> usize {
+        self.entities.capacity()
+    }
+
+    /// One past the highest index ever handed out.
+    pub(crate) fn index_end(&self) -> usize {
+        self.generations.len()
+    }
+
+    /// The number of entities alive at once at most, the indices the storages address.
+    pub fn max_entities(&self) -> usize {
+        BVec::<Entity>::MAX_LEN
+    }
+
+    /// How many more entities can be spawned before [`Entities::try_spawn_entity`] fails.
+    pub fn headroom(&self) -> usize {
+        self.max_entities() - self.len()
+    }
+
+    /// Number of freed indices waiting to be reused.
+    pub fn free_count(&self) -> usize {
+        self.free.len()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if every index is used, see [`Entities::try_spawn_entity`].
+    pub fn spawn_entity(&mut self) -> &Entity {
+        self.try_spawn_entity().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Spawns an entity, reusing a freed index first. Fails with
+    /// [`EcsError::EntitiesExhausted`] once all the [`max_entities`](Entities::max_entities)
+    /// indices are alive.
+    pub fn try_spawn_entity(&mut self) -> Result<&Entity, EcsError> {
+        let reused = match self.reuse {
+            IndexReuse::Lifo => self.free.pop_back(),
+            IndexReuse::Fifo => self.free.pop_front(),
+        };
+        let (index, slot) = match reused {
+            Some(index) => {
+                let index = index as usize;
+                assert!(self.entities.insert(index, Entity::PLACEHOLDER).is_none(), "Free index {} is used", index);
+                (index, self.entities.get_mut(index).unwrap())
+            }
+            // The first empty slot is past the end, or a gap left by `spawn_at`.
+            None => {
+                let index = self.entities.first_empty();
+                let chunk = self.growth.entity_chunk;
+                if chunk > 0 && index >= self.grown && index < CAPACITY {
+                    self.grow_to((index / chunk + 1) * chunk);
+                }
+                self.entities.insert_first_empty(Entity::PLACEHOLDER).map_err(|_| EcsError::EntitiesExhausted {
+                    capacity: BVec::<Entity>::MAX_LEN,
+                })?
+            }
+        };
+        if index >= self.generations.len() {
+            self.generations.resize(index + 1, 1);
+        }
+        *slot = Entity::new(index as u32, self.generations[index]).in_world(self.world);
+        Ok(slot)
+    }
+
+    /// Spawns an entity at exactly `index` with exactly `generation`.
+    ///
+    /// This is meant for ids dictated by someone else, a server for instance. Normal spawns
+    /// will never hand out a slot claimed this way while it is alive.
+    pub fn spawn_at(&mut self, index: u32, generation: u32) -> Result<Entity, SpawnAtError> {
+        let slot = index as usize;
+        if slot >= CAPACITY {
+            return Err(SpawnAtError::OutOfRange(index));
+        }
+        if let Some(entity) = self.entities.get(slot) {
+            return Err(SpawnAtError::Occupied(*entity));
+        }
+        if slot >= self.generations.len() {
+            self.generations.resize(slot + 1, 1);
+        }
+        let next = self.generations[slot];
+        if generation < next {
+            return Err(SpawnAtError::StaleGeneration { index, generation, next });
+        }
+        if let Some(position) = self.free.iter().position(|free| *free == index) {
+            self.free.remove(position);
+        }
+        self.generations[slot] = generation;
+        let entity = Entity::new(index, generation).in_world(self.world);
+        self.entities.insert(slot, entity);
+        Ok(entity)
+    }
+
+    /// Kills the entity, returns false if it was not alive.
+    pub fn despawn_entity(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        let index = entity.index as usize;
+        self.entities.remove(index);
+        self.generations[index] = self.generations[index].checked_add(1).unwrap_or(1);
+        self.free.push_back(entity.index);
+        true
+    }
+
+    /// False for the entities of other worlds, see [`Entities::check_alive`].
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.check_alive(entity).is_ok()
+    }
+
+    /// Fails with [`EcsError::WrongWorld`] if another world spawned the entity, with
+    /// [`EcsError::EntityNotAlive`] if it is not alive.
+    pub fn check_alive(&self, entity: Entity) -> Result<(), EcsError> {
+        if let Some(world) = entity.world().filter(|world| *world != self.world) {
+            return Err(EcsError::WrongWorld {
+                entity,
+                world,
+                expected: self.world,
+            });
+        }
+        match self.entities.get(entity.index as usize) == Some(&entity) {
+            true => Ok(()),
+            false => Err(EcsError::EntityNotAlive(entity)),
+        }
+    }
+
+    /// Returns the living entity at `index` if any.
+    pub fn get(&self, index: u32) -> Option<Entity> {
+        self.entities.get(index as usize).copied()
+    }
+
+    /// The live entities at the indices of `range`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`BVec::slice`], every index must be alive.
+    pub(crate) unsafe fn slice(&self, range: Range<usize>) -> &[Entity] {
+        self.entities.slice(range)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter().map(|(_, entity)| *entity)
+    }
+
+    pub(crate) fn mask(&self) -> &BMask {
+        self.entities.mask()
+    }
+
+    /// Moves the entities past the first `len()` indices to the free indices below, lowest first,
+    /// so that the live indices are `0..len()`. A moved entity gets the generation its new index
+    /// hands out and its old handle dies. Returns the `(old, new)` pairs in index order.
+    pub(crate) fn defragment(&mut self) -> Vec<(Entity, Entity)> {
+        let len = self.len();
+        let movers: Vec<Entity> = self.iter().filter(|entity| entity.index as usize >= len).collect();
+        let holes: Vec<usize> = (0..len).filter(|index| !self.entities.contains(*index)).collect();
+        let mut moves = Vec::with_capacity(movers.len());
+        for (old, index) in movers.into_iter().zip(holes) {
+            self.despawn_entity(old);
+            let new = Entity::new(index as u32, self.generations[index]).in_world(self.world);
+            self.entities.insert(index, new);
+            moves.push((old, new));
+        }
+        // Spawns take the first empty index once none is free, packing the new entities too.
+        self.free.clear();
+        self.entities.compact();
+        moves
+    }
+
+    /// Lists the entities whose slot disagrees with them: stored at another index, with another
+    /// generation than the one the slot hands out, or alive while their index is free.
+    pub(crate) fn check(&self) -> Vec<(Entity, String)> {
+        let mut errors = Vec::new();
+        for (slot, entity) in self.entities.iter() {
+            if entity.index as usize != slot {
+                errors.push((*entity, format!("stored in slot {}", slot)));
+            }
+            match self.generations.get(slot) {
+                Some(generation) if *generation == entity.generation() => {}
+                Some(generation) => errors.push((*entity, format!("slot {} is at generation {}", slot, generation))),
+                None => errors.push((*entity, format!("slot {} has no generation", slot))),
+            }
+        }
+        for (position, index) in self.free.iter().enumerate() {
+            if let Some(entity) = self.get(*index) {
+                errors.push((entity, format!("index {} is alive but free", index)));
+            } else if self.free.iter().skip(position + 1).any(|other| other == index) {
+                errors.push((Entity::new(*index, self.generations[*index as usize]), format!("index {} is free twice", index)));
+            }
+        }
+        errors
+    }
+}
+
+impl Default for Entities {
+    fn default() -> Self {
+        Self::init()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spawn_despawn() {
+        let mut entities = Entities::init();
+        let a = *entities.spawn_entity();
+        let b = *entities.spawn_entity();
+        assert_eq!((a.index(), b.index()), (0, 1));
+        assert!(entities.despawn_entity(a));
+        assert!(!entities.despawn_entity(a));
+        assert!(!entities.is_alive(a));
+        let c = *entities.spawn_entity();
+        assert_eq!(c.index(), 0);
+        assert_ne!(c, a);
+        assert_eq!(entities.len(), 2);
+    }
+
+    #[test]
+    fn spawn_at_empty_and_occupied() {
+        let mut entities = Entities::init();
+        let e = entities.spawn_at(40, 7).unwrap();
+        assert_eq!((e.index(), e.generation()), (40, 7));
+        assert!(entities.is_alive(e));
+        assert_eq!(entities.spawn_at(40, 7), Err(SpawnAtError::Occupied(e)));
+        assert_eq!(entities.spawn_at(40, 8), Err(SpawnAtError::Occupied(e)));
+        assert_eq!(
+            entities.spawn_at(CAPACITY as u32, 0),
+            Err(SpawnAtError::OutOfRange(CAPACITY as u32))
+        );
+    }
+
+    #[test]
+    fn spawn_at_interleaved_with_spawns() {
+        let mut entities = Entities::init();
+        entities.spawn_at(2, 1).unwrap();
+        entities.spawn_at(4, 1).unwrap();
+        let spawned: Vec<u32> = (0..6).map(|_| entities.spawn_entity().index()).collect();
+        assert_eq!(spawned, vec![0, 1, 3, 5, 6, 7]);
+        assert!(matches!(entities.spawn_at(3, 1), Err(SpawnAtError::Occupied(_))));
+        assert_eq!(entities.len(), 8);
+    }
+
+    #[test]
+    fn spawn_at_respects_generations() {
+        let mut entities = Entities::init();
+        let old = entities.spawn_at(3, 5).unwrap();
+        entities.despawn_entity(old);
+        assert_eq!(
+            entities.spawn_at(3, 5),
+            Err(SpawnAtError::StaleGeneration { index: 3, generation: 5, next: 6 })
+        );
+        let new = entities.spawn_at(3, 9).unwrap();
+        assert!(!entities.is_alive(old));
+        assert!(entities.is_alive(new));
+        entities.despawn_entity(new);
+        // The freed index is reused before the lower indices that were never used.
+        let spawned: Vec<Entity> = (0..4).map(|_| *entities.spawn_entity()).collect();
+        assert_eq!(spawned[0], Entity::new(3, 10));
+        assert_eq!(spawned[1..].iter().map(Entity::index).collect::<Vec<_>>(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn freed_indices_are_recycled() {
+        for reuse in [IndexReuse::Lifo, IndexReuse::Fifo] {
+            let mut entities = Entities::init();
+            entities.set_index_reuse(reuse);
+            let first: Vec<Entity> = (0..100).map(|_| *entities.spawn_entity()).collect();
+            for entity in &first {
+                entities.despawn_entity(*entity);
+            }
+            assert_eq!(entities.free_count(), 100);
+            let second: Vec<Entity> = (0..100).map(|_| *entities.spawn_entity()).collect();
+            assert!(second.iter().all(|e| e.index() < 100 && e.generation() == 2));
+            assert!(first.iter().all(|e| !entities.is_alive(*e)));
+            assert!(second.iter().all(|e| entities.is_alive(*e)));
+            assert_eq!(entities.free_count(), 0);
+            assert!(entities.check().is_empty());
+        }
+    }
+
+    #[test]
+    fn reuse_order() {
+        let mut entities = Entities::init();
+        let spawned: Vec<Entity> = (0..4).map(|_| *entities.spawn_entity()).collect();
+        for i in [1, 3, 0] {
+            entities.despawn_entity(spawned[i]);
+        }
+        let mut fifo = Entities::init();
+        fifo.set_index_reuse(IndexReuse::Fifo);
+        let spawned: Vec<Entity> = (0..4).map(|_| *fifo.spawn_entity()).collect();
+        for i in [1, 3, 0] {
+            fifo.despawn_entity(spawned[i]);
+        }
+        let lifo: Vec<u32> = (0..4).map(|_| entities.spawn_entity().index()).collect();
+        let fifo: Vec<u32> = (0..4).map(|_| fifo.spawn_entity().index()).collect();
+        assert_eq!(lifo, [0, 3, 1, 4]);
+        assert_eq!(fifo, [1, 3, 0, 4]);
+    }
+
+    #[test]
+    fn generation_zero_is_never_used() {
+        assert_eq!(size_of::<Option<Entity>>(), size_of::<Entity>());
+        let mut entities = Entities::init();
+        assert_eq!(
+            entities.spawn_at(0, 0),
+            Err(SpawnAtError::StaleGeneration { index: 0, generation: 0, next: 1 })
+        );
+        let first = *entities.spawn_entity();
+        assert_eq!(first.generation(), 1);
+        entities.despawn_entity(first);
+        // Wrapping around skips 0.
+        entities.generations[0] = u32::MAX;
+        let last = *entities.spawn_entity();
+        entities.despawn_entity(last);
+        assert_eq!(entities.spawn_entity().generation(), 1);
+    }
+
+    #[test]
+    fn bits_round_trip() {
+        for entity in [Entity::new(0, 1), Entity::new(42, 3), Entity::new(u32::MAX, u32::MAX)] {
+            assert_eq!(Entity::from_bits(entity.to_bits()), Some(entity));
+        }
+        assert_eq!(Entity::new(42, 3).to_bits(), 3 << 32 | 42);
+        assert_eq!(Entity::from_bits(42), None);
+        assert_eq!(format!("{} {:?}", Entity::new(42, 3), Entity::new(7, 1)), "Entity(42v3) Entity(7v1)");
+    }
+}
@@ -0,0 +1,376 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::ptr::NonNull;
+
+use crate::change_detection::Mut;
+use crate::component::{Component, ComponentId, ComponentSet};
+use crate::entity::Entity;
+use crate::query::Disabled;
+use crate::{EcsError, PartitionId, World};
+
+// In debug builds and with the `strict-checks` feature the reads through a handle check that the
+// entity is still alive too, instead of answering `None` for a despawned one.
+fn check_read(world: &World, entity: Entity) {
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    if let Err(error) = world.entities.check_alive(entity) {
+        panic!("{}", error);
+    }
+    #[cfg(not(any(debug_assertions, feature = "strict-checks")))]
+    let _ = (world, entity);
+}
+
+/// Shared access to an entity that is alive and to its components, for the inspectors listing
+/// the components of an entity without knowing their types.
+#[derive(Clone, Copy)]
+pub struct EntityRef<'w> {
+    world: &'w World,
+    entity: Entity,
+}
+
+impl<'w> EntityRef<'w> {
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn world(&self) -> &'w World {
+        self.world
+    }
+
+    /// Fails with [`EcsError::EntityNotAlive`] if the entity was despawned since the handle was
+    /// made, which only unsafe code can do with a shared handle.
+    pub fn check_alive(&self) -> Result<(), EcsError> {
+        self.world.entities.check_alive(self.entity)
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&'w T> {
+        check_read(self.world, self.entity);
+        self.world.get_component(self.entity)
+    }
+
+    pub fn contains<T: Component>(&self) -> bool {
+        check_read(self.world, self.entity);
+        self.world.has_component::<T>(self.entity)
+    }
+
+    /// The components of the entity, see [`World::entity_signature`].
+    pub fn signature(&self) -> ComponentSet {
+        check_read(self.world, self.entity);
+        self.world.entity_signature(self.entity).unwrap()
+    }
+
+    /// The ids of the components of the entity in ascending order, read from its signature.
+    pub fn component_ids(&self) -> impl Iterator<Item = ComponentId> + 'w {
+        let ids: Vec<ComponentId> = self.signature().iter().collect();
+        ids.into_iter()
+    }
+
+    /// A pointer to the component `id` of the entity, see [`World::get_by_id`].
+    pub fn get_by_id(&self, id: ComponentId) -> Option<NonNull<u8>> {
+        check_read(self.world, self.entity);
+        self.world.get_by_id(self.entity, id)
+    }
+
+    /// The name and a printout of each component of the entity, in ascending id order. The
+    /// components registered with [`World::register_debug`] are printed with `Debug`, the others
+    /// as their size, `<4 bytes>`.
+    pub fn iter_debug(&self) -> impl Iterator<Item = (&'static str, String)> + 'w {
+        let (world, index) = (self.world, self.entity.index() as usize);
+        self.component_ids().map(move |id| {
+            let info = world.components.info(id).unwrap();
+            let value = info.debug_fn().and_then(|debug| debug(world.storages.get(id), index));
+            (info.name(), value.unwrap_or_else(|| format!("<{} bytes>", info.layout().size())))
+        })
+    }
+}
+
+/// Exclusive access to an entity that is alive and to its components.
+///
+/// The entity may die while the handle lives, despawned by an observer of a component inserted
+/// or removed through it. Every structural change checks that it is still alive first and
+/// panics otherwise, the `try_` methods return [`EcsError::EntityNotAlive`] instead.
+pub struct EntityMut<'w> {
+    world: &'w mut World,
+    entity: Entity,
+}
+
+impl<'w> EntityMut<'w> {
+    pub fn id(&self) -> Entity {
+        self.entity
+    }
+
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    /// Fails with [`EcsError::EntityNotAlive`] if the entity was despawned since the handle was
+    /// made.
+    pub fn check_alive(&self) -> Result<(), EcsError> {
+        self.world.entities.check_alive(self.entity)
+    }
+
+    pub fn get<T: Component>(&self) -> Option<&T> {
+        check_read(self.world, self.entity);
+        self.world.get_component(self.entity)
+    }
+
+    pub fn get_mut<T: Component>(&mut self) -> Option<Mut<'_, T>> {
+        check_read(self.world, self.entity);
+        self.world.get_component_mut(self.entity)
+    }
+
+    pub fn contains<T: Component>(&self) -> bool {
+        check_read(self.world, self.entity);
+        self.world.has_component::<T>(self.entity)
+    }
+
+    /// The components of the entity, see [`World::entity_signature`].
+    pub fn signature(&self) -> ComponentSet {
+        check_read(self.world, self.entity);
+        self.world.entity_signature(self.entity).unwrap()
+    }
+
+    /// Adds or replaces a component, see [`World::add_component`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive anymore.
+    pub fn insert<T: Component>(&mut self, component: T) -> &mut Self {
+        self.try_insert(component).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_insert<T: Component>(&mut self, component: T) -> Result<&mut Self, EcsError> {
+        self.world.try_add_component(self.entity, component)?;
+        Ok(self)
+    }
+
+    /// Adds or replaces a component with its registered default value, see
+    /// [`World::insert_default_component`].
+    pub fn insert_default<T: Component>(&mut self) -> Result<&mut Self, EcsError> {
+        let id = self.world.components().id::<T>().ok_or(EcsError::NoDefault {
+            type_name: type_name::<T>(),
+        })?;
+        self.insert_default_by_id(id)
+    }
+
+    /// Same as [`EntityMut::insert_default`] for a component known by id, like the one picked in
+    /// the component list of an editor.
+    pub fn insert_default_by_id(&mut self, id: ComponentId) -> Result<&mut Self, EcsError> {
+        self.world.insert_default_component(self.entity, id)?;
+        Ok(self)
+    }
+
+    /// Removes the component and returns it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive anymore.
+    pub fn remove<T: Component>(&mut self) -> Option<T> {
+        self.try_remove().unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_remove<T: Component>(&mut self) -> Result<Option<T>, EcsError> {
+        self.check_alive()?;
+        Ok(self.world.remove_component(self.entity))
+    }
+
+    /// Moves the entity into `partition`, see [`World::set_partition`].
+    pub fn set_partition(&mut self, partition: PartitionId) -> &mut Self {
+        self.world.set_partition(self.entity, partition).unwrap_or_else(|error| panic!("{}", error));
+        self
+    }
+
+    /// Turns the `A` of the entity into a `B` in one step, see [`World::replace_component`].
+    pub fn replace<A, B>(&mut self, f: impl FnOnce(A) -> B) -> Result<Option<B>, EcsError>
+    where
+        A: Component,
+        B: Component,
+    {
+        self.world.replace_component(self.entity, f)
+    }
+
+    /// Attaches the children after the other children of this entity, see
+    /// [`World::push_children`].
+    pub fn push_children(&mut self, children: &[Entity]) -> &mut Self {
+        self.world.push_children(self.entity, children);
+        self
+    }
+
+    /// Adds or removes the [`Disabled`] marker, the components are kept either way. The
+    /// descendants are left as they are, see [`World::set_enabled_recursive`].
+    pub fn set_enabled(&mut self, enabled: bool) -> &mut Self {
+        match enabled {
+            true => {
+                self.remove::<Disabled>();
+            }
+            false => {
+                self.insert(Disabled);
+            }
+        }
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.contains::<Disabled>()
+    }
+}
+
+impl World {
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive.
+    pub fn entity(&self, entity: Entity) -> EntityRef<'_> {
+        self.try_entity(entity).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_entity(&self, entity: Entity) -> Result<EntityRef<'_>, EcsError> {
+        self.entities.check_alive(entity)?;
+        Ok(EntityRef {
+            world: self,
+            entity,
+        })
+    }
+
+    pub fn get_entity(&self, entity: Entity) -> Option<EntityRef<'_>> {
+        self.try_entity(entity).ok()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive.
+    pub fn entity_mut(&mut self, entity: Entity) -> EntityMut<'_> {
+        self.try_entity_mut(entity).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    pub fn try_entity_mut(&mut self, entity: Entity) -> Result<EntityMut<'_>, EcsError> {
+        self.entities.check_alive(entity)?;
+        Ok(EntityMut {
+            world: self,
+            entity,
+        })
+    }
+
+    pub fn get_entity_mut(&mut self, entity: Entity) -> Option<EntityMut<'_>> {
+        self.try_entity_mut(entity).ok()
+    }
+
+    /// The entity if it is alive, otherwise spawns it at its index and generation, for the ids a
+    /// server hands out. Together with [`EntityMut::insert`] messages about the entity apply the
+    /// same whichever comes first.
+    ///
+    /// Returns `None` for stale ids, when another generation of the index is alive or a newer one
+    /// was already despawned, see [`World::spawn_at`].
+    pub fn get_or_spawn(&mut self, entity: Entity) -> Option<EntityMut<'_>> {
+        let entity = match self.entities.check_alive(entity) {
+            Ok(()) => entity,
+            Err(_) => self.spawn_at(entity.index(), entity.generation()).ok()?,
+        };
+        Some(EntityMut {
+            world: self,
+            entity,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::alloc::Layout;
+
+    use super::*;
+    use crate::component::ComponentDescriptor;
+    use crate::observer::{DeferredWorld, OnAdd, Trigger};
+
+    #[derive(Debug, Component)]
+    struct Position(i32, i32);
+    #[derive(Debug, Component)]
+    struct Health(u32);
+    #[derive(Debug, Component)]
+    struct Player;
+    #[derive(Component)]
+    struct Opaque(u64);
+
+    #[test]
+    fn registered_components_are_printed() {
+        let mut world = World::new();
+        world.register_debug::<Position>();
+        world.register_debug::<Health>();
+        world.register_debug::<Player>();
+        let e = world.spawn_batch_iter([(Position(1, -2), Health(30), Player)])[0];
+        world.spawn_batch([Opaque(0)]);
+        let entity = world.entity(e);
+        let ids: Vec<ComponentId> = entity.component_ids().collect();
+        assert_eq!(ids, entity.signature().iter().collect::<Vec<_>>());
+        let printed: Vec<(&str, String)> = entity.iter_debug().collect();
+        let names: Vec<&str> = printed.iter().map(|(name, _)| name.rsplit("::").next().unwrap()).collect();
+        assert_eq!(names, ["Position", "Health", "Player"]);
+        let values: Vec<&str> = printed.iter().map(|(_, value)| value.as_str()).collect();
+        assert_eq!(values, ["Position(1, -2)", "Health(30)", "Player"]);
+        let health = world.components().id::<Health>().unwrap();
+        let ptr = entity.get_by_id(health).unwrap();
+        assert_eq!(unsafe { ptr.cast::<Health>().as_ref() }.0, 30);
+    }
+
+    #[test]
+    fn unprintable_components_show_their_size() {
+        let mut world = World::new();
+        let meters = world.register_component_with_descriptor(ComponentDescriptor::new("Meters", Layout::new::<u16>()));
+        let e = world.spawn_batch([Opaque(7)])[0];
+        let mut value = 3u16;
+        unsafe { world.insert_by_id(e, meters, NonNull::from(&mut value).cast()) };
+        let printed: Vec<(&str, String)> = world.entity(e).iter_debug().collect();
+        assert_eq!(printed[0], ("Meters", String::from("<2 bytes>")));
+        assert_eq!(printed[1].1, "<8 bytes>");
+        assert_eq!(printed.len(), 2);
+        world.despawn_entity(e);
+        assert!(world.get_entity(e).is_none());
+    }
+
+    // Despawns the entities given a `Fuse`, while the handle that inserted it still lives.
+    #[derive(Component)]
+    struct Fuse;
+
+    fn world_with_fuses() -> World {
+        let mut world = World::new();
+        world.add_observer(|trigger: Trigger<OnAdd<Fuse>>, world: &mut DeferredWorld| {
+            world.commands().despawn(trigger.entity());
+        });
+        world
+    }
+
+    #[test]
+    fn stale_handles_reject_structural_changes() {
+        let mut world = world_with_fuses();
+        let e = world.spawn_batch([Health(3)])[0];
+        let mut entity = world.entity_mut(e);
+        entity.insert(Fuse);
+        assert_eq!(entity.check_alive(), Err(EcsError::EntityNotAlive(e)));
+        assert_eq!(entity.try_insert(Position(1, 1)).err(), Some(EcsError::EntityNotAlive(e)));
+        assert_eq!(entity.try_remove::<Health>().err(), Some(EcsError::EntityNotAlive(e)));
+
+        // Nothing was written at the index for the next entity to find.
+        let next = world.spawn_batch([Player])[0];
+        assert_eq!(next.index(), e.index());
+        assert_eq!(world.entity(next).component_ids().count(), 1);
+        assert!(world.get_component::<Position>(next).is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not alive")]
+    fn stale_handles_panic_on_inserts() {
+        let mut world = world_with_fuses();
+        let e = world.spawn_batch([Health(3)])[0];
+        world.entity_mut(e).insert(Fuse).insert(Position(0, 0));
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    #[should_panic(expected = "is not alive")]
+    fn stale_handles_panic_on_reads() {
+        let mut world = world_with_fuses();
+        let e = world.spawn_batch([Health(3)])[0];
+        let mut entity = world.entity_mut(e);
+        entity.insert(Fuse);
+        entity.get::<Health>();
+    }
+}
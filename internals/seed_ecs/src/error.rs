@@ -0,0 +1,227 @@
+use core::error::Error;
+use core::fmt;
+
+use crate::component::ComponentId;
+use crate::entity::{Entity, WorldId};
+
+/// Why an operation of a [`World`](crate::World) failed, returned by its `try_` methods. The
+/// methods without the prefix panic with the same message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EcsError {
+    EntityNotAlive(Entity),
+    MissingComponent { entity: Entity, type_name: &'static str },
+    /// The world has no component with that id.
+    UnknownComponent(ComponentId),
+    /// Every slot of the storage is used, `cap` is how many values it holds at most.
+    StorageFull { type_name: &'static str, cap: usize },
+    /// Every entity index is alive, `capacity` is how many entities a world addresses at most.
+    EntitiesExhausted { capacity: usize },
+    /// An entity can't be attached to itself.
+    OwnParent(Entity),
+    /// The component has no default value registered, see
+    /// [`World::register_with_default`](crate::World::register_with_default).
+    NoDefault { type_name: &'static str },
+    /// Every entity of a [`Pool`](crate::Pool) of bundles `type_name` is acquired.
+    PoolExhausted { type_name: &'static str, capacity: usize },
+    /// The entity was spawned by the world `world`, not by this one. Only checked in debug builds
+    /// or with the `strict-checks` feature.
+    WrongWorld { entity: Entity, world: WorldId, expected: WorldId },
+    MissingResource { type_name: &'static str },
+    /// The resource was requested while a [`World::resource_scope`](crate::World::resource_scope)
+    /// held it, the systems are `None` for the code running outside of systems.
+    AccessConflict {
+        type_name: &'static str,
+        held_by: Option<&'static str>,
+        requested_by: Option<&'static str>,
+    },
+}
+
+impl fmt::Display for EcsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EntityNotAlive(entity) => write!(f, "Entity {:?} is not alive", entity),
+            Self::MissingComponent { entity, type_name } => {
+                write!(f, "Entity {:?} has no component {}", entity, type_name)
+            }
+            Self::UnknownComponent(id) => write!(f, "Unknown component {:?}", id),
+            Self::StorageFull { type_name, cap } => {
+                write!(f, "Storage of {} is full, it holds at most {} values", type_name, cap)
+            }
+            Self::EntitiesExhausted { capacity } => {
+                write!(f, "Entity capacity ({}) exhausted, despawn entities to spawn more", capacity)
+            }
+            Self::OwnParent(entity) => write!(f, "Entity {:?} can't be its own parent", entity),
+            Self::NoDefault { type_name } => {
+                write!(f, "Component {} has no default value registered", type_name)
+            }
+            Self::PoolExhausted { type_name, capacity } => {
+                write!(f, "Pool of {} is exhausted, its {} entities are acquired", type_name, capacity)
+            }
+            Self::WrongWorld { entity, world, expected } => write!(
+                f,
+                "Entity {:?} belongs to world {}, not to world {}",
+                entity, world, expected
+            ),
+            Self::MissingResource { type_name } => write!(f, "Resource {} does not exist", type_name),
+            Self::AccessConflict {
+                type_name,
+                held_by,
+                requested_by,
+            } => write!(
+                f,
+                "Resource {} requested by {} is held by {} in a resource scope",
+                type_name,
+                SystemName(*requested_by),
+                SystemName(*held_by)
+            ),
+        }
+    }
+}
+
+// A system named in an error, or the code outside of systems.
+struct SystemName(Option<&'static str>);
+
+impl fmt::Display for SystemName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(name) => write!(f, "system {}", name),
+            None => f.write_str("code outside of systems"),
+        }
+    }
+}
+
+impl Error for EcsError {}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+    use core::any::type_name;
+    use core::ptr::NonNull;
+    use crate::component::Component;
+
+    use super::*;
+    use crate::relation::Relation;
+    use crate::utils::CAPACITY;
+    use crate::World;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Likes;
+    impl Relation for Likes {}
+
+    #[test]
+    fn try_operations_report_what_failed() {
+        let mut world = World::new();
+        let alive = world.try_spawn_entity().unwrap();
+        let dead = world.try_spawn_entity().unwrap();
+        world.try_despawn_entity(dead).unwrap();
+        assert_eq!(world.try_despawn_entity(dead), Err(EcsError::EntityNotAlive(dead)));
+
+        let error = world.try_add_component(dead, Health(1)).unwrap_err();
+        assert_eq!(error, EcsError::EntityNotAlive(dead));
+        assert_eq!(error.to_string(), format!("Entity {:?} is not alive", dead));
+        assert_eq!(world.try_add_component(alive, Health(1)), Ok(None));
+        assert_eq!(world.try_get_component::<Health>(alive), Ok(&Health(1)));
+        assert_eq!(world.try_get_component::<Health>(dead), Err(EcsError::EntityNotAlive(dead)));
+        assert_eq!(world.try_remove_component::<Health>(alive), Ok(Health(1)));
+
+        let missing = EcsError::MissingComponent {
+            entity: alive,
+            type_name: type_name::<Health>(),
+        };
+        assert_eq!(world.try_get_component::<Health>(alive), Err(missing));
+        assert_eq!(world.try_get_component_mut::<Health>(alive).err(), Some(missing));
+        assert_eq!(world.try_remove_component::<Health>(alive), Err(missing));
+        // Never registered.
+        assert!(matches!(world.try_get_component::<Likes>(alive), Err(EcsError::MissingComponent { .. })));
+        assert_eq!(missing.to_string(), format!("Entity {:?} has no component {}", alive, type_name::<Health>()));
+
+        assert!(world.try_entity_mut(dead).is_err());
+        assert_eq!(world.try_set_parent(alive, alive), Err(EcsError::OwnParent(alive)));
+        assert_eq!(world.try_set_parent(alive, dead), Err(EcsError::EntityNotAlive(dead)));
+        assert_eq!(world.parent(alive), None);
+        assert_eq!(world.try_relate::<Likes>(dead, alive), Err(EcsError::EntityNotAlive(dead)));
+
+        let id = world.register_component::<Health>();
+        let unknown = crate::component::ComponentId::new(id.index() + 10);
+        let mut value = Health(2);
+        let result = unsafe { world.try_insert_by_id(alive, unknown, NonNull::from(&mut value).cast()) };
+        assert_eq!(result, Err(EcsError::UnknownComponent(unknown)));
+        let result = unsafe { world.try_insert_by_id(dead, id, NonNull::from(&mut value).cast()) };
+        assert_eq!(result, Err(EcsError::EntityNotAlive(dead)));
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    fn entities_of_other_worlds_are_rejected() {
+        let mut simulation = World::new();
+        let mut ui = World::new();
+        assert_ne!(simulation.id(), ui.id());
+        let unit = *simulation.spawn_entity();
+        // Same index and generation, a plain index check would take one for the other.
+        let button = *ui.spawn_entity();
+        assert_eq!(unit, button);
+        ui.add_component(button, Health(1));
+        let wrong = EcsError::WrongWorld {
+            entity: unit,
+            world: simulation.id(),
+            expected: ui.id(),
+        };
+
+        assert_eq!(ui.try_get_component::<Health>(unit), Err(wrong));
+        assert_eq!(ui.try_get_component_mut::<Health>(unit).err(), Some(wrong));
+        assert_eq!(ui.try_add_component(unit, Health(2)), Err(wrong));
+        assert_eq!(ui.try_remove_component::<Health>(unit), Err(wrong));
+        assert_eq!(ui.try_despawn_entity(unit), Err(wrong));
+        let panel = *ui.spawn_entity();
+        assert_eq!(ui.try_set_parent(panel, unit), Err(wrong));
+        assert_eq!(ui.try_entity_mut(unit).err(), Some(wrong));
+        assert!(!ui.is_alive(unit) && ui.get_component::<Health>(unit).is_none());
+        assert_eq!(ui.get_component::<Health>(button), Some(&Health(1)));
+        assert_eq!(
+            wrong.to_string(),
+            format!("Entity {:?} belongs to world {}, not to world {}", unit, simulation.id(), ui.id())
+        );
+
+        // A handle kept from another world is caught.
+        let (simulation_id, ui_id) = (simulation.id(), ui.id());
+        let mut cached = ui.entity_mut(button);
+        cached.insert(Health(3));
+        let error = simulation.try_add_component(cached.id(), Health(4)).unwrap_err();
+        assert_eq!(error, EcsError::WrongWorld {
+            entity: button,
+            world: ui_id,
+            expected: simulation_id,
+        });
+        // Entities made from bits belong to no world.
+        let unpacked = crate::entity::Entity::from_bits(unit.to_bits()).unwrap();
+        assert_eq!(unpacked.world(), None);
+        assert_eq!(ui.try_get_component::<Health>(unpacked), Ok(&Health(3)));
+    }
+
+    #[test]
+    fn spawning_past_the_capacity_fails() {
+        let mut world = World::new();
+        for _ in 0..CAPACITY {
+            world.try_spawn_entity().unwrap();
+        }
+        let error = world.try_spawn_entity().unwrap_err();
+        assert_eq!(error, EcsError::EntitiesExhausted { capacity: CAPACITY });
+        assert!(error.to_string().contains("Entity capacity (1048576) exhausted"), "{}", error);
+        // Despawning frees an index again.
+        let first = world.enities().iter().next().unwrap();
+        world.despawn_entity(first);
+        assert!(world.try_spawn_entity().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not alive")]
+    fn panicking_versions_keep_the_message() {
+        let mut world = World::new();
+        let e = *world.spawn_entity();
+        world.despawn_entity(e);
+        world.add_component(e, Health(1));
+    }
+}
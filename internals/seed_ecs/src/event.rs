@@ -0,0 +1,663 @@
+//! Events sent by systems to the systems that run after them, kept for two frames, see
+//! [`Events`].
+
+use alloc::collections::VecDeque;
+use core::any::type_name;
+use core::marker::PhantomData;
+
+use crate::system::{Res, ResMut, SystemMeta, SystemParam};
+use crate::utils::MRing;
+use crate::{UnsafeWorldCell, World};
+
+/// What [`Events::send`] does with an event once the channel is full, see
+/// [`EventSettings::capacity`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropPolicy {
+    /// Drops the oldest event kept to make room, the readers that didn't get to it miss it. With
+    /// a capacity of 0 the event sent is dropped instead.
+    DropOldest,
+    /// Drops the event sent.
+    DropNewest,
+    /// Panics, for the channels that must never lose an event.
+    Panic,
+}
+
+/// How many events of a type are kept at most and what happens past that, given to
+/// [`World::add_event_with`]. The default keeps every event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventSettings {
+    pub capacity: usize,
+    pub policy: DropPolicy,
+}
+
+impl Default for EventSettings {
+    fn default() -> Self {
+        Self { capacity: usize::MAX, policy: DropPolicy::DropOldest }
+    }
+}
+
+/// The events of type `T` sent during the current and the previous frame, a resource. Frames are
+/// delimited by [`Events::update`], which [`update_events`] calls from a schedule.
+///
+/// The events sit in a ring buffer bounded by the [settings](EventSettings) of the channel. Each
+/// event gets a sequence number that never wraps, and readers keep the number of the next event
+/// they didn't read in an [`EventCursor`]. A reader that fell behind the dropped events resumes
+/// at the oldest one kept, so it still reads the events in order, with a gap it can detect.
+pub struct Events<T> {
+    // The events of the previous frame then of the current one, oldest first.
+    buffer: VecDeque<T>,
+    // The sequence number of the front event.
+    front: u64,
+    // The sequence number of the first event sent during the current frame.
+    frame_start: u64,
+    settings: EventSettings,
+    dropped: u64,
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Self::new(EventSettings::default())
+    }
+}
+
+impl<T> Events<T> {
+    pub fn new(settings: EventSettings) -> Self {
+        Self {
+            buffer: VecDeque::new(),
+            front: 0,
+            frame_start: 0,
+            settings,
+            dropped: 0,
+        }
+    }
+
+    pub fn settings(&self) -> EventSettings {
+        self.settings
+    }
+
+    // The sequence number of the next event sent.
+    fn end(&self) -> u64 {
+        self.front + self.buffer.len() as u64
+    }
+
+    /// Sends the event to the readers, returns false if the channel was full and dropped it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the channel is full and its policy is [`DropPolicy::Panic`].
+    pub fn send(&mut self, event: T) -> bool {
+        if self.buffer.len() >= self.settings.capacity {
+            match self.settings.policy {
+                DropPolicy::DropOldest if !self.buffer.is_empty() => {
+                    self.buffer.pop_front();
+                    self.front += 1;
+                }
+                // A channel of capacity 0 has nothing to drop but the event sent.
+                DropPolicy::DropOldest | DropPolicy::DropNewest => {
+                    self.dropped += 1;
+                    return false;
+                }
+                DropPolicy::Panic => panic!(
+                    "Event channel of {} is full, it holds at most {} events",
+                    type_name::<T>(),
+                    self.settings.capacity
+                ),
+            }
+            self.dropped += 1;
+        }
+        self.buffer.push_back(event);
+        true
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// Ends the frame: the events of the previous frame are dropped, the ones sent during this
+    /// one are kept for one more frame.
+    pub fn update(&mut self) {
+        let stale = self.frame_start.saturating_sub(self.front).min(self.buffer.len() as u64);
+        self.buffer.drain(..stale as usize);
+        self.front += stale;
+        self.frame_start = self.end();
+    }
+
+    /// How many events the channel dropped since it was created, because it was full. Dropped
+    /// by [`Events::update`] isn't counted.
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped
+    }
+
+    /// The events the cursor didn't read yet, oldest first, and moves the cursor past them.
+    pub fn read<'a>(&'a self, cursor: &mut EventCursor<T>) -> impl ExactSizeIterator<Item = &'a T> + 'a {
+        let start = cursor.advance(self.front, self.end());
+        self.buffer.range(start.min(self.buffer.len())..)
+    }
+
+    /// The events kept, oldest first.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &T> + '_ {
+        self.buffer.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buffer.is_empty()
+    }
+
+    /// Drops every event kept, the readers skip them.
+    pub fn clear(&mut self) {
+        self.front = self.end();
+        self.frame_start = self.front;
+        self.buffer.clear();
+    }
+}
+
+/// Where a reader of [`Events`] is in the channel, kept by an [`EventReader`] or a
+/// [`Local`](crate::system::Local) of the system reading. A new cursor reads every event kept.
+pub struct EventCursor<T> {
+    next: u64,
+    missed: u64,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventCursor<T> {
+    fn default() -> Self {
+        Self { next: 0, missed: 0, _marker: PhantomData }
+    }
+}
+
+impl<T> EventCursor<T> {
+    /// How many events were dropped before this reader got to them.
+    pub fn missed(&self) -> u64 {
+        self.missed
+    }
+
+    // Moves the cursor past the events numbered up to `end`, the front one being `front`, and
+    // returns the index of the first event it didn't read.
+    fn advance(&mut self, front: u64, end: u64) -> usize {
+        if self.next < front {
+            self.missed += front - self.next;
+            self.next = front;
+        }
+        let start = (self.next - front) as usize;
+        self.next = end;
+        start
+    }
+}
+
+/// The events of type `T` of the current and the previous frame, at most `N` of them, a
+/// resource. Like [`Events`] with a capacity of `N` and [`DropPolicy::DropOldest`], but the
+/// events sit inline in a ring buffer, so sending never allocates. Read with an [`EventCursor`].
+pub struct RingEvents<T, const N: usize> {
+    ring: MRing<T, N>,
+    // The sequence number of the oldest event.
+    front: u64,
+    // The sequence number of the first event sent during the current frame.
+    frame_start: u64,
+    dropped: u64,
+}
+
+impl<T, const N: usize> Default for RingEvents<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> RingEvents<T, N> {
+    pub const fn new() -> Self {
+        Self { ring: MRing::new(), front: 0, frame_start: 0, dropped: 0 }
+    }
+
+    // The sequence number of the next event sent.
+    fn end(&self) -> u64 {
+        self.front + self.ring.len() as u64
+    }
+
+    /// Sends the event to the readers, dropping the oldest one kept if the channel is full.
+    /// Returns false if `N` is 0 and the event was dropped.
+    pub fn send(&mut self, event: T) -> bool {
+        let full = self.ring.is_full();
+        if self.ring.push_overwrite(event).is_none() {
+            return true;
+        }
+        self.dropped += 1;
+        if full && N > 0 {
+            self.front += 1;
+        }
+        N > 0
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        for event in events {
+            self.send(event);
+        }
+    }
+
+    /// See [`Events::update`].
+    pub fn update(&mut self) {
+        let stale = self.frame_start.saturating_sub(self.front).min(self.ring.len() as u64);
+        for _ in 0..stale {
+            self.ring.pop_oldest();
+        }
+        self.front += stale;
+        self.frame_start = self.end();
+    }
+
+    /// See [`Events::dropped_count`].
+    pub fn dropped_count(&self) -> u64 {
+        self.dropped
+    }
+
+    /// See [`Events::read`].
+    pub fn read<'a>(&'a self, cursor: &mut EventCursor<T>) -> impl ExactSizeIterator<Item = &'a T> + 'a {
+        let start = cursor.advance(self.front, self.end());
+        self.ring.iter().skip(start)
+    }
+
+    /// The events kept, oldest first.
+    pub fn iter(&self) -> impl ExactSizeIterator<Item = &T> + '_ {
+        self.ring.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ring.is_empty()
+    }
+
+    /// Drops every event kept, the readers skip them.
+    pub fn clear(&mut self) {
+        self.front = self.end();
+        self.frame_start = self.front;
+        self.ring.clear();
+    }
+}
+
+/// Sends events of type `T` from a system, the [`Events`] resource borrowed mutably.
+///
+/// # Panics
+///
+/// The system panics if the events weren't added, see [`World::add_event`].
+pub struct EventWriter<'w, T: Send + Sync + 'static> {
+    events: ResMut<'w, Events<T>>,
+}
+
+impl<'w, T: Send + Sync + 'static> EventWriter<'w, T> {
+    /// See [`Events::send`].
+    pub fn send(&mut self, event: T) -> bool {
+        self.events.send(event)
+    }
+
+    pub fn send_batch(&mut self, events: impl IntoIterator<Item = T>) {
+        self.events.send_batch(events);
+    }
+}
+
+unsafe impl<'a, T: Send + Sync + 'static> SystemParam for EventWriter<'a, T> {
+    type State = ();
+    type Item<'w, 's> = EventWriter<'w, T>;
+
+    fn init_state(world: &mut World, meta: &mut SystemMeta) -> Self::State {
+        ResMut::<Events<T>>::init_state(world, meta)
+    }
+
+    unsafe fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        EventWriter {
+            events: ResMut::<Events<T>>::get_param(state, meta, world),
+        }
+    }
+}
+
+/// Reads events of type `T` from a system, the [`Events`] resource borrowed shared. The cursor
+/// belongs to the system: every reader reads each event once, whatever the other readers did,
+/// and the readers don't conflict with each other.
+///
+/// The cursor starts at the current frame when the system is initialized, a reader added late
+/// skips the events of the frames before but reads those of the frame it first runs in.
+///
+/// # Panics
+///
+/// The system panics if the events weren't added, see [`World::add_event`].
+pub struct EventReader<'w, 's, T: Send + Sync + 'static> {
+    events: Res<'w, Events<T>>,
+    cursor: &'s mut EventCursor<T>,
+}
+
+impl<'w, 's, T: Send + Sync + 'static> EventReader<'w, 's, T> {
+    /// The events this system didn't read yet, oldest first, see [`Events::read`].
+    pub fn read(&mut self) -> impl ExactSizeIterator<Item = &T> + '_ {
+        self.events.read(self.cursor)
+    }
+
+    /// Skips the events this system didn't read yet.
+    pub fn clear(&mut self) {
+        self.cursor.next = self.events.end();
+    }
+
+    /// See [`EventCursor::missed`].
+    pub fn missed(&self) -> u64 {
+        self.cursor.missed
+    }
+}
+
+unsafe impl<'a, 'b, T: Send + Sync + 'static> SystemParam for EventReader<'a, 'b, T> {
+    type State = EventCursor<T>;
+    type Item<'w, 's> = EventReader<'w, 's, T>;
+
+    fn init_state(world: &mut World, meta: &mut SystemMeta) -> Self::State {
+        Res::<Events<T>>::init_state(world, meta);
+        let next = world.get_resource::<Events<T>>().map_or(0, |events| events.frame_start.max(events.front));
+        EventCursor { next, ..EventCursor::default() }
+    }
+
+    unsafe fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        EventReader {
+            events: Res::<Events<T>>::get_param(&mut (), meta, world),
+            cursor: state,
+        }
+    }
+}
+
+/// Ends the frame of the events of type `T`, see [`Events::update`]. To add to a schedule, once.
+pub fn update_events<T: Send + Sync + 'static>(mut events: ResMut<Events<T>>) {
+    events.update();
+}
+
+/// Ends the frame of the [`RingEvents`] of type `T`, see [`update_events`].
+pub fn update_ring_events<T: Send + Sync + 'static, const N: usize>(mut events: ResMut<RingEvents<T, N>>) {
+    events.update();
+}
+
+impl World {
+    /// Inserts the [`Events`] of type `T`, keeping every event, unless they are there already.
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        self.init_resource::<Events<T>>();
+    }
+
+    /// Inserts the [`RingEvents`] of type `T` keeping at most `N` events, unless they are there
+    /// already.
+    pub fn add_ring_event<T: Send + Sync + 'static, const N: usize>(&mut self) {
+        self.init_resource::<RingEvents<T, N>>();
+    }
+
+    /// Inserts the [`Events`] of type `T` bounded by `settings`, the settings change if the
+    /// events are there already.
+    pub fn add_event_with<T: Send + Sync + 'static>(&mut self, settings: EventSettings) {
+        match self.get_resource_mut::<Events<T>>() {
+            Some(mut events) => events.settings = settings,
+            None => {
+                self.insert_resource(Events::<T>::new(settings));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::{AccessConflict, IntoSystem, IntoSystemConfig, Local, Res, Schedule};
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Collision(u32);
+
+    fn bounded(capacity: usize, policy: DropPolicy) -> Events<Collision> {
+        let mut events = Events::new(EventSettings { capacity, policy });
+        events.send_batch((0..capacity as u32).map(Collision));
+        events
+    }
+
+    #[test]
+    fn drop_oldest_keeps_the_latest_events() {
+        let mut events = bounded(4, DropPolicy::DropOldest);
+        let mut early = EventCursor::default();
+        assert_eq!(events.read(&mut early).len(), 4);
+        assert!(events.send(Collision(4)));
+        assert!(events.send(Collision(5)));
+        assert_eq!(events.dropped_count(), 2);
+        let kept: Vec<u32> = events.iter().map(|event| event.0).collect();
+        assert_eq!(kept, [2, 3, 4, 5]);
+        // The early reader was past the dropped events, it misses nothing.
+        let read: Vec<u32> = events.read(&mut early).map(|event| event.0).collect();
+        assert_eq!((read, early.missed()), (vec![4, 5], 0));
+    }
+
+    #[test]
+    fn drop_newest_keeps_the_earliest_events() {
+        let mut events = bounded(4, DropPolicy::DropNewest);
+        assert!(!events.send(Collision(4)));
+        events.send_batch((5..8).map(Collision));
+        assert_eq!(events.dropped_count(), 4);
+        let kept: Vec<u32> = events.iter().map(|event| event.0).collect();
+        assert_eq!(kept, [0, 1, 2, 3]);
+        events.update();
+        events.update();
+        assert!(events.send(Collision(8)));
+        assert_eq!(events.dropped_count(), 4);
+    }
+
+    #[test]
+    fn zero_capacity_drops_every_event() {
+        let mut events = bounded(0, DropPolicy::DropOldest);
+        let mut cursor = EventCursor::default();
+        assert!(!events.send(Collision(7)));
+        assert!(events.is_empty());
+        assert_eq!(events.read(&mut cursor).len(), 0);
+        assert_eq!((events.dropped_count(), cursor.missed()), (1, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "full, it holds at most 4 events")]
+    fn panic_policy_panics_on_overflow() {
+        let mut events = bounded(4, DropPolicy::Panic);
+        events.send(Collision(4));
+    }
+
+    #[test]
+    fn late_readers_see_an_ordered_suffix() {
+        let mut events = bounded(3, DropPolicy::DropOldest);
+        let mut late = EventCursor::default();
+        events.send_batch((3..10).map(Collision));
+        assert_eq!(events.dropped_count(), 7);
+        let read: Vec<u32> = events.read(&mut late).map(|event| event.0).collect();
+        assert_eq!((read, late.missed()), (vec![7, 8, 9], 7));
+        assert_eq!(events.read(&mut late).len(), 0);
+
+        events.update();
+        events.send(Collision(10));
+        events.update();
+        // The events of two frames ago are gone, only the overflows count as dropped.
+        assert_eq!(events.dropped_count(), 8);
+        let mut fresh = EventCursor::default();
+        let read: Vec<u32> = events.read(&mut fresh).map(|event| event.0).collect();
+        assert_eq!(read, [10]);
+        assert_eq!(fresh.missed(), 10);
+        let read: Vec<u32> = events.read(&mut late).map(|event| event.0).collect();
+        assert_eq!((read, late.missed()), (vec![10], 7));
+    }
+
+    #[test]
+    fn ring_events_keep_the_latest_events() {
+        let mut events = RingEvents::<Collision, 4>::new();
+        let (mut early, mut late) = (EventCursor::default(), EventCursor::default());
+        events.send_batch((0..3).map(Collision));
+        assert_eq!(events.read(&mut early).len(), 3);
+        let (_, allocations) = crate::utils::counting_alloc::count_allocations(|| events.send_batch((3..7).map(Collision)));
+        assert_eq!((allocations, events.dropped_count()), (0, 3));
+        let read: Vec<u32> = events.read(&mut early).map(|event| event.0).collect();
+        assert_eq!((read, early.missed()), (vec![3, 4, 5, 6], 0));
+        let read: Vec<u32> = events.read(&mut late).map(|event| event.0).collect();
+        assert_eq!((read, late.missed()), (vec![3, 4, 5, 6], 3));
+
+        events.update();
+        events.send(Collision(7));
+        events.update();
+        // The events of two frames ago are gone, only the overflows count as dropped.
+        let kept: Vec<u32> = events.iter().map(|event| event.0).collect();
+        assert_eq!((kept, events.dropped_count()), (vec![7], 4));
+        let read: Vec<u32> = events.read(&mut late).map(|event| event.0).collect();
+        assert_eq!((read, late.missed()), (vec![7], 3));
+
+        let mut none = RingEvents::<Collision, 0>::new();
+        assert!(!none.send(Collision(8)));
+        assert_eq!((none.len(), none.dropped_count()), (0, 1));
+    }
+
+    #[derive(Default)]
+    struct Seen(Vec<u32>);
+
+    #[test]
+    fn systems_read_the_events_once() {
+        let mut world = World::new();
+        world.add_event_with::<Collision>(EventSettings { capacity: 8, policy: DropPolicy::DropOldest });
+        world.insert_resource(Seen::default());
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(|mut events: ResMut<Events<Collision>>, mut frame: Local<u32>| {
+                events.send_batch((0..5).map(|i| Collision(*frame * 10 + i)));
+                *frame += 1;
+            })
+            .add_system(
+                |events: Res<Events<Collision>>, mut cursor: Local<EventCursor<Collision>>, mut seen: ResMut<Seen>| {
+                    seen.0.extend(events.read(&mut cursor).map(|event| event.0));
+                },
+            )
+            .add_system(update_events::<Collision>);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        let seen = &world.get_resource::<Seen>().unwrap().0;
+        assert_eq!(*seen, [0, 1, 2, 3, 4, 10, 11, 12, 13, 14]);
+        let events = world.get_resource::<Events<Collision>>().unwrap();
+        // The second frame overflowed into the first, which then ended.
+        assert_eq!((events.len(), events.dropped_count()), (5, 2));
+    }
+
+    #[test]
+    fn systems_read_the_ring_events_once() {
+        let mut world = World::new();
+        world.add_ring_event::<Collision, 8>();
+        world.insert_resource(Seen::default());
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(|mut events: ResMut<RingEvents<Collision, 8>>, mut frame: Local<u32>| {
+                events.send_batch((0..5).map(|i| Collision(*frame * 10 + i)));
+                *frame += 1;
+            })
+            .add_system(
+                |events: Res<RingEvents<Collision, 8>>, mut cursor: Local<EventCursor<Collision>>, mut seen: ResMut<Seen>| {
+                    seen.0.extend(events.read(&mut cursor).map(|event| event.0));
+                },
+            )
+            .add_system(update_ring_events::<Collision, 8>);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        let seen = &world.get_resource::<Seen>().unwrap().0;
+        assert_eq!(*seen, [0, 1, 2, 3, 4, 10, 11, 12, 13, 14]);
+        let events = world.get_resource::<RingEvents<Collision, 8>>().unwrap();
+        assert_eq!((events.len(), events.dropped_count()), (5, 2));
+    }
+
+    #[derive(Default)]
+    struct Heard(Vec<u32>);
+
+    fn collide(mut writer: EventWriter<Collision>, mut frame: Local<u32>) {
+        writer.send_batch((0..3).map(|i| Collision(*frame * 10 + i)));
+        *frame += 1;
+    }
+
+    fn see(mut reader: EventReader<Collision>, mut seen: ResMut<Seen>) {
+        seen.0.extend(reader.read().map(|event| event.0));
+    }
+
+    fn hear(mut reader: EventReader<Collision>, mut heard: ResMut<Heard>) {
+        heard.0.extend(reader.read().map(|event| event.0));
+    }
+
+    fn collisions(world: &mut World) -> Schedule {
+        world.add_event::<Collision>();
+        world.insert_resource(Seen::default());
+        world.insert_resource(Heard::default());
+        let mut schedule = Schedule::new();
+        schedule.add_system(collide).add_system(update_events::<Collision>.after(collide));
+        schedule
+    }
+
+    #[test]
+    fn readers_have_their_own_cursor() {
+        let mut world = World::new();
+        let mut schedule = collisions(&mut world);
+        // One reader runs before the writer, it reads the events of a frame on the next one.
+        schedule.add_system(see.after(collide).before(update_events::<Collision>)).add_system(hear.before(collide));
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, [0, 1, 2, 10, 11, 12, 20, 21, 22]);
+        assert_eq!(world.get_resource::<Heard>().unwrap().0, [0, 1, 2, 10, 11, 12]);
+        schedule.run(&mut world);
+        assert_eq!(world.get_resource::<Heard>().unwrap().0, [0, 1, 2, 10, 11, 12, 20, 21, 22]);
+    }
+
+    #[test]
+    fn late_readers_start_at_the_current_frame() {
+        let mut world = World::new();
+        let mut schedule = collisions(&mut world);
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(world.get_resource::<Events<Collision>>().unwrap().len(), 3);
+        // The events of the second frame are still kept but not read.
+        schedule.add_system(see.after(collide).before(update_events::<Collision>));
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(world.get_resource::<Seen>().unwrap().0, [20, 21, 22, 30, 31, 32]);
+
+        // Clearing skips what the system didn't read.
+        world
+            .run_system_once(|mut reader: EventReader<Collision>, mut heard: ResMut<Heard>| {
+                reader.clear();
+                heard.0.extend(reader.read().map(|event| event.0));
+            })
+            .unwrap();
+        assert!(world.get_resource::<Heard>().unwrap().0.is_empty());
+    }
+
+    #[test]
+    fn readers_are_compatible_with_each_other() {
+        let mut world = World::new();
+        world.add_event::<Collision>();
+        let mut meta = |init: fn(&mut World, &mut SystemMeta)| {
+            let mut meta = SystemMeta::new("test");
+            init(&mut world, &mut meta);
+            meta
+        };
+        let reader: fn(&mut World, &mut SystemMeta) = |world, meta| {
+            EventReader::<Collision>::init_state(world, meta);
+        };
+        let (first, second) = (meta(reader), meta(reader));
+        let writer = meta(EventWriter::<Collision>::init_state);
+        assert!(first.is_compatible(&second));
+        assert!(!first.is_compatible(&writer) && !writer.is_compatible(&second));
+
+        // Two readers of one system don't conflict either, and each reads every event.
+        world.get_resource_mut::<Events<Collision>>().unwrap().send_batch([Collision(1), Collision(2)]);
+        let counts = |mut a: EventReader<Collision>, mut b: EventReader<Collision>| {
+            assert_eq!((a.read().len(), b.read().len()), (2, 2));
+        };
+        world.run_system_once(counts).unwrap();
+        let conflict = world.run_system_once(|_: EventReader<Collision>, _: EventWriter<Collision>| {});
+        assert!(matches!(conflict, Err(AccessConflict::Resource { .. })));
+    }
+}
@@ -0,0 +1,246 @@
+//! Copying components from a main world into a render world every frame.
+//!
+//! Each main world entity with an extracted component gets one entity in the render world, with a
+//! [`MainEntity`] pointing back at it. The mapping is kept in the [`ExtractedEntities`] resource
+//! of the render world and is keyed by the whole entity, generation included, so that an index
+//! reused in the main world gets a render entity of its own.
+//!
+//! The changes are found with the trackers of the main world: extract once per frame, before
+//! [`World::clear_trackers`] ends the frame of the main world.
+
+use crate::change_detection::Mut;
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::utils::HashMap;
+use crate::World;
+
+/// The main world entity a render world entity was extracted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component)]
+pub struct MainEntity(pub Entity);
+
+/// The render world entity of each main world entity extracted, a resource of the render world.
+#[derive(Debug, Default)]
+pub struct ExtractedEntities {
+    entities: HashMap<Entity, Entity>,
+}
+
+impl ExtractedEntities {
+    /// The render world entity extracted from `main`.
+    pub fn get(&self, main: Entity) -> Option<Entity> {
+        self.entities.get(&main).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// The main world entities with their render world entity, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, Entity)> + '_ {
+        self.entities.iter().map(|(main, render)| (*main, *render))
+    }
+}
+
+/// A component extracted into something else, see [`extract_mapped`].
+pub trait ExtractComponent: Component {
+    /// The component the render world entities get.
+    type Out: Component;
+
+    /// The value for the render world, `None` to remove it from there.
+    fn extract(&self) -> Option<Self::Out>;
+}
+
+/// Brings the `T` of the render world up to date with the one of the main world: the entities
+/// seen for the first time are spawned, the values changed since the frame started are copied,
+/// and the `T` removed from the main world is removed from the render world too. A render world
+/// entity left with nothing but its [`MainEntity`], or whose main world entity was despawned, is
+/// despawned.
+pub fn extract_component<T: Component + Clone>(main: &World, render: &mut World) {
+    extract_with(main, render, |value: &T| Some(value.clone()));
+}
+
+/// Same as [`extract_component`] with the render world getting [`ExtractComponent::extract`] run
+/// on the `T` of the main world.
+pub fn extract_mapped<T: ExtractComponent>(main: &World, render: &mut World) {
+    extract_with(main, render, T::extract);
+}
+
+fn extract_with<T: Component, Out: Component>(main: &World, render: &mut World, extract: impl Fn(&T) -> Option<Out>) {
+    render.init_resource::<ExtractedEntities>();
+    render.resource_scope(|render, mut extracted: Mut<ExtractedEntities>| {
+        // Removals first, the index of a despawned entity may already hold a new one.
+        for entity in main.removed::<T>() {
+            // Removals are reported for two frames, the entity may have got a new `T` since.
+            if main.has_component::<T>(entity) {
+                continue;
+            }
+            if let Some(target) = extracted.get(entity) {
+                render.remove_component::<Out>(target);
+                retire(render, &mut extracted, entity, !main.is_alive(entity));
+            }
+        }
+
+        let Some(id) = main.components.id::<T>() else {
+            return;
+        };
+        let storage = main.storages.typed::<T>(id);
+        let (last_run, this_run) = (main.last_change_tick(), main.change_tick());
+        for index in storage.mask().iter() {
+            let (Some(entity), Some(value)) = (main.entities.get(index as u32), storage.get(index)) else {
+                continue;
+            };
+            let target = extracted.get(entity).filter(|target| render.is_alive(*target));
+            let target = match target {
+                Some(target) => {
+                    // Zero sized components keep no ticks, they are only extracted once.
+                    let changed = storage.get_ticks(index).is_some_and(|ticks| ticks.is_changed(last_run, this_run));
+                    if !changed && render.has_component::<Out>(target) {
+                        continue;
+                    }
+                    target
+                }
+                None => {
+                    let target = *render.spawn_entity();
+                    render.add_component(target, MainEntity(entity));
+                    extracted.entities.insert(entity, target);
+                    target
+                }
+            };
+            match extract(value) {
+                Some(out) => drop(render.add_component(target, out)),
+                None => {
+                    render.remove_component::<Out>(target);
+                    retire(render, &mut extracted, entity, false);
+                }
+            }
+        }
+    });
+}
+
+// Despawns the render world entity of `main` if asked to or if it only has its `MainEntity` left.
+fn retire(render: &mut World, extracted: &mut ExtractedEntities, main: Entity, despawn: bool) {
+    let Some(target) = extracted.get(main) else {
+        return;
+    };
+    let components = render.entity_signature(target).map_or(0, |signature| signature.len());
+    if despawn || components <= 1 {
+        render.despawn_entity(target);
+        extracted.entities.remove(&main);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Transform(f32);
+
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Mesh(&'static str);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Visibility(bool);
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Visible;
+
+    impl ExtractComponent for Visibility {
+        type Out = Visible;
+
+        fn extract(&self) -> Option<Visible> {
+            self.0.then_some(Visible)
+        }
+    }
+
+    fn extract_frame(main: &mut World, render: &mut World) {
+        extract_component::<Transform>(main, render);
+        extract_component::<Mesh>(main, render);
+        extract_mapped::<Visibility>(main, render);
+        main.clear_trackers();
+    }
+
+    fn render_entity(render: &World, main: Entity) -> Entity {
+        let target = render.get_resource::<ExtractedEntities>().unwrap().get(main).unwrap();
+        assert_eq!(render.get_component::<MainEntity>(target), Some(&MainEntity(main)));
+        target
+    }
+
+    #[test]
+    fn frames_carry_adds_changes_and_removals() {
+        let mut main = World::new();
+        let mut render = World::new();
+        let ship = *main.spawn_entity();
+        main.add_component(ship, Transform(0.0));
+        main.add_component(ship, Mesh("ship"));
+        main.add_component(ship, Visibility(true));
+        let rock = *main.spawn_entity();
+        main.add_component(rock, Transform(5.0));
+        main.add_component(rock, Visibility(false));
+        // Never extracted, no render entity.
+        let _camera = main.spawn_entity();
+        extract_frame(&mut main, &mut render);
+
+        assert_eq!(render.enities().len(), 2);
+        let ship_render = render_entity(&render, ship);
+        let rock_render = render_entity(&render, rock);
+        assert_eq!(render.get_component::<Mesh>(ship_render), Some(&Mesh("ship")));
+        assert!(render.has_component::<Visible>(ship_render));
+        assert_eq!(render.get_component::<Transform>(rock_render), Some(&Transform(5.0)));
+        assert!(!render.has_component::<Visible>(rock_render));
+
+        main.get_component_mut::<Transform>(ship).unwrap().0 = 1.0;
+        main.get_component_mut::<Visibility>(ship).unwrap().0 = false;
+        main.remove_component::<Mesh>(ship);
+        main.add_component(rock, Mesh("rock"));
+        // The render world copy is overwritten only if the main world changed.
+        render.get_component_mut::<Transform>(rock_render).unwrap().0 = -1.0;
+        extract_frame(&mut main, &mut render);
+
+        assert_eq!(render.get_component::<Transform>(ship_render), Some(&Transform(1.0)));
+        assert!(!render.has_component::<Mesh>(ship_render));
+        assert!(!render.has_component::<Visible>(ship_render));
+        assert_eq!(render.get_component::<Mesh>(rock_render), Some(&Mesh("rock")));
+        assert_eq!(render.get_component::<Transform>(rock_render), Some(&Transform(-1.0)));
+
+        // Losing its last extracted component despawns the render entity.
+        main.remove_component::<Mesh>(rock);
+        main.remove_component::<Transform>(rock);
+        main.remove_component::<Visibility>(rock);
+        extract_frame(&mut main, &mut render);
+        assert!(!render.is_alive(rock_render));
+        assert!(render.is_alive(ship_render));
+        assert_eq!(render.get_resource::<ExtractedEntities>().unwrap().len(), 1);
+        // The removals reported a second frame change nothing.
+        extract_frame(&mut main, &mut render);
+        assert_eq!(render.enities().len(), 1);
+    }
+
+    #[test]
+    fn reused_indices_get_their_own_render_entity() {
+        let mut main = World::new();
+        let mut render = World::new();
+        let old = *main.spawn_entity();
+        main.add_component(old, Transform(1.0));
+        main.add_component(old, Mesh("old"));
+        extract_frame(&mut main, &mut render);
+        let old_render = render_entity(&render, old);
+
+        main.despawn_entity(old);
+        let new = *main.spawn_entity();
+        main.add_component(new, Transform(2.0));
+        assert_eq!(new.index(), old.index());
+        assert_ne!(new, old);
+        extract_frame(&mut main, &mut render);
+
+        assert!(!render.is_alive(old_render));
+        let new_render = render_entity(&render, new);
+        assert_eq!(render.get_component::<Transform>(new_render), Some(&Transform(2.0)));
+        assert!(!render.has_component::<Mesh>(new_render));
+        let extracted = render.get_resource::<ExtractedEntities>().unwrap();
+        assert_eq!(extracted.iter().collect::<Vec<_>>(), vec![(new, new_render)]);
+    }
+}
@@ -0,0 +1,343 @@
+//! Groups keep the components that are always queried together in lockstep, so that the queries
+//! asking for exactly those components walk parallel slices instead of intersecting masks.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use crate::component::{Component, ComponentId, RemovalPolicy, StorageKind};
+use crate::entity::{Entity, EntityMapper, MapEntities};
+use crate::tuples::all_tuples;
+use crate::World;
+
+/// The tuples of components that can be grouped with [`World::register_group`].
+pub trait ComponentGroup {
+    /// Registers the components and moves their values to sparse set storages, returns their ids.
+    fn pack(world: &mut World) -> Vec<ComponentId>;
+}
+
+macro_rules! impl_component_group {
+    ($($name: ident),*) => {
+        impl<$($name: Component),*> ComponentGroup for ($($name,)*) {
+            fn pack(_world: &mut World) -> Vec<ComponentId> {
+                vec![$(_world.pack_storage::<$name>()),*]
+            }
+        }
+    };
+}
+
+all_tuples!(impl_component_group);
+
+// The entities having every component of a group hold the first positions of each of their
+// sparse sets, in the same order: `entities[i]` is at position `i` in all of them.
+struct Group {
+    components: Vec<ComponentId>,
+    entities: Vec<Entity>,
+}
+
+#[derive(Default)]
+pub(crate) struct Groups {
+    groups: Vec<Group>,
+    // The group of each component, by component index.
+    of: Vec<Option<usize>>,
+}
+
+impl Groups {
+    pub(crate) fn of(&self, id: ComponentId) -> Option<usize> {
+        self.of.get(id.index()).copied().flatten()
+    }
+
+    /// Rewrites the members after their entities moved to other indices, they keep their positions.
+    pub(crate) fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        for group in &mut self.groups {
+            group.entities.map_entities(mapper);
+        }
+    }
+
+    /// The members of the group made of exactly `components`, sorted, in position order.
+    pub(crate) fn matching(&self, components: &[ComponentId]) -> Option<&[Entity]> {
+        let group = &self.groups[self.of(*components.first()?)?];
+        (group.components == components).then_some(&*group.entities)
+    }
+}
+
+impl World {
+    /// Groups the components of `G`: the entities having all of them are kept at the front of the
+    /// storages of each component, in the same order. The queries whose required components are
+    /// exactly the group, like `Query<(&A, &mut B)>` or `Query<&A, With<B>>` for `(A, B)`, then
+    /// iterate over the members without checking any mask with [`Query::iter_unordered`], and
+    /// [`Query::iter_chunks_unordered`] gives the components of the whole group as parallel
+    /// slices. Both go in the order of the group, the ordered iterators keep to the index order.
+    ///
+    /// The components move to [`StorageKind::SparseSet`] storages. Every addition or removal of a
+    /// grouped component moves the entity in or out of the group, which costs one swap per
+    /// component of the group, and the other queries on these components pay a lookup per value.
+    ///
+    /// [`Query::iter_unordered`]: crate::query::Query::iter_unordered
+    /// [`Query::iter_chunks_unordered`]: crate::query::Query::iter_chunks_unordered
+    ///
+    /// # Panics
+    ///
+    /// Panics if the group has less than two different components, or if one of them is zero
+    /// sized, already part of another group or registered with [`RemovalPolicy::Tombstone`].
+    pub fn register_group<G: ComponentGroup>(&mut self) {
+        let mut components = G::pack(self);
+        components.sort();
+        components.dedup();
+        assert!(components.len() >= 2, "A group needs at least two different components");
+        for id in &components {
+            let name = self.components.info(*id).unwrap().name();
+            assert!(self.groups.of(*id).is_none(), "Component {} is already grouped", name);
+        }
+        let group = self.groups.groups.len();
+        let end = components.last().unwrap().index() + 1;
+        if self.groups.of.len() < end {
+            self.groups.of.resize(end, None);
+        }
+        for id in &components {
+            self.groups.of[id.index()] = Some(group);
+        }
+        self.groups.groups.push(Group {
+            components,
+            entities: Vec::new(),
+        });
+        let members: Vec<Entity> = self.entities.iter().filter(|e| self.should_join(group, *e)).collect();
+        for entity in members {
+            self.join_group(group, entity);
+        }
+        self.flush_relocations(&[]);
+    }
+
+    // Moves the values of `T` to a sparse set, the storage of the grouped components.
+    fn pack_storage<T: Component>(&mut self) -> ComponentId {
+        assert_ne!(mem::size_of::<T>(), 0, "Zero sized components can't be grouped");
+        let id = self.register_component::<T>();
+        let storage = self.storages.typed_mut::<T>(id);
+        let name = self.components.info(id).unwrap().name();
+        let tombstone = storage.removal_policy() == Some(RemovalPolicy::Tombstone);
+        assert!(!tombstone, "Component {} leaves holes, it can't be grouped", name);
+        storage.make_sparse();
+        self.components.set_storage(id, StorageKind::SparseSet);
+        id
+    }
+
+    /// Moves the entity into the group of `id` if it now has all of its components. To call
+    /// after `id` was added to the entity.
+    pub(crate) fn join_groups(&mut self, entity: Entity, id: ComponentId) {
+        if let Some(group) = self.groups.of(id) {
+            if self.should_join(group, entity) {
+                self.join_group(group, entity);
+            }
+        }
+    }
+
+    /// Moves the entity out of the group of `id`. To call before `id` is removed from the entity.
+    pub(crate) fn leave_groups(&mut self, entity: Entity, id: ComponentId) {
+        let Some(group) = self.groups.of(id) else {
+            return;
+        };
+        let index = entity.index() as usize;
+        let components = &self.groups.groups[group].components;
+        let Some(position) = self.storages.get(components[0]).position(index) else {
+            return;
+        };
+        let entities = &mut self.groups.groups[group].entities;
+        if position >= entities.len() {
+            return;
+        }
+        let last = entities.len() - 1;
+        entities.swap_remove(position);
+        for id in &self.groups.groups[group].components {
+            self.storages.get_mut(*id).swap_positions(position, last);
+        }
+    }
+
+    /// Moves the entity out of every group, before it is despawned.
+    pub(crate) fn leave_all_groups(&mut self, entity: Entity) {
+        for group in 0..self.groups.groups.len() {
+            let id = self.groups.groups[group].components[0];
+            self.leave_groups(entity, id);
+        }
+    }
+
+    fn should_join(&self, group: usize, entity: Entity) -> bool {
+        let index = entity.index() as usize;
+        let group = &self.groups.groups[group];
+        let len = group.entities.len();
+        let mut positions = group.components.iter().map(|id| self.storages.get(*id).position(index));
+        // Members sit before the end of the group in every storage, checking one is enough.
+        positions.next().flatten().is_some_and(|position| position >= len) && positions.all(|p| p.is_some())
+    }
+
+    fn join_group(&mut self, group: usize, entity: Entity) {
+        let index = entity.index() as usize;
+        let Group { components, entities } = &mut self.groups.groups[group];
+        let end = entities.len();
+        for id in components.iter() {
+            let storage = self.storages.get_mut(*id);
+            let position = storage.position(index).unwrap();
+            storage.swap_positions(position, end);
+        }
+        entities.push(entity);
+    }
+
+    // The broken invariants of the groups, with the component involved.
+    pub(crate) fn check_groups(&self) -> Vec<(Option<Entity>, ComponentId, &'static str)> {
+        let mut errors = Vec::new();
+        for group in &self.groups.groups {
+            for (position, entity) in group.entities.iter().enumerate() {
+                for id in &group.components {
+                    if !self.entities.is_alive(*entity) {
+                        errors.push((Some(*entity), *id, "grouped while not alive"));
+                    } else if self.storages.get(*id).position(entity.index() as usize) != Some(position) {
+                        errors.push((Some(*entity), *id, "out of place in its group"));
+                    }
+                }
+            }
+            for entity in self.entities.iter() {
+                if self.should_join(self.groups.of(group.components[0]).unwrap(), entity) {
+                    errors.push((Some(entity), group.components[0], "missing from its group"));
+                }
+            }
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::query::Without;
+    use crate::testing::Rng;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(u32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Velocity(u32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Frozen;
+
+    fn sorted<T: Ord>(mut items: Vec<T>) -> Vec<T> {
+        items.sort();
+        items
+    }
+
+    fn snapshot(world: &mut World) -> Vec<(u32, u32, u32)> {
+        let query = world.query_filtered::<(Entity, &Position, &Velocity), Without<Frozen>>();
+        sorted(query.iter(world).map(|(e, p, v)| (e.index(), p.0, v.0)).collect())
+    }
+
+    #[test]
+    fn interleaved_changes_match_an_ungrouped_world() {
+        for seed in 1..=6u64 {
+            let mut rng = Rng::new(seed);
+            let mut grouped = World::new();
+            let mut plain = World::new();
+            let first = grouped.spawn_batch((0..50).map(Position));
+            plain.spawn_batch((0..50).map(Position));
+            // The plain world spawns the same ids, unpacking them drops the world they come from.
+            let twin = |e: Entity| Entity::from_bits(e.to_bits()).unwrap();
+            for e in first.iter().step_by(3) {
+                grouped.add_component(*e, Velocity(e.index()));
+                plain.add_component(twin(*e), Velocity(e.index()));
+            }
+            grouped.register_group::<(Position, Velocity)>();
+            grouped.validate().unwrap();
+            for step in 0..800u32 {
+                let alive: Vec<Entity> = grouped.entities.iter().collect();
+                let e = alive[rng.below(alive.len())];
+                match rng.below(8) {
+                    0 => {
+                        grouped.add_component(e, Position(step));
+                        plain.add_component(twin(e), Position(step));
+                    }
+                    1 | 2 => {
+                        grouped.add_component(e, Velocity(step));
+                        plain.add_component(twin(e), Velocity(step));
+                    }
+                    3 => {
+                        grouped.remove_component::<Position>(e);
+                        plain.remove_component::<Position>(twin(e));
+                    }
+                    4 => {
+                        grouped.remove_component::<Velocity>(e);
+                        plain.remove_component::<Velocity>(twin(e));
+                    }
+                    5 => {
+                        grouped.add_component(e, Frozen);
+                        plain.add_component(twin(e), Frozen);
+                    }
+                    6 => {
+                        grouped.despawn_entity(e);
+                        plain.despawn_entity(twin(e));
+                    }
+                    _ => {
+                        let spawned = grouped.spawn_batch([Velocity(step)]);
+                        assert_eq!(spawned, plain.spawn_batch([Velocity(step)]));
+                        grouped.add_component(spawned[0], Position(step));
+                        plain.add_component(twin(spawned[0]), Position(step));
+                    }
+                }
+                if step % 50 == 0 {
+                    grouped.validate().unwrap();
+                }
+                assert_eq!(snapshot(&mut grouped), snapshot(&mut plain), "seed {} step {}", seed, step);
+            }
+            grouped.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn group_chunks_cover_the_members() {
+        let mut world = World::new();
+        world.register_group::<(Position, Velocity)>();
+        let entities = world.spawn_batch((0..3000).map(Position));
+        for e in entities.iter().filter(|e| e.index() % 3 != 0) {
+            world.add_component(*e, Velocity(1));
+        }
+        world.despawn_entity(entities[1]);
+        world.remove_component::<Position>(entities[2]);
+
+        let mut state = world.query::<(&mut Position, &Velocity)>();
+        let mut query = state.query_mut(&mut world);
+        let mut members = Vec::new();
+        let mut chunks = 0;
+        for (entities, (positions, velocities)) in query.iter_chunks_unordered() {
+            assert_eq!(entities.len(), positions.len());
+            for (position, velocity) in positions.iter_mut().zip(velocities) {
+                position.0 += velocity.0;
+            }
+            members.extend_from_slice(entities);
+            chunks += 1;
+        }
+        // The members are packed, only the length of a chunk splits them.
+        assert_eq!(members.len(), 1998);
+        assert_eq!(chunks, 2);
+        let expected: Vec<Entity> = entities.iter().copied().filter(|e| e.index() % 3 != 0).skip(2).collect();
+        assert_eq!(sorted(members), expected);
+        for e in &expected {
+            assert_eq!(world.get_component::<Position>(*e), Some(&Position(e.index() + 1)));
+        }
+        assert_eq!(world.get_component::<Position>(entities[3]), Some(&Position(3)));
+        // The other queries see the sparse set out of index order and split their chunks.
+        let mut positions = world.query::<(Entity, &Position)>();
+        let expected: Vec<_> = positions.iter(&world).map(|(e, p)| (e, *p)).collect();
+        assert_eq!(expected.len(), 2998);
+        let mut state = world.query::<&Position>();
+        let mut seen = Vec::new();
+        for (entities, positions) in state.query_mut(&mut world).iter_chunks() {
+            seen.extend(entities.iter().copied().zip(positions.iter().copied()));
+        }
+        assert_eq!(seen, expected);
+        world.validate().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "already grouped")]
+    fn components_belong_to_one_group() {
+        let mut world = World::new();
+        world.register_group::<(Position, Velocity)>();
+        world.register_group::<(Velocity, Position)>();
+    }
+}
@@ -0,0 +1,419 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem;
+use core::ops::Deref;
+
+use crate::commands::Commands;
+use crate::component::Component;
+use crate::entity::{Entity, EntityMapper, MapEntities};
+use crate::query::{Changed, Disabled, IncludeDisabled, Query};
+use crate::system::Removed;
+use crate::utils::HashMap;
+use crate::{EcsError, World};
+
+/// A name for people looking at the world, printed by [`World::debug_hierarchy`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Component)]
+pub struct Name(pub String);
+
+impl Name {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+/// The entity this entity is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Component)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+impl MapEntities for Parent {
+    fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        self.0.map_entities(mapper);
+    }
+}
+
+/// The entities attached to this entity, in insertion order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Component)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Deref for Children {
+    type Target = [Entity];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl MapEntities for Children {
+    fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        self.0.map_entities(mapper);
+    }
+}
+
+/// Marks the entities [`Disabled`] only because an ancestor is, see
+/// [`World::set_enabled_recursive`]. They are enabled again with the ancestor, unlike the ones
+/// disabled on their own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Component)]
+pub struct InheritedDisabled;
+
+/// Updates the [`InheritedDisabled`] entities after the hierarchy changed: the subtrees moved
+/// under another parent or detached since the last run follow their new parent. To add to a
+/// schedule, once.
+pub fn propagate_disabled(
+    moved: Query<Entity, (Changed<Parent>, IncludeDisabled)>,
+    detached: Removed<Parent>,
+    mut commands: Commands,
+) {
+    let mut roots: Vec<Entity> = moved.iter().chain(detached.iter()).collect();
+    if roots.is_empty() {
+        return;
+    }
+    roots.sort_unstable();
+    roots.dedup();
+    commands.add(move |world: &mut World| {
+        for root in roots {
+            if world.is_alive(root) {
+                world.refresh_disabled(root);
+            }
+        }
+    });
+}
+
+impl World {
+    /// Attaches `child` to `parent`, detaching it from its previous parent first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the entities is not alive or if they are the same.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.try_set_parent(child, parent).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Same as [`World::set_parent`] but fails instead of panicking, leaving the hierarchy as it
+    /// was.
+    pub fn try_set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), EcsError> {
+        if child == parent {
+            return Err(EcsError::OwnParent(child));
+        }
+        for entity in [child, parent] {
+            self.entities.check_alive(entity)?;
+        }
+        self.register_map_entities::<Parent>();
+        self.register_map_entities::<Children>();
+        self.remove_parent(child);
+        self.add_component(child, Parent(parent));
+        match self.get_component_mut::<Children>(parent) {
+            Some(mut children) => children.0.push(child),
+            None => {
+                self.add_component(parent, Children(vec![child]));
+            }
+        }
+        Ok(())
+    }
+
+    /// Attaches the children after the other children of `parent`, see
+    /// [`World::insert_children`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the entities is not alive or if `parent` is among the children.
+    pub fn push_children(&mut self, parent: Entity, children: &[Entity]) {
+        self.try_insert_children(parent, usize::MAX, children)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Attaches the children to `parent` in their order, starting at `index` in its children or
+    /// after them if there are less. The children of `parent` are updated once for all of them.
+    ///
+    /// The children attached to another parent are detached from it first, the ones already
+    /// attached to `parent` move to their new place and the duplicates only count once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the entities is not alive or if `parent` is among the children.
+    pub fn insert_children(&mut self, parent: Entity, index: usize, children: &[Entity]) {
+        self.try_insert_children(parent, index, children)
+            .unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Same as [`World::insert_children`] but fails instead of panicking, leaving the hierarchy as
+    /// it was.
+    pub fn try_insert_children(&mut self, parent: Entity, index: usize, children: &[Entity]) -> Result<(), EcsError> {
+        self.entities.check_alive(parent)?;
+        let mut seen = HashMap::default();
+        let mut added = Vec::with_capacity(children.len());
+        for child in children {
+            if *child == parent {
+                return Err(EcsError::OwnParent(parent));
+            }
+            self.entities.check_alive(*child)?;
+            if seen.insert(*child, ()).is_none() {
+                added.push(*child);
+            }
+        }
+        if added.is_empty() {
+            return Ok(());
+        }
+        self.register_map_entities::<Parent>();
+        self.register_map_entities::<Children>();
+        let mut list = match self.get_component_mut::<Children>(parent) {
+            Some(mut children) => mem::take(&mut children.0),
+            None => Vec::new(),
+        };
+        list.retain(|child| !seen.contains_key(child));
+        for child in &added {
+            if self.parent(*child) != Some(parent) {
+                self.remove_parent(*child);
+                self.add_component(*child, Parent(parent));
+            }
+        }
+        let index = index.min(list.len());
+        list.splice(index..index, added);
+        match self.get_component_mut::<Children>(parent) {
+            Some(mut children) => children.0 = list,
+            None => {
+                self.add_component(parent, Children(list));
+            }
+        }
+        Ok(())
+    }
+
+    /// Detaches `child` from its parent, returns the parent it had.
+    pub fn remove_parent(&mut self, child: Entity) -> Option<Entity> {
+        let Parent(parent) = self.remove_component::<Parent>(child)?;
+        if let Some(mut children) = self.get_component_mut::<Children>(parent) {
+            children.0.retain(|e| *e != child);
+            if children.0.is_empty() {
+                self.remove_component::<Children>(parent);
+            }
+        }
+        Some(parent)
+    }
+
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.get_component::<Parent>(entity).map(Parent::get)
+    }
+
+    pub fn children(&self, entity: Entity) -> &[Entity] {
+        self.get_component::<Children>(entity)
+            .map_or(&[], |children| &children.0)
+    }
+
+    /// Disables the entity and its descendants, or enables them again, returns false if it is not
+    /// alive. The descendants disabled on their own stay disabled, along with their descendants,
+    /// and the entity itself stays disabled while an ancestor is.
+    ///
+    /// The flags are only updated by this walk and by [`propagate_disabled`], the entities
+    /// attached to another parent keep theirs until it runs.
+    pub fn set_enabled_recursive(&mut self, entity: Entity, enabled: bool) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.remove_component::<InheritedDisabled>(entity);
+        match enabled {
+            true => {
+                self.remove_component::<Disabled>(entity);
+            }
+            false => {
+                if !self.has_component::<Disabled>(entity) {
+                    self.add_component(entity, Disabled);
+                }
+            }
+        }
+        self.refresh_disabled(entity);
+        true
+    }
+
+    // Sets the inherited flags of the subtree from the parent of `root`, the entities disabled on
+    // their own keep their flag.
+    pub(crate) fn refresh_disabled(&mut self, root: Entity) {
+        let inherited = self.parent(root).is_some_and(|parent| self.has_component::<Disabled>(parent));
+        let mut stack = vec![(root, inherited)];
+        while let Some((entity, inherited)) = stack.pop() {
+            let flagged = self.has_component::<InheritedDisabled>(entity);
+            let disabled = self.has_component::<Disabled>(entity);
+            let own = disabled && !flagged;
+            if !own && inherited {
+                if !disabled {
+                    self.add_component(entity, Disabled);
+                }
+                if !flagged {
+                    self.add_component(entity, InheritedDisabled);
+                }
+            } else if !own && flagged {
+                self.remove_component::<Disabled>(entity);
+                self.remove_component::<InheritedDisabled>(entity);
+            }
+            let disabled = own || inherited;
+            stack.extend(self.children(entity).iter().map(|child| (*child, disabled)));
+        }
+    }
+
+    /// Despawns the entity and all of its descendants, returns false if it was not alive.
+    pub fn despawn_recursive(&mut self, entity: Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        self.remove_parent(entity);
+        let mut stack = vec![entity];
+        while let Some(current) = stack.pop() {
+            if let Some(Children(children)) = self.remove_component::<Children>(current) {
+                stack.extend(children);
+            }
+            self.despawn_entity(current);
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::QueryState;
+    use crate::system::Schedule;
+
+    fn enabled(world: &World, state: &QueryState<Entity>) -> Vec<Entity> {
+        state.iter(world).collect()
+    }
+
+    #[test]
+    fn reparenting() {
+        let mut world = World::new();
+        let a = *world.spawn_entity();
+        let b = *world.spawn_entity();
+        let child = *world.spawn_entity();
+        world.set_parent(child, a);
+        assert_eq!(world.children(a), &[child]);
+        world.set_parent(child, b);
+        assert_eq!(world.parent(child), Some(b));
+        assert!(world.children(a).is_empty());
+        assert!(!world.has_component::<Children>(a));
+        assert_eq!(world.children(b), &[child]);
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn despawn_subtree() {
+        let mut world = World::new();
+        let root = *world.spawn_entity();
+        let child = *world.spawn_entity();
+        let grandchild = *world.spawn_entity();
+        let other = *world.spawn_entity();
+        world.set_parent(root, other);
+        world.set_parent(child, root);
+        world.set_parent(grandchild, child);
+        assert!(world.despawn_recursive(root));
+        assert!(!world.is_alive(child) && !world.is_alive(grandchild));
+        assert!(world.children(other).is_empty());
+        assert_eq!(world.enities().len(), 1);
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn bulk_children_keep_their_order() {
+        let mut world = World::new();
+        let parent = *world.spawn_entity();
+        let old = *world.spawn_entity();
+        let kids: Vec<Entity> = (0..6).map(|_| *world.spawn_entity()).collect();
+        world.push_children(old, &kids[4..]);
+        world.entity_mut(parent).push_children(&[kids[0], kids[1], kids[0]]);
+        assert_eq!(world.children(parent), [kids[0], kids[1]]);
+
+        // Re-parented children leave their previous parent.
+        world.insert_children(parent, 1, &[kids[5], kids[2], kids[3]]);
+        assert_eq!(world.children(parent), [kids[0], kids[5], kids[2], kids[3], kids[1]]);
+        assert_eq!(world.children(old), [kids[4]]);
+        assert_eq!(world.parent(kids[5]), Some(parent));
+
+        // Children already there move, the index counts the others.
+        world.insert_children(parent, 0, &[kids[1], kids[4]]);
+        assert_eq!(world.children(parent), [kids[1], kids[4], kids[0], kids[5], kids[2], kids[3]]);
+        assert!(!world.has_component::<Children>(old));
+        world.insert_children(parent, 100, &[kids[1]]);
+        assert_eq!(world.children(parent).last(), Some(&kids[1]));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn bulk_children_errors() {
+        let mut world = World::new();
+        let parent = *world.spawn_entity();
+        let child = *world.spawn_entity();
+        let dead = *world.spawn_entity();
+        world.despawn_entity(dead);
+        assert_eq!(world.try_insert_children(parent, 0, &[child, parent]), Err(EcsError::OwnParent(parent)));
+        assert_eq!(world.try_insert_children(parent, 0, &[child, dead]), Err(EcsError::EntityNotAlive(dead)));
+        assert_eq!(world.try_insert_children(dead, 0, &[child]), Err(EcsError::EntityNotAlive(dead)));
+        // Nothing changed on failure.
+        assert_eq!(world.parent(child), None);
+        assert!(world.children(parent).is_empty());
+        assert_eq!(world.try_insert_children(parent, 0, &[]), Ok(()));
+        assert!(!world.has_component::<Children>(parent));
+    }
+
+    #[test]
+    fn enabling_again_keeps_what_was_disabled_on_its_own() {
+        let mut world = World::new();
+        let [panel, button, icon, label] = [(); 4].map(|_| *world.spawn_entity());
+        world.push_children(panel, &[button, label]);
+        world.set_parent(icon, button);
+        let visible = world.query::<Entity>();
+
+        assert!(world.set_enabled_recursive(label, false));
+        assert_eq!(enabled(&world, &visible), [panel, button, icon]);
+        world.set_enabled_recursive(panel, false);
+        assert!(enabled(&world, &visible).is_empty());
+        assert!(world.has_component::<InheritedDisabled>(icon));
+        assert!(!world.has_component::<InheritedDisabled>(label));
+
+        // The icon can't be enabled while its ancestors are disabled.
+        world.set_enabled_recursive(icon, true);
+        assert!(enabled(&world, &visible).is_empty());
+        world.set_enabled_recursive(panel, true);
+        assert_eq!(enabled(&world, &visible), [panel, button, icon]);
+        world.set_enabled_recursive(label, true);
+        assert_eq!(enabled(&world, &visible), [panel, button, icon, label]);
+        assert_eq!(world.query::<&InheritedDisabled>().iter(&world).count(), 0);
+        world.despawn_recursive(label);
+        assert!(!world.set_enabled_recursive(label, false));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn moved_subtrees_follow_their_new_parent() {
+        let mut world = World::new();
+        let [root, panel, child, grandchild, hidden] = [(); 5].map(|_| *world.spawn_entity());
+        world.set_parent(child, panel);
+        world.push_children(child, &[grandchild, hidden]);
+        world.set_enabled_recursive(hidden, false);
+        world.set_enabled_recursive(panel, false);
+        let mut schedule = Schedule::new();
+        schedule.add_system(propagate_disabled);
+        schedule.run(&mut world);
+        let visible = world.query::<Entity>();
+        assert_eq!(enabled(&world, &visible), [root]);
+
+        // Under an enabled parent only the entities disabled on their own stay disabled.
+        world.set_parent(child, root);
+        assert_eq!(enabled(&world, &visible), [root]);
+        schedule.run(&mut world);
+        assert_eq!(enabled(&world, &visible), [root, child, grandchild]);
+
+        // And back under a disabled one, then detached.
+        world.set_parent(grandchild, panel);
+        world.push_children(grandchild, &[hidden]);
+        schedule.run(&mut world);
+        assert_eq!(enabled(&world, &visible), [root, child]);
+        assert!(world.has_component::<InheritedDisabled>(grandchild));
+        world.remove_parent(grandchild);
+        schedule.run(&mut world);
+        assert_eq!(enabled(&world, &visible), [root, child, grandchild]);
+        assert!(!world.has_component::<InheritedDisabled>(hidden) && world.has_component::<Disabled>(hidden));
+        world.validate().unwrap();
+    }
+}
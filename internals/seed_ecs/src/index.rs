@@ -0,0 +1,168 @@
+//! Finding the entities by the value of one of their components.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::{type_name, Any};
+use core::hash::Hash;
+
+use crate::component::{Component, ComponentId};
+use crate::entity::Entity;
+use crate::utils::BVec;
+use crate::World;
+
+#[derive(Default)]
+pub(crate) struct Indexes {
+    indexes: Vec<(ComponentId, Box<dyn Any + Send + Sync>)>,
+}
+
+#[cfg(feature = "std")]
+impl Indexes {
+    fn get_mut<T: 'static>(&mut self, id: ComponentId) -> Option<&mut ValueIndex<T>> {
+        let (_, index) = self.indexes.iter_mut().find(|(other, _)| *other == id)?;
+        index.downcast_mut()
+    }
+}
+
+// The key each entity is filed under is kept by entity index, to find its entry again once the
+// value changed or left.
+#[cfg(feature = "std")]
+struct ValueIndex<T> {
+    keys: BVec<(Entity, T)>,
+    entities: std::collections::HashMap<T, Vec<Entity>>,
+}
+
+#[cfg(feature = "std")]
+impl<T: Hash + Eq + Clone> ValueIndex<T> {
+    fn new() -> Self {
+        Self { keys: BVec::new(), entities: Default::default() }
+    }
+
+    fn insert(&mut self, entity: Entity, key: T) {
+        self.entities.entry(key.clone()).or_default().push(entity);
+        self.keys.insert(entity.index() as usize, (entity, key));
+    }
+
+    fn remove(&mut self, index: usize) {
+        let Some((entity, key)) = self.keys.remove(index) else {
+            return;
+        };
+        let entities = self.entities.get_mut(&key).unwrap();
+        let position = entities.iter().position(|other| *other == entity).unwrap();
+        entities.swap_remove(position);
+        if entities.is_empty() {
+            self.entities.remove(&key);
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl World {
+    /// Keeps the entities with a `T` findable by its value, see [`World::lookup_by_value`].
+    /// Indexing again does nothing.
+    ///
+    /// Every insertion, removal and mutable borrow of a `T` marks its entity, written or not, and
+    /// the next lookup clones and hashes the value of each marked entity again. A component that
+    /// systems go through mutably every frame is rehashed whole at every lookup, the index suits
+    /// the values that rarely change like names and ids.
+    pub fn add_index<T: Component + Hash + Eq + Clone>(&mut self) {
+        let id = self.register_component::<T>();
+        if self.indexes.get_mut::<T>(id).is_some() {
+            return;
+        }
+        let storage = self.storages.typed_mut::<T>(id);
+        storage.track_touched();
+        let mut index = ValueIndex::new();
+        for entity_index in storage.mask().iter() {
+            let entity = self.entities.get(entity_index as u32).unwrap();
+            index.insert(entity, storage.get(entity_index).unwrap().clone());
+        }
+        self.indexes.indexes.push((id, Box::new(index)));
+    }
+
+    /// The entities whose `T` equals `value`, in no particular order. Files the values marked
+    /// since the last lookup again first.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` isn't indexed, see [`World::add_index`].
+    pub fn lookup_by_value<T: Component + Hash + Eq + Clone>(
+        &mut self,
+        value: &T,
+    ) -> impl Iterator<Item = Entity> + '_ {
+        let index = self
+            .components
+            .id::<T>()
+            .and_then(|id| Some((id, self.indexes.get_mut::<T>(id)?)));
+        let Some((id, index)) = index else {
+            panic!("{} isn't indexed", type_name::<T>());
+        };
+        let storage = self.storages.typed_mut::<T>(id);
+        for entity_index in storage.take_touched().iter() {
+            index.remove(entity_index);
+            let entity = self.entities.get(entity_index as u32);
+            if let (Some(value), Some(entity)) = (storage.get(entity_index), entity) {
+                index.insert(entity, value.clone());
+            }
+        }
+        index.entities.get(value).into_iter().flatten().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hierarchy::Name;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component)]
+    struct Team(u32);
+
+    fn sorted(entities: impl Iterator<Item = Entity>) -> Vec<Entity> {
+        let mut entities: Vec<Entity> = entities.collect();
+        entities.sort();
+        entities
+    }
+
+    #[test]
+    fn lookups_follow_every_change_of_the_key() {
+        let mut world = World::new();
+        let before = *world.spawn_entity();
+        world.add_component(before, Name::new("before"));
+        world.add_index::<Name>();
+        let after = *world.spawn_entity();
+        world.add_component(after, Name::new("after"));
+        assert_eq!(sorted(world.lookup_by_value(&Name::new("before"))), [before]);
+        assert_eq!(sorted(world.lookup_by_value(&Name::new("after"))), [after]);
+
+        world.get_component_mut::<Name>(after).unwrap().0 = "renamed".into();
+        assert_eq!(world.lookup_by_value(&Name::new("after")).count(), 0);
+        assert_eq!(sorted(world.lookup_by_value(&Name::new("renamed"))), [after]);
+        let mut names = world.query::<&mut Name>();
+        for mut name in names.iter_mut(&mut world) {
+            name.0.push('!');
+        }
+        assert_eq!(sorted(world.lookup_by_value(&Name::new("renamed!"))), [after]);
+
+        world.remove_component::<Name>(before);
+        assert_eq!(world.lookup_by_value(&Name::new("before!")).count(), 0);
+        world.despawn_entity(after);
+        assert_eq!(world.lookup_by_value(&Name::new("renamed!")).count(), 0);
+        let reused = *world.spawn_entity();
+        world.add_component(reused, Name::new("renamed!"));
+        assert_eq!(sorted(world.lookup_by_value(&Name::new("renamed!"))), [reused]);
+    }
+
+    #[test]
+    fn equal_values_map_to_every_entity() {
+        let mut world = World::new();
+        world.add_index::<Team>();
+        let entities: Vec<Entity> = world.spawn_batch((0..10).map(|i| Team(i % 3)));
+        let red: Vec<Entity> = entities.iter().copied().step_by(3).collect();
+        assert_eq!(sorted(world.lookup_by_value(&Team(0))), red);
+
+        world.add_component(red[1], Team(1));
+        world.despawn_entity(red[2]);
+        assert_eq!(sorted(world.lookup_by_value(&Team(0))), [red[0], red[3]]);
+        assert_eq!(world.lookup_by_value(&Team(1)).count(), 4);
+        assert_eq!(world.lookup_by_value(&Team(7)).count(), 0);
+    }
+}
@@ -0,0 +1,374 @@
+//! Components holding a handle to a value kept in an arena resource, for the large values like
+//! `Inventory(Vec<Item>)` that snapshots and duplicates would otherwise copy every time.
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem;
+
+use crate::change_detection::Mut;
+use crate::component::{Component, ComponentId};
+use crate::entity::Entity;
+use crate::observer::{DeferredWorld, OnRemove, Trigger};
+use crate::World;
+
+// The arena looks for values without handles once it holds this many slots, then each time it
+// doubled since.
+const FIRST_RECLAIM: usize = 64;
+
+struct Slot<T> {
+    value: Option<T>,
+    // Cloned by every handle to the value, the arena keeps one. Replaced when the value is freed,
+    // so the handles left behind don't match the next value of the slot.
+    owners: Arc<()>,
+}
+
+/// The resource holding the values of the [`Indirect<T>`] components, registered by
+/// [`World::register_indirect`].
+///
+/// A value is freed when the last component pointing to it is removed or despawned. The values
+/// whose last handle was dropped another way, replaced by a new one for instance, are freed by
+/// [`IndirectArena::reclaim`], which the arena runs itself as it grows.
+pub struct IndirectArena<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<u32>,
+    next_reclaim: usize,
+}
+
+/// The counts of an [`IndirectArena`], see [`World::arena_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Number of slots allocated, used or free.
+    pub slots: usize,
+    /// Number of values stored.
+    pub values: usize,
+    /// Number of values more than one handle points to.
+    pub shared: usize,
+    /// Number of values no handle points to anymore, freed by the next reclaim.
+    pub unreferenced: usize,
+}
+
+impl<T> IndirectArena<T> {
+    pub fn new() -> Self {
+        Self {
+            slots: Vec::new(),
+            free: Vec::new(),
+            next_reclaim: FIRST_RECLAIM,
+        }
+    }
+
+    /// Stores `value` in a free slot and returns the first handle to it.
+    pub fn insert(&mut self, value: T) -> Indirect<T> {
+        if self.free.is_empty() && self.slots.len() >= self.next_reclaim {
+            self.reclaim();
+            self.next_reclaim = 2 * self.slots.len();
+        }
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    value: None,
+                    owners: Arc::new(()),
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.value = Some(value);
+        Indirect {
+            index,
+            owner: slot.owners.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Number of values stored.
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Frees the values no handle points to anymore and the free slots at the end, and returns
+    /// how many values were freed.
+    pub fn reclaim(&mut self) -> usize {
+        let unreferenced: Vec<u32> = (0..self.slots.len() as u32).filter(|index| self.handles(*index) == Some(0)).collect();
+        for index in &unreferenced {
+            self.free_slot(*index);
+        }
+        while self.slots.last().is_some_and(|slot| slot.value.is_none()) {
+            self.slots.pop();
+        }
+        let len = self.slots.len() as u32;
+        self.free.retain(|index| *index < len);
+        unreferenced.len()
+    }
+
+    pub fn stats(&self) -> ArenaStats {
+        let handles = || (0..self.slots.len() as u32).filter_map(|index| self.handles(index));
+        ArenaStats {
+            slots: self.slots.len(),
+            values: self.len(),
+            shared: handles().filter(|count| *count > 1).count(),
+            unreferenced: handles().filter(|count| *count == 0).count(),
+        }
+    }
+
+    // Number of handles to the value of the slot, `None` if the slot is free.
+    fn handles(&self, index: u32) -> Option<usize> {
+        let slot = &self.slots[index as usize];
+        slot.value.as_ref().map(|_| Arc::strong_count(&slot.owners) - 1)
+    }
+
+    // The slot of the value of the handle, `None` if the value was freed.
+    fn slot(&self, handle: &Indirect<T>) -> Option<&Slot<T>> {
+        let slot = self.slots.get(handle.index as usize)?;
+        Some(slot).filter(|slot| Arc::ptr_eq(&slot.owners, &handle.owner))
+    }
+
+    // True if the value of the handle has no other handle.
+    fn is_last(&self, handle: &Indirect<T>) -> bool {
+        self.slot(handle).is_some() && !handle.is_shared()
+    }
+
+    fn free_slot(&mut self, index: u32) {
+        let slot = &mut self.slots[index as usize];
+        slot.value = None;
+        slot.owners = Arc::new(());
+        self.free.push(index);
+    }
+
+    // Drops the handle, and its value if it was the last one.
+    fn release(&mut self, handle: Indirect<T>) {
+        if self.is_last(&handle) {
+            self.free_slot(handle.index);
+        }
+    }
+}
+
+impl<T> Default for IndirectArena<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A component standing for a `T` stored in the [`IndirectArena<T>`] of the world.
+///
+/// Cloning a handle shares the value, which is only copied once one of the handles writes to it
+/// with [`Indirect::get_mut`]. Duplicating an entity clones its handles, see
+/// [`World::register_clone`].
+///
+/// ```
+/// # use seed_ecs::World;
+/// # use seed_ecs::Indirect;
+/// #[derive(Clone)]
+/// struct Inventory(Vec<u32>);
+///
+/// let mut world = World::new();
+/// let player = *world.spawn_entity();
+/// world.insert_indirect(player, Inventory(vec![1, 2, 3]));
+/// let snapshot = world.duplicate_entity(player).unwrap();
+/// world.get_indirect_mut::<Inventory>(player).unwrap().0.push(4);
+/// assert_eq!(world.get_indirect::<Inventory>(snapshot).unwrap().0, [1, 2, 3]);
+/// assert_eq!(world.arena_stats::<Inventory>().unwrap().values, 2);
+/// ```
+#[derive(Component)]
+pub struct Indirect<T> {
+    index: u32,
+    owner: Arc<()>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Indirect<T> {
+    /// The value, `None` if it was freed, when the handle was taken out of its entity with
+    /// [`World::remove_component`] for instance.
+    pub fn get<'a>(&self, arena: &'a IndirectArena<T>) -> Option<&'a T> {
+        arena.slot(self)?.value.as_ref()
+    }
+
+    /// The value, copied first into a slot of its own if other handles share it.
+    pub fn get_mut<'a>(&mut self, arena: &'a mut IndirectArena<T>) -> Option<&'a mut T>
+    where
+        T: Clone,
+    {
+        let slot = arena.slot(self)?;
+        if self.is_shared() {
+            let copy = slot.value.clone().unwrap();
+            // Dropping the old handle leaves the value to the others.
+            drop(mem::replace(self, arena.insert(copy)));
+        }
+        arena.slots[self.index as usize].value.as_mut()
+    }
+
+    /// True if other handles point to the same value.
+    pub fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.owner) > 2
+    }
+}
+
+impl<T> Clone for Indirect<T> {
+    fn clone(&self) -> Self {
+        Self {
+            index: self.index,
+            owner: self.owner.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl World {
+    /// Adds the [`IndirectArena<T>`] resource and frees its values along with their last
+    /// component. Done by [`World::insert_indirect`] too, registering again does nothing.
+    pub fn register_indirect<T: Send + Sync + 'static>(&mut self) -> ComponentId {
+        if !self.contains_resource::<IndirectArena<T>>() {
+            self.insert_resource(IndirectArena::<T>::new());
+            self.register_clone::<Indirect<T>>();
+            self.add_observer(|trigger: Trigger<OnRemove<Indirect<T>>>, world: &mut DeferredWorld| {
+                let index = {
+                    let world = world.world();
+                    let handle = world.get_component::<Indirect<T>>(trigger.entity());
+                    match (handle, world.get_resource::<IndirectArena<T>>()) {
+                        (Some(handle), Some(arena)) if arena.is_last(handle) => handle.index,
+                        _ => return,
+                    }
+                };
+                world.get_resource_mut::<IndirectArena<T>>().unwrap().free_slot(index);
+            });
+        }
+        self.register_component::<Indirect<T>>()
+    }
+
+    /// Stores `value` in the arena and adds the handle to the entity, replacing the one it had.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive, like [`World::add_component`].
+    pub fn insert_indirect<T: Send + Sync + 'static>(&mut self, entity: Entity, value: T) {
+        self.register_indirect::<T>();
+        let handle = self.get_resource_mut::<IndirectArena<T>>().unwrap().insert(value);
+        if let Some(previous) = self.add_component(entity, handle) {
+            self.get_resource_mut::<IndirectArena<T>>().unwrap().release(previous);
+        }
+    }
+
+    /// The value the [`Indirect<T>`] of the entity points to.
+    pub fn get_indirect<T: Send + Sync + 'static>(&self, entity: Entity) -> Option<&T> {
+        self.get_component::<Indirect<T>>(entity)?.get(self.get_resource()?)
+    }
+
+    /// Mutable version of [`World::get_indirect`] copying the value first if other handles share
+    /// it, see [`Indirect::get_mut`]. Marks the component changed.
+    pub fn get_indirect_mut<T: Clone + Send + Sync + 'static>(&mut self, entity: Entity) -> Option<&mut T> {
+        self.entities.check_alive(entity).ok()?;
+        let id = self.components.id::<Indirect<T>>()?;
+        let (arena, _) = self.resources.get_mut::<IndirectArena<T>>()?;
+        let storage = self.storages.typed_mut::<Indirect<T>>(id);
+        let (handle, ticks) = storage.get_with_ticks_mut(entity.index() as usize)?;
+        let mut handle = match ticks {
+            Some(ticks) => Mut::with_ticks(handle, ticks, self.last_change_tick, self.change_tick),
+            None => Mut::new(handle),
+        };
+        handle.get_mut(arena)
+    }
+
+    /// The counts of the [`IndirectArena<T>`], `None` before [`World::register_indirect`].
+    pub fn arena_stats<T: Send + Sync + 'static>(&self) -> Option<ArenaStats> {
+        Some(self.get_resource::<IndirectArena<T>>()?.stats())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Inventory(Vec<u32>);
+
+    #[test]
+    fn removals_and_despawns_free_the_slots() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..3).map(|_| *world.spawn_entity()).collect();
+        for (i, e) in entities.iter().enumerate() {
+            world.insert_indirect(*e, Inventory(vec![i as u32]));
+        }
+        let stats = world.arena_stats::<Inventory>().unwrap();
+        assert_eq!((stats.slots, stats.values), (3, 3));
+        let removed = world.remove_component::<Indirect<Inventory>>(entities[0]).unwrap();
+        world.despawn_entity(entities[1]);
+        assert_eq!(world.arena_stats::<Inventory>().unwrap().values, 1);
+        // The handle taken out lost its value with the component.
+        assert_eq!(removed.get(world.get_resource().unwrap()), None);
+        // The freed slots are reused.
+        let e = *world.spawn_entity();
+        world.insert_indirect(e, Inventory(vec![9]));
+        assert_eq!(world.get_indirect::<Inventory>(e), Some(&Inventory(vec![9])));
+        assert_eq!(world.get_indirect::<Inventory>(entities[2]), Some(&Inventory(vec![2])));
+        assert_eq!(world.arena_stats::<Inventory>().unwrap().slots, 3);
+    }
+
+    #[test]
+    fn snapshots_share_until_written() {
+        let mut world = World::new();
+        let player = *world.spawn_entity();
+        world.insert_indirect(player, Inventory(vec![1, 2]));
+        let snapshot = world.duplicate_entity(player).unwrap();
+        let stats = world.arena_stats::<Inventory>().unwrap();
+        assert_eq!((stats.values, stats.shared), (1, 1));
+        assert!(world.get_component::<Indirect<Inventory>>(player).unwrap().is_shared());
+
+        world.clear_trackers();
+        world.get_indirect_mut::<Inventory>(player).unwrap().0.push(3);
+        let stats = world.arena_stats::<Inventory>().unwrap();
+        assert_eq!((stats.values, stats.shared), (2, 0));
+        assert_eq!(world.get_indirect::<Inventory>(player), Some(&Inventory(vec![1, 2, 3])));
+        assert_eq!(world.get_indirect::<Inventory>(snapshot), Some(&Inventory(vec![1, 2])));
+        let changed = world.query_filtered::<Entity, crate::query::Changed<Indirect<Inventory>>>();
+        assert_eq!(changed.iter(&world).collect::<Vec<_>>(), [player]);
+        // The value is not shared anymore, writing again doesn't copy it.
+        world.get_indirect_mut::<Inventory>(player).unwrap().0.push(4);
+        assert_eq!(world.arena_stats::<Inventory>().unwrap().slots, 2);
+        world.despawn_entity(snapshot);
+        assert_eq!(world.arena_stats::<Inventory>().unwrap().values, 1);
+    }
+
+    #[test]
+    fn churn_leaves_no_value_behind() {
+        let mut world = World::new();
+        world.register_indirect::<Inventory>();
+        let baseline = world.arena_stats::<Inventory>().unwrap();
+        let mut entities: Vec<Entity> = Vec::new();
+        for step in 0..2000u32 {
+            match step % 5 {
+                0 | 1 => {
+                    let e = *world.spawn_entity();
+                    world.insert_indirect(e, Inventory(vec![step]));
+                    entities.push(e);
+                }
+                2 => {
+                    let e = entities[step as usize % entities.len()];
+                    if let Some(copy) = world.duplicate_entity(e) {
+                        entities.push(copy);
+                    }
+                }
+                // Replacing a handle drops the old one without removing the component.
+                3 => {
+                    let e = entities[step as usize % entities.len()];
+                    world.insert_indirect(e, Inventory(vec![step]));
+                }
+                _ => {
+                    let e = entities.swap_remove(step as usize % entities.len());
+                    world.despawn_entity(e);
+                }
+            }
+        }
+        let stats = world.arena_stats::<Inventory>().unwrap();
+        assert_eq!((stats.values, stats.unreferenced), (world.query::<&Indirect<Inventory>>().iter(&world).count(), 0));
+        assert!(stats.slots < 2000);
+        world.despawn_batch(&entities);
+        let mut arena = world.get_resource_mut::<IndirectArena<Inventory>>().unwrap();
+        arena.reclaim();
+        assert_eq!(arena.stats(), baseline);
+    }
+}
@@ -0,0 +1,388 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug};
+
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::hierarchy::Name;
+use crate::storage::{AnyStorage, Storage};
+use crate::World;
+
+fn debug_component<T: Debug + 'static>(storage: &dyn AnyStorage, index: usize) -> Option<String> {
+    let storage = storage
+        .as_any()
+        .downcast_ref::<Storage<T>>()
+        .expect("Storage type mismatch");
+    storage.get(index).map(|value| format!("{:?}", value))
+}
+
+/// A component found by [`World::inspect_entity`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentInspection {
+    pub name: &'static str,
+    /// The `Debug` output of the value, `None` if the type didn't register one.
+    pub value: Option<String>,
+}
+
+/// Everything a world knows about an entity, printable for debugging.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EntityInspection {
+    pub entity: Entity,
+    pub alive: bool,
+    /// The components in registration order, empty if the entity is dead.
+    pub components: Vec<ComponentInspection>,
+}
+
+impl fmt::Display for EntityInspection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.entity)?;
+        if !self.alive {
+            return writeln!(f, ": dead");
+        }
+        writeln!(f, ": {} components", self.components.len())?;
+        for component in &self.components {
+            match &component.value {
+                Some(value) => writeln!(f, "  {}: {}", component.name, value)?,
+                None => writeln!(f, "  {}: <no debug>", component.name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl World {
+    /// Lets the world print `T` when inspecting entities.
+    pub fn register_debug<T: Component + Debug>(&mut self) {
+        let id = self.register_component::<T>();
+        self.components.set_debug(id, debug_component::<T>);
+    }
+
+    pub fn inspect_entity(&self, entity: Entity) -> EntityInspection {
+        let alive = self.is_alive(entity);
+        let index = entity.index() as usize;
+        let components = self
+            .components
+            .iter()
+            .filter(|info| alive && self.storages.get(info.id()).contains(index))
+            .map(|info| ComponentInspection {
+                name: info.name(),
+                value: info.debug_fn().and_then(|debug| debug(self.storages.get(info.id()), index)),
+            })
+            .collect();
+        EntityInspection {
+            entity,
+            alive,
+            components,
+        }
+    }
+
+    /// Writes the inspection of every live entity, stopping after `max_entities` of them.
+    pub fn inspect_all(&self, writer: &mut impl fmt::Write, max_entities: usize) -> fmt::Result {
+        for entity in self.entities.iter().take(max_entities) {
+            write!(writer, "{}", self.inspect_entity(entity))?;
+        }
+        let remaining = self.entities.len().saturating_sub(max_entities);
+        if remaining > 0 {
+            writeln!(writer, "... {} more entities", remaining)?;
+        }
+        Ok(())
+    }
+}
+
+/// How much of the hierarchy [`World::debug_hierarchy_with`] prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HierarchyLimits {
+    /// The depth below which children are only counted, roots are at depth 0.
+    pub max_depth: usize,
+    /// The roots and the children per entity printed, the others are only counted.
+    pub max_width: usize,
+}
+
+impl Default for HierarchyLimits {
+    fn default() -> Self {
+        Self {
+            max_depth: 16,
+            max_width: 64,
+        }
+    }
+}
+
+// The entities from the root to the one being printed, kept on the stack.
+struct Ancestors<'a> {
+    entity: Entity,
+    parent: Option<&'a Ancestors<'a>>,
+}
+
+impl Ancestors<'_> {
+    fn contains(&self, entity: Entity) -> bool {
+        self.entity == entity || self.parent.is_some_and(|parent| parent.contains(entity))
+    }
+}
+
+// Writes the type name without the module paths, `Vec<alloc::string::String>` as `Vec<String>`.
+fn write_short_name(writer: &mut impl fmt::Write, name: &str) -> fmt::Result {
+    let mut rest = name;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| !(c.is_alphanumeric() || c == '_' || c == ':')).unwrap_or(rest.len());
+        let (path, tail) = rest.split_at(end);
+        writer.write_str(path.rsplit("::").next().unwrap_or(path))?;
+        let next = tail.chars().next().map_or(0, char::len_utf8);
+        writer.write_str(&tail[..next])?;
+        rest = &tail[next..];
+    }
+    Ok(())
+}
+
+impl World {
+    /// Writes the tree of each root entity, with the name, id and component types of every entity,
+    /// see [`World::debug_hierarchy_with`].
+    pub fn debug_hierarchy(&self, writer: &mut impl fmt::Write) -> fmt::Result {
+        self.debug_hierarchy_with(writer, HierarchyLimits::default())
+    }
+
+    /// Writes the tree of each root entity, indented by depth. The entities whose parent is dead
+    /// or doesn't list them are printed as roots and flagged, so are the parents' cycles, printed
+    /// from their lowest index. The children that are dead, that have another parent or that are
+    /// their own ancestors are flagged too.
+    ///
+    /// Allocates nothing besides what the writer does.
+    pub fn debug_hierarchy_with(&self, writer: &mut impl fmt::Write, limits: HierarchyLimits) -> fmt::Result {
+        let mut printed = 0;
+        let mut skipped = 0;
+        for entity in self.entities.iter() {
+            let Some(flag) = self.root_flag(entity) else {
+                continue;
+            };
+            if printed == limits.max_width {
+                skipped += 1;
+                continue;
+            }
+            printed += 1;
+            self.write_tree(writer, entity, flag, None, 0, limits)?;
+        }
+        if skipped > 0 {
+            writeln!(writer, "... {} more roots", skipped)?;
+        }
+        Ok(())
+    }
+
+    // Why the entity is printed as a root, `None` if it is printed under its parent.
+    fn root_flag(&self, entity: Entity) -> Option<Option<&'static str>> {
+        let Some(parent) = self.parent(entity) else {
+            return Some(None);
+        };
+        if !self.is_alive(parent) {
+            return Some(Some("parent is dead"));
+        }
+        if !self.children(parent).contains(&entity) {
+            return Some(Some("missing from the children of its parent"));
+        }
+        // Walks up to a root, a chain longer than the world loops.
+        let mut lowest = entity;
+        let mut current = parent;
+        for _ in 0..self.entities.len() {
+            if current == entity {
+                return (lowest == entity).then_some(Some("parent cycle"));
+            }
+            match self.parent(current) {
+                Some(next) if self.is_alive(next) && self.children(next).contains(&current) => {
+                    lowest = lowest.min(current);
+                    current = next;
+                }
+                _ => return None,
+            }
+        }
+        // Hangs below a cycle, printed from it.
+        None
+    }
+
+    fn write_tree(
+        &self,
+        writer: &mut impl fmt::Write,
+        entity: Entity,
+        flag: Option<&str>,
+        ancestors: Option<&Ancestors<'_>>,
+        depth: usize,
+        limits: HierarchyLimits,
+    ) -> fmt::Result {
+        write!(writer, "{:1$}{2}", "", depth * 2, entity)?;
+        if !self.is_alive(entity) {
+            return writeln!(writer, " !dead");
+        }
+        if let Some(Name(name)) = self.get_component::<Name>(entity) {
+            write!(writer, " {:?}", name)?;
+        }
+        writer.write_str(" [")?;
+        let index = entity.index() as usize;
+        let components = self.components.iter().filter(|info| self.storages.get(info.id()).contains(index));
+        for (i, info) in components.enumerate() {
+            if i > 0 {
+                writer.write_str(", ")?;
+            }
+            write_short_name(writer, info.name())?;
+        }
+        writer.write_str("]")?;
+        if let Some(flag) = flag {
+            write!(writer, " !{}", flag)?;
+        }
+        if ancestors.is_some_and(|ancestors| ancestors.contains(entity)) {
+            return writeln!(writer, " !cycle");
+        }
+        writeln!(writer)?;
+
+        let children = self.children(entity);
+        if depth == limits.max_depth {
+            if !children.is_empty() {
+                writeln!(writer, "{:1$}... {2} children", "", (depth + 1) * 2, children.len())?;
+            }
+            return Ok(());
+        }
+        let ancestors = Ancestors {
+            entity,
+            parent: ancestors,
+        };
+        for child in children.iter().take(limits.max_width) {
+            let flag = match self.parent(*child) {
+                Some(parent) if parent == entity => None,
+                _ => Some("has another parent"),
+            };
+            self.write_tree(writer, *child, flag, Some(&ancestors), depth + 1, limits)?;
+        }
+        if children.len() > limits.max_width {
+            writeln!(writer, "{:1$}... {2} more children", "", (depth + 1) * 2, children.len() - limits.max_width)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hierarchy::{Children, Parent};
+
+    #[derive(Debug, Component)]
+    struct Health(u32);
+    #[derive(Component)]
+    struct Opaque;
+
+    #[test]
+    fn inspect_small_entity() {
+        let mut world = World::new();
+        world.register_debug::<Health>();
+        world.register_debug::<Name>();
+        world.spawn_entity();
+        let orc = *world.spawn_entity();
+        world.add_component(orc, Health(10));
+        world.add_component(orc, Name::new("orc"));
+        world.add_component(orc, Opaque);
+
+        let expected = format!(
+            "Entity(1v1): 3 components\n  {}: Health(10)\n  {}: Name(\"orc\")\n  {}: <no debug>\n",
+            std::any::type_name::<Health>(),
+            std::any::type_name::<Name>(),
+            std::any::type_name::<Opaque>(),
+        );
+        assert_eq!(world.inspect_entity(orc).to_string(), expected);
+
+        world.despawn_entity(orc);
+        let dead = world.inspect_entity(orc);
+        assert!(!dead.alive && dead.components.is_empty());
+        assert_eq!(dead.to_string(), "Entity(1v1): dead\n");
+    }
+
+    #[test]
+    fn hierarchy_golden() {
+        let mut world = World::new();
+        let ui = *world.spawn_entity();
+        let panel = *world.spawn_entity();
+        let button = *world.spawn_entity();
+        let label = *world.spawn_entity();
+        let stray = *world.spawn_entity();
+        let gone = *world.spawn_entity();
+        world.add_component(ui, Name::new("ui"));
+        world.add_component(panel, Name::new("panel"));
+        world.add_component(button, Health(3));
+        world.add_component(label, Opaque);
+        world.push_children(ui, &[panel]);
+        world.push_children(panel, &[button, label]);
+        world.set_parent(stray, gone);
+        world.despawn_entity(gone);
+        // Despawning doesn't touch the components pointing at the entity.
+        assert_eq!(world.parent(stray), Some(gone));
+
+        let mut tree = String::new();
+        world.debug_hierarchy(&mut tree).unwrap();
+        let expected = "\
+Entity(0v1) \"ui\" [Name, Children]
+  Entity(1v1) \"panel\" [Name, Parent, Children]
+    Entity(2v1) [Health, Parent]
+    Entity(3v1) [Opaque, Parent]
+Entity(4v1) [Parent] !parent is dead
+";
+        assert_eq!(tree, expected);
+    }
+
+    #[test]
+    fn hierarchy_is_capped() {
+        let mut world = World::new();
+        let root = *world.spawn_entity();
+        let mut parent = root;
+        for _ in 0..5 {
+            let child = *world.spawn_entity();
+            world.set_parent(child, parent);
+            parent = child;
+        }
+        let leaves = [*world.spawn_entity(), *world.spawn_entity()];
+        world.push_children(root, &leaves);
+        world.spawn_entity();
+
+        let mut tree = String::new();
+        let limits = HierarchyLimits {
+            max_depth: 2,
+            max_width: 2,
+        };
+        world.debug_hierarchy_with(&mut tree, limits).unwrap();
+        let expected = "\
+Entity(0v1) [Children]
+  Entity(1v1) [Parent, Children]
+    Entity(2v1) [Parent, Children]
+      ... 1 children
+  Entity(6v1) [Parent]
+  ... 1 more children
+Entity(8v1) []
+";
+        assert_eq!(tree, expected);
+    }
+
+    #[test]
+    fn hierarchy_cycles_are_flagged() {
+        let mut world = World::new();
+        let a = *world.spawn_entity();
+        let b = *world.spawn_entity();
+        world.set_parent(b, a);
+        // Only a broken world has cycles.
+        world.add_component(a, Parent(b));
+        world.add_component(b, Children(vec![a]));
+        let mut tree = String::new();
+        world.debug_hierarchy(&mut tree).unwrap();
+        assert_eq!(tree, "\
+Entity(0v1) [Parent, Children] !parent cycle
+  Entity(1v1) [Parent, Children]
+    Entity(0v1) [Parent, Children] !cycle
+");
+    }
+
+    #[test]
+    fn inspect_all_is_capped() {
+        let mut world = World::new();
+        for _ in 0..5 {
+            world.spawn_entity();
+        }
+        let mut dump = String::new();
+        world.inspect_all(&mut dump, 2).unwrap();
+        assert_eq!(
+            dump,
+            "Entity(0v1): 0 components\nEntity(1v1): 0 components\n... 3 more entities\n"
+        );
+    }
+}
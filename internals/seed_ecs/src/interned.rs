@@ -0,0 +1,348 @@
+//! Storing one copy of the component values many entities hold equal, see
+//! [`World::register_interned`].
+
+use alloc::vec::Vec;
+use core::hash::{Hash, Hasher};
+use core::mem;
+
+use crate::change_detection::{ComponentTicks, Tick};
+use crate::component::Component;
+use crate::utils::{BVec, HashMap};
+
+/// The operations on `T` an interned storage needs, captured where `T` is known to support them.
+pub(crate) struct InternOps<T> {
+    hash: fn(&T) -> u64,
+    eq: fn(&T, &T) -> bool,
+    clone: fn(&T) -> T,
+}
+
+impl<T> Clone for InternOps<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for InternOps<T> {}
+
+#[cfg(feature = "std")]
+impl<T: Hash + Eq + Clone> InternOps<T> {
+    pub fn new() -> Self {
+        fn hash<T: Hash>(value: &T) -> u64 {
+            // Not seeded, every storage of `T` hashes alike on every thread.
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+        Self { hash: hash::<T>, eq: T::eq, clone: T::clone }
+    }
+}
+
+struct Slot<T> {
+    value: T,
+    users: u32,
+    // The hash the value is listed under in `shared`, `None` once it was lent mutably.
+    hash: Option<u64>,
+}
+
+/// The values of an interned storage: each entity holds the handle of a slot, and the entities
+/// with equal values share the slot. Lending a shared value mutably copies it to a slot of its
+/// own first, which stays unlisted since its value may change anytime.
+pub(crate) struct Interned<T> {
+    // The slot of the value of each entity, by entity index.
+    handles: BVec<u32>,
+    ticks: BVec<ComponentTicks>,
+    // There are never more slots than entities, a `BVec` holds them all and never moves them.
+    slots: BVec<Slot<T>>,
+    // The listed slots by the hash of their value.
+    shared: HashMap<u64, Vec<u32>>,
+    ops: InternOps<T>,
+}
+
+impl<T> Interned<T> {
+    pub fn new(ops: InternOps<T>) -> Self {
+        Self {
+            handles: BVec::new(),
+            ticks: BVec::new(),
+            slots: BVec::new(),
+            shared: HashMap::default(),
+            ops,
+        }
+    }
+
+    /// An empty storage for the same type.
+    pub fn empty(&self) -> Self {
+        Self::new(self.ops)
+    }
+
+    pub fn handles(&self) -> &BVec<u32> {
+        &self.handles
+    }
+
+    pub fn ticks(&self) -> &BVec<ComponentTicks> {
+        &self.ticks
+    }
+
+    pub fn ticks_mut(&mut self) -> &mut BVec<ComponentTicks> {
+        &mut self.ticks
+    }
+
+    /// Number of distinct values stored.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Stores `value` at `index` and returns the value that was there before if any, a copy of it
+    /// if other entities still share it. The value is added at `tick`, or changed at `tick` if it
+    /// replaces another one.
+    pub fn insert(&mut self, index: usize, value: T, tick: Tick) -> Option<T> {
+        let mut ticks = self.ticks.get(index).copied().unwrap_or(ComponentTicks::new(tick));
+        ticks.changed = tick;
+        let previous = self.take(index);
+        let slot = self.intern(value);
+        self.handles.insert(index, slot);
+        self.ticks.insert(index, ticks);
+        previous
+    }
+
+    // The slot of a value equal to `value`, a new one if there is none.
+    fn intern(&mut self, value: T) -> u32 {
+        let hash = (self.ops.hash)(&value);
+        let listed = self.shared.get(&hash).into_iter().flatten();
+        let equal = listed.copied().find(|slot| (self.ops.eq)(&self.slots.get(*slot as usize).unwrap().value, &value));
+        if let Some(slot) = equal {
+            self.slots.get_mut(slot as usize).unwrap().users += 1;
+            return slot;
+        }
+        let slot = Slot { value, users: 1, hash: Some(hash) };
+        let Ok((slot, _)) = self.slots.insert_first_empty(slot) else {
+            unreachable!("There are never more values than entities");
+        };
+        self.shared.entry(hash).or_default().push(slot as u32);
+        slot as u32
+    }
+
+    // Takes the slot out of `shared`, its value is about to change or to be dropped.
+    fn unlist(&mut self, slot: u32) {
+        let Some(hash) = self.slots.get_mut(slot as usize).unwrap().hash.take() else {
+            return;
+        };
+        let slots = self.shared.get_mut(&hash).unwrap();
+        slots.retain(|other| *other != slot);
+        if slots.is_empty() {
+            self.shared.remove(&hash);
+        }
+    }
+
+    // Stops the entity at `index` from using its slot, and returns the value if it was the last
+    // user.
+    fn release(&mut self, index: usize) -> Option<Option<T>> {
+        let slot = self.handles.remove(index)?;
+        self.ticks.remove(index);
+        let entry = self.slots.get_mut(slot as usize).unwrap();
+        entry.users -= 1;
+        if entry.users != 0 {
+            return Some(None);
+        }
+        self.unlist(slot);
+        Some(Some(self.slots.remove(slot as usize).unwrap().value))
+    }
+
+    /// Removes the value at `index`, copied if other entities still share it.
+    pub fn take(&mut self, index: usize) -> Option<T> {
+        let slot = *self.handles.get(index)?;
+        let value = self.release(index)?;
+        Some(value.unwrap_or_else(|| (self.ops.clone)(&self.slots.get(slot as usize).unwrap().value)))
+    }
+
+    /// Removes the value at `index` without copying it, returns false if there was none.
+    pub fn remove(&mut self, index: usize) -> bool {
+        self.release(index).is_some()
+    }
+
+    /// Moves the handle at `from` to the empty `to`.
+    pub fn relocate(&mut self, from: usize, to: usize) -> bool {
+        let Some(slot) = self.handles.remove(from) else {
+            return false;
+        };
+        self.handles.insert(to, slot);
+        if let Some(ticks) = self.ticks.remove(from) {
+            self.ticks.insert(to, ticks);
+        }
+        true
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let slot = *self.handles.get(index)?;
+        Some(&self.slots.get(slot as usize).unwrap().value)
+    }
+
+    /// The value at `index`, moved to a slot of its own first if it is shared.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        let slot = self.make_unique(index)?;
+        Some(&mut self.slots.get_mut(slot as usize).unwrap().value)
+    }
+
+    /// Same as [`Interned::get_mut`] with the ticks of the value.
+    pub fn get_with_ticks_mut(&mut self, index: usize) -> Option<(&mut T, &mut ComponentTicks)> {
+        let slot = self.make_unique(index)?;
+        let value = &mut self.slots.get_mut(slot as usize).unwrap().value;
+        Some((value, self.ticks.get_mut(index).unwrap()))
+    }
+
+    // Gives the entity at `index` a slot no other entity uses and returns it.
+    fn make_unique(&mut self, index: usize) -> Option<u32> {
+        let slot = *self.handles.get(index)?;
+        let entry = self.slots.get_mut(slot as usize).unwrap();
+        if entry.users == 1 {
+            self.unlist(slot);
+            return Some(slot);
+        }
+        entry.users -= 1;
+        let value = (self.ops.clone)(&entry.value);
+        let Ok((unique, _)) = self.slots.insert_first_empty(Slot { value, users: 1, hash: None }) else {
+            unreachable!("There are never more values than entities");
+        };
+        self.handles.insert(index, unique as u32);
+        Some(unique as u32)
+    }
+
+    /// Every value with its ticks, each moved to a slot of its own first.
+    pub fn iter_with_ticks_mut(&mut self) -> impl Iterator<Item = (usize, &mut T, &mut ComponentTicks)> + '_ {
+        let indices: Vec<usize> = self.handles.mask().iter().collect();
+        for index in indices {
+            self.make_unique(index);
+        }
+        let slots: *mut BVec<Slot<T>> = &mut self.slots;
+        let handles = self.handles.iter().zip(self.ticks.iter_mut());
+        // Every entity has a slot of its own now, the values lent are all distinct.
+        handles.map(move |((index, slot), (_, ticks))| {
+            let value = unsafe { &mut (*slots).get_mut(*slot as usize).unwrap().value };
+            (index, value, ticks)
+        })
+    }
+
+    pub fn reserve(&mut self, len: usize) {
+        self.handles.reserve(len);
+        self.ticks.reserve(len);
+    }
+
+    pub fn reserve_mask(&mut self, len: usize) {
+        self.handles.reserve_mask(len);
+    }
+
+    pub fn allocated_bytes(&self) -> usize {
+        let shared = self.shared.len() * (mem::size_of::<u64>() + mem::size_of::<Vec<u32>>());
+        self.handles.allocated_bytes() + self.ticks.allocated_bytes() + self.slots.allocated_bytes() + shared
+    }
+
+    pub fn compact(&mut self) {
+        self.handles.compact();
+        self.ticks.compact();
+        self.slots.compact();
+    }
+}
+
+#[cfg(feature = "std")]
+impl crate::World {
+    /// Stores the values of `T` once per distinct value, the entities holding equal values share
+    /// it. For the big components many entities hold alike, like the description of a mesh.
+    ///
+    /// Reading is as fast as for the other storages. Inserting hashes the value, and borrowing
+    /// it mutably copies it out of the shared slot unless the entity is its only user; the copy
+    /// isn't shared again, even once equal to another value. Iterating mutably over every `T`
+    /// copies all the shared values. Returns the id of `T`, moving its values if it had some.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero sized or if its values are in a group, see
+    /// [`World::register_group`](crate::World::register_group).
+    pub fn register_interned<T: Component + Hash + Eq + Clone>(&mut self) -> crate::component::ComponentId {
+        assert_ne!(mem::size_of::<T>(), 0, "Zero sized components can't be interned");
+        let id = self.register_component::<T>();
+        self.storages.typed_mut::<T>(id).make_interned(InternOps::new());
+        self.components.set_storage(id, crate::component::StorageKind::Interned);
+        id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::{Component, StorageKind};
+    use crate::entity::Entity;
+    use crate::World;
+
+    #[derive(Debug, Clone, PartialEq, Eq, Hash, Component)]
+    struct Mesh {
+        vertices: Vec<[u32; 3]>,
+    }
+
+    fn cube() -> Mesh {
+        Mesh { vertices: vec![[1, 2, 3]; 170] }
+    }
+
+    #[test]
+    fn equal_values_share_one_slot() {
+        let mut world = World::new();
+        let id = world.register_interned::<Mesh>();
+        assert_eq!(world.components().info(id).unwrap().storage(), StorageKind::Interned);
+        let cubes = world.spawn_batch((0..1000).map(|_| cube()));
+        let stats = world.storage_stats::<Mesh>();
+        assert_eq!((stats.live, stats.distinct_values), (1000, 1));
+        assert!(stats.bytes_allocated < 1000 * 170 * 12 / 10, "{:?}", stats);
+
+        let query = world.query::<&Mesh>();
+        assert!(query.iter(&world).all(|mesh| *mesh == cube()));
+        assert_eq!(world.get_component::<Mesh>(cubes[0]), world.get_component::<Mesh>(cubes[999]));
+    }
+
+    #[test]
+    fn writes_copy_the_value_for_that_entity_only() {
+        let mut world = World::new();
+        world.register_interned::<Mesh>();
+        let cubes = world.spawn_batch((0..3).map(|_| cube()));
+        world.get_component_mut::<Mesh>(cubes[1]).unwrap().vertices.push([0; 3]);
+
+        assert_eq!(world.get_component::<Mesh>(cubes[0]), Some(&cube()));
+        assert_eq!(world.get_component::<Mesh>(cubes[1]).unwrap().vertices.len(), 171);
+        assert_eq!(world.get_component::<Mesh>(cubes[2]), Some(&cube()));
+        assert_eq!(world.storage_stats::<Mesh>().distinct_values, 2);
+
+        let mut meshes = world.query::<(Entity, &mut Mesh)>();
+        for (entity, mut mesh) in meshes.iter_mut(&mut world) {
+            if entity == cubes[2] {
+                mesh.vertices.clear();
+            }
+        }
+        assert_eq!(world.get_component::<Mesh>(cubes[0]), Some(&cube()));
+        assert!(world.get_component::<Mesh>(cubes[2]).unwrap().vertices.is_empty());
+        assert_eq!(world.storage_stats::<Mesh>().distinct_values, 3);
+    }
+
+    #[test]
+    fn the_last_user_drops_the_value() {
+        let mut world = World::new();
+        let before = *world.spawn_entity();
+        world.add_component(before, cube());
+        world.register_interned::<Mesh>();
+        let cubes = world.spawn_batch((0..3).map(|_| cube()));
+        let empty = *world.spawn_entity();
+        world.add_component(empty, Mesh { vertices: Vec::new() });
+        assert_eq!(world.storage_stats::<Mesh>().distinct_values, 2);
+
+        assert_eq!(world.remove_component::<Mesh>(before), Some(cube()));
+        world.despawn_batch(&cubes[..2]);
+        assert_eq!(world.storage_stats::<Mesh>().distinct_values, 2);
+        world.despawn_entity(cubes[2]);
+        assert_eq!(world.storage_stats::<Mesh>().distinct_values, 1);
+        // Replacing the value releases the previous one.
+        world.add_component(empty, cube());
+        let stats = world.storage_stats::<Mesh>();
+        assert_eq!((stats.live, stats.distinct_values), (1, 1));
+        world.remove_component::<Mesh>(empty);
+        assert_eq!(world.storage_stats::<Mesh>().distinct_values, 0);
+        // A value freed is shared again by the next equal insert.
+        let again = world.spawn_batch((0..2).map(|_| cube()));
+        assert_eq!(world.storage_stats::<Mesh>().distinct_values, 1);
+        assert_eq!(world.get_component::<Mesh>(again[1]), Some(&cube()));
+    }
+}
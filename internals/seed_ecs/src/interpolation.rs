@@ -0,0 +1,120 @@
+//! The value components had at the previous simulation step, for rendering in between steps.
+
+use core::ops::{Deref, DerefMut};
+
+use crate::commands::Commands;
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::query::{Query, With, Without};
+use crate::system::Schedule;
+
+/// The value of the entity's `T` when [`capture_previous`] last ran.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Component)]
+pub struct Previous<T>(pub T);
+
+impl<T> Deref for Previous<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Previous<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Copies every `T` into the [`Previous<T>`] of its entity, inserting it through the commands
+/// the first time, and removes the `Previous<T>` of the entities that lost their `T`.
+///
+/// `T` is only read so it isn't marked changed. Runs at the start of each simulation step, see
+/// [`Schedule::track_previous`].
+pub fn capture_previous<T: Component + Clone>(
+    mut commands: Commands,
+    mut current: Query<(Entity, &T, Option<&mut Previous<T>>)>,
+    lost: Query<Entity, (With<Previous<T>>, Without<T>)>,
+) {
+    for (entity, value, previous) in current.iter_mut() {
+        match previous {
+            Some(mut previous) => previous.0.clone_from(value),
+            None => drop(commands.insert(entity, Previous(value.clone()))),
+        }
+    }
+    for entity in lost.iter() {
+        commands.remove::<Previous<T>>(entity);
+    }
+}
+
+impl Schedule {
+    /// Adds [`capture_previous::<T>`] to the schedule, before the systems that change `T` in a
+    /// schedule running the simulation steps.
+    pub fn track_previous<T: Component + Clone>(&mut self) -> &mut Self {
+        self.add_system(capture_previous::<T>)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Changed;
+    use crate::system::ResMut;
+    use crate::World;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Transform(f32);
+    struct Step(f32);
+
+    fn simulate(mut query: Query<&mut Transform>, mut step: ResMut<Step>) {
+        step.0 += 1.0;
+        for mut transform in query.iter_mut() {
+            transform.0 = step.0;
+        }
+    }
+
+    #[test]
+    fn previous_holds_the_value_of_the_last_step() {
+        let mut world = World::new();
+        world.insert_resource(Step(0.0));
+        let ship = *world.spawn_entity();
+        let rock = *world.spawn_entity();
+        world.add_component(ship, Transform(0.0));
+        world.add_component(rock, Transform(0.0));
+        let mut fixed_update = Schedule::new();
+        fixed_update.track_previous::<Transform>().add_system(simulate);
+
+        fixed_update.run(&mut world);
+        assert_eq!(world.get_component::<Previous<Transform>>(ship), Some(&Previous(Transform(0.0))));
+        fixed_update.run(&mut world);
+        assert_eq!(world.get_component::<Transform>(ship), Some(&Transform(2.0)));
+        assert_eq!(world.get_component::<Previous<Transform>>(ship), Some(&Previous(Transform(1.0))));
+
+        world.remove_component::<Transform>(rock);
+        world.clear_trackers();
+        fixed_update.run(&mut world);
+        assert!(!world.has_component::<Previous<Transform>>(rock));
+        assert_eq!(world.get_component::<Previous<Transform>>(ship), Some(&Previous(Transform(2.0))));
+    }
+
+    struct Changes(usize);
+
+    #[test]
+    fn capturing_does_not_change_the_values() {
+        let mut world = World::new();
+        world.insert_resource(Changes(0));
+        let ship = *world.spawn_entity();
+        world.add_component(ship, Transform(1.0));
+        let mut fixed_update = Schedule::new();
+        fixed_update
+            .track_previous::<Transform>()
+            .add_system(|changed: Query<Entity, Changed<Transform>>, mut changes: ResMut<Changes>| {
+                changes.0 = changed.iter().count();
+            });
+        fixed_update.run(&mut world);
+        assert_eq!(world.get_resource::<Changes>().unwrap().0, 1);
+        fixed_update.run(&mut world);
+        assert_eq!(world.get_resource::<Changes>().unwrap().0, 0);
+        assert_eq!(world.get_component::<Previous<Transform>>(ship), Some(&Previous(Transform(1.0))));
+    }
+}
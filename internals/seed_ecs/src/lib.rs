@@ -1,31 +1,1153 @@
 #![allow(dead_code, unused)]
-use std::any::{TypeId, Any};
-use std::alloc::Layout;
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
+#[macro_use]
+extern crate alloc;
+// The derives name the crate by its path, implemented here too.
+extern crate self as seed_ecs;
 
-use entity::{Entities, Entity};
+use alloc::vec::Vec;
+use core::any::{type_name, TypeId, Any};
+use core::alloc::Layout;
+use core::ptr::NonNull;
 
+use change_detection::{Mut, RemovedComponents, Tick, CHECK_TICK_THRESHOLD};
+use component::{Component, ComponentId, Components, StorageKind};
+use dangling::{DanglingCleanups, DespawnEvents};
+use entity::{Entities, Entity, EntityWeak, GrowthPolicy, IndexReuse, SpawnAtError, WorldId};
+use group::Groups;
+use index::Indexes;
+use observer::{ObserverKind, Observers};
+use partition::Partitions;
+use relation::Relations;
+use relocation::Relocations;
+use replication::Replication;
+use resource::Resources;
+use storage::{Storage, Storages};
+use utils::BMask;
+
+pub mod arena;
+mod bundle;
+pub mod change_detection;
+pub mod commands;
+pub mod component;
+mod dangling;
+mod defaults;
+mod defragment;
+mod duplicate;
+mod dynamic;
 pub mod entity;
+mod entity_ref;
+mod error;
+pub mod event;
+pub mod extract;
+mod group;
+pub mod hierarchy;
+mod index;
+mod indirect;
+mod interned;
+mod inspect;
+pub mod interpolation;
+mod merge;
+mod metrics;
+pub mod observer;
+mod partition;
+pub mod prelude;
+mod pool;
+mod prefab;
+pub mod query;
+mod read_only;
+pub mod recorder;
+pub mod relation;
+mod relocation;
+mod replace;
+mod replication;
+pub mod reflect;
+mod resource;
+pub mod scene;
+mod signature;
+mod storage;
+pub mod system;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+#[cfg(feature = "trace")]
+pub mod trace;
+mod tuples;
 mod utils;
+mod validate;
+mod weak;
+
+pub use bundle::{Bundle, DynamicBundle};
+pub use dangling::{ClearDanglingReferences, DanglingCleanup, Despawned, EntityDespawned};
+pub use duplicate::{DuplicateError, DuplicateOptions};
+pub use dynamic::{DynamicQuery, DynamicQueryBuilder};
+pub use entity_ref::{EntityMut, EntityRef};
+pub use error::EcsError;
+pub use group::ComponentGroup;
+pub use indirect::{ArenaStats, Indirect, IndirectArena};
+pub use inspect::{ComponentInspection, EntityInspection, HierarchyLimits};
+pub use merge::{MergeError, ResourceMergePolicy};
+pub use metrics::{ComponentChurn, StructuralMetrics, StructuralReport};
+pub use partition::PartitionId;
+pub use pool::{Pool, PoolExhaustion};
+pub use prefab::Prefab;
+pub use read_only::ReadOnlyWorld;
+#[cfg(feature = "std")]
+pub use read_only::WorldScope;
+pub use weak::WeakRefs;
+pub use relocation::StorageRelocation;
+pub use replication::ReplicationDiff;
+pub use resource::{FromWorld, ResourceInfo, ResourceOverrideGuard};
+pub use storage::StorageStats;
+pub use validate::WorldInvariantError;
+
+/// The items the derive macros name from the crates using them.
+#[doc(hidden)]
+pub mod __private {
+    pub use alloc::vec::Vec;
+
+    pub use crate::__impl_component as impl_component;
+}
+
+/// The impl written by `#[derive(Component)]`, dropped with `loose-components` so that the
+/// derive and the blanket impl don't overlap.
+#[cfg(not(feature = "loose-components"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_component {
+    ($($impl: tt)*) => {
+        $($impl)*
+    };
+}
 
+#[cfg(feature = "loose-components")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __impl_component {
+    ($($impl: tt)*) => {};
+}
+
+/// Dropping a world drops the components of its entities first and its resources after them, the
+/// ones marked with [`World::set_resource_drop_last`] last, so that components can hold handles
+/// into a resource. No observer runs then, see [`World::clear_all`].
 pub struct World {
     entities: Entities,
+    components: Components,
+    // Fields drop in order, the storages must stay before the resources.
+    storages: Storages,
+    resources: Resources,
+    observers: Observers,
+    relations: Relations,
+    groups: Groups,
+    partitions: Partitions,
+    change_tick: Tick,
+    last_change_tick: Tick,
+    last_check_tick: Tick,
+    removed: RemovedComponents,
+    replication: Replication,
+    despawned: DespawnEvents,
+    dangling: DanglingCleanups,
+    relocations: Relocations,
+    weak_refs: WeakRefs,
+    indexes: Indexes,
+    metrics: StructuralMetrics,
+    // The system a schedule is running, named in the access conflicts.
+    running_system: Option<&'static str>,
+}
+
+/// The counts of a world, [`World::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WorldStats {
+    /// Number of entities alive.
+    pub entities: usize,
+    /// Number of entities alive at once at most, see [`Entities::max_entities`].
+    pub max_entities: usize,
+    /// Number of entities that can still be spawned, despawns give it back.
+    pub headroom: usize,
+    /// Number of freed indices waiting to be reused.
+    pub free_indices: usize,
+    pub components: usize,
+    pub resources: usize,
 }
 
 impl World {
     pub fn new() -> Self {
         Self {
             entities: Entities::init(),
+            components: Components::default(),
+            storages: Storages::default(),
+            resources: Resources::default(),
+            observers: Observers::default(),
+            relations: Relations::default(),
+            groups: Groups::default(),
+            partitions: Partitions::default(),
+            change_tick: Tick::new(1),
+            last_change_tick: Tick::new(0),
+            last_check_tick: Tick::new(0),
+            removed: RemovedComponents::default(),
+            replication: Replication::default(),
+            despawned: DespawnEvents::default(),
+            dangling: DanglingCleanups::default(),
+            relocations: Relocations::default(),
+            weak_refs: WeakRefs::default(),
+            indexes: Indexes::default(),
+            metrics: StructuralMetrics::default(),
+            running_system: None,
         }
     }
-    
+
+    /// The name of the system a [`Schedule`](system::Schedule) is running, `None` between
+    /// systems and outside of schedules.
+    pub fn running_system(&self) -> Option<&'static str> {
+        self.running_system
+    }
+
+    /// The tick the changes made outside of systems are recorded at.
+    pub fn change_tick(&self) -> Tick {
+        self.change_tick
+    }
+
+    /// The tick the queries and accessors used outside of systems compare against, see
+    /// [`World::clear_trackers`].
+    pub fn last_change_tick(&self) -> Tick {
+        self.last_change_tick
+    }
+
+    /// Moves to the next tick and returns the current one, systems call it for every run.
+    pub fn increment_change_tick(&mut self) -> Tick {
+        let tick = self.change_tick;
+        self.change_tick = tick.next();
+        tick
+    }
+
+    /// Ends a frame for the code running outside of systems: the changes made so far are not
+    /// reported anymore by the queries and accessors of the world. The removals and despawns of
+    /// the frame before the one ending are forgotten, see [`World::removed`] and
+    /// [`World::despawned`].
+    pub fn clear_trackers(&mut self) {
+        self.last_change_tick = self.increment_change_tick();
+        self.removed.swap();
+        self.despawned.swap();
+    }
+
+    /// Clamps the ticks of every component and resource that are older than
+    /// [`MAX_CHANGE_AGE`](change_detection::MAX_CHANGE_AGE), so that wrapping around doesn't make
+    /// them look new. Only does it if [`CHECK_TICK_THRESHOLD`] ticks passed since the previous
+    /// time, and returns the tick the ages were measured from then.
+    ///
+    /// The ticks of the systems run outside of a [`Schedule`](system::Schedule) must be clamped
+    /// by their owner with [`System::check_change_tick`](system::System::check_change_tick).
+    pub fn check_change_ticks(&mut self) -> Option<Tick> {
+        let tick = self.change_tick;
+        if tick.get().wrapping_sub(self.last_check_tick.get()) < CHECK_TICK_THRESHOLD {
+            return None;
+        }
+        for storage in self.storages.iter_mut() {
+            storage.check_change_ticks(tick);
+        }
+        self.resources.check_change_ticks(tick);
+        self.replication.check_change_ticks(tick);
+        self.dangling.check_change_ticks(tick);
+        self.last_change_tick.check_tick(tick);
+        self.last_check_tick = tick;
+        Some(tick)
+    }
+
+    /// # Panics
+    ///
+    /// Panics if every entity index is used, see [`World::try_spawn_entity`].
     pub fn spawn_entity(&mut self) -> &Entity {
+        self.metrics.spawned(1);
         self.entities.spawn_entity()
     }
 
+    /// Spawns an entity, fails with [`EcsError::EntitiesExhausted`] if every index is alive.
+    pub fn try_spawn_entity(&mut self) -> Result<Entity, EcsError> {
+        let entity = self.entities.try_spawn_entity().copied()?;
+        self.metrics.spawned(1);
+        Ok(entity)
+    }
+
+    /// Changes which freed index spawns reuse first, see [`IndexReuse`].
+    pub fn set_index_reuse(&mut self, reuse: IndexReuse) {
+        self.entities.set_index_reuse(reuse);
+    }
+
+    /// Spawns an entity with a dictated index and generation, see [`Entities::spawn_at`].
+    pub fn spawn_at(&mut self, index: u32, generation: u32) -> Result<Entity, SpawnAtError> {
+        let entity = self.entities.spawn_at(index, generation)?;
+        self.metrics.spawned(1);
+        Ok(entity)
+    }
+
+    /// Spawns an entity for each value of `components`, it is the only component they get.
+    pub fn spawn_batch<T: Component>(&mut self, components: impl IntoIterator<Item = T>) -> Vec<Entity> {
+        let components = components.into_iter();
+        let mut spawned = Vec::with_capacity(components.size_hint().0);
+        for component in components {
+            let entity = *self.spawn_entity();
+            self.add_component(entity, component);
+            spawned.push(entity);
+        }
+        spawned
+    }
+
+    /// Allocates what spawning `additional` more entities needs, see [`Entities::capacity`].
+    pub fn reserve_entities(&mut self, additional: usize) {
+        self.entities.reserve(additional);
+    }
+
+    /// Changes how the entity slots grow when the spawns run out of them, see [`GrowthPolicy`].
+    /// Growing in big chunks, pre-touched, keeps the allocations and the page faults out of the
+    /// frames that spawn a lot.
+    pub fn set_growth_policy(&mut self, policy: GrowthPolicy) {
+        self.entities.set_growth_policy(policy);
+    }
+
+    pub fn growth_policy(&self) -> GrowthPolicy {
+        self.entities.growth_policy()
+    }
+
+    /// Allocates the slots of `capacity` entities and the mask words of the storages already
+    /// registered for as many indices, at load time. Spawning up to `capacity` entities doesn't
+    /// allocate afterwards, adding components to them may still allocate for the values.
+    pub fn grow_to(&mut self, capacity: usize) {
+        self.entities.grow_to(capacity);
+        for storage in self.storages.iter_mut() {
+            storage.reserve_mask(capacity);
+        }
+    }
+
+    /// Allocates the storage of `T` for the live entities and `additional` more, so that adding `T` to
+    /// them doesn't allocate. The capacity shows in [`World::storage_stats`].
+    pub fn reserve_components<T: Component>(&mut self, additional: usize) {
+        let id = self.register_component::<T>();
+        let len = self.entities.index_end() + additional;
+        self.storages.typed_mut::<T>(id).reserve(len);
+    }
+
+    /// Despawns the entity and drops all of its components, returns false if it was not alive.
+    pub fn despawn_entity(&mut self, entity: Entity) -> bool {
+        self.try_despawn_entity(entity).is_ok()
+    }
+
+    /// Despawns the entity and drops all of its components.
+    pub fn try_despawn_entity(&mut self, entity: Entity) -> Result<(), EcsError> {
+        self.entities.check_alive(entity)?;
+        self.trigger_despawn_observers(entity);
+        // An observer may have despawned it already.
+        if !self.entities.despawn_entity(entity) {
+            return Ok(());
+        }
+        self.despawned.push(entity);
+        self.metrics.despawned(1);
+        self.leave_all_groups(entity);
+        self.partitions.forget(entity.index() as usize);
+        for (index, storage) in self.storages.iter_mut().enumerate() {
+            if storage.remove(entity.index() as usize) {
+                self.metrics.removed(ComponentId::new(index), 1);
+                self.removed.push(ComponentId::new(index), entity);
+                self.replication.removed(ComponentId::new(index), entity);
+            }
+        }
+        #[cfg(any(debug_assertions, feature = "strict-checks"))]
+        self.assert_despawned(&[entity]);
+        self.relations.forget(entity);
+        self.weak_refs.despawned(entity);
+        self.flush_relocations(&[entity]);
+        Ok(())
+    }
+
+    /// Despawns the entities like [`World::despawn_entity`] and returns how many were alive. Each
+    /// storage drops their components in one pass, which is cheaper than despawning them one at
+    /// a time.
+    ///
+    /// The observers run for every entity before any of them is despawned.
+    pub fn despawn_batch(&mut self, entities: &[Entity]) -> usize {
+        for entity in entities {
+            if self.is_alive(*entity) {
+                self.trigger_despawn_observers(*entity);
+            }
+        }
+        let mut despawned = Vec::with_capacity(entities.len());
+        for entity in entities {
+            // Observers may have despawned some already, and an entity may be given twice.
+            if self.entities.despawn_entity(*entity) {
+                self.despawned.push(*entity);
+                self.leave_all_groups(*entity);
+                self.partitions.forget(entity.index() as usize);
+                despawned.push(*entity);
+            }
+        }
+        let mut indices: Vec<usize> = despawned.iter().map(|entity| entity.index() as usize).collect();
+        indices.sort_unstable();
+        for (index, storage) in self.storages.iter_mut().enumerate() {
+            let mask = storage.mask();
+            let present = despawned.iter().copied().filter(|entity| mask.is_present(entity.index() as usize));
+            let present: Vec<Entity> = present.collect();
+            if present.is_empty() {
+                continue;
+            }
+            self.metrics.removed(ComponentId::new(index), present.len() as u32);
+            self.removed.extend(ComponentId::new(index), present.iter().copied());
+            self.replication.removed_many(ComponentId::new(index), present);
+            storage.remove_many(&indices);
+        }
+        #[cfg(any(debug_assertions, feature = "strict-checks"))]
+        self.assert_despawned(&despawned);
+        for entity in &despawned {
+            self.relations.forget(*entity);
+            self.weak_refs.despawned(*entity);
+        }
+        self.flush_relocations(&despawned);
+        self.metrics.despawned(despawned.len() as u32);
+        despawned.len()
+    }
+
+    // Catches the storages that kept a value of a despawned entity, which the next entity at its
+    // index would find.
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    fn assert_despawned(&self, entities: &[Entity]) {
+        for index in 0..self.storages.len() {
+            let id = ComponentId::new(index);
+            let storage = self.storages.get(id);
+            if let Some(entity) = entities.iter().find(|entity| storage.contains(entity.index() as usize)) {
+                let name = self.components.info(id).map_or("?", |info| info.name());
+                panic!("The storage of {} still has a value for {:?} after its despawn", name, entity);
+            }
+        }
+    }
+
+    fn trigger_despawn_observers(&mut self, entity: Entity) {
+        if !self.has_observers() {
+            return;
+        }
+        self.trigger_despawn(entity);
+        let present: Vec<ComponentId> = self
+            .components
+            .iter()
+            .map(|info| info.id())
+            .filter(|id| self.storages.get(*id).contains(entity.index() as usize))
+            .collect();
+        for id in present {
+            self.trigger_component(ObserverKind::Remove, id, entity);
+        }
+    }
+
+    /// Despawns every entity, running the observers like [`World::despawn_entity`], then drops the
+    /// resources in the order the world drops them. The entities spawned by the observers are
+    /// despawned too.
+    ///
+    /// Components, observers and groups stay registered, the world can be used again afterwards.
+    pub fn clear_all(&mut self) {
+        while !self.entities.is_empty() {
+            let entities: Vec<Entity> = self.entities.iter().collect();
+            for entity in entities {
+                self.despawn_entity(entity);
+            }
+        }
+        self.resources.clear();
+    }
+
+    /// Unique to this world, stamped into the entities it spawns, see [`Entity::world`].
+    pub fn id(&self) -> WorldId {
+        self.entities.world_id()
+    }
+
+    /// Takes strong and weak handles, see [`EntityWeak`](entity::EntityWeak).
+    pub fn is_alive(&self, entity: impl Into<EntityWeak>) -> bool {
+        self.entities.is_alive(entity.into().entity())
+    }
+
     pub fn enities(&self) -> &Entities {
         &self.entities
     }
 
+    pub fn components(&self) -> &Components {
+        &self.components
+    }
+
+    /// Registers `T` as a component type and creates its storage if needed.
+    pub fn register_component<T: Component>(&mut self) -> ComponentId {
+        if let Some(id) = self.components.id::<T>() {
+            return id;
+        }
+        let id = self.components.register::<T>();
+        // Registration just happened so the info is there.
+        let mut kind = self.components.info(id).unwrap().storage();
+        match T::STORAGE {
+            StorageKind::Dense => {}
+            StorageKind::SparseSet if kind == StorageKind::Dense => {
+                kind = StorageKind::SparseSet;
+                self.components.set_storage(id, kind);
+            }
+            StorageKind::SparseSet => {}
+            other => panic!("Component {} can't choose the {:?} storage", type_name::<T>(), other),
+        }
+        self.storages.push::<T>(id, kind);
+        id
+    }
+
+    /// Adds `component` to the entity and returns the previous value if there was one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entity is not alive.
+    pub fn add_component<T: Component>(&mut self, entity: Entity, component: T) -> Option<T> {
+        self.try_add_component(entity, component).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Adds `component` to the entity and returns the previous value if there was one.
+    pub fn try_add_component<T: Component>(
+        &mut self,
+        entity: Entity,
+        component: T,
+    ) -> Result<Option<T>, EcsError> {
+        self.entities.check_alive(entity)?;
+        let id = self.register_component::<T>();
+        let previous = self.storages.typed_mut::<T>(id).insert(entity.index() as usize, component, self.change_tick);
+        if previous.is_none() {
+            self.metrics.inserted(id, 1);
+            self.join_groups(entity, id);
+            self.flush_relocations(&[]);
+            self.trigger_component(ObserverKind::Add, id, entity);
+        }
+        Ok(previous)
+    }
+
+    pub fn get_component<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.try_get_component(entity).ok()
+    }
+
+    /// The component of the entity, telling apart a dead entity from a missing component.
+    pub fn try_get_component<T: Component>(&self, entity: Entity) -> Result<&T, EcsError> {
+        self.entities.check_alive(entity)?;
+        let missing = EcsError::MissingComponent {
+            entity,
+            type_name: type_name::<T>(),
+        };
+        let id = self.components.id::<T>().ok_or(missing)?;
+        self.storages.typed::<T>(id).get(entity.index() as usize).ok_or(missing)
+    }
+
+    /// The component of the entity, marked changed when it is written to.
+    pub fn get_component_mut<T: Component>(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+        self.try_get_component_mut(entity).ok()
+    }
+
+    /// Same as [`World::get_component_mut`], telling apart a dead entity from a missing component.
+    pub fn try_get_component_mut<T: Component>(&mut self, entity: Entity) -> Result<Mut<'_, T>, EcsError> {
+        const { component::assert_mutable::<T>() };
+        self.entities.check_alive(entity)?;
+        let missing = EcsError::MissingComponent {
+            entity,
+            type_name: type_name::<T>(),
+        };
+        let id = self.components.id::<T>().ok_or(missing)?;
+        let storage = self.storages.typed_mut::<T>(id);
+        let (value, ticks) = storage.get_with_ticks_mut(entity.index() as usize).ok_or(missing)?;
+        Ok(match ticks {
+            Some(ticks) => Mut::with_ticks(value, ticks, self.last_change_tick, self.change_tick),
+            None => Mut::new(value),
+        })
+    }
+
+    /// Every stored `T` with its entity, in index order. Values left at an index whose entity is
+    /// not alive anymore are skipped.
+    pub fn iter_components<T: Component>(&self) -> impl Iterator<Item = (Entity, &T)> + '_ {
+        let storage = self.components.id::<T>().map(|id| self.storages.typed::<T>(id));
+        storage.into_iter().flat_map(move |storage| {
+            storage.mask().iter().filter_map(move |index| {
+                let entity = self.entities.get(index as u32)?;
+                Some((entity, storage.get(index)?))
+            })
+        })
+    }
+
+    /// Same as [`World::iter_components`] with the values marked changed when written to, in the
+    /// order of the storage: the index order, but for sparse set storages.
+    pub fn iter_components_mut<T: Component>(&mut self) -> impl Iterator<Item = (Entity, Mut<'_, T>)> + '_ {
+        const { component::assert_mutable::<T>() };
+        let (last_run, this_run) = (self.last_change_tick, self.change_tick);
+        let entities = &self.entities;
+        let storage = self.components.id::<T>().map(|id| self.storages.typed_mut::<T>(id));
+        storage.into_iter().flat_map(|storage| storage.iter_with_ticks_mut()).filter_map(move |(index, value, ticks)| {
+            let entity = entities.get(index as u32)?;
+            Some(match ticks {
+                Some(ticks) => (entity, Mut::with_ticks(value, ticks, last_run, this_run)),
+                None => (entity, Mut::new(value)),
+            })
+        })
+    }
+
+    pub fn has_component<T: Component>(&self, entity: Entity) -> bool {
+        self.get_component::<T>(entity).is_some()
+    }
+
+    /// Removes the component from the entity and returns it.
+    pub fn remove_component<T: Component>(&mut self, entity: Entity) -> Option<T> {
+        self.try_remove_component(entity).ok()
+    }
+
+    /// Removes the component from the entity and returns it, telling apart a dead entity from a
+    /// missing component.
+    pub fn try_remove_component<T: Component>(&mut self, entity: Entity) -> Result<T, EcsError> {
+        self.entities.check_alive(entity)?;
+        let missing = EcsError::MissingComponent {
+            entity,
+            type_name: type_name::<T>(),
+        };
+        let id = self.components.id::<T>().ok_or(missing)?;
+        if !self.storages.get(id).contains(entity.index() as usize) {
+            return Err(missing);
+        }
+        self.trigger_component(ObserverKind::Remove, id, entity);
+        self.leave_groups(entity, id);
+        // An observer may have removed it already.
+        let removed = self.storages.typed_mut::<T>(id).take(entity.index() as usize).ok_or(missing)?;
+        self.removed.push(id, entity);
+        self.replication.removed(id, entity);
+        self.metrics.removed(id, 1);
+        self.flush_relocations(&[]);
+        Ok(removed)
+    }
+
+    /// Removes the `T` of each entity like [`World::remove_component`], drops them and returns how
+    /// many were removed. The storage drops them in one pass, which is cheaper than removing them
+    /// one at a time.
+    ///
+    /// The observers run for every entity, in index order, before any `T` is removed.
+    pub fn remove_component_batch<T: Component>(&mut self, entities: &[Entity]) -> usize {
+        let Some(id) = self.components.id::<T>() else {
+            return 0;
+        };
+        let has = |world: &World, entity: &Entity| {
+            world.is_alive(*entity) && world.storages.get(id).contains(entity.index() as usize)
+        };
+        let mut targets: Vec<Entity> = entities.iter().copied().filter(|entity| has(self, entity)).collect();
+        targets.sort_unstable_by_key(|entity| entity.index());
+        targets.dedup();
+        for entity in &targets {
+            self.trigger_component(ObserverKind::Remove, id, *entity);
+            self.leave_groups(*entity, id);
+        }
+        if self.has_observers() {
+            // Observers may have removed some already.
+            targets.retain(|entity| has(self, entity));
+        }
+        let indices: Vec<usize> = targets.iter().map(|entity| entity.index() as usize).collect();
+        let removed = self.storages.get_mut(id).remove_many(&indices);
+        self.metrics.removed(id, removed as u32);
+        self.removed.extend(id, targets.iter().copied());
+        self.replication.removed_many(id, targets);
+        self.flush_relocations(&[]);
+        removed
+    }
+
+    /// The entities that lost their `T`, removed or despawned, during the current and the
+    /// previous frame as delimited by [`World::clear_trackers`].
+    pub fn removed<T: Component>(&self) -> impl Iterator<Item = Entity> + '_ {
+        let id = self.components.id::<T>();
+        id.into_iter().flat_map(|id| self.removed.get(id))
+    }
+
+    /// How full the world is, see [`WorldStats`].
+    pub fn stats(&self) -> WorldStats {
+        WorldStats {
+            entities: self.entities.len(),
+            max_entities: self.entities.max_entities(),
+            headroom: self.entities.headroom(),
+            free_indices: self.entities.free_count(),
+            components: self.components.len(),
+            resources: self.resources.len(),
+        }
+    }
+
+    /// Memory usage of the storage of `T`, zeroed if `T` was never registered.
+    pub fn storage_stats<T: Component>(&self) -> StorageStats {
+        match self.components.id::<T>() {
+            Some(id) => self.storages.get(id).stats(),
+            None => StorageStats::default(),
+        }
+    }
+
+    /// Releases the memory of the storage of `T` that no component uses anymore, like the pages
+    /// emptied by removals. Entities keep their indices, only internal buffers move, and the
+    /// holes of a [tombstone](component::RemovalPolicy::Tombstone) sparse set close.
+    pub fn compact<T: Component>(&mut self) {
+        if let Some(id) = self.components.id::<T>() {
+            self.storages.get_mut(id).compact();
+            self.flush_relocations(&[]);
+        }
+    }
+
+    /// Compacts every storage, see [`World::compact`].
+    pub fn compact_all(&mut self) {
+        for storage in self.storages.iter_mut() {
+            storage.compact();
+        }
+        self.flush_relocations(&[]);
+    }
+
+    pub fn as_unsafe_world_cell(&self) -> UnsafeWorldCell<'_> {
+        self.as_unsafe_world_cell_at(self.last_change_tick, self.change_tick)
+    }
+
+    /// A cell whose queries report the changes made after `last_run`, writing at `this_run`.
+    pub fn as_unsafe_world_cell_at(&self, last_run: Tick, this_run: Tick) -> UnsafeWorldCell<'_> {
+        UnsafeWorldCell {
+            world: self,
+            last_run,
+            this_run,
+        }
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A view of a [`World`] that allows to borrow several storages mutably at the same time.
+///
+/// It is up to the user to make sure no value is borrowed mutably twice.
+#[derive(Clone, Copy)]
+pub struct UnsafeWorldCell<'w> {
+    world: &'w World,
+    last_run: Tick,
+    this_run: Tick,
+}
+
+impl<'w> UnsafeWorldCell<'w> {
+    /// The tick changes are compared against.
+    pub fn last_run(self) -> Tick {
+        self.last_run
+    }
+
+    /// The tick writes are recorded at.
+    pub fn this_run(self) -> Tick {
+        self.this_run
+    }
+
+    pub fn entities(self) -> &'w Entities {
+        &self.world.entities
+    }
+
+    pub(crate) fn despawned(self) -> &'w DespawnEvents {
+        &self.world.despawned
+    }
+
+    pub fn components(self) -> &'w Components {
+        &self.world.components
+    }
+
+    /// # Safety
+    ///
+    /// The storage must not be borrowed mutably while the reference lives.
+    pub unsafe fn storage<T: Send + Sync + 'static>(self, id: ComponentId) -> &'w Storage<T> {
+        self.world.storages.typed::<T>(id)
+    }
+
+    /// # Safety
+    ///
+    /// The caller must have exclusive access to the storage while it uses the pointer.
+    pub unsafe fn storage_ptr<T: Send + Sync + 'static>(self, id: ComponentId) -> NonNull<Storage<T>> {
+        self.world.storages.typed_ptr::<T>(id)
+    }
+
+    /// # Safety
+    ///
+    /// The resource must not be borrowed mutably while the reference lives.
+    pub unsafe fn resource<T: Send + Sync + 'static>(self) -> Option<&'w T> {
+        self.world.resources.get()
+    }
+
+    /// # Safety
+    ///
+    /// The caller must have exclusive access to the resource while the reference lives.
+    pub unsafe fn resource_mut<T: Send + Sync + 'static>(self) -> Option<Mut<'w, T>> {
+        let (value, ticks) = self.world.resources.get_ptr::<T>()?;
+        Some(Mut::with_ticks(&mut *value, &mut *ticks, self.last_run, self.this_run))
+    }
+
+    /// The members of the group made of exactly `components`, sorted, see
+    /// [`World::register_group`].
+    pub(crate) fn group(self, components: &[ComponentId]) -> Option<&'w [Entity]> {
+        self.world.groups.matching(components)
+    }
+
+    pub(crate) fn storage_mask(self, id: ComponentId) -> &'w BMask {
+        // Masks are never modified while a query runs.
+        unsafe { self.world.storages.mask_unchecked(id) }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Player;
+    #[derive(Component)]
+    struct Visible;
+
+    #[test]
+    fn components_lifecycle() {
+        let mut world = World::new();
+        let e = *world.spawn_entity();
+        assert_eq!(world.add_component(e, Health(10)), None);
+        assert_eq!(world.add_component(e, Health(20)), Some(Health(10)));
+        world.get_component_mut::<Health>(e).unwrap().0 += 1;
+        assert_eq!(world.get_component::<Health>(e), Some(&Health(21)));
+        assert_eq!(world.remove_component::<Health>(e), Some(Health(21)));
+        assert!(!world.has_component::<Health>(e));
+        world.add_component(e, Health(1));
+        assert!(world.despawn_entity(e));
+        assert_eq!(world.get_component::<Health>(e), None);
+        assert_eq!(world.storage_stats::<Health>().live, 0);
+    }
+
+    #[test]
+    fn marker_components_only_use_a_mask() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..5000).map(|_| *world.spawn_entity()).collect();
+        for e in entities.iter().step_by(2) {
+            assert_eq!(world.add_component(*e, Player), None);
+        }
+        for e in entities.iter().step_by(4) {
+            assert_eq!(world.remove_component::<Player>(*e), Some(Player));
+        }
+        assert_eq!(world.get_component::<Player>(entities[2]), Some(&Player));
+        assert_eq!(world.get_component::<Player>(entities[4]), None);
+        assert_eq!(world.get_component::<Player>(entities[1]), None);
+
+        let players = world.query_filtered::<Entity, query::With<Player>>();
+        let found: Vec<Entity> = players.iter(&world).collect();
+        let expected: Vec<Entity> = entities.iter().copied().skip(2).step_by(4).collect();
+        assert_eq!(found, expected);
+
+        let hidden = world.query_filtered::<Entity, (query::With<Player>, query::Without<Visible>)>();
+        assert_eq!(hidden.iter(&world).count(), 1250);
+
+        let stats = world.storage_stats::<Player>();
+        assert_eq!(stats.live, 1250);
+        assert_eq!(stats.capacity_slots, 0);
+        // 5000 bits fit in 157 leaf words, the layers round their capacity to a power of two.
+        assert!(stats.bytes_allocated <= (256 + 8 + 1) * 4, "{:?}", stats);
+    }
+
+    #[test]
+    fn compact_after_mass_removal() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..20_000).map(|_| *world.spawn_entity()).collect();
+        for (i, e) in entities.iter().enumerate() {
+            world.add_component(*e, Health(i as u32));
+            world.add_component(*e, Player);
+        }
+        let full = world.storage_stats::<Health>();
+        assert_eq!(full.pages, 20);
+        // Keeps one entity out of twenty, all in the first pages and a few far away.
+        let kept: Vec<usize> = (0..1000).chain((19_990..20_000).step_by(5)).collect();
+        for (i, e) in entities.iter().enumerate() {
+            if !kept.contains(&i) {
+                world.despawn_entity(*e);
+            }
+        }
+        assert_eq!(world.storage_stats::<Health>(), StorageStats { live: 1002, ..full });
+
+        world.compact::<Health>();
+        let compacted = world.storage_stats::<Health>();
+        assert_eq!(compacted.live, 1002);
+        assert_eq!(compacted.pages, 2);
+        assert!(compacted.bytes_allocated * 5 < full.bytes_allocated, "{:?} {:?}", compacted, full);
+        for i in &kept {
+            assert_eq!(world.get_component::<Health>(entities[*i]), Some(&Health(*i as u32)));
+        }
+
+        let tags = world.storage_stats::<Player>();
+        world.compact_all();
+        assert!(world.storage_stats::<Player>().bytes_allocated <= tags.bytes_allocated);
+        let players = world.query_filtered::<Entity, query::With<Player>>();
+        assert_eq!(players.iter(&world).count(), 1002);
+        // The storage grows back as needed.
+        let e = *world.spawn_entity();
+        world.add_component(e, Health(7));
+        assert_eq!(world.get_component::<Health>(e), Some(&Health(7)));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn reserved_spawn_batch_does_not_allocate() {
+        let mut world = World::new();
+        world.spawn_batch((0..10).map(Health));
+        world.reserve_entities(5000);
+        world.reserve_components::<Health>(5000);
+        world.reserve_components::<Player>(5000);
+        assert!(world.enities().capacity() >= 5010);
+        let reserved = world.storage_stats::<Health>();
+        assert!(reserved.capacity_slots >= 5010, "{:?}", reserved);
+
+        let (spawned, allocations) = utils::counting_alloc::count_allocations(|| world.spawn_batch((0..5000).map(Health)));
+        // Only the returned list of entities is allocated.
+        assert_eq!(allocations, 1);
+        assert_eq!(world.storage_stats::<Health>(), StorageStats { live: 5010, ..reserved });
+        let (_, allocations) = utils::counting_alloc::count_allocations(|| {
+            for e in &spawned {
+                world.add_component(*e, Player);
+            }
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(world.get_component::<Health>(spawned[4999]), Some(&Health(4999)));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn grown_worlds_spawn_without_allocating() {
+        let mut world = World::new();
+        world.spawn_batch((0..10).map(Health));
+        world.spawn_batch([Player]);
+        world.set_growth_policy(GrowthPolicy {
+            entity_chunk: 0,
+            pre_touch: true,
+        });
+        world.grow_to(20_000);
+        assert!(world.enities().capacity() >= 20_000);
+
+        let (_, allocations) = utils::counting_alloc::count_allocations(|| {
+            for _ in 0..19_000 {
+                let e = *world.spawn_entity();
+                world.add_component(e, Player);
+            }
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(world.storage_stats::<Player>().live, 19_001);
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn entities_grow_by_chunks() {
+        let mut world = World::new();
+        world.set_growth_policy(GrowthPolicy {
+            entity_chunk: 4096,
+            pre_touch: false,
+        });
+        world.spawn_entity();
+        assert_eq!(world.enities().capacity(), 4096);
+        let (_, allocations) = utils::counting_alloc::count_allocations(|| {
+            for _ in 1..4096 {
+                world.spawn_entity();
+            }
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(world.enities().capacity(), 4096);
+        world.spawn_entity();
+        assert_eq!(world.enities().capacity(), 8192);
+
+        // Chunks that are not whole pages round up to them.
+        world.set_growth_policy(GrowthPolicy {
+            entity_chunk: 1500,
+            pre_touch: true,
+        });
+        world.spawn_batch((4097..9000).map(Health));
+        assert_eq!(world.enities().capacity(), 9216);
+    }
+
+    // Drops log their name, the handles hold the cache log like assets would hold their cache.
+    type DropLog = std::sync::Arc<std::sync::Mutex<Vec<&'static str>>>;
+
+    struct Logged(&'static str, DropLog);
+
+    impl Drop for Logged {
+        fn drop(&mut self) {
+            self.1.lock().unwrap().push(self.0);
+        }
+    }
+
+    #[derive(Component)]
+    struct MeshHandle(Logged);
+    struct AssetCache(Logged);
+    struct Settings(Logged);
+
+    fn teardown_world(log: &DropLog) -> World {
+        let mut world = World::new();
+        world.insert_resource(AssetCache(Logged("cache", log.clone())));
+        world.set_resource_drop_last::<AssetCache>(true);
+        world.insert_resource(Settings(Logged("settings", log.clone())));
+        for _ in 0..3 {
+            let e = *world.spawn_entity();
+            world.add_component(e, MeshHandle(Logged("mesh", log.clone())));
+        }
+        world
+    }
+
+    #[test]
+    fn components_drop_before_resources() {
+        let log = DropLog::default();
+        drop(teardown_world(&log));
+        assert_eq!(*log.lock().unwrap(), ["mesh", "mesh", "mesh", "settings", "cache"]);
+
+        let mut world = teardown_world(&log);
+        assert!(!world.set_resource_drop_last::<Health>(true));
+        // Inserting the cache again forgets the flag, the settings now drop after it.
+        world.insert_resource(AssetCache(Logged("new cache", log.clone())));
+        world.set_resource_drop_last::<Settings>(true);
+        log.lock().unwrap().clear();
+        drop(world);
+        assert_eq!(*log.lock().unwrap(), ["mesh", "mesh", "mesh", "new cache", "settings"]);
+    }
+
+    #[test]
+    fn scopes_keep_the_drop_order() {
+        let log = DropLog::default();
+        let mut world = teardown_world(&log);
+        world.resource_scope(|world, _: Mut<AssetCache>| {
+            assert!(!world.set_resource_drop_last::<AssetCache>(false));
+        });
+        drop(world);
+        assert_eq!(*log.lock().unwrap(), ["mesh", "mesh", "mesh", "settings", "cache"]);
+    }
+
+    #[test]
+    fn clear_all_runs_the_observers() {
+        use crate::observer::{DeferredWorld, OnRemove, Trigger};
+
+        let log = DropLog::default();
+        let mut world = teardown_world(&log);
+        let observed = log.clone();
+        world.add_observer(move |trigger: Trigger<OnRemove<MeshHandle>>, world: &mut DeferredWorld| {
+            // The cache is still there to release the handle.
+            assert!(world.get_resource::<AssetCache>().is_some());
+            assert!(world.get_component::<MeshHandle>(trigger.entity()).is_some());
+            observed.lock().unwrap().push("observer");
+        });
+        world.clear_all();
+        assert_eq!(
+            *log.lock().unwrap(),
+            ["observer", "mesh", "observer", "mesh", "observer", "mesh", "settings", "cache"]
+        );
+        assert!(world.enities().is_empty());
+        assert!(!world.contains_resource::<AssetCache>());
+
+        // The world is still usable.
+        let e = *world.spawn_entity();
+        world.add_component(e, MeshHandle(Logged("mesh", log.clone())));
+        log.lock().unwrap().clear();
+        drop(world);
+        assert_eq!(*log.lock().unwrap(), ["mesh"]);
+    }
+
+    // Every third entity is in the group of health and mesh, every fifth a player.
+    fn wave(log: &DropLog) -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        world.register_group::<(Health, MeshHandle)>();
+        let entities = world.spawn_batch((0..3000).map(Health));
+        for (i, e) in entities.iter().enumerate() {
+            if i.is_multiple_of(3) {
+                world.add_component(*e, MeshHandle(Logged("mesh", log.clone())));
+            }
+            if i.is_multiple_of(5) {
+                world.add_component(*e, Player);
+            }
+        }
+        let dead = *world.spawn_entity();
+        world.despawn_entity(dead);
+        let mut victims: Vec<Entity> = entities.iter().copied().filter(|e| e.index() % 4 != 1).collect();
+        victims.extend([entities[8], dead, entities[0]]);
+        (world, victims)
+    }
+
+    fn assert_same_world(batched: &World, looped: &World) {
+        batched.validate().unwrap();
+        let removed = |world: &World| {
+            let mut removed: Vec<Entity> = world.removed::<Health>().chain(world.removed::<MeshHandle>()).collect();
+            removed.sort();
+            removed
+        };
+        assert_eq!(removed(batched), removed(looped));
+        let health = |world: &World| world.iter_components::<Health>().map(|(e, h)| (e, h.0)).collect::<Vec<_>>();
+        assert_eq!(health(batched), health(looped));
+        let meshes = |world: &World| world.iter_components::<MeshHandle>().map(|(e, _)| e).collect::<Vec<_>>();
+        assert_eq!(meshes(batched), meshes(looped));
+        let players = |world: &World| world.iter_components::<Player>().map(|(e, _)| e).collect::<Vec<_>>();
+        assert_eq!(players(batched), players(looped));
+        assert_eq!(batched.enities().len(), looped.enities().len());
+    }
+
+    #[test]
+    fn batch_removal_matches_the_naive_loop() {
+        let (batched_log, looped_log) = (DropLog::default(), DropLog::default());
+        let (mut batched, victims) = wave(&batched_log);
+        let (mut looped, looped_victims) = wave(&looped_log);
+        let removed = batched.remove_component_batch::<MeshHandle>(&victims);
+        let expected = looped_victims.iter().filter(|e| looped.remove_component::<MeshHandle>(**e).is_some()).count();
+        assert_eq!((removed, expected), (750, 750));
+        assert_eq!(batched_log.lock().unwrap().len(), 750);
+        assert_eq!(batched.remove_component_batch::<Visible>(&victims), 0);
+        assert_same_world(&batched, &looped);
+
+        let despawned = batched.despawn_batch(&victims);
+        let expected = looped_victims.iter().filter(|e| looped.despawn_entity(**e)).count();
+        assert_eq!((despawned, expected), (2250, 2250));
+        assert_eq!(batched.despawn_batch(&victims), 0);
+        assert_same_world(&batched, &looped);
+        assert_eq!(*batched_log.lock().unwrap(), *looped_log.lock().unwrap());
+        drop((batched, looped));
+        assert_eq!(batched_log.lock().unwrap().len(), 1000);
+    }
+
+    #[test]
+    fn component_iteration_rebuilds_live_handles() {
+        let mut world = World::new();
+        let old = *world.spawn_entity();
+        world.despawn_entity(old);
+        let reused = *world.spawn_entity();
+        let other = *world.spawn_entity();
+        let stale = *world.spawn_entity();
+        for (e, health) in [(reused, 1), (other, 2), (stale, 3)] {
+            world.add_component(e, Health(health));
+        }
+        world.add_component(other, Player);
+        // A despawn interrupted before the storages were cleaned.
+        world.entities.despawn_entity(stale);
+
+        let seen: Vec<(Entity, u32)> = world.iter_components::<Health>().map(|(e, h)| (e, h.0)).collect();
+        assert_eq!(seen, [(reused, 1), (other, 2)]);
+        assert_eq!((seen[0].0.index(), seen[0].0.generation()), (old.index(), old.generation() + 1));
+        assert_eq!(world.iter_components::<Player>().map(|(e, _)| e).collect::<Vec<_>>(), [other]);
+        assert!(world.iter_components::<Visible>().next().is_none());
+
+        world.clear_trackers();
+        for (e, mut health) in world.iter_components_mut::<Health>() {
+            if e == other {
+                health.0 *= 10;
+            }
+        }
+        assert_eq!(world.get_component::<Health>(other), Some(&Health(20)));
+        let changed = world.query_filtered::<Entity, crate::query::Changed<Health>>();
+        assert_eq!(changed.iter(&world).collect::<Vec<_>>(), [other]);
+        assert_eq!(world.storages.typed::<Health>(world.components.id::<Health>().unwrap()).get(stale.index() as usize), Some(&Health(3)));
+    }
+
+    #[test]
+    fn stats_show_the_headroom_left() {
+        let mut world = World::new();
+        let max = world.stats().max_entities;
+        assert_eq!(world.stats().headroom, max);
+        let spawned = world.spawn_batch_iter((0..max).map(|i| (Health(i as u32),)));
+        let stats = world.stats();
+        assert_eq!((stats.entities, stats.headroom, stats.components), (max, 0, 1));
+        assert_eq!(world.try_spawn_entity(), Err(EcsError::EntitiesExhausted { capacity: max }));
+        let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| *world.spawn_entity()));
+        let message = *panic.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.contains("exhausted"), "{}", message);
+
+        world.despawn_batch(&spawned[..10]);
+        let stats = world.stats();
+        assert_eq!((stats.headroom, stats.free_indices), (10, 10));
+        assert_eq!(world.spawn_batch((0..10).map(Health)).len(), 10);
+        assert!(world.try_spawn_entity().is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use crate::component::Component;
+use crate::entity::{DanglingPolicy, Entity, EntityMapper, MapEntities};
+use crate::observer::ObserverKind;
+use crate::storage::{AnyStorage, Storage};
+use crate::World;
+
+/// Why [`World::merge_from`] gave up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MergeError {
+    /// Components referenced entities that are not alive in the source world, under
+    /// [`DanglingPolicy::Error`](crate::entity::DanglingPolicy::Error).
+    Dangling(Vec<Entity>),
+}
+
+impl fmt::Display for MergeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Dangling(entities) => write!(f, "Dangling entity references: {:?}", entities),
+        }
+    }
+}
+
+impl Error for MergeError {}
+
+/// What [`World::merge_from`] does with the resources of the merged world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResourceMergePolicy {
+    /// Drop them.
+    #[default]
+    Ignore,
+    /// Move the ones this world doesn't have yet.
+    KeepExisting,
+    /// Move all of them, replacing the ones this world already has.
+    Overwrite,
+}
+
+fn map_storage<T: MapEntities + 'static>(storage: &mut dyn AnyStorage, mapper: &mut EntityMapper) {
+    let storage = storage
+        .as_any_mut()
+        .downcast_mut::<Storage<T>>()
+        .expect("Storage type mismatch");
+    for (_, value) in storage.iter_mut() {
+        value.map_entities(mapper);
+    }
+}
+
+impl World {
+    /// Lets the worlds rewrite the entities stored in `T` when it is moved between worlds.
+    pub fn register_map_entities<T: Component + MapEntities>(&mut self) {
+        let id = self.register_component::<T>();
+        self.components.set_map_entities(id, map_storage::<T>);
+    }
+
+    /// Moves every entity of `other` into this world along with its components.
+    ///
+    /// Each entity gets a new id here, recorded in `mapper`, and the references stored in the
+    /// components registered with [`World::register_map_entities`] (in either world) are rewritten.
+    /// Component values are moved, never cloned. On error this world and `mapper` are left
+    /// untouched, and `other` is dropped.
+    pub fn merge_from(
+        &mut self,
+        mut other: World,
+        mapper: &mut EntityMapper,
+        resources: ResourceMergePolicy,
+    ) -> Result<(), MergeError> {
+        let sources: Vec<Entity> = other.entities.iter().collect();
+        if mapper.policy() == DanglingPolicy::Error {
+            // The unknown references are found before anything is spawned: mapping every known
+            // entity to itself finds them and, under this policy, leaves the values as they are.
+            let mut check = EntityMapper::new(DanglingPolicy::Error);
+            for source in sources.iter().copied().chain(mapper.iter().map(|(source, _)| source)) {
+                check.insert(source, source);
+            }
+            self.map_entities_of(&mut other, &mut check);
+            let dangling = check.take_dangling();
+            if !dangling.is_empty() {
+                return Err(MergeError::Dangling(dangling));
+            }
+        }
+
+        let mut targets = vec![None; sources.last().map_or(0, |e| e.index() as usize + 1)];
+        for source in &sources {
+            let target = *self.spawn_entity();
+            mapper.insert(*source, target);
+            targets[source.index() as usize] = Some(target);
+        }
+        // References are rewritten while the values still live in `other`.
+        mapper.take_dangling();
+        self.map_entities_of(&mut other, mapper);
+
+        let mut added = Vec::new();
+        for info in other.components.iter() {
+            let id = self.components.register_info(info);
+            let src = other.storages.get_mut(info.id());
+            if id.index() == self.storages.len() {
+                self.storages.push_like(id, src);
+            }
+            let dst = self.storages.get_mut(id);
+            let indices: Vec<usize> = src.mask().iter().collect();
+            let moved = added.len();
+            for index in indices {
+                if let Some(target) = targets.get(index).copied().flatten() {
+                    src.move_to(index, dst, target.index() as usize, self.change_tick);
+                    added.push((id, target));
+                }
+            }
+            self.metrics.inserted(id, (added.len() - moved) as u32);
+        }
+        for (id, target) in &added {
+            self.join_groups(*target, *id);
+        }
+        self.flush_relocations(&[]);
+        // Observers see the merged world once every component is in place.
+        if self.has_observers() {
+            for (id, target) in added {
+                self.trigger_component(ObserverKind::Add, id, target);
+            }
+        }
+
+        let other_resources = core::mem::take(&mut other.resources);
+        match resources {
+            ResourceMergePolicy::Ignore => {}
+            ResourceMergePolicy::KeepExisting => self.resources.merge(other_resources, false),
+            ResourceMergePolicy::Overwrite => self.resources.merge(other_resources, true),
+        }
+        Ok(())
+    }
+
+    // Rewrites the references stored in the components of `other` registered for it in either world.
+    fn map_entities_of(&self, other: &mut World, mapper: &mut EntityMapper) {
+        for info in other.components.iter() {
+            let map_entities = info.map_entities().or_else(|| {
+                let id = self.components.get_id(info.type_id()?)?;
+                self.components.info(id)?.map_entities()
+            });
+            if let Some(map_entities) = map_entities {
+                map_entities(other.storages.get_mut(info.id()), mapper);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::entity::DanglingPolicy;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Parent(Entity);
+
+    impl MapEntities for Parent {
+        fn map_entities(&mut self, mapper: &mut EntityMapper) {
+            self.0.map_entities(mapper);
+        }
+    }
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Name(&'static str);
+
+    fn chain() -> World {
+        let mut world = World::new();
+        world.register_map_entities::<Parent>();
+        let root = *world.spawn_entity();
+        let middle = *world.spawn_entity();
+        let leaf = *world.spawn_entity();
+        world.add_component(root, Name("root"));
+        world.add_component(middle, Name("middle"));
+        world.add_component(leaf, Name("leaf"));
+        world.add_component(middle, Parent(root));
+        world.add_component(leaf, Parent(middle));
+        world
+    }
+
+    #[test]
+    fn parent_chain_survives_merge() {
+        let mut world = World::new();
+        for _ in 0..10 {
+            world.spawn_entity();
+        }
+        let mut mapper = EntityMapper::new(DanglingPolicy::Error);
+        world.merge_from(chain(), &mut mapper, ResourceMergePolicy::Ignore).unwrap();
+        assert_eq!(world.enities().len(), 13);
+
+        let leaf = mapper.get(Entity::new(2, 1)).unwrap();
+        assert_eq!(leaf.index(), 12);
+        let mut names = vec![world.get_component::<Name>(leaf).unwrap().0];
+        let mut current = leaf;
+        while let Some(Parent(parent)) = world.get_component::<Parent>(current) {
+            names.push(world.get_component::<Name>(*parent).unwrap().0);
+            current = *parent;
+        }
+        assert_eq!(names, vec!["leaf", "middle", "root"]);
+    }
+
+    #[test]
+    fn dangling_policy() {
+        let dangling = || {
+            let mut world = chain();
+            let gone = *world.spawn_entity();
+            let orphan = *world.spawn_entity();
+            world.add_component(orphan, Parent(gone));
+            world.despawn_entity(gone);
+            (world, gone)
+        };
+
+        let mut world = World::new();
+        let (other, gone) = dangling();
+        let mut mapper = EntityMapper::new(DanglingPolicy::Error);
+        let result = world.merge_from(other, &mut mapper, ResourceMergePolicy::Ignore);
+        assert_eq!(result, Err(MergeError::Dangling(vec![gone])));
+        assert!(world.enities().is_empty());
+        assert!(mapper.is_empty());
+        // Nothing was spawned then despawned, the first index is still fresh.
+        assert_eq!(*world.spawn_entity(), Entity::new(0, 1));
+
+        for (policy, expected) in [
+            (DanglingPolicy::MapToDead, Entity::PLACEHOLDER),
+            (DanglingPolicy::Keep, gone),
+        ] {
+            let mut world = World::new();
+            let mut mapper = EntityMapper::new(policy);
+            world.merge_from(dangling().0, &mut mapper, ResourceMergePolicy::Ignore).unwrap();
+            let orphan = mapper.get(Entity::new(4, 1)).unwrap();
+            assert_eq!(world.get_component::<Parent>(orphan), Some(&Parent(expected)));
+        }
+    }
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    // Not `Clone` on purpose, and counts its drops to prove the values were moved.
+    #[derive(Component)]
+    struct Payload(usize);
+
+    impl Drop for Payload {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Seed(u64);
+
+    #[test]
+    fn merge_chunk_moves_values() {
+        let mut chunk = World::new();
+        chunk.register_map_entities::<Parent>();
+        chunk.insert_resource(Seed(1));
+        chunk.insert_resource(Name("chunk"));
+        let mut previous = None;
+        for i in 0..1000 {
+            let e = *chunk.spawn_entity();
+            chunk.add_component(e, Payload(i));
+            // Every ten entities form a little chain.
+            if let Some(parent) = previous.filter(|_| i % 10 != 0) {
+                chunk.add_component(e, Parent(parent));
+            }
+            previous = Some(e);
+        }
+
+        let mut world = World::new();
+        world.insert_resource(Seed(2));
+        for _ in 0..500 {
+            world.spawn_entity();
+        }
+        let mut mapper = EntityMapper::new(DanglingPolicy::Error);
+        world.merge_from(chunk, &mut mapper, ResourceMergePolicy::KeepExisting).unwrap();
+        assert_eq!(DROPS.load(Ordering::Relaxed), 0);
+        assert_eq!(world.enities().len(), 1500);
+        assert_eq!(world.storage_stats::<Payload>().live, 1000);
+        assert_eq!(world.storage_stats::<Parent>().live, 900);
+        assert_eq!(world.get_resource::<Seed>(), Some(&Seed(2)));
+        assert_eq!(world.get_resource::<Name>(), Some(&Name("chunk")));
+
+        for (source, target) in mapper.iter() {
+            let payload = world.get_component::<Payload>(target).unwrap().0;
+            assert_eq!(payload, source.index() as usize);
+            if let Some(Parent(parent)) = world.get_component::<Parent>(target) {
+                assert_eq!(world.get_component::<Payload>(*parent).unwrap().0, payload - 1);
+            }
+        }
+
+        drop(world);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1000);
+    }
+}
@@ -0,0 +1,273 @@
+//! Counting the structural changes of a world, to catch the systems that respawn the same
+//! entities every frame.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::component::{ComponentId, Components};
+use crate::World;
+
+type ExceedHook = Box<dyn FnMut(&StructuralReport) + Send + Sync>;
+
+/// The spawns, despawns, insertions and removals made through the world since the last
+/// [`World::clear_metrics`], which [`Schedule`](crate::system::Schedule) calls at the end of
+/// each frame. Replacing the value of a component isn't structural and isn't counted.
+///
+/// Without the `metrics` feature nothing is counted and every count stays at zero.
+#[derive(Default)]
+pub struct StructuralMetrics {
+    spawns: u32,
+    despawns: u32,
+    // By component index, grown on the first change of a component.
+    inserts: Vec<u32>,
+    removals: Vec<u32>,
+    last_frame: StructuralReport,
+    hook: Option<(u32, ExceedHook)>,
+}
+
+impl StructuralMetrics {
+    pub fn spawns(&self) -> u32 {
+        self.spawns
+    }
+
+    /// The despawned entities, their components count as removals too.
+    pub fn despawns(&self) -> u32 {
+        self.despawns
+    }
+
+    pub fn inserts(&self, id: ComponentId) -> u32 {
+        self.inserts.get(id.index()).copied().unwrap_or(0)
+    }
+
+    pub fn removals(&self, id: ComponentId) -> u32 {
+        self.removals.get(id.index()).copied().unwrap_or(0)
+    }
+
+    /// Every change counted, the spawns and despawns included.
+    pub fn total(&self) -> u32 {
+        let components = self.inserts.iter().chain(&self.removals).sum::<u32>();
+        self.spawns + self.despawns + components
+    }
+
+    /// The counts of the frame cleared last.
+    pub fn last_frame(&self) -> &StructuralReport {
+        &self.last_frame
+    }
+
+    /// Calls `hook` with the report of each frame whose total exceeds `threshold`, once when the
+    /// frame is cleared. Replaces the previous hook.
+    pub fn on_exceed(&mut self, threshold: u32, hook: impl FnMut(&StructuralReport) + Send + Sync + 'static) {
+        self.hook = Some((threshold, Box::new(hook)));
+    }
+
+    pub(crate) fn spawned(&mut self, count: u32) {
+        #[cfg(feature = "metrics")]
+        {
+            self.spawns += count;
+        }
+    }
+
+    pub(crate) fn despawned(&mut self, count: u32) {
+        #[cfg(feature = "metrics")]
+        {
+            self.despawns += count;
+        }
+    }
+
+    pub(crate) fn inserted(&mut self, id: ComponentId, count: u32) {
+        #[cfg(feature = "metrics")]
+        bump(&mut self.inserts, id, count);
+    }
+
+    pub(crate) fn removed(&mut self, id: ComponentId, count: u32) {
+        #[cfg(feature = "metrics")]
+        bump(&mut self.removals, id, count);
+    }
+
+    fn report(&self, components: &Components) -> StructuralReport {
+        let churn = components.iter().map(|info| ComponentChurn {
+            id: info.id(),
+            name: info.name(),
+            inserts: self.inserts(info.id()),
+            removals: self.removals(info.id()),
+        });
+        StructuralReport {
+            spawns: self.spawns,
+            despawns: self.despawns,
+            components: churn.filter(|churn| churn.inserts + churn.removals != 0).collect(),
+        }
+    }
+}
+
+fn bump(counts: &mut Vec<u32>, id: ComponentId, count: u32) {
+    if id.index() >= counts.len() {
+        counts.resize(id.index() + 1, 0);
+    }
+    counts[id.index()] += count;
+}
+
+/// The structural changes of a frame, see [`StructuralMetrics`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StructuralReport {
+    pub spawns: u32,
+    pub despawns: u32,
+    /// The components that were inserted or removed, by registration order.
+    pub components: Vec<ComponentChurn>,
+}
+
+impl StructuralReport {
+    pub fn total(&self) -> u32 {
+        let components = self.components.iter().map(|churn| churn.inserts + churn.removals).sum::<u32>();
+        self.spawns + self.despawns + components
+    }
+}
+
+impl fmt::Display for StructuralReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} structural changes: {} spawns, {} despawns", self.total(), self.spawns, self.despawns)?;
+        for churn in &self.components {
+            write!(f, ", {} +{} -{}", churn.name, churn.inserts, churn.removals)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentChurn {
+    pub id: ComponentId,
+    pub name: &'static str,
+    pub inserts: u32,
+    pub removals: u32,
+}
+
+impl World {
+    pub fn structural_metrics(&self) -> &StructuralMetrics {
+        &self.metrics
+    }
+
+    /// To set the hook of [`StructuralMetrics::on_exceed`].
+    pub fn structural_metrics_mut(&mut self) -> &mut StructuralMetrics {
+        &mut self.metrics
+    }
+
+    /// Ends the frame of the metrics: the counts move to [`StructuralMetrics::last_frame`], the
+    /// hook runs if they exceed its threshold, and counting starts over from zero.
+    pub fn clear_metrics(&mut self) {
+        let metrics = &mut self.metrics;
+        metrics.last_frame = metrics.report(&self.components);
+        metrics.spawns = 0;
+        metrics.despawns = 0;
+        metrics.inserts.fill(0);
+        metrics.removals.fill(0);
+        if let Some((threshold, hook)) = &mut metrics.hook {
+            if metrics.last_frame.total() > *threshold {
+                hook(&metrics.last_frame);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+    use crate::component::Component;
+
+    use super::*;
+    use crate::commands::Commands;
+    use crate::system::{Local, Schedule};
+
+    #[derive(Clone, Component)]
+    struct Node;
+    #[derive(Clone, Component)]
+    struct Label(&'static str);
+
+    #[test]
+    fn counts_follow_a_scripted_frame() {
+        let mut world = World::new();
+        let root = *world.spawn_entity();
+        world.add_component(root, Node);
+        world.add_component(root, Label("root"));
+        // Replacing isn't structural.
+        world.add_component(root, Label("menu"));
+        let items = world.spawn_batch((0..3).map(|_| Node));
+        world.remove_component::<Label>(root);
+        world.despawn_entity(items[0]);
+        world.despawn_batch(&items[1..]);
+
+        let metrics = world.structural_metrics();
+        let (node, label) = (world.components().id::<Node>().unwrap(), world.components().id::<Label>().unwrap());
+        assert_eq!((metrics.spawns(), metrics.despawns()), (4, 3));
+        assert_eq!((metrics.inserts(node), metrics.removals(node)), (4, 3));
+        assert_eq!((metrics.inserts(label), metrics.removals(label)), (1, 1));
+        assert_eq!(metrics.total(), 16);
+    }
+
+    #[test]
+    fn bulk_spawns_are_counted() {
+        use crate::entity::{DanglingPolicy, Entity, EntityMapper};
+        use crate::merge::ResourceMergePolicy;
+        use crate::prefab::Prefab;
+
+        let mut world = World::new();
+        world.register_clone::<Label>();
+        let prefab = Prefab::new().with(Node).with(Label("prefab"));
+        let nodes = world.spawn_prefab_batch(&prefab, 10);
+        world.duplicate_entity(nodes[0]).unwrap();
+        let mut chunk = World::new();
+        chunk.spawn_batch((0..5).map(|_| Node));
+        world.merge_from(chunk, &mut EntityMapper::new(DanglingPolicy::Error), ResourceMergePolicy::Ignore).unwrap();
+        world.get_or_spawn(Entity::new(100, 1)).unwrap();
+
+        let metrics = world.structural_metrics();
+        let (node, label) = (world.components().id::<Node>().unwrap(), world.components().id::<Label>().unwrap());
+        assert_eq!(metrics.spawns(), 17);
+        // The duplicate only gets the components with a clone function.
+        assert_eq!((metrics.inserts(node), metrics.inserts(label)), (15, 11));
+    }
+
+    #[test]
+    fn clearing_keeps_the_last_frame() {
+        let mut world = World::new();
+        world.spawn_batch((0..2).map(|_| Node));
+        world.clear_metrics();
+        assert_eq!(world.structural_metrics().total(), 0);
+        let last = world.structural_metrics().last_frame();
+        assert_eq!((last.spawns, last.total()), (2, 4));
+        assert_eq!(last.components[0].name, core::any::type_name::<Node>());
+
+        let e = *world.spawn_entity();
+        world.despawn_entity(e);
+        world.clear_metrics();
+        let last = world.structural_metrics().last_frame();
+        assert_eq!((last.spawns, last.despawns), (1, 1));
+        assert!(last.components.is_empty());
+    }
+
+    #[test]
+    fn the_hook_fires_once_for_the_churning_frame() {
+        let mut world = World::new();
+        let fired = Arc::new(AtomicU32::new(0));
+        let counter = fired.clone();
+        world.structural_metrics_mut().on_exceed(100, move |report| {
+            assert_eq!(report.spawns, 60);
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+        let mut schedule = Schedule::new();
+        schedule.add_system(|mut commands: Commands, mut runs: Local<u32>| {
+            if *runs == 0 {
+                commands.add(|world: &mut World| {
+                    let nodes = world.spawn_batch((0..60).map(|_| Node));
+                    world.despawn_batch(&nodes);
+                });
+            }
+            *runs += 1;
+        });
+        for _ in 0..3 {
+            schedule.run(&mut world);
+        }
+        assert_eq!(fired.load(Ordering::Relaxed), 1);
+        assert_eq!(world.structural_metrics().last_frame().total(), 0);
+    }
+}
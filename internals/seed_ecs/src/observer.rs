@@ -0,0 +1,464 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::TypeId;
+use crate::utils::HashMap;
+use core::marker::PhantomData;
+
+use crate::change_detection::Mut;
+use crate::commands::{CommandQueue, Commands};
+use crate::component::{Component, ComponentId};
+use crate::entity::Entity;
+#[cfg(feature = "std")]
+use crate::system::{SystemMeta, SystemParam, SystemParamItem};
+#[cfg(feature = "std")]
+use crate::tuples::all_tuples;
+use crate::World;
+
+/// How deep observers may trigger each other through their commands. The triggers past this
+/// depth are dropped, which stops observers that would otherwise feed themselves forever. With
+/// the `trace` feature each dropped trigger logs a warning.
+pub const MAX_OBSERVER_DEPTH: usize = 16;
+
+/// Triggered right after `T` is added to an entity that didn't have it.
+pub struct OnAdd<T>(PhantomData<T>);
+
+/// Triggered right before `T` is removed from an entity, the value can still be read.
+pub struct OnRemove<T>(PhantomData<T>);
+
+/// Triggered right before an entity is despawned, its components can still be read.
+pub struct OnDespawn;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub(crate) enum ObserverKind {
+    Add,
+    Remove,
+    Despawn,
+}
+
+/// What an observer listens to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObserverKey {
+    kind: ObserverKind,
+    component: Option<TypeId>,
+}
+
+/// The events observers can be added for.
+pub trait ObserverEvent: 'static {
+    fn key() -> ObserverKey;
+}
+
+impl<T: 'static> ObserverEvent for OnAdd<T> {
+    fn key() -> ObserverKey {
+        ObserverKey {
+            kind: ObserverKind::Add,
+            component: Some(TypeId::of::<T>()),
+        }
+    }
+}
+
+impl<T: 'static> ObserverEvent for OnRemove<T> {
+    fn key() -> ObserverKey {
+        ObserverKey {
+            kind: ObserverKind::Remove,
+            component: Some(TypeId::of::<T>()),
+        }
+    }
+}
+
+impl ObserverEvent for OnDespawn {
+    fn key() -> ObserverKey {
+        ObserverKey {
+            kind: ObserverKind::Despawn,
+            component: None,
+        }
+    }
+}
+
+/// The event an observer is running for.
+pub struct Trigger<E> {
+    entity: Entity,
+    _marker: PhantomData<fn() -> E>,
+}
+
+impl<E> Trigger<E> {
+    fn new(entity: Entity) -> Self {
+        Self {
+            entity,
+            _marker: PhantomData,
+        }
+    }
+
+    /// The entity the event happened to.
+    pub fn entity(&self) -> Entity {
+        self.entity
+    }
+}
+
+/// The world as seen by observers: components and resources can be read and written in place but
+/// structural changes go through [`DeferredWorld::commands`], applied once the observers ran.
+pub struct DeferredWorld<'w> {
+    world: &'w mut World,
+    commands: &'w mut CommandQueue,
+}
+
+impl<'w> DeferredWorld<'w> {
+    pub fn world(&self) -> &World {
+        self.world
+    }
+
+    pub fn commands(&mut self) -> Commands<'_> {
+        Commands::new(self.commands)
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.world.is_alive(entity)
+    }
+
+    pub fn get_component<T: Component>(&self, entity: Entity) -> Option<&T> {
+        self.world.get_component(entity)
+    }
+
+    pub fn get_component_mut<T: Component>(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+        self.world.get_component_mut(entity)
+    }
+
+    pub fn get_resource<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.world.get_resource()
+    }
+
+    pub fn get_resource_mut<T: Send + Sync + 'static>(&mut self) -> Option<Mut<'_, T>> {
+        self.world.get_resource_mut()
+    }
+}
+
+type ObserverFn = Box<dyn FnMut(Entity, &mut DeferredWorld) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct Observers {
+    observers: HashMap<ObserverKey, Vec<ObserverFn>>,
+    depth: usize,
+}
+
+impl Observers {
+    pub fn is_empty(&self) -> bool {
+        self.observers.is_empty()
+    }
+}
+
+/// Conversion into an observer of `E`, implemented for the closures taking the [`Trigger`] and a
+/// [`DeferredWorld`] and, with the `std` feature, for the functions taking the [`Trigger`] then
+/// [`SystemParam`]s. `Marker` is the signature of the function.
+pub trait IntoObserver<E, Marker>: Send + Sync + 'static {
+    #[doc(hidden)]
+    fn into_observer(self, world: &mut World) -> ObserverFn;
+}
+
+#[doc(hidden)]
+pub struct IsDeferredObserver;
+
+impl<E, F> IntoObserver<E, IsDeferredObserver> for F
+where
+    E: ObserverEvent,
+    F: FnMut(Trigger<E>, &mut DeferredWorld) + Send + Sync + 'static,
+{
+    fn into_observer(mut self, _world: &mut World) -> ObserverFn {
+        Box::new(move |entity, world| self(Trigger::new(entity), world))
+    }
+}
+
+/// Functions that can run as observers of `E`, `Marker` is the signature of the function.
+#[cfg(feature = "std")]
+pub trait ObserverParamFunction<E, Marker>: Send + Sync + 'static {
+    type Param: SystemParam;
+
+    fn run(&mut self, trigger: Trigger<E>, param: SystemParamItem<'_, '_, Self::Param>);
+}
+
+#[doc(hidden)]
+#[cfg(feature = "std")]
+pub struct IsObserverSystem;
+
+/// The parameters are initialized when the observer is added, what they defer, like the
+/// [`Commands`] they queue, is applied with the commands of the [`DeferredWorld`] once every
+/// observer of the event ran.
+///
+/// # Panics
+///
+/// Panics if the parameters conflict with each other, like the systems do.
+#[cfg(feature = "std")]
+impl<E, Marker, F> IntoObserver<E, (IsObserverSystem, Marker)> for F
+where
+    E: ObserverEvent,
+    Marker: 'static,
+    F: ObserverParamFunction<E, Marker>,
+{
+    fn into_observer(mut self, world: &mut World) -> ObserverFn {
+        use std::sync::{Arc, Mutex, PoisonError};
+
+        use crate::system::DiscardOnUnwind;
+
+        let mut meta = SystemMeta::new(core::any::type_name::<F>());
+        let state = F::Param::init_state(world, &mut meta);
+        if let Some(conflict) = meta.conflict() {
+            panic!("{}", conflict);
+        }
+        // Shared with the command applying what a run deferred.
+        let state = Arc::new(Mutex::new(state));
+        Box::new(move |entity, deferred| {
+            let world = &mut *deferred.world;
+            let this_run = world.increment_change_tick();
+            let mut locked = state.lock().unwrap_or_else(PoisonError::into_inner);
+            let mut guard = DiscardOnUnwind::<F::Param> {
+                state: &mut locked,
+                ran: false,
+            };
+            // The world is borrowed exclusively and the parameters checked their access against
+            // each other when they were initialized.
+            let cell = world.as_unsafe_world_cell_at(meta.last_run(), this_run);
+            let param = unsafe { F::Param::get_param(guard.state, &meta, cell) };
+            self.run(Trigger::new(entity), param);
+            guard.ran = true;
+            meta.set_last_run(this_run);
+            let state = state.clone();
+            deferred.commands.push(move |world: &mut World| {
+                F::Param::apply(&mut state.lock().unwrap_or_else(PoisonError::into_inner), world);
+            });
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+macro_rules! impl_observer_function {
+    ($($param: ident),*) => {
+        #[allow(non_snake_case)]
+        impl<Event, Func, $($param: SystemParam),*> ObserverParamFunction<Event, fn(Trigger<Event>, $($param,)*)> for Func
+        where
+            Event: 'static,
+            Func: Send + Sync + 'static,
+            for<'a> &'a mut Func: FnMut(Trigger<Event>, $($param),*) + FnMut(Trigger<Event>, $(SystemParamItem<$param>),*),
+        {
+            type Param = ($($param,)*);
+
+            fn run(&mut self, trigger: Trigger<Event>, param: SystemParamItem<'_, '_, ($($param,)*)>) {
+                // Calling through a helper gives the compiler the concrete argument types.
+                #[allow(clippy::too_many_arguments)]
+                fn call<Event, $($param),*>(mut func: impl FnMut(Trigger<Event>, $($param),*), trigger: Trigger<Event>, $($param: $param),*) {
+                    func(trigger, $($param),*)
+                }
+                let ($($param,)*) = param;
+                call(self, trigger, $($param),*)
+            }
+        }
+    };
+}
+
+#[cfg(feature = "std")]
+all_tuples!(impl_observer_function);
+
+impl World {
+    /// Runs `observer` every time the event `E` happens, right at the mutation point. The
+    /// observer is a closure taking the [`Trigger`] and a [`DeferredWorld`], or a function taking
+    /// the [`Trigger`] then system parameters, see [`IntoObserver`].
+    ///
+    /// Observers of the same event run in the order they were added.
+    pub fn add_observer<E: ObserverEvent, Marker>(&mut self, observer: impl IntoObserver<E, Marker>) {
+        let observer = observer.into_observer(self);
+        self.observers.observers.entry(E::key()).or_default().push(observer);
+    }
+
+    pub(crate) fn has_observers(&self) -> bool {
+        !self.observers.is_empty()
+    }
+
+    pub(crate) fn trigger_component(&mut self, kind: ObserverKind, id: ComponentId, entity: Entity) {
+        if self.observers.is_empty() {
+            return;
+        }
+        // Observers are keyed by Rust type, components registered by descriptor can't have any.
+        let Some(component) = self.components.info(id).and_then(|info| info.type_id()) else {
+            return;
+        };
+        self.trigger(ObserverKey { kind, component: Some(component) }, entity);
+    }
+
+    pub(crate) fn trigger_despawn(&mut self, entity: Entity) {
+        self.trigger(OnDespawn::key(), entity);
+    }
+
+    fn trigger(&mut self, key: ObserverKey, entity: Entity) {
+        if self.observers.depth >= MAX_OBSERVER_DEPTH {
+            #[cfg(feature = "trace")]
+            crate::trace::observer_depth_exceeded(entity);
+            return;
+        }
+        let Some(observers) = self.observers.observers.remove(&key) else {
+            return;
+        };
+        self.observers.depth += 1;
+        let mut guard = TriggerGuard {
+            world: self,
+            key,
+            observers: Some(observers),
+        };
+        // The commands of the observers are dropped if one of them panics.
+        let mut commands = CommandQueue::default();
+        for observer in guard.observers.iter_mut().flatten() {
+            let mut world = DeferredWorld {
+                world: guard.world,
+                commands: &mut commands,
+            };
+            observer(entity, &mut world);
+        }
+        guard.restore();
+        commands.apply(guard.world);
+    }
+}
+
+// Puts the observers back and leaves the trigger depth, even if an observer or command panics.
+struct TriggerGuard<'a> {
+    world: &'a mut World,
+    key: ObserverKey,
+    observers: Option<Vec<ObserverFn>>,
+}
+
+impl TriggerGuard<'_> {
+    fn restore(&mut self) {
+        // Observers can't add observers while they run, the slot is still empty.
+        if let Some(observers) = self.observers.take() {
+            self.world.observers.observers.insert(self.key, observers);
+        }
+    }
+}
+
+impl Drop for TriggerGuard<'_> {
+    fn drop(&mut self) {
+        self.restore();
+        self.world.observers.depth -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::entity::{DanglingPolicy, EntityMapper};
+    use crate::query::Query;
+    use crate::system::ResMut;
+    use crate::{Prefab, ResourceMergePolicy};
+
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Collider(f32);
+
+    #[derive(Default)]
+    struct Broadphase(Vec<(Entity, f32)>);
+
+    #[derive(Clone, Component)]
+    struct Link;
+
+    #[test]
+    fn observers_run_at_the_mutation_point() {
+        let mut world = World::new();
+        world.insert_resource(Broadphase::default());
+        world.add_observer(|trigger: Trigger<OnAdd<Collider>>, world: &mut DeferredWorld| {
+            let radius = world.get_component::<Collider>(trigger.entity()).unwrap().0;
+            world.get_resource_mut::<Broadphase>().unwrap().0.push((trigger.entity(), radius));
+        });
+        world.add_observer(|trigger: Trigger<OnRemove<Collider>>, world: &mut DeferredWorld| {
+            // The value is still there, the broadphase forgets it.
+            assert!(world.get_component::<Collider>(trigger.entity()).is_some());
+            world.get_resource_mut::<Broadphase>().unwrap().0.retain(|(e, _)| *e != trigger.entity());
+        });
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let despawned = log.clone();
+        world.add_observer(move |trigger: Trigger<OnDespawn>, world: &mut DeferredWorld| {
+            assert!(world.is_alive(trigger.entity()));
+            despawned.lock().unwrap().push(trigger.entity());
+        });
+
+        let a = *world.spawn_entity();
+        let b = *world.spawn_entity();
+        world.add_component(a, Collider(1.0));
+        // The observer already ran before anyone else could look.
+        assert_eq!(world.get_resource::<Broadphase>().unwrap().0, vec![(a, 1.0)]);
+        // Replacing a value is not an addition.
+        world.add_component(a, Collider(2.0));
+        world.add_component(b, Collider(3.0));
+        assert_eq!(world.get_resource::<Broadphase>().unwrap().0, vec![(a, 1.0), (b, 3.0)]);
+
+        world.remove_component::<Collider>(a);
+        world.despawn_entity(b);
+        assert!(world.get_resource::<Broadphase>().unwrap().0.is_empty());
+        assert_eq!(*log.lock().unwrap(), vec![b]);
+    }
+
+    #[test]
+    fn observers_take_system_params() {
+        let mut world = World::new();
+        world.insert_resource(Broadphase::default());
+        world.add_observer(
+            |trigger: Trigger<OnAdd<Collider>>, colliders: Query<&Collider>, mut broadphase: ResMut<Broadphase>, mut commands: Commands| {
+                let radius = colliders.get(trigger.entity()).unwrap().0;
+                broadphase.0.push((trigger.entity(), radius));
+                commands.insert(trigger.entity(), Link);
+            },
+        );
+        world.add_observer(|trigger: Trigger<OnAdd<Collider>>, world: &mut DeferredWorld| {
+            // The commands of the observers wait for all of them to run.
+            assert!(world.get_component::<Link>(trigger.entity()).is_none());
+        });
+        let e = *world.spawn_entity();
+        world.add_component(e, Collider(2.0));
+        assert_eq!(world.get_resource::<Broadphase>().unwrap().0, vec![(e, 2.0)]);
+        assert!(world.has_component::<Link>(e));
+    }
+
+    #[test]
+    #[should_panic(expected = "Collider")]
+    fn observer_params_are_checked_when_added() {
+        let mut world = World::new();
+        world.add_observer(|_: Trigger<OnDespawn>, _: Query<&mut Collider>, _: Query<&Collider>| {});
+    }
+
+    #[test]
+    fn nested_triggers_are_limited() {
+        let mut world = World::new();
+        let count = Arc::new(Mutex::new(0));
+        let counter = count.clone();
+        // Every link spawns another one, forever without the depth limit.
+        world.add_observer(move |_: Trigger<OnAdd<Link>>, world: &mut DeferredWorld| {
+            *counter.lock().unwrap() += 1;
+            world.commands().add(|world: &mut World| {
+                let e = *world.spawn_entity();
+                world.add_component(e, Link);
+            });
+        });
+        let e = *world.spawn_entity();
+        world.add_component(e, Link);
+        assert_eq!(*count.lock().unwrap(), MAX_OBSERVER_DEPTH);
+        assert_eq!(world.enities().len(), MAX_OBSERVER_DEPTH + 1);
+    }
+
+    #[test]
+    fn observers_fire_for_prefabs_and_merges() {
+        let mut world = World::new();
+        let added = Arc::new(Mutex::new(0));
+        let counter = added.clone();
+        world.add_observer(move |_: Trigger<OnAdd<Collider>>, _: &mut DeferredWorld| {
+            *counter.lock().unwrap() += 1;
+        });
+
+        let prefab = Prefab::new().with(Collider(1.0)).with(Link);
+        world.spawn_prefab_batch(&prefab, 10);
+        assert_eq!(*added.lock().unwrap(), 10);
+
+        let mut scene = World::new();
+        for _ in 0..5 {
+            let e = *scene.spawn_entity();
+            scene.add_component(e, Collider(2.0));
+        }
+        let mut mapper = EntityMapper::new(DanglingPolicy::Error);
+        world.merge_from(scene, &mut mapper, ResourceMergePolicy::Ignore).unwrap();
+        assert_eq!(*added.lock().unwrap(), 15);
+    }
+}
@@ -0,0 +1,234 @@
+//! Partitions split the entities in disjoint sets that are despawned or iterated at once, like
+//! the content of a streamed chunk, without a marker component to query.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::entity::Entity;
+use crate::utils::BMask;
+use crate::{EcsError, World};
+
+/// A partition of the entities of a world, see [`World::create_partition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PartitionId(u32);
+
+impl PartitionId {
+    /// The partition of the entities that were never put in another one.
+    pub const GLOBAL: Self = Self(0);
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+pub(crate) struct Partitions {
+    labels: Vec<String>,
+    // The entity indices of each partition, by partition index. The global one stays empty, its
+    // entities are the others.
+    masks: Vec<BMask>,
+    // The partition of each entity index.
+    of: Vec<u32>,
+}
+
+impl Default for Partitions {
+    fn default() -> Self {
+        Self {
+            labels: vec![String::from("global")],
+            masks: vec![BMask::new()],
+            of: Vec::new(),
+        }
+    }
+}
+
+impl Partitions {
+    fn of(&self, index: usize) -> PartitionId {
+        PartitionId(self.of.get(index).copied().unwrap_or(0))
+    }
+
+    fn check(&self, partition: PartitionId) {
+        assert!(partition.index() < self.masks.len(), "{:?} belongs to another world", partition);
+    }
+
+    fn set(&mut self, index: usize, partition: PartitionId) {
+        let previous = self.of(index);
+        if previous == partition {
+            return;
+        }
+        if previous != PartitionId::GLOBAL {
+            self.masks[previous.index()].remove(index);
+        }
+        if partition != PartitionId::GLOBAL {
+            self.masks[partition.index()].add(index);
+        }
+        if self.of.len() <= index {
+            self.of.resize(index + 1, 0);
+        }
+        self.of[index] = partition.0;
+    }
+
+    /// Puts the index of a despawned entity back in the global partition.
+    pub(crate) fn forget(&mut self, index: usize) {
+        self.set(index, PartitionId::GLOBAL);
+    }
+
+    /// Moves the partition of an entity that moved from `from` to `to`.
+    pub(crate) fn relocate(&mut self, from: usize, to: usize) {
+        let partition = self.of(from);
+        self.set(from, PartitionId::GLOBAL);
+        self.set(to, partition);
+    }
+}
+
+impl World {
+    /// The partition labeled `label`, created empty if none is yet. Partitions live as long as
+    /// the world, despawning their entities keeps them for the next ones.
+    pub fn create_partition(&mut self, label: impl Into<String>) -> PartitionId {
+        let label = label.into();
+        let partitions = &mut self.partitions;
+        if let Some(index) = partitions.labels.iter().position(|other| *other == label) {
+            return PartitionId(index as u32);
+        }
+        partitions.labels.push(label);
+        partitions.masks.push(BMask::new());
+        PartitionId(partitions.masks.len() as u32 - 1)
+    }
+
+    /// The label the partition was created with, `"global"` for [`PartitionId::GLOBAL`].
+    pub fn partition_label(&self, partition: PartitionId) -> Option<&str> {
+        self.partitions.labels.get(partition.index()).map(String::as_str)
+    }
+
+    /// The partition of the entity, [`PartitionId::GLOBAL`] for the dead ones.
+    pub fn partition_of(&self, entity: Entity) -> PartitionId {
+        match self.is_alive(entity) {
+            true => self.partitions.of(entity.index() as usize),
+            false => PartitionId::GLOBAL,
+        }
+    }
+
+    /// Moves the entity out of its partition into `partition`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another world created the partition.
+    pub fn set_partition(&mut self, entity: Entity, partition: PartitionId) -> Result<(), EcsError> {
+        self.partitions.check(partition);
+        self.entities.check_alive(entity)?;
+        self.partitions.set(entity.index() as usize, partition);
+        Ok(())
+    }
+
+    /// The entities of the partition in index order.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another world created the partition.
+    pub fn iter_partition(&self, partition: PartitionId) -> impl Iterator<Item = Entity> + '_ {
+        self.partitions.check(partition);
+        let (all, mask) = match partition {
+            PartitionId::GLOBAL => (Some(self.entities.iter()), None),
+            _ => (None, Some(self.partitions.masks[partition.index()].iter())),
+        };
+        let unassigned = |entity: &Entity| self.partitions.of(entity.index() as usize) == PartitionId::GLOBAL;
+        let global = all.into_iter().flatten().filter(unassigned);
+        let other = mask.into_iter().flatten().filter_map(|index| self.entities.get(index as u32));
+        global.chain(other)
+    }
+
+    /// Despawns every entity of the partition with [`World::despawn_batch`], and returns how
+    /// many there were. The partition stays, empty.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another world created the partition.
+    pub fn despawn_partition(&mut self, partition: PartitionId) -> usize {
+        let entities: Vec<Entity> = self.iter_partition(partition).collect();
+        self.despawn_batch(&entities)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::entity::DanglingPolicy;
+    use crate::entity::EntityMapper;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Tree(u32);
+
+    #[test]
+    fn despawning_a_partition_leaves_the_others() {
+        let mut world = World::new();
+        let chunks = [world.create_partition("chunk (3,4)"), world.create_partition("chunk (3,5)")];
+        let mut kept = world.spawn_batch((0..100).map(Tree));
+        for (i, entity) in world.spawn_batch((0..20_000).map(Tree)).into_iter().enumerate() {
+            world.set_partition(entity, chunks[i % 2]).unwrap();
+            if i % 2 == 1 {
+                kept.push(entity);
+            }
+        }
+        assert_eq!(world.despawn_partition(chunks[0]), 10_000);
+        assert_eq!(world.enities().len(), 10_100);
+        assert!(kept.iter().all(|entity| world.is_alive(*entity)));
+        assert_eq!(world.iter_partition(chunks[0]).count(), 0);
+        assert_eq!(world.iter_partition(chunks[1]).count(), 10_000);
+        assert_eq!(world.iter_partition(PartitionId::GLOBAL).collect::<Vec<_>>(), kept[..100]);
+
+        // Reloading the chunk fills the same partition.
+        assert_eq!(world.create_partition("chunk (3,4)"), chunks[0]);
+        let reloaded = world.spawn_batch([Tree(7)]);
+        world.set_partition(reloaded[0], chunks[0]).unwrap();
+        assert_eq!(world.iter_partition(chunks[0]).collect::<Vec<_>>(), reloaded);
+    }
+
+    #[test]
+    fn partitions_iterate_their_own_entities() {
+        let mut world = World::new();
+        let forest = world.create_partition("forest");
+        let lake = world.create_partition("lake");
+        let entities = world.spawn_batch((0..6).map(Tree));
+        for entity in &entities[..3] {
+            world.set_partition(*entity, forest).unwrap();
+        }
+        world.set_partition(entities[4], lake).unwrap();
+        assert_eq!(world.iter_partition(forest).collect::<Vec<_>>(), entities[..3]);
+        assert_eq!(world.iter_partition(lake).collect::<Vec<_>>(), [entities[4]]);
+        assert_eq!(world.iter_partition(PartitionId::GLOBAL).collect::<Vec<_>>(), [entities[3], entities[5]]);
+        assert_eq!(world.partition_label(lake), Some("lake"));
+        assert_eq!(world.partition_label(PartitionId::GLOBAL), Some("global"));
+
+        // Despawned entities leave their partition, the next one at their index starts global.
+        world.despawn_entity(entities[1]);
+        assert_eq!(world.iter_partition(forest).collect::<Vec<_>>(), [entities[0], entities[2]]);
+        let respawned = world.spawn_batch([Tree(9)])[0];
+        assert_eq!(respawned.index(), entities[1].index());
+        assert_eq!(world.partition_of(respawned), PartitionId::GLOBAL);
+    }
+
+    #[test]
+    fn entities_move_between_partitions() {
+        let mut world = World::new();
+        let near = world.create_partition("near");
+        let far = world.create_partition("far");
+        let entities = world.spawn_batch((0..4).map(Tree));
+        world.entity_mut(entities[2]).set_partition(near);
+        world.entity_mut(entities[3]).set_partition(near);
+        world.entity_mut(entities[2]).set_partition(far);
+        assert_eq!(world.partition_of(entities[2]), far);
+        assert_eq!(world.iter_partition(near).collect::<Vec<_>>(), [entities[3]]);
+        assert_eq!(world.iter_partition(far).collect::<Vec<_>>(), [entities[2]]);
+        world.entity_mut(entities[3]).set_partition(PartitionId::GLOBAL);
+        assert_eq!(world.iter_partition(near).count(), 0);
+        assert_eq!(world.iter_partition(PartitionId::GLOBAL).count(), 3);
+
+        // Defragmenting moves the entities with their partition.
+        world.despawn_entity(entities[0]);
+        let mut mapper = EntityMapper::new(DanglingPolicy::Keep);
+        world.defragment(&mut mapper);
+        let moved = mapper.get(entities[2]).unwrap_or(entities[2]);
+        assert_eq!(world.partition_of(moved), far);
+        assert_eq!(world.iter_partition(far).collect::<Vec<_>>(), [moved]);
+        assert_eq!(world.get_component::<Tree>(moved), Some(&Tree(2)));
+    }
+}
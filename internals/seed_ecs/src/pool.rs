@@ -0,0 +1,233 @@
+//! Pools of disabled entities handed out and taken back, for the entities spawned and despawned
+//! every frame like bullets or particles.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::marker::PhantomData;
+
+use crate::bundle::Bundle;
+use crate::entity::Entity;
+use crate::utils::HashMap;
+use crate::{EcsError, World};
+
+/// What [`Pool::acquire`] does once every entity of the pool is acquired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PoolExhaustion {
+    /// Spawns a new entity with the default bundle, it joins the pool when released.
+    #[default]
+    Grow,
+    /// Fails with [`EcsError::PoolExhausted`].
+    Fail,
+}
+
+type ResetFn = Box<dyn FnMut(&mut World, Entity) + Send + Sync>;
+
+/// Entities spawned once with the bundle `B` and kept [disabled](crate::query::Disabled) while
+/// they are not in use, created by [`World::create_pool`].
+///
+/// Acquiring an entity enables it and releasing it resets its components and disables it again,
+/// so the queries stop seeing it without despawning it. The pool doesn't own the world, the same
+/// world must be given to every call.
+pub struct Pool<B: Bundle> {
+    free: Vec<Entity>,
+    // Every entity of the pool, with whether it is acquired.
+    members: HashMap<Entity, bool>,
+    reset: ResetFn,
+    exhaustion: PoolExhaustion,
+    _marker: PhantomData<fn() -> B>,
+}
+
+impl<B: Bundle + Default> Pool<B> {
+    /// Replaces how released entities are reset, by default `B::default()` is inserted again.
+    pub fn with_reset(mut self, reset: impl FnMut(&mut World, Entity) + Send + Sync + 'static) -> Self {
+        self.reset = Box::new(reset);
+        self
+    }
+
+    pub fn with_exhaustion(mut self, exhaustion: PoolExhaustion) -> Self {
+        self.exhaustion = exhaustion;
+        self
+    }
+
+    /// Number of entities owned by the pool, acquired or not.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    /// Number of entities that can be acquired without exhausting the pool.
+    pub fn available(&self) -> usize {
+        self.free.len()
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the pool is exhausted with [`PoolExhaustion::Fail`], see [`Pool::try_acquire`].
+    pub fn acquire(&mut self, world: &mut World) -> Entity {
+        self.try_acquire(world).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Enables an entity of the pool and returns it. The entities despawned by someone else are
+    /// forgotten on the way.
+    pub fn try_acquire(&mut self, world: &mut World) -> Result<Entity, EcsError> {
+        while let Some(entity) = self.free.pop() {
+            if world.is_alive(entity) {
+                world.entity_mut(entity).set_enabled(true);
+                self.members.insert(entity, true);
+                return Ok(entity);
+            }
+            self.members.remove(&entity);
+        }
+        match self.exhaustion {
+            PoolExhaustion::Grow => {
+                let entity = world.try_spawn_entity()?;
+                world.insert_bundle(entity, B::default());
+                self.members.insert(entity, true);
+                Ok(entity)
+            }
+            PoolExhaustion::Fail => Err(EcsError::PoolExhausted {
+                type_name: type_name::<B>(),
+                capacity: self.members.len(),
+            }),
+        }
+    }
+
+    /// Resets the entity and disables it, returns false if it was not acquired from this pool.
+    pub fn release(&mut self, world: &mut World, entity: Entity) -> bool {
+        match self.members.get_mut(&entity) {
+            Some(acquired) if *acquired => *acquired = false,
+            _ => return false,
+        }
+        if !world.is_alive(entity) {
+            self.members.remove(&entity);
+            return false;
+        }
+        (self.reset)(world, entity);
+        world.entity_mut(entity).set_enabled(false);
+        self.free.push(entity);
+        true
+    }
+}
+
+impl World {
+    /// Spawns `capacity` disabled entities with `B::default()` to hand out with [`Pool::acquire`].
+    pub fn create_pool<B: Bundle + Default>(&mut self, capacity: usize) -> Pool<B> {
+        let mut free = self.spawn_batch_iter((0..capacity).map(|_| B::default()));
+        for entity in &free {
+            self.entity_mut(*entity).set_enabled(false);
+        }
+        // Entities are popped from the back, hand out the first spawned first.
+        free.reverse();
+        Pool {
+            members: free.iter().map(|entity| (*entity, false)).collect(),
+            free,
+            reset: Box::new(|world, entity| world.insert_bundle(entity, B::default())),
+            exhaustion: PoolExhaustion::default(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::query::IncludeDisabled;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Default, Component)]
+    struct Bullet {
+        traveled: u32,
+    }
+    #[derive(Debug, Clone, Copy, PartialEq, Default, Component)]
+    struct Damage(u32);
+
+    fn active(world: &mut World) -> usize {
+        let bullets = world.query::<&Bullet>();
+        bullets.iter(world).count()
+    }
+
+    #[test]
+    fn cycles_under_capacity_reuse_the_entities() {
+        let mut world = World::new();
+        let mut pool = world.create_pool::<(Bullet, Damage)>(16);
+        assert_eq!((pool.len(), pool.available(), active(&mut world)), (16, 16, 0));
+        let mut acquired = Vec::new();
+        for frame in 0..200 {
+            for _ in 0..(frame % 7) {
+                let e = pool.acquire(&mut world);
+                world.get_component_mut::<Bullet>(e).unwrap().traveled += frame;
+                acquired.push(e);
+            }
+            assert_eq!(active(&mut world), acquired.len());
+            // The oldest bullets hit something.
+            let hits = acquired.len() / 2 + 1;
+            for e in acquired.drain(..hits.min(acquired.len())) {
+                assert!(pool.release(&mut world, e));
+                assert!(!pool.release(&mut world, e));
+            }
+            assert!(acquired.len() < 16);
+            assert_eq!(world.enities().len(), 16);
+        }
+        assert_eq!(pool.len(), 16);
+        // Released entities were reset to the default bundle.
+        let everything = world.query_filtered::<&Bullet, IncludeDisabled>();
+        let traveled: u32 = everything.iter(&world).map(|b| b.traveled).sum();
+        let kept: u32 = acquired.iter().map(|e| world.get_component::<Bullet>(*e).unwrap().traveled).sum();
+        assert_eq!(traveled, kept);
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn exhaustion_policies() {
+        let mut world = World::new();
+        let mut pool = world.create_pool::<(Bullet,)>(2).with_exhaustion(PoolExhaustion::Fail);
+        let a = pool.acquire(&mut world);
+        let b = pool.acquire(&mut world);
+        let error = pool.try_acquire(&mut world).unwrap_err();
+        assert_eq!(
+            error,
+            EcsError::PoolExhausted {
+                type_name: type_name::<(Bullet,)>(),
+                capacity: 2,
+            }
+        );
+        pool.release(&mut world, a);
+        assert_eq!(pool.acquire(&mut world), a);
+
+        // A despawned entity leaves the pool instead of coming back.
+        world.despawn_entity(b);
+        assert!(!pool.release(&mut world, b));
+        assert_eq!(pool.len(), 1);
+
+        let mut pool = pool.with_exhaustion(PoolExhaustion::Grow);
+        let c = pool.acquire(&mut world);
+        assert_eq!(world.get_component::<Bullet>(c), Some(&Bullet::default()));
+        assert_eq!((pool.len(), pool.available()), (2, 0));
+        assert!(pool.release(&mut world, c));
+        assert_eq!(pool.available(), 1);
+        let stranger = *world.spawn_entity();
+        assert!(!pool.release(&mut world, stranger));
+    }
+
+    #[test]
+    fn custom_reset_and_gameplay_queries() {
+        let mut world = World::new();
+        let mut pool = world.create_pool::<(Bullet, Damage)>(4).with_reset(|world, entity| {
+            world.get_component_mut::<Damage>(entity).unwrap().0 = 1;
+        });
+        let shots: Vec<Entity> = (0..3).map(|_| pool.acquire(&mut world)).collect();
+        for e in &shots {
+            world.get_component_mut::<Damage>(*e).unwrap().0 = 10;
+        }
+        let damage = world.query::<(Entity, &Damage)>();
+        assert_eq!(damage.iter(&world).map(|(_, d)| d.0).sum::<u32>(), 30);
+        pool.release(&mut world, shots[1]);
+        assert_eq!(damage.iter(&world).map(|(e, _)| e).collect::<Vec<_>>(), [shots[0], shots[2]]);
+        assert_eq!(world.get_component::<Damage>(shots[1]), Some(&Damage(1)));
+        assert!(!world.entity_mut(shots[1]).is_enabled());
+    }
+}
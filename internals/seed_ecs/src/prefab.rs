@@ -0,0 +1,176 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+
+use crate::change_detection::Tick;
+use crate::component::{Component, ComponentId};
+use crate::entity::Entity;
+use crate::entity_ref::EntityMut;
+use crate::observer::ObserverKind;
+use crate::storage::{AnyStorage, Storage};
+use crate::World;
+
+struct PrefabComponent {
+    type_id: TypeId,
+    value: Box<dyn Any + Send + Sync>,
+    register: fn(&mut World) -> ComponentId,
+    // Inserts a clone of the value at each of the indices.
+    insert: fn(&dyn Any, &mut dyn AnyStorage, &[usize], Tick),
+}
+
+fn insert_clones<T: Clone + 'static>(value: &dyn Any, storage: &mut dyn AnyStorage, indices: &[usize], tick: Tick) {
+    let value = value.downcast_ref::<T>().expect("Prefab value type mismatch");
+    let storage = storage
+        .as_any_mut()
+        .downcast_mut::<Storage<T>>()
+        .expect("Storage type mismatch");
+    for index in indices {
+        storage.insert(*index, value.clone(), tick);
+    }
+}
+
+/// A set of component values to spawn entities from.
+///
+/// ```
+/// # use seed_ecs::component::Component;
+/// # use seed_ecs::{Prefab, World};
+/// #[derive(Clone, Component)]
+/// struct Health(u32);
+///
+/// let orc = Prefab::new().with(Health(100));
+/// let mut world = World::new();
+/// let entity = world.spawn_prefab(&orc);
+/// assert_eq!(world.get_component::<Health>(entity).unwrap().0, 100);
+/// ```
+#[derive(Default)]
+pub struct Prefab {
+    components: Vec<PrefabComponent>,
+}
+
+impl Prefab {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a component to the prefab, replacing the previous value of that type.
+    pub fn with<T: Component + Clone>(mut self, component: T) -> Self {
+        let component = PrefabComponent {
+            type_id: TypeId::of::<T>(),
+            value: Box::new(component),
+            register: World::register_component::<T>,
+            insert: insert_clones::<T>,
+        };
+        match self.components.iter_mut().find(|c| c.type_id == component.type_id) {
+            Some(previous) => *previous = component,
+            None => self.components.push(component),
+        }
+        self
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        let component = self.components.iter().find(|c| c.type_id == TypeId::of::<T>())?;
+        component.value.downcast_ref()
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.get::<T>().is_some()
+    }
+
+    /// The number of component types in the prefab.
+    pub fn len(&self) -> usize {
+        self.components.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.components.is_empty()
+    }
+}
+
+impl World {
+    /// Spawns an entity with a clone of every component of the prefab.
+    pub fn spawn_prefab(&mut self, prefab: &Prefab) -> Entity {
+        self.spawn_prefab_batch(prefab, 1)[0]
+    }
+
+    /// Spawns `count` entities from the prefab, filling each storage in one go.
+    pub fn spawn_prefab_batch(&mut self, prefab: &Prefab, count: usize) -> Vec<Entity> {
+        let entities: Vec<Entity> = (0..count).map(|_| *self.spawn_entity()).collect();
+        let indices: Vec<usize> = entities.iter().map(|e| e.index() as usize).collect();
+        for component in &prefab.components {
+            let id = (component.register)(self);
+            (component.insert)(&*component.value, self.storages.get_mut(id), &indices, self.change_tick);
+            self.metrics.inserted(id, count as u32);
+            for entity in &entities {
+                self.join_groups(*entity, id);
+            }
+        }
+        self.flush_relocations(&[]);
+        if self.has_observers() {
+            for component in &prefab.components {
+                let id = self.components.get_id(component.type_id).unwrap();
+                for entity in &entities {
+                    self.trigger_component(ObserverKind::Add, id, *entity);
+                }
+            }
+        }
+        entities
+    }
+
+    /// Spawns an entity from the prefab then lets `f` override its components.
+    pub fn spawn_prefab_with<F>(&mut self, prefab: &Prefab, f: F) -> Entity
+    where
+        F: FnOnce(EntityMut<'_>),
+    {
+        let entity = self.spawn_prefab(prefab);
+        f(self.entity_mut(entity));
+        entity
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Health(u32);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Sprite(&'static str);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Enemy;
+
+    fn orc() -> Prefab {
+        Prefab::new().with(Health(50)).with(Sprite("orc")).with(Enemy).with(Health(100))
+    }
+
+    #[test]
+    fn spawn_many_from_one_prefab() {
+        let prefab = orc();
+        assert_eq!(prefab.len(), 3);
+        assert_eq!(prefab.get::<Health>(), Some(&Health(100)));
+
+        let mut world = World::new();
+        let orcs = world.spawn_prefab_batch(&prefab, 1000);
+        assert_eq!(world.enities().len(), 1000);
+        for orc in &orcs {
+            assert_eq!(world.get_component::<Health>(*orc), Some(&Health(100)));
+            assert_eq!(world.get_component::<Sprite>(*orc), Some(&Sprite("orc")));
+            assert!(world.has_component::<Enemy>(*orc));
+        }
+        assert_eq!(world.storage_stats::<Health>().live, 1000);
+    }
+
+    #[test]
+    fn override_one_instance() {
+        let prefab = orc();
+        let mut world = World::new();
+        let plain = world.spawn_prefab(&prefab);
+        let boss = world.spawn_prefab_with(&prefab, |mut e| {
+            e.get_mut::<Health>().unwrap().0 = 500;
+            e.insert(Sprite("orc_boss"));
+        });
+        assert_eq!(world.get_component::<Health>(plain), Some(&Health(100)));
+        assert_eq!(world.get_component::<Health>(boss), Some(&Health(500)));
+        assert_eq!(world.get_component::<Sprite>(boss), Some(&Sprite("orc_boss")));
+        assert_eq!(prefab.get::<Health>(), Some(&Health(100)));
+    }
+}
@@ -0,0 +1,60 @@
+//! The types and traits most programs need, `use seed_ecs::prelude::*;` brings them in scope.
+//!
+//! ```
+//! use seed_ecs::prelude::*;
+//!
+//! #[derive(Clone, Component)]
+//! struct Position(f32);
+//! #[derive(Clone, Component)]
+//! struct Velocity(f32);
+//! struct Frame(u32);
+//!
+//! fn movement(mut query: Query<(&mut Position, &Velocity)>, mut frame: ResMut<Frame>) {
+//!     for (mut position, velocity) in query.iter_mut() {
+//!         position.0 += velocity.0;
+//!     }
+//!     frame.0 += 1;
+//! }
+//!
+//! fn cleanup(query: Query<(Entity, &Position), Without<Velocity>>, mut commands: Commands) {
+//!     for (entity, position) in query.iter() {
+//!         if position.0 > 10.0 {
+//!             commands.despawn(entity);
+//!         }
+//!     }
+//! }
+//!
+//! let mut world = World::new();
+//! world.insert_resource(Frame(0));
+//! let moving = Prefab::new().with(Position(0.0)).with(Velocity(4.0));
+//! for entity in world.spawn_prefab_batch(&moving, 3) {
+//!     world.entity_mut(entity).insert(Velocity(entity.index() as f32 * 4.0));
+//! }
+//! let rock = world.spawn_prefab(&Prefab::new().with(Position(20.0)));
+//!
+//! let mut systems: Vec<Box<dyn System>> = vec![
+//!     Box::new(movement.into_system()),
+//!     Box::new(cleanup.into_system()),
+//! ];
+//! for _ in 0..3 {
+//!     for system in &mut systems {
+//!         system.run(&mut world);
+//!     }
+//! }
+//! assert_eq!(world.get_resource::<Frame>().unwrap().0, 3);
+//! assert!(!world.is_alive(rock));
+//! assert_eq!(world.enities().len(), 3);
+//! ```
+
+pub use crate::change_detection::Mut;
+pub use crate::commands::Commands;
+pub use crate::component::Component;
+pub use crate::entity::{Entity, EntityWeak, MapEntities};
+pub use crate::event::{EventCursor, EventReader, EventSettings, EventWriter, Events};
+pub use crate::hierarchy::{Children, Name, Parent};
+pub use crate::observer::{DeferredWorld, OnAdd, OnDespawn, OnRemove, Trigger};
+pub use crate::query::{Added, Changed, Disabled, IncludeDisabled, Query, QueryState, With, Without};
+pub use crate::reflect::Reflect;
+pub use crate::relation::Relation;
+pub use crate::system::{IntoSetConfig, IntoSystem, IntoSystemConfig, Local, Res, ResMut, System, SystemParam, SystemSet};
+pub use crate::{Bundle, EntityMut, EntityRef, FromWorld, Prefab, World};
@@ -0,0 +1,106 @@
+use alloc::vec::Vec;
+
+use crate::component::ComponentId;
+
+/// The components a query reads and writes, kept sorted.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Access {
+    reads: Vec<ComponentId>,
+    writes: Vec<ComponentId>,
+    // The first component two members of a tuple reach with one of them writing it.
+    aliased: Option<ComponentId>,
+}
+
+impl Access {
+    pub fn add_read(&mut self, id: ComponentId) {
+        if let Err(at) = self.reads.binary_search(&id) {
+            self.reads.insert(at, id);
+        }
+    }
+
+    pub fn add_write(&mut self, id: ComponentId) {
+        if let Err(at) = self.writes.binary_search(&id) {
+            self.writes.insert(at, id);
+        }
+    }
+
+    /// Adds the access of another member of the same tuple query. Their items alias if one of
+    /// them writes a component the other reaches, see [`Access::aliased`].
+    pub fn add_member(&mut self, member: &Access) {
+        if self.aliased.is_none() {
+            self.aliased = member.aliased.or_else(|| self.conflict(member));
+        }
+        for id in &member.reads {
+            self.add_read(*id);
+        }
+        for id in &member.writes {
+            self.add_write(*id);
+        }
+    }
+
+    /// A component the query reaches twice with one of them writing it, like `(&mut A, &A)`.
+    /// Such a query would hand out a mutable and another reference to the same value.
+    pub fn aliased(&self) -> Option<ComponentId> {
+        self.aliased
+    }
+
+    pub fn reads(&self) -> &[ComponentId] {
+        &self.reads
+    }
+
+    pub fn writes(&self) -> &[ComponentId] {
+        &self.writes
+    }
+
+    /// True if the component is read or written.
+    pub fn has_read(&self, id: ComponentId) -> bool {
+        self.reads.binary_search(&id).is_ok() || self.has_write(id)
+    }
+
+    pub fn has_write(&self, id: ComponentId) -> bool {
+        self.writes.binary_search(&id).is_ok()
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.writes.is_empty()
+    }
+
+    /// A component one of the accesses writes and the other reads or writes, if any.
+    pub fn conflict(&self, other: &Access) -> Option<ComponentId> {
+        let written = self.writes.iter().find(|id| other.has_read(**id));
+        written.or_else(|| other.writes.iter().find(|id| self.has_read(**id))).copied()
+    }
+}
+
+/// The access of a query along with the components its entities must and must not have.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FilteredAccess {
+    access: Access,
+    with: Vec<ComponentId>,
+    without: Vec<ComponentId>,
+}
+
+impl FilteredAccess {
+    pub fn new(access: Access, with: Vec<ComponentId>, without: Vec<ComponentId>) -> Self {
+        Self { access, with, without }
+    }
+
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    /// True if no entity can be matched by both queries, one requiring a component the other
+    /// excludes.
+    pub fn is_disjoint(&self, other: &FilteredAccess) -> bool {
+        self.with.iter().any(|id| other.without.contains(id))
+            || other.with.iter().any(|id| self.without.contains(id))
+    }
+
+    /// A component both queries may reach with at least one writing it, if any.
+    pub fn conflict(&self, other: &FilteredAccess) -> Option<ComponentId> {
+        if self.is_disjoint(other) {
+            return None;
+        }
+        self.access.conflict(&other.access)
+    }
+}
@@ -0,0 +1,291 @@
+use alloc::vec::Vec;
+use core::iter;
+use core::slice;
+
+use super::{QueryFilter, QueryState, ReadOnlyWorldQuery, WorldQuery};
+use crate::entity::{Entity, WorldId};
+use crate::World;
+
+/// A [`QueryState`] keeping the entities it matched, for the queries that run every frame over
+/// components rarely added or removed, like the obstacles of a pathfinder.
+///
+/// The masks are intersected again only once an entity was spawned or despawned, or a value of a
+/// required or excluded component was added or removed, since the last iteration. The filters
+/// reading the ticks, like [`Changed`](super::Changed), are checked on each iteration against
+/// the cached entities, so changing a value never rebuilds the list.
+///
+/// ```
+/// # use seed_ecs::prelude::*;
+/// # use seed_ecs::query::CachedQuery;
+/// #[derive(Component)]
+/// struct NavObstacle;
+/// #[derive(Component)]
+/// struct Transform(f32);
+///
+/// let mut world = World::new();
+/// let rock = world.spawn_batch_iter([(NavObstacle, Transform(2.0))])[0];
+/// let mut obstacles = CachedQuery::<(&NavObstacle, &Transform)>::new(&mut world);
+/// assert_eq!(obstacles.iter(&world).count(), 1);
+/// world.get_component_mut::<Transform>(rock).unwrap().0 = 3.0;
+/// assert_eq!(obstacles.iter(&world).count(), 1);
+/// assert_eq!(obstacles.rebuilds(), 1);
+/// ```
+pub struct CachedQuery<Q: WorldQuery, F: QueryFilter = ()> {
+    state: QueryState<Q, F>,
+    world: WorldId,
+    entities: Vec<Entity>,
+    // The structural changes of the entity mask then of the required and excluded storages when
+    // the entities were matched, `None` until the first match.
+    built: Option<Vec<u64>>,
+    rebuilds: u64,
+}
+
+impl<Q: WorldQuery, F: QueryFilter> CachedQuery<Q, F> {
+    /// The query can only iterate over `world`.
+    ///
+    /// # Panics
+    ///
+    /// Same as [`QueryState::new`].
+    pub fn new(world: &mut World) -> Self {
+        Self {
+            state: QueryState::new(world),
+            world: world.id(),
+            entities: Vec::new(),
+            built: None,
+            rebuilds: 0,
+        }
+    }
+
+    pub fn state(&self) -> &QueryState<Q, F> {
+        &self.state
+    }
+
+    /// The items of the matched entities in ascending index order, matching the entities again
+    /// first if the list is stale.
+    pub fn iter<'w, 's>(&'s mut self, world: &'w World) -> CachedQueryIter<'w, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        self.refresh(world);
+        // Read only queries can't alias anything.
+        unsafe { CachedQueryIter::new(world, self) }
+    }
+
+    /// Mutable version of [`CachedQuery::iter`].
+    pub fn iter_mut<'w, 's>(&'s mut self, world: &'w mut World) -> CachedQueryIter<'w, 's, Q, F> {
+        self.refresh(world);
+        // The world is borrowed mutably for as long as the items live, and the entities are
+        // distinct.
+        unsafe { CachedQueryIter::new(world, self) }
+    }
+
+    /// Drops the list, the next iteration matches the entities again.
+    pub fn invalidate(&mut self) {
+        self.built = None;
+    }
+
+    /// True if the next iteration will match the entities again, because the list was never
+    /// built, was [invalidated](CachedQuery::invalidate), or entities or components it depends on
+    /// were added or removed since.
+    pub fn is_stale(&self, world: &World) -> bool {
+        match &self.built {
+            Some(changes) => !changes.iter().copied().eq(self.changes(world)),
+            None => true,
+        }
+    }
+
+    /// How many times the entities were matched.
+    pub fn rebuilds(&self) -> u64 {
+        self.rebuilds
+    }
+
+    /// The entities matched by the last iteration, before the filters, which may be stale.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    // The structural changes the list depends on, in the order of `built`.
+    fn changes<'a>(&'a self, world: &'a World) -> impl Iterator<Item = u64> + 'a {
+        let storages = self.state.required.iter().chain(&self.state.excluded);
+        iter::once(world.entities.mask().changes())
+            .chain(storages.map(|id| world.storages.get(*id).structural_changes()))
+    }
+
+    fn refresh(&mut self, world: &World) {
+        assert_eq!(world.id(), self.world, "CachedQuery used with another world than its own");
+        if !self.is_stale(world) {
+            return;
+        }
+        self.entities.clear();
+        let cell = world.as_unsafe_world_cell();
+        let driver = self.state.driver(cell);
+        let mut next_word = 0;
+        while let Some(word_idx) = driver.next_word(next_word) {
+            next_word = word_idx + 1;
+            let mut bits = self.state.word(cell, word_idx);
+            while bits != 0 {
+                let index = (word_idx << 5) | bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                // The entity mask is part of the intersection so the index is alive.
+                self.entities.push(world.entities.get(index as u32).unwrap());
+            }
+        }
+        self.built = Some(self.changes(world).collect());
+        self.rebuilds += 1;
+    }
+}
+
+/// Iterates over the entities kept by a [`CachedQuery`], see [`CachedQuery::iter`].
+pub struct CachedQueryIter<'w, 's, Q: WorldQuery, F: QueryFilter> {
+    entities: slice::Iter<'s, Entity>,
+    fetch: Q::Fetch<'w>,
+    filter: F::Fetch<'w>,
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> CachedQueryIter<'w, 's, Q, F> {
+    // The caller must make sure the access of the query is allowed on `world`, and that the
+    // entities of the cache are up to date.
+    unsafe fn new(world: &'w World, cache: &'s CachedQuery<Q, F>) -> Self {
+        let world = world.as_unsafe_world_cell();
+        Self {
+            entities: cache.entities.iter(),
+            fetch: Q::init_fetch(world, &cache.state.fetch_state),
+            filter: F::init_fetch(world, &cache.state.filter_state),
+        }
+    }
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Iterator for CachedQueryIter<'w, 's, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entity = *self.entities.next()?;
+            if F::filter(&mut self.filter, entity.index() as usize) {
+                return Some(unsafe { Q::fetch(&mut self.fetch, entity) });
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.entities.len()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::query::{Changed, Without};
+    use crate::testing::Rng;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct NavObstacle(u32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Transform(u32);
+    #[derive(Component)]
+    struct Static;
+
+    #[test]
+    fn value_changes_keep_the_list() {
+        let mut world = World::new();
+        let rocks: Vec<Entity> = world.spawn_batch_iter((0..4).map(|i| (NavObstacle(i), Transform(i))));
+        let mut cached = CachedQuery::<(&NavObstacle, &Transform)>::new(&mut world);
+        assert!(cached.is_stale(&world));
+        for frame in 0..10 {
+            assert_eq!(cached.iter(&world).count(), 4);
+            for mut transform in world.query::<&mut Transform>().iter_mut(&mut world) {
+                transform.0 += frame;
+            }
+            world.get_component_mut::<NavObstacle>(rocks[1]).unwrap().0 = frame;
+            // Unrelated components come and go.
+            world.add_component(rocks[2], Static);
+            world.remove_component::<Static>(rocks[2]);
+            assert!(!cached.is_stale(&world));
+        }
+        assert_eq!(cached.rebuilds(), 1);
+        assert_eq!(cached.entities(), rocks);
+    }
+
+    #[test]
+    fn structural_changes_rebuild_the_list() {
+        let mut world = World::new();
+        let rocks: Vec<Entity> = world.spawn_batch_iter((0..3).map(|i| (NavObstacle(i), Transform(i))));
+        let mut cached = CachedQuery::<&NavObstacle, Without<Static>>::new(&mut world);
+        assert_eq!(cached.iter(&world).count(), 3);
+
+        world.add_component(rocks[0], Static);
+        assert!(cached.is_stale(&world));
+        assert_eq!(cached.iter(&world).collect::<Vec<_>>(), [&NavObstacle(1), &NavObstacle(2)]);
+        world.despawn_entity(rocks[1]);
+        let spawned = world.spawn_batch([NavObstacle(3)])[0];
+        // The new entity took the index of the despawned one.
+        assert_eq!(cached.iter(&world).collect::<Vec<_>>(), [&NavObstacle(3), &NavObstacle(2)]);
+        assert_eq!(cached.rebuilds(), 3);
+
+        cached.invalidate();
+        assert!(cached.is_stale(&world));
+        assert_eq!(cached.iter(&world).count(), 2);
+        world.despawn_entity(spawned);
+        assert_eq!(cached.iter(&world).count(), 1);
+        assert_eq!(cached.rebuilds(), 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "another world than its own")]
+    fn other_worlds_are_rejected() {
+        let mut world = World::new();
+        let mut cached = CachedQuery::<&NavObstacle>::new(&mut world);
+        let other = World::new();
+        cached.iter(&other).count();
+    }
+
+    #[test]
+    fn results_match_an_uncached_query() {
+        let mut world = World::new();
+        let mut entities: Vec<Entity> = world.spawn_batch_iter((0..50).map(|i| (NavObstacle(i), Transform(i))));
+        let mut cached = CachedQuery::<(Entity, &mut Transform), Without<Static>>::new(&mut world);
+        let mut changed = CachedQuery::<Entity, Changed<NavObstacle>>::new(&mut world);
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+        for step in 0..500u32 {
+            let e = entities[rng.below(entities.len())];
+            match rng.below(7) {
+                0 => {
+                    world.add_component(e, Static);
+                }
+                1 => {
+                    world.remove_component::<Static>(e);
+                }
+                2 => {
+                    world.remove_component::<Transform>(e);
+                }
+                3 => {
+                    world.add_component(e, Transform(step));
+                }
+                4 => {
+                    world.despawn_entity(e);
+                    entities.retain(|other| *other != e);
+                    entities.extend(world.spawn_batch_iter([(NavObstacle(step), Transform(step))]));
+                }
+                5 => world.get_component_mut::<NavObstacle>(e).unwrap().0 = step,
+                _ => {
+                    if let Some(mut transform) = world.get_component_mut::<Transform>(e) {
+                        transform.0 = step;
+                    }
+                }
+            }
+            let expected: Vec<(Entity, u32)> = world
+                .query_filtered::<(Entity, &Transform), Without<Static>>()
+                .iter(&world)
+                .map(|(entity, transform)| (entity, transform.0))
+                .collect();
+            let got: Vec<(Entity, u32)> =
+                cached.iter_mut(&mut world).map(|(entity, transform)| (entity, transform.0)).collect();
+            assert_eq!(got, expected, "step {}", step);
+            let expected: Vec<Entity> = world.query_filtered::<Entity, Changed<NavObstacle>>().iter(&world).collect();
+            assert_eq!(changed.iter(&world).collect::<Vec<_>>(), expected, "step {}", step);
+            world.clear_trackers();
+        }
+        assert!(cached.rebuilds() < 500);
+    }
+}
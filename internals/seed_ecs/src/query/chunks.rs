@@ -0,0 +1,364 @@
+use core::ops::Range;
+
+use super::{Query, QueryFilter, QueryState, WorldQuery};
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::tuples::all_tuples;
+use crate::utils::PAGE_SIZE;
+use crate::UnsafeWorldCell;
+
+/// The most entities a chunk of [`Query::iter_chunks`] holds. Components are stored in pages of
+/// that many consecutive indices and a chunk never spans two pages.
+pub const MAX_CHUNK_LEN: usize = PAGE_SIZE;
+
+/// Queries that can fetch the components of consecutive entities as slices.
+///
+/// # Safety
+///
+/// `fetch_chunk` must only access what the [`WorldQuery`] implementation declares.
+pub unsafe trait ChunkQuery: WorldQuery {
+    type Chunk<'w>;
+
+    /// # Safety
+    ///
+    /// Every index of `range` must be matched by the query, and the range must not be empty nor
+    /// cross a multiple of [`MAX_CHUNK_LEN`]. For mutable queries the caller must not fetch the
+    /// same index twice while the first chunk is still alive.
+    unsafe fn fetch_chunk<'w>(fetch: &mut Self::Fetch<'w>, range: Range<usize>) -> Self::Chunk<'w>;
+
+    /// The values at `positions` of the sparse sets of a group, see
+    /// [`World::register_group`](crate::World::register_group).
+    ///
+    /// # Safety
+    ///
+    /// The query must require exactly the components of the group and `positions` must be within
+    /// its members. Mutable queries have the same requirement as for [`ChunkQuery::fetch_chunk`].
+    unsafe fn fetch_group_chunk<'w>(fetch: &mut Self::Fetch<'w>, positions: Range<usize>) -> Self::Chunk<'w>;
+
+    /// How many of the matched indices at the start of `range` can be fetched as one chunk:
+    /// all of them unless a component lives in a sparse set in another order.
+    fn chunk_len(fetch: &Self::Fetch<'_>, range: Range<usize>) -> usize;
+}
+
+unsafe impl<T: Component> ChunkQuery for &T {
+    type Chunk<'w> = &'w [T];
+
+    #[inline]
+    unsafe fn fetch_chunk<'w>(fetch: &mut Self::Fetch<'w>, range: Range<usize>) -> Self::Chunk<'w> {
+        (*fetch).slice(range)
+    }
+
+    #[inline]
+    unsafe fn fetch_group_chunk<'w>(fetch: &mut Self::Fetch<'w>, positions: Range<usize>) -> Self::Chunk<'w> {
+        (*fetch).positions(positions)
+    }
+
+    #[inline]
+    fn chunk_len(fetch: &Self::Fetch<'_>, range: Range<usize>) -> usize {
+        fetch.contiguous_len(range)
+    }
+}
+
+unsafe impl<T: Component> ChunkQuery for &mut T {
+    type Chunk<'w> = &'w mut [T];
+
+    // The whole chunk is marked changed, writes through a slice can't be told apart.
+    #[inline]
+    unsafe fn fetch_chunk<'w>(fetch: &mut Self::Fetch<'w>, range: Range<usize>) -> Self::Chunk<'w> {
+        fetch.slice(range)
+    }
+
+    #[inline]
+    unsafe fn fetch_group_chunk<'w>(fetch: &mut Self::Fetch<'w>, positions: Range<usize>) -> Self::Chunk<'w> {
+        fetch.positions(positions)
+    }
+
+    #[inline]
+    fn chunk_len(fetch: &Self::Fetch<'_>, range: Range<usize>) -> usize {
+        fetch.contiguous_len(range)
+    }
+}
+
+macro_rules! impl_tuple_chunk_query {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case, clippy::unused_unit)]
+        unsafe impl<$($name: ChunkQuery),*> ChunkQuery for ($($name,)*) {
+            type Chunk<'w> = ($($name::Chunk<'w>,)*);
+
+            #[inline]
+            unsafe fn fetch_chunk<'w>(fetch: &mut Self::Fetch<'w>, _range: Range<usize>) -> Self::Chunk<'w> {
+                let ($($name,)*) = fetch;
+                ($($name::fetch_chunk($name, _range.clone()),)*)
+            }
+
+            #[inline]
+            unsafe fn fetch_group_chunk<'w>(fetch: &mut Self::Fetch<'w>, _positions: Range<usize>) -> Self::Chunk<'w> {
+                let ($($name,)*) = fetch;
+                ($($name::fetch_group_chunk($name, _positions.clone()),)*)
+            }
+
+            #[inline]
+            fn chunk_len(fetch: &Self::Fetch<'_>, range: Range<usize>) -> usize {
+                let ($($name,)*) = fetch;
+                let len = range.len();
+                $(let len = len.min($name::chunk_len($name, range.clone()));)*
+                len
+            }
+        }
+    };
+}
+
+all_tuples!(impl_tuple_chunk_query);
+
+impl<'w, 's, Q: ChunkQuery, F: QueryFilter> Query<'w, 's, Q, F> {
+    /// Iterates over the matched entities by runs of consecutive indices, giving each component
+    /// of the run as one slice along with the entities:
+    ///
+    /// ```
+    /// # use seed_ecs::prelude::*;
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Velocity(f32);
+    ///
+    /// fn integrate(mut query: Query<(&mut Position, &Velocity)>) {
+    ///     for (entities, (positions, velocities)) in query.iter_chunks() {
+    ///         assert_eq!(entities.len(), positions.len());
+    ///         for (position, velocity) in positions.iter_mut().zip(velocities) {
+    ///             position.0 += velocity.0;
+    ///         }
+    ///     }
+    /// }
+    /// # IntoSystem::into_system(integrate).run(&mut World::new());
+    /// ```
+    ///
+    /// Runs are as long as possible but stop at the first index the query doesn't match and at
+    /// every multiple of [`MAX_CHUNK_LEN`], so a fragmented world gives many short chunks, down
+    /// to one entity each. Chunks come in ascending index order, like [`Query::iter`].
+    ///
+    /// Over a group the values are packed in the order of the group, so ascending indices are
+    /// seldom contiguous and the chunks are short, see [`Query::iter_chunks_unordered`].
+    ///
+    /// Slices are aligned for their element type and nothing more: a chunk may start at any index
+    /// of a page, only the start of a page is aligned like a page allocated for `T`. Code using
+    /// wider SIMD loads should handle the unaligned head and tail of the slices.
+    ///
+    /// The mutable components of every chunk are marked changed when the chunk is fetched.
+    pub fn iter_chunks(&mut self) -> QueryChunkIter<'_, 's, Q, F> {
+        // The query is borrowed mutably for as long as the chunks live.
+        unsafe { QueryChunkIter::new(self.world, self.state, false) }
+    }
+
+    /// Same as [`Query::iter_chunks`] but in an unspecified order. When the query requires
+    /// exactly the components of a group, the chunks are runs of its members in the order of the
+    /// group, as long as [`MAX_CHUNK_LEN`] and only split by the filters. See
+    /// [`World::register_group`](crate::World::register_group).
+    pub fn iter_chunks_unordered(&mut self) -> QueryChunkIter<'_, 's, Q, F> {
+        unsafe { QueryChunkIter::new(self.world, self.state, true) }
+    }
+}
+
+/// Iterates over runs of consecutive entities of a query, see [`Query::iter_chunks`].
+pub struct QueryChunkIter<'w, 's, Q: WorldQuery, F: QueryFilter> {
+    world: UnsafeWorldCell<'w>,
+    state: &'s QueryState<Q, F>,
+    fetch: Q::Fetch<'w>,
+    filter: F::Fetch<'w>,
+    next_word: usize,
+    word_idx: usize,
+    bits: u32,
+    // The index, or the group position, that ended the previous run, it starts the next one.
+    pending: Option<usize>,
+    // The part of the previous run that couldn't be fetched with it.
+    rest: Range<usize>,
+    // The members of the group the query iterates over, and the position of the next one.
+    group: Option<&'w [Entity]>,
+    position: usize,
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> QueryChunkIter<'w, 's, Q, F> {
+    /// # Safety
+    ///
+    /// The caller must make sure the access of the query is allowed on `world`.
+    unsafe fn new(world: UnsafeWorldCell<'w>, state: &'s QueryState<Q, F>, unordered: bool) -> Self {
+        Self {
+            world,
+            state,
+            fetch: Q::init_fetch(world, &state.fetch_state),
+            filter: F::init_fetch(world, &state.filter_state),
+            next_word: 0,
+            word_idx: 0,
+            bits: 0,
+            pending: None,
+            rest: 0..0,
+            group: world.group(&state.required).filter(|_| unordered),
+            position: 0,
+        }
+    }
+
+    fn next_position(&mut self, members: &[Entity]) -> Option<usize> {
+        while let Some(entity) = members.get(self.position) {
+            self.position += 1;
+            let index = entity.index() as usize;
+            if !self.state.excludes(self.world, index) && F::filter(&mut self.filter, index) {
+                return Some(self.position - 1);
+            }
+        }
+        None
+    }
+
+    fn next_group_chunk(&mut self, members: &'w [Entity]) -> Option<(&'w [Entity], Q::Chunk<'w>)>
+    where
+        Q: ChunkQuery,
+    {
+        let start = self.pending.take().or_else(|| self.next_position(members))?;
+        let mut end = start + 1;
+        while end - start < MAX_CHUNK_LEN {
+            match self.next_position(members) {
+                Some(position) if position == end => end += 1,
+                next => {
+                    self.pending = next;
+                    break;
+                }
+            }
+        }
+        // Each position is yielded in a single chunk.
+        unsafe { Some((&members[start..end], Q::fetch_group_chunk(&mut self.fetch, start..end))) }
+    }
+
+    fn next_index(&mut self) -> Option<usize> {
+        loop {
+            while self.bits != 0 {
+                let bit = self.bits.trailing_zeros() as usize;
+                self.bits &= self.bits - 1;
+                let index = (self.word_idx << 5) | bit;
+                if F::filter(&mut self.filter, index) {
+                    return Some(index);
+                }
+            }
+            self.word_idx = self.state.driver(self.world).next_word(self.next_word)?;
+            self.next_word = self.word_idx + 1;
+            self.bits = self.state.word(self.world, self.word_idx);
+        }
+    }
+}
+
+impl<'w, 's, Q: ChunkQuery, F: QueryFilter> Iterator for QueryChunkIter<'w, 's, Q, F> {
+    type Item = (&'w [Entity], Q::Chunk<'w>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(members) = self.group {
+            return self.next_group_chunk(members);
+        }
+        let run = if self.rest.is_empty() {
+            let start = self.pending.take().or_else(|| self.next_index())?;
+            let mut end = start + 1;
+            while end % MAX_CHUNK_LEN != 0 {
+                match self.next_index() {
+                    Some(index) if index == end => end += 1,
+                    next => {
+                        self.pending = next;
+                        break;
+                    }
+                }
+            }
+            start..end
+        } else {
+            core::mem::replace(&mut self.rest, 0..0)
+        };
+        let (start, end) = (run.start, run.start + Q::chunk_len(&self.fetch, run.clone()));
+        self.rest = end..run.end;
+        // The entity mask is part of the intersection so every index of the run is alive, and
+        // each index is yielded in a single chunk.
+        unsafe {
+            let entities = self.world.entities().slice(start..end);
+            Some((entities, Q::fetch_chunk(&mut self.fetch, start..end)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::query::{Changed, Without};
+    use crate::World;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(f32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Velocity(f32);
+    #[derive(Component)]
+    struct Frozen;
+
+    #[test]
+    fn dense_world_is_one_chunk() {
+        let mut world = World::new();
+        let entities = world.spawn_batch((0..100).map(|i| Position(i as f32)));
+        for e in &entities {
+            world.add_component(*e, Velocity(1.0));
+        }
+        let mut state = world.query::<(&Position, &Velocity)>();
+        let mut query = state.query_mut(&mut world);
+        let chunks: Vec<_> = query.iter_chunks().collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].0, entities);
+        assert_eq!(chunks[0].1 .0[42], Position(42.0));
+        assert_eq!(chunks[0].1 .1.len(), 100);
+    }
+
+    #[test]
+    fn fragmented_chunks_concatenate_to_the_iteration() {
+        let mut world = World::new();
+        let entities = world.spawn_batch((0..3000).map(|i| Position(i as f32)));
+        for (i, e) in entities.iter().enumerate() {
+            if i % 7 != 3 {
+                world.add_component(*e, Velocity(i as f32));
+            }
+            if i % 100 == 50 {
+                world.add_component(*e, Frozen);
+            }
+            if i % 250 == 0 {
+                world.despawn_entity(*e);
+            }
+        }
+        let mut state = world.query_filtered::<(&Position, &Velocity), Without<Frozen>>();
+        let mut query = state.query_mut(&mut world);
+        let mut seen = Vec::new();
+        let mut count = 0;
+        for (entities, (positions, velocities)) in query.iter_chunks() {
+            assert!(entities.len() <= MAX_CHUNK_LEN);
+            assert_eq!(entities.len(), positions.len());
+            assert_eq!(entities.len(), velocities.len());
+            let first = entities[0].index() as usize;
+            assert_eq!(first / MAX_CHUNK_LEN, (first + entities.len() - 1) / MAX_CHUNK_LEN);
+            seen.extend(entities.iter().zip(positions.iter().zip(velocities)).map(|(e, (p, v))| (*e, *p, *v)));
+            count += 1;
+        }
+        let state = world.query_filtered::<(Entity, &Position, &Velocity), Without<Frozen>>();
+        let expected: Vec<_> = state.iter(&world).map(|(e, p, v)| (e, *p, *v)).collect();
+        assert_eq!(seen, expected);
+        assert!(count > 3000 / 7);
+    }
+
+    #[test]
+    fn writes_through_chunks_are_kept() {
+        let mut world = World::new();
+        let entities = world.spawn_batch((0..10).map(|i| Position(i as f32)));
+        for e in &entities[..5] {
+            world.add_component(*e, Velocity(2.0));
+        }
+        world.clear_trackers();
+        let mut state = world.query::<(&mut Position, &Velocity)>();
+        for (_, (positions, velocities)) in state.query_mut(&mut world).iter_chunks() {
+            for (position, velocity) in positions.iter_mut().zip(velocities.iter()) {
+                position.0 += velocity.0;
+            }
+        }
+        let positions: Vec<_> = entities.iter().map(|e| world.get_component::<Position>(*e).unwrap().0).collect();
+        assert_eq!(positions, [2.0, 3.0, 4.0, 5.0, 6.0, 5.0, 6.0, 7.0, 8.0, 9.0]);
+        let changed = world.query_filtered::<Entity, Changed<Position>>();
+        assert_eq!(changed.iter(&world).collect::<Vec<_>>(), entities[..5]);
+    }
+}
@@ -0,0 +1,316 @@
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::Range;
+use core::ptr::NonNull;
+
+use super::Access;
+use crate::change_detection::{Mut, Tick};
+use crate::component::{assert_mutable, Component, ComponentId, Components};
+use crate::entity::Entity;
+use crate::storage::Storage;
+use crate::{UnsafeWorldCell, World};
+use crate::tuples::all_tuples;
+
+/// Types that can be fetched for every entity matched by a query.
+///
+/// # Safety
+///
+/// `required` must list every component the fetch reads, since the query only calls `fetch` for
+/// the entities present in all the required storages.
+pub unsafe trait WorldQuery {
+    type Item<'w>;
+    type Fetch<'w>;
+    type State: Clone + Send + Sync + 'static;
+    /// The same query with every mutable access downgraded to a shared one.
+    type ReadOnly: ReadOnlyWorldQuery + WorldQuery<State = Self::State>;
+
+    fn init_state(world: &mut World) -> Self::State;
+
+    /// Builds the state without registering anything, `None` if a component is unknown.
+    fn get_state(components: &Components) -> Option<Self::State>;
+
+    /// Records the components the fetch reads and writes.
+    fn access(state: &Self::State, access: &mut Access);
+
+    /// Pushes the components an entity must have to be matched.
+    fn required(state: &Self::State, required: &mut Vec<ComponentId>);
+
+    /// # Safety
+    ///
+    /// The caller must make sure the access declared by the query is allowed on `world`.
+    unsafe fn init_fetch<'w>(world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w>;
+
+    /// # Safety
+    ///
+    /// `entity` must be alive and have all the required components. For mutable queries the
+    /// caller must not fetch the same entity twice while the first item is still alive.
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity) -> Self::Item<'w>;
+}
+
+/// Marker for the queries that never give out mutable access.
+///
+/// # Safety
+///
+/// The fetch must only read from the world.
+pub unsafe trait ReadOnlyWorldQuery: WorldQuery {}
+
+unsafe impl WorldQuery for Entity {
+    type Item<'w> = Entity;
+    type Fetch<'w> = ();
+    type State = ();
+    type ReadOnly = Self;
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn get_state(_components: &Components) -> Option<Self::State> {
+        Some(())
+    }
+
+    fn access(_state: &Self::State, _access: &mut Access) {}
+
+    fn required(_state: &Self::State, _required: &mut Vec<ComponentId>) {}
+
+    unsafe fn init_fetch<'w>(_world: UnsafeWorldCell<'w>, _state: &Self::State) -> Self::Fetch<'w> {}
+
+    #[inline]
+    unsafe fn fetch<'w>(_fetch: &mut Self::Fetch<'w>, entity: Entity) -> Self::Item<'w> {
+        entity
+    }
+}
+
+unsafe impl ReadOnlyWorldQuery for Entity {}
+
+unsafe impl<T: Component> WorldQuery for &T {
+    type Item<'w> = &'w T;
+    type Fetch<'w> = &'w Storage<T>;
+    type State = ComponentId;
+    type ReadOnly = Self;
+
+    fn init_state(world: &mut World) -> Self::State {
+        world.register_component::<T>()
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        components.id::<T>()
+    }
+
+    fn access(state: &Self::State, access: &mut Access) {
+        access.add_read(*state);
+    }
+
+    fn required(state: &Self::State, required: &mut Vec<ComponentId>) {
+        required.push(*state);
+    }
+
+    unsafe fn init_fetch<'w>(world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w> {
+        world.storage::<T>(*state)
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity) -> Self::Item<'w> {
+        fetch.get(entity.index() as usize).unwrap_unchecked()
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyWorldQuery for &T {}
+
+pub struct WriteFetch<'w, T> {
+    storage: NonNull<Storage<T>>,
+    last_run: Tick,
+    this_run: Tick,
+    _marker: PhantomData<&'w mut Storage<T>>,
+}
+
+impl<'w, T: Component> WriteFetch<'w, T> {
+    /// # Safety
+    ///
+    /// Same as [`UnsafeWorldCell::storage_ptr`].
+    unsafe fn new(world: UnsafeWorldCell<'w>, id: ComponentId) -> Self {
+        Self {
+            storage: world.storage_ptr::<T>(id),
+            last_run: world.last_run(),
+            this_run: world.this_run(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The values of `range`, marked changed.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Storage::slice`], and no other reference to these values may be alive.
+    pub(super) unsafe fn slice(&mut self, range: Range<usize>) -> &'w mut [T] {
+        (*self.storage.as_ptr()).slice_mut(range, self.this_run)
+    }
+
+    /// The values at `positions` of a sparse set, marked changed.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Storage::positions`], and no other reference to these values may be alive.
+    pub(super) unsafe fn positions(&mut self, positions: Range<usize>) -> &'w mut [T] {
+        (*self.storage.as_ptr()).positions_mut(positions, self.this_run)
+    }
+
+    pub(super) fn contiguous_len(&self, range: Range<usize>) -> usize {
+        // Only the values are borrowed by the items, never the positions.
+        unsafe { (*self.storage.as_ptr()).contiguous_len(range) }
+    }
+
+    /// # Safety
+    ///
+    /// No other reference to the value at `index` may be alive.
+    #[inline]
+    unsafe fn get(&mut self, index: usize) -> Option<Mut<'w, T>> {
+        let (value, ticks) = (*self.storage.as_ptr()).get_with_ticks_mut(index)?;
+        Some(match ticks {
+            Some(ticks) => Mut::with_ticks(value, ticks, self.last_run, self.this_run),
+            None => Mut::new(value),
+        })
+    }
+}
+
+unsafe impl<T: Component> WorldQuery for &mut T {
+    type Item<'w> = Mut<'w, T>;
+    type Fetch<'w> = WriteFetch<'w, T>;
+    type State = ComponentId;
+    type ReadOnly = &'static T;
+
+    fn init_state(world: &mut World) -> Self::State {
+        const { assert_mutable::<T>() };
+        world.register_component::<T>()
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        components.id::<T>()
+    }
+
+    fn access(state: &Self::State, access: &mut Access) {
+        access.add_write(*state);
+    }
+
+    fn required(state: &Self::State, required: &mut Vec<ComponentId>) {
+        required.push(*state);
+    }
+
+    unsafe fn init_fetch<'w>(world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w> {
+        WriteFetch::new(world, *state)
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity) -> Self::Item<'w> {
+        fetch.get(entity.index() as usize).unwrap_unchecked()
+    }
+}
+
+unsafe impl<T: Component> WorldQuery for Option<&T> {
+    type Item<'w> = Option<&'w T>;
+    type Fetch<'w> = &'w Storage<T>;
+    type State = ComponentId;
+    type ReadOnly = Self;
+
+    fn init_state(world: &mut World) -> Self::State {
+        world.register_component::<T>()
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        components.id::<T>()
+    }
+
+    fn access(state: &Self::State, access: &mut Access) {
+        access.add_read(*state);
+    }
+
+    fn required(_state: &Self::State, _required: &mut Vec<ComponentId>) {}
+
+    unsafe fn init_fetch<'w>(world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w> {
+        world.storage::<T>(*state)
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity) -> Self::Item<'w> {
+        fetch.get(entity.index() as usize)
+    }
+}
+
+unsafe impl<T: Component> ReadOnlyWorldQuery for Option<&T> {}
+
+unsafe impl<T: Component> WorldQuery for Option<&mut T> {
+    type Item<'w> = Option<Mut<'w, T>>;
+    type Fetch<'w> = WriteFetch<'w, T>;
+    type State = ComponentId;
+    type ReadOnly = Option<&'static T>;
+
+    fn init_state(world: &mut World) -> Self::State {
+        const { assert_mutable::<T>() };
+        world.register_component::<T>()
+    }
+
+    fn get_state(components: &Components) -> Option<Self::State> {
+        components.id::<T>()
+    }
+
+    fn access(state: &Self::State, access: &mut Access) {
+        access.add_write(*state);
+    }
+
+    fn required(_state: &Self::State, _required: &mut Vec<ComponentId>) {}
+
+    unsafe fn init_fetch<'w>(world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w> {
+        WriteFetch::new(world, *state)
+    }
+
+    #[inline]
+    unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, entity: Entity) -> Self::Item<'w> {
+        fetch.get(entity.index() as usize)
+    }
+}
+
+macro_rules! impl_tuple_query {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case, clippy::unused_unit)]
+        unsafe impl<$($name: WorldQuery),*> WorldQuery for ($($name,)*) {
+            type Item<'w> = ($($name::Item<'w>,)*);
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+            type State = ($($name::State,)*);
+            type ReadOnly = ($($name::ReadOnly,)*);
+
+            fn init_state(_world: &mut World) -> Self::State {
+                ($($name::init_state(_world),)*)
+            }
+
+            fn get_state(_components: &Components) -> Option<Self::State> {
+                Some(($($name::get_state(_components)?,)*))
+            }
+
+            fn access(state: &Self::State, _access: &mut Access) {
+                let ($($name,)*) = state;
+                $(
+                    let mut member = Access::default();
+                    $name::access($name, &mut member);
+                    _access.add_member(&member);
+                )*
+            }
+
+            fn required(state: &Self::State, _required: &mut Vec<ComponentId>) {
+                let ($($name,)*) = state;
+                $($name::required($name, _required);)*
+            }
+
+            unsafe fn init_fetch<'w>(_world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w> {
+                let ($($name,)*) = state;
+                ($($name::init_fetch(_world, $name),)*)
+            }
+
+            #[inline]
+            unsafe fn fetch<'w>(fetch: &mut Self::Fetch<'w>, _entity: Entity) -> Self::Item<'w> {
+                let ($($name,)*) = fetch;
+                ($($name::fetch($name, _entity),)*)
+            }
+        }
+
+        unsafe impl<$($name: ReadOnlyWorldQuery),*> ReadOnlyWorldQuery for ($($name,)*) {}
+    };
+}
+
+all_tuples!(impl_tuple_query);
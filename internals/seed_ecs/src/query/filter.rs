@@ -0,0 +1,254 @@
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::marker::PhantomData;
+use core::mem::size_of;
+
+use crate::change_detection::Tick;
+use crate::component::{Component, ComponentId};
+use crate::query::Access;
+use crate::storage::Storage;
+use crate::{UnsafeWorldCell, World};
+use crate::tuples::all_tuples;
+
+/// Restricts the entities matched by a query without fetching anything.
+///
+/// # Safety
+///
+/// `filter` must only read from the world.
+pub unsafe trait QueryFilter {
+    type Fetch<'w>;
+    type State: Clone + Send + Sync + 'static;
+
+    fn init_state(world: &mut World) -> Self::State;
+
+    /// Pushes the components an entity must have to be matched.
+    fn required(state: &Self::State, required: &mut Vec<ComponentId>);
+
+    /// Pushes the components an entity must not have to be matched.
+    fn excluded(state: &Self::State, excluded: &mut Vec<ComponentId>);
+
+    /// Adds the components `filter` reads, the queries writing them conflict with this one.
+    fn access(_state: &Self::State, _access: &mut Access) {}
+
+    /// # Safety
+    ///
+    /// The world must outlive the fetch.
+    unsafe fn init_fetch<'w>(world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w>;
+
+    /// Called for the entities that passed the mask checks.
+    fn filter(fetch: &mut Self::Fetch<'_>, index: usize) -> bool;
+
+    /// True if the filter opts out of skipping the [`Disabled`] entities.
+    fn include_disabled() -> bool {
+        false
+    }
+
+    /// True if `filter` passes every entity, what the filter does is then in its required and
+    /// excluded components and the matched entities are counted from the masks alone.
+    fn is_mask_only() -> bool {
+        false
+    }
+}
+
+/// Marks an entity as turned off: queries skip it unless they mention `Disabled` or use
+/// [`IncludeDisabled`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Component)]
+pub struct Disabled;
+
+/// Matches the [`Disabled`] entities along with the enabled ones.
+pub struct IncludeDisabled;
+
+/// Only matches the entities that have a `T`.
+pub struct With<T>(PhantomData<T>);
+
+/// Only matches the entities that don't have a `T`.
+pub struct Without<T>(PhantomData<T>);
+
+unsafe impl<T: Component> QueryFilter for With<T> {
+    type Fetch<'w> = ();
+    type State = ComponentId;
+
+    fn init_state(world: &mut World) -> Self::State {
+        world.register_component::<T>()
+    }
+
+    fn required(state: &Self::State, required: &mut Vec<ComponentId>) {
+        required.push(*state);
+    }
+
+    fn excluded(_state: &Self::State, _excluded: &mut Vec<ComponentId>) {}
+
+    unsafe fn init_fetch<'w>(_world: UnsafeWorldCell<'w>, _state: &Self::State) -> Self::Fetch<'w> {}
+
+    #[inline]
+    fn filter(_fetch: &mut Self::Fetch<'_>, _index: usize) -> bool {
+        true
+    }
+
+    fn is_mask_only() -> bool {
+        true
+    }
+}
+
+unsafe impl<T: Component> QueryFilter for Without<T> {
+    type Fetch<'w> = ();
+    type State = ComponentId;
+
+    fn init_state(world: &mut World) -> Self::State {
+        world.register_component::<T>()
+    }
+
+    fn required(_state: &Self::State, _required: &mut Vec<ComponentId>) {}
+
+    fn excluded(state: &Self::State, excluded: &mut Vec<ComponentId>) {
+        excluded.push(*state);
+    }
+
+    unsafe fn init_fetch<'w>(_world: UnsafeWorldCell<'w>, _state: &Self::State) -> Self::Fetch<'w> {}
+
+    #[inline]
+    fn filter(_fetch: &mut Self::Fetch<'_>, _index: usize) -> bool {
+        true
+    }
+
+    fn is_mask_only() -> bool {
+        true
+    }
+}
+
+/// Only matches the entities whose `T` was added since the last run of the system.
+///
+/// # Panics
+///
+/// Zero sized components keep no ticks, the filter panics on initialization for them.
+pub struct Added<T>(PhantomData<T>);
+
+/// Only matches the entities whose `T` was added or written since the last run of the system.
+///
+/// # Panics
+///
+/// Zero sized components keep no ticks, the filter panics on initialization for them.
+pub struct Changed<T>(PhantomData<T>);
+
+fn init_tracked<T: Component>(world: &mut World, filter: &str) -> ComponentId {
+    assert!(
+        size_of::<T>() != 0,
+        "{}<{}>: zero sized components don't track changes",
+        filter,
+        type_name::<T>()
+    );
+    world.register_component::<T>()
+}
+
+macro_rules! impl_tick_filter {
+    ($filter: ident, $check: ident) => {
+        unsafe impl<T: Component> QueryFilter for $filter<T> {
+            type Fetch<'w> = (&'w Storage<T>, Tick, Tick);
+            type State = ComponentId;
+
+            fn init_state(world: &mut World) -> Self::State {
+                init_tracked::<T>(world, stringify!($filter))
+            }
+
+            fn required(state: &Self::State, required: &mut Vec<ComponentId>) {
+                required.push(*state);
+            }
+
+            fn excluded(_state: &Self::State, _excluded: &mut Vec<ComponentId>) {}
+
+            // The ticks of `T` are read, they may be under a `Mut<T>` of another query.
+            fn access(state: &Self::State, access: &mut Access) {
+                access.add_read(*state);
+            }
+
+            unsafe fn init_fetch<'w>(world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w> {
+                (world.storage::<T>(*state), world.last_run(), world.this_run())
+            }
+
+            #[inline]
+            fn filter(fetch: &mut Self::Fetch<'_>, index: usize) -> bool {
+                let (storage, last_run, this_run) = *fetch;
+                storage.get_ticks(index).is_some_and(|ticks| ticks.$check(last_run, this_run))
+            }
+        }
+    };
+}
+
+impl_tick_filter!(Added, is_added);
+impl_tick_filter!(Changed, is_changed);
+
+unsafe impl QueryFilter for IncludeDisabled {
+    type Fetch<'w> = ();
+    type State = ();
+
+    fn init_state(_world: &mut World) -> Self::State {}
+
+    fn required(_state: &Self::State, _required: &mut Vec<ComponentId>) {}
+
+    fn excluded(_state: &Self::State, _excluded: &mut Vec<ComponentId>) {}
+
+    unsafe fn init_fetch<'w>(_world: UnsafeWorldCell<'w>, _state: &Self::State) -> Self::Fetch<'w> {}
+
+    #[inline]
+    fn filter(_fetch: &mut Self::Fetch<'_>, _index: usize) -> bool {
+        true
+    }
+
+    fn include_disabled() -> bool {
+        true
+    }
+
+    fn is_mask_only() -> bool {
+        true
+    }
+}
+
+macro_rules! impl_tuple_filter {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case, clippy::unused_unit)]
+        unsafe impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            type Fetch<'w> = ($($name::Fetch<'w>,)*);
+            type State = ($($name::State,)*);
+
+            fn init_state(_world: &mut World) -> Self::State {
+                ($($name::init_state(_world),)*)
+            }
+
+            fn required(state: &Self::State, _required: &mut Vec<ComponentId>) {
+                let ($($name,)*) = state;
+                $($name::required($name, _required);)*
+            }
+
+            fn excluded(state: &Self::State, _excluded: &mut Vec<ComponentId>) {
+                let ($($name,)*) = state;
+                $($name::excluded($name, _excluded);)*
+            }
+
+            fn access(state: &Self::State, _access: &mut Access) {
+                let ($($name,)*) = state;
+                $($name::access($name, _access);)*
+            }
+
+            unsafe fn init_fetch<'w>(_world: UnsafeWorldCell<'w>, state: &Self::State) -> Self::Fetch<'w> {
+                let ($($name,)*) = state;
+                ($($name::init_fetch(_world, $name),)*)
+            }
+
+            #[inline]
+            fn filter(fetch: &mut Self::Fetch<'_>, _index: usize) -> bool {
+                let ($($name,)*) = fetch;
+                true $(&& $name::filter($name, _index))*
+            }
+
+            fn include_disabled() -> bool {
+                false $(|| $name::include_disabled())*
+            }
+
+            fn is_mask_only() -> bool {
+                true $(&& $name::is_mask_only())*
+            }
+        }
+    };
+}
+
+all_tuples!(impl_tuple_filter);
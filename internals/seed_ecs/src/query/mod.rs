@@ -0,0 +1,803 @@
+mod access;
+mod cached;
+mod chunks;
+mod fetch;
+mod filter;
+mod resumable;
+mod sorted;
+mod split;
+mod view;
+
+pub use access::*;
+pub use cached::*;
+pub use chunks::*;
+pub use fetch::*;
+pub use filter::*;
+pub use resumable::*;
+pub use sorted::*;
+pub use split::*;
+pub use view::*;
+
+
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::ops::{ControlFlow, Range};
+
+use crate::component::{ComponentId, Components};
+use crate::entity::Entity;
+use crate::utils::BMask;
+use crate::{UnsafeWorldCell, World};
+
+fn panic_aliased<Q>(components: &Components, id: ComponentId) -> ! {
+    let component = components.info(id).map_or("?", |info| info.name());
+    panic!("Query {} reaches component {} mutably and another time, its items would alias", type_name::<Q>(), component)
+}
+
+/// The cached part of a query: the component ids it needs and the masks to intersect.
+///
+/// A query reaching a component twice with one of them mutable, like `(&mut A, &A)` or
+/// `(&mut A, Option<&mut A>)`, panics when its state is built, and fails to
+/// [initialize](crate::system::System::initialize) as a system parameter, like two `ResMut<T>`
+/// in the same system. The rest is rejected by the borrow checker.
+///
+/// Mutable items can't be fetched from a world borrowed shared:
+///
+/// ```compile_fail
+/// # use seed_ecs::prelude::*;
+/// #[derive(Component)]
+/// struct Position(f32);
+///
+/// let mut world = World::new();
+/// let mut state = world.query::<&mut Position>();
+/// for position in state.iter(&world) {}
+/// ```
+///
+/// An item can't outlive the query it came from:
+///
+/// ```compile_fail
+/// # use seed_ecs::prelude::*;
+/// #[derive(Component)]
+/// struct Position(f32);
+///
+/// let mut world = World::new();
+/// let e = *world.spawn_entity();
+/// world.add_component(e, Position(0.0));
+/// let mut state = world.query::<&mut Position>();
+/// let position = {
+///     let mut query = state.query_mut(&mut world);
+///     query.get_mut(e).unwrap()
+/// };
+/// ```
+///
+/// Nor can two items of the same query be borrowed mutably at once, they could be the same
+/// entity:
+///
+/// ```compile_fail
+/// # use seed_ecs::prelude::*;
+/// #[derive(Component)]
+/// struct Position(f32);
+///
+/// let mut world = World::new();
+/// let (a, b) = (*world.spawn_entity(), *world.spawn_entity());
+/// let mut state = world.query::<&mut Position>();
+/// let mut query = state.query_mut(&mut world);
+/// let mut first = query.get_mut(a).unwrap();
+/// let second = query.get_mut(b).unwrap();
+/// first.0 = second.0;
+/// ```
+///
+/// Nor can an item of an iterator outlive a change to the world:
+///
+/// ```compile_fail
+/// # use seed_ecs::prelude::*;
+/// #[derive(Component)]
+/// struct Position(f32);
+///
+/// let mut world = World::new();
+/// let mut state = world.query::<(Entity, &mut Position)>();
+/// for (entity, mut position) in state.iter_mut(&mut world) {
+///     world.despawn_entity(entity);
+///     position.0 = 0.0;
+/// }
+/// ```
+pub struct QueryState<Q: WorldQuery, F: QueryFilter = ()> {
+    fetch_state: Q::State,
+    filter_state: F::State,
+    required: Vec<ComponentId>,
+    excluded: Vec<ComponentId>,
+    access: Access,
+    // Reused by the sorted iterators to avoid allocating every frame.
+    scratch: ScratchBuffer,
+}
+
+impl<Q: WorldQuery, F: QueryFilter> QueryState<Q, F> {
+    /// # Panics
+    ///
+    /// Panics if the query reaches a component twice with one of them mutable, like
+    /// `(&mut A, &A)`, see [`Access::aliased`].
+    pub fn new(world: &mut World) -> Self {
+        let state = Self::init(world);
+        if let Some(id) = state.access.aliased() {
+            panic_aliased::<Q>(world.components(), id);
+        }
+        state
+    }
+
+    // Doesn't check the aliasing, for the system parameters which report it as a conflict.
+    pub(crate) fn init(world: &mut World) -> Self {
+        let fetch_state = Q::init_state(world);
+        let filter_state = F::init_state(world);
+        let mut required = Vec::new();
+        let mut excluded = Vec::new();
+        Q::required(&fetch_state, &mut required);
+        F::required(&filter_state, &mut required);
+        F::excluded(&filter_state, &mut excluded);
+        required.sort();
+        required.dedup();
+        excluded.sort();
+        excluded.dedup();
+        let mut access = Access::default();
+        Q::access(&fetch_state, &mut access);
+        // Filters only read, and only the entity being matched, they never alias the items.
+        F::access(&filter_state, &mut access);
+        // Disabled entities are skipped unless the query asks about them.
+        let disabled = world.register_component::<Disabled>();
+        let mentioned = access.has_read(disabled)
+            || required.binary_search(&disabled).is_ok()
+            || excluded.binary_search(&disabled).is_ok();
+        if !mentioned && !F::include_disabled() {
+            excluded.push(disabled);
+            excluded.sort();
+        }
+        Self {
+            fetch_state,
+            filter_state,
+            required,
+            excluded,
+            access,
+            scratch: ScratchBuffer::default(),
+        }
+    }
+
+    /// The components the query data reads and writes, along with the ones its filters read.
+    pub fn access(&self) -> &Access {
+        &self.access
+    }
+
+    /// The access along with the components the matched entities must and must not have.
+    pub fn filtered_access(&self) -> FilteredAccess {
+        FilteredAccess::new(self.access.clone(), self.required.clone(), self.excluded.clone())
+    }
+
+    /// The same query with every mutable access downgraded to a shared one, to run it on a world
+    /// borrowed shared:
+    ///
+    /// ```
+    /// # use seed_ecs::prelude::*;
+    /// # use seed_ecs::query::ReadOnlyQuery;
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    ///
+    /// let mut world = World::new();
+    /// let movers = world.query::<(Entity, &mut Position)>();
+    /// let positions = movers.to_readonly();
+    /// let query: ReadOnlyQuery<(Entity, &mut Position)> = positions.query(&world);
+    /// assert_eq!(query.count(), 0);
+    /// ```
+    pub fn to_readonly(&self) -> QueryState<Q::ReadOnly, F> {
+        let mut access = Access::default();
+        Q::ReadOnly::access(&self.fetch_state, &mut access);
+        F::access(&self.filter_state, &mut access);
+        QueryState {
+            fetch_state: self.fetch_state.clone(),
+            filter_state: self.filter_state.clone(),
+            required: self.required.clone(),
+            excluded: self.excluded.clone(),
+            access,
+            scratch: ScratchBuffer::default(),
+        }
+    }
+
+    pub fn query<'w, 's>(&'s self, world: &'w World) -> Query<'w, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        unsafe { Query::new(world.as_unsafe_world_cell(), self) }
+    }
+
+    pub fn query_mut<'w, 's>(&'s mut self, world: &'w mut World) -> Query<'w, 's, Q, F> {
+        unsafe { Query::new(world.as_unsafe_world_cell(), self) }
+    }
+
+    pub fn iter<'w, 's>(&'s self, world: &'w World) -> QueryIter<'w, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        // Read only queries can't alias anything.
+        unsafe { QueryIter::new(world.as_unsafe_world_cell(), self) }
+    }
+
+    pub fn iter_mut<'w, 's>(&'s mut self, world: &'w mut World) -> QueryIter<'w, 's, Q, F> {
+        // The world is borrowed mutably for as long as the items live.
+        unsafe { QueryIter::new(world.as_unsafe_world_cell(), self) }
+    }
+
+    /// See [`Query::iter_unordered`].
+    pub fn iter_unordered<'w, 's>(&'s self, world: &'w World) -> QueryIter<'w, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        unsafe { QueryIter::new_unordered(world.as_unsafe_world_cell(), self) }
+    }
+
+    /// See [`Query::iter_unordered_mut`].
+    pub fn iter_unordered_mut<'w, 's>(&'s mut self, world: &'w mut World) -> QueryIter<'w, 's, Q, F> {
+        unsafe { QueryIter::new_unordered(world.as_unsafe_world_cell(), self) }
+    }
+
+    /// See [`Query::for_each`].
+    pub fn for_each<'w>(&self, world: &'w World, mut f: impl FnMut(Q::Item<'w>))
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        let _ = unsafe {
+            self.walk_words(world.as_unsafe_world_cell(), 0..usize::MAX, |item| {
+                f(item);
+                ControlFlow::<()>::Continue(())
+            })
+        };
+    }
+
+    /// See [`Query::for_each_mut`].
+    pub fn for_each_mut<'w>(&mut self, world: &'w mut World, mut f: impl FnMut(Q::Item<'w>)) {
+        let _ = unsafe {
+            self.walk_words(world.as_unsafe_world_cell(), 0..usize::MAX, |item| {
+                f(item);
+                ControlFlow::<()>::Continue(())
+            })
+        };
+    }
+
+    pub fn get<'w>(&self, world: &'w World, entity: Entity) -> Option<Q::Item<'w>>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        unsafe { self.get_unchecked(world.as_unsafe_world_cell(), entity) }
+    }
+
+    pub fn get_mut<'w>(&mut self, world: &'w mut World, entity: Entity) -> Option<Q::Item<'w>> {
+        unsafe { self.get_unchecked(world.as_unsafe_world_cell(), entity) }
+    }
+
+    /// # Safety
+    ///
+    /// The caller must make sure the access of the query is allowed on `world`.
+    pub unsafe fn get_unchecked<'w>(
+        &self,
+        world: UnsafeWorldCell<'w>,
+        entity: Entity,
+    ) -> Option<Q::Item<'w>> {
+        if !self.matches(world, entity) {
+            return None;
+        }
+        let mut fetch = Q::init_fetch(world, &self.fetch_state);
+        let mut filter = F::init_fetch(world, &self.filter_state);
+        if !F::filter(&mut filter, entity.index() as usize) {
+            return None;
+        }
+        Some(Q::fetch(&mut fetch, entity))
+    }
+
+    // Checks liveness and the required and excluded masks.
+    fn matches(&self, world: UnsafeWorldCell<'_>, entity: Entity) -> bool {
+        let index = entity.index() as usize;
+        world.entities().is_alive(entity)
+            && self.required.iter().all(|id| world.storage_mask(*id).is_present(index))
+            && !self.excludes(world, index)
+    }
+
+    fn excludes(&self, world: UnsafeWorldCell<'_>, index: usize) -> bool {
+        self.excluded.iter().any(|id| world.storage_mask(*id).is_present(index))
+    }
+
+    // Leaf word `word_idx` of the intersection of all the masks.
+    #[inline]
+    fn word(&self, world: UnsafeWorldCell<'_>, word_idx: usize) -> u32 {
+        let mut word = world.entities().mask().word(word_idx);
+        for id in &self.required {
+            word &= world.storage_mask(*id).word(word_idx);
+        }
+        for id in &self.excluded {
+            word &= !world.storage_mask(*id).word(word_idx);
+        }
+        word
+    }
+
+    // Number of entities matched, stopping at `limit`. Only the filter fetch is built, which
+    // reads the ticks.
+    fn count_up_to(&self, world: UnsafeWorldCell<'_>, limit: usize) -> usize {
+        let mut filter = unsafe { F::init_fetch(world, &self.filter_state) };
+        let driver = self.driver(world);
+        let mut count = 0;
+        let mut next_word = 0;
+        while let Some(word_idx) = driver.next_word(next_word).filter(|_| count < limit) {
+            next_word = word_idx + 1;
+            let mut bits = self.word(world, word_idx);
+            if F::is_mask_only() {
+                count += bits.count_ones() as usize;
+                continue;
+            }
+            while bits != 0 {
+                let index = (word_idx << 5) | bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                count += F::filter(&mut filter, index) as usize;
+            }
+        }
+        count
+    }
+
+    // Pushes the indices of the matched entities in ascending order.
+    fn collect_indices(&self, world: UnsafeWorldCell<'_>, indices: &mut Vec<usize>) {
+        let mut filter = unsafe { F::init_fetch(world, &self.filter_state) };
+        let driver = self.driver(world);
+        let mut next_word = 0;
+        while let Some(word_idx) = driver.next_word(next_word) {
+            next_word = word_idx + 1;
+            let mut bits = self.word(world, word_idx);
+            while bits != 0 {
+                let index = (word_idx << 5) | bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                if F::filter(&mut filter, index) {
+                    indices.push(index);
+                }
+            }
+        }
+    }
+
+    // Calls `f` with the items of the entities in the leaf words `words` of the intersection, in
+    // ascending index order, until it breaks. Each word is read once and its bits walked locally,
+    // the core of the `for_each` family.
+    //
+    // The caller must make sure the access of the query is allowed on `world`, and for mutable
+    // queries that no other item of these words is alive.
+    #[inline]
+    unsafe fn walk_words<'w, B>(
+        &self,
+        world: UnsafeWorldCell<'w>,
+        words: Range<usize>,
+        mut f: impl FnMut(Q::Item<'w>) -> ControlFlow<B>,
+    ) -> ControlFlow<B> {
+        let mut fetch = Q::init_fetch(world, &self.fetch_state);
+        let mut filter = F::init_fetch(world, &self.filter_state);
+        let driver = self.driver(world);
+        let entities = world.entities();
+        let mut next_word = words.start;
+        while let Some(word_idx) = driver.next_word(next_word).filter(|word_idx| *word_idx < words.end) {
+            next_word = word_idx + 1;
+            let mut bits = self.word(world, word_idx);
+            while bits != 0 {
+                let index = (word_idx << 5) | bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                if F::filter(&mut filter, index) {
+                    // The entity mask is part of the intersection so the index is alive.
+                    let entity = entities.get(index as u32).unwrap_unchecked();
+                    f(Q::fetch(&mut fetch, entity))?;
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    // The mask with the fewest leaf words, used to skip the empty words.
+    fn driver<'w>(&self, world: UnsafeWorldCell<'w>) -> &'w BMask {
+        self.required
+            .iter()
+            .map(|id| world.storage_mask(*id))
+            .chain(core::iter::once(world.entities().mask()))
+            .min_by_key(|mask| mask.word_count())
+            .unwrap()
+    }
+}
+
+/// Iterates over the entities matched by a [`QueryState`] in ascending index order.
+///
+/// The order only depends on the indices of the matched entities: not on the order components
+/// were added in, on how the storages grew, or on which mask drives the intersection. The
+/// queries requiring exactly the components of a group keep to it too, [`Query::iter_unordered`]
+/// walks the members of the group in its own order instead, see [`World::register_group`].
+pub struct QueryIter<'w, 's, Q: WorldQuery, F: QueryFilter> {
+    world: UnsafeWorldCell<'w>,
+    state: &'s QueryState<Q, F>,
+    fetch: Q::Fetch<'w>,
+    filter: F::Fetch<'w>,
+    next_word: usize,
+    word_idx: usize,
+    bits: u32,
+    // The members of the group the query iterates over, and the position of the next one.
+    group: Option<&'w [Entity]>,
+    position: usize,
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> QueryIter<'w, 's, Q, F> {
+    /// # Safety
+    ///
+    /// The caller must make sure the access of the query is allowed on `world`.
+    pub unsafe fn new(world: UnsafeWorldCell<'w>, state: &'s QueryState<Q, F>) -> Self {
+        Self {
+            world,
+            state,
+            fetch: Q::init_fetch(world, &state.fetch_state),
+            filter: F::init_fetch(world, &state.filter_state),
+            next_word: 0,
+            word_idx: 0,
+            bits: 0,
+            group: None,
+            position: 0,
+        }
+    }
+
+    /// Iterates in the order of the group the query matches if any, see
+    /// [`Query::iter_unordered`].
+    ///
+    /// # Safety
+    ///
+    /// Same as [`QueryIter::new`].
+    pub unsafe fn new_unordered(world: UnsafeWorldCell<'w>, state: &'s QueryState<Q, F>) -> Self {
+        Self {
+            group: world.group(&state.required),
+            ..Self::new(world, state)
+        }
+    }
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Iterator for QueryIter<'w, 's, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(members) = self.group {
+            // Members are alive and have every required component.
+            while let Some(entity) = members.get(self.position).copied() {
+                self.position += 1;
+                let index = entity.index() as usize;
+                if !self.state.excludes(self.world, index) && F::filter(&mut self.filter, index) {
+                    return Some(unsafe { Q::fetch(&mut self.fetch, entity) });
+                }
+            }
+            return None;
+        }
+        loop {
+            while self.bits != 0 {
+                let bit = self.bits.trailing_zeros() as usize;
+                self.bits &= self.bits - 1;
+                let index = (self.word_idx << 5) | bit;
+                if !F::filter(&mut self.filter, index) {
+                    continue;
+                }
+                // The entity mask is part of the intersection so the index is alive.
+                let entity = self.world.entities().get(index as u32)?;
+                return Some(unsafe { Q::fetch(&mut self.fetch, entity) });
+            }
+            self.word_idx = self.state.driver(self.world).next_word(self.next_word)?;
+            self.next_word = self.word_idx + 1;
+            self.bits = self.state.word(self.world, self.word_idx);
+        }
+    }
+}
+
+impl World {
+    pub fn query<Q: WorldQuery>(&mut self) -> QueryState<Q, ()> {
+        QueryState::new(self)
+    }
+
+    pub fn query_filtered<Q: WorldQuery, F: QueryFilter>(&mut self) -> QueryState<Q, F> {
+        QueryState::new(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Position(f32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Velocity(f32);
+    #[derive(Component)]
+    struct Frozen;
+
+    #[test]
+    fn iterate_and_mutate() {
+        let mut world = World::new();
+        let mut entities = Vec::new();
+        for i in 0..100 {
+            let e = *world.spawn_entity();
+            world.add_component(e, Position(i as f32));
+            if i % 2 == 0 {
+                world.add_component(e, Velocity(1.0));
+            }
+            if i % 4 == 0 {
+                world.add_component(e, Frozen);
+            }
+            entities.push(e);
+        }
+
+        let mut moving = world.query_filtered::<(&mut Position, &Velocity), Without<Frozen>>();
+        for (mut position, velocity) in moving.iter_mut(&mut world) {
+            position.0 += velocity.0;
+        }
+
+        let positions = world.query::<(Entity, &Position)>();
+        let seen: Vec<_> = positions.iter(&world).map(|(e, p)| (e.index(), p.0)).collect();
+        assert_eq!(seen.len(), 100);
+        for (index, value) in seen {
+            let expected = if index % 2 == 0 && index % 4 != 0 { index as f32 + 1.0 } else { index as f32 };
+            assert_eq!(value, expected);
+        }
+    }
+
+    #[test]
+    fn skips_dead_and_missing() {
+        let mut world = World::new();
+        let a = *world.spawn_entity();
+        let b = *world.spawn_entity();
+        world.add_component(a, Position(1.0));
+        world.add_component(b, Position(2.0));
+        world.despawn_entity(a);
+
+        let query = world.query::<(Entity, &Position, Option<&Velocity>)>();
+        let items: Vec<_> = query.iter(&world).collect();
+        assert_eq!(items, vec![(b, &Position(2.0), None)]);
+        assert!(query.get(&world, a).is_none());
+        assert_eq!(query.get(&world, b).map(|(_, p, _)| p.0), Some(2.0));
+    }
+
+    #[test]
+    fn disabled_entities_are_skipped() {
+        let mut world = World::new();
+        let a = *world.spawn_entity();
+        let b = *world.spawn_entity();
+        world.add_component(a, Position(1.0));
+        world.add_component(b, Position(2.0));
+        world.entity_mut(a).set_enabled(false);
+        assert!(!world.entity_mut(a).is_enabled());
+
+        let all = world.query::<Entity>();
+        let positions = world.query_filtered::<&Position, Without<Velocity>>();
+        let disabled = world.query_filtered::<Entity, With<Disabled>>();
+        let everything = world.query_filtered::<Entity, IncludeDisabled>();
+        assert_eq!(all.iter(&world).collect::<Vec<_>>(), vec![b]);
+        assert_eq!(positions.iter(&world).collect::<Vec<_>>(), vec![&Position(2.0)]);
+        assert!(positions.get(&world, a).is_none());
+        assert_eq!(disabled.iter(&world).collect::<Vec<_>>(), vec![a]);
+        assert_eq!(everything.iter(&world).collect::<Vec<_>>(), vec![a, b]);
+        assert_eq!(world.get_component::<Position>(a), Some(&Position(1.0)));
+
+        world.entity_mut(a).set_enabled(true);
+        assert_eq!(all.iter(&world).collect::<Vec<_>>(), vec![a, b]);
+        assert!(disabled.iter(&world).next().is_none());
+    }
+
+    // The entities of index `i` get a position when `i % 3 != 0`, a velocity when `i % 2 == 0` and
+    // are frozen when `i % 5 == 0`.
+    fn has_position(i: usize) -> bool {
+        !i.is_multiple_of(3)
+    }
+
+    fn has_velocity(i: usize) -> bool {
+        i.is_multiple_of(2)
+    }
+
+    fn is_frozen(i: usize) -> bool {
+        i.is_multiple_of(5)
+    }
+
+    type Outputs = (Vec<(Entity, u32, u32)>, Vec<Entity>, Vec<Entity>);
+
+    fn outputs(world: &mut World) -> Outputs {
+        let moving = world.query_filtered::<(Entity, &Position, &Velocity), Without<Frozen>>();
+        let still = world.query_filtered::<Entity, (With<Position>, Without<Velocity>)>();
+        let frozen = world.query_filtered::<Entity, With<Frozen>>();
+        let moving: Vec<_> = moving.iter(world).map(|(e, p, v)| (e, p.0 as u32, v.0 as u32)).collect();
+        (moving, still.iter(world).collect(), frozen.iter(world).collect())
+    }
+
+    #[test]
+    fn iteration_follows_the_index_order() {
+        const LEN: usize = 2500;
+        // Components added in index order.
+        let mut forward = World::new();
+        let entities: Vec<Entity> = (0..LEN).map(|_| *forward.spawn_entity()).collect();
+        for (i, e) in entities.iter().enumerate() {
+            if has_position(i) {
+                forward.add_component(*e, Position(i as f32));
+            }
+            if has_velocity(i) {
+                forward.add_component(*e, Velocity(i as f32 * 2.0));
+            }
+            if is_frozen(i) {
+                forward.add_component(*e, Frozen);
+            }
+        }
+
+        // Components added backwards, velocities first, with values replaced and removed on the way
+        // and the storages compacted in between.
+        let mut backward = World::new();
+        let entities: Vec<Entity> = (0..LEN).map(|_| *backward.spawn_entity()).collect();
+        for (i, e) in entities.iter().enumerate().rev() {
+            backward.add_component(*e, Velocity(0.0));
+            if !has_velocity(i) {
+                backward.remove_component::<Velocity>(*e);
+            }
+            backward.add_component(*e, Frozen);
+        }
+        backward.compact_all();
+        for (i, e) in entities.iter().enumerate().rev() {
+            if has_position(i) {
+                backward.add_component(*e, Position(i as f32));
+            }
+            if has_velocity(i) {
+                backward.add_component(*e, Velocity(i as f32 * 2.0));
+            }
+            if !is_frozen(i) {
+                backward.remove_component::<Frozen>(*e);
+            }
+        }
+
+        // A group reorders the packed values, the components are added in a scrambled order.
+        let mut grouped = World::new();
+        grouped.register_group::<(Position, Velocity)>();
+        let entities: Vec<Entity> = (0..LEN).map(|_| *grouped.spawn_entity()).collect();
+        for step in 0..LEN {
+            let i = (step * 7919) % LEN;
+            if has_velocity(i) {
+                grouped.add_component(entities[i], Velocity(i as f32 * 2.0));
+            }
+            if is_frozen(i) {
+                grouped.add_component(entities[i], Frozen);
+            }
+        }
+        for step in 0..LEN {
+            let i = (step * 104_729) % LEN;
+            if has_position(i) {
+                grouped.add_component(entities[i], Position(i as f32));
+            }
+        }
+
+        let expected = outputs(&mut forward);
+        assert!(expected.0.windows(2).all(|w| w[0].0.index() < w[1].0.index()));
+        assert_eq!(expected.0.len(), (0..LEN).filter(|i| has_position(*i) && has_velocity(*i) && !is_frozen(*i)).count());
+        assert_eq!(outputs(&mut backward), expected);
+        assert_eq!(outputs(&mut grouped), expected);
+
+        // The unordered iteration of the group gives the same entities.
+        let query = grouped.query_filtered::<(Entity, &Position, &Velocity), Without<Frozen>>();
+        let mut unordered: Vec<_> = query.iter_unordered(&grouped).map(|(e, p, v)| (e, p.0 as u32, v.0 as u32)).collect();
+        assert_ne!(unordered, expected.0);
+        unordered.sort();
+        assert_eq!(unordered, expected.0);
+    }
+
+    #[test]
+    fn queries_reach_past_the_first_32768_indices() {
+        const LEN: usize = 100_000;
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..LEN).map(|_| *world.spawn_entity()).collect();
+        for (i, e) in entities.iter().enumerate() {
+            if has_position(i) {
+                world.add_component(*e, Position(i as f32));
+            }
+            if has_velocity(i) {
+                world.add_component(*e, Velocity(i as f32 * 2.0));
+            }
+            if is_frozen(i) {
+                world.add_component(*e, Frozen);
+            }
+        }
+        let (moving, still, frozen) = outputs(&mut world);
+        let expected = (0..LEN).filter(|i| has_position(*i) && has_velocity(*i) && !is_frozen(*i));
+        assert!(moving.iter().map(|(e, p, v)| (e.index() as usize, *p, *v)).eq(expected.map(|i| (i, i as u32, 2 * i as u32))));
+        assert_eq!(still.len(), (0..LEN).filter(|i| has_position(*i) && !has_velocity(*i)).count());
+        assert_eq!(frozen.len(), LEN / 5);
+        assert_eq!(*still.last().unwrap(), entities[99_997]);
+
+        let mut state = world.query_filtered::<(&mut Position, &Velocity), Without<Frozen>>();
+        state.for_each_mut(&mut world, |(mut position, velocity)| position.0 += velocity.0);
+        assert_eq!(world.get_component::<Position>(entities[70_002]), None);
+        assert_eq!(world.get_component::<Position>(entities[70_004]), Some(&Position(210_012.0)));
+        let mut state = world.query::<&Position>();
+        let chunked: usize = state.query_mut(&mut world).iter_chunks().map(|(entities, _)| entities.len()).sum();
+        assert_eq!(chunked, (0..LEN).filter(|i| has_position(*i)).count());
+    }
+
+    #[test]
+    fn for_each_visits_what_iter_yields() {
+        const LEN: usize = 2500;
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..LEN).map(|_| *world.spawn_entity()).collect();
+        for (i, e) in entities.iter().enumerate() {
+            if has_position(i) {
+                world.add_component(*e, Position(i as f32));
+            }
+            if has_velocity(i) {
+                world.add_component(*e, Velocity(i as f32 * 2.0));
+            }
+            if is_frozen(i) {
+                world.add_component(*e, Frozen);
+            }
+        }
+        // Whole words of entities without any component.
+        for e in &entities[320..960] {
+            world.despawn_entity(*e);
+        }
+
+        let query = world.query_filtered::<(Entity, &Position, &Velocity), Without<Frozen>>();
+        let iterated: Vec<_> = query.iter(&world).map(|(e, p, v)| (e, p.0, v.0)).collect();
+        let mut visited = Vec::new();
+        query.for_each(&world, |(e, p, v)| visited.push((e, p.0, v.0)));
+        assert!(!iterated.is_empty());
+        assert_eq!(visited, iterated);
+
+        let mut state = world.query_filtered::<(&mut Position, &Velocity), Without<Frozen>>();
+        state.for_each_mut(&mut world, |(mut position, velocity)| position.0 += velocity.0);
+        for (e, p, v) in &iterated {
+            assert_eq!(world.get_component::<Position>(*e), Some(&Position(p + v)));
+        }
+
+        // Breaking stops at the item it broke on.
+        let mut seen = 0;
+        let third = query.query(&world).try_for_each(|(e, ..)| {
+            seen += 1;
+            match seen {
+                3 => ControlFlow::Break(e),
+                _ => ControlFlow::Continue(()),
+            }
+        });
+        assert_eq!((third, seen), (ControlFlow::Break(iterated[2].0), 3));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn par_for_each_visits_every_entity_once() {
+        use std::sync::Mutex;
+
+        let mut world = World::new();
+        let entities = world.spawn_batch((0..20_000).map(|i| Position(i as f32)));
+        for e in entities.iter().step_by(3) {
+            world.add_component(*e, Velocity(1.0));
+        }
+        let query = world.query::<(Entity, &Position, &Velocity)>();
+        let visited = Mutex::new(Vec::new());
+        query.query(&world).par_for_each(|(e, ..)| visited.lock().unwrap().push(e));
+        let mut visited = visited.into_inner().unwrap();
+        visited.sort();
+        assert_eq!(visited, query.iter(&world).map(|(e, ..)| e).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn aliasing_items_are_rejected() {
+        let mut world = World::new();
+        // Shared items may repeat.
+        world.query::<(&Position, Option<&Position>)>();
+        world.query_filtered::<&mut Position, With<Position>>();
+        for build in [
+            |world: &mut World| drop(world.query::<(&mut Position, &Position)>()),
+            |world: &mut World| drop(world.query::<(&mut Position, &mut Position)>()),
+            |world: &mut World| drop(world.query::<((Entity, Option<&mut Position>), &Velocity, &Position)>()),
+        ] {
+            let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| build(&mut world))).unwrap_err();
+            let message = panic.downcast_ref::<alloc::string::String>().unwrap();
+            assert!(message.contains(&format!("component {} mutably", type_name::<Position>())), "{}", message);
+        }
+        assert!(world.query::<(&mut Position, &Velocity)>().access().aliased().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "its items would alias")]
+    fn lenses_cant_alias_either() {
+        let mut world = World::new();
+        let mut state = world.query::<&mut Position>();
+        let mut query = state.query_mut(&mut world);
+        let _ = query.transmute_lens::<(&mut Position, &Position)>();
+    }
+}
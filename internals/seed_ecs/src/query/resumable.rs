@@ -0,0 +1,208 @@
+use super::{Query, QueryFilter, QueryState, WorldQuery};
+use crate::UnsafeWorldCell;
+
+/// Where a [`Query::iter_resumable`] stopped, kept from one frame to the next like in a
+/// [`Local`](crate::system::Local).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct QueryCursor {
+    index: usize,
+    passes: u64,
+}
+
+impl QueryCursor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The lowest entity index the next iteration visits.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// How many times an iteration went past the last matched entity and started over.
+    pub fn passes(&self) -> u64 {
+        self.passes
+    }
+
+    /// Starts the current pass over, from index 0.
+    pub fn restart(&mut self) {
+        self.index = 0;
+    }
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Query<'w, 's, Q, F> {
+    /// Iterates in ascending index order from where the last iteration with `cursor` stopped,
+    /// for the systems doing a few entities per frame. The cursor moves past every entity
+    /// yielded, so dropping the iterator early, after a `take(k)` for instance, resumes from the
+    /// next one. Once the last matched entity is passed the iterator ends and the cursor goes
+    /// back to index 0 for the next pass.
+    ///
+    /// A pass visits every entity matched from its start to its end exactly once. An entity that
+    /// starts matching during a pass, spawned or given the components, is visited by that pass if
+    /// its index is past the cursor and by the next one otherwise, and a new entity reusing the
+    /// index of one already visited waits for the next pass too.
+    ///
+    /// ```
+    /// # use seed_ecs::prelude::*;
+    /// # use seed_ecs::query::QueryCursor;
+    /// #[derive(Component)]
+    /// struct Path(u32);
+    ///
+    /// let mut world = World::new();
+    /// world.spawn_batch((0..10).map(Path));
+    /// let mut state = world.query::<&mut Path>();
+    /// let mut cursor = QueryCursor::new();
+    /// let mut frames = 0;
+    /// while cursor.passes() == 0 {
+    ///     for mut path in state.query_mut(&mut world).iter_resumable(&mut cursor).take(4) {
+    ///         path.0 += 1;
+    ///     }
+    ///     frames += 1;
+    /// }
+    /// assert_eq!(frames, 3);
+    /// ```
+    pub fn iter_resumable<'a>(&'a mut self, cursor: &'a mut QueryCursor) -> QueryResumableIter<'a, 's, Q, F> {
+        // The query is borrowed mutably for as long as the items live.
+        unsafe { QueryResumableIter::new(self.world, self.state, cursor) }
+    }
+}
+
+/// Iterates over the entities of a query from a [`QueryCursor`], see
+/// [`Query::iter_resumable`].
+pub struct QueryResumableIter<'w, 's, Q: WorldQuery, F: QueryFilter> {
+    world: UnsafeWorldCell<'w>,
+    state: &'s QueryState<Q, F>,
+    fetch: Q::Fetch<'w>,
+    filter: F::Fetch<'w>,
+    cursor: &'w mut QueryCursor,
+    next_word: usize,
+    word_idx: usize,
+    bits: u32,
+    done: bool,
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> QueryResumableIter<'w, 's, Q, F> {
+    // The caller must make sure the access of the query is allowed on `world`.
+    unsafe fn new(world: UnsafeWorldCell<'w>, state: &'s QueryState<Q, F>, cursor: &'w mut QueryCursor) -> Self {
+        let word_idx = cursor.index >> 5;
+        // The bits of the word below the cursor were visited already.
+        let bits = state.word(world, word_idx) & (u32::MAX << (cursor.index & 31));
+        Self {
+            world,
+            state,
+            fetch: Q::init_fetch(world, &state.fetch_state),
+            filter: F::init_fetch(world, &state.filter_state),
+            cursor,
+            next_word: word_idx + 1,
+            word_idx,
+            bits,
+            done: false,
+        }
+    }
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Iterator for QueryResumableIter<'w, 's, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        loop {
+            while self.bits != 0 {
+                let bit = self.bits.trailing_zeros() as usize;
+                self.bits &= self.bits - 1;
+                let index = (self.word_idx << 5) | bit;
+                if !F::filter(&mut self.filter, index) {
+                    continue;
+                }
+                self.cursor.index = index + 1;
+                // The entity mask is part of the intersection so the index is alive.
+                let entity = self.world.entities().get(index as u32)?;
+                return Some(unsafe { Q::fetch(&mut self.fetch, entity) });
+            }
+            match self.state.driver(self.world).next_word(self.next_word) {
+                Some(word_idx) => {
+                    self.word_idx = word_idx;
+                    self.next_word = word_idx + 1;
+                    self.bits = self.state.word(self.world, word_idx);
+                }
+                None => {
+                    self.done = true;
+                    self.cursor.index = 0;
+                    self.cursor.passes += 1;
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use crate::component::Component;
+
+    use super::*;
+    use crate::entity::Entity;
+    use crate::query::Without;
+    use crate::World;
+
+    #[derive(Component)]
+    struct Target(u32);
+    #[derive(Component)]
+    struct Sleeping;
+
+    #[test]
+    fn a_pass_visits_every_entity_once_despite_spawns() {
+        let mut world = World::new();
+        let first = world.spawn_batch((0..200).map(Target));
+        // Holes for the spawns to fill, behind and ahead of the cursor.
+        world.despawn_batch(&first[..20]);
+        world.despawn_batch(&first[150..170]);
+        let mut state = world.query_filtered::<(Entity, &mut Target), Without<Sleeping>>();
+        let mut cursor = QueryCursor::new();
+        let mut visits: HashMap<Entity, u32> = HashMap::new();
+        let mut spawned = Vec::new();
+        let mut frames = 0;
+        while cursor.passes() == 0 {
+            for (entity, mut target) in state.query_mut(&mut world).iter_resumable(&mut cursor).take(13) {
+                target.0 += 1;
+                *visits.entry(entity).or_default() += 1;
+            }
+            frames += 1;
+            spawned.extend(world.spawn_batch([Target(0), Target(0)]));
+            world.add_component(first[100 + frames], Sleeping);
+        }
+        assert!(visits.values().all(|count| *count == 1));
+        for entity in first[20..150].iter().chain(&first[170..]) {
+            // Put to sleep before the cursor got there, or visited.
+            let asleep = world.has_component::<Sleeping>(*entity);
+            assert!(asleep || visits.contains_key(entity), "{:?}", entity);
+        }
+        let ahead = spawned.iter().filter(|entity| visits.contains_key(entity)).count();
+        assert!(ahead > 0 && ahead < spawned.len());
+
+        // The next pass starts over and visits the entities spawned behind the cursor.
+        let mut second = Vec::new();
+        while cursor.passes() == 1 {
+            second.extend(state.query_mut(&mut world).iter_resumable(&mut cursor).take(50).map(|(entity, _)| entity));
+        }
+        assert_eq!(second, state.query_mut(&mut world).iter_mut().map(|(entity, _)| entity).collect::<Vec<_>>());
+        assert!(spawned.iter().all(|entity| second.contains(entity)));
+    }
+
+    #[test]
+    fn an_empty_query_ends_the_pass_at_once() {
+        let mut world = World::new();
+        let mut state = world.query::<&Target>();
+        let mut cursor = QueryCursor::new();
+        let mut query = state.query_mut(&mut world);
+        {
+            let mut iter = query.iter_resumable(&mut cursor);
+            assert!(iter.next().is_none());
+            assert!(iter.next().is_none());
+        }
+        assert_eq!((cursor.index(), cursor.passes()), (0, 1));
+    }
+}
@@ -0,0 +1,165 @@
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::{Query, QueryFilter, QueryIter, QueryState, WorldQuery};
+use crate::UnsafeWorldCell;
+
+/// The index buffer the sorted iterators reuse between sorts.
+///
+/// Never blocks: when two sorts overlap the second one allocates its own buffer, and only one of
+/// them is kept when they are handed back.
+#[derive(Default)]
+pub(crate) struct ScratchBuffer {
+    busy: AtomicBool,
+    indices: UnsafeCell<Vec<usize>>,
+}
+
+// The buffer is only touched by whoever flipped `busy`.
+unsafe impl Sync for ScratchBuffer {}
+
+impl ScratchBuffer {
+    pub fn take(&self) -> Vec<usize> {
+        if self.busy.swap(true, Ordering::Acquire) {
+            return Vec::new();
+        }
+        let indices = core::mem::take(unsafe { &mut *self.indices.get() });
+        self.busy.store(false, Ordering::Release);
+        indices
+    }
+
+    pub fn put(&self, indices: Vec<usize>) {
+        if !self.busy.swap(true, Ordering::Acquire) {
+            unsafe { *self.indices.get() = indices };
+            self.busy.store(false, Ordering::Release);
+        }
+    }
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Query<'w, 's, Q, F> {
+    /// Iterates over the matched entities in the order of the keys extracted by `key`.
+    ///
+    /// The keys are read through the read-only version of the query, then the full items are
+    /// fetched in order. The sort is stable so equal keys keep the index order.
+    pub fn iter_sorted_by_key<K, G>(&mut self, key: G) -> QuerySortedIter<'_, 's, Q, F>
+    where
+        K: Ord,
+        G: for<'a> Fn(&<Q::ReadOnly as WorldQuery>::Item<'a>) -> K,
+    {
+        let mut indices = self.state.scratch.take();
+        indices.clear();
+        self.state.collect_indices(self.world, &mut indices);
+        let entities = self.world.entities();
+        // The read-only fetch only lives during the sort, before any mutable item is given out.
+        let mut keys =
+            unsafe { <Q::ReadOnly as WorldQuery>::init_fetch(self.world, &self.state.fetch_state) };
+        indices.sort_by_key(|index| {
+            let entity = entities.get(*index as u32).unwrap();
+            key(&unsafe { <Q::ReadOnly as WorldQuery>::fetch(&mut keys, entity) })
+        });
+        QuerySortedIter {
+            world: self.world,
+            state: self.state,
+            fetch: unsafe { Q::init_fetch(self.world, &self.state.fetch_state) },
+            indices,
+            cursor: 0,
+        }
+    }
+
+    /// Iterates over the matched entities sorted by [`Entity`](crate::entity::Entity).
+    ///
+    /// Live entities have distinct indices and the masks are walked in index order, so this is
+    /// the plain iterator.
+    pub fn iter_sorted_by_entity(&mut self) -> QueryIter<'_, 's, Q, F> {
+        self.iter_mut()
+    }
+}
+
+/// Iterates over the entities of a query in a precomputed order, see
+/// [`Query::iter_sorted_by_key`].
+pub struct QuerySortedIter<'w, 's, Q: WorldQuery, F: QueryFilter> {
+    world: UnsafeWorldCell<'w>,
+    state: &'s QueryState<Q, F>,
+    fetch: Q::Fetch<'w>,
+    indices: Vec<usize>,
+    cursor: usize,
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Iterator for QuerySortedIter<'w, 's, Q, F> {
+    type Item = Q::Item<'w>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = *self.indices.get(self.cursor)?;
+        self.cursor += 1;
+        let entity = self.world.entities().get(index as u32)?;
+        // Each index is yielded once and the query is borrowed for as long as the items live.
+        Some(unsafe { Q::fetch(&mut self.fetch, entity) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.indices.len() - self.cursor;
+        (len, Some(len))
+    }
+}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> ExactSizeIterator for QuerySortedIter<'w, 's, Q, F> {}
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Drop for QuerySortedIter<'w, 's, Q, F> {
+    fn drop(&mut self) {
+        // Hands the buffer back to the state for the next sort.
+        self.state.scratch.put(core::mem::take(&mut self.indices));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::component::Component;
+    use crate::entity::Entity;
+    use crate::World;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Sprite(&'static str);
+    #[derive(Debug, PartialEq, Component)]
+    struct Z(i32);
+
+    #[test]
+    fn z_sorted_and_stable() {
+        let mut world = World::new();
+        let mut entities = Vec::new();
+        for (name, z) in [("sky", -10), ("hero", 5), ("tree", 0), ("cloud", -10), ("hud", 100)] {
+            let e = *world.spawn_entity();
+            world.add_component(e, Sprite(name));
+            world.add_component(e, Z(z));
+            entities.push(e);
+        }
+
+        let mut state = world.query::<(&Sprite, &Z)>();
+        let mut query = state.query_mut(&mut world);
+        let names: Vec<_> = query.iter_sorted_by_key(|(_, z)| z.0).map(|(s, _)| s.0).collect();
+        assert_eq!(names, vec!["sky", "cloud", "tree", "hero", "hud"]);
+        let reversed: Vec<_> = query.iter_sorted_by_key(|(_, z)| -z.0).map(|(s, _)| s.0).collect();
+        assert_eq!(reversed, vec!["hud", "hero", "tree", "sky", "cloud"]);
+
+        let mut state = world.query::<Entity>();
+        let mut query = state.query_mut(&mut world);
+        assert_eq!(query.iter_sorted_by_entity().collect::<Vec<_>>(), entities);
+    }
+
+    #[test]
+    fn mutate_in_sorted_order() {
+        let mut world = World::new();
+        for z in [3, 1, 2] {
+            let e = *world.spawn_entity();
+            world.add_component(e, Z(z));
+        }
+        let mut state = world.query::<&mut Z>();
+        let mut query = state.query_mut(&mut world);
+        // Rank the entities by their previous z.
+        for (rank, mut z) in query.iter_sorted_by_key(|z| z.0).enumerate() {
+            z.0 = rank as i32 * 10;
+        }
+        let zs: Vec<_> = query.iter_mut().map(|z| z.0).collect();
+        assert_eq!(zs, vec![20, 0, 10]);
+        assert_eq!(query.iter_sorted_by_key(|z| z.0).len(), 3);
+    }
+}
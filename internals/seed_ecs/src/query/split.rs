@@ -0,0 +1,196 @@
+use core::any::type_name;
+use core::error::Error;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr::NonNull;
+
+use super::{Query, QueryFilter, QueryState, WorldQuery};
+use crate::change_detection::Mut;
+use crate::component::{assert_mutable, Component};
+use crate::entity::Entity;
+use crate::storage::Storage;
+use crate::{UnsafeWorldCell, World};
+
+/// The storage of `T` borrowed mutably next to a query that doesn't reach it, see
+/// [`World::query_and_storage_mut`].
+pub struct StorageMut<'w, T> {
+    world: UnsafeWorldCell<'w>,
+    storage: NonNull<Storage<T>>,
+    _marker: PhantomData<&'w mut Storage<T>>,
+}
+
+// The handle is the only way to the storage while it lives, like a `&mut Storage<T>`.
+unsafe impl<T: Send> Send for StorageMut<'_, T> {}
+unsafe impl<T: Sync> Sync for StorageMut<'_, T> {}
+
+impl<'w, T: Component> StorageMut<'w, T> {
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        if !self.world.entities().is_alive(entity) {
+            return None;
+        }
+        unsafe { self.storage.as_ref() }.get(entity.index() as usize)
+    }
+
+    /// The `T` of the entity, marked changed when written like [`World::get_component_mut`].
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Mut<'_, T>> {
+        if !self.world.entities().is_alive(entity) {
+            return None;
+        }
+        let storage = unsafe { self.storage.as_mut() };
+        let (value, ticks) = storage.get_with_ticks_mut(entity.index() as usize)?;
+        Some(match ticks {
+            Some(ticks) => Mut::with_ticks(value, ticks, self.world.last_run(), self.world.this_run()),
+            None => Mut::new(value),
+        })
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+}
+
+/// The query of [`World::query_and_storage_mut`] reaches the component whose storage was asked
+/// for, through its data or its filters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageAliased {
+    pub query: &'static str,
+    pub component: &'static str,
+}
+
+impl fmt::Display for StorageAliased {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Query {} reaches component {}, its storage can't be borrowed mutably next to the query",
+            self.query, self.component
+        )
+    }
+}
+
+impl Error for StorageAliased {}
+
+impl World {
+    /// Runs `state` along with mutable access to the storage of `T`, for the lookups into other
+    /// entities while iterating, like the positions of the neighbours of a boid.
+    ///
+    /// Fails if the query reads, writes or filters on `T`: its items could alias the values
+    /// handed out by the storage.
+    ///
+    /// ```
+    /// # use seed_ecs::prelude::*;
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    /// #[derive(Component)]
+    /// struct Leader(Entity);
+    ///
+    /// let mut world = World::new();
+    /// let leader = *world.spawn_entity();
+    /// world.add_component(leader, Position(4.0));
+    /// let follower = *world.spawn_entity();
+    /// world.add_component(follower, Position(0.0));
+    /// world.add_component(follower, Leader(leader));
+    /// let mut followers = world.query::<(Entity, &Leader)>();
+    /// let (query, mut positions) = world.query_and_storage_mut::<Position, _, _>(&mut followers).unwrap();
+    /// for (entity, leader) in query.iter() {
+    ///     let target = positions.get(leader.0).unwrap().0;
+    ///     positions.get_mut(entity).unwrap().0 = target;
+    /// }
+    /// assert_eq!(world.get_component::<Position>(follower).unwrap().0, 4.0);
+    /// ```
+    pub fn query_and_storage_mut<'w, 's, T, Q, F>(
+        &'w mut self,
+        state: &'s mut QueryState<Q, F>,
+    ) -> Result<(Query<'w, 's, Q, F>, StorageMut<'w, T>), StorageAliased>
+    where
+        T: Component,
+        Q: WorldQuery,
+        F: QueryFilter,
+    {
+        const { assert_mutable::<T>() };
+        let id = self.register_component::<T>();
+        let aliased = state.access.has_read(id)
+            || state.required.binary_search(&id).is_ok()
+            || state.excluded.binary_search(&id).is_ok();
+        if aliased {
+            return Err(StorageAliased {
+                query: type_name::<QueryState<Q, F>>(),
+                component: type_name::<T>(),
+            });
+        }
+        let world = self.as_unsafe_world_cell();
+        // The query never touches the storage of `T`, the handle has it to itself.
+        let storage = StorageMut {
+            world,
+            storage: unsafe { world.storage_ptr::<T>(id) },
+            _marker: PhantomData,
+        };
+        Ok((unsafe { Query::new(world, state) }, storage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::{Changed, With};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(f32, f32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Velocity(f32, f32);
+    #[derive(Component)]
+    struct Neighbors(Vec<Entity>);
+
+    #[test]
+    fn boids_read_their_neighbours_while_iterating() {
+        let mut world = World::new();
+        let boids: Vec<Entity> = (0..4)
+            .map(|i| {
+                let e = *world.spawn_entity();
+                world.add_component(e, Position(i as f32, 0.0));
+                world.add_component(e, Velocity(0.0, 0.0));
+                e
+            })
+            .collect();
+        for (i, boid) in boids.iter().enumerate() {
+            let neighbors = boids.iter().copied().filter(|other| other != boid).take(2 + i % 2).collect();
+            world.add_component(*boid, Neighbors(neighbors));
+        }
+        let gone = *world.spawn_entity();
+        world.add_component(gone, Position(9.0, 9.0));
+        world.despawn_entity(gone);
+        world.clear_trackers();
+
+        let mut flock = world.query::<(Entity, &Neighbors, &mut Velocity)>();
+        let (mut query, mut positions) = world.query_and_storage_mut::<Position, _, _>(&mut flock).unwrap();
+        for (boid, neighbors, mut velocity) in query.iter_mut() {
+            let own = *positions.get(boid).unwrap();
+            let count = neighbors.0.len() as f32;
+            let center = neighbors.0.iter().map(|e| positions.get(*e).unwrap().0).sum::<f32>() / count;
+            velocity.0 = center - own.0;
+            positions.get_mut(boid).unwrap().1 = count;
+        }
+        assert!(!positions.contains(gone));
+
+        let velocities: Vec<f32> = boids.iter().map(|e| world.get_component::<Velocity>(*e).unwrap().0).collect();
+        // The heights written along the way leave the centers alone.
+        assert_eq!(velocities, [1.5, 5.0 / 3.0 - 1.0, -1.5, -2.0]);
+        let heights: Vec<f32> = boids.iter().map(|e| world.get_component::<Position>(*e).unwrap().1).collect();
+        assert_eq!(heights, [2.0, 3.0, 2.0, 3.0]);
+        let changed = world.query_filtered::<Entity, Changed<Position>>();
+        assert_eq!(changed.iter(&world).count(), 4);
+    }
+
+    #[test]
+    fn aliasing_queries_are_rejected() {
+        let mut world = World::new();
+        let mut reading = world.query::<(&Position, &mut Velocity)>();
+        let error = world.query_and_storage_mut::<Position, _, _>(&mut reading).err().unwrap();
+        assert!(error.component.ends_with("Position"), "{}", error);
+        assert!(error.to_string().contains("can't be borrowed mutably"), "{}", error);
+        let mut writing = world.query::<&mut Position>();
+        assert!(world.query_and_storage_mut::<Position, _, _>(&mut writing).is_err());
+        let mut filtering = world.query_filtered::<&mut Velocity, With<Position>>();
+        assert!(world.query_and_storage_mut::<Position, _, _>(&mut filtering).is_err());
+        assert!(world.query_and_storage_mut::<Neighbors, _, _>(&mut filtering).is_ok());
+    }
+}
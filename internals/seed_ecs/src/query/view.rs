@@ -0,0 +1,418 @@
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::error::Error;
+use core::fmt;
+use core::ops::ControlFlow;
+
+use super::{Access, QueryFilter, QueryIter, QueryState, ReadOnlyWorldQuery, WorldQuery};
+use crate::component::ComponentId;
+use crate::entity::Entity;
+use crate::UnsafeWorldCell;
+
+/// A [`QueryState`] borrowed together with the world it runs on.
+pub struct Query<'w, 's, Q: WorldQuery, F: QueryFilter = ()> {
+    pub(super) world: UnsafeWorldCell<'w>,
+    pub(super) state: &'s QueryState<Q, F>,
+}
+
+/// A [`Query`] with every mutable access downgraded to a shared one. It runs on a world borrowed
+/// shared, from as many threads at once as needed, see [`QueryState::to_readonly`].
+pub type ReadOnlyQuery<'w, 's, Q, F = ()> = Query<'w, 's, <Q as WorldQuery>::ReadOnly, F>;
+
+impl<'w, 's, Q: WorldQuery, F: QueryFilter> Query<'w, 's, Q, F> {
+    /// # Safety
+    ///
+    /// The caller must make sure the access of the query is allowed on `world` for `'w`.
+    pub unsafe fn new(world: UnsafeWorldCell<'w>, state: &'s QueryState<Q, F>) -> Self {
+        Self { world, state }
+    }
+
+    pub fn iter(&self) -> QueryIter<'_, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        unsafe { QueryIter::new(self.world, self.state) }
+    }
+
+    pub fn iter_mut(&mut self) -> QueryIter<'_, 's, Q, F> {
+        // The query is borrowed mutably for as long as the items live.
+        unsafe { QueryIter::new(self.world, self.state) }
+    }
+
+    /// Iterates over the same entities as [`Query::iter`] in an unspecified order, which lets the
+    /// queries on a group walk its members directly, see
+    /// [`World::register_group`](crate::World::register_group).
+    pub fn iter_unordered(&self) -> QueryIter<'_, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        unsafe { QueryIter::new_unordered(self.world, self.state) }
+    }
+
+    /// Mutable version of [`Query::iter_unordered`].
+    pub fn iter_unordered_mut(&mut self) -> QueryIter<'_, 's, Q, F> {
+        unsafe { QueryIter::new_unordered(self.world, self.state) }
+    }
+
+    /// Calls `f` on the same items as [`Query::iter`], in the same order. Walking the masks from
+    /// the inside is faster than pulling the items out of an iterator one by one.
+    pub fn for_each(&self, mut f: impl FnMut(Q::Item<'_>))
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        let _ = self.try_for_each(|item| {
+            f(item);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Same as [`Query::for_each`], stopping at the first item `f` breaks on.
+    ///
+    /// ```
+    /// # use core::ops::ControlFlow;
+    /// # use seed_ecs::component::Component;
+    /// # use seed_ecs::World;
+    /// #[derive(Component)]
+    /// struct Health(u32);
+    ///
+    /// let mut world = World::new();
+    /// world.spawn_batch([Health(3), Health(0), Health(5)]);
+    /// let state = world.query::<&Health>();
+    /// let first_dead = state.query(&world).try_for_each(|health| match health.0 {
+    ///     0 => ControlFlow::Break(()),
+    ///     _ => ControlFlow::Continue(()),
+    /// });
+    /// assert!(first_dead.is_break());
+    /// ```
+    pub fn try_for_each<B>(&self, f: impl FnMut(Q::Item<'_>) -> ControlFlow<B>) -> ControlFlow<B>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        unsafe { self.state.walk_words(self.world, 0..usize::MAX, f) }
+    }
+
+    /// Mutable version of [`Query::for_each`].
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(Q::Item<'_>)) {
+        let _ = self.try_for_each_mut(|item| {
+            f(item);
+            ControlFlow::<()>::Continue(())
+        });
+    }
+
+    /// Mutable version of [`Query::try_for_each`].
+    pub fn try_for_each_mut<B>(&mut self, f: impl FnMut(Q::Item<'_>) -> ControlFlow<B>) -> ControlFlow<B> {
+        // The query is borrowed mutably while `f` runs and every entity is visited once.
+        unsafe { self.state.walk_words(self.world, 0..usize::MAX, f) }
+    }
+
+    /// Calls `f` on the items of [`Query::iter`] from as many threads as the machine runs, each
+    /// thread walking its own range of the masks. Runs on the calling thread when there are few
+    /// entities.
+    #[cfg(feature = "std")]
+    pub fn par_for_each(&self, f: impl Fn(Q::Item<'_>) + Sync)
+    where
+        Q: ReadOnlyWorldQuery,
+        QueryState<Q, F>: Sync,
+    {
+        // Below this many leaf words, 32 entities each, spawning threads costs more than it saves.
+        const MIN_WORDS: usize = 64;
+        let words = self.state.driver(self.world).word_count();
+        let threads = std::thread::available_parallelism().map_or(1, |threads| threads.get());
+        let chunk = words.div_ceil(threads).max(MIN_WORDS);
+        let walk = |range| {
+            let _ = unsafe {
+                self.state.walk_words(self.world, range, |item| {
+                    f(item);
+                    ControlFlow::<()>::Continue(())
+                })
+            };
+        };
+        if chunk >= words {
+            return walk(0..words);
+        }
+        std::thread::scope(|scope| {
+            for start in (chunk..words).step_by(chunk) {
+                let walk = &walk;
+                scope.spawn(move || walk(start..(start + chunk).min(words)));
+            }
+            walk(0..chunk);
+        });
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<Q::Item<'_>>
+    where
+        Q: ReadOnlyWorldQuery,
+    {
+        unsafe { self.state.get_unchecked(self.world, entity) }
+    }
+
+    /// Number of entities matched. The masks are intersected a word at a time and their bits
+    /// counted, the filters reading ticks like [`Changed`](super::Changed) are checked on each of
+    /// them, and no item is fetched: nothing is marked changed, even for mutable queries.
+    pub fn count(&self) -> usize {
+        self.state.count_up_to(self.world, usize::MAX)
+    }
+
+    /// True if the query matches no entity, stops at the first one matched. Like
+    /// [`Query::count`] no item is fetched.
+    pub fn is_empty(&self) -> bool {
+        self.state.count_up_to(self.world, 1) == 0
+    }
+
+    /// True if the query matches the entity, checked on the masks and the filters without
+    /// fetching the item.
+    pub fn contains(&self, entity: Entity) -> bool {
+        let mut filter = unsafe { F::init_fetch(self.world, &self.state.filter_state) };
+        self.state.matches(self.world, entity) && F::filter(&mut filter, entity.index() as usize)
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<Q::Item<'_>> {
+        unsafe { self.state.get_unchecked(self.world, entity) }
+    }
+
+    /// The same query with every mutable access downgraded to a shared one.
+    pub fn as_readonly(&self) -> QueryLens<'_, Q::ReadOnly, F> {
+        QueryLens {
+            world: self.world,
+            state: self.state.to_readonly(),
+        }
+    }
+
+    /// Views the query as a query fetching `NewQ` over the same entities.
+    ///
+    /// `NewQ` may only read the components this query reads or writes, write the ones it writes,
+    /// and require the ones it requires. The masks to intersect are reused as they are.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the items of `NewQ` alias, like those of [`QueryState::new`].
+    pub fn transmute_lens<NewQ: WorldQuery>(
+        &mut self,
+    ) -> Result<QueryLens<'_, NewQ, F>, QueryLensError> {
+        let components = self.world.components();
+        let fetch_state = NewQ::get_state(components).ok_or_else(|| QueryLensError {
+            missing: vec![type_name::<NewQ>()],
+            escalated: Vec::new(),
+        })?;
+        let mut access = Access::default();
+        NewQ::access(&fetch_state, &mut access);
+        if let Some(id) = access.aliased() {
+            super::panic_aliased::<NewQ>(components, id);
+        }
+        let mut required = Vec::new();
+        NewQ::required(&fetch_state, &mut required);
+
+        let original = &self.state.access;
+        let mut missing: Vec<_> = access.reads().iter().filter(|id| !original.has_read(**id)).collect();
+        missing.extend(access.writes().iter().filter(|id| !original.has_read(**id)));
+        missing.extend(required.iter().filter(|id| self.state.required.binary_search(id).is_err()));
+        missing.sort();
+        missing.dedup();
+        let escalated = access
+            .writes()
+            .iter()
+            .filter(|id| original.has_read(**id) && !original.has_write(**id));
+        if !missing.is_empty() || escalated.clone().next().is_some() {
+            let name = |id: &ComponentId| components.info(*id).map_or("?", |info| info.name());
+            return Err(QueryLensError {
+                missing: missing.into_iter().map(name).collect(),
+                escalated: escalated.map(name).collect(),
+            });
+        }
+
+        F::access(&self.state.filter_state, &mut access);
+        Ok(QueryLens {
+            world: self.world,
+            state: QueryState {
+                fetch_state,
+                filter_state: self.state.filter_state.clone(),
+                required: self.state.required.clone(),
+                excluded: self.state.excluded.clone(),
+                access,
+                scratch: Default::default(),
+            },
+        })
+    }
+}
+
+/// A query derived from another one by [`Query::transmute_lens`] or [`Query::as_readonly`].
+pub struct QueryLens<'w, Q: WorldQuery, F: QueryFilter = ()> {
+    world: UnsafeWorldCell<'w>,
+    state: QueryState<Q, F>,
+}
+
+impl<'w, Q: WorldQuery, F: QueryFilter> QueryLens<'w, Q, F> {
+    pub fn query(&mut self) -> Query<'_, '_, Q, F> {
+        // The lens borrows the original query, which had this access or a wider one.
+        unsafe { Query::new(self.world, &self.state) }
+    }
+}
+
+/// Why a lens could not be built from a query, with the names of the offending components.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QueryLensError {
+    /// Components the lens accesses or requires but the query doesn't.
+    pub missing: Vec<&'static str>,
+    /// Components the lens writes but the query only reads.
+    pub escalated: Vec<&'static str>,
+}
+
+impl fmt::Display for QueryLensError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Query lens is not a subset of the query")?;
+        if !self.missing.is_empty() {
+            write!(f, ", missing access to {}", self.missing.join(", "))?;
+        }
+        if !self.escalated.is_empty() {
+            write!(f, ", read access escalated to write for {}", self.escalated.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for QueryLensError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::query::{Changed, With, Without};
+    use crate::World;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Transform(f32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Velocity(f32);
+    #[derive(Component)]
+    struct Sleeping;
+
+    fn sum_transforms(query: &Query<&Transform>) -> f32 {
+        query.iter().map(|t| t.0).sum()
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        for i in 0..50 {
+            let e = *world.spawn_entity();
+            world.add_component(e, Transform(i as f32));
+            if i % 2 == 0 {
+                world.add_component(e, Velocity(1.0));
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn narrow_to_a_subset() {
+        let mut world = world();
+        let mut state = world.query::<(Entity, &Transform, &mut Velocity)>();
+        let mut query = state.query_mut(&mut world);
+        let expected: Vec<Entity> = query.iter_mut().map(|(e, _, _)| e).collect();
+
+        let mut lens = query.transmute_lens::<Entity>().unwrap();
+        assert_eq!(lens.query().iter().collect::<Vec<_>>(), expected);
+        let mut lens = query.transmute_lens::<&Transform>().unwrap();
+        assert_eq!(sum_transforms(&lens.query()), (0..50).step_by(2).sum::<i32>() as f32);
+        let mut lens = query.transmute_lens::<&mut Velocity>().unwrap();
+        for mut velocity in lens.query().iter_mut() {
+            velocity.0 = 2.0;
+        }
+        assert!(query.iter_mut().all(|(_, _, v)| v.0 == 2.0));
+    }
+
+    #[test]
+    fn widening_errors() {
+        let mut world = world();
+        world.register_component::<Sleeping>();
+        let mut state = world.query::<(&Transform, Option<&Velocity>)>();
+        let mut query = state.query_mut(&mut world);
+
+        let error = query.transmute_lens::<&mut Transform>().err().unwrap();
+        assert_eq!(error.escalated, vec![type_name::<Transform>()]);
+        let error = query.transmute_lens::<(&Velocity, &Sleeping)>().err().unwrap();
+        assert_eq!(error.missing, vec![type_name::<Velocity>(), type_name::<Sleeping>()]);
+        assert!(error.escalated.is_empty());
+        assert!(error.to_string().contains("Sleeping"));
+        assert!(query.transmute_lens::<Option<&Velocity>>().is_ok());
+    }
+
+    #[test]
+    fn counts_match_the_iterations() {
+        let mut world = world();
+        let sleeping: Vec<Entity> = world.query::<Entity>().iter(&world).step_by(3).collect();
+        for e in &sleeping {
+            world.add_component(*e, Sleeping);
+        }
+        let state = world.query::<&Transform>();
+        assert_eq!(state.query(&world).count(), 50);
+        let state = world.query_filtered::<&Transform, (With<Velocity>, Without<Sleeping>)>();
+        let query = state.query(&world);
+        assert_eq!(query.count(), query.iter().count());
+        assert!(!query.is_empty());
+        let state = world.query_filtered::<Entity, (With<Sleeping>, Without<Transform>)>();
+        assert!(state.query(&world).is_empty());
+        assert_eq!(state.query(&world).count(), 0);
+
+        world.clear_trackers();
+        for e in &sleeping[..4] {
+            world.get_component_mut::<Transform>(*e).unwrap().0 += 1.0;
+        }
+        let state = world.query_filtered::<Entity, (Changed<Transform>, Without<Velocity>)>();
+        let query = state.query(&world);
+        assert_eq!(query.count(), query.iter().count());
+        assert_eq!(query.count(), sleeping[..4].iter().filter(|e| !world.has_component::<Velocity>(**e)).count());
+    }
+
+    #[test]
+    fn contains_checks_the_filters() {
+        let mut world = world();
+        let entities: Vec<Entity> = world.query::<Entity>().iter(&world).collect();
+        world.add_component(entities[2], Sleeping);
+        let state = world.query_filtered::<&Transform, (With<Velocity>, Without<Sleeping>)>();
+        let query = state.query(&world);
+        assert!(query.contains(entities[0]));
+        assert!(!query.contains(entities[1]));
+        assert!(!query.contains(entities[2]));
+        world.despawn_entity(entities[0]);
+        let state = world.query_filtered::<Entity, With<Velocity>>();
+        assert!(!state.query(&world).contains(entities[0]));
+
+        world.clear_trackers();
+        world.get_component_mut::<Transform>(entities[4]).unwrap().0 = 0.0;
+        let state = world.query_filtered::<Entity, Changed<Transform>>();
+        assert!(state.query(&world).contains(entities[4]));
+        assert!(!state.query(&world).contains(entities[6]));
+    }
+
+    #[test]
+    fn mutable_queries_mark_nothing_when_counting() {
+        let mut world = world();
+        let e = world.query::<Entity>().iter(&world).nth(4).unwrap();
+        world.clear_trackers();
+        let mut state = world.query::<&mut Transform>();
+        {
+            let query = state.query_mut(&mut world);
+            assert_eq!(query.count(), 50);
+            assert!(!query.is_empty());
+            assert!(query.contains(e));
+        }
+        let changed = world.query_filtered::<Entity, Changed<Transform>>();
+        assert!(changed.query(&world).is_empty());
+        // Fetching the items does mark them.
+        state.query_mut(&mut world).get_mut(e).unwrap().0 = 7.0;
+        assert_eq!(changed.query(&world).iter().collect::<Vec<_>>(), [e]);
+    }
+
+    #[test]
+    fn readonly_view_of_a_mutable_query() {
+        let mut world = world();
+        let mut state = world.query::<&mut Transform>();
+        let mut query = state.query_mut(&mut world);
+        for mut transform in query.iter_mut() {
+            transform.0 = 1.0;
+        }
+        let mut readonly = query.as_readonly();
+        assert!(readonly.query().iter().all(|t| *t == Transform(1.0)));
+        assert_eq!(sum_transforms(&readonly.query()), 50.0);
+    }
+}
@@ -0,0 +1,266 @@
+//! A view of the world that can only read, to share it with other threads while the owner keeps it
+//! borrowed.
+
+use crate::change_detection::Tick;
+use crate::component::{Component, Components};
+use crate::entity::{Entities, Entity};
+use crate::hierarchy::{Name, Parent};
+use crate::query::{Changed, Query, QueryFilter, QueryIter, QueryState, ReadOnlyQuery, ReadOnlyWorldQuery, With};
+use crate::World;
+
+// Fails to compile if the world stops being shareable across threads. Nothing reachable from a
+// `&World` writes: the ticks are only written through `&mut World` and the storages and resources
+// are only mutated through it or an `UnsafeWorldCell`. The read only queries and their iterators
+// only hold shared references, they can be sent to other threads when their components can.
+const _: () = {
+    const fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<World>();
+    assert_send_sync::<ReadOnlyWorld<'static>>();
+    type Named = (Entity, &'static Name, Option<&'static mut Parent>);
+    assert_send_sync::<ReadOnlyQuery<'static, 'static, Named, Changed<Name>>>();
+    assert_send_sync::<QueryIter<'static, 'static, <Named as crate::query::WorldQuery>::ReadOnly, With<Parent>>>();
+};
+
+/// The getters and read only queries of a [`World`], created by [`World::as_read_only`].
+///
+/// It is `Copy` and `Sync`, an audio or render thread can read positions while the main thread
+/// holds the borrow. Reading never updates any tick, the change filters see the same changes from
+/// every thread.
+#[derive(Clone, Copy)]
+pub struct ReadOnlyWorld<'w> {
+    world: &'w World,
+}
+
+impl<'w> ReadOnlyWorld<'w> {
+    pub fn change_tick(&self) -> Tick {
+        self.world.change_tick()
+    }
+
+    pub fn last_change_tick(&self) -> Tick {
+        self.world.last_change_tick()
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.world.is_alive(entity)
+    }
+
+    pub fn entities(&self) -> &'w Entities {
+        self.world.enities()
+    }
+
+    pub fn components(&self) -> &'w Components {
+        self.world.components()
+    }
+
+    pub fn get_component<T: Component>(&self, entity: Entity) -> Option<&'w T> {
+        self.world.get_component(entity)
+    }
+
+    pub fn has_component<T: Component>(&self, entity: Entity) -> bool {
+        self.world.has_component::<T>(entity)
+    }
+
+    pub fn get_resource<T: Send + Sync + 'static>(&self) -> Option<&'w T> {
+        self.world.get_resource()
+    }
+
+    pub fn contains_resource<T: Send + Sync + 'static>(&self) -> bool {
+        self.world.contains_resource::<T>()
+    }
+
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.world.parent(entity)
+    }
+
+    pub fn children(&self, entity: Entity) -> &'w [Entity] {
+        self.world.children(entity)
+    }
+
+    /// Runs a query built beforehand with [`World::query`], only read only queries are allowed.
+    pub fn query<'s, Q, F>(&self, state: &'s QueryState<Q, F>) -> Query<'w, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+        F: QueryFilter,
+    {
+        state.query(self.world)
+    }
+
+    pub fn iter<'s, Q, F>(&self, state: &'s QueryState<Q, F>) -> QueryIter<'w, 's, Q, F>
+    where
+        Q: ReadOnlyWorldQuery,
+        F: QueryFilter,
+    {
+        state.iter(self.world)
+    }
+
+    pub fn get<Q, F>(&self, state: &QueryState<Q, F>, entity: Entity) -> Option<Q::Item<'w>>
+    where
+        Q: ReadOnlyWorldQuery,
+        F: QueryFilter,
+    {
+        state.get(self.world, entity)
+    }
+}
+
+impl World {
+    pub fn as_read_only(&self) -> ReadOnlyWorld<'_> {
+        ReadOnlyWorld { world: self }
+    }
+
+    /// Runs `f` with a scope whose threads all read the world at the same time, joined before
+    /// returning like [`std::thread::scope`]. The threads only get a `&World`, mutable queries and
+    /// structural changes are rejected at compile time:
+    ///
+    /// ```compile_fail
+    /// # use seed_ecs::prelude::*;
+    /// #[derive(Component)]
+    /// struct Position(f32);
+    ///
+    /// let mut world = World::new();
+    /// let mut positions = world.query::<&mut Position>();
+    /// world.par_scope(|scope| {
+    ///     scope.spawn(|world| positions.iter_mut(world).count());
+    /// });
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if a resource is taken out by a [`World::resource_scope`], it is borrowed mutably by
+    /// the scope and the threads wouldn't find it.
+    #[cfg(feature = "std")]
+    pub fn par_scope<'env, T>(&'env self, f: impl for<'scope> FnOnce(WorldScope<'scope, 'env>) -> T) -> T {
+        if let Some(held_by) = self.resources.any_scoped() {
+            panic!("World::par_scope called while {} holds a resource", held_by.unwrap_or("a resource scope"));
+        }
+        std::thread::scope(|scope| f(WorldScope { scope, world: self }))
+    }
+}
+
+/// The scope of [`World::par_scope`], which spawns the threads reading the world.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy)]
+pub struct WorldScope<'scope, 'env: 'scope> {
+    scope: &'scope std::thread::Scope<'scope, 'env>,
+    world: &'env World,
+}
+
+#[cfg(feature = "std")]
+impl<'scope, 'env> WorldScope<'scope, 'env> {
+    /// Spawns a thread running `f` on the world, joined at the latest when the scope ends.
+    pub fn spawn<T, F>(&self, f: F) -> std::thread::ScopedJoinHandle<'scope, T>
+    where
+        T: Send + 'scope,
+        F: FnOnce(&'env World) -> T + Send + 'scope,
+    {
+        let world = self.world;
+        self.scope.spawn(move || f(world))
+    }
+
+    pub fn world(&self) -> &'env World {
+        self.world
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::query::Changed;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(f32);
+    #[derive(Debug, PartialEq)]
+    struct Listener(Entity);
+
+    #[test]
+    fn threads_read_while_the_owner_holds_the_view() {
+        let mut world = World::new();
+        let emitters: Vec<Entity> = (0..1000)
+            .map(|i| {
+                let e = *world.spawn_entity();
+                world.add_component(e, Position(i as f32));
+                e
+            })
+            .collect();
+        world.insert_resource(Listener(emitters[10]));
+        world.clear_trackers();
+        for e in emitters.iter().step_by(3) {
+            world.get_component_mut::<Position>(*e).unwrap().0 += 0.5;
+        }
+        let positions = world.query::<&Position>();
+        let changed = world.query_filtered::<Entity, Changed<Position>>();
+        let tick = world.change_tick();
+
+        let view = world.as_read_only();
+        let (sums, moved): (Vec<f32>, Vec<usize>) = thread::scope(|scope| {
+            let readers: Vec<_> = (0..4)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let listener = view.get_resource::<Listener>().unwrap().0;
+                        let heard = view.get_component::<Position>(listener).unwrap().0;
+                        let sum: f32 = view.iter(&positions).map(|p| p.0).sum();
+                        (sum + heard, view.iter(&changed).count())
+                    })
+                })
+                .collect();
+            // The owner reads too while the readers run.
+            assert_eq!(view.query(&positions).iter().count(), 1000);
+            readers.into_iter().map(|reader| reader.join().unwrap()).unzip()
+        });
+        assert!(sums.windows(2).all(|pair| pair[0] == pair[1]));
+        // Reading didn't consume the changes nor moved the ticks.
+        assert_eq!(moved, [334; 4]);
+        assert_eq!(changed.iter(&world).count(), 334);
+        assert_eq!(world.change_tick(), tick);
+        assert_eq!(view.get(&positions, emitters[3]), Some(&Position(3.5)));
+    }
+
+    #[derive(Component)]
+    struct Velocity(f32);
+    #[derive(Component)]
+    struct Emitter(u32);
+
+    #[test]
+    fn scoped_threads_run_read_only_queries_together() {
+        let mut world = World::new();
+        for i in 0..2000 {
+            let e = *world.spawn_entity();
+            world.add_component(e, Position(i as f32));
+            world.add_component(e, Velocity(1.0));
+            if i % 4 == 0 {
+                world.add_component(e, Emitter(i));
+            }
+        }
+        let movers = world.query::<(&mut Position, &Velocity)>();
+        let render = movers.to_readonly();
+        let audio = world.query::<(&Position, &Emitter)>();
+
+        let barrier = std::sync::Barrier::new(2);
+        let (moved, heard) = world.par_scope(|scope| {
+            let render = scope.spawn(|world| {
+                let query: ReadOnlyQuery<(&mut Position, &Velocity)> = render.query(world);
+                barrier.wait();
+                query.iter().map(|(position, velocity)| position.0 + velocity.0).sum::<f32>()
+            });
+            // Both threads are iterating at once past the barrier.
+            let audio = scope.spawn(|world| {
+                barrier.wait();
+                audio.iter(world).map(|(_, emitter)| emitter.0).max()
+            });
+            (render.join().unwrap(), audio.join().unwrap())
+        });
+        assert_eq!(moved, (0..2000).map(|i| i as f32 + 1.0).sum::<f32>());
+        assert_eq!(heard, Some(1996));
+    }
+
+    #[test]
+    #[should_panic(expected = "World::par_scope called while a resource scope holds a resource")]
+    fn par_scope_rejects_held_resources() {
+        let mut world = World::new();
+        world.insert_resource(Listener(Entity::PLACEHOLDER));
+        world.resource_scope(|world, _: crate::change_detection::Mut<Listener>| {
+            world.par_scope(|_| ());
+        });
+    }
+}
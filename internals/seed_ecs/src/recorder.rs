@@ -0,0 +1,382 @@
+//! Recording what happens to a world frame by frame, to replay it into a fresh world while
+//! chasing the bugs that only show up in a given session.
+//!
+//! A [`WorldRecorder`] compares the world with what it recorded at the previous frame and turns
+//! the differences into [`RecordedOp`]s: the spawns and despawns, and the inserts, writes and
+//! removals of the components and resources it knows. The values are encoded by the
+//! [`SceneComponent`] impls of a [`SceneRegistry`], each [`RecordedFrame`] is plain data like a
+//! [`Scene`](crate::scene::Scene), to be written out as soon as it is recorded.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::entity::{DanglingPolicy, Entity, EntityMapper};
+use crate::scene::{SceneComponent, SceneRegistry, Value};
+use crate::World;
+
+/// A change to a world, the entities are the bits of the entities of the recorded world.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RecordedOp {
+    Spawn(u64),
+    Despawn(u64),
+    /// The component was added or its value changed, by its scene name.
+    Insert { entity: u64, component: String, value: Value },
+    Remove { entity: u64, component: String },
+    /// The resource was inserted or its value changed.
+    SetResource { name: String, value: Value },
+    RemoveResource { name: String },
+}
+
+/// The changes of a frame, despawns first, then spawns, then the components and the resources.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct RecordedFrame {
+    /// Counted from zero, the first frame recorded.
+    pub frame: u64,
+    pub ops: Vec<RecordedOp>,
+}
+
+type SaveResourceFn = fn(&World) -> Option<Value>;
+// Returns false if the value was rejected.
+type LoadResourceFn = fn(&mut World, &Value) -> bool;
+type RemoveResourceFn = fn(&mut World);
+
+struct RecordedResource {
+    name: String,
+    save: SaveResourceFn,
+    load: LoadResourceFn,
+    remove: RemoveResourceFn,
+    // The value at the last frame.
+    last: Option<Value>,
+}
+
+fn save_resource<T: SceneComponent>(world: &World) -> Option<Value> {
+    world.get_resource::<T>().map(T::to_value)
+}
+
+fn load_resource<T: SceneComponent>(world: &mut World, value: &Value) -> bool {
+    T::from_value(value).map(|resource| world.insert_resource(resource)).is_some()
+}
+
+fn remove_resource<T: SceneComponent>(world: &mut World) {
+    world.remove_resource::<T>();
+}
+
+/// Records the changes of a world frame by frame, see the [module docs](self).
+///
+/// Each frame encodes every recorded component, recording is meant for debugging sessions rather
+/// than for shipping.
+///
+/// ```
+/// # use seed_ecs::prelude::*;
+/// # use seed_ecs::recorder::WorldRecorder;
+/// # use seed_ecs::scene::{SceneComponent, SceneRegistry, Value};
+/// #[derive(Component)]
+/// struct Health(i64);
+///
+/// impl SceneComponent for Health {
+///     fn to_value(&self) -> Value {
+///         Value::Int(self.0)
+///     }
+///
+///     fn from_value(value: &Value) -> Option<Self> {
+///         value.as_int().map(Health)
+///     }
+/// }
+///
+/// let mut registry = SceneRegistry::new();
+/// registry.register_named::<Health>("health", 1);
+/// let mut recorder = WorldRecorder::new(registry);
+/// let mut world = World::new();
+/// let mut frames = Vec::new();
+/// let e = *world.spawn_entity();
+/// world.add_component(e, Health(10));
+/// frames.push(recorder.record_frame(&world));
+/// world.get_component_mut::<Health>(e).unwrap().0 -= 4;
+/// frames.push(recorder.record_frame(&world));
+///
+/// let mut replayed = World::new();
+/// assert_eq!(recorder.replay(&frames, &mut replayed), 0);
+/// let health = replayed.query::<&Health>();
+/// assert_eq!(health.iter(&replayed).map(|health| health.0).collect::<Vec<_>>(), [6]);
+/// ```
+pub struct WorldRecorder {
+    registry: SceneRegistry,
+    resources: Vec<RecordedResource>,
+    frame: u64,
+    // The entities alive at the last frame, and the values of each registered type then.
+    alive: BTreeSet<Entity>,
+    values: Vec<BTreeMap<Entity, Value>>,
+    skipped: BTreeSet<&'static str>,
+}
+
+impl WorldRecorder {
+    /// Records the components registered in `registry`, under their scene names.
+    pub fn new(registry: SceneRegistry) -> Self {
+        Self {
+            registry,
+            resources: Vec::new(),
+            frame: 0,
+            alive: BTreeSet::new(),
+            values: Vec::new(),
+            skipped: BTreeSet::new(),
+        }
+    }
+
+    pub fn registry(&self) -> &SceneRegistry {
+        &self.registry
+    }
+
+    /// Records the resource `T` as `name` too.
+    pub fn record_resource<T: SceneComponent>(&mut self, name: &str) -> &mut Self {
+        self.resources.push(RecordedResource {
+            name: name.to_string(),
+            save: save_resource::<T>,
+            load: load_resource::<T>,
+            remove: remove_resource::<T>,
+            last: None,
+        });
+        self
+    }
+
+    /// The changes since the previous call, everything the world holds on the first call.
+    pub fn record_frame(&mut self, world: &World) -> RecordedFrame {
+        let mut ops = Vec::new();
+        let alive: BTreeSet<Entity> = world.entities.iter().collect();
+        ops.extend(self.alive.difference(&alive).map(|gone| RecordedOp::Despawn(gone.to_bits())));
+        ops.extend(alive.difference(&self.alive).map(|new| RecordedOp::Spawn(new.to_bits())));
+
+        let types = self.registry.save_by_type(world);
+        self.values.resize_with(types.len(), BTreeMap::new);
+        for ((name, values), last) in types.into_iter().zip(&mut self.values) {
+            let values: BTreeMap<Entity, Value> = values.into_iter().collect();
+            // The components of the despawned entities went with them.
+            let removed = last.keys().filter(|e| alive.contains(e) && !values.contains_key(e));
+            ops.extend(removed.map(|e| RecordedOp::Remove {
+                entity: e.to_bits(),
+                component: name.to_string(),
+            }));
+            let written = values.iter().filter(|(e, value)| last.get(e) != Some(value));
+            ops.extend(written.map(|(e, value)| RecordedOp::Insert {
+                entity: e.to_bits(),
+                component: name.to_string(),
+                value: value.clone(),
+            }));
+            *last = values;
+        }
+        for resource in &mut self.resources {
+            let value = (resource.save)(world);
+            if value == resource.last {
+                continue;
+            }
+            let name = resource.name.clone();
+            ops.push(match &value {
+                Some(value) => RecordedOp::SetResource { name, value: value.clone() },
+                None => RecordedOp::RemoveResource { name },
+            });
+            resource.last = value;
+        }
+
+        for info in world.components.iter() {
+            let registered = info.type_id().is_some_and(|type_id| self.registry.is_registered(type_id));
+            if !registered && world.storages.get(info.id()).stats().live > 0 {
+                self.skipped.insert(info.name());
+            }
+        }
+        self.alive = alive;
+        self.frame += 1;
+        RecordedFrame { frame: self.frame - 1, ops }
+    }
+
+    /// The components seen on entities that couldn't be recorded, not being in the registry,
+    /// sorted by name. Replays miss them.
+    pub fn warnings(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.skipped.iter().copied()
+    }
+
+    /// Applies the frames to `world` in order, spawning an entity for each one recorded. The
+    /// frames must start from the first one recorded, for the entities to be known.
+    ///
+    /// Returns how many ops couldn't be applied: the components and resources of no recorded
+    /// name, the values their [`SceneComponent::from_value`] rejected and the entities never
+    /// spawned.
+    pub fn replay(&self, frames: &[RecordedFrame], world: &mut World) -> usize {
+        let mut mapper = EntityMapper::new(DanglingPolicy::MapToDead);
+        let mut skipped = 0;
+        let resource = |name: &str| self.resources.iter().find(|resource| resource.name == name);
+        for op in frames.iter().flat_map(|frame| &frame.ops) {
+            let applied = match op {
+                RecordedOp::Spawn(bits) => {
+                    let spawned = *world.spawn_entity();
+                    Entity::from_bits(*bits).map(|e| mapper.insert(e, spawned)).is_some()
+                }
+                RecordedOp::Despawn(bits) => Entity::from_bits(*bits)
+                    .and_then(|e| mapper.remove(e))
+                    .is_some_and(|e| world.despawn_entity(e)),
+                RecordedOp::Insert { entity, component, value } => {
+                    let target = Entity::from_bits(*entity).and_then(|e| mapper.get(e));
+                    target.is_some_and(|e| self.registry.load_one(component, world, e, value, &mut mapper))
+                }
+                RecordedOp::Remove { entity, component } => {
+                    let target = Entity::from_bits(*entity).and_then(|e| mapper.get(e));
+                    target.is_some_and(|e| self.registry.remove_one(component, world, e))
+                }
+                RecordedOp::SetResource { name, value } => resource(name).is_some_and(|r| (r.load)(world, value)),
+                RecordedOp::RemoveResource { name } => resource(name).map(|r| (r.remove)(world)).is_some(),
+            };
+            skipped += usize::from(!applied);
+        }
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::scene::SceneRegistry;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(f64, f64);
+    #[derive(Debug, PartialEq, Component)]
+    struct Health(i64);
+    #[derive(Debug, PartialEq, Component)]
+    struct Score(i64);
+    // Not in the registry.
+    #[derive(Component)]
+    struct Particles(u32);
+
+    impl SceneComponent for Position {
+        fn to_value(&self) -> Value {
+            Value::Seq(vec![Value::Float(self.0), Value::Float(self.1)])
+        }
+
+        fn from_value(value: &Value) -> Option<Self> {
+            match value {
+                Value::Seq(xy) => Some(Position(xy.first()?.as_float()?, xy.get(1)?.as_float()?)),
+                _ => None,
+            }
+        }
+    }
+
+    impl SceneComponent for Health {
+        fn to_value(&self) -> Value {
+            Value::Int(self.0)
+        }
+
+        fn from_value(value: &Value) -> Option<Self> {
+            value.as_int().map(Health)
+        }
+    }
+
+    impl SceneComponent for Score {
+        fn to_value(&self) -> Value {
+            Value::Int(self.0)
+        }
+
+        fn from_value(value: &Value) -> Option<Self> {
+            value.as_int().map(Score)
+        }
+    }
+
+    fn recorder() -> WorldRecorder {
+        let mut registry = SceneRegistry::new();
+        registry.register_named::<Position>("position", 1).register_named::<Health>("health", 1);
+        let mut recorder = WorldRecorder::new(registry);
+        recorder.record_resource::<Score>("score");
+        recorder
+    }
+
+    // The components of each entity and the score, regardless of the entity ids.
+    fn checksum(world: &mut World) -> (Vec<String>, Option<i64>) {
+        let query = world.query::<(Option<&Position>, Option<&Health>)>();
+        let mut entities: Vec<String> = query.iter(world).map(|item| format!("{:?}", item)).collect();
+        entities.sort();
+        (entities, world.get_resource::<Score>().map(|score| score.0))
+    }
+
+    #[test]
+    fn a_scripted_session_replays_identically() {
+        let mut world = World::new();
+        let mut recorder = recorder();
+        let mut frames = Vec::new();
+        let mut units = Vec::new();
+        for frame in 0..12i64 {
+            let e = *world.spawn_entity();
+            world.add_component(e, Position(frame as f64, 0.0));
+            if frame % 3 == 0 {
+                world.add_component(e, Health(100));
+            }
+            units.push(e);
+            for (i, unit) in units.iter().enumerate() {
+                if let Some(mut position) = world.get_component_mut::<Position>(*unit) {
+                    position.1 += i as f64;
+                }
+            }
+            match frame % 4 {
+                0 => {
+                    world.insert_resource(Score(frame));
+                }
+                1 => {
+                    world.despawn_entity(units.remove(0));
+                }
+                2 => {
+                    world.remove_component::<Position>(units[0]);
+                }
+                _ => {
+                    if let Some(mut score) = world.get_resource_mut::<Score>() {
+                        score.0 += 5;
+                    }
+                }
+            }
+            if frame == 6 {
+                world.remove_resource::<Score>();
+            }
+            frames.push(recorder.record_frame(&world));
+        }
+        assert_eq!(frames.iter().map(|frame| frame.frame).collect::<Vec<_>>(), (0..12).collect::<Vec<_>>());
+        // Nothing changed since.
+        assert!(recorder.record_frame(&world).ops.is_empty());
+
+        let mut replayed = World::new();
+        assert_eq!(recorder.replay(&frames, &mut replayed), 0);
+        assert_eq!(replayed.enities().len(), world.enities().len());
+        assert_eq!(checksum(&mut replayed), checksum(&mut world));
+        // Replaying up to a frame gives the world of that frame.
+        let mut halfway = World::new();
+        recorder.replay(&frames[..6], &mut halfway);
+        assert_eq!(halfway.get_resource::<Score>(), Some(&Score(4)));
+    }
+
+    #[test]
+    fn unregistered_components_are_warned_about() {
+        let mut world = World::new();
+        let mut recorder = recorder();
+        let e = *world.spawn_entity();
+        world.add_component(e, Particles(64));
+        world.add_component(e, Health(3));
+        let frame = recorder.record_frame(&world);
+        assert_eq!(recorder.warnings().collect::<Vec<_>>(), [core::any::type_name::<Particles>()]);
+        assert_eq!(
+            frame.ops,
+            [
+                RecordedOp::Spawn(e.to_bits()),
+                RecordedOp::Insert {
+                    entity: e.to_bits(),
+                    component: "health".to_string(),
+                    value: Value::Int(3),
+                },
+            ]
+        );
+
+        let mut replayed = World::new();
+        let unknown = RecordedOp::Insert {
+            entity: e.to_bits(),
+            component: "particles".to_string(),
+            value: Value::Int(64),
+        };
+        let frames = [frame, RecordedFrame { frame: 1, ops: vec![unknown] }];
+        assert_eq!(recorder.replay(&frames, &mut replayed), 1);
+        assert_eq!(checksum(&mut replayed).0, ["(None, Some(Health(3)))"]);
+    }
+}
@@ -0,0 +1,303 @@
+//! Access to the fields of components by name, for consoles and property editors.
+
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use core::any::{type_name, Any};
+use core::error::Error;
+use core::fmt;
+
+use crate::component::{Component, ComponentId, ReflectFns};
+use crate::entity::Entity;
+use crate::storage::{AnyStorage, Storage};
+use crate::World;
+
+/// Components whose fields can be read and written by name.
+///
+/// ```
+/// # use core::any::Any;
+/// # use seed_ecs::reflect::{set_value, Reflect, ReflectError};
+/// struct Position {
+///     x: f32,
+/// }
+///
+/// impl Reflect for Position {
+///     fn field_names(&self) -> &'static [&'static str] {
+///         &["x"]
+///     }
+///
+///     fn field(&self, name: &str) -> Option<&dyn Any> {
+///         match name {
+///             "x" => Some(&self.x),
+///             _ => None,
+///         }
+///     }
+///
+///     fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any> {
+///         match name {
+///             "x" => Some(&mut self.x),
+///             _ => None,
+///         }
+///     }
+///
+///     fn set_field(&mut self, name: &str, value: Box<dyn Any>) -> Result<(), ReflectError> {
+///         match name {
+///             "x" => set_value(&mut self.x, name, value),
+///             _ => Err(ReflectError::UnknownField(name.to_string())),
+///         }
+///     }
+/// }
+/// ```
+pub trait Reflect: Any + Send + Sync {
+    fn field_names(&self) -> &'static [&'static str];
+
+    fn field(&self, name: &str) -> Option<&dyn Any>;
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any>;
+
+    /// Replaces the value of a field, see [`set_value`].
+    fn set_field(&mut self, name: &str, value: Box<dyn Any>) -> Result<(), ReflectError>;
+}
+
+/// Moves `value` into `slot` if it holds a `T`, used to implement [`Reflect::set_field`].
+pub fn set_value<T: Any>(slot: &mut T, field: &str, value: Box<dyn Any>) -> Result<(), ReflectError> {
+    match value.downcast::<T>() {
+        Ok(value) => {
+            *slot = *value;
+            Ok(())
+        }
+        Err(_) => Err(ReflectError::WrongType {
+            field: field.to_string(),
+            expected: type_name::<T>(),
+        }),
+    }
+}
+
+/// Why a field could not be accessed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReflectError {
+    NotAlive(Entity),
+    /// No component type has that name.
+    UnknownComponent(String),
+    /// The component never went through [`World::register_reflect`].
+    NotReflect(&'static str),
+    /// The entity doesn't have the component.
+    MissingComponent(&'static str),
+    UnknownField(String),
+    WrongType { field: String, expected: &'static str },
+}
+
+impl fmt::Display for ReflectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotAlive(entity) => write!(f, "Entity {:?} is not alive", entity),
+            Self::UnknownComponent(name) => write!(f, "Unknown component {}", name),
+            Self::NotReflect(name) => write!(f, "Component {} is not registered for reflection", name),
+            Self::MissingComponent(name) => write!(f, "Entity has no component {}", name),
+            Self::UnknownField(name) => write!(f, "Unknown field {}", name),
+            Self::WrongType { field, expected } => {
+                write!(f, "Field {} expects a value of type {}", field, expected)
+            }
+        }
+    }
+}
+
+impl Error for ReflectError {}
+
+fn reflect_ref<T: Reflect>(storage: &dyn AnyStorage, index: usize) -> Option<&dyn Reflect> {
+    let storage = storage.as_any().downcast_ref::<Storage<T>>()?;
+    storage.get(index).map(|value| value as &dyn Reflect)
+}
+
+fn reflect_mut<T: Reflect>(storage: &mut dyn AnyStorage, index: usize) -> Option<&mut dyn Reflect> {
+    let storage = storage.as_any_mut().downcast_mut::<Storage<T>>()?;
+    storage.get_mut(index).map(|value| value as &mut dyn Reflect)
+}
+
+impl World {
+    /// Lets the fields of `T` be accessed by name.
+    pub fn register_reflect<T: Component + Reflect>(&mut self) {
+        let id = self.register_component::<T>();
+        let fns = ReflectFns {
+            get: reflect_ref::<T>,
+            get_mut: reflect_mut::<T>,
+        };
+        self.components.set_reflect(id, fns);
+    }
+
+    /// Finds a reflected component by its full or short type name.
+    fn reflect_lookup(
+        &self,
+        entity: Entity,
+        component: &str,
+    ) -> Result<(ComponentId, ReflectFns), ReflectError> {
+        if !self.is_alive(entity) {
+            return Err(ReflectError::NotAlive(entity));
+        }
+        let id = self
+            .components
+            .get_id_by_name(component)
+            .ok_or_else(|| ReflectError::UnknownComponent(component.to_string()))?;
+        let info = self.components.info(id).unwrap();
+        let fns = info.reflect_fns().ok_or(ReflectError::NotReflect(info.name()))?;
+        if !self.storages.get(id).contains(entity.index() as usize) {
+            return Err(ReflectError::MissingComponent(info.name()));
+        }
+        Ok((id, fns))
+    }
+
+    pub fn get_reflect(&self, entity: Entity, component: &str) -> Result<&dyn Reflect, ReflectError> {
+        let (id, fns) = self.reflect_lookup(entity, component)?;
+        Ok((fns.get)(self.storages.get(id), entity.index() as usize).unwrap())
+    }
+
+    pub fn get_reflect_mut(
+        &mut self,
+        entity: Entity,
+        component: &str,
+    ) -> Result<&mut dyn Reflect, ReflectError> {
+        let (id, fns) = self.reflect_lookup(entity, component)?;
+        Ok((fns.get_mut)(self.storages.get_mut(id), entity.index() as usize).unwrap())
+    }
+
+    /// Reads a field, `world.get_component_field(e, "Position", "x")`.
+    pub fn get_component_field(
+        &self,
+        entity: Entity,
+        component: &str,
+        field: &str,
+    ) -> Result<&dyn Any, ReflectError> {
+        let value = self.get_reflect(entity, component)?;
+        value.field(field).ok_or_else(|| ReflectError::UnknownField(field.to_string()))
+    }
+
+    pub fn get_component_field_mut(
+        &mut self,
+        entity: Entity,
+        component: &str,
+        field: &str,
+    ) -> Result<&mut dyn Any, ReflectError> {
+        let value = self.get_reflect_mut(entity, component)?;
+        value.field_mut(field).ok_or_else(|| ReflectError::UnknownField(field.to_string()))
+    }
+
+    /// Reads a field and downcasts it to `T`.
+    pub fn get_component_field_as<T: Any>(
+        &self,
+        entity: Entity,
+        component: &str,
+        field: &str,
+    ) -> Result<&T, ReflectError> {
+        let value = self.get_component_field(entity, component, field)?;
+        value.downcast_ref().ok_or_else(|| ReflectError::WrongType {
+            field: field.to_string(),
+            expected: type_name::<T>(),
+        })
+    }
+
+    pub fn set_component_field(
+        &mut self,
+        entity: Entity,
+        component: &str,
+        field: &str,
+        value: Box<dyn Any>,
+    ) -> Result<(), ReflectError> {
+        self.get_reflect_mut(entity, component)?.set_field(field, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+
+    impl Reflect for Position {
+        fn field_names(&self) -> &'static [&'static str] {
+            &["x", "y"]
+        }
+
+        fn field(&self, name: &str) -> Option<&dyn Any> {
+            match name {
+                "x" => Some(&self.x),
+                "y" => Some(&self.y),
+                _ => None,
+            }
+        }
+
+        fn field_mut(&mut self, name: &str) -> Option<&mut dyn Any> {
+            match name {
+                "x" => Some(&mut self.x),
+                "y" => Some(&mut self.y),
+                _ => None,
+            }
+        }
+
+        fn set_field(&mut self, name: &str, value: Box<dyn Any>) -> Result<(), ReflectError> {
+            match name {
+                "x" => set_value(&mut self.x, name, value),
+                "y" => set_value(&mut self.y, name, value),
+                _ => Err(ReflectError::UnknownField(name.to_string())),
+            }
+        }
+    }
+
+    #[derive(Component)]
+    struct Velocity(f32);
+
+    #[test]
+    fn fields_by_name() {
+        let mut world = World::new();
+        world.register_reflect::<Position>();
+        let e = *world.spawn_entity();
+        world.add_component(e, Position { x: 1.0, y: 2.0 });
+        world.add_component(e, Velocity(3.0));
+
+        assert_eq!(world.get_component_field_as::<f32>(e, "Position", "x"), Ok(&1.0));
+        world.set_component_field(e, "Position", "x", Box::new(5.0f32)).unwrap();
+        let y = world.get_component_field_mut(e, "Position", "y").unwrap();
+        *y.downcast_mut::<f32>().unwrap() += 1.0;
+        assert_eq!(world.get_component(e), Some(&Position { x: 5.0, y: 3.0 }));
+        assert_eq!(world.get_reflect(e, type_name::<Position>()).unwrap().field_names(), &["x", "y"]);
+
+        let wrong_type = |expected| ReflectError::WrongType {
+            field: "x".to_string(),
+            expected,
+        };
+        let result = world.set_component_field(e, "Position", "x", Box::new(5.0f64));
+        assert_eq!(result, Err(wrong_type(type_name::<f32>())));
+        let result = world.get_component_field_as::<u32>(e, "Position", "x");
+        assert_eq!(result.err(), Some(wrong_type(type_name::<u32>())));
+        assert_eq!(
+            world.get_component_field(e, "Position", "z").err(),
+            Some(ReflectError::UnknownField("z".to_string()))
+        );
+    }
+
+    #[test]
+    fn component_errors() {
+        let mut world = World::new();
+        world.register_reflect::<Position>();
+        let e = *world.spawn_entity();
+        world.add_component(e, Velocity(3.0));
+
+        assert_eq!(
+            world.get_component_field(e, "Velocity", "0").err(),
+            Some(ReflectError::NotReflect(type_name::<Velocity>()))
+        );
+        assert_eq!(
+            world.get_component_field(e, "Position", "x").err(),
+            Some(ReflectError::MissingComponent(type_name::<Position>()))
+        );
+        assert_eq!(
+            world.get_component_field(e, "Rotation", "x").err(),
+            Some(ReflectError::UnknownComponent("Rotation".to_string()))
+        );
+        world.despawn_entity(e);
+        let result = world.get_component_field(e, "Position", "x");
+        assert_eq!(result.err(), Some(ReflectError::NotAlive(e)));
+    }
+}
@@ -0,0 +1,234 @@
+//! Typed links between entities, like `Targets` or `MemberOf`, that can be many-to-many.
+
+use alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::mem;
+
+use crate::entity::{Entity, EntityMapper, MapEntities};
+use crate::utils::{SparseMap, TypeIdMap};
+use crate::{EcsError, World};
+
+/// A kind of link from a subject entity to object entities, implemented by marker types.
+///
+/// ```
+/// # use seed_ecs::relation::Relation;
+/// # use seed_ecs::World;
+/// struct Targets;
+/// impl Relation for Targets {}
+///
+/// let mut world = World::new();
+/// let turret = *world.spawn_entity();
+/// let enemy = *world.spawn_entity();
+/// world.relate::<Targets>(turret, enemy);
+/// assert_eq!(world.related::<Targets>(turret).collect::<Vec<_>>(), [enemy]);
+/// assert_eq!(world.relating_to::<Targets>(enemy).collect::<Vec<_>>(), [turret]);
+/// ```
+pub trait Relation: Send + Sync + 'static {}
+
+// The links of one relation type, stored both ways so that either side can be looked up.
+struct RelationStorage {
+    name: &'static str,
+    objects: SparseMap<Entity, Vec<Entity>>,
+    subjects: SparseMap<Entity, Vec<Entity>>,
+}
+
+fn link(links: &mut SparseMap<Entity, Vec<Entity>>, from: Entity, to: Entity) {
+    match links.get_mut(from) {
+        Some(targets) => targets.push(to),
+        None => drop(links.insert(from, vec![to])),
+    }
+}
+
+fn unlink(links: &mut SparseMap<Entity, Vec<Entity>>, from: Entity, to: Entity) -> bool {
+    let Some(targets) = links.get_mut(from) else {
+        return false;
+    };
+    let Some(position) = targets.iter().position(|e| *e == to) else {
+        return false;
+    };
+    targets.remove(position);
+    if targets.is_empty() {
+        links.remove(from);
+    }
+    true
+}
+
+impl RelationStorage {
+    fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            objects: SparseMap::new(),
+            subjects: SparseMap::new(),
+        }
+    }
+
+    // Removes every link of the entity, whichever side it is on.
+    fn forget(&mut self, entity: Entity) {
+        for object in self.objects.remove(entity).unwrap_or_default() {
+            unlink(&mut self.subjects, object, entity);
+        }
+        for subject in self.subjects.remove(entity).unwrap_or_default() {
+            unlink(&mut self.objects, subject, entity);
+        }
+    }
+}
+
+/// The links of every relation type of a world.
+#[derive(Default)]
+pub(crate) struct Relations {
+    storages: TypeIdMap<RelationStorage>,
+}
+
+impl Relations {
+    pub fn forget(&mut self, entity: Entity) {
+        for storage in self.storages.values_mut() {
+            storage.forget(entity);
+        }
+    }
+
+    /// Rewrites both sides of the links.
+    pub fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        for storage in self.storages.values_mut() {
+            for links in [&mut storage.objects, &mut storage.subjects] {
+                let mut mapped = SparseMap::new();
+                for (mut from, to) in links.iter_mut() {
+                    from.map_entities(mapper);
+                    to.map_entities(mapper);
+                    mapped.insert(from, mem::take(to));
+                }
+                *links = mapped;
+            }
+        }
+    }
+
+    /// Every entity on either side of a link, with the name of the relation type.
+    pub fn linked(&self) -> impl Iterator<Item = (&'static str, Entity)> + '_ {
+        self.storages.values().flat_map(|storage| {
+            let objects = storage.objects.iter().flat_map(|(subject, objects)| {
+                core::iter::once(subject).chain(objects.iter().copied())
+            });
+            objects.map(move |entity| (storage.name, entity))
+        })
+    }
+
+    fn get<R: Relation>(&self) -> Option<&RelationStorage> {
+        self.storages.get(&TypeId::of::<R>())
+    }
+}
+
+impl World {
+    /// Links `subject` to `object` with `R`, returns false if they were already linked.
+    ///
+    /// Links are removed when either entity is despawned.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the entities is not alive.
+    pub fn relate<R: Relation>(&mut self, subject: Entity, object: Entity) -> bool {
+        self.try_relate::<R>(subject, object).unwrap_or_else(|error| panic!("{}", error))
+    }
+
+    /// Same as [`World::relate`] but fails instead of panicking.
+    pub fn try_relate<R: Relation>(&mut self, subject: Entity, object: Entity) -> Result<bool, EcsError> {
+        for entity in [subject, object] {
+            self.entities.check_alive(entity)?;
+        }
+        let storage = self.relations.storages.entry(TypeId::of::<R>()).or_insert_with(|| RelationStorage::new(type_name::<R>()));
+        if storage.objects.get(subject).is_some_and(|objects| objects.contains(&object)) {
+            return Ok(false);
+        }
+        link(&mut storage.objects, subject, object);
+        link(&mut storage.subjects, object, subject);
+        Ok(true)
+    }
+
+    /// Removes the `R` link from `subject` to `object`, returns false if there was none.
+    pub fn unrelate<R: Relation>(&mut self, subject: Entity, object: Entity) -> bool {
+        let Some(storage) = self.relations.storages.get_mut(&TypeId::of::<R>()) else {
+            return false;
+        };
+        unlink(&mut storage.subjects, object, subject);
+        unlink(&mut storage.objects, subject, object)
+    }
+
+    pub fn is_related<R: Relation>(&self, subject: Entity, object: Entity) -> bool {
+        self.related::<R>(subject).any(|e| e == object)
+    }
+
+    /// The objects `subject` is linked to with `R`, in the order they were related.
+    pub fn related<R: Relation>(&self, subject: Entity) -> impl Iterator<Item = Entity> + '_ {
+        let objects = self.relations.get::<R>().and_then(|storage| storage.objects.get(subject));
+        objects.into_iter().flatten().copied()
+    }
+
+    /// The subjects linked to `object` with `R`, in the order they were related.
+    pub fn relating_to<R: Relation>(&self, object: Entity) -> impl Iterator<Item = Entity> + '_ {
+        let subjects = self.relations.get::<R>().and_then(|storage| storage.subjects.get(object));
+        subjects.into_iter().flatten().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct MemberOf;
+    impl Relation for MemberOf {}
+    struct Targets;
+    impl Relation for Targets {}
+
+    fn related<R: Relation>(world: &World, subject: Entity) -> Vec<Entity> {
+        world.related::<R>(subject).collect()
+    }
+
+    fn relating_to<R: Relation>(world: &World, object: Entity) -> Vec<Entity> {
+        world.relating_to::<R>(object).collect()
+    }
+
+    #[test]
+    fn many_to_many() {
+        let mut world = World::new();
+        let [alice, bob, red, blue] = [(); 4].map(|_| *world.spawn_entity());
+        assert!(world.relate::<MemberOf>(alice, red));
+        assert!(world.relate::<MemberOf>(alice, blue));
+        assert!(world.relate::<MemberOf>(bob, red));
+        assert!(!world.relate::<MemberOf>(bob, red));
+        world.relate::<Targets>(bob, alice);
+
+        assert_eq!(related::<MemberOf>(&world, alice), [red, blue]);
+        assert_eq!(relating_to::<MemberOf>(&world, red), [alice, bob]);
+        assert_eq!(relating_to::<MemberOf>(&world, blue), [alice]);
+        assert_eq!(related::<Targets>(&world, bob), [alice]);
+        assert_eq!(relating_to::<Targets>(&world, red), []);
+        assert!(world.is_related::<Targets>(bob, alice) && !world.is_related::<Targets>(alice, bob));
+
+        assert!(world.unrelate::<MemberOf>(alice, red));
+        assert!(!world.unrelate::<MemberOf>(alice, red));
+        assert_eq!(relating_to::<MemberOf>(&world, red), [bob]);
+        assert_eq!(related::<MemberOf>(&world, alice), [blue]);
+    }
+
+    #[test]
+    fn despawn_cleans_both_directions() {
+        let mut world = World::new();
+        let [alice, bob, red] = [(); 3].map(|_| *world.spawn_entity());
+        world.relate::<MemberOf>(alice, red);
+        world.relate::<MemberOf>(bob, red);
+        world.relate::<Targets>(red, alice);
+
+        world.despawn_entity(red);
+        assert_eq!(related::<MemberOf>(&world, alice), []);
+        assert_eq!(related::<MemberOf>(&world, bob), []);
+        assert_eq!(relating_to::<Targets>(&world, alice), []);
+
+        // A new entity reusing the index starts without links.
+        let reused = *world.spawn_entity();
+        assert_eq!(reused.index(), red.index());
+        assert_eq!(relating_to::<MemberOf>(&world, reused), []);
+        world.relate::<MemberOf>(alice, reused);
+        world.despawn_entity(alice);
+        assert_eq!(relating_to::<MemberOf>(&world, reused), []);
+        assert!(world.relations.storages.values().all(|s| s.objects.is_empty() && s.subjects.is_empty()));
+        world.validate().unwrap();
+    }
+}
@@ -0,0 +1,298 @@
+//! Callbacks told where the values of a component sit in their storage, for the renderers that
+//! mirror them in buffers indexed by the same slots.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+
+use core::mem;
+
+use crate::component::{Component, ComponentId, RemovalPolicy, StorageKind};
+use crate::entity::Entity;
+use crate::storage::SlotEvent;
+use crate::utils::HashMap;
+use crate::World;
+
+/// A value that took, changed or left its slot in the storage of its component, see
+/// [`World::on_storage_relocate`].
+///
+/// The slot is the position in the storage of the grouped and sparse set components, and the
+/// entity index for the others, whose values never move. Exchanging two values is two moves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StorageRelocation {
+    Inserted { entity: Entity, slot: usize },
+    Moved { entity: Entity, from: usize, to: usize },
+    Removed { entity: Entity, slot: usize },
+}
+
+type RelocateFn = Box<dyn FnMut(StorageRelocation) + Send + Sync>;
+
+#[derive(Default)]
+pub(crate) struct Relocations {
+    hooks: Vec<(ComponentId, RelocateFn)>,
+}
+
+impl World {
+    /// Calls `callback` for every value of `T` inserted, moved or removed in its storage, at the
+    /// end of the world operation that did it. The values already stored are reported as inserted
+    /// right away. Registering again replaces the callback.
+    ///
+    /// Replacing a value keeps its slot and is not reported, the change detection tells when to
+    /// upload it again.
+    pub fn on_storage_relocate<T: Component>(
+        &mut self,
+        mut callback: impl FnMut(StorageRelocation) + Send + Sync + 'static,
+    ) {
+        let id = self.register_component::<T>();
+        let existing = self.storages.typed_mut::<T>(id).record_slots();
+        for (index, slot) in existing {
+            let entity = self.entities.get(index as u32).unwrap();
+            callback(StorageRelocation::Inserted { entity, slot });
+        }
+        let hooks = &mut self.relocations.hooks;
+        hooks.retain(|(other, _)| *other != id);
+        hooks.push((id, Box::new(callback)));
+    }
+
+    /// Moves the values of `T` to a sparse set storage removing them with `policy`, and returns
+    /// the id of `T`. With [`RemovalPolicy::Tombstone`] the slots never change until
+    /// [`World::compact`], which reports each value it moves to the callbacks of
+    /// [`World::on_storage_relocate`]. The values already stored are reported as moved.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero sized or interned, or if the policy is a tombstone and `T` is part of
+    /// a group, see [`World::register_group`].
+    pub fn register_sparse<T: Component>(&mut self, policy: RemovalPolicy) -> ComponentId {
+        assert_ne!(mem::size_of::<T>(), 0, "Zero sized components can't be stored in sparse sets");
+        let id = self.register_component::<T>();
+        if policy == RemovalPolicy::Tombstone {
+            let name = self.components.info(id).unwrap().name();
+            assert!(self.groups.of(id).is_none(), "Component {} is grouped, its values can't leave holes", name);
+        }
+        let storage = self.storages.typed_mut::<T>(id);
+        match policy {
+            RemovalPolicy::SwapRemove => storage.make_sparse(),
+            RemovalPolicy::Tombstone => storage.make_stable(),
+        }
+        self.components.set_storage(id, StorageKind::SparseSet);
+        self.flush_relocations(&[]);
+        id
+    }
+
+    /// The slot of the entity's `T` in its storage, see [`StorageRelocation`].
+    pub fn component_slot<T: Component>(&self, entity: Entity) -> Option<usize> {
+        let id = self.components.id::<T>()?;
+        let index = entity.index() as usize;
+        if !self.is_alive(entity) || !self.storages.get(id).contains(index) {
+            return None;
+        }
+        Some(self.storages.get(id).position(index).unwrap_or(index))
+    }
+
+    /// Runs the callbacks on the slot events recorded so far. `despawned` are the entities
+    /// despawned by the operation, whose indices don't resolve anymore.
+    pub(crate) fn flush_relocations(&mut self, despawned: &[Entity]) {
+        if self.relocations.hooks.is_empty() {
+            return;
+        }
+        let despawned: HashMap<usize, Entity> = despawned.iter().map(|e| (e.index() as usize, *e)).collect();
+        for (id, callback) in &mut self.relocations.hooks {
+            let Some(events) = self.storages.get_mut(*id).slot_events() else {
+                continue;
+            };
+            let entities = &self.entities;
+            let entity = |index: usize| entities.get(index as u32).or(despawned.get(&index).copied()).unwrap();
+            for event in events.drain(..) {
+                callback(match event {
+                    SlotEvent::Inserted { index, slot } => StorageRelocation::Inserted {
+                        entity: entity(index),
+                        slot,
+                    },
+                    SlotEvent::Moved { index, from, to } => StorageRelocation::Moved {
+                        entity: entity(index),
+                        from,
+                        to,
+                    },
+                    SlotEvent::Removed { index, slot } => StorageRelocation::Removed {
+                        entity: entity(index),
+                        slot,
+                    },
+                });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::testing::Rng;
+    use crate::utils::HashMap;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct MeshInstance(u32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Transform(u32);
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Health(u32);
+
+    // Where the renderer believes each value sits, checked against every event it gets.
+    type Mirror = Arc<Mutex<HashMap<Entity, usize>>>;
+
+    fn mirror<T: Component>(world: &mut World) -> Mirror {
+        let mirror = Mirror::default();
+        let slots = mirror.clone();
+        world.on_storage_relocate::<T>(move |relocation| {
+            let mut slots = slots.lock().unwrap();
+            match relocation {
+                StorageRelocation::Inserted { entity, slot } => assert_eq!(slots.insert(entity, slot), None),
+                StorageRelocation::Moved { entity, from, to } => {
+                    assert_ne!(from, to);
+                    assert_eq!(slots.insert(entity, to), Some(from), "{:?} moved twice", entity);
+                }
+                StorageRelocation::Removed { entity, slot } => assert_eq!(slots.remove(&entity), Some(slot)),
+            }
+        });
+        mirror
+    }
+
+    fn assert_mirrored<T: Component>(world: &World, mirror: &Mirror) {
+        let slots = mirror.lock().unwrap();
+        let mut count = 0;
+        for entity in world.enities().iter().filter(|entity| world.has_component::<T>(*entity)) {
+            assert_eq!(slots.get(&entity).copied(), world.component_slot::<T>(entity), "{:?}", entity);
+            count += 1;
+        }
+        assert_eq!(slots.len(), count);
+    }
+
+    #[test]
+    fn every_move_of_a_grouped_storage_is_reported_once() {
+        let mut world = World::new();
+        let mut entities: Vec<Entity> = world.spawn_batch((0..40).map(MeshInstance));
+        let mirror = mirror::<MeshInstance>(&mut world);
+        assert_mirrored::<MeshInstance>(&world, &mirror);
+        for e in entities.iter().step_by(2) {
+            world.add_component(*e, Transform(0));
+        }
+        // The values move to a sparse set and the members to its front.
+        world.register_group::<(MeshInstance, Transform)>();
+        assert_mirrored::<MeshInstance>(&world, &mirror);
+
+        let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+        for step in 0..2000u32 {
+            let e = entities[rng.below(entities.len())];
+            match rng.below(6) {
+                0 => {
+                    world.add_component(e, MeshInstance(step));
+                }
+                1 => {
+                    world.remove_component::<MeshInstance>(e);
+                }
+                2 => {
+                    world.add_component(e, Transform(step));
+                }
+                3 => {
+                    world.remove_component::<Transform>(e);
+                }
+                4 => {
+                    world.despawn_entity(e);
+                    entities.retain(|other| *other != e);
+                }
+                _ => entities.extend(world.spawn_batch_iter([(MeshInstance(step), Transform(step))])),
+            }
+            if entities.is_empty() {
+                entities.push(*world.spawn_entity());
+            }
+            assert_mirrored::<MeshInstance>(&world, &mirror);
+        }
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn dense_values_keep_the_slot_of_their_index() {
+        let mut world = World::new();
+        let mirror = mirror::<Health>(&mut world);
+        let a = *world.spawn_entity();
+        let b = *world.spawn_entity();
+        world.add_component(a, Health(1));
+        world.add_component(b, Health(2));
+        world.add_component(b, Health(3));
+        assert_eq!(world.component_slot::<Health>(b), Some(b.index() as usize));
+        world.despawn_entity(a);
+        world.compact_all();
+        assert_eq!(*mirror.lock().unwrap(), [(b, b.index() as usize)].into_iter().collect());
+        assert_mirrored::<Health>(&world, &mirror);
+    }
+
+    #[test]
+    fn tombstones_keep_the_slots_until_compacted() {
+        let mut world = World::new();
+        world.register_sparse::<MeshInstance>(RemovalPolicy::Tombstone);
+        let entities: Vec<Entity> = world.spawn_batch((0..6).map(MeshInstance));
+        let mirror = mirror::<MeshInstance>(&mut world);
+        for e in [entities[0], entities[3]] {
+            world.remove_component::<MeshInstance>(e);
+        }
+        world.despawn_entity(entities[1]);
+        for (slot, e) in entities.iter().enumerate().skip(2).filter(|(slot, _)| *slot != 3) {
+            assert_eq!(world.component_slot::<MeshInstance>(*e), Some(slot));
+        }
+        let query = world.query::<&MeshInstance>();
+        assert_eq!(query.iter(&world).map(|mesh| mesh.0).collect::<Vec<_>>(), [2, 4, 5]);
+        assert_eq!(world.storage_stats::<MeshInstance>().live, 3);
+        assert_mirrored::<MeshInstance>(&world, &mirror);
+
+        // Every value after a hole moves down, in slot order.
+        let moves = Arc::new(Mutex::new(Vec::new()));
+        let seen = moves.clone();
+        world.on_storage_relocate::<MeshInstance>(move |relocation| {
+            if let StorageRelocation::Moved { entity, from, to } = relocation {
+                seen.lock().unwrap().push((entity, from, to));
+            }
+        });
+        world.compact::<MeshInstance>();
+        assert_eq!(*moves.lock().unwrap(), [(entities[2], 2, 0), (entities[4], 4, 1), (entities[5], 5, 2)]);
+        assert_eq!(world.component_slot::<MeshInstance>(entities[5]), Some(2));
+        world.validate().unwrap();
+    }
+
+    #[test]
+    fn tombstones_are_reused_and_reported() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = world.spawn_batch((0..8).map(Health));
+        world.register_sparse::<Health>(RemovalPolicy::SwapRemove);
+        let mirror = mirror::<Health>(&mut world);
+        // The values keep the positions they had in the swap-remove set.
+        world.remove_component::<Health>(entities[1]);
+        let slot = world.component_slot::<Health>(entities[7]);
+        world.register_sparse::<Health>(RemovalPolicy::Tombstone);
+        assert_eq!(world.component_slot::<Health>(entities[7]), slot);
+        world.remove_component::<Health>(entities[4]);
+        world.add_component(entities[1], Health(10));
+        assert_eq!(world.component_slot::<Health>(entities[1]), Some(4));
+        assert_mirrored::<Health>(&world, &mirror);
+
+        world.despawn_entity(entities[0]);
+        world.compact_all();
+        assert_mirrored::<Health>(&world, &mirror);
+        // Back to swap removes, the values stay where compaction left them.
+        world.register_sparse::<Health>(RemovalPolicy::SwapRemove);
+        world.remove_component::<Health>(entities[2]);
+        assert_mirrored::<Health>(&world, &mirror);
+        let mut values: Vec<u32> = world.query::<&Health>().iter(&world).map(|health| health.0).collect();
+        values.sort();
+        assert_eq!(values, [3, 5, 6, 7, 10]);
+        world.validate().unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "leaves holes, it can't be grouped")]
+    fn tombstones_cant_be_grouped() {
+        let mut world = World::new();
+        world.register_sparse::<MeshInstance>(RemovalPolicy::Tombstone);
+        world.register_group::<(Transform, MeshInstance)>();
+    }
+}
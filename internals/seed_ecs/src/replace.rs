@@ -0,0 +1,230 @@
+//! Components turned into another component in one step, for the state machines swapping
+//! `Walking` for `Running(speed)`.
+
+use alloc::alloc::{alloc, dealloc, handle_alloc_error};
+use core::alloc::Layout;
+use core::any::type_name;
+use core::ptr::NonNull;
+
+use crate::component::{Component, ComponentId};
+use crate::entity::Entity;
+use crate::observer::ObserverKind;
+use crate::{EcsError, World};
+
+impl World {
+    /// Takes the `A` of the entity out, builds a `B` from it with `f` and adds it in its place,
+    /// and returns the `B` it replaced if the entity had one.
+    ///
+    /// The observers of [`OnRemove<A>`](crate::observer::OnRemove) run first, with the `A`
+    /// still there, then those of [`OnAdd<B>`](crate::observer::OnAdd) once `B` is there: no
+    /// observer nor command runs while the entity has neither. If `f` panics the entity is left
+    /// without both.
+    ///
+    /// Fails if the entity is not alive or has no `A`, including when an observer of the removal
+    /// removed it.
+    pub fn replace_component<A, B>(&mut self, entity: Entity, f: impl FnOnce(A) -> B) -> Result<Option<B>, EcsError>
+    where
+        A: Component,
+        B: Component,
+    {
+        self.entities.check_alive(entity)?;
+        let missing = EcsError::MissingComponent {
+            entity,
+            type_name: type_name::<A>(),
+        };
+        let from = self.components.id::<A>().ok_or(missing)?;
+        let to = self.register_component::<B>();
+        self.start_replace(entity, from, missing)?;
+        let index = entity.index() as usize;
+        let removed = self.storages.typed_mut::<A>(from).take(index).ok_or(missing)?;
+        self.end_remove(entity, from);
+        let previous = self.storages.typed_mut::<B>(to).insert(index, f(removed), self.change_tick);
+        self.end_replace(entity, to, previous.is_some());
+        Ok(previous)
+    }
+
+    /// Same as [`World::replace_component`] for components known by id, registered by type or by
+    /// descriptor. `f` gets the value of `from`, which it owns and must drop or move out, and the
+    /// memory of the value of `to`, which it must initialize. Returns true if that replaced a
+    /// value of `to`.
+    ///
+    /// # Safety
+    ///
+    /// `f` must leave a valid value of `to` behind its second pointer, both pointers are aligned
+    /// for their components.
+    pub unsafe fn replace_by_id(
+        &mut self,
+        entity: Entity,
+        from: ComponentId,
+        to: ComponentId,
+        f: impl FnOnce(NonNull<u8>, NonNull<u8>),
+    ) -> Result<bool, EcsError> {
+        self.entities.check_alive(entity)?;
+        let from_info = self.components.info(from).ok_or(EcsError::UnknownComponent(from))?;
+        let missing = EcsError::MissingComponent {
+            entity,
+            type_name: from_info.name(),
+        };
+        let from_layout = from_info.layout();
+        let to_layout = self.components.info(to).ok_or(EcsError::UnknownComponent(to))?.layout();
+        self.start_replace(entity, from, missing)?;
+        let removed = Buffer::new(from_layout);
+        if !self.storages.get_mut(from).take_ptr(entity.index() as usize, removed.0.as_ptr()) {
+            return Err(missing);
+        }
+        self.end_remove(entity, from);
+        let value = Buffer::new(to_layout);
+        f(removed.0, value.0);
+        let replaced = self.storages.get_mut(to).insert_ptr(entity.index() as usize, value.0.as_ptr(), self.change_tick);
+        self.end_replace(entity, to, replaced);
+        Ok(replaced)
+    }
+
+    // Runs the observers of the removal and takes the entity out of the groups of `from`.
+    fn start_replace(&mut self, entity: Entity, from: ComponentId, missing: EcsError) -> Result<(), EcsError> {
+        if !self.storages.get(from).contains(entity.index() as usize) {
+            return Err(missing);
+        }
+        self.trigger_component(ObserverKind::Remove, from, entity);
+        self.leave_groups(entity, from);
+        Ok(())
+    }
+
+    fn end_remove(&mut self, entity: Entity, from: ComponentId) {
+        self.removed.push(from, entity);
+        self.replication.removed(from, entity);
+        self.metrics.removed(from, 1);
+    }
+
+    fn end_replace(&mut self, entity: Entity, to: ComponentId, replaced: bool) {
+        if !replaced {
+            self.metrics.inserted(to, 1);
+            self.join_groups(entity, to);
+        }
+        self.flush_relocations(&[]);
+        if !replaced {
+            self.trigger_component(ObserverKind::Add, to, entity);
+        }
+    }
+}
+
+// Memory for one value of a layout, freed without dropping the value.
+struct Buffer(NonNull<u8>, Layout);
+
+impl Buffer {
+    fn new(layout: Layout) -> Self {
+        if layout.size() == 0 {
+            // Aligned and never read from nor written to.
+            return Self(unsafe { NonNull::new_unchecked(layout.align() as *mut u8) }, layout);
+        }
+        let ptr = unsafe { alloc(layout) };
+        Self(NonNull::new(ptr).unwrap_or_else(|| handle_alloc_error(layout)), layout)
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        if self.1.size() != 0 {
+            unsafe { dealloc(self.0.as_ptr(), self.1) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::component::ComponentDescriptor;
+    use crate::observer::{DeferredWorld, OnAdd, OnRemove, Trigger};
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Walking(u32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Running(u32);
+
+    type Log = Arc<Mutex<Vec<&'static str>>>;
+
+    // Records each observer run along with what the entity has at that point.
+    fn observe(world: &mut World) -> Log {
+        let log = Log::default();
+        let has = |world: &DeferredWorld, entity| {
+            match (world.get_component::<Walking>(entity).is_some(), world.get_component::<Running>(entity).is_some()) {
+                (true, false) => "walking",
+                (false, true) => "running",
+                (true, true) => "both",
+                (false, false) => "neither",
+            }
+        };
+        let removed = log.clone();
+        world.add_observer(move |trigger: Trigger<OnRemove<Walking>>, world: &mut DeferredWorld| {
+            removed.lock().unwrap().extend(["remove walking", has(world, trigger.entity())]);
+        });
+        let added = log.clone();
+        world.add_observer(move |trigger: Trigger<OnAdd<Running>>, world: &mut DeferredWorld| {
+            added.lock().unwrap().extend(["add running", has(world, trigger.entity())]);
+        });
+        log
+    }
+
+    #[test]
+    fn observers_never_see_the_gap() {
+        let mut world = World::new();
+        let log = observe(&mut world);
+        let e = world.spawn_batch([Walking(3)])[0];
+        assert_eq!(world.entity_mut(e).replace(|walking: Walking| Running(walking.0 * 2)), Ok(None));
+        assert_eq!(world.get_component::<Running>(e), Some(&Running(6)));
+        assert!(!world.has_component::<Walking>(e));
+        let log = log.lock().unwrap();
+        assert_eq!(*log, ["remove walking", "walking", "add running", "running"]);
+    }
+
+    #[test]
+    fn replacing_an_existing_value_is_not_an_addition() {
+        let mut world = World::new();
+        let log = observe(&mut world);
+        let e = world.spawn_batch_iter([(Walking(1), Running(9))])[0];
+        log.lock().unwrap().clear();
+        assert_eq!(world.replace_component(e, |walking: Walking| Running(walking.0)), Ok(Some(Running(9))));
+        assert_eq!(*log.lock().unwrap(), ["remove walking", "both"]);
+        assert_eq!(world.query::<&Running>().iter(&world).collect::<Vec<_>>(), [&Running(1)]);
+    }
+
+    #[test]
+    fn missing_components_are_errors() {
+        let mut world = World::new();
+        let e = *world.spawn_entity();
+        let missing = EcsError::MissingComponent {
+            entity: e,
+            type_name: type_name::<Walking>(),
+        };
+        assert_eq!(world.replace_component(e, |walking: Walking| Running(walking.0)), Err(missing));
+        world.register_component::<Walking>();
+        assert_eq!(world.entity_mut(e).replace(|walking: Walking| Running(walking.0)), Err(missing));
+        assert!(!world.has_component::<Running>(e));
+        world.despawn_entity(e);
+        let dead = world.replace_component(e, |walking: Walking| Running(walking.0));
+        assert_eq!(dead, Err(EcsError::EntityNotAlive(e)));
+    }
+
+    #[test]
+    fn replace_by_id_moves_descriptor_values() {
+        let mut world = World::new();
+        let meters = world.register_component_with_descriptor(ComponentDescriptor::new("Meters", Layout::new::<u32>()));
+        let running = world.register_component::<Running>();
+        let e = *world.spawn_entity();
+        let mut value = 40u32;
+        unsafe { world.insert_by_id(e, meters, NonNull::from(&mut value).cast()) };
+        let replaced = unsafe {
+            world.replace_by_id(e, meters, running, |from, to| {
+                to.cast::<Running>().as_ptr().write(Running(from.cast::<u32>().as_ptr().read() + 2));
+            })
+        };
+        assert_eq!(replaced, Ok(false));
+        assert_eq!(world.get_by_id(e, meters), None);
+        assert_eq!(world.get_component::<Running>(e), Some(&Running(42)));
+        let again = unsafe { world.replace_by_id(e, meters, running, |_, _| unreachable!()) };
+        let missing = EcsError::MissingComponent { entity: e, type_name: "Meters" };
+        assert_eq!(again, Err(missing));
+    }
+}
@@ -0,0 +1,279 @@
+//! Per component diffs for network replication, taken at the rate of the network instead of the
+//! frames delimited by [`World::clear_trackers`].
+
+use alloc::vec::Vec;
+use core::any::type_name;
+use core::mem;
+
+use crate::change_detection::Tick;
+use crate::component::{Component, ComponentId};
+use crate::entity::Entity;
+use crate::utils::HashMap;
+use crate::World;
+
+/// What happened to the components of a type between two calls to
+/// [`World::take_replication_diff`].
+///
+/// An entity that lost its component and got a new one in between is both in `removed` and in
+/// `added`, receivers apply the removals first. A component added then removed in between is only
+/// in `removed`, receivers ignore the removals of entities they don't know.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplicationDiff {
+    pub added: Vec<Entity>,
+    /// The components written through a `Mut` or replaced, the added ones aren't repeated here.
+    pub changed: Vec<Entity>,
+    /// The entities that lost the component, removed or despawned.
+    pub removed: Vec<Entity>,
+}
+
+impl ReplicationDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+struct Tracked {
+    // `None` until the first take, which reports every component as added.
+    last_take: Option<Tick>,
+    removed: Vec<Entity>,
+}
+
+/// The components registered for replication. Additions and changes are found from the ticks of
+/// the values when the diff is taken, only the removals need to be recorded as they happen.
+#[derive(Default)]
+pub(crate) struct Replication {
+    tracked: HashMap<ComponentId, Tracked>,
+}
+
+impl Replication {
+    pub fn removed(&mut self, id: ComponentId, entity: Entity) {
+        self.removed_many(id, [entity]);
+    }
+
+    pub fn removed_many(&mut self, id: ComponentId, entities: impl IntoIterator<Item = Entity>) {
+        if let Some(tracked) = self.tracked.get_mut(&id) {
+            tracked.removed.extend(entities);
+        }
+    }
+
+    pub fn check_change_ticks(&mut self, tick: Tick) {
+        for last_take in self.tracked.values_mut().filter_map(|tracked| tracked.last_take.as_mut()) {
+            last_take.check_tick(tick);
+        }
+    }
+}
+
+impl World {
+    /// Starts recording the diffs of `T` for [`World::take_replication_diff`], the first diff
+    /// reports every existing `T` as added. Registering twice does nothing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` is zero sized, its values have no ticks to tell changes apart.
+    pub fn register_replicated<T: Component>(&mut self) {
+        assert_ne!(mem::size_of::<T>(), 0, "Zero sized components can't be replicated");
+        let id = self.register_component::<T>();
+        self.replication.tracked.entry(id).or_insert(Tracked {
+            last_take: None,
+            removed: Vec::new(),
+        });
+    }
+
+    /// Drains what happened to the components `T` since the previous call, in ascending entity
+    /// order for the additions and changes and in order of removal for the removals.
+    ///
+    /// It moves to the next change tick so that the writes made right after are in the next diff.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `T` was not registered with [`World::register_replicated`].
+    pub fn take_replication_diff<T: Component>(&mut self) -> ReplicationDiff {
+        let id = self.components.id::<T>();
+        let Some(id) = id.filter(|id| self.replication.tracked.contains_key(id)) else {
+            panic!("Component {} is not replicated", type_name::<T>());
+        };
+        let this_run = self.increment_change_tick();
+        let tracked = self.replication.tracked.get_mut(&id).unwrap();
+        let last_take = tracked.last_take.replace(this_run);
+        let mut removed = mem::take(&mut tracked.removed);
+
+        let mut diff = ReplicationDiff::default();
+        let storage = self.storages.typed::<T>(id);
+        for index in storage.mask().iter() {
+            let ticks = storage.get_ticks(index).unwrap();
+            let entity = self.entities.get(index as u32).unwrap();
+            match last_take {
+                None => diff.added.push(entity),
+                Some(last) if ticks.added.is_newer_than(last, this_run) => diff.added.push(entity),
+                Some(last) if ticks.changed.is_newer_than(last, this_run) => diff.changed.push(entity),
+                Some(_) => {}
+            }
+        }
+        // Removals before the first take are covered by the additions not reporting them.
+        if last_take.is_some() {
+            let mut seen = HashMap::default();
+            removed.retain(|entity| seen.insert(*entity, ()).is_none());
+            diff.removed = removed;
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Position(f32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Health(u32);
+
+    fn spawn(world: &mut World, x: f32) -> Entity {
+        let e = *world.spawn_entity();
+        world.add_component(e, Position(x));
+        e
+    }
+
+    #[test]
+    fn mutations_land_in_exactly_one_diff() {
+        let mut world = World::new();
+        let existing = spawn(&mut world, 0.0);
+        world.register_replicated::<Position>();
+        world.register_replicated::<Position>();
+        let untouched: Vec<Entity> = (0..10).map(|i| spawn(&mut world, i as f32)).collect();
+        let diff = world.take_replication_diff::<Position>();
+        assert_eq!(diff.added.len(), 11);
+        assert_eq!(diff.added[0], existing);
+
+        let moved = spawn(&mut world, 1.0);
+        world.get_component_mut::<Position>(existing).unwrap().0 = 5.0;
+        // Frames pass in between, the network doesn't care.
+        world.clear_trackers();
+        world.clear_trackers();
+        world.add_component(untouched[3], Position(3.5));
+        let mut positions = world.query::<&mut Position>();
+        for mut position in positions.iter_mut(&mut world) {
+            if position.0 == 1.0 {
+                position.0 = 1.5;
+            }
+        }
+        world.despawn_entity(untouched[4]);
+        world.add_component(untouched[5], Health(3));
+        let diff = world.take_replication_diff::<Position>();
+        assert_eq!(diff.added, [moved]);
+        assert_eq!(diff.changed, [existing, untouched[1], untouched[3]]);
+        assert_eq!(diff.removed, [untouched[4]]);
+
+        // A write right after a take goes to the next one.
+        world.get_component_mut::<Position>(existing).unwrap().0 = 6.0;
+        assert_eq!(world.take_replication_diff::<Position>().changed, [existing]);
+        assert!(world.take_replication_diff::<Position>().is_empty());
+    }
+
+    #[test]
+    fn short_lived_components_are_only_removed() {
+        let mut world = World::new();
+        world.register_replicated::<Position>();
+        let a = spawn(&mut world, 0.0);
+        world.take_replication_diff::<Position>();
+
+        let flash = spawn(&mut world, 1.0);
+        world.remove_component::<Position>(flash);
+        world.remove_component::<Position>(a);
+        world.add_component(a, Position(2.0));
+        world.remove_by_id(a, world.components().id::<Position>().unwrap());
+        world.add_component(a, Position(3.0));
+        let diff = world.take_replication_diff::<Position>();
+        assert_eq!(diff.added, [a]);
+        assert!(diff.changed.is_empty());
+        assert_eq!(diff.removed, [flash, a]);
+    }
+
+    // What a client gets from a server, with the server's ids.
+    #[derive(Debug, Clone, Copy)]
+    enum Message {
+        Spawned(Entity),
+        Moved(Entity, f32),
+        Hit(Entity, u32),
+    }
+
+    fn apply(world: &mut World, message: Message) -> bool {
+        let entity = match message {
+            Message::Spawned(entity) | Message::Moved(entity, _) | Message::Hit(entity, _) => entity,
+        };
+        let Some(mut entity) = world.get_or_spawn(entity) else {
+            return false;
+        };
+        match message {
+            Message::Spawned(_) => {}
+            Message::Moved(_, x) => drop(entity.insert(Position(x))),
+            Message::Hit(_, health) => drop(entity.insert(Health(health))),
+        }
+        true
+    }
+
+    fn state(world: &World) -> Vec<(Entity, Option<Position>, Option<u32>)> {
+        let mut state: Vec<_> = world
+            .enities()
+            .iter()
+            .map(|e| (e, world.get_component::<Position>(e).copied(), world.get_component::<Health>(e).map(|h| h.0)))
+            .collect();
+        state.sort_by_key(|(e, ..)| *e);
+        state
+    }
+
+    #[test]
+    fn out_of_order_messages_converge() {
+        let mut server = World::new();
+        let [ship, rock]: [Entity; 2] = core::array::from_fn(|_| *server.spawn_entity());
+        // The client only knows the bits of the ids.
+        let [ship, rock] = [ship, rock].map(|e| Entity::from_bits(e.to_bits()).unwrap());
+        let messages = [
+            Message::Spawned(ship),
+            Message::Moved(ship, 1.0),
+            Message::Spawned(rock),
+            Message::Hit(rock, 7),
+            Message::Moved(rock, 4.0),
+        ];
+        let mut expected = World::new();
+        for message in messages {
+            assert!(apply(&mut expected, message));
+        }
+        let mut reversed = World::new();
+        for message in messages.iter().rev() {
+            assert!(apply(&mut reversed, *message));
+        }
+        let mut shuffled = World::new();
+        for i in [3, 1, 4, 0, 2] {
+            assert!(apply(&mut shuffled, messages[i]));
+        }
+        assert_eq!(state(&expected).len(), 2);
+        assert_eq!(state(&reversed), state(&expected));
+        assert_eq!(state(&shuffled), state(&expected));
+    }
+
+    #[test]
+    fn stale_generations_are_rejected() {
+        let mut client = World::new();
+        let (old, new) = (Entity::new(5, 2), Entity::new(5, 3));
+        assert!(apply(&mut client, Message::Moved(new, 1.0)));
+        // Another generation is alive at the index.
+        assert!(!apply(&mut client, Message::Moved(old, 2.0)));
+        client.despawn_entity(new);
+        // The index is free but a newer generation was already used.
+        assert!(!apply(&mut client, Message::Spawned(old)));
+        assert!(!apply(&mut client, Message::Hit(new, 1)));
+        assert!(client.enities().iter().next().is_none());
+        let newer = Entity::new(5, 4);
+        assert!(apply(&mut client, Message::Hit(newer, 1)));
+        assert_eq!(client.get_component::<Health>(newer), Some(&Health(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "is not replicated")]
+    fn taking_requires_registration() {
+        let mut world = World::new();
+        spawn(&mut world, 0.0);
+        world.take_replication_diff::<Position>();
+    }
+}
@@ -0,0 +1,679 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::{type_name, Any, TypeId};
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::{Deref, DerefMut};
+
+use crate::change_detection::{ComponentTicks, Mut, Tick};
+use crate::utils::TypeIdMap;
+use crate::{EcsError, World};
+
+struct ResourceData {
+    name: &'static str,
+    size: usize,
+    value: Box<UnsafeCell<dyn Any + Send + Sync>>,
+    ticks: UnsafeCell<ComponentTicks>,
+    drop_last: bool,
+}
+
+/// The values of a world that are not attached to any entity, one per type.
+///
+/// Like the component storages, values sit behind an `UnsafeCell` so that several of them can be
+/// borrowed mutably at once through an [`UnsafeWorldCell`](crate::UnsafeWorldCell).
+#[derive(Default)]
+pub(crate) struct Resources {
+    resources: TypeIdMap<ResourceData>,
+    // The resources taken out by a `World::resource_scope`, with the system running it.
+    scoped: TypeIdMap<Option<&'static str>>,
+}
+
+// Resources are only mutated through `&mut self` or by the callers of the unsafe methods, who must
+// uphold the borrow rules.
+unsafe impl Sync for Resources {}
+
+impl Resources {
+    /// Inserts the resource as added at `tick`.
+    pub fn insert<T: Send + Sync + 'static>(&mut self, value: T, tick: Tick) -> Option<T> {
+        let previous = self.remove::<T>();
+        self.resources.insert(
+            TypeId::of::<T>(),
+            ResourceData {
+                name: type_name::<T>(),
+                size: mem::size_of::<T>(),
+                value: Box::new(UnsafeCell::new(value)),
+                ticks: UnsafeCell::new(ComponentTicks::new(tick)),
+                drop_last: false,
+            },
+        );
+        previous
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        let data = self.resources.get(&TypeId::of::<T>())?;
+        unsafe { (*data.value.get()).downcast_ref() }
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<(&mut T, &mut ComponentTicks)> {
+        let data = self.resources.get_mut(&TypeId::of::<T>())?;
+        Some((data.value.get_mut().downcast_mut()?, data.ticks.get_mut()))
+    }
+
+    /// The resource and its ticks.
+    ///
+    /// # Safety
+    ///
+    /// The caller must make sure nothing else accesses the resource while the pointers are used
+    /// mutably.
+    pub unsafe fn get_ptr<T: 'static>(&self) -> Option<(*mut T, *mut ComponentTicks)> {
+        let data = self.resources.get(&TypeId::of::<T>())?;
+        let value = (*data.value.get()).downcast_mut::<T>()?;
+        Some((value as *mut T, data.ticks.get()))
+    }
+
+    // Takes the resource out as it is, ticks and flags included, for `World::resource_scope`.
+    fn take<T: 'static>(&mut self) -> Option<ResourceData> {
+        self.resources.remove(&TypeId::of::<T>())
+    }
+
+    // Puts back a resource taken out by `Resources::take`, dropping the one inserted since.
+    fn restore<T: 'static>(&mut self, data: ResourceData) {
+        self.resources.insert(TypeId::of::<T>(), data);
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        let data = self.resources.remove(&TypeId::of::<T>())?;
+        // The map is keyed by type id so the value is a `T`.
+        let value = Box::into_raw(data.value) as *mut UnsafeCell<T>;
+        Some(unsafe { Box::from_raw(value) }.into_inner())
+    }
+
+    /// Drops the resource of that type, returns false if there is none.
+    pub fn remove_by_id(&mut self, type_id: TypeId) -> bool {
+        self.resources.remove(&type_id).is_some()
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Why the resource can't be borrowed by `requested_by`, a system or the code outside of
+    /// systems.
+    pub fn unavailable<T: 'static>(&self, requested_by: Option<&'static str>) -> EcsError {
+        match self.scoped.get(&TypeId::of::<T>()) {
+            Some(held_by) => EcsError::AccessConflict {
+                type_name: type_name::<T>(),
+                held_by: *held_by,
+                requested_by,
+            },
+            None => EcsError::MissingResource {
+                type_name: type_name::<T>(),
+            },
+        }
+    }
+
+    /// True if the resource is taken out by a scope.
+    pub fn is_scoped<T: 'static>(&self) -> bool {
+        self.scoped.contains_key(&TypeId::of::<T>())
+    }
+
+    /// The system running the first scope still holding a resource, `Some(None)` outside systems.
+    pub fn any_scoped(&self) -> Option<Option<&'static str>> {
+        self.scoped.values().next().copied()
+    }
+
+    pub fn ticks<T: 'static>(&self) -> Option<ComponentTicks> {
+        let data = self.resources.get(&TypeId::of::<T>())?;
+        // Ticks are only written through `&mut self` or by the holders of a `Mut`, which borrow
+        // the world exclusively.
+        Some(unsafe { *data.ticks.get() })
+    }
+
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.resources.values().map(|data| data.name)
+    }
+
+    pub fn infos(&self) -> impl Iterator<Item = ResourceInfo> + '_ {
+        self.resources.iter().map(|(type_id, data)| ResourceInfo {
+            name: data.name,
+            type_id: *type_id,
+            size: data.size,
+            // Same as `Resources::ticks`.
+            ticks: unsafe { *data.ticks.get() },
+        })
+    }
+
+    /// Returns false if there is no resource `T`.
+    pub fn set_drop_last<T: 'static>(&mut self, drop_last: bool) -> bool {
+        match self.resources.get_mut(&TypeId::of::<T>()) {
+            Some(data) => {
+                data.drop_last = drop_last;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drops every resource, the ones marked with [`Resources::set_drop_last`] after the others.
+    pub fn clear(&mut self) {
+        let (last, first): (Vec<_>, Vec<_>) = mem::take(&mut self.resources)
+            .into_values()
+            .partition(|data| data.drop_last);
+        drop(first);
+        drop(last);
+    }
+
+    /// Clamps the ticks of the resources, see [`Tick::check_tick`].
+    pub fn check_change_ticks(&mut self, tick: Tick) {
+        for data in self.resources.values_mut() {
+            data.ticks.get_mut().check_ticks(tick);
+        }
+    }
+
+    /// Moves the resources of `other` here, `overwrite` tells which one to keep on conflicts.
+    pub fn merge(&mut self, mut other: Resources, overwrite: bool) {
+        for (type_id, data) in mem::take(&mut other.resources) {
+            if overwrite || !self.resources.contains_key(&type_id) {
+                self.resources.insert(type_id, data);
+            }
+        }
+    }
+}
+
+impl Drop for Resources {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+/// What the world knows about one of its resources, see [`World::iter_resources`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResourceInfo {
+    pub name: &'static str,
+    pub type_id: TypeId,
+    /// The size of the value, not counting what it owns on the heap.
+    pub size: usize,
+    pub ticks: ComponentTicks,
+}
+
+/// Types that can build themselves from the world, to initialize resources that depend on other
+/// resources for instance.
+pub trait FromWorld {
+    fn from_world(world: &mut World) -> Self;
+}
+
+impl<T: Default> FromWorld for T {
+    fn from_world(_world: &mut World) -> Self {
+        T::default()
+    }
+}
+
+impl World {
+    /// Inserts the resource built by [`FromWorld`] unless there is one already.
+    pub fn init_resource<T: FromWorld + Send + Sync + 'static>(&mut self) {
+        if !self.contains_resource::<T>() {
+            let value = T::from_world(self);
+            self.insert_resource(value);
+        }
+    }
+
+    /// Inserts a resource and returns the previous value of that type if any.
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.resources.insert(value, self.change_tick)
+    }
+
+    pub fn get_resource<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.resources.get()
+    }
+
+    /// The resource, marked changed when it is written to.
+    pub fn get_resource_mut<T: Send + Sync + 'static>(&mut self) -> Option<Mut<'_, T>> {
+        let (value, ticks) = self.resources.get_mut()?;
+        Some(Mut::with_ticks(value, ticks, self.last_change_tick, self.change_tick))
+    }
+
+    pub fn remove_resource<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.resources.remove()
+    }
+
+    pub fn contains_resource<T: Send + Sync + 'static>(&self) -> bool {
+        self.resources.contains::<T>()
+    }
+
+    /// Same as [`World::get_resource`] but tells why the resource is missing: an
+    /// [`EcsError::AccessConflict`] naming the system holding it if a
+    /// [`World::resource_scope`] took it out, [`EcsError::MissingResource`] otherwise.
+    pub fn try_resource<T: Send + Sync + 'static>(&self) -> Result<&T, EcsError> {
+        self.resources.get().ok_or_else(|| self.resources.unavailable::<T>(self.running_system))
+    }
+
+    /// Same as [`World::get_resource_mut`] but fails like [`World::try_resource`].
+    pub fn try_resource_mut<T: Send + Sync + 'static>(&mut self) -> Result<Mut<'_, T>, EcsError> {
+        if !self.contains_resource::<T>() {
+            return Err(self.resources.unavailable::<T>(self.running_system));
+        }
+        Ok(self.get_resource_mut().unwrap())
+    }
+
+    /// Every resource of the world, in no particular order. For the tools and the hot reloads
+    /// that don't know the types of the resources.
+    pub fn iter_resources(&self) -> impl Iterator<Item = ResourceInfo> + '_ {
+        self.resources.infos()
+    }
+
+    /// Drops the resource of that type, returns false if there is none. The typed way is
+    /// [`World::remove_resource`].
+    pub fn remove_resource_by_id(&mut self, type_id: TypeId) -> bool {
+        self.resources.remove_by_id(type_id)
+    }
+
+    /// Drops every resource, the ones marked with [`World::set_resource_drop_last`] last. The
+    /// entities stay.
+    pub fn clear_resources(&mut self) {
+        self.resources.clear();
+    }
+
+    /// When the resource was inserted and last written, to compare with the ticks of a system.
+    pub fn resource_ticks<T: Send + Sync + 'static>(&self) -> Option<ComponentTicks> {
+        self.resources.ticks::<T>()
+    }
+
+    /// True if the resource was inserted since the last [`World::clear_trackers`].
+    pub fn is_resource_added<T: Send + Sync + 'static>(&self) -> bool {
+        self.resource_ticks::<T>()
+            .is_some_and(|ticks| ticks.is_added(self.last_change_tick, self.change_tick))
+    }
+
+    /// True if the resource was inserted or written since the last [`World::clear_trackers`].
+    pub fn is_resource_changed<T: Send + Sync + 'static>(&self) -> bool {
+        self.resource_ticks::<T>()
+            .is_some_and(|ticks| ticks.is_changed(self.last_change_tick, self.change_tick))
+    }
+
+    /// Makes the resource drop after the others when the world is dropped or
+    /// [cleared](World::clear_all), for the caches the other resources and the components hold
+    /// handles into. Inserting the resource again resets it, a [`World::resource_scope`] over it
+    /// doesn't.
+    ///
+    /// Returns false if the resource doesn't exist.
+    pub fn set_resource_drop_last<T: Send + Sync + 'static>(&mut self, drop_last: bool) -> bool {
+        self.resources.set_drop_last::<T>(drop_last)
+    }
+
+    /// Takes the resource out for the duration of `f`, so that the world and the resource can be
+    /// borrowed mutably at the same time.
+    ///
+    /// The resource is put back when `f` returns or panics, replacing whatever `f` inserted. Inside
+    /// `f` the world doesn't have the resource. Taking it out isn't a change, it is only marked
+    /// changed if `f` writes to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the resource doesn't exist.
+    pub fn resource_scope<T, R>(&mut self, f: impl FnOnce(&mut World, Mut<T>) -> R) -> R
+    where
+        T: Send + Sync + 'static,
+    {
+        self.try_resource_scope(f)
+            .unwrap_or_else(|| panic!("{}", self.resources.unavailable::<T>(self.running_system)))
+    }
+
+    /// Same as [`World::resource_scope`] but returns `None` if the resource doesn't exist.
+    pub fn try_resource_scope<T, R>(&mut self, f: impl FnOnce(&mut World, Mut<T>) -> R) -> Option<R>
+    where
+        T: Send + Sync + 'static,
+    {
+        struct Guard<'a, T: Send + Sync + 'static> {
+            world: &'a mut World,
+            data: Option<ResourceData>,
+            _marker: PhantomData<T>,
+        }
+
+        impl<'a, T: Send + Sync + 'static> Drop for Guard<'a, T> {
+            fn drop(&mut self) {
+                self.world.resources.scoped.remove(&TypeId::of::<T>());
+                if let Some(data) = self.data.take() {
+                    self.world.resources.restore::<T>(data);
+                }
+            }
+        }
+
+        let data = self.resources.take::<T>()?;
+        self.resources.scoped.insert(TypeId::of::<T>(), self.running_system);
+        let (last_run, this_run) = (self.last_change_tick, self.change_tick);
+        let mut guard = Guard::<T> {
+            world: self,
+            data: Some(data),
+            _marker: PhantomData,
+        };
+        let Guard { world, data, .. } = &mut guard;
+        let data = data.as_mut().unwrap();
+        // The map is keyed by type id so the value is a `T`.
+        let value = data.value.get_mut().downcast_mut::<T>().unwrap();
+        Some(f(world, Mut::with_ticks(value, data.ticks.get_mut(), last_run, this_run)))
+    }
+
+    /// Replaces the resource until the returned guard drops, which puts the previous value back
+    /// along with its ticks, or removes the resource if there was none. For the mock inputs and
+    /// fixed clocks of tests and tools.
+    ///
+    /// The guard derefs to the world, overriding again through it stacks a new override that is
+    /// undone first.
+    ///
+    /// ```
+    /// # use seed_ecs::prelude::*;
+    /// struct Time(f32);
+    ///
+    /// let mut world = World::new();
+    /// world.insert_resource(Time(12.5));
+    /// {
+    ///     let mut world = world.override_resource(Time(0.0));
+    ///     let frozen = world.override_resource(Time(1.0));
+    ///     assert_eq!(frozen.get_resource::<Time>().unwrap().0, 1.0);
+    /// }
+    /// assert_eq!(world.get_resource::<Time>().unwrap().0, 12.5);
+    /// ```
+    pub fn override_resource<T: Send + Sync + 'static>(&mut self, value: T) -> ResourceOverrideGuard<'_, T> {
+        let ticks = self.resource_ticks::<T>();
+        let previous = self.insert_resource(value).zip(ticks);
+        ResourceOverrideGuard { world: self, previous }
+    }
+
+    /// Runs `f` with the resource replaced by `value`, the closure form of
+    /// [`World::override_resource`]. The previous value is back once `f` returns or panics.
+    pub fn with_resource<T, R>(&mut self, value: T, f: impl FnOnce(&mut World) -> R) -> R
+    where
+        T: Send + Sync + 'static,
+    {
+        let mut guard = self.override_resource(value);
+        f(&mut guard)
+    }
+}
+
+/// Restores a resource replaced by [`World::override_resource`] when dropped, and derefs to the
+/// world meanwhile.
+pub struct ResourceOverrideGuard<'w, T: Send + Sync + 'static> {
+    world: &'w mut World,
+    previous: Option<(T, ComponentTicks)>,
+}
+
+impl<T: Send + Sync + 'static> Deref for ResourceOverrideGuard<'_, T> {
+    type Target = World;
+
+    fn deref(&self) -> &World {
+        self.world
+    }
+}
+
+impl<T: Send + Sync + 'static> DerefMut for ResourceOverrideGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut World {
+        self.world
+    }
+}
+
+impl<T: Send + Sync + 'static> Drop for ResourceOverrideGuard<'_, T> {
+    fn drop(&mut self) {
+        let Some((value, ticks)) = self.previous.take() else {
+            self.world.remove_resource::<T>();
+            return;
+        };
+        self.world.insert_resource(value);
+        // Coming back isn't a change, the systems see the value as they left it.
+        *self.world.resources.get_mut::<T>().unwrap().1 = ticks;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::{String, ToString};
+
+    use super::*;
+    use crate::commands::Commands;
+    use crate::component::Component;
+    use crate::system::{Res, Schedule};
+
+    #[derive(Debug, PartialEq)]
+    struct Gravity(f32);
+    #[derive(Component)]
+    struct Prop(&'static str);
+
+    #[test]
+    fn resource_lifecycle() {
+        let mut world = World::new();
+        assert!(!world.contains_resource::<Gravity>());
+        assert_eq!(world.insert_resource(Gravity(9.8)), None);
+        world.get_resource_mut::<Gravity>().unwrap().0 = 1.6;
+        assert_eq!(world.get_resource::<Gravity>(), Some(&Gravity(1.6)));
+        assert_eq!(world.insert_resource(Gravity(3.7)), Some(Gravity(1.6)));
+        assert_eq!(world.remove_resource::<Gravity>(), Some(Gravity(3.7)));
+        assert_eq!(world.get_resource::<Gravity>(), None);
+    }
+
+    struct Loader {
+        loaded: Vec<&'static str>,
+    }
+
+    #[test]
+    fn scope_spawns_while_holding_the_resource() {
+        let mut world = World::new();
+        world.insert_resource(Loader { loaded: Vec::new() });
+        let spawned = world.resource_scope(|world, mut loader: Mut<Loader>| {
+            for name in ["tree", "rock"] {
+                let e = *world.spawn_entity();
+                world.add_component(e, Prop(name));
+                loader.loaded.push(name);
+            }
+            // The resource is out of the world for now.
+            assert!(world.get_resource::<Loader>().is_none());
+            world.enities().len()
+        });
+        assert_eq!(spawned, 2);
+        assert_eq!(world.get_resource::<Loader>().unwrap().loaded, vec!["tree", "rock"]);
+        assert_eq!(world.try_resource_scope(|_, _: Mut<Gravity>| ()), None);
+    }
+
+    #[test]
+    fn scope_reinserts_on_panic() {
+        let mut world = World::new();
+        world.insert_resource(Gravity(9.8));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            world.resource_scope(|_, mut gravity: Mut<Gravity>| {
+                gravity.0 = 1.6;
+                panic!("loader failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(world.get_resource::<Gravity>(), Some(&Gravity(1.6)));
+    }
+
+    #[test]
+    fn scopes_only_track_writes() {
+        let mut world = World::new();
+        world.insert_resource(Gravity(9.8));
+        world.clear_trackers();
+        let added = world.resource_ticks::<Gravity>().unwrap().added;
+        world.resource_scope(|_, gravity: Mut<Gravity>| assert_eq!(gravity.0, 9.8));
+        assert!(!world.is_resource_added::<Gravity>());
+        assert!(!world.is_resource_changed::<Gravity>());
+        assert_eq!(world.resource_ticks::<Gravity>().unwrap().added, added);
+
+        world.resource_scope(|_, mut gravity: Mut<Gravity>| gravity.0 = 1.6);
+        assert!(!world.is_resource_added::<Gravity>());
+        assert!(world.is_resource_changed::<Gravity>());
+    }
+
+    #[test]
+    fn scoped_resources_name_their_holder() {
+        let mut world = World::new();
+        world.insert_resource(Gravity(9.8));
+        let error = world.resource_scope(|world, _: Mut<Gravity>| world.try_resource_mut::<Gravity>().err());
+        let conflict = EcsError::AccessConflict {
+            type_name: type_name::<Gravity>(),
+            held_by: None,
+            requested_by: None,
+        };
+        assert_eq!(error, Some(conflict));
+        assert_eq!(world.try_resource::<Gravity>(), Ok(&Gravity(9.8)));
+        let missing = world.try_resource::<Loader>().err().unwrap();
+        assert_eq!(missing, EcsError::MissingResource { type_name: type_name::<Loader>() });
+        assert_eq!(missing.to_string(), format!("Resource {} does not exist", type_name::<Loader>()));
+    }
+
+    struct Score(u32);
+    struct Report(String);
+
+    fn settle(mut commands: Commands) {
+        commands.add(|world: &mut World| {
+            let report = world.resource_scope(|world, _: Mut<Score>| {
+                let mut inner = Schedule::new();
+                inner.add_system(read_score);
+                inner.run_catching(world).unwrap_err().message().unwrap().to_string()
+            });
+            world.insert_resource(Report(report));
+        });
+    }
+
+    fn read_score(score: Res<Score>) {
+        assert!(score.0 < 100);
+    }
+
+    #[test]
+    fn conflicts_in_a_schedule_name_both_systems() {
+        let mut world = World::new();
+        world.insert_resource(Score(3));
+        let mut schedule = Schedule::new();
+        schedule.add_system(settle);
+        schedule.run(&mut world);
+        let report = &world.get_resource::<Report>().unwrap().0;
+        assert!(report.starts_with(&format!("Resource {} requested by system ", type_name::<Score>())), "{}", report);
+        let (requested_by, held_by) = report.split_once(" is held by system ").unwrap();
+        assert!(requested_by.ends_with("::read_score"), "{}", report);
+        assert!(held_by.ends_with("::settle in a resource scope"), "{}", report);
+        assert_eq!(world.running_system(), None);
+    }
+
+    #[derive(Default)]
+    struct Config {
+        cache_size: usize,
+    }
+
+    struct AssetCache {
+        slots: Vec<u32>,
+    }
+
+    impl FromWorld for AssetCache {
+        fn from_world(world: &mut World) -> Self {
+            let size = world.get_resource::<Config>().map_or(1, |config| config.cache_size);
+            AssetCache {
+                slots: vec![0; size],
+            }
+        }
+    }
+
+    #[test]
+    fn init_from_world() {
+        let mut world = World::new();
+        world.init_resource::<Config>();
+        world.get_resource_mut::<Config>().unwrap().cache_size = 8;
+        world.init_resource::<AssetCache>();
+        assert_eq!(world.get_resource::<AssetCache>().unwrap().slots.len(), 8);
+
+        // Both are already there.
+        world.get_resource_mut::<AssetCache>().unwrap().slots.clear();
+        world.init_resource::<Config>();
+        world.init_resource::<AssetCache>();
+        assert_eq!(world.get_resource::<Config>().unwrap().cache_size, 8);
+        assert!(world.get_resource::<AssetCache>().unwrap().slots.is_empty());
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Time(f32);
+
+    #[test]
+    fn nested_overrides_restore_in_reverse_order() {
+        let mut world = World::new();
+        world.insert_resource(Time(12.5));
+        world.clear_trackers();
+        {
+            let mut paused = world.override_resource(Time(0.0));
+            {
+                let mut stepped = paused.override_resource(Time(1.0));
+                stepped.get_resource_mut::<Time>().unwrap().0 += 0.5;
+                assert_eq!(stepped.get_resource::<Time>(), Some(&Time(1.5)));
+            }
+            assert_eq!(paused.get_resource::<Time>(), Some(&Time(0.0)));
+        }
+        assert_eq!(world.get_resource::<Time>(), Some(&Time(12.5)));
+        assert!(!world.is_resource_changed::<Time>());
+    }
+
+    #[test]
+    fn overriding_a_missing_resource_removes_it_afterwards() {
+        let mut world = World::new();
+        let seen = world.with_resource(Time(3.0), |world| world.get_resource::<Time>().unwrap().0);
+        assert_eq!(seen, 3.0);
+        assert!(!world.contains_resource::<Time>());
+    }
+
+    #[test]
+    fn a_panic_in_the_closure_still_restores() {
+        let mut world = World::new();
+        world.insert_resource(Time(12.5));
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            world.with_resource(Time(0.0), |world| {
+                world.remove_resource::<Time>();
+                panic!("system failed");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(world.get_resource::<Time>(), Some(&Time(12.5)));
+    }
+
+    struct Module(&'static str, alloc::sync::Arc<()>);
+
+    #[test]
+    fn resources_are_listed_and_removed_by_id() {
+        let mut world = World::new();
+        world.insert_resource(Gravity(9.8));
+        world.insert_resource(Time(0.5));
+        world.clear_trackers();
+        world.get_resource_mut::<Time>().unwrap().0 = 1.0;
+        let mut infos: Vec<ResourceInfo> = world.iter_resources().collect();
+        infos.sort_by_key(|info| info.name);
+        assert_eq!(infos.len(), 2);
+        assert_eq!((infos[0].name, infos[0].type_id), (type_name::<Gravity>(), TypeId::of::<Gravity>()));
+        assert_eq!((infos[1].name, infos[1].size), (type_name::<Time>(), mem::size_of::<Time>()));
+        assert!(infos[1].ticks.changed.is_newer_than(infos[0].ticks.changed, world.change_tick()));
+
+        // The type is only known as an id, like the resources of an unloaded module.
+        let gravity = infos[0].type_id;
+        assert!(world.remove_resource_by_id(gravity));
+        assert!(!world.remove_resource_by_id(gravity));
+        assert!(!world.contains_resource::<Gravity>());
+        assert_eq!(world.iter_resources().count(), 1);
+    }
+
+    #[test]
+    fn clearing_drops_every_resource() {
+        let mut world = World::new();
+        let handle = alloc::sync::Arc::new(());
+        world.insert_resource(Module("physics", handle.clone()));
+        world.insert_resource((Module("audio", handle.clone()), 0u8));
+        world.set_resource_drop_last::<Module>(true);
+        let e = *world.spawn_entity();
+        assert_eq!(alloc::sync::Arc::strong_count(&handle), 3);
+        world.clear_resources();
+        assert_eq!(alloc::sync::Arc::strong_count(&handle), 1);
+        assert_eq!(world.iter_resources().count(), 0);
+        assert!(world.is_alive(e));
+    }
+}
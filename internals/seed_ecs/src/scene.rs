@@ -0,0 +1,516 @@
+//! Scenes keyed by stable component names and versions, so that saves survive renamed modules and
+//! components whose layout changed.
+//!
+//! A [`Scene`] is plain data: the encoders write it out as is, the names and versions are part of
+//! it. A [`SceneRegistry`] lists the component types of the game under their stable names, with
+//! the migrations bringing the values saved by older versions up to date.
+
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::any::TypeId;
+
+use crate::component::Component;
+use crate::entity::{DanglingPolicy, Entity, EntityMapper, MapEntities};
+use crate::utils::HashMap;
+use crate::World;
+
+/// A component value in a scene, shaped like the values of the serialization formats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Unit,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    String(String),
+    Seq(Vec<Value>),
+    /// Named fields, in order.
+    Map(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// The field `name` of a map.
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        match self {
+            Self::Map(fields) => fields.iter().find(|(field, _)| field == name).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Self::Int(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Integers are read as floats too.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Self::Float(value) => Some(*value),
+            Self::Int(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// Components that can be saved in a [`Scene`].
+pub trait SceneComponent: Sized + Component {
+    fn to_value(&self) -> Value;
+
+    /// `None` if the value doesn't describe a `Self`, the load reports it as invalid.
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+/// A saved component, with the name and version it was registered under when saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneComponentData {
+    pub name: String,
+    pub version: u32,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SceneEntity {
+    /// The bits of the entity in the saved world, only meaningful inside the scene.
+    pub id: u64,
+    pub components: Vec<SceneComponentData>,
+}
+
+/// The entities saved by [`SceneRegistry::save`], in ascending order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Scene {
+    pub entities: Vec<SceneEntity>,
+}
+
+/// What [`SceneRegistry::load`] did. A scene is always loaded as far as possible, the components
+/// that could not be are counted here by name, sorted by name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct SceneLoadReport {
+    /// The entities spawned, one for each entity of the scene and in the same order.
+    pub spawned: Vec<Entity>,
+    /// Number of components inserted, the migrated ones included.
+    pub loaded: usize,
+    /// Number of components inserted after migrating them from an older version.
+    pub migrated: usize,
+    /// The components of no registered name.
+    pub unknown: Vec<(String, usize)>,
+    /// The components saved by a newer version, missing a migration step or rejected by
+    /// [`SceneComponent::from_value`].
+    pub invalid: Vec<(String, usize)>,
+}
+
+type SaveFn = fn(&World) -> Vec<(Entity, Value)>;
+// Returns false if the value was rejected.
+type LoadFn = fn(&mut World, Entity, &Value, &mut EntityMapper) -> bool;
+type RemoveFn = fn(&mut World, Entity);
+type Migration = Box<dyn Fn(Value) -> Value + Send + Sync>;
+
+struct SceneType {
+    name: String,
+    version: u32,
+    type_id: TypeId,
+    save: SaveFn,
+    load: LoadFn,
+    remove: RemoveFn,
+}
+
+fn save<T: SceneComponent>(world: &World) -> Vec<(Entity, Value)> {
+    world.iter_components::<T>().map(|(entity, value)| (entity, value.to_value())).collect()
+}
+
+fn load<T: SceneComponent>(world: &mut World, entity: Entity, value: &Value, _mapper: &mut EntityMapper) -> bool {
+    match T::from_value(value) {
+        Some(component) => {
+            world.add_component(entity, component);
+            true
+        }
+        None => false,
+    }
+}
+
+fn remove<T: SceneComponent>(world: &mut World, entity: Entity) {
+    world.remove_component::<T>(entity);
+}
+
+fn load_mapped<T: SceneComponent + MapEntities>(
+    world: &mut World,
+    entity: Entity,
+    value: &Value,
+    mapper: &mut EntityMapper,
+) -> bool {
+    match T::from_value(value) {
+        Some(mut component) => {
+            component.map_entities(mapper);
+            world.add_component(entity, component);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The component types saved and loaded by scenes, under names that stay the same when the types
+/// are moved or renamed.
+#[derive(Default)]
+pub struct SceneRegistry {
+    types: Vec<SceneType>,
+    by_name: HashMap<String, usize>,
+    // The steps from each version to the next, by name.
+    migrations: HashMap<String, Vec<(u32, Migration)>>,
+}
+
+impl SceneRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Saves and loads `T` as `name`, the values are saved with `version`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name or the type is already registered.
+    pub fn register_named<T: SceneComponent>(&mut self, name: &str, version: u32) -> &mut Self {
+        self.register::<T>(name, version, load::<T>)
+    }
+
+    /// Same as [`SceneRegistry::register_named`] for the components storing entities, saved as
+    /// their bits. Loading rewrites them to the entities spawned for the scene, the ones outside
+    /// of the scene become [`Entity::PLACEHOLDER`].
+    pub fn register_named_mapped<T: SceneComponent + MapEntities>(&mut self, name: &str, version: u32) -> &mut Self {
+        self.register::<T>(name, version, load_mapped::<T>)
+    }
+
+    fn register<T: SceneComponent>(&mut self, name: &str, version: u32, load: LoadFn) -> &mut Self {
+        assert!(!self.by_name.contains_key(name), "Scene name {} is already registered", name);
+        let type_id = TypeId::of::<T>();
+        if let Some(other) = self.types.iter().find(|ty| ty.type_id == type_id) {
+            panic!("{} is already registered as {}", core::any::type_name::<T>(), other.name);
+        }
+        self.by_name.insert(name.to_string(), self.types.len());
+        self.types.push(SceneType {
+            name: name.to_string(),
+            version,
+            type_id,
+            save: save::<T>,
+            load,
+            remove: remove::<T>,
+        });
+        self
+    }
+
+    /// Upgrades the values of `name` saved at version `from` to version `from + 1`, the load
+    /// chains the steps up to the registered version. Registering a step again replaces it.
+    pub fn register_migration(
+        &mut self,
+        name: &str,
+        from: u32,
+        migration: impl Fn(Value) -> Value + Send + Sync + 'static,
+    ) -> &mut Self {
+        let steps = self.migrations.entry(name.to_string()).or_default();
+        steps.retain(|(version, _)| *version != from);
+        steps.push((from, Box::new(migration)));
+        self
+    }
+
+    /// Saves the registered components of the world, the entities without any are left out.
+    pub fn save(&self, world: &World) -> Scene {
+        let mut entities: BTreeMap<Entity, Vec<SceneComponentData>> = BTreeMap::new();
+        for ty in &self.types {
+            for (entity, value) in (ty.save)(world) {
+                entities.entry(entity).or_default().push(SceneComponentData {
+                    name: ty.name.clone(),
+                    version: ty.version,
+                    value,
+                });
+            }
+        }
+        Scene {
+            entities: entities
+                .into_iter()
+                .map(|(entity, components)| SceneEntity {
+                    id: entity.to_bits(),
+                    components,
+                })
+                .collect(),
+        }
+    }
+
+    /// Spawns the entities of the scene and inserts the components it can, migrating the ones
+    /// saved by older versions.
+    pub fn load(&self, scene: &Scene, world: &mut World) -> SceneLoadReport {
+        let mut report = SceneLoadReport::default();
+        let mut unknown: BTreeMap<&str, usize> = BTreeMap::new();
+        let mut invalid: BTreeMap<&str, usize> = BTreeMap::new();
+        // Every entity is spawned first, for the components referencing the ones saved after them.
+        let mut mapper = EntityMapper::new(DanglingPolicy::MapToDead);
+        for saved in &scene.entities {
+            let entity = *world.spawn_entity();
+            report.spawned.push(entity);
+            if let Some(id) = Entity::from_bits(saved.id) {
+                mapper.insert(id, entity);
+            }
+        }
+        for (i, saved) in scene.entities.iter().enumerate() {
+            let entity = report.spawned[i];
+            for component in &saved.components {
+                let name = component.name.as_str();
+                let Some(ty) = self.by_name.get(name).map(|index| &self.types[*index]) else {
+                    *unknown.entry(name).or_default() += 1;
+                    continue;
+                };
+                let loaded = self
+                    .migrate(component, ty.version)
+                    .is_some_and(|value| (ty.load)(world, entity, &value, &mut mapper));
+                if !loaded {
+                    *invalid.entry(name).or_default() += 1;
+                    continue;
+                }
+                report.loaded += 1;
+                if component.version < ty.version {
+                    report.migrated += 1;
+                }
+            }
+        }
+        report.unknown = unknown.into_iter().map(|(name, count)| (name.to_string(), count)).collect();
+        report.invalid = invalid.into_iter().map(|(name, count)| (name.to_string(), count)).collect();
+        report
+    }
+
+    // The name and the values of each registered type, in registration order.
+    pub(crate) fn save_by_type(&self, world: &World) -> Vec<(&str, Vec<(Entity, Value)>)> {
+        self.types.iter().map(|ty| (ty.name.as_str(), (ty.save)(world))).collect()
+    }
+
+    pub(crate) fn is_registered(&self, type_id: TypeId) -> bool {
+        self.types.iter().any(|ty| ty.type_id == type_id)
+    }
+
+    // Inserts a value of the current version, false if the name is unknown or the value rejected.
+    pub(crate) fn load_one(&self, name: &str, world: &mut World, entity: Entity, value: &Value, mapper: &mut EntityMapper) -> bool {
+        let ty = self.by_name.get(name).map(|index| &self.types[*index]);
+        ty.is_some_and(|ty| (ty.load)(world, entity, value, mapper))
+    }
+
+    pub(crate) fn remove_one(&self, name: &str, world: &mut World, entity: Entity) -> bool {
+        let ty = self.by_name.get(name).map(|index| &self.types[*index]);
+        ty.map(|ty| (ty.remove)(world, entity)).is_some()
+    }
+
+    fn migrate(&self, component: &SceneComponentData, version: u32) -> Option<Value> {
+        if component.version > version {
+            return None;
+        }
+        let mut value = component.value.clone();
+        for from in component.version..version {
+            let steps = self.migrations.get(component.name.as_str())?;
+            let (_, migration) = steps.iter().find(|(step, _)| *step == from)?;
+            value = migration(value);
+        }
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::EntityWeak;
+
+    // The first version of the position, saved as a pair.
+    #[derive(Debug, PartialEq, Component)]
+    struct OldPosition(f32, f32);
+    #[derive(Debug, PartialEq, Component)]
+    struct Position {
+        x: f32,
+        y: f32,
+    }
+    #[derive(Debug, PartialEq, Component)]
+    struct Legacy(String);
+
+    impl SceneComponent for OldPosition {
+        fn to_value(&self) -> Value {
+            Value::Seq(vec![Value::Float(self.0 as f64), Value::Float(self.1 as f64)])
+        }
+
+        fn from_value(value: &Value) -> Option<Self> {
+            match value {
+                Value::Seq(xy) => Some(Self(xy.first()?.as_float()? as f32, xy.get(1)?.as_float()? as f32)),
+                _ => None,
+            }
+        }
+    }
+
+    impl SceneComponent for Position {
+        fn to_value(&self) -> Value {
+            Value::Map(vec![
+                ("x".to_string(), Value::Float(self.x as f64)),
+                ("y".to_string(), Value::Float(self.y as f64)),
+            ])
+        }
+
+        fn from_value(value: &Value) -> Option<Self> {
+            Some(Self {
+                x: value.field("x")?.as_float()? as f32,
+                y: value.field("y")?.as_float()? as f32,
+            })
+        }
+    }
+
+    impl SceneComponent for Legacy {
+        fn to_value(&self) -> Value {
+            Value::String(self.0.clone())
+        }
+
+        fn from_value(value: &Value) -> Option<Self> {
+            value.as_str().map(|name| Self(name.to_string()))
+        }
+    }
+
+    fn old_scene() -> Scene {
+        let mut registry = SceneRegistry::new();
+        registry
+            .register_named::<OldPosition>("game::Position", 1)
+            .register_named::<Legacy>("game::Legacy", 1);
+        let mut world = World::new();
+        let a = *world.spawn_entity();
+        world.add_component(a, OldPosition(1.0, 2.0));
+        world.add_component(a, Legacy("a".to_string()));
+        world.spawn_entity();
+        let b = *world.spawn_entity();
+        world.add_component(b, Legacy("b".to_string()));
+        world.add_component(b, OldPosition(3.0, 4.0));
+        let c = *world.spawn_entity();
+        world.add_component(c, Legacy("c".to_string()));
+        registry.save(&world)
+    }
+
+    #[test]
+    fn old_scenes_load_through_migrations() {
+        let scene = old_scene();
+        assert_eq!(scene.entities.len(), 3);
+        assert_eq!(
+            scene.entities[0].components[0],
+            SceneComponentData {
+                name: "game::Position".to_string(),
+                version: 1,
+                value: Value::Seq(vec![Value::Float(1.0), Value::Float(2.0)]),
+            }
+        );
+
+        let mut registry = SceneRegistry::new();
+        registry
+            .register_named::<Position>("game::Position", 2)
+            .register_migration("game::Position", 1, |old| match old {
+                Value::Seq(xy) if xy.len() == 2 => Value::Map(vec![
+                    ("x".to_string(), xy[0].clone()),
+                    ("y".to_string(), xy[1].clone()),
+                ]),
+                other => other,
+            });
+        let mut world = World::new();
+        let report = registry.load(&scene, &mut world);
+        assert_eq!(report.spawned.len(), 3);
+        assert_eq!((report.loaded, report.migrated), (2, 2));
+        assert_eq!(report.unknown, [("game::Legacy".to_string(), 3)]);
+        assert!(report.invalid.is_empty());
+        assert_eq!(world.get_component::<Position>(report.spawned[0]), Some(&Position { x: 1.0, y: 2.0 }));
+        assert_eq!(world.get_component::<Position>(report.spawned[1]), Some(&Position { x: 3.0, y: 4.0 }));
+        assert!(world.is_alive(report.spawned[2]) && !world.has_component::<Position>(report.spawned[2]));
+
+        // Saving again writes the current version, which loads without migrating.
+        let resaved = registry.save(&world);
+        assert!(resaved.entities.iter().all(|entity| entity.components[0].version == 2));
+        let report = registry.load(&resaved, &mut World::new());
+        assert_eq!((report.loaded, report.migrated), (2, 0));
+    }
+
+    #[test]
+    fn values_that_cant_be_migrated_are_reported() {
+        let mut scene = old_scene();
+        scene.entities[2].components.push(SceneComponentData {
+            name: "game::Position".to_string(),
+            version: 3,
+            value: Value::Unit,
+        });
+        let mut registry = SceneRegistry::new();
+        registry.register_named::<Position>("game::Position", 2);
+        let report = registry.load(&scene, &mut World::new());
+        assert_eq!(report.loaded, 0);
+        // Two without a migration step and one saved by a newer version.
+        assert_eq!(report.invalid, [("game::Position".to_string(), 3)]);
+        assert_eq!(report.unknown, [("game::Legacy".to_string(), 3)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "already registered")]
+    fn names_are_unique() {
+        let mut registry = SceneRegistry::new();
+        registry.register_named::<Position>("game::Position", 1).register_named::<OldPosition>("game::Position", 1);
+    }
+
+    // A quest remembering the NPC to talk to, which may be gone by the time it is read.
+    #[derive(Debug, PartialEq, Component)]
+    struct Quest(EntityWeak);
+
+    impl SceneComponent for Quest {
+        fn to_value(&self) -> Value {
+            Value::Int(self.0.to_bits() as i64)
+        }
+
+        fn from_value(value: &Value) -> Option<Self> {
+            EntityWeak::from_bits(value.as_int()? as u64).map(Self)
+        }
+    }
+
+    impl MapEntities for Quest {
+        fn map_entities(&mut self, mapper: &mut EntityMapper) {
+            self.0.map_entities(mapper);
+        }
+    }
+
+    #[test]
+    fn weak_references_are_remapped_on_load() {
+        let mut registry = SceneRegistry::new();
+        registry.register_named_mapped::<Quest>("game::Quest", 1);
+        registry.register_named::<Position>("game::Position", 2);
+        let mut world = World::new();
+        let giver = *world.spawn_entity();
+        let npc = *world.spawn_entity();
+        let gone = *world.spawn_entity();
+        world.add_component(npc, Position { x: 1.0, y: 0.0 });
+        world.add_component(giver, Quest(npc.downgrade()));
+        world.add_component(npc, Quest(gone.downgrade()));
+        world.despawn_entity(gone);
+        let scene = registry.save(&world);
+
+        // Entities already there take the saved indices.
+        let mut loaded = World::new();
+        for _ in 0..5 {
+            loaded.spawn_entity();
+        }
+        let report = registry.load(&scene, &mut loaded);
+        assert_eq!(report.loaded, 3);
+        let [giver, npc] = [report.spawned[0], report.spawned[1]];
+        let Quest(target) = loaded.get_component::<Quest>(giver).unwrap();
+        assert_eq!(target.upgrade(&loaded), Some(npc));
+        assert_eq!(loaded.get_component::<Position>(npc), Some(&Position { x: 1.0, y: 0.0 }));
+        // The despawned entity wasn't saved.
+        let Quest(target) = loaded.get_component::<Quest>(npc).unwrap();
+        assert_eq!(*target, Entity::PLACEHOLDER.downgrade());
+        assert_eq!(target.upgrade(&loaded), None);
+    }
+}
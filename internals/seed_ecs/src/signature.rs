@@ -0,0 +1,155 @@
+//! The set of components each entity has, for the editors filtering entities by their exact
+//! components.
+
+use alloc::vec::Vec;
+use core::iter;
+
+use crate::component::{ComponentId, ComponentSet};
+use crate::entity::Entity;
+use crate::utils::BMask;
+use crate::World;
+
+impl World {
+    /// The components the entity has, `None` if it is not alive. The set is read from the
+    /// storage masks, so it is up to date with every insertion and removal.
+    pub fn entity_signature(&self, entity: Entity) -> Option<ComponentSet> {
+        if !self.entities.is_alive(entity) {
+            return None;
+        }
+        let index = entity.index() as usize;
+        let ids = (0..self.storages.len()).map(ComponentId::new);
+        Some(ids.filter(|id| self.storages.get(*id).contains(index)).collect())
+    }
+
+    /// The entities having every component of `set`, and no other if `exact`, in index order.
+    /// Disabled entities are included, [`Disabled`](crate::query::Disabled) being one more
+    /// component of their signature.
+    pub fn entities_with_signature(&self, set: &ComponentSet, exact: bool) -> Vec<Entity> {
+        let count = self.storages.len();
+        // No entity has a component the world doesn't know.
+        if set.iter().any(|id| id.index() >= count) {
+            return Vec::new();
+        }
+        let mask = |id: ComponentId| self.storages.get(id).mask();
+        let required: Vec<&BMask> = set.iter().map(mask).collect();
+        let others: Vec<&BMask> = match exact {
+            true => (0..count).map(ComponentId::new).filter(|id| !set.contains(*id)).map(mask).collect(),
+            false => Vec::new(),
+        };
+        let alive = self.entities.mask();
+        let driver = required.iter().copied().chain(iter::once(alive)).min_by_key(|mask| mask.word_count()).unwrap();
+        let mut entities = Vec::new();
+        let mut next_word = 0;
+        while let Some(word_idx) = driver.next_word(next_word) {
+            next_word = word_idx + 1;
+            let mut bits = required.iter().fold(alive.word(word_idx), |bits, mask| bits & mask.word(word_idx));
+            for other in &others {
+                if bits == 0 {
+                    break;
+                }
+                bits &= !other.word(word_idx);
+            }
+            while bits != 0 {
+                let index = (word_idx << 5) | bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                entities.push(self.entities.get(index as u32).unwrap());
+            }
+        }
+        entities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::testing::Rng;
+
+    #[derive(Component)]
+    struct Position(u32);
+    #[derive(Component)]
+    struct Velocity(u32);
+    #[derive(Component)]
+    struct Player;
+
+    fn set(ids: &[Option<ComponentId>]) -> ComponentSet {
+        ids.iter().map(|id| id.unwrap()).collect()
+    }
+
+    #[test]
+    fn signatures_follow_inserts_and_removals() {
+        let mut world = World::new();
+        let e = *world.spawn_entity();
+        assert_eq!(world.entity_signature(e), Some(ComponentSet::new()));
+        world.add_component(e, Position(0));
+        world.add_component(e, Player);
+        let (position, player) = (world.components().id::<Position>(), world.components().id::<Player>());
+        assert_eq!(world.entity_signature(e), Some(set(&[position, player])));
+        world.entity_mut(e).replace(|position: Position| Velocity(position.0)).unwrap();
+        let velocity = world.components().id::<Velocity>();
+        assert_eq!(world.entity_signature(e), Some(set(&[player, velocity])));
+        world.remove_component::<Player>(e);
+        assert_eq!(world.entity_signature(e), Some(set(&[velocity])));
+        world.despawn_entity(e);
+        assert_eq!(world.entity_signature(e), None);
+    }
+
+    #[test]
+    fn exact_and_superset_matches() {
+        let mut world = World::new();
+        let moving = world.spawn_batch_iter((0..3).map(|i| (Position(i), Velocity(i))));
+        let still = world.spawn_batch((0..2).map(Position));
+        let player = world.spawn_batch_iter([(Position(9), Velocity(9), Player)])[0];
+        let (position, velocity) = (world.components().id::<Position>(), world.components().id::<Velocity>());
+
+        let both = set(&[position, velocity]);
+        let mut exact = world.entities_with_signature(&both, true);
+        assert_eq!(exact, moving);
+        exact.push(player);
+        assert_eq!(world.entities_with_signature(&both, false), exact);
+        let positions = world.entities_with_signature(&set(&[position]), true);
+        assert_eq!(positions, still);
+        assert_eq!(world.entities_with_signature(&ComponentSet::new(), false).len(), 6);
+        assert!(world.entities_with_signature(&ComponentSet::new(), true).is_empty());
+        let unknown: ComponentSet = [ComponentId::new(1000)].into_iter().collect();
+        assert!(world.entities_with_signature(&unknown, false).is_empty());
+    }
+
+    #[test]
+    fn signatures_match_the_storage_masks() {
+        let mut world = World::new();
+        let mut entities: Vec<Entity> = world.spawn_batch((0..40).map(Position));
+        let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+        for step in 0..400u32 {
+            let e = entities[rng.below(entities.len())];
+            match rng.below(5) {
+                0 => {
+                    world.add_component(e, Velocity(step));
+                }
+                1 => {
+                    world.remove_component::<Velocity>(e);
+                }
+                2 => {
+                    world.add_component(e, Player);
+                }
+                3 => {
+                    world.remove_component::<Position>(e);
+                }
+                _ => {
+                    world.despawn_entity(e);
+                    entities.retain(|other| *other != e);
+                    entities.push(*world.spawn_entity());
+                }
+            }
+        }
+        world.validate().unwrap();
+        for e in &entities {
+            let signature = world.entity_signature(*e).unwrap();
+            for info in world.components().iter() {
+                let stored = world.storages.get(info.id()).contains(e.index() as usize);
+                assert_eq!(signature.contains(info.id()), stored, "{:?} {}", e, info.name());
+            }
+            assert!(world.entities_with_signature(&signature, true).contains(e));
+        }
+    }
+}
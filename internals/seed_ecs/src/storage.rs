@@ -0,0 +1,1434 @@
+use alloc::alloc::{alloc, dealloc, handle_alloc_error, realloc};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::any::Any;
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem;
+use core::ops::Range;
+use core::ptr::{self, NonNull};
+use core::slice;
+
+use crate::change_detection::{ComponentTicks, Tick};
+use crate::component::{ComponentId, DropFn, RemovalPolicy, StorageKind};
+use crate::interned::{InternOps, Interned};
+use crate::utils::{BMask, BVec, SparseSet, StableSet};
+
+/// Memory usage of a component storage.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StorageStats {
+    /// Number of components stored.
+    pub live: usize,
+    /// Number of value slots allocated.
+    pub capacity_slots: usize,
+    /// Bytes allocated for the values and the occupancy mask.
+    pub bytes_allocated: usize,
+    /// Number of value pages allocated, for the storages that allocate their values by page.
+    pub pages: usize,
+    /// Number of distinct values of an interned storage, zero for the others.
+    pub distinct_values: usize,
+}
+
+/// Type erased operations every storage supports.
+pub(crate) trait AnyStorage: Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+    /// Drops the component at `index`, returns false if there was none.
+    fn remove(&mut self, index: usize) -> bool;
+    /// Drops the components at the ascending `indices`, returns how many there were.
+    fn remove_many(&mut self, indices: &[usize]) -> usize;
+    fn contains(&self, index: usize) -> bool;
+    fn mask(&self) -> &BMask;
+    fn stats(&self) -> StorageStats;
+    /// Releases the memory no component uses anymore, the indices of the components don't change.
+    /// Closes the holes of the tombstone sparse sets, recording the moves.
+    fn compact(&mut self);
+    /// Allocates the mask words for the indices below `len`, not the values.
+    fn reserve_mask(&mut self, len: usize);
+    /// Creates an empty storage for the same component type.
+    fn empty(&self) -> Box<UnsafeCell<dyn AnyStorage>>;
+    /// Moves the component at `index` into `dst` at `dst_index`, `dst` must store the same type.
+    /// The component counts as added to `dst` at `tick`.
+    fn move_to(&mut self, index: usize, dst: &mut dyn AnyStorage, dst_index: usize, tick: Tick) -> bool;
+    /// Moves the component at `from` to the empty `to` with its ticks, at the same position in a
+    /// sparse set storage. Returns false if there was none.
+    fn relocate(&mut self, from: usize, to: usize) -> bool;
+    /// Moves the value behind `value` in at `index` at `tick`, dropping the previous one, returns
+    /// true if there was one.
+    ///
+    /// # Safety
+    ///
+    /// `value` must point to a valid value of the stored type, it may be unaligned. The value is
+    /// owned by the storage afterwards and must not be used or dropped by the caller.
+    unsafe fn insert_ptr(&mut self, index: usize, value: *const u8, tick: Tick) -> bool;
+    /// Moves the component at `index` out to `dst`, returns false if there was none.
+    ///
+    /// # Safety
+    ///
+    /// `dst` must be valid for writes of a value of the stored type, it may be unaligned. The
+    /// value written is owned by the caller.
+    unsafe fn take_ptr(&mut self, index: usize, dst: *mut u8) -> bool;
+    fn get_ptr(&self, index: usize) -> Option<NonNull<u8>>;
+    fn get_mut_ptr(&mut self, index: usize) -> Option<NonNull<u8>>;
+    /// Clamps the ticks of the components, see [`Tick::check_tick`].
+    fn check_change_ticks(&mut self, tick: Tick);
+    /// The position of the component at `index` in a sparse set storage.
+    fn position(&self, index: usize) -> Option<usize>;
+    /// Exchanges the components at two positions of a sparse set storage.
+    fn swap_positions(&mut self, a: usize, b: usize);
+    /// The slot events recorded since they were last drained, `None` if they are not recorded.
+    fn slot_events(&mut self) -> Option<&mut Vec<SlotEvent>>;
+    /// Grows whenever components are added or removed, see [`Storage::structural_changes`].
+    fn structural_changes(&self) -> u64 {
+        self.mask().changes()
+    }
+}
+
+/// A value of a storage that took a slot, moved or left its slot, recorded for
+/// [`World::on_storage_relocate`](crate::World::on_storage_relocate). The slots are the positions
+/// of the sparse sets and the indices for the other storages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SlotEvent {
+    Inserted { index: usize, slot: usize },
+    Moved { index: usize, from: usize, to: usize },
+    Removed { index: usize, slot: usize },
+}
+
+fn record(log: &mut Option<Vec<SlotEvent>>, event: SlotEvent) {
+    if let Some(log) = log {
+        log.push(event);
+    }
+}
+
+fn touch(touched: &mut Option<BMask>, index: usize) {
+    if let Some(touched) = touched {
+        touched.add(index);
+    }
+}
+
+enum Inner<T> {
+    // The ticks of a value sit at the same index as the value.
+    Dense(BVec<T>, BVec<ComponentTicks>),
+    // Zero sized values carry no data, only the mask is kept.
+    Tag(BMask, PhantomData<T>),
+    // The ticks of a value sit at the same position as the value.
+    Sparse(SparseSet<T>, Vec<ComponentTicks>),
+    // Equal values are stored once, see `World::register_interned`.
+    Interned(Interned<T>),
+    // A sparse set with holes, see `RemovalPolicy::Tombstone`. The ticks of a value sit at the
+    // same position as the value, the ones of the holes are stale.
+    Stable(StableSet<T>, Vec<ComponentTicks>),
+}
+
+/// Stores all the components of type `T` of a world, indexed by entity index.
+pub struct Storage<T> {
+    inner: Inner<T>,
+    slot_log: Option<Vec<SlotEvent>>,
+    // The indices whose value was inserted, removed or lent mutably, for the value indices.
+    touched: Option<BMask>,
+    // The changes of the masks the storage had before its current one, see
+    // `Storage::structural_changes`.
+    epoch: u64,
+}
+
+impl<T> Storage<T> {
+    pub fn new(kind: StorageKind) -> Self {
+        let inner = match kind {
+            StorageKind::Dense => Inner::Dense(BVec::new(), BVec::new()),
+            StorageKind::Tag => {
+                assert_eq!(mem::size_of::<T>(), 0, "Tag storage only holds zero sized types");
+                Inner::Tag(BMask::new(), PhantomData)
+            }
+            StorageKind::SparseSet => {
+                assert_ne!(mem::size_of::<T>(), 0, "Sparse set storage only holds sized types");
+                Inner::Sparse(SparseSet::new(), Vec::new())
+            }
+            StorageKind::Blob => panic!("Blob storage is only for components registered by descriptor"),
+            StorageKind::Interned => panic!("Interned storages are made by `World::register_interned`"),
+        };
+        Self { inner, slot_log: None, touched: None, epoch: 0 }
+    }
+
+    // Swaps the values for another layout of the same, counted as a structural change.
+    fn replace_inner(&mut self, inner: Inner<T>) {
+        self.epoch += self.mask().changes() + 1;
+        self.inner = inner;
+    }
+
+    /// Grows whenever values are added or removed, or move to another layout. An unchanged count
+    /// means the same indices hold a value, see [`BMask::changes`].
+    pub fn structural_changes(&self) -> u64 {
+        self.epoch + self.mask().changes()
+    }
+
+    /// Starts marking the indices whose value may have changed.
+    pub(crate) fn track_touched(&mut self) {
+        self.touched.get_or_insert_with(BMask::new);
+    }
+
+    /// The indices marked since the last call, see [`Storage::track_touched`].
+    pub(crate) fn take_touched(&mut self) -> BMask {
+        self.touched.as_mut().map(mem::take).unwrap_or_default()
+    }
+
+    // Marks every stored index before lending all the values mutably.
+    fn touch_all(&mut self) {
+        if let Some(mut touched) = self.touched.take() {
+            for index in self.mask().iter() {
+                touched.add(index);
+            }
+            self.touched = Some(touched);
+        }
+    }
+
+    /// Starts recording the [`SlotEvent`]s, returns the slots of the values already stored.
+    pub(crate) fn record_slots(&mut self) -> Vec<(usize, usize)> {
+        self.slot_log.get_or_insert_with(Vec::new);
+        self.mask().iter().map(|index| (index, self.position(index).unwrap_or(index))).collect()
+    }
+
+    pub fn kind(&self) -> StorageKind {
+        match self.inner {
+            Inner::Dense(..) => StorageKind::Dense,
+            Inner::Tag(..) => StorageKind::Tag,
+            Inner::Sparse(..) | Inner::Stable(..) => StorageKind::SparseSet,
+            Inner::Interned(..) => StorageKind::Interned,
+        }
+    }
+
+    /// What removals do to the other values of a sparse set storage, `None` for the others.
+    pub fn removal_policy(&self) -> Option<RemovalPolicy> {
+        match self.inner {
+            Inner::Sparse(..) => Some(RemovalPolicy::SwapRemove),
+            Inner::Stable(..) => Some(RemovalPolicy::Tombstone),
+            _ => None,
+        }
+    }
+
+    /// Moves the values of a dense storage to an interned storage, which keeps one copy of each
+    /// distinct value.
+    pub(crate) fn make_interned(&mut self, ops: InternOps<T>) {
+        let Inner::Dense(vec, ticks) = &mut self.inner else {
+            assert_eq!(self.kind(), StorageKind::Interned, "Only dense storages can become interned");
+            return;
+        };
+        let mut interned = Interned::new(ops);
+        let indices: Vec<usize> = vec.mask().iter().collect();
+        for index in indices {
+            let tick = ticks.remove(index).unwrap();
+            interned.insert(index, vec.remove(index).unwrap(), tick.added);
+            interned.ticks_mut().insert(index, tick);
+        }
+        self.replace_inner(Inner::Interned(interned));
+    }
+
+    /// Moves the values of a dense storage to a sparse set, in index order. The values of a
+    /// tombstone sparse set keep their order and close the holes.
+    pub fn make_sparse(&mut self) {
+        if let Inner::Stable(stable, ticks) = &mut self.inner {
+            let mut set = SparseSet::new();
+            let mut packed_ticks = Vec::with_capacity(stable.len());
+            for (position, index, value) in stable.drain() {
+                if position != set.len() {
+                    record(&mut self.slot_log, SlotEvent::Moved { index, from: position, to: set.len() });
+                }
+                set.insert(index, value);
+                packed_ticks.push(ticks[position]);
+            }
+            self.replace_inner(Inner::Sparse(set, packed_ticks));
+            return;
+        }
+        let Inner::Dense(vec, ticks) = &mut self.inner else {
+            assert_eq!(self.kind(), StorageKind::SparseSet, "Only dense storages can become sparse sets");
+            return;
+        };
+        let mut set = SparseSet::new();
+        let mut packed_ticks = Vec::with_capacity(vec.len());
+        let indices: Vec<usize> = vec.mask().iter().collect();
+        for index in indices {
+            if index != set.len() {
+                record(&mut self.slot_log, SlotEvent::Moved { index, from: index, to: set.len() });
+            }
+            set.insert(index, vec.remove(index).unwrap());
+            packed_ticks.push(ticks.remove(index).unwrap());
+        }
+        self.replace_inner(Inner::Sparse(set, packed_ticks));
+    }
+
+    /// Moves the values of a dense storage or of a sparse set to a tombstone sparse set, the
+    /// values of a sparse set keep their positions. See [`RemovalPolicy::Tombstone`].
+    pub fn make_stable(&mut self) {
+        let mut stable = StableSet::new();
+        let mut stable_ticks = Vec::new();
+        match &mut self.inner {
+            Inner::Stable(..) => return,
+            Inner::Sparse(set, ticks) => {
+                stable_ticks = mem::take(ticks);
+                for (index, value) in mem::take(set).into_entries() {
+                    stable.insert(index, value);
+                }
+            }
+            Inner::Dense(vec, ticks) => {
+                let indices: Vec<usize> = vec.mask().iter().collect();
+                for index in indices {
+                    if index != stable.len() {
+                        record(&mut self.slot_log, SlotEvent::Moved { index, from: index, to: stable.len() });
+                    }
+                    stable.insert(index, vec.remove(index).unwrap());
+                    stable_ticks.push(ticks.remove(index).unwrap());
+                }
+            }
+            _ => panic!("Only dense storages and sparse sets can become tombstone sparse sets"),
+        }
+        self.replace_inner(Inner::Stable(stable, stable_ticks));
+    }
+
+    /// The position of the value at `index` in a sparse set storage.
+    #[inline]
+    pub fn position(&self, index: usize) -> Option<usize> {
+        match &self.inner {
+            Inner::Sparse(set, _) => set.position(index),
+            Inner::Stable(set, _) => set.position(index),
+            _ => None,
+        }
+    }
+
+    /// Exchanges the values at two positions of a sparse set storage.
+    pub fn swap_positions(&mut self, a: usize, b: usize) {
+        let Inner::Sparse(set, ticks) = &mut self.inner else {
+            panic!("Only sparse set storages have positions");
+        };
+        set.swap(a, b);
+        ticks.swap(a, b);
+        if a != b {
+            let (at_a, at_b) = (set.indices()[a] as usize, set.indices()[b] as usize);
+            record(&mut self.slot_log, SlotEvent::Moved { index: at_a, from: b, to: a });
+            record(&mut self.slot_log, SlotEvent::Moved { index: at_b, from: a, to: b });
+        }
+    }
+
+    /// Stores `value` at `index` and returns the value that was there before if any. The value
+    /// is added at `tick`, or changed at `tick` if it replaces another one.
+    pub fn insert(&mut self, index: usize, value: T, tick: Tick) -> Option<T> {
+        touch(&mut self.touched, index);
+        let log = &mut self.slot_log;
+        match &mut self.inner {
+            Inner::Dense(vec, ticks) => {
+                let previous = vec.insert(index, value);
+                match ticks.get_mut(index) {
+                    Some(ticks) if previous.is_some() => ticks.changed = tick,
+                    _ => {
+                        ticks.insert(index, ComponentTicks::new(tick));
+                        record(log, SlotEvent::Inserted { index, slot: index });
+                    }
+                }
+                previous
+            }
+            Inner::Sparse(set, ticks) => {
+                let previous = set.insert(index, value);
+                match previous {
+                    Some(_) => ticks[set.position(index).unwrap()].changed = tick,
+                    None => {
+                        ticks.push(ComponentTicks::new(tick));
+                        record(log, SlotEvent::Inserted { index, slot: set.len() - 1 });
+                    }
+                }
+                previous
+            }
+            Inner::Stable(set, ticks) => {
+                let previous = set.insert(index, value);
+                let position = set.position(index).unwrap();
+                match previous {
+                    Some(_) => ticks[position].changed = tick,
+                    None => {
+                        if position == ticks.len() {
+                            ticks.push(ComponentTicks::new(tick));
+                        } else {
+                            ticks[position] = ComponentTicks::new(tick);
+                        }
+                        record(log, SlotEvent::Inserted { index, slot: position });
+                    }
+                }
+                previous
+            }
+            Inner::Interned(interned) => {
+                let previous = interned.insert(index, value, tick);
+                if previous.is_none() {
+                    record(log, SlotEvent::Inserted { index, slot: index });
+                }
+                previous
+            }
+            Inner::Tag(mask, _) => {
+                // The value is kept "inside" the mask bit and given back by `remove`.
+                mem::forget(value);
+                if mask.is_present(index) {
+                    Some(unsafe { conjure() })
+                } else {
+                    mask.add(index);
+                    record(log, SlotEvent::Inserted { index, slot: index });
+                    None
+                }
+            }
+        }
+    }
+
+    /// Stores each value at its index as added or changed at `tick`, dropping the values it
+    /// replaces. Sorted indices are the fast path of the dense storages, see
+    /// [`BVec::insert_many`].
+    pub fn insert_many(&mut self, values: Vec<(usize, T)>, tick: Tick) {
+        match &mut self.inner {
+            Inner::Dense(vec, ticks) => {
+                for (index, _) in &values {
+                    touch(&mut self.touched, *index);
+                    if let Some(ticks) = ticks.get_mut(*index) {
+                        ticks.changed = tick;
+                    }
+                }
+                let added = values.iter().filter(|(index, _)| !vec.contains(*index));
+                if let Some(log) = &mut self.slot_log {
+                    log.extend(added.clone().map(|(index, _)| SlotEvent::Inserted { index: *index, slot: *index }));
+                }
+                ticks.insert_many(added.map(|(index, _)| (*index, ComponentTicks::new(tick))));
+                vec.insert_many(values);
+            }
+            _ => {
+                for (index, value) in values {
+                    self.insert(index, value, tick);
+                }
+            }
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        match &self.inner {
+            Inner::Dense(vec, _) => vec.get(index),
+            Inner::Sparse(set, _) => set.get(index),
+            Inner::Stable(set, _) => set.get(index),
+            Inner::Interned(interned) => interned.get(index),
+            Inner::Tag(mask, _) => mask
+                .is_present(index)
+                .then(|| unsafe { NonNull::<T>::dangling().as_ref() }),
+        }
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        touch(&mut self.touched, index);
+        match &mut self.inner {
+            Inner::Dense(vec, _) => vec.get_mut(index),
+            Inner::Sparse(set, _) => set.get_mut(index),
+            Inner::Stable(set, _) => set.get_mut(index),
+            Inner::Interned(interned) => interned.get_mut(index),
+            Inner::Tag(mask, _) => mask
+                .is_present(index)
+                .then(|| unsafe { NonNull::<T>::dangling().as_mut() }),
+        }
+    }
+
+    /// Allocates what is needed to store values at the indices below `len`.
+    pub fn reserve(&mut self, len: usize) {
+        match &mut self.inner {
+            Inner::Dense(vec, ticks) => {
+                vec.reserve(len);
+                ticks.reserve(len);
+            }
+            Inner::Sparse(set, ticks) => {
+                set.reserve(len);
+                ticks.reserve(len.saturating_sub(ticks.len()));
+            }
+            Inner::Stable(set, ticks) => {
+                set.reserve(len);
+                ticks.reserve(len.saturating_sub(ticks.len()));
+            }
+            Inner::Interned(interned) => interned.reserve(len),
+            Inner::Tag(mask, _) => mask.reserve(len),
+        }
+    }
+
+    /// Allocates the mask words for the indices below `len`, adding values there may still
+    /// allocate for the values, see [`World::grow_to`](crate::World::grow_to).
+    pub fn reserve_mask(&mut self, len: usize) {
+        match &mut self.inner {
+            Inner::Dense(vec, _) => vec.reserve_mask(len),
+            Inner::Sparse(set, _) => set.reserve_mask(len),
+            Inner::Stable(set, _) => set.reserve_mask(len),
+            Inner::Interned(interned) => interned.reserve_mask(len),
+            Inner::Tag(mask, _) => mask.reserve(len),
+        }
+    }
+
+    /// The ticks of the value at `index`, `None` for zero sized values.
+    #[inline]
+    pub fn get_ticks(&self, index: usize) -> Option<&ComponentTicks> {
+        match &self.inner {
+            Inner::Dense(_, ticks) => ticks.get(index),
+            Inner::Sparse(set, ticks) => Some(&ticks[set.position(index)?]),
+            Inner::Stable(set, ticks) => Some(&ticks[set.position(index)?]),
+            Inner::Interned(interned) => interned.ticks().get(index),
+            Inner::Tag(..) => None,
+        }
+    }
+
+    /// The value at `index` along with its ticks, which zero sized values don't have.
+    #[inline]
+    pub fn get_with_ticks_mut(&mut self, index: usize) -> Option<(&mut T, Option<&mut ComponentTicks>)> {
+        touch(&mut self.touched, index);
+        match &mut self.inner {
+            Inner::Dense(vec, ticks) => Some((vec.get_mut(index)?, ticks.get_mut(index))),
+            Inner::Sparse(set, ticks) => {
+                let position = set.position(index)?;
+                Some((&mut set.values_mut()[position], Some(&mut ticks[position])))
+            }
+            Inner::Stable(set, ticks) => {
+                let position = set.position(index)?;
+                Some((set.get_mut(index)?, Some(&mut ticks[position])))
+            }
+            Inner::Interned(interned) => interned.get_with_ticks_mut(index).map(|(value, ticks)| (value, Some(ticks))),
+            Inner::Tag(mask, _) => mask
+                .is_present(index)
+                .then(|| (unsafe { NonNull::<T>::dangling().as_mut() }, None)),
+        }
+    }
+
+    /// How many of the indices at the start of `range` can be sliced together: all of them
+    /// unless the values of a sparse set are not in index order.
+    pub fn contiguous_len(&self, range: Range<usize>) -> usize {
+        match &self.inner {
+            Inner::Sparse(set, _) => {
+                let Some(first) = set.position(range.start) else {
+                    return 0;
+                };
+                range.clone().zip(first..).take_while(|(index, position)| set.position(*index) == Some(*position)).count()
+            }
+            // Every value is in a slot of its own.
+            Inner::Interned(_) | Inner::Stable(..) => range.len().min(1),
+            _ => range.len(),
+        }
+    }
+
+    /// The values at the indices of `range` as one slice.
+    ///
+    /// # Safety
+    ///
+    /// Every index of `range` must hold a value, and the range must not be empty nor cross a
+    /// multiple of `PAGE_SIZE`. For sparse sets the whole range must be
+    /// [contiguous](Storage::contiguous_len).
+    pub unsafe fn slice(&self, range: Range<usize>) -> &[T] {
+        match &self.inner {
+            Inner::Dense(vec, _) => vec.slice(range),
+            Inner::Sparse(set, _) => {
+                let first = set.position(range.start).unwrap_unchecked();
+                set.values().get_unchecked(first..first + range.len())
+            }
+            Inner::Interned(interned) => slice::from_ref(interned.get(range.start).unwrap_unchecked()),
+            Inner::Stable(set, _) => slice::from_ref(set.get(range.start).unwrap_unchecked()),
+            Inner::Tag(..) => slice::from_raw_parts(NonNull::dangling().as_ptr(), range.len()),
+        }
+    }
+
+    /// The values at the indices of `range` as one slice, marked changed at `tick`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Storage::slice`].
+    pub unsafe fn slice_mut(&mut self, range: Range<usize>, tick: Tick) -> &mut [T] {
+        for index in range.clone() {
+            touch(&mut self.touched, index);
+        }
+        match &mut self.inner {
+            Inner::Dense(vec, ticks) => {
+                for ticks in ticks.slice_mut(range.clone()) {
+                    ticks.changed = tick;
+                }
+                vec.slice_mut(range)
+            }
+            Inner::Sparse(set, ticks) => {
+                let first = set.position(range.start).unwrap_unchecked();
+                let positions = first..first + range.len();
+                for ticks in ticks.get_unchecked_mut(positions.clone()) {
+                    ticks.changed = tick;
+                }
+                set.values_mut().get_unchecked_mut(positions)
+            }
+            Inner::Interned(interned) => {
+                let (value, ticks) = interned.get_with_ticks_mut(range.start).unwrap_unchecked();
+                ticks.changed = tick;
+                slice::from_mut(value)
+            }
+            Inner::Stable(set, ticks) => {
+                ticks.get_unchecked_mut(set.position(range.start).unwrap_unchecked()).changed = tick;
+                slice::from_mut(set.get_mut(range.start).unwrap_unchecked())
+            }
+            Inner::Tag(..) => slice::from_raw_parts_mut(NonNull::dangling().as_ptr(), range.len()),
+        }
+    }
+
+    /// The values at `positions` of a sparse set storage.
+    ///
+    /// # Safety
+    ///
+    /// The storage must be a sparse set with a value at every position.
+    pub unsafe fn positions(&self, positions: Range<usize>) -> &[T] {
+        match &self.inner {
+            Inner::Sparse(set, _) => set.values().get_unchecked(positions),
+            _ => unreachable!("Only sparse set storages have positions"),
+        }
+    }
+
+    /// The values at `positions` of a sparse set storage, marked changed at `tick`.
+    ///
+    /// # Safety
+    ///
+    /// Same as [`Storage::positions`].
+    pub unsafe fn positions_mut(&mut self, positions: Range<usize>, tick: Tick) -> &mut [T] {
+        match &mut self.inner {
+            Inner::Sparse(set, ticks) => {
+                for ticks in ticks.get_unchecked_mut(positions.clone()) {
+                    ticks.changed = tick;
+                }
+                for index in set.indices().get_unchecked(positions.clone()) {
+                    touch(&mut self.touched, *index as usize);
+                }
+                set.values_mut().get_unchecked_mut(positions)
+            }
+            _ => unreachable!("Only sparse set storages have positions"),
+        }
+    }
+
+    /// Removes the value at `index`, the last value of a sparse set takes its position unless it
+    /// leaves a hole, see [`RemovalPolicy::Tombstone`].
+    pub fn take(&mut self, index: usize) -> Option<T> {
+        touch(&mut self.touched, index);
+        let log = &mut self.slot_log;
+        match &mut self.inner {
+            Inner::Dense(vec, ticks) => {
+                ticks.remove(index);
+                let value = vec.remove(index)?;
+                record(log, SlotEvent::Removed { index, slot: index });
+                Some(value)
+            }
+            Inner::Sparse(set, ticks) => {
+                let position = set.position(index)?;
+                ticks.swap_remove(position);
+                let value = set.remove(index);
+                record(log, SlotEvent::Removed { index, slot: position });
+                if let Some(moved) = set.indices().get(position) {
+                    let moved = SlotEvent::Moved {
+                        index: *moved as usize,
+                        from: set.len(),
+                        to: position,
+                    };
+                    record(log, moved);
+                }
+                value
+            }
+            Inner::Stable(set, _) => {
+                let position = set.position(index)?;
+                let value = set.remove(index);
+                record(log, SlotEvent::Removed { index, slot: position });
+                value
+            }
+            Inner::Interned(interned) => {
+                let value = interned.take(index)?;
+                record(log, SlotEvent::Removed { index, slot: index });
+                Some(value)
+            }
+            Inner::Tag(mask, _) => {
+                if !mask.is_present(index) {
+                    return None;
+                }
+                mask.remove(index);
+                record(log, SlotEvent::Removed { index, slot: index });
+                Some(unsafe { conjure() })
+            }
+        }
+    }
+
+    /// Drops the value at `index`, returns false if there was none. Interned storages don't copy
+    /// it out like [`Storage::take`] would when other entities share it.
+    pub fn remove(&mut self, index: usize) -> bool {
+        let Inner::Interned(interned) = &mut self.inner else {
+            return self.take(index).is_some();
+        };
+        touch(&mut self.touched, index);
+        let removed = interned.remove(index);
+        if removed {
+            record(&mut self.slot_log, SlotEvent::Removed { index, slot: index });
+        }
+        removed
+    }
+
+    /// Drops the values at the ascending `indices` and returns how many there were. Dense
+    /// storages update their masks once per word, see [`BVec::remove_many`].
+    pub fn remove_many(&mut self, indices: &[usize]) -> usize {
+        let Inner::Dense(vec, ticks) = &mut self.inner else {
+            return indices.iter().filter(|index| self.remove(**index)).count();
+        };
+        if self.touched.is_some() || self.slot_log.is_some() {
+            for index in indices.iter().filter(|index| vec.contains(**index)) {
+                touch(&mut self.touched, *index);
+                record(&mut self.slot_log, SlotEvent::Removed { index: *index, slot: *index });
+            }
+        }
+        ticks.remove_many(indices.iter().copied());
+        vec.remove_many(indices.iter().copied())
+    }
+
+    /// Moves the value at `from` to the empty `to` with its ticks, a sparse set keeps it at its
+    /// position. Recorded as removed from `from` and inserted at `to`, the entity changes.
+    pub fn relocate(&mut self, from: usize, to: usize) -> bool {
+        let slot = match &mut self.inner {
+            Inner::Dense(vec, ticks) => {
+                let Some(value) = vec.remove(from) else {
+                    return false;
+                };
+                vec.insert(to, value);
+                if let Some(moved) = ticks.remove(from) {
+                    ticks.insert(to, moved);
+                }
+                None
+            }
+            Inner::Sparse(set, _) => {
+                if !set.rekey(from, to) {
+                    return false;
+                }
+                set.position(to)
+            }
+            Inner::Stable(set, _) => {
+                if !set.rekey(from, to) {
+                    return false;
+                }
+                set.position(to)
+            }
+            Inner::Interned(interned) => {
+                if !interned.relocate(from, to) {
+                    return false;
+                }
+                None
+            }
+            Inner::Tag(mask, _) => {
+                if !mask.is_present(from) {
+                    return false;
+                }
+                mask.remove(from);
+                mask.add(to);
+                None
+            }
+        };
+        touch(&mut self.touched, from);
+        touch(&mut self.touched, to);
+        record(&mut self.slot_log, SlotEvent::Removed { index: from, slot: slot.unwrap_or(from) });
+        record(&mut self.slot_log, SlotEvent::Inserted { index: to, slot: slot.unwrap_or(to) });
+        true
+    }
+
+    #[inline]
+    pub fn contains(&self, index: usize) -> bool {
+        self.mask().is_present(index)
+    }
+
+    #[inline]
+    pub fn mask(&self) -> &BMask {
+        match &self.inner {
+            Inner::Dense(vec, _) => vec.mask(),
+            Inner::Sparse(set, _) => set.mask(),
+            Inner::Stable(set, _) => set.mask(),
+            Inner::Interned(interned) => interned.handles().mask(),
+            Inner::Tag(mask, _) => mask,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.mask().len()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        self.touch_all();
+        let (dense, sparse, interned, tags, stable) = match &mut self.inner {
+            Inner::Dense(vec, _) => (Some(vec.iter_mut()), None, None, None, None),
+            Inner::Sparse(set, _) => (None, Some(set.iter_mut()), None, None, None),
+            Inner::Interned(interned) => (None, None, Some(interned.iter_with_ticks_mut()), None, None),
+            Inner::Tag(mask, _) => (None, None, None, Some(mask.iter()), None),
+            Inner::Stable(set, _) => (None, None, None, None, Some(set.iter_mut())),
+        };
+        let interned = interned.into_iter().flatten().map(|(index, value, _)| (index, value));
+        let tags = tags
+            .into_iter()
+            .flatten()
+            .map(|index| (index, unsafe { NonNull::<T>::dangling().as_mut() }));
+        let sparse = sparse.into_iter().flatten().chain(stable.into_iter().flatten());
+        dense.into_iter().flatten().chain(sparse).chain(interned).chain(tags)
+    }
+
+    /// Same as [`Storage::iter_mut`] with the ticks of each value, `None` for zero sized values.
+    pub fn iter_with_ticks_mut(&mut self) -> impl Iterator<Item = (usize, &mut T, Option<&mut ComponentTicks>)> + '_ {
+        self.touch_all();
+        let (dense, sparse, interned, tags, stable) = match &mut self.inner {
+            Inner::Dense(vec, ticks) => (Some(vec.iter_mut().zip(ticks.iter_mut())), None, None, None, None),
+            Inner::Sparse(set, ticks) => (None, Some(set.iter_mut().zip(ticks.iter_mut())), None, None, None),
+            Inner::Interned(interned) => (None, None, Some(interned.iter_with_ticks_mut()), None, None),
+            Inner::Tag(mask, _) => (None, None, None, Some(mask.iter()), None),
+            Inner::Stable(set, ticks) => (None, None, None, None, Some(set.iter_with_mut(ticks))),
+        };
+        // The ticks share the mask of the values, or their positions.
+        let dense = dense.into_iter().flatten().map(|((index, value), (_, ticks))| (index, value, Some(ticks)));
+        let sparse = sparse.into_iter().flatten().map(|((index, value), ticks)| (index, value, Some(ticks)));
+        let interned = interned.into_iter().flatten().map(|(index, value, ticks)| (index, value, Some(ticks)));
+        let stable = stable.into_iter().flatten().map(|(index, value, ticks)| (index, value, Some(ticks)));
+        let tags = tags
+            .into_iter()
+            .flatten()
+            .map(|index| (index, unsafe { NonNull::<T>::dangling().as_mut() }, None));
+        dense.chain(sparse).chain(interned).chain(tags).chain(stable)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mask().is_empty()
+    }
+
+    pub fn stats(&self) -> StorageStats {
+        match &self.inner {
+            Inner::Dense(vec, ticks) => StorageStats {
+                live: vec.len(),
+                capacity_slots: vec.capacity(),
+                bytes_allocated: vec.allocated_bytes() + ticks.allocated_bytes(),
+                pages: vec.page_count(),
+                distinct_values: 0,
+            },
+            Inner::Sparse(set, ticks) => StorageStats {
+                live: set.len(),
+                capacity_slots: set.capacity(),
+                bytes_allocated: set.allocated_bytes() + ticks.capacity() * mem::size_of::<ComponentTicks>(),
+                pages: set.page_count(),
+                distinct_values: 0,
+            },
+            Inner::Stable(set, ticks) => StorageStats {
+                live: set.len(),
+                capacity_slots: set.capacity(),
+                bytes_allocated: set.allocated_bytes() + ticks.capacity() * mem::size_of::<ComponentTicks>(),
+                pages: set.page_count(),
+                distinct_values: 0,
+            },
+            Inner::Interned(interned) => StorageStats {
+                live: interned.handles().len(),
+                capacity_slots: interned.handles().capacity(),
+                bytes_allocated: interned.allocated_bytes(),
+                pages: interned.handles().page_count(),
+                distinct_values: interned.slot_count(),
+            },
+            Inner::Tag(mask, _) => StorageStats {
+                live: mask.len(),
+                capacity_slots: 0,
+                bytes_allocated: mask.allocated_bytes(),
+                pages: 0,
+                distinct_values: 0,
+            },
+        }
+    }
+
+    /// Releases the memory no value uses anymore. Tombstone sparse sets close their holes, which
+    /// moves the values after them.
+    pub fn compact(&mut self) {
+        let log = &mut self.slot_log;
+        match &mut self.inner {
+            Inner::Dense(vec, ticks) => {
+                vec.compact();
+                ticks.compact();
+            }
+            Inner::Sparse(set, ticks) => {
+                set.compact();
+                ticks.shrink_to_fit();
+            }
+            Inner::Stable(set, ticks) => {
+                set.compact(|index, from, to| {
+                    ticks[to] = ticks[from];
+                    record(log, SlotEvent::Moved { index, from, to });
+                });
+                ticks.truncate(set.len());
+                ticks.shrink_to_fit();
+            }
+            Inner::Interned(interned) => interned.compact(),
+            Inner::Tag(mask, _) => mask.shrink_to_fit(),
+        }
+    }
+}
+
+// Produces a value of a zero sized type. Only sound for values that were previously forgotten.
+unsafe fn conjure<T>() -> T {
+    debug_assert_eq!(mem::size_of::<T>(), 0);
+    ptr::read(NonNull::<T>::dangling().as_ptr())
+}
+
+impl<T> Drop for Storage<T> {
+    fn drop(&mut self) {
+        if let Inner::Tag(mask, _) = &self.inner {
+            if mem::needs_drop::<T>() {
+                for _ in mask.iter() {
+                    unsafe { drop(conjure::<T>()) }
+                }
+            }
+        }
+    }
+}
+
+impl<T: Send + Sync + 'static> AnyStorage for Storage<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        Storage::remove(self, index)
+    }
+
+    fn remove_many(&mut self, indices: &[usize]) -> usize {
+        Storage::remove_many(self, indices)
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        Storage::contains(self, index)
+    }
+
+    fn mask(&self) -> &BMask {
+        Storage::mask(self)
+    }
+
+    fn stats(&self) -> StorageStats {
+        Storage::stats(self)
+    }
+
+    fn compact(&mut self) {
+        Storage::compact(self)
+    }
+
+    fn reserve_mask(&mut self, len: usize) {
+        Storage::reserve_mask(self, len)
+    }
+
+    fn empty(&self) -> Box<UnsafeCell<dyn AnyStorage>> {
+        let storage = match &self.inner {
+            Inner::Interned(interned) => Storage {
+                inner: Inner::Interned(interned.empty()),
+                slot_log: None,
+                touched: None,
+                epoch: 0,
+            },
+            Inner::Stable(..) => Storage {
+                inner: Inner::Stable(StableSet::new(), Vec::new()),
+                slot_log: None,
+                touched: None,
+                epoch: 0,
+            },
+            _ => Storage::<T>::new(self.kind()),
+        };
+        Box::new(UnsafeCell::new(storage))
+    }
+
+    fn move_to(&mut self, index: usize, dst: &mut dyn AnyStorage, dst_index: usize, tick: Tick) -> bool {
+        let dst = dst.as_any_mut().downcast_mut::<Storage<T>>().expect("Storage type mismatch");
+        match self.take(index) {
+            Some(value) => {
+                dst.insert(dst_index, value, tick);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) -> bool {
+        Storage::relocate(self, from, to)
+    }
+
+    unsafe fn insert_ptr(&mut self, index: usize, value: *const u8, tick: Tick) -> bool {
+        self.insert(index, ptr::read_unaligned(value as *const T), tick).is_some()
+    }
+
+    unsafe fn take_ptr(&mut self, index: usize, dst: *mut u8) -> bool {
+        match self.take(index) {
+            Some(value) => {
+                ptr::write_unaligned(dst as *mut T, value);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_ptr(&self, index: usize) -> Option<NonNull<u8>> {
+        self.get(index).map(|value| NonNull::from(value).cast())
+    }
+
+    fn get_mut_ptr(&mut self, index: usize) -> Option<NonNull<u8>> {
+        self.get_mut(index).map(|value| NonNull::from(value).cast())
+    }
+
+    fn check_change_ticks(&mut self, tick: Tick) {
+        match &mut self.inner {
+            Inner::Dense(_, ticks) => ticks.iter_mut().for_each(|(_, ticks)| ticks.check_ticks(tick)),
+            Inner::Sparse(_, ticks) | Inner::Stable(_, ticks) => ticks.iter_mut().for_each(|ticks| ticks.check_ticks(tick)),
+            Inner::Interned(interned) => interned.ticks_mut().iter_mut().for_each(|(_, ticks)| ticks.check_ticks(tick)),
+            Inner::Tag(..) => {}
+        }
+    }
+
+    fn position(&self, index: usize) -> Option<usize> {
+        Storage::position(self, index)
+    }
+
+    fn swap_positions(&mut self, a: usize, b: usize) {
+        Storage::swap_positions(self, a, b)
+    }
+
+    fn slot_events(&mut self) -> Option<&mut Vec<SlotEvent>> {
+        self.slot_log.as_mut()
+    }
+
+    fn structural_changes(&self) -> u64 {
+        Storage::structural_changes(self)
+    }
+}
+
+/// Stores the components registered by descriptor as raw bytes, indexed by entity index.
+pub(crate) struct BlobStorage {
+    mask: BMask,
+    data: NonNull<u8>,
+    // In values, the values are laid out every `item.size()` bytes.
+    capacity: usize,
+    item: Layout,
+    drop: Option<DropFn>,
+}
+
+// The values are plain bytes to the world, it is up to whoever described the component to only
+// use the world from threads the values can be sent to and shared with.
+unsafe impl Send for BlobStorage {}
+unsafe impl Sync for BlobStorage {}
+
+impl BlobStorage {
+    pub fn new(layout: Layout, drop: Option<DropFn>) -> Self {
+        let item = layout.pad_to_align();
+        Self {
+            mask: BMask::new(),
+            data: NonNull::new(ptr::without_provenance_mut(item.align())).unwrap(),
+            capacity: 0,
+            item,
+            drop,
+        }
+    }
+
+    fn slot(&self, index: usize) -> *mut u8 {
+        unsafe { self.data.as_ptr().add(index * self.item.size()) }
+    }
+
+    fn array_layout(&self, capacity: usize) -> Layout {
+        let size = self.item.size().checked_mul(capacity).expect("Blob storage capacity overflow");
+        Layout::from_size_align(size, self.item.align()).expect("Blob storage capacity overflow")
+    }
+
+    fn reserve(&mut self, index: usize) {
+        if index < self.capacity {
+            return;
+        }
+        self.resize((index + 1).next_power_of_two().max(4));
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        if capacity == 0 {
+            if self.capacity != 0 && self.item.size() != 0 {
+                unsafe { dealloc(self.data.as_ptr(), self.array_layout(self.capacity)) };
+            }
+            self.data = NonNull::new(ptr::without_provenance_mut(self.item.align())).unwrap();
+        } else if self.item.size() != 0 {
+            let new_layout = self.array_layout(capacity);
+            let data = if self.capacity == 0 {
+                unsafe { alloc(new_layout) }
+            } else {
+                let old_layout = self.array_layout(self.capacity);
+                unsafe { realloc(self.data.as_ptr(), old_layout, new_layout.size()) }
+            };
+            self.data = NonNull::new(data).unwrap_or_else(|| handle_alloc_error(new_layout));
+        }
+        self.capacity = capacity;
+    }
+
+    unsafe fn drop_slot(&mut self, index: usize) {
+        if let Some(drop) = self.drop {
+            drop(self.slot(index).cast());
+        }
+    }
+}
+
+impl Drop for BlobStorage {
+    fn drop(&mut self) {
+        let present: Vec<usize> = self.mask.iter().collect();
+        for index in present {
+            unsafe { self.drop_slot(index) };
+        }
+        if self.capacity != 0 && self.item.size() != 0 {
+            unsafe { dealloc(self.data.as_ptr(), self.array_layout(self.capacity)) };
+        }
+    }
+}
+
+impl AnyStorage for BlobStorage {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn remove(&mut self, index: usize) -> bool {
+        if !self.mask.is_present(index) {
+            return false;
+        }
+        unsafe { self.drop_slot(index) };
+        self.mask.remove(index);
+        true
+    }
+
+    fn remove_many(&mut self, indices: &[usize]) -> usize {
+        let present: Vec<usize> = indices.iter().copied().filter(|index| self.mask.is_present(*index)).collect();
+        self.mask.remove_sorted(&present);
+        for index in &present {
+            unsafe { self.drop_slot(*index) };
+        }
+        present.len()
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.mask.is_present(index)
+    }
+
+    fn mask(&self) -> &BMask {
+        &self.mask
+    }
+
+    fn stats(&self) -> StorageStats {
+        StorageStats {
+            live: self.mask.len(),
+            capacity_slots: self.capacity,
+            bytes_allocated: self.item.size() * self.capacity + self.mask.allocated_bytes(),
+            pages: 0,
+            distinct_values: 0,
+        }
+    }
+
+    fn compact(&mut self) {
+        // The values are contiguous, only the slots past the last one can be released.
+        let len = self.mask.iter().last().map_or(0, |index| index + 1);
+        if len < self.capacity {
+            self.resize(len);
+        }
+        self.mask.shrink_to_fit();
+    }
+
+    fn reserve_mask(&mut self, len: usize) {
+        self.mask.reserve(len);
+    }
+
+    fn empty(&self) -> Box<UnsafeCell<dyn AnyStorage>> {
+        Box::new(UnsafeCell::new(BlobStorage::new(self.item, self.drop)))
+    }
+
+    fn move_to(&mut self, index: usize, dst: &mut dyn AnyStorage, dst_index: usize, tick: Tick) -> bool {
+        let dst = dst.as_any_mut().downcast_mut::<BlobStorage>().expect("Storage type mismatch");
+        assert_eq!(self.item, dst.item, "Storage type mismatch");
+        if !self.mask.is_present(index) {
+            return false;
+        }
+        // The bytes now belong to `dst`, the slot is forgotten here.
+        unsafe { dst.insert_ptr(dst_index, self.slot(index), tick) };
+        self.mask.remove(index);
+        true
+    }
+
+    fn relocate(&mut self, from: usize, to: usize) -> bool {
+        if !self.mask.is_present(from) {
+            return false;
+        }
+        self.reserve(to);
+        unsafe { ptr::copy_nonoverlapping(self.slot(from), self.slot(to), self.item.size()) };
+        self.mask.remove(from);
+        self.mask.add(to);
+        true
+    }
+
+    unsafe fn take_ptr(&mut self, index: usize, dst: *mut u8) -> bool {
+        if !self.mask.is_present(index) {
+            return false;
+        }
+        // The bytes now belong to the caller, the slot is forgotten here.
+        ptr::copy_nonoverlapping(self.slot(index), dst, self.item.size());
+        self.mask.remove(index);
+        true
+    }
+
+    // Values described at runtime can only be reached by id, they don't track changes.
+    unsafe fn insert_ptr(&mut self, index: usize, value: *const u8, _tick: Tick) -> bool {
+        self.reserve(index);
+        let previous = self.mask.is_present(index);
+        if previous {
+            self.drop_slot(index);
+        }
+        ptr::copy_nonoverlapping(value, self.slot(index), self.item.size());
+        self.mask.add(index);
+        previous
+    }
+
+    fn get_ptr(&self, index: usize) -> Option<NonNull<u8>> {
+        self.mask.is_present(index).then(|| unsafe { NonNull::new_unchecked(self.slot(index)) })
+    }
+
+    fn get_mut_ptr(&mut self, index: usize) -> Option<NonNull<u8>> {
+        self.get_ptr(index)
+    }
+
+    fn check_change_ticks(&mut self, _tick: Tick) {}
+
+    fn position(&self, _index: usize) -> Option<usize> {
+        None
+    }
+
+    fn swap_positions(&mut self, _a: usize, _b: usize) {
+        panic!("Only sparse set storages have positions");
+    }
+
+    fn slot_events(&mut self) -> Option<&mut Vec<SlotEvent>> {
+        None
+    }
+}
+
+/// The storages of a world indexed by [`ComponentId`].
+///
+/// Storages sit behind an `UnsafeCell` so that queries can borrow several of them mutably at once
+/// through an [`UnsafeWorldCell`](crate::UnsafeWorldCell).
+#[derive(Default)]
+pub(crate) struct Storages {
+    storages: Vec<Box<UnsafeCell<dyn AnyStorage>>>,
+}
+
+// Storages are only mutated through `&mut self` or by the callers of the unsafe methods, who must
+// uphold the borrow rules.
+unsafe impl Sync for Storages {}
+
+impl Storages {
+    /// Adds the storage for the component of id `id`, ids must be pushed in order.
+    pub fn push<T: Send + Sync + 'static>(&mut self, id: ComponentId, kind: StorageKind) {
+        debug_assert_eq!(id.index(), self.storages.len());
+        self.storages.push(Box::new(UnsafeCell::new(Storage::<T>::new(kind))));
+    }
+
+    /// Adds the storage for a component registered by descriptor.
+    pub fn push_blob(&mut self, id: ComponentId, layout: Layout, drop: Option<DropFn>) {
+        debug_assert_eq!(id.index(), self.storages.len());
+        self.storages.push(Box::new(UnsafeCell::new(BlobStorage::new(layout, drop))));
+    }
+
+    /// Adds an empty storage shaped like `model`, for the component of id `id`.
+    pub fn push_like(&mut self, id: ComponentId, model: &dyn AnyStorage) {
+        debug_assert_eq!(id.index(), self.storages.len());
+        self.storages.push(model.empty());
+    }
+
+    pub fn len(&self) -> usize {
+        self.storages.len()
+    }
+
+    pub fn get(&self, id: ComponentId) -> &dyn AnyStorage {
+        unsafe { &*self.storages[id.index()].get() }
+    }
+
+    pub fn get_mut(&mut self, id: ComponentId) -> &mut dyn AnyStorage {
+        self.storages[id.index()].get_mut()
+    }
+
+    pub fn typed<T: 'static>(&self, id: ComponentId) -> &Storage<T> {
+        self.get(id).as_any().downcast_ref().expect("Storage type mismatch")
+    }
+
+    pub fn typed_mut<T: 'static>(&mut self, id: ComponentId) -> &mut Storage<T> {
+        self.get_mut(id).as_any_mut().downcast_mut().expect("Storage type mismatch")
+    }
+
+    /// # Safety
+    ///
+    /// The caller must make sure nothing else accesses the storage while the pointer is used
+    /// mutably.
+    pub unsafe fn typed_ptr<T: 'static>(&self, id: ComponentId) -> NonNull<Storage<T>> {
+        let storage = &mut *self.storages[id.index()].get();
+        NonNull::from(storage.as_any_mut().downcast_mut::<Storage<T>>().expect("Storage type mismatch"))
+    }
+
+    /// # Safety
+    ///
+    /// The caller must make sure the storage is not borrowed mutably.
+    pub unsafe fn mask_unchecked(&self, id: ComponentId) -> &BMask {
+        (*self.storages[id.index()].get()).mask()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut (dyn AnyStorage + 'static)> {
+        self.storages.iter_mut().map(|storage| storage.get_mut())
+    }
+
+    /// Swaps the storage of `id` for another one of the same component type.
+    #[cfg(test)]
+    pub fn replace(&mut self, id: ComponentId, storage: Box<UnsafeCell<dyn AnyStorage>>) {
+        self.storages[id.index()] = storage;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::component::Component;
+    use crate::entity::Entity;
+    use crate::World;
+
+    static DROPS: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Component)]
+    struct Score(u32);
+
+    // A storage that claims to drop its values but keeps them, for the despawn checks.
+    struct Leaky(Storage<Score>);
+
+    impl AnyStorage for Leaky {
+        fn as_any(&self) -> &dyn Any {
+            self.0.as_any()
+        }
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self.0.as_any_mut()
+        }
+        fn remove(&mut self, index: usize) -> bool {
+            self.0.contains(index)
+        }
+        fn remove_many(&mut self, indices: &[usize]) -> usize {
+            indices.iter().filter(|index| self.0.contains(**index)).count()
+        }
+        fn contains(&self, index: usize) -> bool {
+            self.0.contains(index)
+        }
+        fn mask(&self) -> &BMask {
+            self.0.mask()
+        }
+        fn stats(&self) -> StorageStats {
+            self.0.stats()
+        }
+        fn compact(&mut self) {
+            self.0.compact()
+        }
+        fn reserve_mask(&mut self, len: usize) {
+            self.0.reserve_mask(len)
+        }
+        fn empty(&self) -> Box<UnsafeCell<dyn AnyStorage>> {
+            Box::new(UnsafeCell::new(Leaky(Storage::new(self.0.kind()))))
+        }
+        fn move_to(&mut self, index: usize, dst: &mut dyn AnyStorage, dst_index: usize, tick: Tick) -> bool {
+            self.0.move_to(index, dst, dst_index, tick)
+        }
+        fn relocate(&mut self, from: usize, to: usize) -> bool {
+            Storage::relocate(&mut self.0, from, to)
+        }
+        unsafe fn insert_ptr(&mut self, index: usize, value: *const u8, tick: Tick) -> bool {
+            self.0.insert_ptr(index, value, tick)
+        }
+        unsafe fn take_ptr(&mut self, index: usize, dst: *mut u8) -> bool {
+            self.0.take_ptr(index, dst)
+        }
+        fn get_ptr(&self, index: usize) -> Option<NonNull<u8>> {
+            self.0.get_ptr(index)
+        }
+        fn get_mut_ptr(&mut self, index: usize) -> Option<NonNull<u8>> {
+            self.0.get_mut_ptr(index)
+        }
+        fn check_change_ticks(&mut self, tick: Tick) {
+            self.0.check_change_ticks(tick)
+        }
+        fn position(&self, index: usize) -> Option<usize> {
+            Storage::position(&self.0, index)
+        }
+        fn swap_positions(&mut self, a: usize, b: usize) {
+            Storage::swap_positions(&mut self.0, a, b)
+        }
+        fn slot_events(&mut self) -> Option<&mut Vec<SlotEvent>> {
+            self.0.slot_events()
+        }
+    }
+
+    fn leaky_world() -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        let entities = world.spawn_batch([Score(3), Score(4)]);
+        let id = world.components.id::<Score>().unwrap();
+        let mut leaky = Leaky(Storage::new(StorageKind::Dense));
+        for entity in &entities {
+            leaky.0.insert(entity.index() as usize, Score(7), Tick::default());
+        }
+        world.storages.replace(id, Box::new(UnsafeCell::new(leaky)));
+        (world, entities)
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    #[should_panic(expected = "still has a value")]
+    fn despawns_catch_the_storages_keeping_values() {
+        let (mut world, entities) = leaky_world();
+        world.despawn_entity(entities[0]);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    #[should_panic(expected = "still has a value")]
+    fn batch_despawns_catch_the_storages_keeping_values() {
+        let (mut world, entities) = leaky_world();
+        world.despawn_batch(&entities);
+    }
+
+    struct Token;
+
+    impl Drop for Token {
+        fn drop(&mut self) {
+            DROPS.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn tag_storage_drops_its_values() {
+        let mut storage = Storage::<Token>::new(StorageKind::Tag);
+        storage.insert(3, Token, Tick::default());
+        storage.insert(8, Token, Tick::default());
+        assert!(storage.insert(8, Token, Tick::default()).is_some());
+        assert_eq!(DROPS.load(Ordering::Relaxed), 1);
+        drop(storage.take(3));
+        assert_eq!(DROPS.load(Ordering::Relaxed), 2);
+        drop(storage);
+        assert_eq!(DROPS.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn structural_changes_grow_across_layouts() {
+        let mut storage = Storage::<u32>::new(StorageKind::Dense);
+        let tick = Tick::default();
+        storage.insert(3, 30, tick);
+        storage.insert(8, 80, tick);
+        let changes = storage.structural_changes();
+        storage.insert(8, 81, tick);
+        *storage.get_mut(3).unwrap() = 31;
+        assert_eq!(storage.structural_changes(), changes);
+        // The new layout counts from scratch, the storage keeps counting up.
+        storage.make_sparse();
+        let sparse = storage.structural_changes();
+        assert!(sparse > changes);
+        storage.make_stable();
+        assert!(storage.structural_changes() > sparse);
+        let stable = storage.structural_changes();
+        storage.take(3);
+        assert!(storage.structural_changes() > stable);
+    }
+}
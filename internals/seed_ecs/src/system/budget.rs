@@ -0,0 +1,229 @@
+use alloc::collections::VecDeque;
+use alloc::vec::{self, Vec};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// How much work a [`Budgeted`] queue does per run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Budget {
+    /// At most this many items.
+    Items(usize),
+    /// Items until this much time went by, measured after each item so at least one runs.
+    #[cfg(feature = "std")]
+    Time(Duration),
+}
+
+impl Default for Budget {
+    /// Every item queued.
+    fn default() -> Self {
+        Self::Items(usize::MAX)
+    }
+}
+
+/// A queue of work done a bit every frame, for the systems like pathfinding or chunk meshing
+/// that can't do everything at once. Kept in a [`Local`](super::Local), the items a run didn't
+/// reach wait for the next one.
+///
+/// ```
+/// # use seed_ecs::prelude::*;
+/// # use seed_ecs::system::{Budget, Budgeted, WorkQueue};
+/// struct Meshed(usize);
+///
+/// fn mesh_chunks(mut pending: Local<Budgeted<u32>>, mut queue: ResMut<WorkQueue<u32>>, mut meshed: ResMut<Meshed>) {
+///     pending.set_budget(Budget::Items(4));
+///     pending.extend(queue.drain());
+///     pending.run(|_chunk| meshed.0 += 1);
+/// }
+///
+/// let mut world = World::new();
+/// world.insert_resource(Meshed(0));
+/// world.insert_resource(WorkQueue::<u32>::default());
+/// world.get_resource_mut::<WorkQueue<u32>>().unwrap().extend(0..10);
+/// let mut system = mesh_chunks.into_system();
+/// system.run(&mut world);
+/// assert_eq!(world.get_resource::<Meshed>().unwrap().0, 4);
+/// system.run(&mut world);
+/// system.run(&mut world);
+/// assert_eq!(world.get_resource::<Meshed>().unwrap().0, 10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Budgeted<T> {
+    items: VecDeque<T>,
+    budget: Budget,
+}
+
+impl<T> Budgeted<T> {
+    pub fn new(budget: Budget) -> Self {
+        Self {
+            items: VecDeque::new(),
+            budget,
+        }
+    }
+
+    pub fn budget(&self) -> Budget {
+        self.budget
+    }
+
+    /// Changes the budget of the next runs, the items queued stay.
+    pub fn set_budget(&mut self, budget: Budget) {
+        self.budget = budget;
+    }
+
+    /// Queues an item after the others.
+    pub fn push(&mut self, item: T) {
+        self.items.push_back(item);
+    }
+
+    /// Number of items waiting.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The items waiting, in the order they will be done.
+    pub fn iter(&self) -> impl Iterator<Item = &T> + '_ {
+        self.items.iter()
+    }
+
+    /// Drops every item waiting.
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Calls `f` on the items in the order they were queued until the budget is spent, and
+    /// returns how many it got. The items left stay for the next run.
+    pub fn run(&mut self, mut f: impl FnMut(T)) -> usize {
+        let mut done = 0;
+        match self.budget {
+            Budget::Items(count) => {
+                while done < count {
+                    let Some(item) = self.items.pop_front() else {
+                        break;
+                    };
+                    f(item);
+                    done += 1;
+                }
+            }
+            #[cfg(feature = "std")]
+            Budget::Time(duration) => {
+                let start = Instant::now();
+                while let Some(item) = self.items.pop_front() {
+                    f(item);
+                    done += 1;
+                    if start.elapsed() >= duration {
+                        break;
+                    }
+                }
+            }
+        }
+        done
+    }
+}
+
+impl<T> Default for Budgeted<T> {
+    fn default() -> Self {
+        Self::new(Budget::default())
+    }
+}
+
+impl<T> Extend<T> for Budgeted<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        self.items.extend(items);
+    }
+}
+
+/// A resource collecting work for a system, pushed into by the others and drained into a
+/// [`Budgeted`] queue by the system doing it.
+#[derive(Debug, Clone)]
+pub struct WorkQueue<T> {
+    items: Vec<T>,
+}
+
+impl<T> WorkQueue<T> {
+    pub fn new() -> Self {
+        Self { items: Vec::new() }
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.items.push(item);
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Takes the items out in the order they were pushed.
+    pub fn drain(&mut self) -> vec::Drain<'_, T> {
+        self.items.drain(..)
+    }
+}
+
+impl<T> Default for WorkQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Extend<T> for WorkQueue<T> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, items: I) {
+        self.items.extend(items);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::{IntoSystem, Local, ResMut, System};
+    use crate::World;
+
+    #[derive(Default)]
+    struct Done(Vec<u32>);
+
+    #[test]
+    fn item_budgets_spread_the_work_over_frames() {
+        let mut world = World::new();
+        world.insert_resource(Done::default());
+        world.insert_resource(WorkQueue::<u32>::new());
+        world.get_resource_mut::<WorkQueue<u32>>().unwrap().extend(0..1000);
+        let mut system = (|mut pending: Local<Budgeted<u32>>, mut queue: ResMut<WorkQueue<u32>>, mut done: ResMut<Done>| {
+            pending.set_budget(Budget::Items(64));
+            pending.extend(queue.drain());
+            let count = pending.run(|item| done.0.push(item));
+            assert!(count <= 64);
+        })
+        .into_system();
+        let mut frames = 0;
+        while world.get_resource::<Done>().unwrap().0.len() < 1000 {
+            system.run(&mut world);
+            frames += 1;
+            if frames == 3 {
+                // Work pushed while the queue drains comes after it.
+                world.get_resource_mut::<WorkQueue<u32>>().unwrap().push(1000);
+            }
+        }
+        assert_eq!(frames, 16);
+        system.run(&mut world);
+        let done = &world.get_resource::<Done>().unwrap().0;
+        assert!(done.iter().copied().eq(0..1001));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn time_budgets_run_at_least_one_item() {
+        let mut budgeted = Budgeted::new(Budget::Time(Duration::ZERO));
+        budgeted.extend([1, 2, 3]);
+        let mut seen = Vec::new();
+        assert_eq!(budgeted.run(|item| seen.push(item)), 1);
+        budgeted.set_budget(Budget::Time(Duration::from_secs(60)));
+        assert_eq!(budgeted.run(|item| seen.push(item)), 2);
+        assert_eq!(seen, [1, 2, 3]);
+        assert_eq!(budgeted.run(|_| unreachable!()), 0);
+    }
+}
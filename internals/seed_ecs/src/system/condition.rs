@@ -0,0 +1,203 @@
+use core::marker::PhantomData;
+
+use super::{AccessConflict, IntoSystem, System, SystemMeta, SystemTypeId};
+use crate::change_detection::Tick;
+use crate::World;
+
+/// Decides whether a system runs, see [`IntoSystem::run_if`].
+///
+/// It gets the tick it was last checked at and the tick of this check, to compare the ticks of
+/// the world with. Closures taking `(&World, Tick, Tick)` are conditions.
+pub trait RunCondition: Send + Sync + 'static {
+    fn should_run(&mut self, world: &World, last_run: Tick, this_run: Tick) -> bool;
+
+    /// Adds what the condition reads to the access of the system it gates, for
+    /// [`Schedule::ambiguities`](super::Schedule::ambiguities). Returns false if it may read
+    /// anything in the world, which the closures do.
+    fn add_access(&self, _meta: &mut SystemMeta) -> bool {
+        false
+    }
+}
+
+impl<F> RunCondition for F
+where
+    F: FnMut(&World, Tick, Tick) -> bool + Send + Sync + 'static,
+{
+    fn should_run(&mut self, world: &World, last_run: Tick, this_run: Tick) -> bool {
+        self(world, last_run, this_run)
+    }
+}
+
+/// A system that only runs when its condition holds, built by [`IntoSystem::run_if`].
+pub struct RunIf<S, C> {
+    system: S,
+    condition: C,
+    last_run: Tick,
+    // The access of the system with the one of the condition, once initialized.
+    meta: Option<SystemMeta>,
+}
+
+impl<S, C> RunIf<S, C> {
+    pub(super) fn new(system: S, condition: C) -> Self {
+        Self {
+            system,
+            condition,
+            last_run: Tick::new(0),
+            meta: None,
+        }
+    }
+}
+
+impl<S: System, C: RunCondition> System for RunIf<S, C> {
+    fn name(&self) -> &'static str {
+        self.system.name()
+    }
+
+    fn initialize(&mut self, world: &mut World) -> Result<(), AccessConflict> {
+        let result = self.system.initialize(world);
+        let mut meta = self.system.meta().cloned();
+        self.meta = meta.take_if(|meta| self.condition.add_access(meta));
+        result
+    }
+
+    /// Checks the condition every time, even when the system doesn't run.
+    fn run(&mut self, world: &mut World) {
+        let this_run = world.increment_change_tick();
+        let last_run = core::mem::replace(&mut self.last_run, this_run);
+        if self.condition.should_run(world, last_run, this_run) {
+            self.system.run(world);
+        }
+    }
+
+    fn check_change_tick(&mut self, tick: Tick) {
+        self.last_run.check_tick(tick);
+        self.system.check_change_tick(tick);
+    }
+
+    fn system_type(&self) -> SystemTypeId {
+        self.system.system_type()
+    }
+
+    fn meta(&self) -> Option<&SystemMeta> {
+        self.meta.as_ref()
+    }
+}
+
+enum ResourceCheck {
+    Changed,
+    Added,
+    Exists,
+}
+
+struct ResourceCondition<T> {
+    check: ResourceCheck,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: Send + Sync + 'static> RunCondition for ResourceCondition<T> {
+    fn should_run(&mut self, world: &World, last_run: Tick, this_run: Tick) -> bool {
+        world.resource_ticks::<T>().is_some_and(|ticks| match self.check {
+            ResourceCheck::Changed => ticks.is_changed(last_run, this_run),
+            ResourceCheck::Added => ticks.is_added(last_run, this_run),
+            ResourceCheck::Exists => true,
+        })
+    }
+
+    fn add_access(&self, meta: &mut SystemMeta) -> bool {
+        meta.add_resource_read::<T>();
+        true
+    }
+}
+
+/// Holds when the resource was inserted or written since the condition was last checked. Reading
+/// the resource, even through a `ResMut` that is not written to, doesn't count.
+pub fn resource_changed<T: Send + Sync + 'static>() -> impl RunCondition {
+    ResourceCondition::<T> {
+        check: ResourceCheck::Changed,
+        _marker: PhantomData,
+    }
+}
+
+/// Holds when the resource was inserted since the condition was last checked.
+pub fn resource_added<T: Send + Sync + 'static>() -> impl RunCondition {
+    ResourceCondition::<T> {
+        check: ResourceCheck::Added,
+        _marker: PhantomData,
+    }
+}
+
+pub fn resource_exists<T: Send + Sync + 'static>() -> impl RunCondition {
+    ResourceCondition::<T> {
+        check: ResourceCheck::Exists,
+        _marker: PhantomData,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::system::{Res, ResMut, Schedule};
+
+    #[derive(Debug, PartialEq)]
+    struct GraphicsSettings {
+        msaa: u32,
+    }
+    struct Rebuilds(u32);
+    struct Frame(u32);
+
+    fn rebuild_pipeline(settings: Res<GraphicsSettings>, mut rebuilds: ResMut<Rebuilds>) {
+        assert!(settings.msaa > 0);
+        rebuilds.0 += 1;
+    }
+
+    #[test]
+    fn gated_systems_run_on_the_frames_the_resource_changed() {
+        let mut world = World::new();
+        world.insert_resource(Rebuilds(0));
+        world.insert_resource(Frame(0));
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(|mut frame: ResMut<Frame>, mut settings: ResMut<GraphicsSettings>| {
+                frame.0 += 1;
+                // Looking through the `ResMut` is not a write.
+                let msaa = settings.bypass_change_detection().msaa;
+                if frame.0.is_multiple_of(3) {
+                    settings.msaa = msaa * 2;
+                }
+            })
+            .add_system(rebuild_pipeline.run_if(resource_changed::<GraphicsSettings>()));
+        world.insert_resource(GraphicsSettings { msaa: 1 });
+
+        let mut rebuilt = Vec::new();
+        for _ in 0..7 {
+            let before = world.get_resource::<Rebuilds>().unwrap().0;
+            schedule.run(&mut world);
+            rebuilt.push(world.get_resource::<Rebuilds>().unwrap().0 > before);
+        }
+        // The insertion counts on the first frame, then every write.
+        assert_eq!(rebuilt, [true, false, true, false, false, true, false]);
+        assert_eq!(world.get_resource::<GraphicsSettings>(), Some(&GraphicsSettings { msaa: 4 }));
+    }
+
+    #[test]
+    fn world_level_resource_changes() {
+        let mut world = World::new();
+        assert!(!world.is_resource_changed::<Frame>());
+        world.insert_resource(Frame(0));
+        assert!(world.is_resource_added::<Frame>() && world.is_resource_changed::<Frame>());
+        world.clear_trackers();
+        assert!(!world.is_resource_added::<Frame>() && !world.is_resource_changed::<Frame>());
+        let _ = world.get_resource::<Frame>();
+        assert!(!world.is_resource_changed::<Frame>());
+        world.get_resource_mut::<Frame>().unwrap().0 += 1;
+        assert!(!world.is_resource_added::<Frame>() && world.is_resource_changed::<Frame>());
+
+        let mut added = resource_added::<Frame>();
+        let mut exists = resource_exists::<Rebuilds>();
+        let (last, this) = (Tick::new(0), world.change_tick());
+        assert!(added.should_run(&world, last, this));
+        let next = world.increment_change_tick();
+        assert!(!added.should_run(&world, this, next));
+        assert!(!exists.should_run(&world, last, this));
+    }
+}
@@ -0,0 +1,143 @@
+use core::any::type_name;
+use core::marker::PhantomData;
+
+use super::{AccessConflict, RunCondition, RunIf, System, SystemMeta, SystemParam, SystemParamItem};
+use crate::change_detection::Tick;
+use crate::World;
+use crate::tuples::all_tuples;
+
+/// Conversion into a [`System`], implemented for the systems themselves and for the functions
+/// whose arguments are all [`SystemParam`]s.
+pub trait IntoSystem<Marker> {
+    type System: System;
+
+    fn into_system(self) -> Self::System;
+
+    /// Runs the system only when the condition holds.
+    fn run_if<C: RunCondition>(self, condition: C) -> RunIf<Self::System, C>
+    where
+        Self: Sized,
+    {
+        RunIf::new(self.into_system(), condition)
+    }
+}
+
+impl<S: System> IntoSystem<()> for S {
+    type System = S;
+
+    fn into_system(self) -> Self::System {
+        self
+    }
+}
+
+#[doc(hidden)]
+pub struct IsFunctionSystem;
+
+impl<Marker: 'static, F: SystemParamFunction<Marker>> IntoSystem<(IsFunctionSystem, Marker)> for F {
+    type System = FunctionSystem<Marker, F>;
+
+    fn into_system(self) -> Self::System {
+        FunctionSystem {
+            func: self,
+            state: None,
+            meta: SystemMeta::new(type_name::<F>()),
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Functions that can run as systems, `Marker` is the signature of the function.
+pub trait SystemParamFunction<Marker>: Send + Sync + 'static {
+    type Param: SystemParam;
+
+    fn run(&mut self, param: SystemParamItem<'_, '_, Self::Param>);
+}
+
+/// A function turned into a [`System`], along with the state of its parameters.
+pub struct FunctionSystem<Marker, F: SystemParamFunction<Marker>> {
+    func: F,
+    state: Option<<F::Param as SystemParam>::State>,
+    meta: SystemMeta,
+    _marker: PhantomData<fn() -> Marker>,
+}
+
+impl<Marker: 'static, F: SystemParamFunction<Marker>> System for FunctionSystem<Marker, F> {
+    fn name(&self) -> &'static str {
+        self.meta.name()
+    }
+
+    fn initialize(&mut self, world: &mut World) -> Result<(), AccessConflict> {
+        if self.state.is_none() {
+            self.state = Some(F::Param::init_state(world, &mut self.meta));
+        }
+        self.meta.conflict().map_or(Ok(()), |conflict| Err(conflict.clone()))
+    }
+
+    fn run(&mut self, world: &mut World) {
+        if let Err(conflict) = self.initialize(world) {
+            panic!("{}", conflict);
+        }
+        let mut guard = DiscardOnUnwind::<F::Param> {
+            state: self.state.as_mut().unwrap(),
+            ran: false,
+        };
+        // The world is borrowed exclusively and the parameters checked their access against each
+        // other when they were initialized.
+        let this_run = world.increment_change_tick();
+        let cell = world.as_unsafe_world_cell_at(self.meta.last_run(), this_run);
+        let param = unsafe { F::Param::get_param(guard.state, &self.meta, cell) };
+        self.func.run(param);
+        guard.ran = true;
+        F::Param::apply(guard.state, world);
+        self.meta.set_last_run(this_run);
+    }
+
+    fn check_change_tick(&mut self, tick: Tick) {
+        let mut last_run = self.meta.last_run();
+        last_run.check_tick(tick);
+        self.meta.set_last_run(last_run);
+    }
+
+    fn meta(&self) -> Option<&SystemMeta> {
+        self.state.is_some().then_some(&self.meta)
+    }
+}
+
+// Discards what a run deferred if the system panics, so the next run doesn't apply it.
+pub(crate) struct DiscardOnUnwind<'a, P: SystemParam> {
+    pub(crate) state: &'a mut P::State,
+    pub(crate) ran: bool,
+}
+
+impl<'a, P: SystemParam> Drop for DiscardOnUnwind<'a, P> {
+    fn drop(&mut self) {
+        if !self.ran {
+            P::discard(self.state);
+        }
+    }
+}
+
+macro_rules! impl_system_function {
+    ($($param: ident),*) => {
+        #[allow(non_snake_case)]
+        impl<Func, $($param: SystemParam),*> SystemParamFunction<fn($($param,)*)> for Func
+        where
+            Func: Send + Sync + 'static,
+            for<'a> &'a mut Func: FnMut($($param),*) + FnMut($(SystemParamItem<$param>),*),
+        {
+            type Param = ($($param,)*);
+
+            fn run(&mut self, param: SystemParamItem<'_, '_, ($($param,)*)>) {
+                // Calling through a helper gives the compiler the concrete argument types.
+                #[allow(clippy::too_many_arguments)]
+                fn call<$($param),*>(mut func: impl FnMut($($param),*), $($param: $param),*) {
+                    func($($param),*)
+                }
+                let ($($param,)*) = param;
+                call(self, $($param),*)
+            }
+        }
+    };
+}
+
+all_tuples!(impl_system_function);
@@ -0,0 +1,220 @@
+//! Functions running against a world, fetching what they need through [`SystemParam`]s.
+
+mod budget;
+mod condition;
+mod function;
+mod param;
+mod schedule;
+mod set;
+mod sync;
+
+pub use budget::*;
+pub use condition::*;
+pub use function::*;
+pub use param::*;
+pub use schedule::*;
+pub use set::*;
+pub use sync::*;
+
+use alloc::vec::Vec;
+use core::any::{type_name, TypeId};
+use core::fmt;
+
+use crate::change_detection::Tick;
+use crate::component::ComponentId;
+use crate::query::{Access, FilteredAccess};
+use crate::World;
+
+/// Something that runs against a world, usually a function turned into a system by
+/// [`IntoSystem`].
+pub trait System: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    /// Builds the state of the parameters, done by the first run if not called before.
+    ///
+    /// Fails if two parameters conflict, the system can't run then.
+    fn initialize(&mut self, world: &mut World) -> Result<(), AccessConflict>;
+
+    /// Runs the system then applies its deferred changes, like the commands it queued.
+    ///
+    /// # Panics
+    ///
+    /// Panics before running anything if [`System::initialize`] fails.
+    fn run(&mut self, world: &mut World);
+
+    /// Clamps the tick of the last run, see [`World::check_change_ticks`].
+    fn check_change_tick(&mut self, tick: Tick);
+
+    /// What the parameters access once initialized. `None` for the systems reaching the whole
+    /// world, which conflict with every other system.
+    fn meta(&self) -> Option<&SystemMeta> {
+        None
+    }
+
+    /// The type of the system, the same for every system built from the same function. Each
+    /// system is a member of this set in its schedule, ordering against a function orders
+    /// against it.
+    fn system_type(&self) -> SystemTypeId {
+        SystemTypeId::of::<Self>(self.name())
+    }
+}
+
+impl World {
+    /// Runs the system a single time, for setup code and tools: its parameters are initialized,
+    /// it runs and applies its commands, then its state is dropped, [`Local`]s included.
+    ///
+    /// Fails without running anything if two parameters of the system conflict.
+    pub fn run_system_once<M>(&mut self, system: impl IntoSystem<M>) -> Result<(), AccessConflict> {
+        let mut system = system.into_system();
+        system.initialize(self)?;
+        let previous = self.running_system.replace(system.name());
+        system.run(self);
+        self.running_system = previous;
+        Ok(())
+    }
+}
+
+/// Two parameters of a system access the same component or resource, one of them mutably.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AccessConflict {
+    Component {
+        system: &'static str,
+        component: &'static str,
+    },
+    Resource {
+        system: &'static str,
+        resource: &'static str,
+    },
+}
+
+impl AccessConflict {
+    pub fn system(&self) -> &'static str {
+        match self {
+            Self::Component { system, .. } | Self::Resource { system, .. } => system,
+        }
+    }
+}
+
+impl fmt::Display for AccessConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Component { system, component } => {
+                write!(f, "System {} has conflicting access to component {}", system, component)
+            }
+            Self::Resource { system, resource } => {
+                write!(f, "System {} has conflicting access to resource {}", system, resource)
+            }
+        }
+    }
+}
+
+impl core::error::Error for AccessConflict {}
+
+/// What a system knows about itself while its parameters are initialized.
+#[derive(Debug, Clone)]
+pub struct SystemMeta {
+    name: &'static str,
+    component_access: Access,
+    // One per query parameter, to tell the disjoint queries apart.
+    query_accesses: Vec<FilteredAccess>,
+    resource_reads: Vec<TypeId>,
+    resource_writes: Vec<TypeId>,
+    conflict: Option<AccessConflict>,
+    last_run: Tick,
+}
+
+impl SystemMeta {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            component_access: Access::default(),
+            query_accesses: Vec::new(),
+            resource_reads: Vec::new(),
+            resource_writes: Vec::new(),
+            conflict: None,
+            last_run: Tick::new(0),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The tick of the previous run, what [`Changed`](crate::query::Changed) compares against.
+    pub fn last_run(&self) -> Tick {
+        self.last_run
+    }
+
+    pub fn set_last_run(&mut self, tick: Tick) {
+        self.last_run = tick;
+    }
+
+    pub fn component_access(&self) -> &Access {
+        &self.component_access
+    }
+
+    /// The first conflict between the parameters added so far.
+    pub fn conflict(&self) -> Option<&AccessConflict> {
+        self.conflict.as_ref()
+    }
+
+    fn record_conflict(&mut self, conflict: AccessConflict) {
+        self.conflict.get_or_insert(conflict);
+    }
+
+    /// Adds the access of a query parameter. It conflicts with the queries it can match the same
+    /// entities as when one of them writes a component the other reaches, and with itself when
+    /// its own items alias, see [`Access::aliased`].
+    pub fn add_component_access(&mut self, world: &World, access: &FilteredAccess) {
+        let aliased = access.access().aliased();
+        let conflict = aliased.or_else(|| self.query_accesses.iter().find_map(|other| other.conflict(access)));
+        if let Some(id) = conflict {
+            let component = world.components().info(id).map_or("?", |info| info.name());
+            self.record_conflict(AccessConflict::Component {
+                system: self.name,
+                component,
+            });
+        }
+        for id in access.access().reads() {
+            self.component_access.add_read(*id);
+        }
+        for id in access.access().writes() {
+            self.component_access.add_write(*id);
+        }
+        self.query_accesses.push(access.clone());
+    }
+
+    /// Conflicts if another parameter writes the resource.
+    pub fn add_resource_read<T: 'static>(&mut self) {
+        let id = TypeId::of::<T>();
+        if self.resource_writes.contains(&id) {
+            self.record_resource_conflict::<T>();
+        }
+        self.resource_reads.push(id);
+    }
+
+    /// Conflicts if another parameter reads or writes the resource.
+    pub fn add_resource_write<T: 'static>(&mut self) {
+        let id = TypeId::of::<T>();
+        if self.resource_reads.contains(&id) || self.resource_writes.contains(&id) {
+            self.record_resource_conflict::<T>();
+        }
+        self.resource_writes.push(id);
+    }
+
+    /// True if the systems could run at the same time: neither writes a resource or a component
+    /// the other reaches. Readers of the same data are compatible.
+    pub fn is_compatible(&self, other: &SystemMeta) -> bool {
+        let writes_into = |writer: &SystemMeta, other: &SystemMeta| {
+            writer.resource_writes.iter().any(|id| other.resource_reads.contains(id) || other.resource_writes.contains(id))
+        };
+        !writes_into(self, other) && !writes_into(other, self) && self.component_access.conflict(&other.component_access).is_none()
+    }
+
+    fn record_resource_conflict<T: 'static>(&mut self) {
+        self.record_conflict(AccessConflict::Resource {
+            system: self.name,
+            resource: type_name::<T>(),
+        });
+    }
+}
@@ -0,0 +1,556 @@
+use core::any::type_name;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use super::SystemMeta;
+use crate::change_detection::Mut;
+use crate::commands::{CommandQueue, Commands};
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::query::{Query, QueryFilter, QueryState, WorldQuery};
+use crate::{FromWorld, UnsafeWorldCell, World};
+use crate::tuples::all_tuples;
+
+pub use seed_ecs_derive::SystemParam;
+
+/// The arguments of the functions that can run as systems.
+///
+/// Structs grouping parameters derive it, their fields are fetched like the parameters of a
+/// function and conflict the same way:
+///
+/// ```
+/// # use seed_ecs::prelude::*;
+/// # #[derive(Component)]
+/// # struct Position(f32);
+/// # struct Time(f32);
+/// #[derive(SystemParam)]
+/// struct Physics<'w, 's> {
+///     bodies: Query<'w, 's, &'static mut Position>,
+///     time: Res<'w, Time>,
+/// }
+///
+/// fn fall(mut physics: Physics) {
+///     let step = physics.time.0;
+///     for mut position in physics.bodies.iter_mut() {
+///         position.0 -= step;
+///     }
+/// }
+///
+/// let mut world = World::new();
+/// world.insert_resource(Time(0.5));
+/// let body = world.spawn_batch([Position(2.0)])[0];
+/// world.run_system_once(fall).unwrap();
+/// assert_eq!(world.get_component::<Position>(body).unwrap().0, 1.5);
+/// ```
+///
+/// # Safety
+///
+/// `init_state` must declare every access `get_param` makes in the [`SystemMeta`].
+pub unsafe trait SystemParam: Sized {
+    type State: Send + Sync + 'static;
+    /// The parameter with the lifetimes of a run.
+    type Item<'w, 's>: SystemParam<State = Self::State>;
+
+    fn init_state(world: &mut World, meta: &mut SystemMeta) -> Self::State;
+
+    /// # Safety
+    ///
+    /// The access declared by `init_state` must be allowed on `world` for `'w`.
+    unsafe fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's>;
+
+    /// Applies the deferred changes once the system ran.
+    fn apply(_state: &mut Self::State, _world: &mut World) {}
+
+    /// Drops the deferred changes of a run that panicked, instead of applying them.
+    fn discard(_state: &mut Self::State) {}
+}
+
+pub type SystemParamItem<'w, 's, P> = <P as SystemParam>::Item<'w, 's>;
+
+/// Shared access to a resource.
+///
+/// # Panics
+///
+/// The system panics if the resource doesn't exist when it runs.
+pub struct Res<'w, T> {
+    value: &'w T,
+}
+
+impl<'w, T> Deref for Res<'w, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+unsafe impl<'a, T: Send + Sync + 'static> SystemParam for Res<'a, T> {
+    type State = ();
+    type Item<'w, 's> = Res<'w, T>;
+
+    fn init_state(_world: &mut World, meta: &mut SystemMeta) -> Self::State {
+        meta.add_resource_read::<T>();
+    }
+
+    unsafe fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        let value = world.resource::<T>().unwrap_or_else(|| missing_resource::<T>(world, meta));
+        Res { value }
+    }
+}
+
+/// Exclusive access to a resource, marking it changed when it is written.
+///
+/// # Panics
+///
+/// The system panics if the resource doesn't exist when it runs.
+pub type ResMut<'w, T> = Mut<'w, T>;
+
+unsafe impl<'a, T: Send + Sync + 'static> SystemParam for Mut<'a, T> {
+    type State = ();
+    type Item<'w, 's> = Mut<'w, T>;
+
+    fn init_state(_world: &mut World, meta: &mut SystemMeta) -> Self::State {
+        meta.add_resource_write::<T>();
+    }
+
+    unsafe fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        world.resource_mut::<T>().unwrap_or_else(|| missing_resource::<T>(world, meta))
+    }
+}
+
+fn missing_resource<T: 'static>(world: UnsafeWorldCell<'_>, meta: &SystemMeta) -> ! {
+    let resources = &world.world.resources;
+    match resources.is_scoped::<T>() {
+        true => panic!("{}", resources.unavailable::<T>(Some(meta.name()))),
+        false => panic!("Resource {} requested by {} does not exist", type_name::<T>(), meta.name()),
+    }
+}
+
+/// A value owned by the system, kept between runs and built by [`FromWorld`] on initialization.
+pub struct Local<'s, T> {
+    value: &'s mut T,
+}
+
+impl<'s, T> Deref for Local<'s, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.value
+    }
+}
+
+impl<'s, T> DerefMut for Local<'s, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.value
+    }
+}
+
+unsafe impl<'a, T: FromWorld + Send + Sync + 'static> SystemParam for Local<'a, T> {
+    type State = T;
+    type Item<'w, 's> = Local<'s, T>;
+
+    fn init_state(world: &mut World, _meta: &mut SystemMeta) -> Self::State {
+        T::from_world(world)
+    }
+
+    unsafe fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        _meta: &SystemMeta,
+        _world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        Local { value: state }
+    }
+}
+
+/// The entities that lost their `T` during the current and the previous frame, see
+/// [`World::removed`]. An entity may be reported by two runs in a row, and may have got a new
+/// `T` since.
+pub struct Removed<'w, T> {
+    world: &'w World,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'w, T: Component> Removed<'w, T> {
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + 'w {
+        self.world.removed::<T>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+unsafe impl<'a, T: Component> SystemParam for Removed<'a, T> {
+    type State = ();
+    type Item<'w, 's> = Removed<'w, T>;
+
+    // The removals are only written with the world borrowed exclusively, no access to declare.
+    fn init_state(_world: &mut World, _meta: &mut SystemMeta) -> Self::State {}
+
+    unsafe fn get_param<'w, 's>(
+        _state: &'s mut Self::State,
+        _meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        Removed {
+            world: world.world,
+            marker: PhantomData,
+        }
+    }
+}
+
+unsafe impl<'a> SystemParam for Commands<'a> {
+    type State = CommandQueue;
+    type Item<'w, 's> = Commands<'s>;
+
+    fn init_state(_world: &mut World, _meta: &mut SystemMeta) -> Self::State {
+        CommandQueue::default()
+    }
+
+    unsafe fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        _meta: &SystemMeta,
+        _world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        Commands::new(state)
+    }
+
+    fn apply(state: &mut Self::State, world: &mut World) {
+        #[cfg(feature = "trace")]
+        let _span = crate::trace::apply(state.len());
+        state.apply(world);
+    }
+
+    fn discard(state: &mut Self::State) {
+        state.clear();
+    }
+}
+
+unsafe impl<'a, 'b, Q, F> SystemParam for Query<'a, 'b, Q, F>
+where
+    Q: WorldQuery + 'static,
+    F: QueryFilter + 'static,
+{
+    type State = QueryState<Q, F>;
+    type Item<'w, 's> = Query<'w, 's, Q, F>;
+
+    fn init_state(world: &mut World, meta: &mut SystemMeta) -> Self::State {
+        let state = QueryState::init(world);
+        meta.add_component_access(world, &state.filtered_access());
+        state
+    }
+
+    unsafe fn get_param<'w, 's>(
+        state: &'s mut Self::State,
+        _meta: &SystemMeta,
+        world: UnsafeWorldCell<'w>,
+    ) -> Self::Item<'w, 's> {
+        Query::new(world, state)
+    }
+}
+
+macro_rules! impl_tuple_param {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case, clippy::unused_unit)]
+        unsafe impl<$($name: SystemParam),*> SystemParam for ($($name,)*) {
+            type State = ($($name::State,)*);
+            type Item<'w, 's> = ($($name::Item<'w, 's>,)*);
+
+            fn init_state(_world: &mut World, _meta: &mut SystemMeta) -> Self::State {
+                ($($name::init_state(_world, _meta),)*)
+            }
+
+            unsafe fn get_param<'w, 's>(
+                state: &'s mut Self::State,
+                _meta: &SystemMeta,
+                _world: UnsafeWorldCell<'w>,
+            ) -> Self::Item<'w, 's> {
+                let ($($name,)*) = state;
+                ($($name::get_param($name, _meta, _world),)*)
+            }
+
+            fn apply(state: &mut Self::State, _world: &mut World) {
+                let ($($name,)*) = state;
+                $($name::apply($name, _world);)*
+            }
+
+            fn discard(state: &mut Self::State) {
+                let ($($name,)*) = state;
+                $($name::discard($name);)*
+            }
+        }
+    };
+}
+
+all_tuples!(impl_tuple_param);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity::Entity;
+    use crate::query::{Changed, With, Without};
+    use crate::system::{AccessConflict, IntoSystem, Schedule, System};
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Position(f32);
+    #[derive(Component)]
+    struct Velocity(f32);
+    struct Frames(u32);
+
+    struct SpawnCount(usize);
+
+    impl FromWorld for SpawnCount {
+        fn from_world(world: &mut World) -> Self {
+            SpawnCount(world.enities().len())
+        }
+    }
+
+    fn movement(mut query: Query<(&mut Position, &Velocity)>, mut frames: ResMut<Frames>) {
+        for (mut position, velocity) in query.iter_mut() {
+            position.0 += velocity.0;
+        }
+        frames.0 += 1;
+    }
+
+    #[test]
+    fn run_function_systems() {
+        let mut world = World::new();
+        world.insert_resource(Frames(0));
+        let e = *world.spawn_entity();
+        world.add_component(e, Position(0.0));
+        world.add_component(e, Velocity(2.0));
+
+        let mut system = movement.into_system();
+        system.run(&mut world);
+        system.run(&mut world);
+        assert_eq!(world.get_component::<Position>(e), Some(&Position(4.0)));
+        assert_eq!(world.get_resource::<Frames>().unwrap().0, 2);
+
+        let mut spawner = (|mut commands: Commands, frames: Res<Frames>| {
+            let frames = frames.0;
+            commands.add(move |world: &mut World| {
+                let e = *world.spawn_entity();
+                world.add_component(e, Position(frames as f32));
+            });
+        })
+        .into_system();
+        spawner.run(&mut world);
+        let mut positions = world.query::<(Entity, &Position)>();
+        assert_eq!(positions.iter(&world).count(), 2);
+    }
+
+    #[test]
+    fn systems_run_once_apply_their_commands() {
+        let mut world = World::new();
+        world.insert_resource(Frames(0));
+        let spawn = |mut commands: Commands, mut runs: Local<u32>, mut frames: ResMut<Frames>| {
+            *runs += 1;
+            frames.0 += *runs;
+            commands.add(|world: &mut World| {
+                world.spawn_batch([Position(1.0)]);
+            });
+        };
+        world.run_system_once(spawn).unwrap();
+        // The locals start over with every run.
+        world.run_system_once(spawn).unwrap();
+        assert_eq!(world.get_resource::<Frames>().unwrap().0, 2);
+        assert_eq!(world.query::<&Position>().iter(&world).count(), 2);
+        assert_eq!(world.running_system(), None);
+
+        let conflict = world.run_system_once(|_: Res<Frames>, _: ResMut<Frames>| unreachable!()).unwrap_err();
+        assert!(matches!(conflict, AccessConflict::Resource { .. }));
+    }
+
+    #[test]
+    fn local_from_world() {
+        let mut world = World::new();
+        world.spawn_entity();
+        world.spawn_entity();
+        let mut system = (|mut seen: Local<SpawnCount>, mut runs: Local<u32>| {
+            *runs += 1;
+            seen.0 += *runs as usize;
+        })
+        .into_system();
+        // The local sees the world as it is when the system first runs.
+        world.spawn_entity();
+        system.run(&mut world);
+        system.run(&mut world);
+        let mut check = (|seen: Local<SpawnCount>| assert_eq!(seen.0, 4)).into_system();
+        world.spawn_entity();
+        check.run(&mut world);
+    }
+
+    #[test]
+    fn sixteen_params() {
+        type L<'s> = Local<'s, u8>;
+        type Wide<'w, 's> = Query<'w, 's, (&'static Position, (), (), (), (), (), (), (), (), (), (), (), (), (), (), ())>;
+        let mut world = World::new();
+        world.insert_resource(Frames(0));
+        let e = *world.spawn_entity();
+        world.add_component(e, Position(1.0));
+        let mut system = (|_: L, _: L, _: L, _: L, _: L, _: L, _: L, _: L, _: L, _: L, _: L, _: L, _: L,
+                           positions: Wide,
+                           _: Commands,
+                           mut frames: ResMut<Frames>| {
+            frames.0 += positions.iter().count() as u32;
+        })
+        .into_system();
+        system.run(&mut world);
+        assert_eq!(world.get_resource::<Frames>().unwrap().0, 1);
+    }
+
+    #[test]
+    fn conflicting_params_are_rejected() {
+        let mut world = World::new();
+        let mut system = (|_: Query<&mut Position>, _: Query<&Position>| {}).into_system();
+        let conflict = system.initialize(&mut world).unwrap_err();
+        assert_eq!(
+            conflict,
+            AccessConflict::Component {
+                system: system.name(),
+                component: type_name::<Position>(),
+            }
+        );
+        assert_eq!(
+            conflict.to_string(),
+            format!("System {} has conflicting access to component {}", system.name(), type_name::<Position>())
+        );
+
+        let mut system = (|_: Res<Frames>, _: ResMut<Frames>| {}).into_system();
+        let conflict = system.initialize(&mut world).unwrap_err();
+        assert_eq!(
+            conflict,
+            AccessConflict::Resource {
+                system: system.name(),
+                resource: type_name::<Frames>(),
+            }
+        );
+        let mut system = (|_: ResMut<Frames>, _: ResMut<Frames>| {}).into_system();
+        assert!(system.initialize(&mut world).is_err());
+
+        // Tick filters read the ticks a `Mut` of the other query writes.
+        let mut system = (|_: Query<&mut Position>, _: Query<Entity, Changed<Position>>| {}).into_system();
+        let conflict = system.initialize(&mut world).unwrap_err();
+        assert!(matches!(conflict, AccessConflict::Component { component, .. } if component == type_name::<Position>()));
+        let mut system = (|_: Query<&mut Position, Changed<Position>>| {}).into_system();
+        assert!(system.initialize(&mut world).is_ok());
+
+        // A single query whose own items alias.
+        let mut system = (|_: Query<(Entity, (&mut Position,), Option<&Position>)>| {}).into_system();
+        let conflict = system.initialize(&mut world).unwrap_err();
+        assert!(matches!(conflict, AccessConflict::Component { component, .. } if component == type_name::<Position>()));
+    }
+
+    #[derive(SystemParam)]
+    struct Movers<'w, 's> {
+        query: Query<'w, 's, (&'static mut Position, &'static Velocity)>,
+        frames: ResMut<'w, Frames>,
+    }
+
+    #[derive(SystemParam)]
+    struct Spawner<'w, 's>(Movers<'w, 's>, Commands<'s>, Local<'s, u32>);
+
+    #[test]
+    fn derived_params_run_in_schedules() {
+        let mut world = World::new();
+        world.insert_resource(Frames(0));
+        let e = world.spawn_batch_iter([(Position(0.0), Velocity(1.5))])[0];
+        let mut schedule = Schedule::new();
+        schedule.add_system(|mut movers: Movers| {
+            for (mut position, velocity) in movers.query.iter_mut() {
+                position.0 += velocity.0;
+            }
+            movers.frames.0 += 1;
+        });
+        schedule.run(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(world.get_component::<Position>(e), Some(&Position(3.0)));
+        assert_eq!(world.get_resource::<Frames>().unwrap().0, 2);
+    }
+
+    #[test]
+    fn derived_params_nest() {
+        let mut world = World::new();
+        world.insert_resource(Frames(0));
+        world.spawn_batch_iter([(Position(0.0), Velocity(1.0))]);
+        let mut system = (|mut spawner: Spawner| {
+            let Spawner(movers, commands, runs) = &mut spawner;
+            **runs += 1;
+            movers.frames.0 += **runs;
+            if movers.query.iter_mut().count() < 3 {
+                commands.add(|world: &mut World| {
+                    world.spawn_batch_iter([(Position(0.0), Velocity(1.0))]);
+                });
+            }
+        })
+        .into_system();
+        system.initialize(&mut world).unwrap();
+        for _ in 0..4 {
+            system.run(&mut world);
+        }
+        // The commands of the nested fields are applied and the locals kept between runs.
+        assert_eq!(world.query::<&Velocity>().iter(&world).count(), 3);
+        assert_eq!(world.get_resource::<Frames>().unwrap().0, 1 + 2 + 3 + 4);
+    }
+
+    #[test]
+    fn derived_params_conflict_like_inline_ones() {
+        let mut world = World::new();
+        let mut system = (|_: Movers, _: Query<&Position>| {}).into_system();
+        let conflict = system.initialize(&mut world).unwrap_err();
+        assert!(matches!(conflict, AccessConflict::Component { component, .. } if component == type_name::<Position>()));
+        let mut system = (|_: Spawner, _: Res<Frames>| {}).into_system();
+        let conflict = system.initialize(&mut world).unwrap_err();
+        assert!(matches!(conflict, AccessConflict::Resource { resource, .. } if resource == type_name::<Frames>()));
+        let mut system = (|_: Movers, _: Query<&Position, Without<Velocity>>| {}).into_system();
+        assert!(system.initialize(&mut world).is_ok());
+    }
+
+    #[derive(Debug, PartialEq, Component)]
+    struct Frozen;
+
+    #[test]
+    fn disjoint_filters_are_compatible() {
+        let mut world = World::new();
+        let moving = *world.spawn_entity();
+        let frozen = *world.spawn_entity();
+        for e in [moving, frozen] {
+            world.add_component(e, Position(1.0));
+        }
+        world.add_component(frozen, Frozen);
+        let mut system = (|mut moving: Query<&mut Position, Without<Frozen>>, frozen: Query<&Position, With<Frozen>>| {
+            let offset = frozen.iter().map(|p| p.0).sum::<f32>();
+            for mut position in moving.iter_mut() {
+                position.0 += offset;
+            }
+        })
+        .into_system();
+        assert_eq!(system.initialize(&mut world), Ok(()));
+        system.run(&mut world);
+        assert_eq!(world.get_component::<Position>(moving), Some(&Position(2.0)));
+
+        // Only one side filters, the queries may meet.
+        let mut system = (|_: Query<&mut Position, With<Frozen>>, _: Query<&Position>| {}).into_system();
+        assert!(system.initialize(&mut world).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "conflicting access to component")]
+    fn conflicting_systems_panic_before_running() {
+        let mut world = World::new();
+        let mut system = (|_: Query<&mut Position>, _: Query<&Position>| unreachable!()).into_system();
+        system.run(&mut world);
+    }
+}
@@ -0,0 +1,1178 @@
+use alloc::boxed::Box;
+use alloc::collections::BTreeSet;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::any::Any;
+use core::fmt;
+
+use super::set::AnySet;
+use super::{AccessConflict, IntoSetConfig, IntoSystemConfig, RunCondition, System, SystemMeta, SystemTypeId};
+use crate::change_detection::Tick;
+use crate::World;
+
+/// Systems run one after the other, in the order they were added unless the sets they joined
+/// order them otherwise, see [`Schedule::configure_sets`], or they are ordered against other
+/// systems with `.after(movement)`. Each system applies its commands
+/// before the next one runs, so the same schedule gives the same world on every run and every
+/// machine, as lockstep simulations need. [`Schedule::deny_ambiguities`] also rejects the
+/// systems whose order only comes from the order they were added in, see
+/// [`Schedule::ambiguities`].
+///
+/// With stepping enabled, see [`Schedule::enable_stepping`], the systems only run one at a time
+/// through [`Schedule::step`], except the ones added with [`Schedule::add_always_run_system`]
+/// which keep running so that the app stays responsive while its logic is paused.
+///
+/// With the `trace` feature the runs, the systems and their commands are spans, see
+/// `seed_ecs::trace`. Without it nothing is traced and the spans don't even exist:
+///
+#[cfg_attr(feature = "trace", doc = "```")]
+#[cfg_attr(not(feature = "trace"), doc = "```compile_fail")]
+/// assert_eq!(seed_ecs::trace::SYSTEM, "system");
+/// ```
+#[derive(Default)]
+pub struct Schedule {
+    systems: Vec<Box<dyn System>>,
+    // Whether each system keeps running while stepping.
+    always_run: Vec<bool>,
+    // The sets of each system and its ordering, by index in `sets`.
+    orderings: Vec<SystemOrdering>,
+    sets: Vec<SetNode>,
+    // The sets with conditions each system is a member of, directly or not.
+    gates: Vec<Vec<usize>>,
+    // The result of the conditions of each set on this run, checked on the first member.
+    checked: Vec<Option<bool>>,
+    // Whether the systems are sorted since the last system or set was added.
+    built: bool,
+    // The next system to step, `None` while not stepping.
+    stepping: Option<usize>,
+    deny_ambiguities: bool,
+    // The systems each system is ordered after through the graph, sorted, since the last build.
+    ancestors: Vec<Vec<usize>>,
+    // Whether the systems built were checked for ambiguities.
+    checked_ambiguities: bool,
+}
+
+#[derive(Default)]
+struct SystemOrdering {
+    sets: Vec<usize>,
+    before: Vec<usize>,
+    after: Vec<usize>,
+}
+
+struct SetNode {
+    set: Box<dyn AnySet>,
+    parents: Vec<usize>,
+    before: Vec<usize>,
+    after: Vec<usize>,
+    conditions: Vec<(Box<dyn RunCondition>, Tick)>,
+}
+
+impl Schedule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_system<M>(&mut self, system: impl IntoSystemConfig<M>) -> &mut Self {
+        let config = system.into_config();
+        let system_type = self.intern(Box::new(config.system.system_type()));
+        let ordering = SystemOrdering {
+            sets: core::iter::once(system_type).chain(config.sets.into_iter().map(|set| self.intern(set))).collect(),
+            before: config.before.into_iter().map(|set| self.intern(set)).collect(),
+            after: config.after.into_iter().map(|set| self.intern(set)).collect(),
+        };
+        self.systems.push(config.system);
+        self.always_run.push(false);
+        self.orderings.push(ordering);
+        self.built = false;
+        self
+    }
+
+    /// Adds a system that runs on every [`Schedule::run`] and [`Schedule::step`] while stepping,
+    /// like rendering.
+    pub fn add_always_run_system<M>(&mut self, system: impl IntoSystemConfig<M>) -> &mut Self {
+        self.add_system(system);
+        *self.always_run.last_mut().unwrap() = true;
+        self
+    }
+
+    /// Nests, orders and gates a set, adding to what previous calls configured. The systems
+    /// that join the set, directly or through a nested set, follow its ordering and only run
+    /// when all of its conditions hold.
+    ///
+    /// ```
+    /// # use seed_ecs::prelude::*;
+    /// # use seed_ecs::system::Schedule;
+    /// #[derive(Debug, PartialEq, Eq)]
+    /// struct PhysicsSet;
+    /// impl SystemSet for PhysicsSet {}
+    /// #[derive(Debug, PartialEq, Eq)]
+    /// struct RenderPrepSet;
+    /// impl SystemSet for RenderPrepSet {}
+    ///
+    /// # fn prepare() {}
+    /// # fn integrate() {}
+    /// let mut schedule = Schedule::new();
+    /// schedule
+    ///     .configure_sets(PhysicsSet.before(RenderPrepSet))
+    ///     .add_system(prepare.in_set(RenderPrepSet))
+    ///     .add_system(integrate.in_set(PhysicsSet));
+    /// schedule.build().unwrap();
+    /// ```
+    pub fn configure_sets(&mut self, config: impl IntoSetConfig) -> &mut Self {
+        let config = config.into_config();
+        let set = self.intern(config.set);
+        for parent in config.parents {
+            let parent = self.intern(parent);
+            self.sets[set].parents.push(parent);
+        }
+        for other in config.before {
+            let other = self.intern(other);
+            self.sets[set].before.push(other);
+        }
+        for other in config.after {
+            let other = self.intern(other);
+            self.sets[set].after.push(other);
+        }
+        let conditions = config.conditions.into_iter().map(|condition| (condition, Tick::new(0)));
+        self.sets[set].conditions.extend(conditions);
+        self.built = false;
+        self
+    }
+
+    /// Makes the schedule panic on its next run if it has [ambiguities](Schedule::ambiguities),
+    /// for the schedules whose result must not depend on the order the systems were added in.
+    /// Only checked with the strict checks, in debug builds or with the `strict-checks` feature.
+    pub fn deny_ambiguities(&mut self, deny: bool) -> &mut Self {
+        self.deny_ambiguities = deny;
+        self.checked_ambiguities = false;
+        self
+    }
+
+    pub fn denies_ambiguities(&self) -> bool {
+        self.deny_ambiguities
+    }
+
+    fn intern(&mut self, set: Box<dyn AnySet>) -> usize {
+        if let Some(index) = self.sets.iter().position(|node| node.set.eq_set(&*set)) {
+            return index;
+        }
+        self.sets.push(SetNode {
+            set,
+            parents: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+        });
+        self.sets.len() - 1
+    }
+
+    /// Sorts the systems by the ordering of their sets, keeping the order they were added in
+    /// where nothing orders them. Done by the next run if not called before.
+    ///
+    /// Fails if the ordering loops, the path of the loop goes through the systems and sets, or
+    /// if an ordering refers to a function added more than once, which of its systems it means
+    /// is unclear then.
+    pub fn build(&mut self) -> Result<(), ScheduleBuildError> {
+        if self.built {
+            return Ok(());
+        }
+        self.check_system_types()?;
+        // Each set is two nodes after the systems, where its systems start and where they end.
+        let systems = self.systems.len();
+        let start = |set: usize| systems + 2 * set;
+        let end = |set: usize| systems + 2 * set + 1;
+        let mut edges = Vec::new();
+        for (set, node) in self.sets.iter().enumerate() {
+            edges.push((start(set), end(set)));
+            for parent in &node.parents {
+                edges.push((start(*parent), start(set)));
+                edges.push((end(set), end(*parent)));
+            }
+            edges.extend(node.before.iter().map(|other| (end(set), start(*other))));
+            edges.extend(node.after.iter().map(|other| (end(*other), start(set))));
+        }
+        for (system, ordering) in self.orderings.iter().enumerate() {
+            for set in &ordering.sets {
+                edges.push((start(*set), system));
+                edges.push((system, end(*set)));
+            }
+            edges.extend(ordering.before.iter().map(|other| (system, start(*other))));
+            edges.extend(ordering.after.iter().map(|other| (end(*other), system)));
+        }
+        let nodes = systems + 2 * self.sets.len();
+        let mut successors = alloc::vec![Vec::new(); nodes];
+        let mut predecessors = alloc::vec![Vec::new(); nodes];
+        for (from, to) in edges {
+            successors[from].push(to);
+            predecessors[to].push(from);
+        }
+
+        // The set nodes are taken as soon as they are ready, they let the earlier systems go.
+        let mut waiting: Vec<usize> = predecessors.iter().map(Vec::len).collect();
+        let mut ready_sets: Vec<usize> = (systems..nodes).filter(|node| waiting[*node] == 0).collect();
+        let mut ready_systems: BTreeSet<usize> = (0..systems).filter(|node| waiting[*node] == 0).collect();
+        let mut order = Vec::with_capacity(systems);
+        let mut visited = 0;
+        while let Some(node) = ready_sets.pop().or_else(|| ready_systems.pop_first()) {
+            visited += 1;
+            if node < systems {
+                order.push(node);
+            }
+            for next in &successors[node] {
+                waiting[*next] -= 1;
+                if waiting[*next] == 0 && *next < systems {
+                    ready_systems.insert(*next);
+                } else if waiting[*next] == 0 {
+                    ready_sets.push(*next);
+                }
+            }
+        }
+        if visited < nodes {
+            return Err(ScheduleBuildError::Cycle(self.cycle(&waiting, &predecessors)));
+        }
+
+        // The systems each system can be reached from, by their index once sorted.
+        let mut position = alloc::vec![0; systems];
+        for (index, system) in order.iter().enumerate() {
+            position[*system] = index;
+        }
+        let mut ancestors = alloc::vec![Vec::new(); systems];
+        for system in 0..systems {
+            let mut reached = alloc::vec![false; nodes];
+            let mut stack = alloc::vec![system];
+            while let Some(node) = stack.pop() {
+                for next in &successors[node] {
+                    if !core::mem::replace(&mut reached[*next], true) {
+                        stack.push(*next);
+                    }
+                }
+            }
+            for later in (0..systems).filter(|node| reached[*node]) {
+                ancestors[position[later]].push(position[system]);
+            }
+        }
+        ancestors.iter_mut().for_each(|ancestors| ancestors.sort_unstable());
+        self.ancestors = ancestors;
+        self.checked_ambiguities = false;
+
+        permute(&mut self.systems, &order);
+        permute(&mut self.always_run, &order);
+        permute(&mut self.orderings, &order);
+        self.gates = self.orderings.iter().map(|ordering| self.gates_of(ordering)).collect();
+        self.built = true;
+        Ok(())
+    }
+
+    // Fails on the first system type an ordering refers to that has more than one system.
+    fn check_system_types(&self) -> Result<(), ScheduleBuildError> {
+        let mut referenced = alloc::vec![false; self.sets.len()];
+        let orderings = self.orderings.iter().map(|ordering| (&ordering.before, &ordering.after));
+        for (before, after) in orderings.chain(self.sets.iter().map(|node| (&node.before, &node.after))) {
+            for set in before.iter().chain(after) {
+                referenced[*set] = true;
+            }
+        }
+        for (set, node) in self.sets.iter().enumerate() {
+            let Some(system_type) = node.set.as_any().downcast_ref::<SystemTypeId>() else {
+                continue;
+            };
+            let count = self.orderings.iter().filter(|ordering| ordering.sets.contains(&set)).count();
+            if referenced[set] && count > 1 {
+                return Err(ScheduleBuildError::AmbiguousSystem {
+                    system: system_type.name(),
+                    count,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Every node left waiting waits on another one left, going up from one of them loops.
+    fn cycle(&self, waiting: &[usize], predecessors: &[Vec<usize>]) -> OrderingCycle {
+        let left = |node: &usize| waiting[*node] > 0;
+        let mut path = alloc::vec![(0..waiting.len()).find(left).unwrap()];
+        let first = loop {
+            let node = *predecessors[*path.last().unwrap()].iter().find(|node| left(node)).unwrap();
+            if let Some(first) = path.iter().position(|other| *other == node) {
+                break first;
+            }
+            path.push(node);
+        };
+        // The path went against the edges, from the end of the loop to its start.
+        let mut cycle: Vec<usize> = path[first..].iter().rev().copied().collect();
+        cycle.push(cycle[0]);
+        let mut names: Vec<String> = Vec::new();
+        for node in cycle {
+            let name = match self.systems.get(node) {
+                Some(system) => system.name().to_string(),
+                None => format!("{:?}", self.sets[(node - self.systems.len()) / 2].set),
+            };
+            // The start and the end of a set read as the set once.
+            if names.last() != Some(&name) {
+                names.push(name);
+            }
+        }
+        OrderingCycle { path: names }
+    }
+
+    // The sets with conditions among the sets of the system and their parents.
+    fn gates_of(&self, ordering: &SystemOrdering) -> Vec<usize> {
+        let mut sets = ordering.sets.clone();
+        let mut index = 0;
+        while let Some(set) = sets.get(index) {
+            let parents = self.sets[*set].parents.iter().filter(|parent| !sets.contains(parent));
+            let parents: Vec<usize> = parents.copied().collect();
+            sets.extend(parents);
+            index += 1;
+        }
+        sets.retain(|set| !self.sets[*set].conditions.is_empty());
+        sets
+    }
+
+    fn build_or_panic(&mut self) {
+        if let Err(error) = self.build() {
+            panic!("{}", error);
+        }
+    }
+
+    /// The pairs of systems that could run in either order: one writes what the other reaches
+    /// and no set or `.after(..)` orders them, only the order they were added in does. What the
+    /// run conditions of a system and of its sets read counts as read by the system, see
+    /// [`RunCondition::add_access`]. The systems and conditions taking the whole world conflict
+    /// with every other system. The pairs come in the order the systems run.
+    ///
+    /// Builds the schedule and initializes its systems first, the systems whose parameters
+    /// conflict are left for their run to panic on.
+    ///
+    /// ```
+    /// # use seed_ecs::prelude::*;
+    /// # use seed_ecs::system::Schedule;
+    /// struct Score(u32);
+    ///
+    /// fn award(mut score: ResMut<Score>) {}
+    /// fn display(score: Res<Score>) {}
+    ///
+    /// let mut world = World::new();
+    /// let mut schedule = Schedule::new();
+    /// schedule.add_system(award).add_system(display);
+    /// assert_eq!(schedule.ambiguities(&mut world).unwrap().len(), 1);
+    ///
+    /// let mut schedule = Schedule::new();
+    /// schedule.add_system(award).add_system(display.after(award));
+    /// assert!(schedule.ambiguities(&mut world).unwrap().is_empty());
+    /// ```
+    pub fn ambiguities(&mut self, world: &mut World) -> Result<Vec<SystemAmbiguity>, ScheduleBuildError> {
+        self.build()?;
+        for system in &mut self.systems {
+            let _ = system.initialize(world);
+        }
+        let metas: Vec<Option<SystemMeta>> = (0..self.systems.len())
+            .map(|index| {
+                let mut meta = self.systems[index].meta()?.clone();
+                let conditions = self.gates[index].iter().flat_map(|set| &self.sets[*set].conditions);
+                conditions.fold(true, |known, (condition, _)| known && condition.add_access(&mut meta)).then_some(meta)
+            })
+            .collect();
+        let mut ambiguities = Vec::new();
+        for second in 0..self.systems.len() {
+            for first in 0..second {
+                let compatible = match (&metas[first], &metas[second]) {
+                    (Some(first), Some(second)) => first.is_compatible(second),
+                    _ => false,
+                };
+                if !compatible && self.ancestors[second].binary_search(&first).is_err() {
+                    ambiguities.push(SystemAmbiguity {
+                        systems: [self.systems[first].name(), self.systems[second].name()],
+                    });
+                }
+            }
+        }
+        Ok(ambiguities)
+    }
+
+    // Panics on the ambiguities of a schedule denying them, once per build.
+    fn check_ambiguities(&mut self, world: &mut World) {
+        #[cfg(any(debug_assertions, feature = "strict-checks"))]
+        if self.deny_ambiguities && !self.checked_ambiguities {
+            let ambiguities = self.ambiguities(world).unwrap_or_else(|error| panic!("{}", error));
+            if !ambiguities.is_empty() {
+                panic!("{}", ScheduleBuildError::Ambiguities(ambiguities));
+            }
+            self.checked_ambiguities = true;
+        }
+    }
+
+    // Forgets the results of the set conditions, the next run checks them again.
+    fn start_frame(&mut self) {
+        self.checked.clear();
+        self.checked.resize(self.sets.len(), None);
+    }
+
+    fn run_system(&mut self, index: usize, world: &mut World) {
+        for gate in 0..self.gates[index].len() {
+            let set = self.gates[index][gate];
+            let holds = *self.checked[set].get_or_insert_with(|| {
+                let mut holds = true;
+                for (condition, last_run) in &mut self.sets[set].conditions {
+                    let this_run = world.increment_change_tick();
+                    let last_run = core::mem::replace(last_run, this_run);
+                    holds &= condition.should_run(world, last_run, this_run);
+                }
+                holds
+            });
+            if !holds {
+                return;
+            }
+        }
+        #[cfg(feature = "trace")]
+        let _span = crate::trace::system(self.systems[index].name());
+        let previous = world.running_system.replace(self.systems[index].name());
+        self.systems[index].run(world);
+        world.running_system = previous;
+    }
+
+    pub fn len(&self) -> usize {
+        self.systems.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.systems.is_empty()
+    }
+
+    /// Initializes the systems that didn't run yet, failing on the first one whose parameters
+    /// conflict. Running the schedule would panic on that system.
+    pub fn initialize(&mut self, world: &mut World) -> Result<(), AccessConflict> {
+        for system in &mut self.systems {
+            system.initialize(world)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the systems, then ends the frame of the [structural metrics](World::clear_metrics)
+    /// and clamps the old ticks of the world and of the systems when enough ticks passed, see
+    /// [`World::check_change_ticks`].
+    ///
+    /// While stepping only the always run systems run.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the systems can't be ordered, see [`Schedule::build`], or on the ambiguities of
+    /// a schedule denying them, see [`Schedule::deny_ambiguities`].
+    pub fn run(&mut self, world: &mut World) {
+        self.build_or_panic();
+        self.check_ambiguities(world);
+        #[cfg(feature = "trace")]
+        let _span = crate::trace::schedule(self.systems.len(), world.enities().len());
+        self.start_frame();
+        let stepping = self.stepping.is_some();
+        for index in 0..self.systems.len() {
+            if !stepping || self.always_run[index] {
+                self.run_system(index, world);
+            }
+        }
+        world.clear_metrics();
+        self.check_change_ticks(world);
+    }
+
+    /// Pauses the systems, they now run one at a time through [`Schedule::step`] starting from
+    /// the first one. Does nothing if stepping is already enabled.
+    pub fn enable_stepping(&mut self) {
+        if self.stepping.is_none() {
+            self.build_or_panic();
+            self.start_frame();
+            self.stepping = Some(self.next_stepped(0));
+        }
+    }
+
+    /// Goes back to running every system on [`Schedule::run`]. The systems the frame being
+    /// stepped didn't reach yet wait for the next run.
+    pub fn disable_stepping(&mut self) {
+        self.stepping = None;
+    }
+
+    pub fn is_stepping(&self) -> bool {
+        self.stepping.is_some()
+    }
+
+    /// The name of the system the next [`Schedule::step`] runs, `None` while not stepping.
+    pub fn cursor(&self) -> Option<&'static str> {
+        self.systems.get(self.stepping?).map(|system| system.name())
+    }
+
+    /// Runs the system at the cursor and the always run systems, in the order they were added,
+    /// and returns the name of the system stepped. Stepping past the last system ends the frame
+    /// and goes back to the first one.
+    ///
+    /// Does nothing while not stepping.
+    pub fn step(&mut self, world: &mut World) -> Option<&'static str> {
+        let cursor = self.stepping?;
+        self.check_ambiguities(world);
+        #[cfg(feature = "trace")]
+        let _span = crate::trace::schedule(self.systems.len(), world.enities().len());
+        for index in 0..self.systems.len() {
+            if index == cursor || self.always_run[index] {
+                self.run_system(index, world);
+            }
+        }
+        let stepped = self.systems.get(cursor).map(|system| system.name());
+        let next = self.next_stepped(cursor + 1);
+        if next == self.systems.len() {
+            self.end_stepped_frame(world);
+        } else {
+            self.stepping = Some(next);
+        }
+        stepped
+    }
+
+    /// Runs the systems from the cursor to the end of the frame and the always run systems, then
+    /// goes back to the first system. Does nothing while not stepping.
+    pub fn continue_frame(&mut self, world: &mut World) {
+        let Some(cursor) = self.stepping else {
+            return;
+        };
+        self.check_ambiguities(world);
+        #[cfg(feature = "trace")]
+        let _span = crate::trace::schedule(self.systems.len(), world.enities().len());
+        for index in 0..self.systems.len() {
+            if index >= cursor || self.always_run[index] {
+                self.run_system(index, world);
+            }
+        }
+        self.end_stepped_frame(world);
+    }
+
+    fn end_stepped_frame(&mut self, world: &mut World) {
+        self.stepping = Some(self.next_stepped(0));
+        world.clear_metrics();
+        self.check_change_ticks(world);
+        self.start_frame();
+    }
+
+    // The first system from `from` that doesn't always run, or the end.
+    fn next_stepped(&self, from: usize) -> usize {
+        (from..self.systems.len()).find(|index| !self.always_run[*index]).unwrap_or(self.systems.len())
+    }
+
+    fn check_change_ticks(&mut self, world: &mut World) {
+        if let Some(tick) = world.check_change_ticks() {
+            for system in &mut self.systems {
+                system.check_change_tick(tick);
+            }
+            for (_, last_run) in self.sets.iter_mut().flat_map(|node| &mut node.conditions) {
+                last_run.check_tick(tick);
+            }
+        }
+    }
+
+    /// Runs the systems, stopping at the first one that panics. Stepping is ignored.
+    ///
+    /// The world stays usable after a panic: what the panicking system deferred, like its
+    /// commands, is dropped instead of applied, and the systems after it don't run this time.
+    #[cfg(feature = "std")]
+    pub fn run_catching(&mut self, world: &mut World) -> Result<(), SystemPanic> {
+        self.build_or_panic();
+        self.check_ambiguities(world);
+        #[cfg(feature = "trace")]
+        let _span = crate::trace::schedule(self.systems.len(), world.enities().len());
+        self.start_frame();
+        let running = world.running_system;
+        for index in 0..self.systems.len() {
+            let run = std::panic::AssertUnwindSafe(|| self.run_system(index, world));
+            if let Err(payload) = std::panic::catch_unwind(run) {
+                world.running_system = running;
+                return Err(SystemPanic {
+                    system: self.systems[index].name(),
+                    payload,
+                });
+            }
+        }
+        world.clear_metrics();
+        self.check_change_ticks(world);
+        Ok(())
+    }
+}
+
+fn permute<T>(items: &mut Vec<T>, order: &[usize]) {
+    let mut taken: Vec<Option<T>> = items.drain(..).map(Some).collect();
+    items.extend(order.iter().map(|index| taken[*index].take().unwrap()));
+}
+
+/// The systems and sets of a schedule are ordered in a loop, see [`Schedule::build`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderingCycle {
+    path: Vec<String>,
+}
+
+impl OrderingCycle {
+    /// The names of the systems and sets along the loop, the first one again at the end.
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+}
+
+impl fmt::Display for OrderingCycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Systems ordered in a cycle: {}", self.path.join(" -> "))
+    }
+}
+
+impl core::error::Error for OrderingCycle {}
+
+/// Two systems that conflict and run in the order they were added in, see
+/// [`Schedule::ambiguities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SystemAmbiguity {
+    systems: [&'static str; 2],
+}
+
+impl SystemAmbiguity {
+    /// The names of the two systems, the one running first first.
+    pub fn systems(&self) -> [&'static str; 2] {
+        self.systems
+    }
+}
+
+impl fmt::Display for SystemAmbiguity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} and {}", self.systems[0], self.systems[1])
+    }
+}
+
+/// Why [`Schedule::build`] failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleBuildError {
+    Cycle(OrderingCycle),
+    /// An ordering refers to a function added as `count` systems.
+    AmbiguousSystem { system: &'static str, count: usize },
+    /// Systems of a schedule denying ambiguities conflict and nothing orders them.
+    Ambiguities(Vec<SystemAmbiguity>),
+}
+
+impl fmt::Display for ScheduleBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Cycle(cycle) => cycle.fmt(f),
+            Self::AmbiguousSystem { system, count } => write!(
+                f,
+                "System {} is ordered against but was added {} times, put each copy in a set of its own with \
+                 `.in_set(..)` and order against the sets instead",
+                system, count
+            ),
+            Self::Ambiguities(ambiguities) => {
+                write!(
+                    f,
+                    "Systems access the same data in an order nothing fixes, order them with `.after(..)` or sets:"
+                )?;
+                for (i, ambiguity) in ambiguities.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { "" } else { "," }, ambiguity)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl core::error::Error for ScheduleBuildError {}
+
+/// A system panicked in [`Schedule::run_catching`].
+pub struct SystemPanic {
+    system: &'static str,
+    payload: Box<dyn Any + Send>,
+}
+
+impl SystemPanic {
+    pub fn system_name(&self) -> &'static str {
+        self.system
+    }
+
+    /// The panic message, if the panic was given one.
+    pub fn message(&self) -> Option<&str> {
+        let literal = self.payload.downcast_ref::<&'static str>().copied();
+        literal.or_else(|| self.payload.downcast_ref::<alloc::string::String>().map(|s| s.as_str()))
+    }
+
+    /// The value the system panicked with, to resume the panic with.
+    pub fn into_payload(self) -> Box<dyn Any + Send> {
+        self.payload
+    }
+}
+
+impl fmt::Debug for SystemPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SystemPanic")
+            .field("system", &self.system)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+impl fmt::Display for SystemPanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "System {} panicked", self.system)?;
+        if let Some(message) = self.message() {
+            write!(f, ": {}", message)?;
+        }
+        Ok(())
+    }
+}
+
+impl core::error::Error for SystemPanic {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Commands;
+    use crate::component::Component;
+    use crate::entity::Entity;
+    use crate::observer::{DeferredWorld, OnAdd, Trigger};
+    use crate::query::Query;
+    use crate::system::{resource_changed, IntoSetConfig, IntoSystem, Res, ResMut, SystemSet};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Component)]
+    struct Marker(u32);
+    struct Frame(u32);
+    struct Fail(bool);
+
+    fn spawn_then_fail(mut commands: Commands, frame: Res<Frame>, fail: Res<Fail>) {
+        let frame = frame.0;
+        commands.add(move |world: &mut World| {
+            let e = *world.spawn_entity();
+            world.add_component(e, Marker(frame));
+        });
+        if fail.0 {
+            panic!("boom");
+        }
+    }
+
+    fn count_frames(mut frame: ResMut<Frame>) {
+        frame.0 += 1;
+    }
+
+    fn markers(world: &mut World) -> Vec<Marker> {
+        let query = world.query::<&Marker>();
+        query.iter(world).copied().collect()
+    }
+
+    #[test]
+    fn panicking_system_is_discarded() {
+        let mut world = World::new();
+        world.insert_resource(Frame(0));
+        world.insert_resource(Fail(true));
+        let mut schedule = Schedule::new();
+        schedule.add_system(spawn_then_fail).add_system(count_frames);
+
+        let panic = schedule.run_catching(&mut world).unwrap_err();
+        assert!(panic.system_name().ends_with("spawn_then_fail"), "{}", panic.system_name());
+        assert_eq!(panic.message(), Some("boom"));
+        assert!(panic.to_string().ends_with("spawn_then_fail panicked: boom"));
+        assert_eq!(world.get_resource::<Frame>().unwrap().0, 0);
+        assert_eq!(markers(&mut world), []);
+
+        world.insert_resource(Fail(false));
+        schedule.run_catching(&mut world).unwrap();
+        schedule.run_catching(&mut world).unwrap();
+        assert_eq!(markers(&mut world), [Marker(0), Marker(1)]);
+
+        // An observer panicking while the commands apply doesn't break the next triggers.
+        static OBSERVED: AtomicUsize = AtomicUsize::new(0);
+        world.add_observer(|trigger: Trigger<OnAdd<Marker>>, world: &mut DeferredWorld| {
+            OBSERVED.fetch_add(1, Ordering::Relaxed);
+            if world.get_component::<Marker>(trigger.entity()) == Some(&Marker(2)) {
+                panic!("observer");
+            }
+        });
+        assert_eq!(schedule.run_catching(&mut world).unwrap_err().message(), Some("observer"));
+        // The component was added before the observer ran, the frame counter didn't run.
+        assert_eq!(markers(&mut world), [Marker(0), Marker(1), Marker(2)]);
+        world.get_resource_mut::<Frame>().unwrap().0 = 3;
+        schedule.run_catching(&mut world).unwrap();
+        assert_eq!(markers(&mut world), [Marker(0), Marker(1), Marker(2), Marker(3)]);
+        assert_eq!(OBSERVED.load(Ordering::Relaxed), 2);
+    }
+
+    #[derive(Default)]
+    struct Log(Vec<&'static str>);
+
+    fn input(mut log: ResMut<Log>) {
+        log.0.push("input");
+    }
+
+    fn physics(mut log: ResMut<Log>) {
+        log.0.push("physics");
+    }
+
+    fn ai(mut log: ResMut<Log>) {
+        log.0.push("ai");
+    }
+
+    fn render(mut log: ResMut<Log>) {
+        log.0.push("render");
+    }
+
+    fn game() -> Schedule {
+        let mut schedule = Schedule::new();
+        schedule.add_system(input).add_system(physics).add_always_run_system(render).add_system(ai);
+        schedule
+    }
+
+    fn take_log(world: &mut World) -> Vec<&'static str> {
+        core::mem::take(&mut world.get_resource_mut::<Log>().unwrap().0)
+    }
+
+    #[test]
+    fn stepping_runs_one_system_at_a_time() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        let mut schedule = game();
+        assert_eq!(schedule.step(&mut world), None);
+        schedule.run(&mut world);
+        let frame = take_log(&mut world);
+        assert_eq!(frame, ["input", "physics", "render", "ai"]);
+
+        schedule.enable_stepping();
+        assert!(schedule.cursor().unwrap().ends_with("input"));
+        let mut stepped = Vec::new();
+        for _ in 0..3 {
+            stepped.push(schedule.step(&mut world).unwrap());
+        }
+        assert!(stepped.iter().zip(["input", "physics", "ai"]).all(|(name, short)| name.ends_with(short)));
+        // Rendering ran on every step, the others in the order of a normal frame.
+        let log = take_log(&mut world);
+        assert_eq!(log, ["input", "render", "physics", "render", "render", "ai"]);
+        let logic: Vec<_> = log.iter().filter(|name| **name != "render").collect();
+        assert!(logic.into_iter().eq(frame.iter().filter(|name| **name != "render")));
+        // The frame ended, the cursor is back at the start.
+        assert!(schedule.cursor().unwrap().ends_with("input"));
+
+        // The logic stays paused on normal runs.
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["render"]);
+    }
+
+    #[test]
+    fn continuing_and_disabling_stepping() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        let mut schedule = game();
+        schedule.enable_stepping();
+        schedule.step(&mut world);
+        schedule.continue_frame(&mut world);
+        assert_eq!(take_log(&mut world), ["input", "render", "physics", "render", "ai"]);
+        assert!(schedule.cursor().unwrap().ends_with("input"));
+
+        schedule.step(&mut world);
+        schedule.disable_stepping();
+        assert_eq!(schedule.cursor(), None);
+        take_log(&mut world);
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["input", "physics", "render", "ai"]);
+        schedule.continue_frame(&mut world);
+        assert_eq!(take_log(&mut world), Vec::<&str>::new());
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct PhysicsSet;
+    impl SystemSet for PhysicsSet {}
+    #[derive(Debug, PartialEq, Eq)]
+    struct RenderPrepSet;
+    impl SystemSet for RenderPrepSet {}
+    #[derive(Debug, PartialEq, Eq)]
+    enum Physics {
+        Collisions,
+    }
+    impl SystemSet for Physics {}
+
+    fn logger(name: &'static str) -> impl FnMut(ResMut<Log>) + Send + Sync + 'static {
+        move |mut log: ResMut<Log>| log.0.push(name)
+    }
+
+    #[test]
+    fn sets_order_their_members() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(logger("extract").in_set(RenderPrepSet))
+            .add_system(logger("input"))
+            .add_system(logger("audio").after(PhysicsSet))
+            .add_system(logger("collide").in_set(Physics::Collisions))
+            .add_system(logger("integrate").in_set(PhysicsSet).before(Physics::Collisions))
+            .configure_sets(PhysicsSet.before(RenderPrepSet))
+            .configure_sets(Physics::Collisions.in_set(PhysicsSet));
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["input", "integrate", "collide", "extract", "audio"]);
+
+        // Adding systems sorts them again, the ones already sorted keep their order.
+        schedule.add_system(logger("debris").in_set(Physics::Collisions));
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["input", "integrate", "collide", "debris", "extract", "audio"]);
+    }
+
+    struct Paused(bool);
+    struct Settings;
+
+    #[test]
+    fn set_conditions_gate_every_member() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        world.insert_resource(Paused(false));
+        let mut schedule = Schedule::new();
+        schedule
+            .configure_sets(PhysicsSet.run_if(|world: &World, _: Tick, _: Tick| {
+                !world.get_resource::<Paused>().unwrap().0
+            }))
+            .configure_sets(Physics::Collisions.in_set(PhysicsSet))
+            .configure_sets(RenderPrepSet.run_if(crate::system::resource_changed::<Settings>()))
+            .add_system(logger("integrate").in_set(PhysicsSet))
+            .add_system(logger("collide").in_set(Physics::Collisions))
+            .add_system(logger("input"))
+            .add_system(logger("extract").in_set(RenderPrepSet))
+            .add_system(logger("upload").in_set(RenderPrepSet));
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["integrate", "collide", "input"]);
+
+        world.insert_resource(Paused(true));
+        world.insert_resource(Settings);
+        schedule.run(&mut world);
+        // The change is seen once per run, by every member.
+        assert_eq!(take_log(&mut world), ["input", "extract", "upload"]);
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["input"]);
+    }
+
+    #[test]
+    fn systems_order_against_functions() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        world.insert_resource(Paused(false));
+        let unpaused = |world: &World, _: Tick, _: Tick| !world.get_resource::<Paused>().unwrap().0;
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(render.after(physics))
+            .add_system(ai.before(render).after(physics))
+            .add_system(physics.run_if(unpaused).after(input))
+            .add_system(input);
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["input", "physics", "ai", "render"]);
+
+        // A function no system was built from orders nothing.
+        let mut schedule = Schedule::new();
+        schedule.add_system(render.after(physics)).add_system(input.after(render));
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["render", "input"]);
+    }
+
+    #[test]
+    fn functions_added_twice_are_ambiguous() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        let mut schedule = Schedule::new();
+        schedule.add_system(physics).add_system(render).add_system(physics);
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["physics", "render", "physics"]);
+
+        schedule.add_system(ai.after(physics));
+        let error = schedule.build().unwrap_err();
+        let ScheduleBuildError::AmbiguousSystem { system, count } = error else {
+            panic!("{}", error);
+        };
+        assert!(system.ends_with("physics") && count == 2, "{}", system);
+        assert!(error.to_string().contains("`.in_set(..)`"), "{}", error);
+
+        // The sets tell the copies apart.
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(ai.after(PhysicsSet))
+            .add_system(physics.in_set(PhysicsSet))
+            .add_system(render.after(ai))
+            .add_system(physics.in_set(RenderPrepSet).after(render));
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["physics", "ai", "render", "physics"]);
+    }
+
+    #[test]
+    fn functions_and_sets_order_together() {
+        let mut world = World::new();
+        world.insert_resource(Log::default());
+        let mut schedule = Schedule::new();
+        schedule
+            .configure_sets(PhysicsSet.after(input))
+            .configure_sets(RenderPrepSet.after(ai))
+            .add_system(logger("extract").in_set(RenderPrepSet))
+            .add_system(render.after(RenderPrepSet))
+            .add_system(ai.after(physics).before(render))
+            .add_system(physics.in_set(PhysicsSet))
+            .add_system(input);
+        schedule.run(&mut world);
+        assert_eq!(take_log(&mut world), ["input", "physics", "ai", "extract", "render"]);
+
+        // Cycles through functions name them.
+        schedule.configure_sets(PhysicsSet.after(render));
+        let Err(ScheduleBuildError::Cycle(cycle)) = schedule.build() else {
+            panic!("the ordering loops");
+        };
+        assert!(cycle.path().iter().any(|name| name.ends_with("::render")), "{}", cycle);
+    }
+
+    #[test]
+    fn ordering_cycles_name_their_path() {
+        let mut schedule = Schedule::new();
+        schedule
+            .configure_sets(PhysicsSet.before(RenderPrepSet))
+            .add_system(input)
+            .add_system(physics.in_set(PhysicsSet))
+            .add_system(render.in_set(RenderPrepSet).before(PhysicsSet));
+        let Err(ScheduleBuildError::Cycle(cycle)) = schedule.build() else {
+            panic!("the ordering loops");
+        };
+        let path: Vec<&str> = cycle.path().iter().map(|name| name.rsplit("::").next().unwrap()).collect();
+        assert_eq!(path, ["PhysicsSet", "RenderPrepSet", "render", "PhysicsSet"]);
+        assert!(cycle.to_string().contains("PhysicsSet -> RenderPrepSet -> "), "{}", cycle);
+
+        // Nesting a set in itself loops as well.
+        let mut schedule = Schedule::new();
+        schedule
+            .configure_sets(Physics::Collisions.in_set(PhysicsSet))
+            .configure_sets(PhysicsSet.in_set(Physics::Collisions));
+        let Err(ScheduleBuildError::Cycle(cycle)) = schedule.build() else {
+            panic!("the nesting loops");
+        };
+        assert_eq!(cycle.path(), ["PhysicsSet", "Collisions", "PhysicsSet"]);
+    }
+
+    #[test]
+    fn panic_inside_sorted_iteration() {
+        let mut world = World::new();
+        for i in [3, 1, 2] {
+            let e = *world.spawn_entity();
+            world.add_component(e, Marker(i));
+        }
+        world.insert_resource(Fail(true));
+        world.insert_resource(Frame(0));
+        let mut schedule = Schedule::new();
+        schedule.add_system(|mut query: Query<(Entity, &mut Marker)>, fail: Res<Fail>, mut seen: ResMut<Frame>| {
+            for (_, marker) in query.iter_sorted_by_key(|(_, marker)| marker.0) {
+                seen.0 = seen.0 * 10 + marker.0;
+                if fail.0 {
+                    panic!("mid iteration");
+                }
+            }
+        });
+        assert!(schedule.run_catching(&mut world).is_err());
+        world.insert_resource(Fail(false));
+        schedule.run_catching(&mut world).unwrap();
+        assert_eq!(world.get_resource::<Frame>().unwrap().0, 1123);
+    }
+
+    #[test]
+    fn runs_are_reproducible() {
+        fn scramble(mut query: Query<&mut Marker>, frame: Res<Frame>) {
+            for mut marker in query.iter_mut() {
+                marker.0 = marker.0.wrapping_mul(31).wrapping_add(frame.0);
+            }
+        }
+        fn spawn_marker(mut commands: Commands, mut frame: ResMut<Frame>) {
+            let seed = frame.0;
+            commands.add(move |world: &mut World| {
+                let e = *world.spawn_entity();
+                world.add_component(e, Marker(seed));
+            });
+            frame.0 = frame.0.wrapping_mul(7) % 1009;
+        }
+        fn salt(mut query: Query<(Entity, &mut Marker)>) {
+            for (entity, mut marker) in query.iter_mut() {
+                marker.0 ^= entity.index();
+            }
+        }
+        // Every system touching the same data is ordered, the order they were added in is unused.
+        fn checksum() -> u64 {
+            let mut world = World::new();
+            world.insert_resource(Frame(1));
+            let mut schedule = Schedule::new();
+            schedule
+                .deny_ambiguities(true)
+                .add_system(spawn_then_fail.after(PhysicsSet))
+                .add_system(salt.after(spawn_marker))
+                .add_system(count_frames.in_set(PhysicsSet).after(salt))
+                .add_system(spawn_marker.after(scramble))
+                .add_system(scramble);
+            world.insert_resource(Fail(false));
+            assert_eq!(schedule.ambiguities(&mut world), Ok(Vec::new()));
+            for _ in 0..20 {
+                schedule.run(&mut world);
+            }
+            let query = world.query::<(Entity, &Marker)>();
+            let mut entities: Vec<(Entity, Marker)> = query.iter(&world).map(|(e, marker)| (e, *marker)).collect();
+            entities.sort_by_key(|(e, _)| e.index());
+            entities.iter().fold(world.get_resource::<Frame>().unwrap().0 as u64, |hash, (e, marker)| {
+                hash.wrapping_mul(1_000_003) ^ ((e.index() as u64) << 32 | marker.0 as u64)
+            })
+        }
+        let first = checksum();
+        assert!((0..10).all(|_| checksum() == first));
+    }
+
+    #[test]
+    fn unordered_conflicts_are_ambiguous() {
+        fn write_frame(mut frame: ResMut<Frame>) {}
+        fn read_frame(frame: Res<Frame>) {}
+        fn read_fail(fail: Res<Fail>) {}
+
+        let mut world = World::new();
+        world.insert_resource(Frame(0));
+        world.insert_resource(Fail(false));
+        let mut schedule = Schedule::new();
+        schedule.add_system(write_frame).add_system(read_frame).add_system(read_fail);
+        let ambiguities = schedule.ambiguities(&mut world).unwrap();
+        assert_eq!(ambiguities.len(), 1);
+        assert!(ambiguities[0].systems()[0].ends_with("write_frame"));
+        assert!(ambiguities[0].systems()[1].ends_with("read_frame"));
+
+        // Through a set, or the other way around.
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(read_frame.after(PhysicsSet))
+            .add_system(write_frame.in_set(PhysicsSet))
+            .add_system(read_fail);
+        assert_eq!(schedule.ambiguities(&mut world), Ok(Vec::new()));
+
+        // The conditions read too, checking them races with the writers.
+        let mut schedule = Schedule::new();
+        schedule.add_system(write_frame).add_system(read_fail.run_if(resource_changed::<Frame>()));
+        assert_eq!(schedule.ambiguities(&mut world).unwrap().len(), 1);
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(write_frame)
+            .add_system(read_fail.in_set(PhysicsSet))
+            .configure_sets(PhysicsSet.run_if(resource_changed::<Frame>()));
+        assert_eq!(schedule.ambiguities(&mut world).unwrap().len(), 1);
+        let mut schedule = Schedule::new();
+        schedule
+            .add_system(write_frame)
+            .add_system(read_fail.run_if(resource_changed::<Frame>()).after(write_frame));
+        assert_eq!(schedule.ambiguities(&mut world), Ok(Vec::new()));
+        let mut schedule = Schedule::new();
+        schedule.add_system(read_frame).add_system(read_fail.run_if(|_: &World, _: Tick, _: Tick| true));
+        assert_eq!(schedule.ambiguities(&mut world).unwrap().len(), 1);
+
+        // Taking the whole world conflicts with everything.
+        let mut schedule = Schedule::new();
+        schedule.add_system(read_fail).add_system(crate::ClearDanglingReferences);
+        assert_eq!(schedule.ambiguities(&mut world).unwrap().len(), 1);
+    }
+
+    #[test]
+    #[cfg(any(debug_assertions, feature = "strict-checks"))]
+    #[should_panic(expected = "Systems access the same data in an order nothing fixes")]
+    fn denied_ambiguities_panic() {
+        let mut world = World::new();
+        world.insert_resource(Frame(0));
+        let mut schedule = Schedule::new();
+        schedule.deny_ambiguities(true);
+        schedule.add_system(count_frames).add_system(spawn_then_fail);
+        world.insert_resource(Fail(false));
+        schedule.run(&mut world);
+    }
+}
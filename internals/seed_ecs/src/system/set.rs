@@ -0,0 +1,212 @@
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any::{Any, TypeId};
+use core::fmt;
+
+use super::{IntoSystem, RunCondition, System};
+
+/// A label grouping systems, ordered and run conditionally as a whole, see
+/// [`Schedule::configure_sets`](super::Schedule::configure_sets).
+///
+/// Unit structs and the variants of fieldless enums make good sets, two sets are the same when
+/// their values are equal.
+pub trait SystemSet: fmt::Debug + Eq + Send + Sync + 'static {}
+
+pub(super) trait AnySet: fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn eq_set(&self, other: &dyn AnySet) -> bool;
+}
+
+/// The set of the systems of one type, see [`System::system_type`]. Systems and functions
+/// stand for their set where a set is expected, `.after(movement)` orders after every system
+/// built from `movement`.
+#[derive(Clone, Copy)]
+pub struct SystemTypeId {
+    id: TypeId,
+    name: &'static str,
+}
+
+impl SystemTypeId {
+    /// The set of the systems of type `S`, named `name` in the errors.
+    pub fn of<S: ?Sized + 'static>(name: &'static str) -> Self {
+        Self {
+            id: TypeId::of::<S>(),
+            name,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl PartialEq for SystemTypeId {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for SystemTypeId {}
+
+impl fmt::Debug for SystemTypeId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+impl SystemSet for SystemTypeId {}
+
+/// Conversion into the set an ordering refers to, implemented for the sets, for the systems and
+/// for the functions that are systems.
+pub trait IntoSystemSet<Marker> {
+    type Set: SystemSet;
+
+    fn into_system_set(self) -> Self::Set;
+}
+
+impl<S: SystemSet> IntoSystemSet<()> for S {
+    type Set = S;
+
+    fn into_system_set(self) -> S {
+        self
+    }
+}
+
+#[doc(hidden)]
+pub struct IsSystemTypeSet;
+
+impl<Marker, S: IntoSystem<Marker>> IntoSystemSet<(IsSystemTypeSet, Marker)> for S {
+    type Set = SystemTypeId;
+
+    fn into_system_set(self) -> SystemTypeId {
+        self.into_system().system_type()
+    }
+}
+
+impl<S: SystemSet> AnySet for S {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn eq_set(&self, other: &dyn AnySet) -> bool {
+        other.as_any().downcast_ref::<S>() == Some(self)
+    }
+}
+
+/// A set along with the sets it joins, its ordering and the conditions its systems run under,
+/// built from a [`SystemSet`] by the methods of [`IntoSetConfig`].
+pub struct SetConfig {
+    pub(super) set: Box<dyn AnySet>,
+    pub(super) parents: Vec<Box<dyn AnySet>>,
+    pub(super) before: Vec<Box<dyn AnySet>>,
+    pub(super) after: Vec<Box<dyn AnySet>>,
+    pub(super) conditions: Vec<Box<dyn RunCondition>>,
+}
+
+pub trait IntoSetConfig: Sized {
+    fn into_config(self) -> SetConfig;
+
+    /// Nests the set in `parent`, its systems are members of `parent` too.
+    fn in_set(self, parent: impl SystemSet) -> SetConfig {
+        let mut config = self.into_config();
+        config.parents.push(Box::new(parent));
+        config
+    }
+
+    /// Runs the systems of the set before the systems of `other`, a set or a system.
+    fn before<M>(self, other: impl IntoSystemSet<M>) -> SetConfig {
+        let mut config = self.into_config();
+        config.before.push(Box::new(other.into_system_set()));
+        config
+    }
+
+    /// Runs the systems of the set after the systems of `other`, a set or a system.
+    fn after<M>(self, other: impl IntoSystemSet<M>) -> SetConfig {
+        let mut config = self.into_config();
+        config.after.push(Box::new(other.into_system_set()));
+        config
+    }
+
+    /// Runs the systems of the set only when the condition holds. It is checked once per run of
+    /// the schedule, right before the first of them.
+    fn run_if(self, condition: impl RunCondition) -> SetConfig {
+        let mut config = self.into_config();
+        config.conditions.push(Box::new(condition));
+        config
+    }
+}
+
+impl<S: SystemSet> IntoSetConfig for S {
+    fn into_config(self) -> SetConfig {
+        SetConfig {
+            set: Box::new(self),
+            parents: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+            conditions: Vec::new(),
+        }
+    }
+}
+
+impl IntoSetConfig for SetConfig {
+    fn into_config(self) -> SetConfig {
+        self
+    }
+}
+
+/// A system along with the sets it joins and its ordering, built by the methods of
+/// [`IntoSystemConfig`].
+pub struct SystemConfig {
+    pub(super) system: Box<dyn System>,
+    pub(super) sets: Vec<Box<dyn AnySet>>,
+    pub(super) before: Vec<Box<dyn AnySet>>,
+    pub(super) after: Vec<Box<dyn AnySet>>,
+}
+
+/// Conversion into a [`SystemConfig`], implemented for the systems and for the configs
+/// themselves. A condition is given to the system first, `system.run_if(..).in_set(..)`.
+pub trait IntoSystemConfig<Marker>: Sized {
+    fn into_config(self) -> SystemConfig;
+
+    /// Makes the system a member of `set`, ordered and gated along with it.
+    fn in_set(self, set: impl SystemSet) -> SystemConfig {
+        let mut config = self.into_config();
+        config.sets.push(Box::new(set));
+        config
+    }
+
+    /// Runs the system before the systems of `set`, or before `set` itself if it is a system.
+    fn before<M>(self, set: impl IntoSystemSet<M>) -> SystemConfig {
+        let mut config = self.into_config();
+        config.before.push(Box::new(set.into_system_set()));
+        config
+    }
+
+    /// Runs the system after the systems of `set`, or after `set` itself if it is a system.
+    fn after<M>(self, set: impl IntoSystemSet<M>) -> SystemConfig {
+        let mut config = self.into_config();
+        config.after.push(Box::new(set.into_system_set()));
+        config
+    }
+}
+
+impl<Marker, S: IntoSystem<Marker>> IntoSystemConfig<Marker> for S {
+    fn into_config(self) -> SystemConfig {
+        SystemConfig {
+            system: Box::new(self.into_system()),
+            sets: Vec::new(),
+            before: Vec::new(),
+            after: Vec::new(),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub struct IsSystemConfig;
+
+impl IntoSystemConfig<IsSystemConfig> for SystemConfig {
+    fn into_config(self) -> SystemConfig {
+        self
+    }
+}
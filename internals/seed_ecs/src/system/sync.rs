@@ -0,0 +1,148 @@
+use alloc::vec::Vec;
+
+use super::{IntoSystem, Removed, System};
+use crate::commands::Commands;
+use crate::component::Component;
+use crate::entity::Entity;
+use crate::query::{Changed, Query};
+use crate::World;
+
+/// A system mirroring `Src` into a `Dst` computed by `map` on the same entity, like a health bar
+/// width following the health. Only the sources changed since the last run are mapped: the
+/// `Dst` already there is replaced if the value differs, so equal values don't mark it changed,
+/// the missing ones are inserted together by one command. The `Dst` of the entities that lost
+/// their `Src` is removed.
+///
+/// ```
+/// # use seed_ecs::prelude::*;
+/// # use seed_ecs::system::sync_component;
+/// #[derive(Component)]
+/// struct Health(u32);
+/// #[derive(PartialEq, Component)]
+/// struct BarWidth(u32);
+///
+/// let mut world = World::new();
+/// let player = world.spawn_batch([Health(10)])[0];
+/// let mut sync = sync_component(|health: &Health| BarWidth(health.0 * 2));
+/// sync.initialize(&mut world).unwrap();
+/// sync.run(&mut world);
+/// assert_eq!(world.get_component::<BarWidth>(player).unwrap().0, 20);
+/// ```
+pub fn sync_component<Src, Dst>(map: fn(&Src) -> Dst) -> impl System
+where
+    Src: Component,
+    Dst: Component + PartialEq,
+{
+    let system = move |mut sources: Query<(Entity, &Src, Option<&mut Dst>), Changed<Src>>,
+                       removed: Removed<Src>,
+                       mut commands: Commands| {
+        let mut missing = Vec::new();
+        for (entity, source, target) in sources.iter_mut() {
+            match target {
+                Some(mut target) => {
+                    target.set_if_neq(map(source));
+                }
+                None => missing.push((entity, map(source))),
+            }
+        }
+        if !missing.is_empty() {
+            commands.insert_batch(missing);
+        }
+        let gone: Vec<Entity> = removed.iter().collect();
+        if !gone.is_empty() {
+            commands.add(move |world: &mut World| {
+                for entity in gone {
+                    // Removals are reported twice, and the entity may have got a new source since.
+                    if !world.has_component::<Src>(entity) {
+                        world.remove_component::<Dst>(entity);
+                    }
+                }
+            });
+        }
+    };
+    system.into_system()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query::Added;
+    use crate::system::ResMut;
+
+    #[derive(Component)]
+    struct Health(u32);
+    #[derive(Debug, PartialEq, Component)]
+    struct BarWidth(u32);
+
+    fn bar_width(health: &Health) -> BarWidth {
+        BarWidth(health.0.min(100))
+    }
+
+    #[derive(Default)]
+    struct Marked(Vec<Entity>);
+
+    fn track(widths: Query<Entity, Changed<BarWidth>>, mut marked: ResMut<Marked>) {
+        marked.0 = widths.iter().collect();
+    }
+
+    #[test]
+    fn targets_follow_their_sources() {
+        let mut world = World::new();
+        world.init_resource::<Marked>();
+        let entities = world.spawn_batch([Health(10), Health(40)]);
+        let mut sync = sync_component(bar_width);
+        let mut track = IntoSystem::into_system(track);
+        sync.initialize(&mut world).unwrap();
+        track.initialize(&mut world).unwrap();
+        let mut frame = |world: &mut World| {
+            sync.run(world);
+            track.run(world);
+            world.clear_trackers();
+            world.get_resource::<Marked>().unwrap().0.clone()
+        };
+
+        // Created on first sight.
+        assert_eq!(frame(&mut world), entities);
+        assert_eq!(world.get_component::<BarWidth>(entities[0]), Some(&BarWidth(10)));
+        assert_eq!(world.get_component::<BarWidth>(entities[1]), Some(&BarWidth(40)));
+
+        // Left alone without changes.
+        assert_eq!(frame(&mut world), []);
+
+        // Updated on the frames the source changed, unmarked when the value stays the same.
+        world.get_component_mut::<Health>(entities[0]).unwrap().0 = 20;
+        world.get_component_mut::<Health>(entities[1]).unwrap().0 = 40;
+        assert_eq!(frame(&mut world), [entities[0]]);
+        assert_eq!(world.get_component::<BarWidth>(entities[0]), Some(&BarWidth(20)));
+        world.get_component_mut::<Health>(entities[1]).unwrap().0 = 300;
+        world.get_component_mut::<Health>(entities[0]).unwrap().0 = 20;
+        assert_eq!(frame(&mut world), [entities[1]]);
+        assert_eq!(world.get_component::<BarWidth>(entities[1]), Some(&BarWidth(100)));
+        world.get_component_mut::<Health>(entities[1]).unwrap().0 = 200;
+        assert_eq!(frame(&mut world), []);
+    }
+
+    #[test]
+    fn targets_are_removed_with_their_sources() {
+        let mut world = World::new();
+        let entities = world.spawn_batch([Health(1), Health(2), Health(3)]);
+        let mut sync = sync_component(bar_width);
+        sync.initialize(&mut world).unwrap();
+        sync.run(&mut world);
+        world.clear_trackers();
+
+        world.remove_component::<Health>(entities[0]);
+        world.despawn_batch(&entities[1..2]);
+        sync.run(&mut world);
+        world.clear_trackers();
+        assert!(!world.has_component::<BarWidth>(entities[0]));
+        assert_eq!(world.get_component::<BarWidth>(entities[2]), Some(&BarWidth(3)));
+
+        // Back with a source while its removal is still reported.
+        world.add_component(entities[0], Health(5));
+        sync.run(&mut world);
+        assert_eq!(world.get_component::<BarWidth>(entities[0]), Some(&BarWidth(5)));
+        let mut added = world.query_filtered::<Entity, Added<BarWidth>>();
+        assert_eq!(added.query(&world).iter().collect::<Vec<_>>(), [entities[0]]);
+    }
+}
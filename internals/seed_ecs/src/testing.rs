@@ -0,0 +1,501 @@
+//! A model based fuzzer for worlds, enabled by the `testing` feature.
+//!
+//! A [`WorldModel`] does everything a [`World`] does with plain hash maps. Random sequences of
+//! [`Op`]s are applied to both with [`apply_ops`], which checks every result against the model,
+//! and [`assert_equivalent`] compares what is left. Any component type that is `Clone`,
+//! `PartialEq` and `Debug` can take part:
+//!
+//! ```
+//! use seed_ecs::component::Component;
+//! use seed_ecs::testing::{apply_ops, assert_equivalent, OpGenerator, WorldModel};
+//! use seed_ecs::World;
+//!
+//! #[derive(Debug, Clone, PartialEq, Component)]
+//! struct Health(u32);
+//! #[derive(Debug, Clone, PartialEq, Component)]
+//! struct Stunned;
+//!
+//! for seed in 0..10 {
+//!     let ops = OpGenerator::new(seed)
+//!         .with_component(|rng| Health(rng.below(100) as u32))
+//!         .with_component(|_| Stunned);
+//!     let mut world = World::new();
+//!     let mut model = WorldModel::new();
+//!     apply_ops(&mut world, &mut model, ops.take(200));
+//!     assert_equivalent(&world, &model);
+//! }
+//! ```
+//!
+//! Failures name the step and the operation, the generator replays the same sequence from the same
+//! seed so it can be shrunk by hand.
+
+use std::any::{type_name, Any, TypeId};
+use std::collections::HashMap;
+use std::fmt;
+use std::string::String;
+use std::vec::Vec;
+
+use crate::entity::Entity;
+use crate::World;
+
+/// Component types that can be checked against a [`WorldModel`].
+pub trait TestComponent: crate::component::Component + Clone + PartialEq + fmt::Debug {}
+
+impl<T: crate::component::Component + Clone + PartialEq + fmt::Debug> TestComponent for T {}
+
+/// Keeps the values of one component type for the model.
+trait ModelStorage {
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+
+    fn name(&self) -> &'static str;
+
+    fn despawn(&mut self, entity: Entity);
+
+    /// Describes every difference between the world and the model.
+    fn compare(&self, world: &World, differences: &mut Vec<String>);
+
+    /// Iterates a query over the component and compares what it yields with the model.
+    fn query_check(&self, world: &mut World, differences: &mut Vec<String>);
+}
+
+struct TypedModel<T> {
+    values: HashMap<Entity, T>,
+}
+
+impl<T: TestComponent> ModelStorage for TypedModel<T> {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+
+    fn name(&self) -> &'static str {
+        type_name::<T>()
+    }
+
+    fn despawn(&mut self, entity: Entity) {
+        self.values.remove(&entity);
+    }
+
+    fn compare(&self, world: &World, differences: &mut Vec<String>) {
+        for (entity, expected) in &self.values {
+            let found = world.get_component::<T>(*entity);
+            if found != Some(expected) {
+                differences.push(format!("{} {}: expected {:?}, found {:?}", entity, self.name(), expected, found));
+            }
+        }
+        let live = world.storage_stats::<T>().live;
+        if live != self.values.len() {
+            differences.push(format!("{}: {} values stored, expected {}", self.name(), live, self.values.len()));
+        }
+    }
+
+    fn query_check(&self, world: &mut World, differences: &mut Vec<String>) {
+        let query = world.query::<(Entity, &T)>();
+        let mut previous: Option<Entity> = None;
+        let mut count = 0;
+        for (entity, value) in query.iter(world) {
+            count += 1;
+            if previous.is_some_and(|previous| previous.index() >= entity.index()) {
+                differences.push(format!("{}: query yielded {} after {:?}", self.name(), entity, previous));
+            }
+            previous = Some(entity);
+            if self.values.get(&entity) != Some(value) {
+                differences.push(format!("{}: query yielded {} with {:?}", self.name(), entity, value));
+            }
+        }
+        if count != self.values.len() {
+            differences.push(format!("{}: query yielded {} entities, expected {}", self.name(), count, self.values.len()));
+        }
+    }
+}
+
+/// What a [`World`] should contain, maintained with plain hash maps.
+#[derive(Default)]
+pub struct WorldModel {
+    // Live entities in spawn order, operations pick their target in it.
+    entities: Vec<Entity>,
+    storages: HashMap<TypeId, Box<dyn ModelStorage>>,
+}
+
+impl WorldModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The live entities, in the order they were spawned.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities
+    }
+
+    pub fn get<T: TestComponent>(&self, entity: Entity) -> Option<&T> {
+        self.storage::<T>()?.values.get(&entity)
+    }
+
+    fn storage<T: TestComponent>(&self) -> Option<&TypedModel<T>> {
+        let storage = self.storages.get(&TypeId::of::<T>())?;
+        Some(storage.as_any().downcast_ref().expect("Model type mismatch"))
+    }
+
+    fn storage_mut<T: TestComponent>(&mut self) -> &mut TypedModel<T> {
+        let storage = self.storages.entry(TypeId::of::<T>()).or_insert_with(|| {
+            Box::new(TypedModel::<T> {
+                values: HashMap::new(),
+            })
+        });
+        storage.as_any_mut().downcast_mut().expect("Model type mismatch")
+    }
+
+    // The entity an operation targets, `None` when nothing is alive.
+    fn target(&self, target: usize) -> Option<Entity> {
+        (!self.entities.is_empty()).then(|| self.entities[target % self.entities.len()])
+    }
+}
+
+trait AnyComponent: fmt::Debug {
+    fn insert(self: Box<Self>, world: &mut World, model: &mut WorldModel, entity: Entity) -> Result<(), String>;
+}
+
+impl<T: TestComponent> AnyComponent for T {
+    fn insert(self: Box<Self>, world: &mut World, model: &mut WorldModel, entity: Entity) -> Result<(), String> {
+        let value = *self;
+        let previous = world.add_component(entity, value.clone());
+        let expected = model.storage_mut::<T>().values.insert(entity, value);
+        if previous != expected {
+            return Err(format!("add_component returned {:?}, expected {:?}", previous, expected));
+        }
+        Ok(())
+    }
+}
+
+/// A value of any [`TestComponent`], for [`Op::Insert`].
+pub struct Component(Box<dyn AnyComponent>);
+
+impl Component {
+    pub fn new<T: TestComponent>(value: T) -> Self {
+        Self(Box::new(value))
+    }
+}
+
+impl fmt::Debug for Component {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+type RemoveFn = fn(&mut World, &mut WorldModel, Entity) -> Result<(), String>;
+
+fn remove<T: TestComponent>(world: &mut World, model: &mut WorldModel, entity: Entity) -> Result<(), String> {
+    let removed = world.remove_component::<T>(entity);
+    let expected = model.storage_mut::<T>().values.remove(&entity);
+    if removed != expected {
+        return Err(format!("remove_component returned {:?}, expected {:?}", removed, expected));
+    }
+    Ok(())
+}
+
+/// A component type, for [`Op::Remove`].
+#[derive(Clone, Copy)]
+pub struct ComponentType {
+    name: &'static str,
+    remove: RemoveFn,
+}
+
+impl ComponentType {
+    pub fn of<T: TestComponent>() -> Self {
+        Self {
+            name: type_name::<T>(),
+            remove: remove::<T>,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl fmt::Debug for ComponentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// One step of a sequence. Targets are positions in [`WorldModel::entities`], taken modulo the
+/// number of live entities, and steps targeting an entity do nothing when none is alive.
+#[derive(Debug)]
+pub enum Op {
+    Spawn,
+    Despawn(usize),
+    Insert(usize, Component),
+    Remove(usize, ComponentType),
+    /// Compares a query over every component type the model knows with the model.
+    QueryCheck,
+}
+
+impl Op {
+    pub fn insert<T: TestComponent>(target: usize, value: T) -> Self {
+        Op::Insert(target, Component::new(value))
+    }
+
+    pub fn remove<T: TestComponent>(target: usize) -> Self {
+        Op::Remove(target, ComponentType::of::<T>())
+    }
+
+    fn apply(self, world: &mut World, model: &mut WorldModel) -> Result<(), String> {
+        match self {
+            Op::Spawn => {
+                let entity = *world.spawn_entity();
+                if model.entities.contains(&entity) {
+                    return Err(format!("spawned {} which is already alive", entity));
+                }
+                model.entities.push(entity);
+            }
+            Op::Despawn(target) => {
+                let Some(entity) = model.target(target) else {
+                    return Ok(());
+                };
+                if !world.despawn_entity(entity) {
+                    return Err(format!("despawn_entity({}) returned false", entity));
+                }
+                model.entities.retain(|e| *e != entity);
+                model.storages.values_mut().for_each(|storage| storage.despawn(entity));
+                if world.despawn_entity(entity) {
+                    return Err(format!("despawned {} twice", entity));
+                }
+            }
+            Op::Insert(target, component) => {
+                if let Some(entity) = model.target(target) {
+                    component.0.insert(world, model, entity)?;
+                }
+            }
+            Op::Remove(target, ty) => {
+                if let Some(entity) = model.target(target) {
+                    (ty.remove)(world, model, entity)?;
+                }
+            }
+            Op::QueryCheck => {
+                let mut differences = Vec::new();
+                for storage in model.storages.values() {
+                    storage.query_check(world, &mut differences);
+                }
+                if !differences.is_empty() {
+                    return Err(differences.join("\n"));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Applies the operations to both the world and the model.
+///
+/// # Panics
+///
+/// Panics at the first operation whose result differs between them.
+pub fn apply_ops(world: &mut World, model: &mut WorldModel, ops: impl IntoIterator<Item = Op>) {
+    for (step, op) in ops.into_iter().enumerate() {
+        let description = format!("{:?}", op);
+        if let Err(error) = op.apply(world, model) {
+            panic!("step {} ({}): {}", step, description, error);
+        }
+    }
+}
+
+/// Checks that the world contains exactly what the model does and that its bookkeeping is
+/// consistent, see [`World::validate`].
+///
+/// # Panics
+///
+/// Panics with every difference found.
+pub fn assert_equivalent(world: &World, model: &WorldModel) {
+    let mut differences = Vec::new();
+    for entity in &model.entities {
+        if !world.is_alive(*entity) {
+            differences.push(format!("{} is not alive", entity));
+        }
+    }
+    if world.enities().len() != model.entities.len() {
+        differences.push(format!("{} entities alive, expected {}", world.enities().len(), model.entities.len()));
+    }
+    for storage in model.storages.values() {
+        storage.compare(world, &mut differences);
+    }
+    if let Err(errors) = world.validate() {
+        differences.extend(errors.iter().map(|error| error.to_string()));
+    }
+    assert!(differences.is_empty(), "the world differs from the model:\n{}", differences.join("\n"));
+}
+
+/// A xorshift generator, small and reproducible from its seed.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Xorshift never leaves 0, the seed is scrambled so that nearby seeds diverge at once.
+        Self(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `0..n`, `n` must not be 0.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+struct GeneratedType {
+    ty: ComponentType,
+    value: Box<dyn FnMut(&mut Rng) -> Component>,
+}
+
+/// An endless iterator of random operations over the component types it was given.
+pub struct OpGenerator {
+    rng: Rng,
+    types: Vec<GeneratedType>,
+}
+
+impl OpGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            types: Vec::new(),
+        }
+    }
+
+    /// Lets the sequence insert values of `T` made by `value` and remove them.
+    pub fn with_component<T: TestComponent>(mut self, mut value: impl FnMut(&mut Rng) -> T + 'static) -> Self {
+        self.types.push(GeneratedType {
+            ty: ComponentType::of::<T>(),
+            value: Box::new(move |rng| Component::new(value(rng))),
+        });
+        self
+    }
+}
+
+impl Iterator for OpGenerator {
+    type Item = Op;
+
+    fn next(&mut self) -> Option<Op> {
+        let target = self.rng.next_u64() as usize;
+        let roll = self.rng.below(20);
+        if self.types.is_empty() {
+            return Some(if roll < 12 { Op::Spawn } else { Op::Despawn(target) });
+        }
+        let generated = self.rng.below(self.types.len());
+        let generated = &mut self.types[generated];
+        Some(match roll {
+            0..=3 => Op::Spawn,
+            4..=5 => Op::Despawn(target),
+            6..=12 => Op::Insert(target, (generated.value)(&mut self.rng)),
+            13..=17 => Op::Remove(target, generated.ty),
+            _ => Op::QueryCheck,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicIsize, Ordering};
+    use crate::component::Component;
+
+    use super::*;
+
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Position(i32, i32);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Frozen;
+
+    static TRACKED: AtomicIsize = AtomicIsize::new(0);
+
+    // Counts its live instances to catch values dropped twice or never.
+    #[derive(Debug, PartialEq, Component)]
+    struct Tracked(u64);
+
+    impl Tracked {
+        fn new(value: u64) -> Self {
+            TRACKED.fetch_add(1, Ordering::Relaxed);
+            Self(value)
+        }
+    }
+
+    impl Clone for Tracked {
+        fn clone(&self) -> Self {
+            Self::new(self.0)
+        }
+    }
+
+    impl Drop for Tracked {
+        fn drop(&mut self) {
+            TRACKED.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    fn generator(seed: u64) -> OpGenerator {
+        OpGenerator::new(seed)
+            .with_component(|rng| Position(rng.below(100) as i32, -(rng.below(100) as i32)))
+            .with_component(|_| Frozen)
+            .with_component(|rng| Tracked::new(rng.next_u64()))
+    }
+
+    #[test]
+    fn random_sequences_match_the_model() {
+        for seed in 0..200 {
+            let mut world = World::new();
+            let mut model = WorldModel::new();
+            apply_ops(&mut world, &mut model, generator(seed).take(300));
+            assert_equivalent(&world, &model);
+            // Despawning everything must drop every value exactly once.
+            let despawns: Vec<Op> = (0..model.entities().len()).map(Op::Despawn).collect();
+            apply_ops(&mut world, &mut model, despawns.into_iter().chain([Op::QueryCheck]));
+            assert_equivalent(&world, &model);
+        }
+        assert_eq!(TRACKED.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    fn handwritten_sequence() {
+        let mut world = World::new();
+        let mut model = WorldModel::new();
+        let ops = vec![
+            Op::Spawn,
+            Op::Spawn,
+            Op::insert(0, Position(1, 2)),
+            Op::insert(1, Position(3, 4)),
+            Op::insert(0, Position(5, 6)),
+            Op::insert(1, Frozen),
+            Op::Despawn(0),
+            Op::Spawn,
+            Op::remove::<Frozen>(0),
+            Op::remove::<Position>(7),
+            Op::QueryCheck,
+        ];
+        apply_ops(&mut world, &mut model, ops);
+        assert_equivalent(&world, &model);
+        let reused = model.entities()[1];
+        assert_eq!(reused.index(), 0);
+        assert_eq!(model.get::<Position>(reused), None);
+        assert_eq!(model.get::<Position>(model.entities()[0]), Some(&Position(3, 4)));
+        assert_eq!(format!("{:?}", Op::insert(3, Frozen)), "Insert(3, Frozen)");
+    }
+
+    #[test]
+    #[should_panic(expected = "Position: expected Position(1, 1), found Some(Position(2, 2))")]
+    fn differences_are_reported() {
+        let mut world = World::new();
+        let mut model = WorldModel::new();
+        apply_ops(&mut world, &mut model, [Op::Spawn, Op::insert(0, Position(1, 1))]);
+        // Changes the world behind the back of the model.
+        *world.get_component_mut::<Position>(model.entities()[0]).unwrap() = Position(2, 2);
+        assert_equivalent(&world, &model);
+    }
+}
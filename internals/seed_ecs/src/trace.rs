@@ -0,0 +1,165 @@
+//! `tracing` spans around the runs of the schedules, of their systems and of the commands the
+//! systems deferred, for the frame profilers like Tracy. Only built with the `trace` feature,
+//! without it no span is ever constructed.
+//!
+//! Every run of a [`Schedule`](crate::system::Schedule) is a [`SCHEDULE`] span, holding one
+//! [`SYSTEM`] span per system that ran, which holds the [`APPLY`] span of its commands.
+
+use tracing::span::EnteredSpan;
+
+/// The span of a run of a schedule, with the number of `systems` it holds and the `entities`
+/// alive when it starts. Stepping the schedule enters it too.
+pub const SCHEDULE: &str = "schedule";
+
+/// The span of a system, named by its `name` field. The systems skipped by their conditions
+/// don't have one.
+pub const SYSTEM: &str = "system";
+
+/// The span of the `commands` of a system being applied, inside the span of the system.
+pub const APPLY: &str = "apply";
+
+pub(crate) fn schedule(systems: usize, entities: usize) -> EnteredSpan {
+    tracing::info_span!(SCHEDULE, systems, entities).entered()
+}
+
+pub(crate) fn system(name: &'static str) -> EnteredSpan {
+    tracing::info_span!(SYSTEM, name).entered()
+}
+
+pub(crate) fn apply(commands: usize) -> EnteredSpan {
+    tracing::info_span!(APPLY, commands).entered()
+}
+
+pub(crate) fn observer_depth_exceeded(entity: crate::entity::Entity) {
+    tracing::warn!(
+        ?entity,
+        "Observer trigger dropped past the depth of {}, observers may be triggering each other forever",
+        crate::observer::MAX_OBSERVER_DEPTH
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt;
+    use std::sync::{Arc, Mutex};
+
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    use super::*;
+    use crate::commands::Commands;
+    use crate::system::{IntoSystem, Res, Schedule, System};
+    use crate::World;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct Span {
+        name: &'static str,
+        parent: Option<usize>,
+        fields: Vec<(&'static str, String)>,
+    }
+
+    impl Visit for Span {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            self.fields.push((field.name(), format!("{:?}", value)));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    // Records the spans with the span entered when they were created as parent.
+    #[derive(Clone, Default)]
+    struct Recorder {
+        spans: Arc<Mutex<Vec<Span>>>,
+        entered: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, attributes: &Attributes<'_>) -> Id {
+            let mut span = Span {
+                name: attributes.metadata().name(),
+                parent: self.entered.lock().unwrap().last().copied(),
+                fields: Vec::new(),
+            };
+            attributes.record(&mut span);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span);
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, _event: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.entered.lock().unwrap().push(span.into_u64() as usize - 1);
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+
+    struct Rate(usize);
+
+    fn fire(mut commands: Commands, rate: Res<Rate>) {
+        for _ in 0..rate.0 {
+            commands.add(|world: &mut World| {
+                world.spawn_entity();
+            });
+        }
+    }
+
+    fn idle(_rate: Res<Rate>) {}
+
+    #[test]
+    fn schedules_record_nested_spans() {
+        let mut world = World::new();
+        world.insert_resource(Rate(3));
+        let mut schedule = Schedule::new();
+        schedule.add_system(fire).add_system(idle);
+        let recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || {
+            schedule.run(&mut world);
+            schedule.run(&mut world);
+        });
+
+        let spans = recorder.spans.lock().unwrap();
+        let fire_name = fire.into_system().name();
+        let idle_name = idle.into_system().name();
+        let frame = |start: usize, entities: usize| {
+            vec![
+                Span {
+                    name: SCHEDULE,
+                    parent: None,
+                    fields: vec![("systems", "2".into()), ("entities", entities.to_string())],
+                },
+                Span {
+                    name: SYSTEM,
+                    parent: Some(start),
+                    fields: vec![("name", fire_name.into())],
+                },
+                Span {
+                    name: APPLY,
+                    parent: Some(start + 1),
+                    fields: vec![("commands", "3".into())],
+                },
+                Span {
+                    name: SYSTEM,
+                    parent: Some(start),
+                    fields: vec![("name", idle_name.into())],
+                },
+            ]
+        };
+        let expected: Vec<Span> = frame(0, 0).into_iter().chain(frame(4, 3)).collect();
+        assert_eq!(*spans, expected);
+    }
+}
@@ -0,0 +1,38 @@
+//! The helper generating the tuple implementations of the traits taking several values at once,
+//! like [`WorldQuery`](crate::query::WorldQuery) or [`SystemParam`](crate::system::SystemParam).
+
+/// Calls the macro `$m` with every list of type parameters from `()` to `(A, .., P)`, so that all
+/// the tuple implementations go up to the same arity.
+macro_rules! all_tuples {
+    ($m: ident) => {
+        $crate::tuples::all_tuples!(@ $m []; A B C D E F G H I J K L M N O P);
+    };
+    (@ $m: ident [$($done: ident)*]; ) => {
+        $m!($($done),*);
+    };
+    (@ $m: ident [$($done: ident)*]; $next: ident $($rest: ident)*) => {
+        $m!($($done),*);
+        $crate::tuples::all_tuples!(@ $m [$($done)* $next]; $($rest)*);
+    };
+}
+
+/// Same as [`all_tuples`] with the index of each type parameter in the tuple, `$m!(A 0, B 1)`,
+/// for the implementations that take the members of two tuples at once.
+macro_rules! all_tuples_indexed {
+    ($m: ident) => {
+        $crate::tuples::all_tuples_indexed!(
+            @ $m [];
+            A 0 B 1 C 2 D 3 E 4 F 5 G 6 H 7 I 8 J 9 K 10 L 11 M 12 N 13 O 14 P 15
+        );
+    };
+    (@ $m: ident [$($done: ident $idx: tt)*]; ) => {
+        $m!($($done $idx),*);
+    };
+    (@ $m: ident [$($done: ident $idx: tt)*]; $next: ident $next_idx: tt $($rest: tt)*) => {
+        $m!($($done $idx),*);
+        $crate::tuples::all_tuples_indexed!(@ $m [$($done $idx)* $next $next_idx]; $($rest)*);
+    };
+}
+
+pub(crate) use all_tuples;
+pub(crate) use all_tuples_indexed;
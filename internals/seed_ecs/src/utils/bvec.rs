@@ -1,26 +1,84 @@
-use std::{ptr::NonNull, alloc::Layout};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem::{self, MaybeUninit};
+use core::ops::Range;
+use core::{ptr, slice};
 
 use super::MVec;
 
 // This is where all the magic happens. Each layer condense the information from the previous one.
 // Each bit of the last layer represent the storage of something inside the vector. If the bit is 0
 // then nothing is stored at its index.
-// Each bit of the layers above represent 4 bits in the layer below. If one of the bits in the
-// layer below is at one the it also is at 1 else it is at 0.
+// Each bit of the layers above represent one word (32 bits) in the layer below. If one of the bits
+// in that word is at 1 then it also is at 1 else it is at 0.
+// Layers are only allocated up to the highest word that was ever touched.
 pub struct BMask {
     root: u32,
     l1: MVec<u32, 32>,
     l2: MVec<u32, {32*32}>, // 32^2
     l3: MVec<u32, {32*32*32}>, // 32^3
+    // Bumped by every call setting or clearing bits, see `BMask::changes`.
+    changes: u64,
+    // Every leaf word below it is full, `first_empty_spot` starts looking there.
+    full_words: usize,
 }
 
 #[inline]
 pub fn position(idx: usize, row_nb: usize) -> (usize, u32) {
-    let index = idx >> 5*row_nb;
-    let bit_nb = (idx >> 5*(row_nb-1)) % 32;
+    let index = idx >> (5*row_nb);
+    let bit_nb = (idx >> (5*(row_nb-1))) % 32;
     (index, bit_nb as u32)
 }
 
+// Returns the word at `idx` in `layer`, growing the layer with empty words if needed.
+#[inline]
+fn word_mut<const N: usize>(layer: &mut MVec<u32, N>, idx: usize) -> &mut u32 {
+    while layer.len() <= idx {
+        layer.push(0);
+    }
+    &mut (**layer)[idx]
+}
+
+// Clears the ascending `bits` of `words`, returns the indices of the words it emptied.
+fn clear_bits(words: &mut [u32], bits: &[usize]) -> Vec<usize> {
+    let mut emptied = Vec::new();
+    for bit in bits {
+        let Some(word) = words.get_mut(bit >> 5) else {
+            break;
+        };
+        let before = *word;
+        *word &= !(1 << (bit & 31));
+        if before != 0 && *word == 0 {
+            emptied.push(bit >> 5);
+        }
+    }
+    emptied
+}
+
+// The bits of `range` that fall in the word at `word_idx`.
+#[inline]
+fn range_bits(word_idx: usize, range: &Range<usize>) -> u32 {
+    let start = range.start.max(word_idx << 5) - (word_idx << 5);
+    let end = range.end.min((word_idx + 1) << 5) - (word_idx << 5);
+    (u32::MAX >> (32 - end)) & (u32::MAX << start)
+}
+
+// Sets the bits of the non empty `range` in `layer`, a word at a time, growing the layer if needed.
+fn fill_bits<const N: usize>(layer: &mut MVec<u32, N>, range: Range<usize>) {
+    for word_idx in range.start >> 5..=(range.end - 1) >> 5 {
+        *word_mut(layer, word_idx) |= range_bits(word_idx, &range);
+    }
+}
+
+fn shrink_layer<const N: usize>(layer: &mut MVec<u32, N>) {
+    while layer.last() == Some(&0) {
+        layer.pop();
+    }
+    layer.shrink_to_fit();
+}
+
 impl BMask {
 
     pub fn new() -> Self {
@@ -29,101 +87,417 @@ impl BMask {
             l1: MVec::new(),
             l2: MVec::new(),
             l3: MVec::new(),
+            changes: 0,
+            full_words: 0,
         }
     }
 
-    fn add(&mut self, idx: usize) {
+    pub fn add(&mut self, idx: usize) {
+        self.changes += 1;
         let (l3_idx, l3_offset) = position(idx, 1);
         let (l2_idx, l2_offset) = position(idx , 2);
         let (l1_idx, l1_offset) = position(idx , 3);
         let (_, root_offset) = position(idx , 4);
         self.root |= 1 << root_offset;
-        (*self.l1)[l1_idx] |= 1 << l1_offset;
-        (*self.l2)[l2_idx] |= 1 << l2_offset;
-        (*self.l3)[l3_idx] |= 1 << l3_offset;
-    }
-
-    fn first_empty_spot(&self) -> usize {
-        let found = false;
-        let mut win= self.root;
-        let mut win_idx = 0;
-        let mut tot_idx = 0;
-        for i in 1..=4 {
-            while self.root & 1 << win_idx != 1 << win_idx {
-                win_idx += 1;
-            }
-            tot_idx = (tot_idx*32 + win_idx)*32;
-            win = match i {
-                1 => (*self.l1)[win_idx as usize * 32],
-                2 => (*self.l2)[win_idx as usize * 32],
-                3 => (*self.l3)[win_idx as usize * 32],
-                _ => 0,
+        *word_mut(&mut self.l1, l1_idx) |= 1 << l1_offset;
+        *word_mut(&mut self.l2, l2_idx) |= 1 << l2_offset;
+        *word_mut(&mut self.l3, l3_idx) |= 1 << l3_offset;
+        while (*self.l3).get(self.full_words) == Some(&u32::MAX) {
+            self.full_words += 1;
+        }
+    }
+
+    /// Sets the bits of `range`, whole leaf words at a time, each word of the upper layers is
+    /// updated once.
+    pub fn add_range(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        assert!(range.end <= CAPACITY, "Range end exceeds the size of the BMask: {} > {}", range.end, CAPACITY);
+        self.changes += 1;
+        let words = (range.start >> 5)..((range.end - 1) >> 5) + 1;
+        let l2_words = (words.start >> 5)..((words.end - 1) >> 5) + 1;
+        let l1_words = (l2_words.start >> 5)..((l2_words.end - 1) >> 5) + 1;
+        fill_bits(&mut self.l3, range);
+        fill_bits(&mut self.l2, words);
+        fill_bits(&mut self.l1, l2_words);
+        self.root |= range_bits(0, &l1_words);
+        while (*self.l3).get(self.full_words) == Some(&u32::MAX) {
+            self.full_words += 1;
+        }
+    }
+
+    /// Clears the bits of `range`, whole leaf words at a time, each word of the upper layers is
+    /// updated once.
+    pub fn remove_range(&mut self, range: Range<usize>) {
+        let range = range.start..range.end.min(self.l3.len() << 5);
+        if range.is_empty() {
+            return;
+        }
+        self.changes += 1;
+        self.full_words = self.full_words.min(range.start >> 5);
+        let mut emptied = Vec::new();
+        for word_idx in range.start >> 5..=(range.end - 1) >> 5 {
+            let word = &mut (*self.l3)[word_idx];
+            let before = *word;
+            *word &= !range_bits(word_idx, &range);
+            if before != 0 && *word == 0 {
+                emptied.push(word_idx);
             }
         }
-        return tot_idx;
+        let emptied = clear_bits(&mut self.l2, &emptied);
+        let emptied = clear_bits(&mut self.l1, &emptied);
+        clear_bits(slice::from_mut(&mut self.root), &emptied);
+    }
+
+    /// Number of bits set in `range`, counted a word at a time over the non empty words.
+    pub fn count_in_range(&self, range: Range<usize>) -> usize {
+        if range.is_empty() {
+            return 0;
+        }
+        let last = (range.end - 1) >> 5;
+        let mut count = 0;
+        let mut next = self.next_word(range.start >> 5);
+        while let Some(word_idx) = next.filter(|word_idx| *word_idx <= last) {
+            count += (self.word(word_idx) & range_bits(word_idx, &range)).count_ones() as usize;
+            next = self.next_word(word_idx + 1);
+        }
+        count
+    }
+
+    /// Returns the first index that has no bit set.
+    pub fn first_empty_spot(&self) -> usize {
+        let words = &(*self.l3)[self.full_words..];
+        match words.iter().position(|word| *word != u32::MAX) {
+            Some(word_idx) => ((self.full_words + word_idx) << 5) | words[word_idx].trailing_ones() as usize,
+            None => self.l3.len() << 5,
+        }
     }
 
-    fn is_present(&self, idx: usize) -> bool {
+    pub fn is_present(&self, idx: usize) -> bool {
         let (l3_idx, l3_offset) = position(idx, 1);
-        (*self.l3)[l3_idx] & 1<<l3_offset == 1<<l3_offset
-     }
+        self.word(l3_idx) & 1<<l3_offset == 1<<l3_offset
+    }
 
-    fn remove(&mut self, idx: usize) {
+    pub fn remove(&mut self, idx: usize) {
+        if !self.is_present(idx) {return;}
+        self.changes += 1;
+        // Every bit of an upper layer stands for exactly one word of the layer below, so a
+        // parent bit is cleared as soon as the single word it covers becomes empty.
         let (l3_idx, l3_offset) = position(idx, 1);
-        if (*self.l3)[l3_idx] ^ 1<<l3_offset != 0 {return;}
-        (*self.l3)[l3_idx] ^= 1<<l3_offset;
+        (*self.l3)[l3_idx] &= !(1<<l3_offset);
+        self.full_words = self.full_words.min(l3_idx);
         if (*self.l3)[l3_idx] != 0 {return;}
         let (l2_idx, l2_offset) = position(idx, 2);
-        (*self.l2)[l2_idx] ^= 1<<l2_offset;
+        (*self.l2)[l2_idx] &= !(1<<l2_offset);
         if (*self.l2)[l2_idx] != 0 {return;}
         let (l1_idx, l1_offset) = position(idx, 3);
-        (*self.l1)[l1_idx] ^= 1<<l1_offset;
+        (*self.l1)[l1_idx] &= !(1<<l1_offset);
         if (*self.l1)[l1_idx] != 0 {return;}
         let (_, root_offset) = position(idx, 4);
-        self.root ^= 1<<root_offset;
+        self.root &= !(1<<root_offset);
+    }
+
+    /// Clears the bits of the ascending `indices`, each word of the upper layers is updated once
+    /// however many of its bits are cleared.
+    pub fn remove_sorted(&mut self, indices: &[usize]) {
+        if let Some(first) = indices.first() {
+            self.changes += 1;
+            self.full_words = self.full_words.min(first >> 5);
+        }
+        let emptied = clear_bits(&mut self.l3, indices);
+        let emptied = clear_bits(&mut self.l2, &emptied);
+        let emptied = clear_bits(&mut self.l1, &emptied);
+        clear_bits(slice::from_mut(&mut self.root), &emptied);
+    }
+
+    /// Returns the first index at or after `idx` that has its bit set.
+    pub fn next(&self, idx: usize) -> Option<usize> {
+        self.next_in_layer(3, idx)
+    }
+
+    // Layer 0 is the root and layer 3 is the leaf layer.
+    fn layer(&self, layer: usize) -> &[u32] {
+        match layer {
+            0 => core::slice::from_ref(&self.root),
+            1 => &self.l1,
+            2 => &self.l2,
+            _ => &self.l3,
+        }
+    }
+
+    fn next_in_layer(&self, layer: usize, from: usize) -> Option<usize> {
+        let words = self.layer(layer);
+        let word_idx = from >> 5;
+        if word_idx >= words.len() {
+            return None;
+        }
+        let bits = words[word_idx] & (u32::MAX << (from & 31));
+        if bits != 0 {
+            return Some((word_idx << 5) | bits.trailing_zeros() as usize);
+        }
+        if layer == 0 {
+            return None;
+        }
+        // The layer above tells us which is the next non empty word.
+        let next_word = self.next_in_layer(layer - 1, word_idx + 1)?;
+        Some((next_word << 5) | words[next_word].trailing_zeros() as usize)
     }
 
-    fn next(&self, idx: usize) -> usize {
-        let found = false;
-        let mut win= self.root;
-        let mut win_idx = 0;
-        let mut tot_idx = 0;
-        for i in 1..=4 {
-            while self.root & 1 << win_idx == 1 << win_idx {
-                win_idx += 1;
+    /// Returns the index of the first non empty leaf word at or after `word_idx`.
+    pub fn next_word(&self, word_idx: usize) -> Option<usize> {
+        self.next_in_layer(2, word_idx)
+    }
+
+    /// Returns the leaf word at `word_idx`, every bit of it represent one index.
+    #[inline]
+    pub fn word(&self, word_idx: usize) -> u32 {
+        (*self.l3).get(word_idx).copied().unwrap_or(0)
+    }
+
+    /// How many times bits were set or cleared, never decreasing: an unchanged count means the
+    /// same bits are set. Setting a bit already set may count.
+    pub fn changes(&self) -> u64 {
+        self.changes
+    }
+
+    /// Number of leaf words allocated.
+    pub fn word_count(&self) -> usize {
+        self.l3.len()
+    }
+
+    /// Number of bits set.
+    pub fn len(&self) -> usize {
+        self.l3.iter().map(|word| word.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root == 0
+    }
+
+    pub fn clear(&mut self) {
+        if !self.is_empty() {
+            self.changes += 1;
+        }
+        self.root = 0;
+        self.full_words = 0;
+        self.l1.iter_mut().for_each(|word| *word = 0);
+        self.l2.iter_mut().for_each(|word| *word = 0);
+        self.l3.iter_mut().for_each(|word| *word = 0);
+    }
+
+    /// Allocates the words of every layer needed by the indices below `len`, so that adding them
+    /// doesn't allocate.
+    pub fn reserve(&mut self, len: usize) {
+        let Some(last) = len.min(CAPACITY).checked_sub(1) else {
+            return;
+        };
+        word_mut(&mut self.l1, position(last, 3).0);
+        word_mut(&mut self.l2, position(last, 2).0);
+        word_mut(&mut self.l3, position(last, 1).0);
+    }
+
+    /// Checks that every bit of the upper layers is set exactly when the word it represents in the
+    /// layer below is not empty.
+    pub fn check(&self) -> Result<(), String> {
+        for layer in 0..3 {
+            let words = self.layer(layer);
+            let below = self.layer(layer + 1);
+            for (word_idx, word) in words.iter().enumerate() {
+                for bit in 0..32 {
+                    let below_idx = (word_idx << 5) | bit;
+                    let set = word & (1 << bit) != 0;
+                    let filled = below.get(below_idx).is_some_and(|word| *word != 0);
+                    if set != filled {
+                        return Err(format!(
+                            "bit {} of word {} of layer {} is {} but the word below is {}",
+                            bit,
+                            word_idx,
+                            layer,
+                            if set { "set" } else { "unset" },
+                            if filled { "not empty" } else { "empty" },
+                        ));
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Drops the trailing empty words of every layer and releases the unused capacity.
+    pub fn shrink_to_fit(&mut self) {
+        shrink_layer(&mut self.l1);
+        shrink_layer(&mut self.l2);
+        shrink_layer(&mut self.l3);
+    }
+
+    /// Number of bytes allocated by the layers of the mask.
+    pub fn allocated_bytes(&self) -> usize {
+        (self.l1.capacity() + self.l2.capacity() + self.l3.capacity()) * mem::size_of::<u32>()
+    }
+
+    pub fn iter(&self) -> BMaskIter<'_> {
+        BMaskIter {
+            mask: self,
+            cursor: 0,
+        }
+    }
+
+    /// Encodes the non empty leaf words in ascending order, each as its index in LEB128 followed
+    /// by the word in little endian. The upper layers are left out, [`BMask::from_bytes`] knows
+    /// them from the leaves.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        let mut next = self.next_word(0);
+        while let Some(word_idx) = next {
+            let mut index = word_idx;
+            while index >= 0x80 {
+                bytes.push(index as u8 | 0x80);
+                index >>= 7;
+            }
+            bytes.push(index as u8);
+            bytes.extend_from_slice(&self.word(word_idx).to_le_bytes());
+            next = self.next_word(word_idx + 1);
+        }
+        bytes
+    }
+
+    /// Decodes what [`BMask::to_bytes`] encoded. Empty words are accepted and add nothing.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError> {
+        let mut mask = Self::new();
+        let mut offset = 0;
+        let mut previous = None;
+        while offset < bytes.len() {
+            let mut word_idx = 0usize;
+            let mut shift = 0;
+            loop {
+                let byte = *bytes.get(offset).ok_or(DecodeError::Truncated { offset })?;
+                offset += 1;
+                word_idx |= ((byte & 0x7f) as usize).checked_shl(shift).unwrap_or(usize::MAX);
+                if byte & 0x80 == 0 {
+                    break;
+                }
+                // Longer encodings can only be out of range, stop before the shift overflows.
+                shift = (shift + 7).min(usize::BITS - 1);
+            }
+            if word_idx >= CAPACITY / 32 {
+                return Err(DecodeError::OutOfRange { word: word_idx });
             }
-            tot_idx = (tot_idx*32 + win_idx)*32;
-            win = match i {
-                1 => (*self.l1)[win_idx as usize * 32],
-                2 => (*self.l2)[win_idx as usize * 32],
-                3 => (*self.l3)[win_idx as usize * 32],
-                _ => 0,
+            if previous.is_some_and(|previous| word_idx <= previous) {
+                return Err(DecodeError::Unordered { word: word_idx });
             }
+            previous = Some(word_idx);
+            let word = bytes.get(offset..offset + 4).ok_or(DecodeError::Truncated { offset })?;
+            offset += 4;
+            mask.set_word(word_idx, u32::from_le_bytes(word.try_into().unwrap()));
+        }
+        Ok(mask)
+    }
+
+    // Overwrites the empty leaf word at `word_idx` and sets the bits standing for it above.
+    fn set_word(&mut self, word_idx: usize, word: u32) {
+        if word == 0 {
+            return;
         }
-        return tot_idx;
+        self.changes += 1;
+        *word_mut(&mut self.l3, word_idx) = word;
+        *word_mut(&mut self.l2, word_idx >> 5) |= 1 << (word_idx & 31);
+        *word_mut(&mut self.l1, word_idx >> 10) |= 1 << ((word_idx >> 5) & 31);
+        self.root |= 1 << ((word_idx >> 10) & 31);
+    }
+}
+
+impl Default for BMask {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// Iterates over the indices set in a [`BMask`] in ascending order.
+pub struct BMaskIter<'a> {
+    mask: &'a BMask,
+    cursor: usize,
+}
+
+impl Iterator for BMaskIter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let idx = self.mask.next(self.cursor)?;
+        self.cursor = idx + 1;
+        Some(idx)
+    }
+}
+
+/// Bytes that [`BMask::from_bytes`] can't decode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ends inside the word index or the word starting at `offset`.
+    Truncated { offset: usize },
+    /// A word index is past the capacity of the mask.
+    OutOfRange { word: usize },
+    /// A word index doesn't come after the one before it.
+    Unordered { word: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated { offset } => write!(f, "Mask bytes truncated at byte {}", offset),
+            Self::OutOfRange { word } => write!(f, "Mask word {} is out of range", word),
+            Self::Unordered { word } => write!(f, "Mask word {} comes after a greater one", word),
+        }
+    }
+}
+
+impl core::error::Error for DecodeError {}
+
+/// Maximum number of elements a [`BVec`] can address, one bit of the leaf layer of the mask for
+/// each of them.
+pub const CAPACITY: usize = 32*32*32*32;
+
+/// Number of elements of a [`BVec`] page, the span of one word of the second layer of the mask.
+pub const PAGE_SIZE: usize = 32*32;
+
+type Page<T> = Box<[MaybeUninit<T>]>;
+
 // BitVector is a vector that allows fast iteration over sparse set of data.
+// Values live in pages of `PAGE_SIZE` slots allocated the first time one of their slots is used,
+// only the slots set in the mask are initialized.
 pub struct BVec<T> {
     mask: BMask,
-    buffer: MVec<T, {32*32*32}>,
+    pages: Vec<Option<Page<T>>>,
 }
 
 impl<T> BVec<T> {
+    /// The number of indices a vector addresses, [`CAPACITY`].
+    pub const MAX_LEN: usize = CAPACITY;
+
     pub fn new() -> Self {
-        let layout = Layout::array::<T>(16*32).unwrap();
         Self {
             mask: BMask::new(),
-            buffer: MVec::new(),
+            pages: Vec::new(),
         }
     }
 
+    // The slot of an index, the page must be allocated.
+    #[inline]
+    fn slot(&self, idx: usize) -> &MaybeUninit<T> {
+        let page = self.pages[idx / PAGE_SIZE].as_ref().expect("BVec page not allocated");
+        &page[idx % PAGE_SIZE]
+    }
+
+    #[inline]
+    fn slot_mut(&mut self, idx: usize) -> &mut MaybeUninit<T> {
+        let page = self.pages[idx / PAGE_SIZE].as_mut().expect("BVec page not allocated");
+        &mut page[idx % PAGE_SIZE]
+    }
+
     pub fn get(&self, idx: usize) -> Option<&T>{
         if !self.mask.is_present(idx) {
             None
         } else {
-            Some(self.buffer.get(idx))
+            Some(unsafe { self.slot(idx).assume_init_ref() })
         }
     }
 
@@ -131,24 +505,280 @@ impl<T> BVec<T> {
         if !self.mask.is_present(idx) {
             None
         } else {
-            Some(self.buffer.get_mut(idx))
+            Some(unsafe { self.slot_mut(idx).assume_init_mut() })
         }
     }
 
-    pub fn insert_first_empty(&mut self, elem: T) -> &T {
-        let idx = self.mask.first_empty_spot();
+    pub fn contains(&self, idx: usize) -> bool {
+        self.mask.is_present(idx)
+    }
+
+    /// Stores `elem` at `idx` and returns the element that was there before if any.
+    pub fn insert(&mut self, idx: usize, elem: T) -> Option<T> {
+        assert!(idx < CAPACITY, "Insert index exeeds the size of the BVec: {} < {}", idx, CAPACITY);
+        if self.mask.is_present(idx) {
+            return Some(mem::replace(unsafe { self.slot_mut(idx).assume_init_mut() }, elem));
+        }
+        let page = idx / PAGE_SIZE;
+        if self.pages.len() <= page {
+            self.pages.resize_with(page + 1, || None);
+        }
+        self.pages[page].get_or_insert_with(|| Box::new_uninit_slice(PAGE_SIZE));
+        self.slot_mut(idx).write(elem);
         self.mask.add(idx);
-        self.buffer.insert(idx, elem);
+        None
+    }
+
+    /// Stores each element at its index, dropping the elements it replaces. Pages are looked up
+    /// once per run of indices falling in the same page and the mask is set once per run of
+    /// consecutive new indices, see [`BMask::add_range`], so sorted indices are the fast path,
+    /// but any order is accepted.
+    pub fn insert_many(&mut self, pairs: impl IntoIterator<Item = (usize, T)>) {
+        let mut current: Option<(usize, *mut MaybeUninit<T>)> = None;
+        // The new indices written but not yet set in the mask.
+        let mut added = 0..0;
+        for (idx, elem) in pairs {
+            assert!(idx < CAPACITY, "Insert index exeeds the size of the BVec: {} < {}", idx, CAPACITY);
+            let page = idx / PAGE_SIZE;
+            let slots = match current {
+                Some((current_page, slots)) if current_page == page => slots,
+                _ => {
+                    if self.pages.len() <= page {
+                        self.pages.resize_with(page + 1, || None);
+                    }
+                    // Pages are boxed, the pointer stays valid while `self.pages` grows.
+                    let slots = self.pages[page].get_or_insert_with(|| Box::new_uninit_slice(PAGE_SIZE)).as_mut_ptr();
+                    current = Some((page, slots));
+                    slots
+                }
+            };
+            let slot = unsafe { &mut *slots.add(idx % PAGE_SIZE) };
+            if added.contains(&idx) || self.mask.is_present(idx) {
+                unsafe { slot.assume_init_drop() };
+            } else if added.end == idx && !added.is_empty() {
+                added.end += 1;
+            } else {
+                self.mask.add_range(mem::replace(&mut added, idx..idx + 1));
+            }
+            slot.write(elem);
+        }
+        self.mask.add_range(added);
+    }
+
+    /// Drops the elements at `indices`, given in any order and with repeats, and returns how many
+    /// there were. The mask is updated in one pass, see [`BMask::remove_sorted`].
+    pub fn remove_many(&mut self, indices: impl IntoIterator<Item = usize>) -> usize {
+        let mut indices: Vec<usize> = indices.into_iter().filter(|idx| self.mask.is_present(*idx)).collect();
+        if !indices.is_sorted_by(|a, b| a < b) {
+            indices.sort_unstable();
+            indices.dedup();
+        }
+        // The slots are marked as empty first, a panicking drop leaks the values left.
+        self.mask.remove_sorted(&indices);
+        if mem::needs_drop::<T>() {
+            for idx in &indices {
+                unsafe { self.slot_mut(*idx).assume_init_drop() }
+            }
+        }
+        indices.len()
+    }
+
+    /// Stores `elem` at the lowest free index and returns that index with the stored element, or
+    /// gives `elem` back if every index is used.
+    pub fn insert_first_empty(&mut self, elem: T) -> Result<(usize, &mut T), T> {
+        let idx = self.mask.first_empty_spot();
+        if idx >= CAPACITY {
+            return Err(elem);
+        }
+        assert!(!self.mask.is_present(idx), "The first empty index {} is used", idx);
+        self.insert(idx, elem);
         // It is safe to unwrap here as we just inserted the element at the index
-        self.get(idx).unwrap()
+        Ok((idx, self.get_mut(idx).unwrap()))
+    }
+
+    /// Index that the next call to `insert_first_empty` will use.
+    pub fn first_empty(&self) -> usize {
+        self.mask.first_empty_spot()
     }
 
-    fn next_item_index(&mut self, idx: usize) -> usize {
+    fn next_item_index(&self, idx: usize) -> Option<usize> {
         self.mask.next(idx)
     }
 
-    pub fn remove(&mut self, idx: usize) {
+    pub fn remove(&mut self, idx: usize) -> Option<T> {
+        if !self.mask.is_present(idx) {
+            return None;
+        }
         self.mask.remove(idx);
+        // The slot is now marked as empty so the value will never be read again.
+        unsafe { Some(self.slot(idx).assume_init_read()) }
+    }
+
+    /// Number of elements stored.
+    pub fn len(&self) -> usize {
+        self.mask.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.mask.is_empty()
+    }
+
+    /// Number of slots allocated in the pages.
+    pub fn capacity(&self) -> usize {
+        self.page_count() * PAGE_SIZE
+    }
+
+    /// Number of pages allocated.
+    pub fn page_count(&self) -> usize {
+        self.pages.iter().filter(|page| page.is_some()).count()
+    }
+
+    pub fn mask(&self) -> &BMask {
+        &self.mask
+    }
+
+    /// Appends the encoded mask of the indices holding an element, see [`BMask::to_bytes`].
+    pub fn write_occupancy(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&self.mask.to_bytes());
+    }
+
+    /// Decodes the indices holding an element written by [`BVec::write_occupancy`].
+    pub fn read_occupancy(bytes: &[u8]) -> Result<BMask, DecodeError> {
+        BMask::from_bytes(bytes)
+    }
+
+    /// Number of bytes allocated by the pages and the mask.
+    pub fn allocated_bytes(&self) -> usize {
+        self.capacity() * mem::size_of::<T>()
+            + self.pages.capacity() * mem::size_of::<Option<Page<T>>>()
+            + self.mask.allocated_bytes()
+    }
+
+    /// Allocates the pages and the mask words for the indices below `len`, so that inserting at
+    /// them doesn't allocate.
+    pub fn reserve(&mut self, len: usize) {
+        self.reserve_pages(len, false);
+    }
+
+    /// Same as [`BVec::reserve`], and writes the pages it allocates once so that the system maps
+    /// their memory right away instead of on the first inserts.
+    pub fn reserve_touched(&mut self, len: usize) {
+        self.reserve_pages(len, true);
+    }
+
+    fn reserve_pages(&mut self, len: usize, touch: bool) {
+        let len = len.min(CAPACITY);
+        let page_count = len.div_ceil(PAGE_SIZE);
+        if self.pages.len() < page_count {
+            self.pages.resize_with(page_count, || None);
+        }
+        for page in &mut self.pages[..page_count] {
+            page.get_or_insert_with(|| {
+                let mut page = Box::new_uninit_slice(PAGE_SIZE);
+                if touch {
+                    // The slots stay uninitialized to the vector, only their bytes are written.
+                    unsafe { ptr::write_bytes(page.as_mut_ptr(), 0, PAGE_SIZE) };
+                }
+                page
+            });
+        }
+        self.mask.reserve(len);
+    }
+
+    /// Allocates the mask words for the indices below `len`, not the pages.
+    pub fn reserve_mask(&mut self, len: usize) {
+        self.mask.reserve(len);
+    }
+
+    /// Frees the pages that hold no element and shrinks the mask, the indices of the elements
+    /// don't change.
+    pub fn compact(&mut self) {
+        for (page_idx, page) in self.pages.iter_mut().enumerate() {
+            let first = page_idx * PAGE_SIZE;
+            let empty = self.mask.next(first).is_none_or(|idx| idx >= first + PAGE_SIZE);
+            if empty {
+                *page = None;
+            }
+        }
+        while matches!(self.pages.last(), Some(None)) {
+            self.pages.pop();
+        }
+        self.pages.shrink_to_fit();
+        self.mask.shrink_to_fit();
+    }
+
+    // Checks that `range` is not empty and doesn't cross a page boundary.
+    #[inline]
+    fn debug_check_range(range: &Range<usize>) {
+        debug_assert!(!range.is_empty() && range.start / PAGE_SIZE == (range.end - 1) / PAGE_SIZE);
+    }
+
+    /// The elements at the indices of `range` as one slice.
+    ///
+    /// # Safety
+    ///
+    /// Every index of `range` must be present, and the range must not be empty nor cross a page
+    /// boundary.
+    pub unsafe fn slice(&self, range: Range<usize>) -> &[T] {
+        Self::debug_check_range(&range);
+        // Offset from the whole page, a pointer to one slot only reaches that slot.
+        let page = self.pages[range.start / PAGE_SIZE].as_ref().expect("BVec page not allocated");
+        slice::from_raw_parts(page.as_ptr().add(range.start % PAGE_SIZE) as *const T, range.len())
+    }
+
+    /// # Safety
+    ///
+    /// Same as [`BVec::slice`].
+    pub unsafe fn slice_mut(&mut self, range: Range<usize>) -> &mut [T] {
+        Self::debug_check_range(&range);
+        let page = self.pages[range.start / PAGE_SIZE].as_mut().expect("BVec page not allocated");
+        slice::from_raw_parts_mut(page.as_mut_ptr().add(range.start % PAGE_SIZE) as *mut T, range.len())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        self.mask.iter().map(|idx| (idx, unsafe { self.slot(idx).assume_init_ref() }))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        let pages = &mut self.pages;
+        // Every index is yielded only once so the references never alias. The indices come in
+        // order, each page is borrowed once and its slots are reached through a raw pointer, so
+        // the references already given out of the page stay valid.
+        let mut current: Option<(usize, *mut MaybeUninit<T>)> = None;
+        self.mask.iter().map(move |idx| {
+            let page = idx / PAGE_SIZE;
+            let slots = match current {
+                Some((current_page, slots)) if current_page == page => slots,
+                _ => {
+                    let slots = pages[page].as_mut().expect("BVec page not allocated").as_mut_ptr();
+                    current = Some((page, slots));
+                    slots
+                }
+            };
+            (idx, unsafe { (*slots.add(idx % PAGE_SIZE)).assume_init_mut() })
+        })
+    }
+
+    pub fn clear(&mut self) {
+        if mem::needs_drop::<T>() {
+            let indices: Vec<usize> = self.mask.iter().collect();
+            for idx in indices {
+                unsafe { self.slot_mut(idx).assume_init_drop() }
+            }
+        }
+        self.mask.clear();
+    }
+}
+
+impl<T> Default for BVec<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for BVec<T> {
+    fn drop(&mut self) {
+        self.clear();
     }
 }
 
@@ -166,16 +796,418 @@ impl<T> IntoIterator for BVec<T> {
 }
 
 pub struct BVecIterator<T> {
-    inner: BVec<T>, 
-    cursor: usize, 
+    inner: BVec<T>,
+    cursor: usize,
 }
 
 impl<T> Iterator for BVecIterator<T> {
     type Item = T;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let idx = self.inner.next_item_index(self.cursor);
-        self.inner.get_mut(idx)
+        let idx = self.inner.next_item_index(self.cursor)?;
+        self.cursor = idx + 1;
+        self.inner.remove(idx)
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Rng;
+
+    #[test]
+    fn mask_add_remove() {
+        let mut mask = BMask::new();
+        for idx in [0, 31, 32, 1023, 1024, 20000] {
+            mask.add(idx);
+        }
+        assert_eq!(mask.iter().collect::<Vec<_>>(), vec![0, 31, 32, 1023, 1024, 20000]);
+        mask.remove(1024);
+        mask.remove(20000);
+        assert!(!mask.is_present(1024));
+        assert_eq!(mask.next(33), Some(1023));
+        assert_eq!(mask.next(1024), None);
+        assert_eq!(mask.len(), 4);
+    }
+
+    #[test]
+    fn mask_remove_one_of_two_bits_in_a_word() {
+        let mut mask = BMask::new();
+        mask.add(3);
+        mask.add(7);
+        mask.remove(3);
+        assert!(!mask.is_present(3) && mask.is_present(7));
+        assert_eq!(mask.iter().collect::<Vec<_>>(), [7]);
+        // Removing an absent bit changes nothing.
+        mask.remove(3);
+        assert_eq!(mask.iter().collect::<Vec<_>>(), [7]);
+        mask.check().unwrap();
+    }
+
+    #[test]
+    fn mask_remove_keeps_siblings_in_other_words() {
+        let mut mask = BMask::new();
+        // Words 0 and 5 of the leaf layer, both under the first bit of the second layer.
+        mask.add(10);
+        mask.add(5 * 32 + 1);
+        // Words 32 and 40, under the first bit of the first layer but another second layer word.
+        mask.add(32 * 32);
+        mask.add(40 * 32 + 9);
+        mask.remove(10);
+        assert_eq!(mask.iter().collect::<Vec<_>>(), [161, 1024, 1289]);
+        mask.remove(32 * 32);
+        assert_eq!(mask.iter().collect::<Vec<_>>(), [161, 1289]);
+        assert_eq!(mask.next(0), Some(161));
+        assert_eq!(mask.next(162), Some(1289));
+        mask.check().unwrap();
+    }
+
+    #[test]
+    fn mask_add_remove_round_trip() {
+        let mut mask = BMask::new();
+        let indices: Vec<usize> = (0..32768).step_by(7).collect();
+        for idx in &indices {
+            mask.add(*idx);
+        }
+        assert_eq!(mask.len(), indices.len());
+        for idx in indices.iter().rev() {
+            mask.remove(*idx);
+        }
+        assert!(mask.is_empty());
+        assert_eq!(mask.len(), 0);
+        assert_eq!(mask.next(0), None);
+        mask.check().unwrap();
+    }
+
+    #[test]
+    fn mask_layers_stay_consistent() {
+        let mut mask = BMask::new();
+        for idx in [0, 31, 32, 1023, 1024, 20000, 32767] {
+            mask.add(idx);
+            mask.check().unwrap();
+        }
+        for idx in [1024, 0, 20000, 77] {
+            mask.remove(idx);
+            mask.check().unwrap();
+        }
+        mask.shrink_to_fit();
+        mask.check().unwrap();
+        mask.l2[0] = 0;
+        assert!(mask.check().is_err());
+    }
+
+    fn assert_round_trip(mask: &BMask) {
+        let decoded = BMask::from_bytes(&mask.to_bytes()).unwrap();
+        decoded.check().unwrap();
+        assert!(decoded.iter().eq(mask.iter()));
+    }
+
+    #[test]
+    fn mask_bytes_round_trip() {
+        assert_eq!(BMask::new().to_bytes(), []);
+        assert_round_trip(&BMask::new());
+        let mut dense = BMask::new();
+        for idx in 0..CAPACITY {
+            dense.add(idx);
+        }
+        assert_round_trip(&dense);
+        // Each word past the 128th takes a second byte for its index, past the 16384th a third.
+        assert_eq!(dense.to_bytes().len(), 128 * 5 + (16384 - 128) * 6 + (32768 - 16384) * 7);
+        let mut sparse = BMask::new();
+        for idx in [5, 4000, 32767] {
+            sparse.add(idx);
+        }
+        assert_round_trip(&sparse);
+        assert_eq!(sparse.to_bytes()[..5], [0, 32, 0, 0, 0]);
+        // Emptied words are left out.
+        sparse.remove(4000);
+        assert_eq!(sparse.to_bytes().len(), 5 + 6);
+    }
+
+    #[test]
+    fn malformed_mask_bytes_are_errors() {
+        assert_eq!(BMask::from_bytes(&[3, 1, 0]).err(), Some(DecodeError::Truncated { offset: 1 }));
+        assert_eq!(BMask::from_bytes(&[0x80]).err(), Some(DecodeError::Truncated { offset: 1 }));
+        let past_the_end = BMask::from_bytes(&[0x80, 0x80, 0x02, 1, 0, 0, 0]);
+        assert_eq!(past_the_end.err(), Some(DecodeError::OutOfRange { word: 32768 }));
+        let overlong = [0xff; 16].into_iter().chain([1, 1, 0, 0, 0]).collect::<Vec<u8>>();
+        assert!(matches!(BMask::from_bytes(&overlong), Err(DecodeError::OutOfRange { .. })));
+        let repeated = BMask::from_bytes(&[2, 1, 0, 0, 0, 2, 1, 0, 0, 0]);
+        assert_eq!(repeated.err(), Some(DecodeError::Unordered { word: 2 }));
+        // An empty word decodes to nothing and keeps the layers consistent.
+        let empty = BMask::from_bytes(&[7, 0, 0, 0, 0]).unwrap();
+        assert!(empty.is_empty());
+        empty.check().unwrap();
+    }
+
+    #[test]
+    fn random_masks_round_trip() {
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+        for _ in 0..200 {
+            let mut vec = BVec::new();
+            let (count, spread) = (rng.below(300), 1 + rng.below(CAPACITY));
+            for _ in 0..count {
+                vec.insert(rng.below(spread), ());
+            }
+            let mut bytes = Vec::new();
+            vec.write_occupancy(&mut bytes);
+            let decoded = BVec::<()>::read_occupancy(&bytes).unwrap();
+            decoded.check().unwrap();
+            assert!(decoded.iter().eq(vec.mask().iter()));
+            // Any cut inside the input is reported.
+            if !bytes.is_empty() {
+                let cut = rng.below(bytes.len());
+                let _ = BMask::from_bytes(&bytes[..cut]);
+                assert!(BMask::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn bvec_remove_many_matches_removing_one_at_a_time() {
+        use std::rc::Rc;
+
+        let drops = Rc::new(());
+        let mut batched = BVec::new();
+        let mut looped = BVec::new();
+        for idx in (0..CAPACITY).filter(|idx| idx % 3 != 0) {
+            batched.insert(idx, drops.clone());
+            looped.insert(idx, drops.clone());
+        }
+        let stored = batched.len();
+        // Absent, repeated and unsorted indices, whole words and pages emptied.
+        let mut indices: Vec<usize> = (0..CAPACITY).filter(|idx| idx % 7 < 3 || *idx < 3000).collect();
+        indices.extend([5, 0, 1, 1]);
+        indices.reverse();
+        let removed = batched.remove_many(indices.iter().copied());
+        let expected = indices.iter().filter(|idx| looped.remove(**idx).is_some()).count();
+        assert_eq!(removed, expected);
+        assert!(batched.mask().iter().eq(looped.mask().iter()));
+        batched.mask().check().unwrap();
+        assert_eq!(Rc::strong_count(&drops), 1 + 2 * (stored - expected));
+        assert_eq!(batched.next_item_index(0), Some(3001));
+        assert_eq!(batched.remove_many([]), 0);
+        drop(batched);
+        drop(looped);
+        assert_eq!(Rc::strong_count(&drops), 1);
+    }
+
+    #[test]
+    fn mask_first_empty_spot() {
+        let mut mask = BMask::new();
+        assert_eq!(mask.first_empty_spot(), 0);
+        for idx in 0..40 {
+            mask.add(idx);
+        }
+        assert_eq!(mask.first_empty_spot(), 40);
+        mask.remove(3);
+        assert_eq!(mask.first_empty_spot(), 3);
+    }
+
+    #[test]
+    fn bvec_insert_get_remove() {
+        let mut vec = BVec::new();
+        assert_eq!(vec.insert(5, String::from("five")), None);
+        assert_eq!(vec.insert(100, String::from("hundred")), None);
+        assert_eq!(vec.get(5).map(String::as_str), Some("five"));
+        assert_eq!(vec.get(6), None);
+        assert_eq!(vec.insert(5, String::from("cinq")), Some(String::from("five")));
+        assert_eq!(vec.remove(100), Some(String::from("hundred")));
+        assert_eq!(vec.remove(100), None);
+        assert_eq!(vec.len(), 1);
+        assert_eq!(vec.into_iter().collect::<Vec<_>>(), vec![String::from("cinq")]);
+    }
+
+    #[test]
+    fn bvec_insert_many_replaces_in_any_order() {
+        let mut vec = BVec::new();
+        vec.insert(7, String::from("old"));
+        let pairs = [(3 * PAGE_SIZE, "c"), (7, "new"), (8, "b"), (2, "a")];
+        vec.insert_many(pairs.iter().map(|(idx, s)| (*idx, String::from(*s))));
+        let items: Vec<_> = vec.iter().map(|(idx, s)| (idx, s.as_str())).collect();
+        assert_eq!(items, vec![(2, "a"), (7, "new"), (8, "b"), (3 * PAGE_SIZE, "c")]);
+        assert_eq!(vec.page_count(), 2);
+        assert!(vec.mask().check().is_ok());
+    }
+
+    #[test]
+    fn bvec_insert_many_repeating_a_new_index() {
+        let mut vec = BVec::new();
+        let pairs = [(5, "a"), (6, "b"), (5, "c"), (9, "d"), (6, "e")];
+        vec.insert_many(pairs.iter().map(|(idx, s)| (*idx, String::from(*s))));
+        let items: Vec<_> = vec.iter().map(|(idx, s)| (idx, s.as_str())).collect();
+        assert_eq!(items, vec![(5, "c"), (6, "e"), (9, "d")]);
+        vec.mask().check().unwrap();
+    }
+
+    #[test]
+    fn mask_ranges_within_and_across_words() {
+        let mut mask = BMask::new();
+        mask.add_range(0..64);
+        mask.add_range(70..75);
+        mask.add_range(95..97);
+        let expected: Vec<usize> = (0..64).chain(70..75).chain(95..97).collect();
+        assert_eq!(mask.iter().collect::<Vec<_>>(), expected);
+        assert_eq!((mask.word(0), mask.word(1)), (u32::MAX, u32::MAX));
+        assert_eq!(mask.first_empty_spot(), 64);
+        assert_eq!(mask.count_in_range(30..96), 2 + 32 + 5 + 1);
+        mask.remove_range(32..64);
+        mask.remove_range(72..96);
+        assert_eq!(mask.iter().collect::<Vec<_>>(), (0..32).chain(70..72).chain(96..97).collect::<Vec<_>>());
+        assert_eq!(mask.word(1), 0);
+        mask.check().unwrap();
+    }
+
+    #[test]
+    fn mask_ranges_across_upper_layer_words() {
+        let mut mask = BMask::new();
+        // Across the words of the second layer, then of the first one.
+        mask.add_range(1000..3100);
+        mask.add_range(32760..32800);
+        mask.check().unwrap();
+        assert_eq!(mask.len(), 2100 + 40);
+        assert_eq!(mask.count_in_range(1024..32768), 2076 + 8);
+        mask.remove_range(1000..3072);
+        mask.check().unwrap();
+        assert_eq!(mask.next(0), Some(3072));
+        mask.remove_range(0..32768);
+        assert_eq!(mask.iter().collect::<Vec<_>>(), (32768..32800).collect::<Vec<_>>());
+        mask.remove_range(32768..CAPACITY);
+        assert!(mask.is_empty());
+        mask.check().unwrap();
+        mask.add_range(CAPACITY - 40..CAPACITY);
+        assert_eq!(mask.next(0), Some(CAPACITY - 40));
+        mask.check().unwrap();
+    }
+
+    #[test]
+    fn mask_empty_ranges_change_nothing() {
+        let mut mask = BMask::new();
+        mask.add(3);
+        let changes = mask.changes();
+        mask.add_range(10..10);
+        #[allow(clippy::reversed_empty_ranges)]
+        mask.remove_range(5..2);
+        mask.remove_range(100..200);
+        assert_eq!(mask.changes(), changes);
+        assert_eq!(mask.count_in_range(3..3), 0);
+        assert_eq!(mask.iter().collect::<Vec<_>>(), [3]);
+        assert_eq!(mask.word_count(), 1);
+    }
+
+    #[test]
+    fn mask_ranges_match_bit_loops() {
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+        let (mut ranged, mut looped) = (BMask::new(), BMask::new());
+        for _ in 0..300 {
+            let start = rng.below(70000);
+            let range = start..start + rng.below(3000);
+            if rng.below(3) == 0 {
+                ranged.remove_range(range.clone());
+                range.clone().for_each(|idx| looped.remove(idx));
+            } else {
+                ranged.add_range(range.clone());
+                range.clone().for_each(|idx| looped.add(idx));
+            }
+            let start = rng.below(75000);
+            let counted = start..start + rng.below(5000);
+            assert_eq!(ranged.count_in_range(counted.clone()), looped.iter().filter(|idx| counted.contains(idx)).count());
+            assert_eq!(ranged.first_empty_spot(), looped.first_empty_spot());
+        }
+        ranged.check().unwrap();
+        assert!(ranged.iter().eq(looped.iter()));
+    }
+
+    #[test]
+    fn bvec_compact_frees_empty_pages() {
+        let mut vec = BVec::new();
+        for idx in [3, PAGE_SIZE + 1, 5 * PAGE_SIZE] {
+            vec.insert(idx, idx);
+        }
+        assert_eq!(vec.page_count(), 3);
+        vec.remove(PAGE_SIZE + 1);
+        vec.compact();
+        assert_eq!(vec.page_count(), 2);
+        vec.remove(5 * PAGE_SIZE);
+        vec.compact();
+        assert_eq!((vec.page_count(), vec.pages.len()), (1, 1));
+        assert_eq!(vec.get(3), Some(&3));
+        vec.insert(PAGE_SIZE + 1, 0);
+        assert_eq!(vec.iter().collect::<Vec<_>>(), vec![(3, &3), (PAGE_SIZE + 1, &0)]);
+    }
+
+    #[test]
+    fn bvec_slices_span_several_slots() {
+        let mut vec = BVec::new();
+        vec.insert_many((PAGE_SIZE - 8..PAGE_SIZE + 8).map(|idx| (idx, idx)));
+        // Run under Miri, the slices must reach past their first slot.
+        let chunk = unsafe { vec.slice_mut(PAGE_SIZE - 8..PAGE_SIZE) };
+        for value in chunk.iter_mut() {
+            *value *= 2;
+        }
+        chunk[7] += 1;
+        let chunk = unsafe { vec.slice_mut(PAGE_SIZE..PAGE_SIZE + 8) };
+        chunk.fill(0);
+        assert_eq!(unsafe { vec.slice(PAGE_SIZE - 2..PAGE_SIZE) }, [2 * PAGE_SIZE - 4, 2 * PAGE_SIZE - 1]);
+        assert_eq!(unsafe { vec.slice(PAGE_SIZE..PAGE_SIZE + 8) }, [0; 8]);
+        assert_eq!(vec.get(PAGE_SIZE - 8), Some(&(2 * PAGE_SIZE - 16)));
+    }
+
+    #[test]
+    fn bvec_iter_mut_items_outlive_the_next_ones() {
+        let mut vec = BVec::new();
+        for idx in [1, 2, 7, PAGE_SIZE, PAGE_SIZE + 3] {
+            vec.insert(idx, idx);
+        }
+        // Run under Miri, yielding an item must leave the ones before it usable.
+        let mut items: Vec<&mut usize> = vec.iter_mut().map(|(_, value)| value).collect();
+        for value in &mut items {
+            **value += 1;
+        }
+        assert_eq!(vec.iter().map(|(_, value)| *value).collect::<Vec<_>>(), [2, 3, 8, PAGE_SIZE + 1, PAGE_SIZE + 4]);
+    }
+
+    #[test]
+    fn bvec_grows_past_32_cubed() {
+        let mut vec = BVec::new();
+        for idx in [70000, 5, 40000, 32767, 32768] {
+            vec.insert(idx, idx);
+        }
+        assert_eq!(vec.iter().map(|(idx, _)| idx).collect::<Vec<_>>(), [5, 32767, 32768, 40000, 70000]);
+        assert_eq!(vec.get(40000), Some(&40000));
+        assert_eq!(vec.get(40001), None);
+        vec.mask().check().unwrap();
+        // Only the pages of the indices used are allocated.
+        assert_eq!((vec.page_count(), vec.capacity()), (5, 5 * PAGE_SIZE));
+        assert!(vec.allocated_bytes() < CAPACITY * mem::size_of::<usize>() / 64);
+        assert_eq!(vec.remove(70000), Some(70000));
+        vec.compact();
+        assert_eq!(vec.page_count(), 4);
+        assert_eq!(vec.mask().next(32769), Some(40000));
+        assert_eq!(vec.mask().next(40001), None);
+    }
+
+    #[test]
+    fn bvec_insert_first_empty_fills_holes() {
+        use std::rc::Rc;
+
+        let drops = Rc::new(());
+        let mut vec = BVec::new();
+        for expected in 0..5 {
+            let (idx, value) = vec.insert_first_empty(drops.clone()).unwrap();
+            assert_eq!(idx, expected);
+            assert!(Rc::ptr_eq(value, &drops));
+        }
+        drop(vec.remove(2));
+        assert_eq!(vec.insert_first_empty(drops.clone()).unwrap().0, 2);
+        assert_eq!(vec.insert_first_empty(drops.clone()).unwrap().0, 5);
+        // Nothing got overwritten, every clone is still stored once.
+        assert_eq!(Rc::strong_count(&drops), 7);
+        vec.clear();
+        assert_eq!(Rc::strong_count(&drops), 1);
+
+        let mut full = BVec::new();
+        full.insert_many((0..CAPACITY).map(|idx| (idx, ())));
+        assert_eq!(full.insert_first_empty(()), Err(()));
+    }
+}
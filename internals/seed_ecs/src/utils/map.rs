@@ -0,0 +1,104 @@
+use core::any::TypeId;
+use core::hash::{BuildHasherDefault, Hasher};
+
+/// The map used by the world, hashed with `std`, ordered with only `alloc` as the keys are all
+/// `Ord` and `alloc` has no hash map.
+#[cfg(feature = "std")]
+pub type HashMap<K, V> = std::collections::HashMap<K, V>;
+#[cfg(not(feature = "std"))]
+pub type HashMap<K, V> = alloc::collections::BTreeMap<K, V>;
+
+/// A hasher for keys that are already hashes, like [`TypeId`]s, passing the value through.
+#[derive(Default)]
+pub struct TypeIdHasher {
+    hash: u64,
+}
+
+impl Hasher for TypeIdHasher {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.hash = value;
+    }
+
+    fn write_u128(&mut self, value: u128) {
+        self.hash = value as u64 ^ (value >> 64) as u64;
+    }
+
+    // Only reached if the hash of `TypeId` changes to write bytes, keeps it correct if slower.
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash = self.hash.rotate_left(8) ^ *byte as u64;
+        }
+    }
+}
+
+/// A map keyed by [`TypeId`] that doesn't rehash the keys.
+#[cfg(feature = "std")]
+pub type TypeIdMap<V> = std::collections::HashMap<TypeId, V, BuildHasherDefault<TypeIdHasher>>;
+#[cfg(not(feature = "std"))]
+pub type TypeIdMap<V> = alloc::collections::BTreeMap<TypeId, V>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::time::Instant;
+
+    fn ids() -> [TypeId; 8] {
+        [
+            TypeId::of::<u8>(),
+            TypeId::of::<u16>(),
+            TypeId::of::<u32>(),
+            TypeId::of::<u64>(),
+            TypeId::of::<i8>(),
+            TypeId::of::<String>(),
+            TypeId::of::<Vec<u8>>(),
+            TypeId::of::<()>(),
+        ]
+    }
+
+    #[test]
+    fn behaves_like_a_map() {
+        let mut map = TypeIdMap::default();
+        for (i, id) in ids().into_iter().enumerate() {
+            assert_eq!(map.insert(id, i), None);
+        }
+        assert_eq!(map.len(), 8);
+        for (i, id) in ids().into_iter().enumerate() {
+            assert_eq!(map.get(&id), Some(&i));
+        }
+        assert_eq!(map.remove(&TypeId::of::<u32>()), Some(2));
+        assert_eq!(map.get(&TypeId::of::<u32>()), None);
+        assert_eq!(map.get(&TypeId::of::<i64>()), None);
+    }
+
+    // cargo test --release -- --ignored --nocapture lookup_bench
+    #[test]
+    #[ignore]
+    fn lookup_bench() {
+        const ROUNDS: usize = 10_000_000;
+        let ids = ids();
+
+        let sip: HashMap<TypeId, usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let start = Instant::now();
+        let mut sum = 0;
+        for i in 0..ROUNDS {
+            sum += sip[&ids[i % ids.len()]];
+        }
+        let sip_time = start.elapsed();
+
+        let fast: TypeIdMap<usize> = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        let start = Instant::now();
+        let mut fast_sum = 0;
+        for i in 0..ROUNDS {
+            fast_sum += fast[&ids[i % ids.len()]];
+        }
+        let fast_time = start.elapsed();
+
+        assert_eq!(sum, fast_sum);
+        println!("SipHash: {:?}, TypeIdMap: {:?} for {} lookups", sip_time, fast_time, ROUNDS);
+    }
+}
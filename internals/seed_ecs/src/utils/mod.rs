@@ -1,4 +1,14 @@
 mod bvec;
+#[cfg(test)]
+pub(crate) mod counting_alloc;
+mod mring;
 mod mvec;
+mod map;
+mod sparse;
+mod sparse_map;
 pub use bvec::*;
+pub use mring::*;
 pub use mvec::*;
+pub use map::*;
+pub use sparse::*;
+pub use sparse_map::*;
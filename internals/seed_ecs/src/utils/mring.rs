@@ -0,0 +1,258 @@
+use core::fmt;
+use core::iter::FusedIterator;
+use core::mem::MaybeUninit;
+use core::ptr;
+
+/// A ring of at most `N` elements stored inline, pushing into a full ring overwrites the oldest
+/// one. Nothing is allocated after construction, which makes it fit for the histories kept every
+/// frame.
+pub struct MRing<T, const N: usize> {
+    buffer: [MaybeUninit<T>; N],
+    // The slot of the oldest element.
+    head: usize,
+    len: usize,
+}
+
+impl<T, const N: usize> MRing<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [const { MaybeUninit::uninit() }; N],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    // The slot of the `i`-th element from the oldest one, `i` must be less than `N`.
+    fn slot(&self, i: usize) -> usize {
+        let slot = self.head + i;
+        if slot >= N { slot - N } else { slot }
+    }
+
+    /// Pushes `elem` as the newest element, returning the oldest one if the ring was full. A ring
+    /// of size 0 gives `elem` back.
+    pub fn push_overwrite(&mut self, elem: T) -> Option<T> {
+        if N == 0 {
+            return Some(elem);
+        }
+        if self.len < N {
+            let slot = self.slot(self.len);
+            self.buffer[slot].write(elem);
+            self.len += 1;
+            return None;
+        }
+        // The slot of the oldest element becomes the one of the newest.
+        let evicted = unsafe { self.buffer[self.head].assume_init_read() };
+        self.buffer[self.head].write(elem);
+        self.head = self.slot(1);
+        Some(evicted)
+    }
+
+    /// Takes the oldest element out of the ring.
+    pub fn pop_oldest(&mut self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        let elem = unsafe { self.buffer[self.head].assume_init_read() };
+        self.head = self.slot(1);
+        self.len -= 1;
+        Some(elem)
+    }
+
+    /// The `i`-th element from the newest one, 0 being the newest.
+    pub fn get_relative(&self, i: usize) -> Option<&T> {
+        if i >= self.len {
+            return None;
+        }
+        let slot = self.slot(self.len - 1 - i);
+        unsafe { Some(self.buffer[slot].assume_init_ref()) }
+    }
+
+    pub fn newest(&self) -> Option<&T> {
+        self.get_relative(0)
+    }
+
+    /// The elements from the oldest to the newest.
+    pub fn iter(&self) -> MRingIter<'_, T, N> {
+        MRingIter {
+            ring: self,
+            front: 0,
+            back: self.len,
+        }
+    }
+
+    /// Drops every element.
+    pub fn clear(&mut self) {
+        let (len, head) = (self.len, self.head);
+        // Forgotten rather than dropped twice if a drop panics.
+        self.len = 0;
+        self.head = 0;
+        for i in 0..len {
+            let slot = if head + i >= N { head + i - N } else { head + i };
+            unsafe { ptr::drop_in_place(self.buffer[slot].as_mut_ptr()) };
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for MRing<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Default for MRing<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for MRing<T, N> {
+    fn clone(&self) -> Self {
+        let mut ring = Self::new();
+        for elem in self.iter() {
+            ring.push_overwrite(elem.clone());
+        }
+        ring
+    }
+}
+
+impl<T: fmt::Debug, const N: usize> fmt::Debug for MRing<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a MRing<T, N> {
+    type Item = &'a T;
+    type IntoIter = MRingIter<'a, T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// The elements of a [`MRing`] from the oldest to the newest.
+pub struct MRingIter<'a, T, const N: usize> {
+    ring: &'a MRing<T, N>,
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for MRingIter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.front == self.back {
+            return None;
+        }
+        let slot = self.ring.slot(self.front);
+        self.front += 1;
+        unsafe { Some(self.ring.buffer[slot].assume_init_ref()) }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.back - self.front;
+        (len, Some(len))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for MRingIter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        let slot = self.ring.slot(self.back);
+        unsafe { Some(self.ring.buffer[slot].assume_init_ref()) }
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for MRingIter<'_, T, N> {}
+
+impl<T, const N: usize> FusedIterator for MRingIter<'_, T, N> {}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use super::*;
+    use crate::utils::counting_alloc::count_allocations;
+
+    #[test]
+    fn wraps_around_in_order() {
+        let mut ring = MRing::<u32, 4>::new();
+        for i in 0..10 {
+            ring.push_overwrite(i);
+        }
+        assert_eq!(ring.len(), 4);
+        assert!(ring.iter().copied().eq(6..10));
+        assert!(ring.iter().rev().copied().eq((6..10).rev()));
+        assert_eq!(ring.get_relative(0), Some(&9));
+        assert_eq!(ring.get_relative(3), Some(&6));
+        assert_eq!(ring.get_relative(4), None);
+    }
+
+    #[test]
+    fn pushing_into_a_full_ring_returns_the_oldest() {
+        let mut ring = MRing::<u32, 3>::new();
+        assert_eq!(ring.push_overwrite(1), None);
+        assert_eq!(ring.push_overwrite(2), None);
+        assert_eq!(ring.push_overwrite(3), None);
+        assert_eq!(ring.push_overwrite(4), Some(1));
+        assert_eq!(ring.push_overwrite(5), Some(2));
+        assert_eq!(ring.pop_oldest(), Some(3));
+        assert_eq!(ring.push_overwrite(6), None);
+        assert!(ring.iter().copied().eq(4..7));
+        let mut empty = MRing::<u32, 0>::new();
+        assert_eq!(empty.push_overwrite(7), Some(7));
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn every_element_is_dropped_once() {
+        let counter = Rc::new(());
+        let mut ring = MRing::<Rc<()>, 5>::new();
+        let (_, allocations) = count_allocations(|| {
+            for _ in 0..12 {
+                drop(ring.push_overwrite(counter.clone()));
+            }
+        });
+        assert_eq!(allocations, 0);
+        assert_eq!(Rc::strong_count(&counter), 6);
+        ring.clear();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        for _ in 0..3 {
+            ring.push_overwrite(counter.clone());
+        }
+        drop(ring);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn iterates_a_partial_fill() {
+        let mut ring = MRing::<u32, 8>::new();
+        assert_eq!(ring.iter().next(), None);
+        ring.push_overwrite(1);
+        ring.push_overwrite(2);
+        ring.push_overwrite(3);
+        assert_eq!(ring.iter().len(), 3);
+        assert!(ring.iter().copied().eq(1..4));
+        assert_eq!(ring.newest(), Some(&3));
+        assert_eq!(format!("{:?}", ring), "[1, 2, 3]");
+    }
+}
@@ -1,6 +1,6 @@
 use core::slice;
-use std::{
-    alloc::{self, Layout},
+use ::alloc::alloc::{self, Layout};
+use core::{
     marker::PhantomData,
     mem,
     ops::{Index, self},
@@ -15,7 +15,11 @@ struct RawVec<T, const N: usize> {
 }
 
 impl<T, const N: usize> RawVec<T, N> {
-    const MAX_CAP: usize = (isize::MAX as usize).min(N);
+    const MAX_CAP: usize = if N < isize::MAX as usize {
+        N
+    } else {
+        isize::MAX as usize
+    };
     pub fn new() -> Self {
         assert!(mem::size_of::<T>() != 0, "TODO: implement ZST support");
         RawVec {
@@ -26,19 +30,43 @@ impl<T, const N: usize> RawVec<T, N> {
     }
 
     pub fn grow(&mut self) {
-        let (new_cap, new_layout) = if self.cap == 0 {
-            (1, Layout::array::<T>(1).unwrap())
-        } else {
-            // This can't overflow because we ensure self.cap <= isize::MAX.
-            let new_cap = usize::min(2 * self.cap, N);
+        self.grow_to(self.grown_cap(1));
+    }
 
-            // Layout::array checks that the number of bytes is <= usize::MAX,
-            // but this is redundant since old_layout.size() <= isize::MAX,
-            // so the `unwrap` should never fail.
-            let new_layout = Layout::array::<T>(new_cap).unwrap();
-            (new_cap, new_layout)
+    /// Makes room for `count` more elements than the capacity, doubling it at least so that a
+    /// run of small extends stays linear.
+    pub fn extend(&mut self, count: usize) {
+        if count != 0 {
+            self.grow_to(self.grown_cap(count));
+        }
+    }
+
+    // The capacity after growing by `count` elements: twice the current one, capped at `N`, or
+    // more if that doesn't fit `count`.
+    fn grown_cap(&self, count: usize) -> usize {
+        let needed = self.cap.checked_add(count).filter(|needed| *needed <= Self::MAX_CAP);
+        let Some(needed) = needed else {
+            panic!("Capacity exceeds the size of the MVec: {} + {} > {}", self.cap, count, N);
         };
+        // This can't overflow because we ensure self.cap <= isize::MAX.
+        usize::max(needed, usize::min(2 * self.cap, Self::MAX_CAP))
+    }
 
+    // Reallocates to fit exactly `target_cap` elements, a fresh allocation when nothing was
+    // allocated yet.
+    pub fn grow_to(&mut self, target_cap: usize) {
+        assert!(
+            target_cap <= Self::MAX_CAP,
+            "Capacity exceeds the size of the MVec: {} > {}",
+            target_cap,
+            N
+        );
+        if target_cap <= self.cap {
+            return;
+        }
+        // Layout::array checks that the number of bytes is <= usize::MAX,
+        // but this is redundant since target_cap <= isize::MAX.
+        let new_layout = Layout::array::<T>(target_cap).unwrap();
         let new_ptr = if self.cap == 0 {
             unsafe { alloc::alloc(new_layout) }
         } else {
@@ -52,23 +80,26 @@ impl<T, const N: usize> RawVec<T, N> {
             Some(p) => p,
             None => alloc::handle_alloc_error(new_layout),
         };
-        self.cap = new_cap;
+        self.cap = target_cap;
     }
 
-    pub fn extend(&mut self, count: usize) {
-        let new_cap = self.cap + count;
-        let new_layout = Layout::array::<T>(new_cap).unwrap();
-        let new_ptr = {
-            let old_layout = Layout::array::<T>(self.cap).unwrap();
-            let old_ptr = self.ptr.as_ptr() as *mut u8;
-            unsafe {
-                alloc::realloc(old_ptr, old_layout, new_layout.size())
-            }
-        };
-        self.ptr = match NonNull::new(new_ptr as *mut T) {
-            Some(p) => p,
-            None => alloc::handle_alloc_error(new_layout),
-        };
+    // Reallocates to fit exactly `new_cap` elements, which must not be less than the length.
+    pub fn shrink_to(&mut self, new_cap: usize) {
+        if new_cap >= self.cap {
+            return;
+        }
+        let old_layout = Layout::array::<T>(self.cap).unwrap();
+        if new_cap == 0 {
+            unsafe { alloc::dealloc(self.ptr.as_ptr() as *mut u8, old_layout) };
+            self.ptr = NonNull::dangling();
+        } else {
+            let new_layout = Layout::array::<T>(new_cap).unwrap();
+            let new_ptr = unsafe { alloc::realloc(self.ptr.as_ptr() as *mut u8, old_layout, new_layout.size()) };
+            self.ptr = match NonNull::new(new_ptr as *mut T) {
+                Some(p) => p,
+                None => alloc::handle_alloc_error(new_layout),
+            };
+        }
         self.cap = new_cap;
     }
 }
@@ -102,6 +133,10 @@ impl<T, const N: usize> MVec<T, N> {
         self.len
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
     fn ptr(&self) -> *mut T {
         self.buffer.ptr.as_ptr()
     }
@@ -140,6 +175,11 @@ impl<T, const N: usize> MVec<T, N> {
         }
     }
 
+    /// Releases the capacity past the length.
+    pub fn shrink_to_fit(&mut self) {
+        self.buffer.shrink_to(self.len);
+    }
+
     pub fn insert(&mut self, idx: usize, elem: T) {
         assert!(
             idx < N,
@@ -147,20 +187,29 @@ impl<T, const N: usize> MVec<T, N> {
             idx,
             N
         );
-        if idx > self.capacity() {
-            self.extend(self.capacity() - idx - 1);
+        while idx >= self.capacity() {
+            self.buffer.grow();
         }
-        if idx > self.len {
+        if idx >= self.len {
             self.len = idx + 1;
         }
         unsafe { ptr::write(self.ptr().add(idx), elem) }
     }
 
     pub fn get(&self, idx: usize) -> &T {
-        unsafe { &ptr::read(self.ptr().add(idx)) }
+        assert!(idx < self.len, "Index out of bounds: {} >= {}", idx, self.len);
+        unsafe { &*self.ptr().add(idx) }
+    }
+
+    pub fn get_mut(&mut self, idx: usize) -> &mut T {
+        assert!(idx < self.len, "Index out of bounds: {} >= {}", idx, self.len);
+        unsafe { &mut *self.ptr().add(idx) }
     }
-    pub fn get_mut(&self, idx: usize) -> &mut T {
-        unsafe { &mut ptr::read(self.ptr().add(idx)) }
+}
+
+impl<T, const N: usize> Default for MVec<T, N> {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -181,3 +230,75 @@ impl<T, const N: usize> ops::DerefMut for MVec<T, N> {
         unsafe { slice::from_raw_parts_mut(self.ptr(), self.len) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::counting_alloc::count_allocations;
+
+    #[test]
+    fn extend_from_empty_allocates() {
+        let mut vec = MVec::<u64, 64>::new();
+        let (_, allocations) = count_allocations(|| vec.extend(5));
+        assert_eq!(allocations, 1);
+        assert_eq!(vec.capacity(), 5);
+        for i in 0..5 {
+            vec.push(i);
+        }
+        assert_eq!(vec.capacity(), 5);
+        assert_eq!(&vec[..], [0, 1, 2, 3, 4]);
+        vec.extend(0);
+        assert_eq!(vec.capacity(), 5);
+    }
+
+    #[test]
+    fn small_extends_grow_geometrically() {
+        let mut vec = MVec::<u32, { 1 << 16 }>::new();
+        let (extends, allocations) = count_allocations(|| {
+            let mut extends = 0;
+            while vec.capacity() < 30_000 {
+                vec.extend(3);
+                extends += 1;
+            }
+            extends
+        });
+        // Growing by exactly 3 would have taken 10000 reallocations.
+        assert_eq!(allocations, extends);
+        assert!(allocations <= 16, "{} allocations", allocations);
+        for i in 0..30_000 {
+            vec.push(i);
+        }
+        assert!((0..30_000).eq(vec.iter().copied()));
+    }
+
+    #[test]
+    fn growth_stops_at_the_maximum_size() {
+        let mut vec = MVec::<u8, 12>::new();
+        vec.extend(5);
+        vec.extend(1);
+        assert_eq!(vec.capacity(), 10);
+        vec.extend(1);
+        assert_eq!(vec.capacity(), 12);
+        for i in 0..12 {
+            vec.push(i);
+        }
+        assert_eq!(vec.len(), 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the size of the MVec")]
+    fn extending_past_the_maximum_size_panics() {
+        let mut vec = MVec::<u8, 12>::new();
+        vec.extend(8);
+        vec.extend(5);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds the size of the MVec")]
+    fn pushing_past_the_maximum_size_panics() {
+        let mut vec = MVec::<u8, 4>::new();
+        for i in 0..5 {
+            vec.push(i);
+        }
+    }
+}
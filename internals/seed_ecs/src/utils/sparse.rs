@@ -0,0 +1,372 @@
+use alloc::vec::Vec;
+use core::mem;
+
+use super::{BMask, BVec};
+
+// Values packed at the front of a vector, found from their index through a `BVec` of positions.
+// Removing a value moves the last one into its position, so the values stay contiguous but their
+// order changes.
+pub struct SparseSet<T> {
+    positions: BVec<u32>,
+    // The index of the value at each position.
+    indices: Vec<u32>,
+    values: Vec<T>,
+}
+
+impl<T> SparseSet<T> {
+    pub fn new() -> Self {
+        Self {
+            positions: BVec::new(),
+            indices: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    /// The position of the value of `idx` in [`SparseSet::values`].
+    #[inline]
+    pub fn position(&self, idx: usize) -> Option<usize> {
+        self.positions.get(idx).map(|position| *position as usize)
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let position = self.position(idx)?;
+        Some(unsafe { self.values.get_unchecked(position) })
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        let position = self.position(idx)?;
+        Some(unsafe { self.values.get_unchecked_mut(position) })
+    }
+
+    pub fn contains(&self, idx: usize) -> bool {
+        self.positions.contains(idx)
+    }
+
+    /// Stores `elem` at `idx` and returns the element that was there before if any. New elements
+    /// go after the others.
+    pub fn insert(&mut self, idx: usize, elem: T) -> Option<T> {
+        if let Some(position) = self.position(idx) {
+            return Some(mem::replace(&mut self.values[position], elem));
+        }
+        self.positions.insert(idx, self.values.len() as u32);
+        self.indices.push(idx as u32);
+        self.values.push(elem);
+        None
+    }
+
+    /// Removes the element of `idx`, the last element takes its position.
+    pub fn remove(&mut self, idx: usize) -> Option<T> {
+        let position = self.positions.remove(idx)? as usize;
+        self.indices.swap_remove(position);
+        if let Some(moved) = self.indices.get(position) {
+            *self.positions.get_mut(*moved as usize).unwrap() = position as u32;
+        }
+        Some(self.values.swap_remove(position))
+    }
+
+    /// Gives the element of `from` to the absent `to`, at the same position.
+    pub fn rekey(&mut self, from: usize, to: usize) -> bool {
+        let Some(position) = self.positions.remove(from) else {
+            return false;
+        };
+        self.positions.insert(to, position);
+        self.indices[position as usize] = to as u32;
+        true
+    }
+
+    /// Exchanges the elements at two positions.
+    pub fn swap(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        self.values.swap(a, b);
+        self.indices.swap(a, b);
+        *self.positions.get_mut(self.indices[a] as usize).unwrap() = a as u32;
+        *self.positions.get_mut(self.indices[b] as usize).unwrap() = b as u32;
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn mask(&self) -> &BMask {
+        self.positions.mask()
+    }
+
+    /// The elements in position order.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    /// The index of the element at each position.
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
+    /// Number of pages allocated for the positions.
+    pub fn page_count(&self) -> usize {
+        self.positions.page_count()
+    }
+
+    /// Number of bytes allocated for the elements and the positions.
+    pub fn allocated_bytes(&self) -> usize {
+        self.values.capacity() * mem::size_of::<T>()
+            + self.indices.capacity() * mem::size_of::<u32>()
+            + self.positions.allocated_bytes()
+    }
+
+    /// Allocates what is needed to store elements at the indices below `len`.
+    pub fn reserve(&mut self, len: usize) {
+        self.positions.reserve(len);
+        self.indices.reserve(len.saturating_sub(self.indices.len()));
+        self.values.reserve(len.saturating_sub(self.values.len()));
+    }
+
+    pub fn reserve_mask(&mut self, len: usize) {
+        self.positions.reserve_mask(len);
+    }
+
+    pub fn compact(&mut self) {
+        self.positions.compact();
+        self.indices.shrink_to_fit();
+        self.values.shrink_to_fit();
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        self.indices.iter().map(|idx| *idx as usize).zip(self.values.iter_mut())
+    }
+
+    /// Takes every element out along with its index, in position order.
+    pub fn into_entries(self) -> impl Iterator<Item = (usize, T)> {
+        self.indices.into_iter().map(|idx| idx as usize).zip(self.values)
+    }
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Values found from their index through a `BVec` of positions, like `SparseSet`, but removing a
+// value leaves a hole at its position instead of moving the last one there. The holes are reused
+// by the next insertions and closed by `StableSet::compact`, the only call moving values.
+pub struct StableSet<T> {
+    positions: BVec<u32>,
+    // The index and value at each position, `None` for the holes.
+    slots: Vec<Option<(u32, T)>>,
+    // The holes, the last one is filled first.
+    free: Vec<u32>,
+}
+
+impl<T> StableSet<T> {
+    pub fn new() -> Self {
+        Self {
+            positions: BVec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    #[inline]
+    pub fn position(&self, idx: usize) -> Option<usize> {
+        self.positions.get(idx).map(|position| *position as usize)
+    }
+
+    #[inline]
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        let position = self.position(idx)?;
+        self.slots[position].as_ref().map(|(_, value)| value)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut T> {
+        let position = self.position(idx)?;
+        self.slots[position].as_mut().map(|(_, value)| value)
+    }
+
+    /// Stores `elem` at `idx` and returns the element that was there before if any. New elements
+    /// fill the last hole left, or go after the others.
+    pub fn insert(&mut self, idx: usize, elem: T) -> Option<T> {
+        if let Some(value) = self.get_mut(idx) {
+            return Some(mem::replace(value, elem));
+        }
+        let position = self.free.pop().unwrap_or_else(|| {
+            self.slots.push(None);
+            self.slots.len() as u32 - 1
+        });
+        self.positions.insert(idx, position);
+        self.slots[position as usize] = Some((idx as u32, elem));
+        None
+    }
+
+    /// Removes the element of `idx`, leaving a hole at its position.
+    pub fn remove(&mut self, idx: usize) -> Option<T> {
+        let position = self.positions.remove(idx)?;
+        self.free.push(position);
+        self.slots[position as usize].take().map(|(_, value)| value)
+    }
+
+    /// Gives the element of `from` to the absent `to`, at the same position.
+    pub fn rekey(&mut self, from: usize, to: usize) -> bool {
+        let Some(position) = self.positions.remove(from) else {
+            return false;
+        };
+        self.positions.insert(to, position);
+        self.slots[position as usize].as_mut().unwrap().0 = to as u32;
+        true
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of positions, holes included.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn mask(&self) -> &BMask {
+        self.positions.mask()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.capacity()
+    }
+
+    /// Number of pages allocated for the positions.
+    pub fn page_count(&self) -> usize {
+        self.positions.page_count()
+    }
+
+    /// Number of bytes allocated for the elements, the holes and the positions.
+    pub fn allocated_bytes(&self) -> usize {
+        self.slots.capacity() * mem::size_of::<Option<(u32, T)>>()
+            + self.free.capacity() * mem::size_of::<u32>()
+            + self.positions.allocated_bytes()
+    }
+
+    /// Allocates what is needed to store elements at the indices below `len`.
+    pub fn reserve(&mut self, len: usize) {
+        self.positions.reserve(len);
+        self.slots.reserve(len.saturating_sub(self.slots.len()));
+    }
+
+    pub fn reserve_mask(&mut self, len: usize) {
+        self.positions.reserve_mask(len);
+    }
+
+    /// Closes the holes by moving the elements after them down, in position order, and frees the
+    /// memory left. `moved` gets the index of each element moved with its old and new position.
+    pub fn compact(&mut self, mut moved: impl FnMut(usize, usize, usize)) {
+        let mut to = 0;
+        for from in 0..self.slots.len() {
+            let Some((idx, _)) = self.slots[from] else {
+                continue;
+            };
+            if from != to {
+                self.slots.swap(from, to);
+                *self.positions.get_mut(idx as usize).unwrap() = to as u32;
+                moved(idx as usize, from, to);
+            }
+            to += 1;
+        }
+        self.slots.truncate(to);
+        self.free.clear();
+        self.positions.compact();
+        self.slots.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
+    /// The elements in position order, skipping the holes.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (usize, &mut T)> + '_ {
+        self.slots.iter_mut().flatten().map(|(idx, value)| (*idx as usize, value))
+    }
+
+    /// Same as [`StableSet::iter_mut`] along with the item of `extra` at the position of each
+    /// element, `extra` holding one item per position.
+    pub fn iter_with_mut<'a, U>(&'a mut self, extra: &'a mut [U]) -> impl Iterator<Item = (usize, &'a mut T, &'a mut U)> {
+        let slots = self.slots.iter_mut().zip(extra);
+        slots.filter_map(|(slot, extra)| slot.as_mut().map(|(idx, value)| (*idx as usize, value, extra)))
+    }
+
+    /// Takes every element out, in position order.
+    pub fn drain(&mut self) -> impl Iterator<Item = (usize, usize, T)> + '_ {
+        self.positions = BVec::new();
+        self.free.clear();
+        let slots = self.slots.drain(..).enumerate();
+        slots.filter_map(|(position, slot)| slot.map(|(idx, value)| (position, idx as usize, value)))
+    }
+}
+
+impl<T> Default for StableSet<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remove_moves_the_last_value() {
+        let mut set = SparseSet::new();
+        for idx in [7, 2, 40, 1500] {
+            set.insert(idx, idx * 10);
+        }
+        assert_eq!(set.values(), [70, 20, 400, 15000]);
+        assert_eq!(set.remove(2), Some(20));
+        assert_eq!(set.values(), [70, 15000, 400]);
+        assert_eq!(set.indices(), [7, 1500, 40]);
+        assert_eq!(set.position(1500), Some(1));
+        assert_eq!(set.get(1500), Some(&15000));
+        assert_eq!(set.remove(2), None);
+
+        set.swap(0, 2);
+        assert_eq!(set.values(), [400, 15000, 70]);
+        assert_eq!(set.position(7), Some(2));
+        assert_eq!(set.insert(40, 41), Some(400));
+        assert_eq!(set.mask().iter().collect::<Vec<_>>(), [7, 40, 1500]);
+    }
+
+    #[test]
+    fn stable_set_keeps_positions_until_compacted() {
+        let mut set = StableSet::new();
+        for idx in [7, 2, 40, 1500] {
+            set.insert(idx, idx * 10);
+        }
+        assert_eq!(set.remove(2), Some(20));
+        assert_eq!(set.remove(7), Some(70));
+        assert_eq!((set.position(40), set.position(1500)), (Some(2), Some(3)));
+        assert_eq!(set.iter_mut().map(|(idx, _)| idx).collect::<Vec<_>>(), [40, 1500]);
+        assert_eq!((set.len(), set.slot_count()), (2, 4));
+        // The last hole is filled first.
+        set.insert(3, 30);
+        assert_eq!(set.position(3), Some(0));
+
+        let mut moves = Vec::new();
+        set.compact(|idx, from, to| moves.push((idx, from, to)));
+        assert_eq!(moves, [(40, 2, 1), (1500, 3, 2)]);
+        assert_eq!((set.get(40), set.position(40)), (Some(&400), Some(1)));
+        assert_eq!((set.len(), set.slot_count()), (3, 3));
+    }
+}
@@ -0,0 +1,171 @@
+use alloc::vec::Vec;
+
+use super::{BMask, BVec, HashMap, CAPACITY};
+
+/// Values of external 64 bits keys stored at dense slots of a `BVec`, so that they iterate like
+/// the components. The slot of each key is hashed, the key of each slot is kept next to the values
+/// to give it back while iterating. Removed slots are reused lowest first.
+pub struct SparseMap<K, T> {
+    values: BVec<T>,
+    slots: HashMap<u64, u32>,
+    // The key of each slot, only meaningful for the slots of the mask.
+    keys: Vec<K>,
+}
+
+impl<K: Copy + Into<u64>, T> SparseMap<K, T> {
+    pub fn new() -> Self {
+        Self {
+            values: BVec::new(),
+            slots: HashMap::default(),
+            keys: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// The slot the value of `key` is stored at.
+    #[inline]
+    pub fn slot(&self, key: K) -> Option<usize> {
+        self.slots.get(&key.into()).map(|slot| *slot as usize)
+    }
+
+    pub fn contains_key(&self, key: K) -> bool {
+        self.slot(key).is_some()
+    }
+
+    #[inline]
+    pub fn get(&self, key: K) -> Option<&T> {
+        self.values.get(self.slot(key)?)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, key: K) -> Option<&mut T> {
+        let slot = self.slot(key)?;
+        self.values.get_mut(slot)
+    }
+
+    /// Stores `value` for `key` and returns the value that was there before if any. A new key
+    /// takes the lowest free slot.
+    ///
+    /// # Panics
+    ///
+    /// Panics if [`CAPACITY`] keys are already stored.
+    pub fn insert(&mut self, key: K, value: T) -> Option<T> {
+        if let Some(slot) = self.slot(key) {
+            return self.values.insert(slot, value);
+        }
+        let Ok((slot, _)) = self.values.insert_first_empty(value) else {
+            panic!("SparseMap is full, it holds at most {} keys", CAPACITY);
+        };
+        self.slots.insert(key.into(), slot as u32);
+        if slot == self.keys.len() {
+            self.keys.push(key);
+        } else {
+            self.keys[slot] = key;
+        }
+        None
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<T> {
+        let slot = self.slots.remove(&key.into())?;
+        self.values.remove(slot as usize)
+    }
+
+    /// The slots in use.
+    pub fn mask(&self) -> &BMask {
+        self.values.mask()
+    }
+
+    /// The key and value of each occupied slot, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (K, &T)> + '_ {
+        self.values.iter().map(|(slot, value)| (self.keys[slot], value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (K, &mut T)> + '_ {
+        let keys = &self.keys;
+        self.values.iter_mut().map(move |(slot, value)| (keys[slot], value))
+    }
+
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.slots.clear();
+        self.keys.clear();
+    }
+}
+
+impl<K: Copy + Into<u64>, T> Default for SparseMap<K, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap as StdHashMap;
+
+    use super::*;
+    use crate::testing::Rng;
+
+    // An asset id, the way the keys come from outside of the world.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    struct AssetId(u64);
+
+    impl From<AssetId> for u64 {
+        fn from(id: AssetId) -> u64 {
+            id.0
+        }
+    }
+
+    #[test]
+    fn random_operations_match_a_hash_map() {
+        let mut rng = Rng::new(0x2545_f491_4f6c_dd1d);
+        let mut map = SparseMap::new();
+        let mut reference = StdHashMap::new();
+        // Few keys spread over the whole range, so that they collide often.
+        let keys: Vec<AssetId> = (0..300).map(|_| AssetId(rng.next_u64())).collect();
+        for step in 0..20_000u32 {
+            let key = keys[rng.below(keys.len())];
+            match rng.below(3) {
+                0 => assert_eq!(map.remove(key), reference.remove(&key)),
+                _ => assert_eq!(map.insert(key, step), reference.insert(key, step)),
+            }
+            assert_eq!(map.get(key), reference.get(&key));
+            assert_eq!(map.len(), reference.len());
+        }
+        let mut pairs: Vec<(AssetId, u32)> = map.iter().map(|(key, value)| (key, *value)).collect();
+        let mut expected: Vec<(AssetId, u32)> = reference.into_iter().collect();
+        pairs.sort_by_key(|(key, _)| key.0);
+        expected.sort_by_key(|(key, _)| key.0);
+        // Each live pair exactly once.
+        assert_eq!(pairs, expected);
+        assert_eq!(map.mask().len(), pairs.len());
+    }
+
+    #[test]
+    fn removed_slots_are_reused() {
+        let mut map = SparseMap::new();
+        for id in [10u64, u64::MAX, 7, 1 << 40] {
+            map.insert(id, id);
+        }
+        assert_eq!([10, u64::MAX, 7, 1 << 40].map(|id| map.slot(id)), [Some(0), Some(1), Some(2), Some(3)]);
+        assert_eq!(map.remove(u64::MAX), Some(u64::MAX));
+        assert_eq!(map.remove(u64::MAX), None);
+        map.remove(10);
+        map.insert(99, 99);
+        map.insert(5, 5);
+        assert_eq!((map.slot(99), map.slot(5), map.slot(10)), (Some(0), Some(1), None));
+        for (key, value) in map.iter_mut() {
+            *value += key;
+        }
+        let pairs: Vec<(u64, u64)> = map.iter().map(|(key, value)| (key, *value)).collect();
+        assert_eq!(pairs, [(99, 198), (5, 10), (7, 14), (1 << 40, 1 << 41)]);
+        map.clear();
+        assert!(map.is_empty() && map.get(7).is_none());
+    }
+}
@@ -0,0 +1,260 @@
+//! Consistency checks of the internal bookkeeping of a world, for tests and debug builds.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::error::Error;
+use core::fmt;
+
+use crate::entity::Entity;
+use crate::hierarchy::{Children, Parent};
+use crate::World;
+
+/// A broken invariant found by [`World::validate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldInvariantError {
+    /// The entity the problem was found on, when there is one.
+    pub entity: Option<Entity>,
+    /// The name of the component or relation involved, when there is one.
+    pub component: Option<&'static str>,
+    pub description: String,
+}
+
+impl fmt::Display for WorldInvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(entity) = self.entity {
+            write!(f, "{}: ", entity)?;
+        }
+        if let Some(component) = self.component {
+            write!(f, "{}: ", component)?;
+        }
+        write!(f, "{}", self.description)
+    }
+}
+
+impl Error for WorldInvariantError {}
+
+#[derive(Default)]
+struct Report {
+    errors: Vec<WorldInvariantError>,
+}
+
+impl Report {
+    fn push(&mut self, entity: Option<Entity>, component: Option<&'static str>, description: String) {
+        self.errors.push(WorldInvariantError {
+            entity,
+            component,
+            description,
+        });
+    }
+}
+
+impl World {
+    /// Checks that the world agrees with itself and returns every violation found:
+    /// - live entities sit in their own slot with the generation the slot hands out, and no free
+    ///   index is alive,
+    /// - every stored component belongs to a live entity,
+    /// - the masks of the entities and of every storage have consistent layers,
+    /// - parents and children are alive and point back at each other,
+    /// - relations only link live entities,
+    /// - the members of each group are alive, in the same place in every grouped storage, and
+    ///   every entity having all the components of a group is a member.
+    ///
+    /// It walks everything the world owns, so it is meant for tests and debugging.
+    pub fn validate(&self) -> Result<(), Vec<WorldInvariantError>> {
+        let mut report = Report::default();
+        if let Err(description) = self.entities.mask().check() {
+            report.push(None, None, format!("entity mask: {}", description));
+        }
+        for (entity, description) in self.entities.check() {
+            report.push(Some(entity), None, description);
+        }
+        self.validate_storages(&mut report);
+        self.validate_hierarchy(&mut report);
+        for (entity, id, description) in self.check_groups() {
+            let name = self.components.info(id).map(|info| info.name());
+            report.push(entity, name, String::from(description));
+        }
+        for (relation, entity) in self.relations.linked() {
+            if !self.is_alive(entity) {
+                report.push(Some(entity), Some(relation), String::from("linked while not alive"));
+            }
+        }
+        if report.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(report.errors)
+        }
+    }
+
+    fn validate_storages(&self, report: &mut Report) {
+        for info in self.components.iter() {
+            let mask = self.storages.get(info.id()).mask();
+            if let Err(description) = mask.check() {
+                report.push(None, Some(info.name()), format!("mask: {}", description));
+            }
+            for index in mask.iter() {
+                if self.entities.get(index as u32).is_none() {
+                    report.push(None, Some(info.name()), format!("stored at index {} without a live entity", index));
+                }
+            }
+        }
+    }
+
+    fn validate_hierarchy(&self, report: &mut Report) {
+        let parent_name = Some(core::any::type_name::<Parent>());
+        let children_name = Some(core::any::type_name::<Children>());
+        for entity in self.entities.iter() {
+            if let Some(Parent(parent)) = self.get_component::<Parent>(entity) {
+                if !self.is_alive(*parent) {
+                    report.push(Some(entity), parent_name, format!("parent {} is not alive", parent));
+                } else if !self.children(*parent).contains(&entity) {
+                    report.push(Some(entity), parent_name, format!("missing from the children of {}", parent));
+                }
+            }
+            let Some(Children(children)) = self.get_component::<Children>(entity) else {
+                continue;
+            };
+            if children.is_empty() {
+                report.push(Some(entity), children_name, String::from("empty children are kept"));
+            }
+            for (i, child) in children.iter().enumerate() {
+                if children[..i].contains(child) {
+                    report.push(Some(entity), children_name, format!("child {} is listed twice", child));
+                } else if !self.is_alive(*child) {
+                    report.push(Some(entity), children_name, format!("child {} is not alive", child));
+                } else if self.parent(*child) != Some(entity) {
+                    report.push(Some(entity), children_name, format!("child {} has another parent", child));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::component::Component;
+    use crate::relation::Relation;
+
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Health(u32);
+    #[derive(Debug, Clone, PartialEq, Component)]
+    struct Frozen;
+    struct Follows;
+    impl Relation for Follows {}
+
+    // A xorshift generator so that failures can be replayed from the seed.
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+
+        fn pick(&mut self, entities: &[Entity]) -> Option<Entity> {
+            (!entities.is_empty()).then(|| entities[self.below(entities.len())])
+        }
+    }
+
+    fn is_ancestor(world: &World, ancestor: Entity, mut entity: Entity) -> bool {
+        while let Some(parent) = world.parent(entity) {
+            if parent == ancestor {
+                return true;
+            }
+            entity = parent;
+        }
+        false
+    }
+
+    #[test]
+    fn random_operations_keep_the_world_valid() {
+        for seed in 1..=8u64 {
+            let mut rng = Rng(seed.wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            let mut world = World::new();
+            world.register_clone::<Health>();
+            world.register_clone::<Frozen>();
+            for step in 0..600 {
+                let alive: Vec<Entity> = world.entities.iter().collect();
+                let a = rng.pick(&alive);
+                let b = rng.pick(&alive);
+                match (rng.below(10), a, b) {
+                    (0 | 1, _, _) => {
+                        world.spawn_entity();
+                    }
+                    (2, Some(a), _) => {
+                        world.despawn_recursive(a);
+                    }
+                    (3, Some(a), _) => {
+                        world.add_component(a, Health(step));
+                    }
+                    (4, Some(a), _) => {
+                        world.remove_component::<Health>(a);
+                        world.add_component(a, Frozen);
+                    }
+                    (5, Some(a), Some(b)) if a != b && !is_ancestor(&world, a, b) => {
+                        world.set_parent(a, b);
+                    }
+                    (6, Some(a), _) => {
+                        world.remove_parent(a);
+                    }
+                    (7, Some(a), Some(b)) => {
+                        world.relate::<Follows>(a, b);
+                    }
+                    (8, Some(a), _) => {
+                        world.duplicate_recursive(a);
+                    }
+                    (9, Some(a), _) if rng.below(4) == 0 => {
+                        world.compact_all();
+                        world.remove_component::<Frozen>(a);
+                    }
+                    _ => {}
+                }
+                if let Err(errors) = world.validate() {
+                    panic!("seed {} step {}: {:#?}", seed, step, errors);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn broken_hierarchy_is_reported() {
+        let mut world = World::new();
+        let parent = *world.spawn_entity();
+        let child = *world.spawn_entity();
+        let other = *world.spawn_entity();
+        world.set_parent(child, parent);
+        world.relate::<Follows>(other, parent);
+        assert_eq!(world.validate(), Ok(()));
+
+        // Bypasses the hierarchy bookkeeping by editing the components directly.
+        world.add_component(child, Parent(other));
+        world.get_component_mut::<Children>(parent).unwrap().0.push(child);
+        let errors = world.validate().unwrap_err();
+        let descriptions: Vec<String> = errors.iter().map(|e| e.description.clone()).collect();
+        assert_eq!(
+            descriptions,
+            [
+                format!("child {} has another parent", child),
+                format!("child {} is listed twice", child),
+                format!("missing from the children of {}", other),
+            ]
+        );
+        assert_eq!(errors[0].entity, Some(parent));
+        assert_eq!(errors[0].component, Some(core::any::type_name::<Children>()));
+        assert_eq!(errors[2].entity, Some(child));
+
+        world.remove_component::<Parent>(child);
+        world.remove_component::<Children>(parent);
+        world.add_component(other, Health(1));
+        world.entities.despawn_entity(other);
+        let errors = world.validate().unwrap_err();
+        assert_eq!(errors.len(), 2, "{:#?}", errors);
+        assert_eq!(errors[0].component, Some(core::any::type_name::<Health>()));
+        assert_eq!(errors[0].description, format!("stored at index {} without a live entity", other.index()));
+        let relation = core::any::type_name::<Follows>();
+        assert_eq!(errors[1].to_string(), format!("{}: {}: linked while not alive", other, relation));
+    }
+}
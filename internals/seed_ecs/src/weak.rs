@@ -0,0 +1,123 @@
+//! Counting the weak references to entities, to find the ones kept after their entity is gone.
+
+use alloc::vec::Vec;
+
+use crate::entity::{Entity, EntityMapper, EntityWeak, MapEntities};
+use crate::utils::HashMap;
+use crate::World;
+
+/// The weak references made with [`World::downgrade_tracked`] and not released yet, by entity.
+///
+/// Despawning an entity moves its count to [`WeakRefs::dangling`], the references that nothing
+/// can upgrade anymore and that whoever holds them should have dropped.
+#[derive(Debug, Default)]
+pub struct WeakRefs {
+    counts: HashMap<Entity, usize>,
+    dangling: Vec<(Entity, usize)>,
+}
+
+impl WeakRefs {
+    /// The references to the live entity not released yet.
+    pub fn count(&self, entity: Entity) -> usize {
+        self.counts.get(&entity).copied().unwrap_or(0)
+    }
+
+    /// The despawned entities that still had references, with how many, in despawn order.
+    pub fn dangling(&self) -> &[(Entity, usize)] {
+        &self.dangling
+    }
+
+    pub fn dangling_count(&self) -> usize {
+        self.dangling.iter().map(|(_, count)| count).sum()
+    }
+
+    pub(crate) fn despawned(&mut self, entity: Entity) {
+        if let Some(count) = self.counts.remove(&entity) {
+            self.dangling.push((entity, count));
+        }
+    }
+
+    pub(crate) fn map_entities(&mut self, mapper: &mut EntityMapper) {
+        self.counts = core::mem::take(&mut self.counts)
+            .into_iter()
+            .map(|(mut entity, count)| {
+                entity.map_entities(mapper);
+                (entity, count)
+            })
+            .collect();
+    }
+}
+
+impl World {
+    /// Same as [`Entity::downgrade`], counting the reference in [`World::weak_refs`] until it is
+    /// given to [`World::release_weak`].
+    pub fn downgrade_tracked(&mut self, entity: Entity) -> EntityWeak {
+        if self.is_alive(entity) {
+            *self.weak_refs.counts.entry(entity).or_default() += 1;
+        }
+        entity.downgrade()
+    }
+
+    /// Stops counting a reference made by [`World::downgrade_tracked`], dangling or not.
+    pub fn release_weak(&mut self, weak: EntityWeak) {
+        let entity = weak.entity();
+        let refs = &mut self.weak_refs;
+        if let Some(count) = refs.counts.get_mut(&entity) {
+            *count -= 1;
+            if *count == 0 {
+                refs.counts.remove(&entity);
+            }
+        } else if let Some(position) = refs.dangling.iter().position(|(dead, _)| *dead == entity) {
+            refs.dangling[position].1 -= 1;
+            if refs.dangling[position].1 == 0 {
+                refs.dangling.remove(position);
+            }
+        }
+    }
+
+    pub fn weak_refs(&self) -> &WeakRefs {
+        &self.weak_refs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upgrade_fails_once_despawned() {
+        let mut world = World::new();
+        let npc = *world.spawn_entity();
+        let target = npc.downgrade();
+        assert_eq!(target.upgrade(&world), Some(npc));
+        assert!(world.is_alive(target));
+        world.despawn_entity(npc);
+        // Another entity takes the index, under a new generation.
+        let other = *world.spawn_entity();
+        assert_eq!(other.index(), npc.index());
+        assert_eq!(target.upgrade(&world), None);
+        assert!(!world.is_alive(target));
+        assert_eq!(EntityWeak::from_bits(target.to_bits()), Some(target));
+    }
+
+    #[test]
+    fn outstanding_references_are_counted() {
+        let mut world = World::new();
+        let npc = *world.spawn_entity();
+        let boss = *world.spawn_entity();
+        let quest = world.downgrade_tracked(npc);
+        let journal = world.downgrade_tracked(npc);
+        let bounty = world.downgrade_tracked(boss);
+        assert_eq!(world.weak_refs().count(npc), 2);
+        world.release_weak(journal);
+        world.despawn_entity(npc);
+        world.despawn_entity(boss);
+        assert_eq!(world.weak_refs().count(npc), 0);
+        assert_eq!(world.weak_refs().dangling(), [(npc, 1), (boss, 1)]);
+        world.release_weak(quest);
+        assert_eq!(world.weak_refs().dangling(), [(boss, 1)]);
+        assert_eq!(world.weak_refs().dangling_count(), 1);
+        world.release_weak(bounty);
+        assert_eq!(world.weak_refs().dangling_count(), 0);
+    }
+}
@@ -0,0 +1,61 @@
+//! Builds `seed_ecs` without its `std` feature, `cargo test -p seed_ecs_alloc` runs the basic
+//! world operations with only `core` and `alloc`.
+//!
+//! Features are unified across the workspace, so this has to be tested on its own to really
+//! leave `std` out.
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use seed_ecs::component::Component;
+use seed_ecs::entity::Entity;
+use seed_ecs::query::Without;
+use seed_ecs::World;
+
+#[derive(Component)]
+pub struct Position(pub i32);
+#[derive(Component)]
+pub struct Velocity(pub i32);
+#[derive(Component)]
+pub struct Frozen;
+
+/// Moves every entity that isn't frozen and returns the ones that moved.
+pub fn step(world: &mut World) -> Vec<Entity> {
+    let mut query = world.query_filtered::<(Entity, &mut Position, &Velocity), Without<Frozen>>();
+    let mut moved = Vec::new();
+    for (entity, mut position, velocity) in query.iter_mut(world) {
+        position.0 += velocity.0;
+        moved.push(entity);
+    }
+    moved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use seed_ecs::query::With;
+
+    #[test]
+    fn spawn_insert_query() {
+        let mut world = World::new();
+        let entities: Vec<Entity> = (0..4).map(|_| *world.spawn_entity()).collect();
+        for (i, entity) in entities.iter().enumerate() {
+            world.add_component(*entity, Position(0));
+            world.add_component(*entity, Velocity(i as i32));
+        }
+        world.add_component(entities[1], Frozen);
+        world.insert_resource(10u32);
+
+        assert_eq!(step(&mut world), [entities[0], entities[2], entities[3]]);
+        assert_eq!(world.get_component::<Position>(entities[3]).unwrap().0, 3);
+        assert_eq!(world.get_component::<Position>(entities[1]).unwrap().0, 0);
+
+        world.despawn_entity(entities[2]);
+        let frozen = world.query_filtered::<Entity, With<Frozen>>();
+        assert_eq!(frozen.iter(&world).count(), 1);
+        assert_eq!(step(&mut world).len(), 2);
+        assert_eq!(world.get_resource::<u32>(), Some(&10));
+    }
+}
@@ -0,0 +1,296 @@
+//! The derive macros of `seed_ecs`, use them through its re-exports.
+
+use proc_macro::TokenStream;
+use proc_macro2::{Group, Ident, TokenTree};
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, Generics, Index, Lit, Meta, NestedMeta};
+
+/// Implements `seed_ecs::component::Component`.
+///
+/// The `component` attribute picks the storage, `#[component(storage = "dense")]` (the default)
+/// or `#[component(storage = "sparse")]`, and `#[component(immutable)]` forbids the mutable
+/// borrows. With the `loose-components` feature of `seed_ecs` the derive expands to nothing and
+/// the attribute is ignored.
+#[proc_macro_derive(Component, attributes(component))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match component(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Implements `seed_ecs::Bundle` for a struct. Each field is a component, or a nested bundle
+/// flattened into this one with `#[bundle]`.
+#[proc_macro_derive(Bundle, attributes(bundle))]
+pub fn derive_bundle(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match bundle(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Implements `seed_ecs::system::SystemParam` for a struct whose fields are system parameters,
+/// fetched one after the other like the parameters of a function. The struct lifetimes must be
+/// named `'w` for the world and `'s` for the state, like `Query<'w, 's, Q>`.
+#[proc_macro_derive(SystemParam)]
+pub fn derive_system_param(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match system_param(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn component(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let mut storage = None;
+    let mut mutable = true;
+    for attribute in input.attrs.iter().filter(|attribute| attribute.path.is_ident("component")) {
+        let Meta::List(list) = attribute.parse_meta()? else {
+            return Err(Error::new_spanned(attribute, "expected `#[component(...)]`"));
+        };
+        for nested in list.nested {
+            match nested {
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("storage") => {
+                    let Lit::Str(kind) = &pair.lit else {
+                        return Err(Error::new_spanned(&pair.lit, "expected a string"));
+                    };
+                    storage = Some(match kind.value().as_str() {
+                        "dense" => quote!(Dense),
+                        "sparse" => quote!(SparseSet),
+                        _ => return Err(Error::new_spanned(kind, "expected `\"dense\"` or `\"sparse\"`")),
+                    });
+                }
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("immutable") => mutable = false,
+                other => return Err(Error::new_spanned(other, "expected `storage = \"...\"` or `immutable`")),
+            }
+        }
+    }
+
+    let generics = thread_safe(&input.generics);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let storage = storage.map(|kind| {
+        quote!(const STORAGE: ::seed_ecs::component::StorageKind = ::seed_ecs::component::StorageKind::#kind;)
+    });
+    let mutable = (!mutable).then(|| quote!(const MUTABLE: bool = false;));
+    // Dropped by `seed_ecs` with the `loose-components` feature, its blanket impl covers the type.
+    Ok(quote! {
+        ::seed_ecs::__private::impl_component! {
+            impl #impl_generics ::seed_ecs::component::Component for #name #type_generics #where_clause {
+                #storage
+                #mutable
+            }
+        }
+    })
+}
+
+fn bundle(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "only structs can derive `Bundle`"));
+    };
+    // Each field is a bundle: the components are handled as a tuple of one.
+    let mut bundles = Vec::new();
+    let mut takes = Vec::new();
+    let mut components: Vec<&syn::Type> = Vec::new();
+    for (i, field) in data.fields.iter().enumerate() {
+        let member = match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(i);
+                quote!(#index)
+            }
+        };
+        let ty = &field.ty;
+        let nested = field.attrs.iter().any(|attribute| attribute.path.is_ident("bundle"));
+        let binding = quote::format_ident!("field_{}", i);
+        if nested {
+            bundles.push((quote!(#ty), quote!(self.#member)));
+            takes.push((member, quote!(#binding?)));
+        } else {
+            if let Some(first) = components.iter().find(|other| quote!(#other).to_string() == quote!(#ty).to_string()) {
+                let message = format!("the bundle holds `{}` twice", quote!(#first));
+                return Err(Error::new_spanned(ty, message));
+            }
+            components.push(ty);
+            bundles.push((quote!((#ty,)), quote!((self.#member,))));
+            takes.push((member, quote!(#binding?.0)));
+        }
+    }
+
+    let generics = thread_safe(&input.generics);
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let bundle = quote!(::seed_ecs::Bundle);
+    let world = quote!(::seed_ecs::World);
+    let id = quote!(::seed_ecs::component::ComponentId);
+    let vec = quote!(::seed_ecs::__private::Vec);
+    let types: Vec<_> = bundles.iter().map(|(ty, _)| ty).collect();
+    let values: Vec<_> = bundles.iter().map(|(_, value)| value).collect();
+    let indices: Vec<_> = (0..bundles.len()).map(Index::from).collect();
+    let bindings: Vec<_> = (0..bundles.len()).map(|i| quote::format_ident!("field_{}", i)).collect();
+    let fields = takes.iter().map(|(member, value)| quote!(#member: #value));
+    let constructed = match &data.fields {
+        Fields::Unit => quote!(#name),
+        _ => quote!(#name { #(#fields),* }),
+    };
+    Ok(quote! {
+        impl #impl_generics #bundle for #name #type_generics #where_clause {
+            type Columns = (#(<#types as #bundle>::Columns,)*);
+
+            const COUNT: usize = 0 #(+ <#types as #bundle>::COUNT)*;
+
+            fn register(world: &mut #world) -> #vec<#id> {
+                let mut ids = #vec::with_capacity(Self::COUNT);
+                #(ids.extend(<#types as #bundle>::register(world));)*
+                ids
+            }
+
+            fn columns(capacity: usize) -> Self::Columns {
+                (#(<#types as #bundle>::columns(capacity),)*)
+            }
+
+            fn push(self, index: usize, columns: &mut Self::Columns) {
+                #(#bundle::push(#values, index, &mut columns.#indices);)*
+            }
+
+            fn insert_columns(world: &mut #world, ids: &[#id], columns: Self::Columns) {
+                let mut start = 0;
+                #(
+                    let end = start + <#types as #bundle>::COUNT;
+                    <#types as #bundle>::insert_columns(world, &ids[start..end], columns.#indices);
+                    start = end;
+                )*
+            }
+
+            fn write(self, world: &mut #world, ids: &[#id], index: usize, added: &mut #vec<#id>) {
+                let mut start = 0;
+                #(
+                    let end = start + <#types as #bundle>::COUNT;
+                    #bundle::write(#values, world, &ids[start..end], index, added);
+                    start = end;
+                )*
+            }
+
+            fn take(world: &mut #world, ids: &[#id], index: usize) -> ::core::option::Option<Self> {
+                let mut start = 0;
+                #(
+                    let end = start + <#types as #bundle>::COUNT;
+                    let #bindings = <#types as #bundle>::take(world, &ids[start..end], index);
+                    start = end;
+                )*
+                ::core::option::Option::Some(#constructed)
+            }
+        }
+    })
+}
+
+/// The generics with the type parameters bound like the components, `Send + Sync + 'static`.
+fn thread_safe(generics: &Generics) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(::core::marker::Send));
+        param.bounds.push(parse_quote!(::core::marker::Sync));
+        param.bounds.push(parse_quote!('static));
+    }
+    generics
+}
+
+fn system_param(input: &DeriveInput) -> Result<proc_macro2::TokenStream, Error> {
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&input.ident, "only structs can derive `SystemParam`"));
+    };
+    for lifetime in input.generics.lifetimes() {
+        let name = lifetime.lifetime.ident.to_string();
+        if name != "w" && name != "s" {
+            return Err(Error::new_spanned(lifetime, "expected the lifetimes `'w` and `'s`"));
+        }
+    }
+    let param = quote!(::seed_ecs::system::SystemParam);
+    // The fields with the lifetimes of the struct as `'static`, and as those of a run.
+    let statics: Vec<_> = data.fields.iter().map(|field| {
+        let ty = &field.ty;
+        rename_lifetimes(quote!(#ty), "static", "static")
+    }).collect();
+    let members: Vec<_> = data
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| match &field.ident {
+            Some(ident) => quote!(#ident),
+            None => {
+                let index = Index::from(i);
+                quote!(#index)
+            }
+        })
+        .collect();
+    let bindings: Vec<_> = (0..members.len()).map(|i| quote::format_ident!("field_{}", i)).collect();
+
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    if generics.type_params().next().is_some() {
+        let where_clause = generics.make_where_clause();
+        for ty in &statics {
+            where_clause.predicates.push(parse_quote!(#ty: #param));
+        }
+    }
+    let (impl_generics, type_generics, where_clause) = generics.split_for_impl();
+    let item_generics = rename_lifetimes(quote!(#type_generics), "__w", "__s");
+    let constructed = match &data.fields {
+        Fields::Unit => quote!(#name),
+        _ => quote!(#name { #(#members: <#statics as #param>::get_param(#bindings, meta, world),)* }),
+    };
+    Ok(quote! {
+        unsafe impl #impl_generics #param for #name #type_generics #where_clause {
+            type State = (#(<#statics as #param>::State,)*);
+            type Item<'__w, '__s> = #name #item_generics;
+
+            fn init_state(world: &mut ::seed_ecs::World, meta: &mut ::seed_ecs::system::SystemMeta) -> Self::State {
+                (#(<#statics as #param>::init_state(world, meta),)*)
+            }
+
+            unsafe fn get_param<'__w, '__s>(
+                state: &'__s mut Self::State,
+                meta: &::seed_ecs::system::SystemMeta,
+                world: ::seed_ecs::UnsafeWorldCell<'__w>,
+            ) -> Self::Item<'__w, '__s> {
+                let (#(#bindings,)*) = state;
+                #constructed
+            }
+
+            fn apply(state: &mut Self::State, world: &mut ::seed_ecs::World) {
+                let (#(#bindings,)*) = state;
+                #(<#statics as #param>::apply(#bindings, world);)*
+            }
+
+            fn discard(state: &mut Self::State) {
+                let (#(#bindings,)*) = state;
+                #(<#statics as #param>::discard(#bindings);)*
+            }
+        }
+    })
+}
+
+/// Renames the lifetimes `'w` and `'s` of the tokens.
+fn rename_lifetimes(tokens: proc_macro2::TokenStream, w: &str, s: &str) -> proc_macro2::TokenStream {
+    let mut renamed = Vec::new();
+    let mut after_quote = false;
+    for tree in tokens {
+        let tree = match tree {
+            TokenTree::Ident(ident) if after_quote && (ident == "w" || ident == "s") => {
+                let name = if ident == "w" { w } else { s };
+                TokenTree::Ident(Ident::new(name, ident.span()))
+            }
+            TokenTree::Group(group) => {
+                let mut renamed = Group::new(group.delimiter(), rename_lifetimes(group.stream(), w, s));
+                renamed.set_span(group.span());
+                TokenTree::Group(renamed)
+            }
+            other => other,
+        };
+        after_quote = matches!(&tree, TokenTree::Punct(punct) if punct.as_char() == '\'');
+        renamed.push(tree);
+    }
+    renamed.into_iter().collect()
+}
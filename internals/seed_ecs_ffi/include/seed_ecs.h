@@ -0,0 +1,43 @@
+/* C interface of seed_ecs, see internals/seed_ecs_ffi/src/lib.rs for the details. */
+#ifndef SEED_ECS_H
+#define SEED_ECS_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct SeedWorld SeedWorld;
+
+typedef enum SeedResult {
+    SEED_OK = 0,
+    SEED_NULL_POINTER = -1,
+    SEED_DEAD_ENTITY = -2,
+    SEED_UNKNOWN_COMPONENT = -3,
+    SEED_SIZE_MISMATCH = -4,
+    SEED_INVALID_DESCRIPTOR = -5,
+    SEED_MISSING_COMPONENT = -6,
+    SEED_PANIC = -7,
+} SeedResult;
+
+typedef void (*SeedDropFn)(void *value);
+
+SeedWorld *seed_world_new(void);
+void seed_world_free(SeedWorld *world);
+
+SeedResult seed_world_spawn(SeedWorld *world, uint64_t *out_entity);
+SeedResult seed_world_despawn(SeedWorld *world, uint64_t entity);
+
+SeedResult seed_world_register_component(SeedWorld *world, const char *name, size_t size, size_t align,
+                                         SeedDropFn drop_fn, size_t *out_id);
+SeedResult seed_world_insert_component(SeedWorld *world, uint64_t entity, size_t component_id,
+                                       const void *value, size_t size);
+SeedResult seed_world_get_component(SeedWorld *world, uint64_t entity, size_t component_id, void **out_value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,307 @@
+//! A C interface to the basic operations of a [`World`], for host engines written in other
+//! languages. `include/seed_ecs.h` declares the functions for C and C++.
+//!
+//! Entities cross the boundary as the `u64` of [`Entity::to_bits`] and components as the index of
+//! their [`ComponentId`]. Components registered from C are stored as raw bytes and dropped with
+//! the function given at registration. No function unwinds into the caller: panics are caught and
+//! reported as [`SeedResult::Panic`].
+
+use std::alloc::Layout;
+use std::ffi::{c_char, c_void, CStr};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr::{self, NonNull};
+
+use seed_ecs::component::{ComponentDescriptor, ComponentId, DropFn};
+use seed_ecs::entity::Entity;
+use seed_ecs::World;
+
+/// What the functions return, anything but `Ok` means nothing happened.
+#[repr(i32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeedResult {
+    Ok = 0,
+    /// A pointer argument was null.
+    NullPointer = -1,
+    /// The entity is not alive, or its bits are not an entity at all.
+    DeadEntity = -2,
+    /// The world has no component with that id.
+    UnknownComponent = -3,
+    /// The size passed doesn't match the size the component was registered with.
+    SizeMismatch = -4,
+    /// The name is not UTF-8 or the size and alignment are not a valid layout.
+    InvalidDescriptor = -5,
+    /// The entity doesn't have the component.
+    MissingComponent = -6,
+    /// Something went wrong inside the world, it should not be used anymore.
+    Panic = -7,
+}
+
+fn catch(f: impl FnOnce() -> SeedResult) -> SeedResult {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(SeedResult::Panic)
+}
+
+unsafe fn world_mut<'a>(world: *mut World) -> Result<&'a mut World, SeedResult> {
+    world.as_mut().ok_or(SeedResult::NullPointer)
+}
+
+fn alive(world: &World, bits: u64) -> Result<Entity, SeedResult> {
+    Entity::from_bits(bits).filter(|entity| world.is_alive(*entity)).ok_or(SeedResult::DeadEntity)
+}
+
+fn component(world: &World, id: usize) -> Result<ComponentId, SeedResult> {
+    let id = ComponentId::new(id);
+    world.components().info(id).map(|_| id).ok_or(SeedResult::UnknownComponent)
+}
+
+fn result(f: impl FnOnce() -> Result<(), SeedResult>) -> SeedResult {
+    catch(|| f().err().unwrap_or(SeedResult::Ok))
+}
+
+/// Creates an empty world, null if it couldn't be created. Free it with [`seed_world_free`].
+#[no_mangle]
+pub extern "C" fn seed_world_new() -> *mut World {
+    panic::catch_unwind(|| Box::into_raw(Box::new(World::new()))).unwrap_or(ptr::null_mut())
+}
+
+/// Drops the world and every component in it, does nothing if `world` is null.
+///
+/// # Safety
+///
+/// `world` must come from [`seed_world_new`] and not be freed already.
+#[no_mangle]
+pub unsafe extern "C" fn seed_world_free(world: *mut World) {
+    if !world.is_null() {
+        // A panicking drop function leaks the rest of the world rather than unwinding.
+        let _ = panic::catch_unwind(AssertUnwindSafe(|| drop(Box::from_raw(world))));
+    }
+}
+
+/// Spawns an entity and writes its bits to `out_entity`.
+///
+/// # Safety
+///
+/// `world` must be a live world and `out_entity` writable.
+#[no_mangle]
+pub unsafe extern "C" fn seed_world_spawn(world: *mut World, out_entity: *mut u64) -> SeedResult {
+    result(|| {
+        let world = world_mut(world)?;
+        let out_entity = out_entity.as_mut().ok_or(SeedResult::NullPointer)?;
+        *out_entity = world.spawn_entity().to_bits();
+        Ok(())
+    })
+}
+
+/// Despawns the entity and drops its components.
+///
+/// # Safety
+///
+/// `world` must be a live world.
+#[no_mangle]
+pub unsafe extern "C" fn seed_world_despawn(world: *mut World, entity: u64) -> SeedResult {
+    result(|| {
+        let world = world_mut(world)?;
+        let entity = alive(world, entity)?;
+        world.despawn_entity(entity);
+        Ok(())
+    })
+}
+
+/// Registers a component of `size` bytes aligned on `align` and writes its id to `out_id`.
+/// `drop_fn`, which may be null, is called on every value the world drops.
+///
+/// Each call registers a new component, even with a name that was already used.
+///
+/// # Safety
+///
+/// `world` must be a live world, `name` a nul terminated string and `out_id` writable.
+#[no_mangle]
+pub unsafe extern "C" fn seed_world_register_component(
+    world: *mut World,
+    name: *const c_char,
+    size: usize,
+    align: usize,
+    drop_fn: Option<DropFn>,
+    out_id: *mut usize,
+) -> SeedResult {
+    result(|| {
+        let world = world_mut(world)?;
+        if name.is_null() || out_id.is_null() {
+            return Err(SeedResult::NullPointer);
+        }
+        let name = CStr::from_ptr(name).to_str().map_err(|_| SeedResult::InvalidDescriptor)?;
+        let layout = Layout::from_size_align(size, align).map_err(|_| SeedResult::InvalidDescriptor)?;
+        // Component names are static, the few registered from C live as long as the program.
+        let mut descriptor = ComponentDescriptor::new(Box::leak(name.into()), layout);
+        if let Some(drop_fn) = drop_fn {
+            descriptor = descriptor.with_drop(drop_fn);
+        }
+        *out_id = world.register_component_with_descriptor(descriptor).index();
+        Ok(())
+    })
+}
+
+/// Moves the `size` bytes at `value` into the component of the entity, dropping the previous
+/// value. The world owns the value afterwards, the caller must not drop it.
+///
+/// # Safety
+///
+/// `world` must be a live world and `value` must point to a valid value of the component.
+#[no_mangle]
+pub unsafe extern "C" fn seed_world_insert_component(
+    world: *mut World,
+    entity: u64,
+    component_id: usize,
+    value: *const c_void,
+    size: usize,
+) -> SeedResult {
+    result(|| {
+        let world = world_mut(world)?;
+        let entity = alive(world, entity)?;
+        let id = component(world, component_id)?;
+        let value = NonNull::new(value as *mut u8).ok_or(SeedResult::NullPointer)?;
+        if world.components().info(id).unwrap().layout().size() != size {
+            return Err(SeedResult::SizeMismatch);
+        }
+        world.insert_by_id(entity, id, value);
+        Ok(())
+    })
+}
+
+/// Writes a pointer to the component of the entity to `out_value`. The pointer stays valid until
+/// the component is removed or the world changes structurally.
+///
+/// # Safety
+///
+/// `world` must be a live world and `out_value` writable.
+#[no_mangle]
+pub unsafe extern "C" fn seed_world_get_component(
+    world: *mut World,
+    entity: u64,
+    component_id: usize,
+    out_value: *mut *mut c_void,
+) -> SeedResult {
+    result(|| {
+        let world = world_mut(world)?;
+        let out_value = out_value.as_mut().ok_or(SeedResult::NullPointer)?;
+        let entity = alive(world, entity)?;
+        let id = component(world, component_id)?;
+        let value = world.get_mut_by_id(entity, id).ok_or(SeedResult::MissingComponent)?;
+        *out_value = value.as_ptr().cast();
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    struct Transform {
+        x: f32,
+        y: f32,
+        id: u64,
+    }
+
+    static DROPPED_IDS: AtomicU64 = AtomicU64::new(0);
+
+    unsafe extern "C" fn drop_transform(value: *mut c_void) {
+        DROPPED_IDS.fetch_add((*(value as *const Transform)).id, Ordering::Relaxed);
+    }
+
+    unsafe fn register(world: *mut World) -> usize {
+        let name = CString::new("Transform").unwrap();
+        let mut id = usize::MAX;
+        let layout = Layout::new::<Transform>();
+        let code = seed_world_register_component(
+            world,
+            name.as_ptr(),
+            layout.size(),
+            layout.align(),
+            Some(drop_transform),
+            &mut id,
+        );
+        assert_eq!(code, SeedResult::Ok);
+        id
+    }
+
+    unsafe fn spawn(world: *mut World) -> u64 {
+        let mut entity = 0;
+        assert_eq!(seed_world_spawn(world, &mut entity), SeedResult::Ok);
+        entity
+    }
+
+    unsafe fn insert(world: *mut World, entity: u64, id: usize, value: Transform) -> SeedResult {
+        let size = std::mem::size_of::<Transform>();
+        seed_world_insert_component(world, entity, id, &value as *const Transform as *const c_void, size)
+    }
+
+    #[test]
+    fn drive_a_world_like_a_host() {
+        unsafe {
+            let world = seed_world_new();
+            let transform = register(world);
+            let a = spawn(world);
+            let b = spawn(world);
+            assert_eq!(insert(world, a, transform, Transform { x: 1.0, y: 2.0, id: 1 }), SeedResult::Ok);
+            assert_eq!(insert(world, b, transform, Transform { x: 0.0, y: 0.0, id: 10 }), SeedResult::Ok);
+
+            let mut value = ptr::null_mut();
+            assert_eq!(seed_world_get_component(world, a, transform, &mut value), SeedResult::Ok);
+            let value = &mut *(value as *mut Transform);
+            assert_eq!(*value, Transform { x: 1.0, y: 2.0, id: 1 });
+            value.x = 5.0;
+            let mut again = ptr::null_mut();
+            seed_world_get_component(world, a, transform, &mut again);
+            assert_eq!((*(again as *const Transform)).x, 5.0);
+
+            // Replacing and despawning drop the old values, freeing the world drops the rest.
+            assert_eq!(insert(world, a, transform, Transform { x: 0.0, y: 0.0, id: 100 }), SeedResult::Ok);
+            assert_eq!(DROPPED_IDS.load(Ordering::Relaxed), 1);
+            assert_eq!(seed_world_despawn(world, b), SeedResult::Ok);
+            assert_eq!(DROPPED_IDS.load(Ordering::Relaxed), 11);
+            seed_world_free(world);
+            assert_eq!(DROPPED_IDS.load(Ordering::Relaxed), 111);
+        }
+    }
+
+    #[test]
+    fn error_codes() {
+        unsafe {
+            let world = seed_world_new();
+            let transform = register(world);
+            let entity = spawn(world);
+            let value = Transform { x: 0.0, y: 0.0, id: 0 };
+            let mut out = ptr::null_mut();
+
+            assert_eq!(insert(world, entity, transform + 1, value), SeedResult::UnknownComponent);
+            assert_eq!(seed_world_get_component(world, entity, 42, &mut out), SeedResult::UnknownComponent);
+            assert_eq!(seed_world_get_component(world, entity, transform, &mut out), SeedResult::MissingComponent);
+            let wrong_size = seed_world_insert_component(world, entity, transform, &value as *const _ as *const c_void, 4);
+            assert_eq!(wrong_size, SeedResult::SizeMismatch);
+            assert_eq!(seed_world_insert_component(world, entity, transform, ptr::null(), 16), SeedResult::NullPointer);
+
+            assert_eq!(seed_world_despawn(world, entity), SeedResult::Ok);
+            assert_eq!(seed_world_despawn(world, entity), SeedResult::DeadEntity);
+            assert_eq!(insert(world, entity, transform, value), SeedResult::DeadEntity);
+            assert_eq!(seed_world_get_component(world, entity, transform, &mut out), SeedResult::DeadEntity);
+            // Generation 0 is never an entity.
+            assert_eq!(seed_world_despawn(world, 3), SeedResult::DeadEntity);
+
+            let name = CString::new("Bad").unwrap();
+            let mut id = 0;
+            let bad_align = seed_world_register_component(world, name.as_ptr(), 4, 3, None, &mut id);
+            assert_eq!(bad_align, SeedResult::InvalidDescriptor);
+            assert_eq!(seed_world_spawn(ptr::null_mut(), &mut 0), SeedResult::NullPointer);
+            assert_eq!(seed_world_spawn(world, ptr::null_mut()), SeedResult::NullPointer);
+            seed_world_free(world);
+        }
+    }
+
+    #[test]
+    fn panics_do_not_cross_the_boundary() {
+        assert_eq!(catch(|| panic!("inside the world")), SeedResult::Panic);
+    }
+}
@@ -6,7 +6,6 @@ pub fn hello_from_lib() {
 mod test {
     #[test]
     fn it_works() {
-        assert(true);
         println!("It works!");
     }
 }